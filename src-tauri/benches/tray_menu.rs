@@ -0,0 +1,73 @@
+// Time building the tray menu model for a large account list, the work done on
+// every tray update. Run with `cargo bench --bench tray_menu`.
+
+use cursor_switcher::secret::SecretString;
+use cursor_switcher::tray::{TrayMenuExtras, TrayMenuModel};
+use cursor_switcher::types::{Account, AccountSource, MembershipStatus};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ACCOUNTS: usize = 1_000;
+const ITERATIONS: u32 = 2_000;
+
+fn account(i: usize) -> Account {
+    Account {
+        index: i as i32,
+        email: format!("user{}@example.com", i),
+        access_token: SecretString::default(),
+        refresh_token: SecretString::default(),
+        cookie: SecretString::default(),
+        days_remaining: Some(7.0),
+        status: MembershipStatus::FreeTrial,
+        record_time: "2024-03-10 12:00:00".to_string(),
+        source: AccountSource::Imported,
+        usage_used: Some(i as f64 % 500.0),
+        usage_remaining: Some(500.0 - i as f64 % 500.0),
+        usage_total: Some(500.0),
+        usage_percentage: Some(i as f64 % 100.0),
+        label_emoji: i.is_multiple_of(3).then(|| "🚀".to_string()),
+        label_color: None,
+        privacy_mode: None,
+        extra_headers: None,
+        pool: None,
+        status_reason: None,
+        tags: Vec::new(),
+        favorite: false,
+        last_used: String::new(),
+        archived: false,
+        password: None,
+        nickname: None,
+    }
+}
+
+fn bench(name: &str, mut run: impl FnMut()) {
+    // Warm up caches and the allocator before measuring
+    for _ in 0..ITERATIONS / 10 {
+        run();
+    }
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    let per_iteration: Duration = started.elapsed() / ITERATIONS;
+    println!("{:<40} {:>10.2?}/iter", name, per_iteration);
+}
+
+fn main() {
+    let accounts: Vec<Account> = (0..ACCOUNTS).map(account).collect();
+    let current = Some(format!("user{}@example.com", ACCOUNTS / 2));
+    let extras = TrayMenuExtras::default();
+
+    bench("menu model, 1000 accounts", || {
+        black_box(TrayMenuModel::new(
+            black_box(&accounts),
+            current.clone(),
+            &extras,
+        ));
+    });
+
+    let model = TrayMenuModel::new(&accounts, current.clone(), &extras);
+    bench("menu model fingerprint", || {
+        black_box(black_box(&model).fingerprint());
+    });
+}
@@ -0,0 +1,195 @@
+// Copies of the account CSV taken before it is rewritten as a whole, kept in
+// `backups/accounts` next to it so a bad batch update can be undone.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime};
+use csv::{ByteRecord, ReaderBuilder};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Backups kept unless configured otherwise
+pub const DEFAULT_RETENTION: u32 = 20;
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+/// A backup as listed by `list_account_backups`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    /// When it was taken, as `record_time::FORMAT`
+    pub timestamp: String,
+    pub rows: usize,
+}
+
+/// Where the backups of `csv_path` go
+pub fn backup_dir(csv_path: &Path) -> PathBuf {
+    csv_path.with_file_name("backups").join("accounts")
+}
+
+// `cursor_auth_total_` for `cursor_auth_total.csv`
+fn file_prefix(csv_path: &Path) -> String {
+    let stem = csv_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format!("{}_", stem)
+}
+
+/// Copy `csv_path` into the backup folder and prune all but the newest
+/// `retention` backups; 0 keeps none and takes none. None when there is no
+/// file to copy yet.
+pub fn back_up(csv_path: &Path, retention: u32, now: DateTime<Local>) -> Result<Option<PathBuf>> {
+    if retention == 0 || !csv_path.exists() {
+        return Ok(None);
+    }
+    let dir = backup_dir(csv_path);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let name = |at: DateTime<Local>| {
+        format!(
+            "{}{}.csv",
+            file_prefix(csv_path),
+            at.format(TIMESTAMP_FORMAT)
+        )
+    };
+    // Writes within the same millisecond get the next free one, so none is
+    // overwritten and the names still sort by age
+    let mut at = now;
+    while dir.join(name(at)).exists() {
+        at += chrono::Duration::milliseconds(1);
+    }
+    let path = dir.join(name(at));
+    fs::copy(csv_path, &path)
+        .with_context(|| format!("Failed to back up {}", csv_path.display()))?;
+
+    for name in backup_files(csv_path)?.iter().skip(retention as usize) {
+        fs::remove_file(dir.join(name)).with_context(|| format!("Failed to remove {}", name))?;
+    }
+    Ok(Some(path))
+}
+
+/// Names of the backups of `csv_path`, newest first (timestamps sort lexically)
+fn backup_files(csv_path: &Path) -> Result<Vec<String>> {
+    let dir = backup_dir(csv_path);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let prefix = file_prefix(csv_path);
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with(&prefix) && name.ends_with(".csv"))
+        .collect();
+    names.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(names)
+}
+
+/// The backups of `csv_path`, newest first
+pub fn list(csv_path: &Path) -> Result<Vec<BackupInfo>> {
+    let dir = backup_dir(csv_path);
+    let prefix = file_prefix(csv_path);
+    backup_files(csv_path)?
+        .into_iter()
+        .map(|name| {
+            let path = dir.join(&name);
+            let timestamp = name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(".csv"))
+                .and_then(|stamp| NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT).ok())
+                .map(|at| at.format(crate::types::record_time::FORMAT).to_string())
+                .unwrap_or_default();
+            let rows = count_rows(&path)?;
+            Ok(BackupInfo {
+                path,
+                timestamp,
+                rows,
+            })
+        })
+        .collect()
+}
+
+/// The rows stored in a backup, without reading their tokens. Rows reads
+/// would skip count too.
+pub fn count_rows(path: &Path) -> Result<usize> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    if reader.byte_headers()?.get(1) != Some(b"Email") {
+        bail!("{} is not an account file", path.display());
+    }
+    let mut record = ByteRecord::new();
+    let mut rows = 0;
+    while reader.read_byte_record(&mut record)? {
+        rows += 1;
+    }
+    Ok(rows)
+}
+
+/// `path` if it names one of the backups of `csv_path`; restores take
+/// nothing from elsewhere
+pub fn resolve(csv_path: &Path, path: &Path) -> Result<PathBuf> {
+    let dir = fs::canonicalize(backup_dir(csv_path)).context("There are no backups yet")?;
+    let path =
+        fs::canonicalize(path).with_context(|| format!("No backup at {}", path.display()))?;
+    let is_backup = path.parent() == Some(dir.as_path())
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                backup_files(csv_path).is_ok_and(|names| names.iter().any(|n| n == name))
+            });
+    if !is_backup {
+        bail!("{} is not a backup of the accounts", path.display());
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at_second(second: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 1, 2, 0, second).unwrap()
+    }
+
+    #[test]
+    fn test_backups_are_pruned_beyond_the_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("cursor_auth_total.csv");
+        assert_eq!(back_up(&csv_path, 3, at_second(0)).unwrap(), None);
+
+        for second in 1..=5 {
+            let rows = "1,a@x.com\n".repeat(second as usize);
+            fs::write(&csv_path, format!("Index,Email\n{}", rows)).unwrap();
+            back_up(&csv_path, 3, at_second(second)).unwrap().unwrap();
+        }
+        fs::write(backup_dir(&csv_path).join("unrelated.csv"), "keep").unwrap();
+        assert_eq!(back_up(&csv_path, 0, at_second(6)).unwrap(), None);
+
+        let backups = list(&csv_path).unwrap();
+        let stamps: Vec<&str> = backups.iter().map(|b| b.timestamp.as_str()).collect();
+        assert_eq!(
+            stamps,
+            vec![
+                "2024-03-01 02:00:05",
+                "2024-03-01 02:00:04",
+                "2024-03-01 02:00:03"
+            ]
+        );
+        assert_eq!(backups[0].rows, 5);
+        assert!(backups[0]
+            .path
+            .ends_with("backups/accounts/cursor_auth_total_20240301-020005-000.csv"));
+        assert!(backup_dir(&csv_path).join("unrelated.csv").exists());
+
+        assert_eq!(
+            resolve(&csv_path, &backups[1].path).unwrap(),
+            fs::canonicalize(&backups[1].path).unwrap()
+        );
+        assert!(resolve(&csv_path, &backup_dir(&csv_path).join("unrelated.csv")).is_err());
+        assert!(resolve(&csv_path, &csv_path).is_err());
+    }
+}
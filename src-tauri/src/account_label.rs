@@ -0,0 +1,110 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::sync::OnceLock;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Longest nickname accepted, in characters
+pub const MAX_NICKNAME_CHARS: usize = 64;
+
+/// Check an account label emoji; empty input clears the label
+pub fn normalize_emoji(emoji: Option<String>) -> Result<Option<String>> {
+    let Some(emoji) = emoji
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty())
+    else {
+        return Ok(None);
+    };
+    if emoji.graphemes(true).count() != 1 {
+        bail!("The label must be a single emoji or character");
+    }
+    Ok(Some(emoji))
+}
+
+/// Check an account label color such as "#ff8800" or "#f80"; empty input clears it
+pub fn normalize_color(color: Option<String>) -> Result<Option<String>> {
+    static HEX_COLOR: OnceLock<Regex> = OnceLock::new();
+    let pattern =
+        HEX_COLOR.get_or_init(|| Regex::new(r"^#([0-9a-f]{3}|[0-9a-f]{6})$").expect("valid color"));
+
+    let Some(color) = color
+        .map(|c| c.trim().to_ascii_lowercase())
+        .filter(|c| !c.is_empty())
+    else {
+        return Ok(None);
+    };
+    if !pattern.is_match(&color) {
+        bail!("The label color must be a hex color like #ff8800");
+    }
+    Ok(Some(color))
+}
+
+/// Check an account nickname such as "Backup 1"; empty input clears it
+pub fn normalize_nickname(nickname: Option<String>) -> Result<Option<String>> {
+    let Some(nickname) = nickname
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+    else {
+        return Ok(None);
+    };
+    if nickname.chars().any(char::is_control) {
+        bail!("The nickname must fit on one line");
+    }
+    if nickname.chars().count() > MAX_NICKNAME_CHARS {
+        bail!(
+            "The nickname must be at most {} characters",
+            MAX_NICKNAME_CHARS
+        );
+    }
+    Ok(Some(nickname))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_emoji() {
+        assert_eq!(
+            normalize_emoji(Some(" 🐱 ".to_string()))
+                .unwrap()
+                .as_deref(),
+            Some("🐱")
+        );
+        // Multi-codepoint emoji are still one grapheme
+        assert!(normalize_emoji(Some("👩‍💻".to_string())).is_ok());
+        assert!(normalize_emoji(Some("🇯🇵".to_string())).is_ok());
+        assert!(normalize_emoji(Some("🐱🐶".to_string())).is_err());
+        assert_eq!(normalize_emoji(Some(String::new())).unwrap(), None);
+        assert_eq!(normalize_emoji(None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_normalize_color() {
+        assert_eq!(
+            normalize_color(Some("#FF8800".to_string()))
+                .unwrap()
+                .as_deref(),
+            Some("#ff8800")
+        );
+        assert!(normalize_color(Some("#f80".to_string())).is_ok());
+        assert!(normalize_color(Some("ff8800".to_string())).is_err());
+        assert!(normalize_color(Some("#ff88001".to_string())).is_err());
+        assert!(normalize_color(Some("red".to_string())).is_err());
+        assert_eq!(normalize_color(Some(" ".to_string())).unwrap(), None);
+    }
+
+    #[test]
+    fn test_normalize_nickname() {
+        assert_eq!(
+            normalize_nickname(Some("  Backup 1 ".to_string()))
+                .unwrap()
+                .as_deref(),
+            Some("Backup 1")
+        );
+        assert!(normalize_nickname(Some("Main\nBackup".to_string())).is_err());
+        assert!(normalize_nickname(Some("é".repeat(MAX_NICKNAME_CHARS))).is_ok());
+        assert!(normalize_nickname(Some("x".repeat(MAX_NICKNAME_CHARS + 1))).is_err());
+        assert_eq!(normalize_nickname(Some(" ".to_string())).unwrap(), None);
+        assert_eq!(normalize_nickname(None).unwrap(), None);
+    }
+}
@@ -0,0 +1,259 @@
+// Where the accounts are kept. The CSV is the default; the SQLite database is
+// for large fleets and can be picked once the CSV was migrated into it.
+
+use crate::csv_manager::{self, AccountPage, AccountQuery, CorruptRow, CsvManager, MergeReport};
+use crate::sqlite_store::SqliteAccountStore;
+use crate::types::{Account, MoveDirection};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Database next to the account CSV used by the SQLite backend
+pub const ACCOUNTS_DB_FILE: &str = "accounts.db";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    #[default]
+    Csv,
+    Sqlite,
+}
+
+pub trait AccountStore: Send + Sync {
+    /// The file the accounts are kept in; it changes on every write
+    fn path(&self) -> &Path;
+    /// Create an empty store unless there is one
+    fn ensure_exists(&self) -> Result<()>;
+    /// Every account in stored order, duplicate emails included
+    fn read_accounts(&self) -> Result<Vec<Account>>;
+    /// Stored rows `read_accounts` skips because they can't be parsed
+    fn corrupt_rows(&self) -> Result<Vec<CorruptRow>>;
+    /// The first account stored under `email`
+    fn find_account_by_email(&self, email: &str) -> Result<Option<Account>>;
+    /// Replace every stored account
+    fn write_accounts(&self, accounts: &[Account]) -> Result<()>;
+    /// Read the accounts, let `change` edit them and write them back, with no
+    /// other write in between. Nothing is written when `change` fails. See
+    /// `modify` for a change that returns a value.
+    fn modify_accounts(
+        &self,
+        change: &mut dyn FnMut(&mut Vec<Account>) -> Result<()>,
+    ) -> Result<()>;
+    fn add_account(&self, account: Account) -> Result<()>;
+    fn batch_add_accounts(&self, new_accounts: Vec<Account>) -> Result<()>;
+    /// Add the accounts whose email isn't stored yet, each email once.
+    /// Returns the emails that were added.
+    fn batch_add_new_accounts(&self, new_accounts: Vec<Account>) -> Result<Vec<String>>;
+    /// Remove every account stored under `email` and number the rest 1..N
+    fn delete_account(&self, email: &str) -> Result<bool>;
+    /// Number the accounts 1..N in their stored order; false when they
+    /// already were
+    fn compact_indexes(&self) -> Result<bool>;
+    /// Replace the first account stored under `email`
+    fn update_account(&self, email: &str, updated_account: Account) -> Result<bool>;
+    /// Whether the tokens are encrypted and the passphrase wasn't given yet
+    fn is_locked(&self) -> bool;
+    /// Encrypt the tokens with a key derived from `passphrase` from now on
+    fn set_passphrase(&self, passphrase: &str) -> Result<()>;
+    /// Unlock an encrypted store, encrypting tokens still in plaintext
+    fn unlock(&self, passphrase: &str) -> Result<()>;
+}
+
+impl dyn AccountStore + '_ {
+    /// `modify_accounts` for a change that returns a value
+    pub fn modify<T>(&self, change: impl FnOnce(&mut Vec<Account>) -> Result<T>) -> Result<T> {
+        let mut change = Some(change);
+        let mut result = None;
+        self.modify_accounts(&mut |accounts| {
+            if let Some(change) = change.take() {
+                result = Some(change(accounts)?);
+            }
+            Ok(())
+        })?;
+        result.ok_or_else(|| anyhow::anyhow!("The account store skipped a change"))
+    }
+
+    /// Add the accounts whose email isn't stored yet and refresh the
+    /// credentials of the ones that are. See `csv_manager::merge_accounts`.
+    pub fn batch_merge_accounts(&self, new_accounts: Vec<Account>) -> Result<MergeReport> {
+        self.modify(|accounts| Ok(csv_manager::merge_accounts(accounts, new_accounts)))
+    }
+
+    /// A page of the accounts matching `query`. See `csv_manager::query_accounts`.
+    pub fn query_accounts(&self, query: &AccountQuery) -> Result<AccountPage> {
+        csv_manager::query_accounts(self.read_accounts()?.into_iter().map(Ok), query)
+    }
+
+    /// Move an account one row up or down. See `csv_manager::move_account`.
+    pub fn move_account(&self, email: &str, direction: MoveDirection) -> Result<bool> {
+        self.modify(|accounts| Ok(csv_manager::move_account(accounts, email, direction)))
+    }
+
+    /// Reorder the accounts by `emails`. See `csv_manager::set_order`.
+    pub fn set_account_order(&self, emails: &[String]) -> Result<()> {
+        self.modify(|accounts| {
+            csv_manager::set_order(accounts, emails);
+            Ok(())
+        })
+    }
+}
+
+impl AccountStore for CsvManager {
+    fn path(&self) -> &Path {
+        self.file_path()
+    }
+
+    fn ensure_exists(&self) -> Result<()> {
+        self.ensure_csv_exists()
+    }
+
+    fn read_accounts(&self) -> Result<Vec<Account>> {
+        CsvManager::read_accounts(self)
+    }
+
+    fn corrupt_rows(&self) -> Result<Vec<CorruptRow>> {
+        CsvManager::corrupt_rows(self)
+    }
+
+    fn find_account_by_email(&self, email: &str) -> Result<Option<Account>> {
+        CsvManager::find_account_by_email(self, email)
+    }
+
+    fn write_accounts(&self, accounts: &[Account]) -> Result<()> {
+        CsvManager::write_accounts(self, accounts)
+    }
+
+    fn modify_accounts(
+        &self,
+        change: &mut dyn FnMut(&mut Vec<Account>) -> Result<()>,
+    ) -> Result<()> {
+        self.modify(change)
+    }
+
+    fn add_account(&self, account: Account) -> Result<()> {
+        CsvManager::add_account(self, account)
+    }
+
+    fn batch_add_accounts(&self, new_accounts: Vec<Account>) -> Result<()> {
+        CsvManager::batch_add_accounts(self, new_accounts)
+    }
+
+    fn batch_add_new_accounts(&self, new_accounts: Vec<Account>) -> Result<Vec<String>> {
+        CsvManager::batch_add_new_accounts(self, new_accounts)
+    }
+
+    fn delete_account(&self, email: &str) -> Result<bool> {
+        CsvManager::delete_account(self, email)
+    }
+
+    fn compact_indexes(&self) -> Result<bool> {
+        CsvManager::compact_indexes(self)
+    }
+
+    fn update_account(&self, email: &str, updated_account: Account) -> Result<bool> {
+        CsvManager::update_account(self, email, updated_account)
+    }
+
+    fn is_locked(&self) -> bool {
+        CsvManager::is_locked(self)
+    }
+
+    fn set_passphrase(&self, passphrase: &str) -> Result<()> {
+        CsvManager::set_passphrase(self, passphrase)
+    }
+
+    fn unlock(&self, passphrase: &str) -> Result<()> {
+        CsvManager::unlock(self, passphrase)
+    }
+}
+
+pub fn db_path(csv_path: &Path) -> PathBuf {
+    csv_path.with_file_name(ACCOUNTS_DB_FILE)
+}
+
+/// The store `backend` keeps the accounts of `csv_path` in. A CSV keeps
+/// `backup_retention` backups, see `CsvManager::with_backups`.
+pub fn open(
+    csv_path: &Path,
+    backend: StoreBackend,
+    backup_retention: u32,
+) -> Box<dyn AccountStore> {
+    match backend {
+        StoreBackend::Csv => {
+            Box::new(CsvManager::new(csv_path.to_path_buf()).with_backups(backup_retention))
+        }
+        StoreBackend::Sqlite => Box::new(SqliteAccountStore::new(db_path(csv_path))),
+    }
+}
+
+/// Copy every account of the CSV into the database next to it, replacing
+/// what the database held. Rows are kept as they are, duplicate emails too.
+/// Returns the number of accounts copied.
+pub fn migrate_csv_to_sqlite(csv_path: &Path) -> Result<usize> {
+    let csv = CsvManager::new(csv_path.to_path_buf());
+    csv.ensure_csv_exists()?;
+    let accounts = csv.read_accounts()?;
+
+    let db = SqliteAccountStore::new(db_path(csv_path));
+    db.ensure_exists()?;
+    db.write_accounts(&accounts)?;
+    tracing::info!(
+        "Migrated {} accounts to {}",
+        accounts.len(),
+        ACCOUNTS_DB_FILE
+    );
+    Ok(accounts.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::account;
+
+    #[test]
+    fn test_migration_keeps_duplicates_and_empty_usage() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("accounts.csv");
+        let csv = CsvManager::new(csv_path.clone());
+        csv.ensure_csv_exists().unwrap();
+        let mut with_usage = Account {
+            access_token: "token_a".into(),
+            ..account("a@x.com")
+        };
+        with_usage.usage_used = Some(3.5);
+        with_usage.usage_total = Some(20.0);
+        csv.batch_add_accounts(vec![
+            with_usage,
+            Account {
+                access_token: "token_b".into(),
+                ..account("b@x.com")
+            },
+            Account {
+                access_token: "token_a2".into(),
+                ..account("a@x.com")
+            },
+        ])
+        .unwrap();
+
+        assert_eq!(migrate_csv_to_sqlite(&csv_path).unwrap(), 3);
+        let db = open(&csv_path, StoreBackend::Sqlite, 0);
+        let migrated = db.read_accounts().unwrap();
+        assert_eq!(migrated, CsvManager::read_accounts(&csv).unwrap());
+        assert_eq!(migrated[0].usage_used, Some(3.5));
+        assert_eq!(migrated[0].usage_remaining, None);
+        assert_eq!(migrated[1].usage_used, None);
+        assert_eq!(migrated[2].access_token.expose(), "token_a2");
+        assert_eq!(
+            db.find_account_by_email("a@x.com")
+                .unwrap()
+                .unwrap()
+                .access_token
+                .expose(),
+            "token_a"
+        );
+
+        // Running it again replaces instead of appending
+        assert_eq!(migrate_csv_to_sqlite(&csv_path).unwrap(), 3);
+        assert_eq!(db.read_accounts().unwrap().len(), 3);
+    }
+}
@@ -1,11 +1,63 @@
-use crate::types::{AccountInfo, UsageInfo};
+use crate::http::{HttpClient, HttpClientFactory};
+use crate::operations::TOKEN_CHECK_TIMEOUT;
+use crate::token_auth::{TokenAuthClient, TokenCheck};
+use crate::types::{AccountInfo, MembershipStatus, UsageInfo};
 use anyhow::{Context, Result};
-use reqwest::blocking::Client;
+use reqwest::blocking::Response;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
 use serde::Deserialize;
-use std::time::Duration;
+use thiserror::Error;
+
+/// What the account flows in `operations` ask Cursor's API, so they can run
+/// against a mock in tests. `CursorApiClient` is the real thing. `extra` are
+/// the account's own headers from `http::extra_headers`.
+pub trait CursorApi: Sync {
+    fn get_account_info(
+        &self,
+        email: &str,
+        access_token: &str,
+        extra: &HeaderMap,
+    ) -> Result<AccountInfo>;
+    fn get_usage_info(&self, access_token: &str, extra: &HeaderMap) -> Result<UsageInfo>;
+    /// Email address of the account a token belongs to
+    fn get_email(&self, access_token: &str) -> Result<String>;
+    /// Must answer within `TOKEN_CHECK_TIMEOUT`, since a switch waits for it
+    fn check_access_token(&self, access_token: &str) -> TokenCheck;
+}
+
+/// Failures worth telling apart from a plain network or parse error
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ApiError {
+    #[error("Cursor rejected the access token (HTTP {status})")]
+    Unauthorized { status: u16 },
+    #[error("Rate limited by Cursor's API")]
+    RateLimited,
+}
+
+impl ApiError {
+    /// Find an `ApiError` anywhere in the context chain of `error`
+    pub fn of(error: &anyhow::Error) -> Option<&ApiError> {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<ApiError>())
+    }
+
+    fn check(response: Response) -> Result<Response> {
+        match response.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(ApiError::Unauthorized {
+                status: response.status().as_u16(),
+            }
+            .into()),
+            StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited.into()),
+            _ => Ok(response),
+        }
+    }
+}
 
 pub struct CursorApiClient {
-    client: Client,
+    client: HttpClient,
+    http: HttpClientFactory,
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,38 +83,48 @@ struct PlanUsage {
 }
 
 impl CursorApiClient {
-    pub fn new() -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
-
-        Self { client }
+    pub fn new(http: &HttpClientFactory) -> Result<Self> {
+        Ok(Self {
+            client: http.client()?,
+            http: http.clone(),
+        })
     }
 
-    pub fn get_account_info(&self, email: &str, access_token: &str) -> Result<AccountInfo> {
+    pub fn get_account_info(
+        &self,
+        email: &str,
+        access_token: &str,
+        extra: &HeaderMap,
+    ) -> Result<AccountInfo> {
         // Get account info from Stripe API
         let stripe_url = "https://api2.cursor.sh/auth/full_stripe_profile";
         let stripe_response: StripeProfileResponse = self
             .client
-            .get(stripe_url)
-            .header("Authorization", format!("Bearer {}", access_token))
-            .header("origin", "vscode-file://vscode-app")
-            .header("x-new-onboarding-completed", "false")
-            .header("x-ghost-mode", "true")
-            .send()
+            .send(
+                self.client
+                    .get(stripe_url)
+                    .header("Authorization", format!("Bearer {}", access_token))
+                    .header("origin", "vscode-file://vscode-app")
+                    .header("x-new-onboarding-completed", "false")
+                    .header("x-ghost-mode", "true")
+                    .headers(extra.clone()),
+            )
+            .and_then(ApiError::check)
             .context("Failed to get stripe profile")?
             .json()
             .context("Failed to parse stripe response")?;
 
-        let membership_type = stripe_response.membership_type.ok_or_else(|| {
-            anyhow::anyhow!("API returned null membership_type - token may be invalid")
-        })?;
+        let membership_type = stripe_response
+            .membership_type
+            .map(MembershipStatus::from)
+            .ok_or_else(|| {
+                anyhow::anyhow!("API returned null membership_type - token may be invalid")
+            })?;
 
         // Get days remaining from trial field, or -1 for paid accounts without trials
         let days_remaining = stripe_response.days_remaining_on_trial.unwrap_or_else(|| {
             // For paid accounts (pro, ultra), there's no trial, so return -1 to indicate "N/A"
-            if membership_type.to_lowercase().contains("trial") || membership_type == "free" {
+            if membership_type.is_trial() || membership_type == MembershipStatus::Free {
                 0.0
             } else {
                 -1.0 // Indicates paid account with no trial
@@ -74,20 +136,25 @@ impl CursorApiClient {
             membership_type,
             days_remaining,
             is_student: false, // Can be enhanced later
+            privacy_mode: None,
         })
     }
 
-    pub fn get_usage_info(&self, access_token: &str) -> Result<UsageInfo> {
+    pub fn get_usage_info(&self, access_token: &str, extra: &HeaderMap) -> Result<UsageInfo> {
         let url = "https://api2.cursor.sh/aiserver.v1.DashboardService/GetCurrentPeriodUsage";
 
         let response: UsageResponse = self
             .client
-            .post(url)
-            .header("Authorization", format!("Bearer {}", access_token))
-            .header("Content-Type", "application/json")
-            .header("origin", "vscode-file://vscode-app")
-            .json(&serde_json::json!({}))
-            .send()
+            .send(
+                self.client
+                    .post(url)
+                    .header("Authorization", format!("Bearer {}", access_token))
+                    .header("Content-Type", "application/json")
+                    .header("origin", "vscode-file://vscode-app")
+                    .headers(extra.clone())
+                    .json(&serde_json::json!({})),
+            )
+            .and_then(ApiError::check)
             .context("Failed to get usage info")?
             .json()
             .context("Failed to parse usage response")?;
@@ -119,3 +186,44 @@ impl CursorApiClient {
         })
     }
 }
+
+impl CursorApi for CursorApiClient {
+    fn get_account_info(
+        &self,
+        email: &str,
+        access_token: &str,
+        extra: &HeaderMap,
+    ) -> Result<AccountInfo> {
+        CursorApiClient::get_account_info(self, email, access_token, extra)
+    }
+
+    fn get_usage_info(&self, access_token: &str, extra: &HeaderMap) -> Result<UsageInfo> {
+        CursorApiClient::get_usage_info(self, access_token, extra)
+    }
+
+    fn get_email(&self, access_token: &str) -> Result<String> {
+        TokenAuthClient::new(&self.http)?.get_email(access_token)
+    }
+
+    fn check_access_token(&self, access_token: &str) -> TokenCheck {
+        TokenAuthClient::check_with_timeout(&self.http, TOKEN_CHECK_TIMEOUT, access_token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_found_through_context() {
+        let error = Err::<(), _>(anyhow::Error::from(ApiError::Unauthorized { status: 401 }))
+            .context("Failed to get stripe profile")
+            .context("Refreshing a@x.com")
+            .unwrap_err();
+        assert_eq!(
+            ApiError::of(&error),
+            Some(&ApiError::Unauthorized { status: 401 })
+        );
+        assert_eq!(ApiError::of(&anyhow::anyhow!("offline")), None);
+    }
+}
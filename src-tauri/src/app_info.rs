@@ -0,0 +1,92 @@
+use crate::path_detector::PathDetector;
+use chrono::DateTime;
+use serde::Serialize;
+use std::path::Path;
+
+/// Files and folders inside the app data directory
+pub const ACCOUNTS_FILE_NAME: &str = "cursor_auth_total.csv";
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+pub const LOG_DIR_NAME: &str = "logs";
+pub const JOURNAL_FILE_NAME: &str = "pending_operation.json";
+
+/// Version, build and path details for the about screen and bug reports.
+/// Holds no tokens or settings values, so it can be pasted anywhere as-is.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppInfo {
+    pub version: String,
+    pub git_commit: String,
+    /// Build time as RFC 3339, when known
+    pub build_timestamp: Option<String>,
+    pub os: String,
+    pub arch: String,
+    pub app_data_dir: Option<String>,
+    pub csv_path: Option<String>,
+    pub log_dir: Option<String>,
+    pub settings_path: Option<String>,
+    pub cursor_path: Option<String>,
+    pub cursor_version: Option<String>,
+    /// Whether the app is in offline mode; only known to the running app
+    pub is_offline: bool,
+}
+
+impl AppInfo {
+    /// `version` comes from the Tauri config; paths are derived from the same
+    /// layout `setup()` and the CLI use.
+    pub fn collect(version: &str, app_data_dir: Option<&Path>, cursor_path: Option<&Path>) -> Self {
+        let display = |path: &Path| path.to_string_lossy().to_string();
+        let in_data_dir = |name: &str| app_data_dir.map(|dir| display(&dir.join(name)));
+
+        Self {
+            version: version.to_string(),
+            git_commit: env!("BUILD_GIT_COMMIT").to_string(),
+            build_timestamp: build_timestamp(env!("BUILD_TIMESTAMP")),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            app_data_dir: app_data_dir.map(display),
+            csv_path: in_data_dir(ACCOUNTS_FILE_NAME),
+            log_dir: in_data_dir(LOG_DIR_NAME),
+            settings_path: in_data_dir(SETTINGS_FILE_NAME),
+            cursor_path: cursor_path.map(display),
+            cursor_version: PathDetector::detect_cursor_version(),
+            is_offline: false,
+        }
+    }
+}
+
+fn build_timestamp(unix_seconds: &str) -> Option<String> {
+    let seconds = unix_seconds.parse::<i64>().ok().filter(|s| *s > 0)?;
+    DateTime::from_timestamp(seconds, 0).map(|time| time.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_derives_data_paths() {
+        let data_dir = Path::new("/data/app");
+        let info = AppInfo::collect("2.1.0", Some(data_dir), None);
+
+        assert_eq!(info.version, "2.1.0");
+        assert!(!info.git_commit.is_empty());
+        assert_eq!(info.os, std::env::consts::OS);
+        assert_eq!(
+            info.csv_path.as_deref().map(Path::new),
+            Some(data_dir.join(ACCOUNTS_FILE_NAME).as_path())
+        );
+        assert_eq!(info.cursor_path, None);
+
+        let info = AppInfo::collect("2.1.0", None, None);
+        assert_eq!(info.settings_path, None);
+    }
+
+    #[test]
+    fn test_build_timestamp() {
+        assert_eq!(
+            build_timestamp("1700000000").as_deref(),
+            Some("2023-11-14T22:13:20+00:00")
+        );
+        assert_eq!(build_timestamp("0"), None);
+        assert_eq!(build_timestamp("soon"), None);
+    }
+}
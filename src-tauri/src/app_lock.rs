@@ -0,0 +1,135 @@
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// How long `unlock_app` keeps the app unlocked unless configured otherwise
+pub const DEFAULT_UNLOCK_MINUTES: u32 = 15;
+const HASH_SCHEME: &str = "pbkdf2-sha256";
+const ITERATIONS: u32 = 210_000;
+const SALT_LENGTH: usize = 16;
+
+/// Lock state reported to the frontend; never contains the password hash
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppLockStatus {
+    pub enabled: bool,
+    pub locked: bool,
+    /// Seconds until the unlocked session expires
+    pub unlocked_for_secs: Option<u64>,
+}
+
+/// Hash a password for storage as `pbkdf2-sha256$<iterations>$<salt>$<hash>`
+pub fn hash_password(password: &str) -> String {
+    let mut salt = [0u8; SALT_LENGTH];
+    rand::thread_rng().fill_bytes(&mut salt);
+    hash_with(password, &salt, ITERATIONS)
+}
+
+fn hash_with(password: &str, salt: &[u8], iterations: u32) -> String {
+    let hash = pbkdf2_sha256(password.as_bytes(), salt, iterations);
+    format!(
+        "{}${}${}${}",
+        HASH_SCHEME,
+        iterations,
+        to_hex(salt),
+        to_hex(&hash)
+    )
+}
+
+/// Check a password against a stored hash in constant time
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    let parts: Vec<&str> = stored.split('$').collect();
+    let (iterations, salt, expected) = match parts.as_slice() {
+        [scheme, iterations, salt, hash] if *scheme == HASH_SCHEME => {
+            match (iterations.parse::<u32>(), from_hex(salt), from_hex(hash)) {
+                (Ok(iterations), Some(salt), Some(hash)) if iterations > 0 => {
+                    (iterations, salt, hash)
+                }
+                _ => return false,
+            }
+        }
+        _ => return false,
+    };
+
+    let actual = pbkdf2_sha256(password.as_bytes(), &salt, iterations);
+    actual.len() == expected.len()
+        && actual
+            .iter()
+            .zip(&expected)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+// PBKDF2 with a single output block, which is all a 32-byte hash needs
+pub(crate) fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut message = salt.to_vec();
+    message.extend_from_slice(&1u32.to_be_bytes());
+
+    let mut u = hmac_sha256(password, &message);
+    let mut result = u;
+    for _ in 1..iterations {
+        u = hmac_sha256(password, &u);
+        for (r, b) in result.iter_mut().zip(u.iter()) {
+            *r ^= b;
+        }
+    }
+    result
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pbkdf2_known_vector() {
+        // RFC 7914 section 11, first PBKDF2-HMAC-SHA256 test vector
+        let hash = pbkdf2_sha256(b"passwd", b"salt", 1);
+        assert_eq!(
+            to_hex(&hash),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+    }
+
+    #[test]
+    fn test_verify_password() {
+        let stored = hash_with("1234", b"0123456789abcdef", 10);
+        assert!(stored.starts_with("pbkdf2-sha256$10$"));
+        assert!(verify_password("1234", &stored));
+        assert!(!verify_password("12345", &stored));
+        assert!(!verify_password("1234", "plain-text"));
+        assert!(!verify_password("1234", "pbkdf2-sha256$0$00$00"));
+    }
+}
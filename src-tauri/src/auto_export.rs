@@ -0,0 +1,274 @@
+use crate::csv_manager::CsvManager;
+use crate::redaction;
+use crate::types::Account;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Exports kept in the folder unless configured otherwise
+pub const DEFAULT_RETENTION: u32 = 7;
+const FILE_PREFIX: &str = "cursor-accounts-";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// Result of one export run
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportReport {
+    pub path: PathBuf,
+    pub accounts: usize,
+    /// Older exports deleted to respect the retention count
+    pub pruned: usize,
+}
+
+/// Write a timestamped export into `dir` and prune old ones.
+/// `retention` is the number of exports to keep; 0 keeps everything.
+/// Passwords are left out unless `include_passwords`.
+pub fn run_export(
+    accounts: &[Account],
+    dir: &Path,
+    format: ExportFormat,
+    retention: u32,
+    include_passwords: bool,
+    now: DateTime<Local>,
+) -> Result<ExportReport> {
+    if !dir.is_dir() {
+        bail!("Export folder does not exist: {}", dir.display());
+    }
+
+    let path = dir.join(format!(
+        "{}{}.{}",
+        FILE_PREFIX,
+        now.format(TIMESTAMP_FORMAT),
+        format.extension()
+    ));
+
+    match format {
+        // Same writer as the account store, so exports can be imported again as-is
+        ExportFormat::Csv if include_passwords => {
+            CsvManager::new(path.clone()).write_accounts(accounts)?
+        }
+        ExportFormat::Csv => {
+            let accounts: Vec<Account> = accounts
+                .iter()
+                .cloned()
+                .map(|account| Account {
+                    password: None,
+                    ..account
+                })
+                .collect();
+            CsvManager::new(path.clone()).write_accounts(&accounts)?
+        }
+        ExportFormat::Json => {
+            let content = accounts_json(accounts, false, include_passwords)?;
+            fs::write(&path, content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+    }
+
+    let pruned = prune_exports(dir, retention)?;
+    Ok(ExportReport {
+        path,
+        accounts: accounts.len(),
+        pruned,
+    })
+}
+
+/// Every field of `accounts` as pretty-printed JSON in stored order, the form
+/// `serde` reads back into accounts. With `mask_tokens` only the last
+/// characters of each credential are kept. Passwords, which accounts never
+/// serialize, are added with `include_passwords`.
+pub fn accounts_json(
+    accounts: &[Account],
+    mask_tokens: bool,
+    include_passwords: bool,
+) -> Result<String> {
+    let mut values = Vec::with_capacity(accounts.len());
+    for account in accounts {
+        let mut account = account.clone();
+        if mask_tokens {
+            redaction::mask_account_tail(&mut account);
+        }
+        let mut value = serde_json::to_value(&account)?;
+        if let (true, Some(password)) = (include_passwords, &account.password) {
+            value["password"] = password.expose().into();
+        }
+        values.push(value);
+    }
+    Ok(serde_json::to_string_pretty(&values)?)
+}
+
+/// Whether an export was already written on the given day
+pub fn has_export_on(dir: &Path, day: chrono::NaiveDate) -> bool {
+    let day_prefix = format!("{}{}", FILE_PREFIX, day.format("%Y%m%d"));
+    export_files(dir)
+        .map(|files| files.iter().any(|name| name.starts_with(&day_prefix)))
+        .unwrap_or(false)
+}
+
+/// Names of export files in `dir`, newest first (timestamps sort lexically)
+fn export_files(dir: &Path) -> Result<Vec<String>> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            name.starts_with(FILE_PREFIX) && (name.ends_with(".csv") || name.ends_with(".json"))
+        })
+        .collect();
+    names.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(names)
+}
+
+fn prune_exports(dir: &Path, retention: u32) -> Result<usize> {
+    if retention == 0 {
+        return Ok(0);
+    }
+
+    let mut pruned = 0;
+    for name in export_files(dir)?.iter().skip(retention as usize) {
+        fs::remove_file(dir.join(name)).with_context(|| format!("Failed to remove {}", name))?;
+        pruned += 1;
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_support::account;
+    use crate::types::AccountSource;
+    use chrono::TimeZone;
+
+    fn at_day(day: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, day, 2, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_export_and_prune() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let accounts = vec![Account {
+            password: Some("hunter22".into()),
+            ..account("a@x.com")
+        }];
+        fs::write(temp_dir.path().join("unrelated.csv"), "keep").unwrap();
+
+        for day in 1..=4 {
+            run_export(
+                &accounts,
+                temp_dir.path(),
+                ExportFormat::Csv,
+                2,
+                false,
+                at_day(day),
+            )
+            .unwrap();
+        }
+
+        let files = export_files(temp_dir.path()).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                "cursor-accounts-20240304-020000.csv",
+                "cursor-accounts-20240303-020000.csv"
+            ]
+        );
+        assert!(temp_dir.path().join("unrelated.csv").exists());
+        assert!(has_export_on(temp_dir.path(), at_day(4).date_naive()));
+        assert!(!has_export_on(temp_dir.path(), at_day(1).date_naive()));
+
+        let restored = CsvManager::new(temp_dir.path().join(&files[0]))
+            .read_accounts()
+            .unwrap();
+        assert_eq!(restored[0].email, "a@x.com");
+        assert!(restored[0].password.is_none());
+    }
+
+    #[test]
+    fn test_json_export() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let accounts = vec![account("a@x.com")];
+
+        let report = run_export(
+            &accounts,
+            temp_dir.path(),
+            ExportFormat::Json,
+            0,
+            false,
+            at_day(1),
+        )
+        .unwrap();
+        assert_eq!(report.accounts, 1);
+        assert!(report.path.extension().unwrap() == "json");
+
+        let content = fs::read_to_string(report.path).unwrap();
+        let parsed: Vec<Account> = serde_json::from_str(&content).unwrap();
+        assert_eq!(parsed[0].email, "a@x.com");
+    }
+
+    #[test]
+    fn test_accounts_json_round_trip() {
+        let accounts = vec![
+            Account {
+                index: 3,
+                days_remaining: Some(4.5),
+                source: AccountSource::WebLogin,
+                usage_used: Some(2.0),
+                usage_remaining: Some(18.0),
+                usage_total: Some(20.0),
+                usage_percentage: Some(10.0),
+                pool: Some("work".to_string()),
+                ..account("a@x.com")
+            },
+            Account {
+                index: 1,
+                access_token: "eyJhbGciOiJIUzI1NiJ9.access".into(),
+                refresh_token: "refresh".into(),
+                password: Some("hunter22".into()),
+                ..account("b@x.com")
+            },
+        ];
+
+        let json = accounts_json(&accounts, false, true).unwrap();
+        let parsed: Vec<Account> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, accounts);
+
+        let masked: Vec<Account> =
+            serde_json::from_str(&accounts_json(&accounts, true, false).unwrap()).unwrap();
+        assert_eq!(
+            masked[1].access_token.expose(),
+            format!("{}access", "*".repeat(21))
+        );
+        assert_eq!(masked[1].refresh_token.expose(), "*efresh");
+        assert!(masked[1].password.is_none());
+        assert_eq!(masked[0].usage_total, Some(20.0));
+        assert_eq!(masked[0].source, AccountSource::WebLogin);
+    }
+
+    #[test]
+    fn test_export_to_missing_folder_fails() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let missing = temp_dir.path().join("nas");
+
+        let result = run_export(&[], &missing, ExportFormat::Csv, 7, false, at_day(1));
+        assert!(result.unwrap_err().to_string().contains("does not exist"));
+    }
+}
@@ -0,0 +1,268 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Outcome of enabling or disabling launch at login
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutostartStatus {
+    pub enabled: bool,
+    /// False when the entry was already in the requested state
+    pub changed: bool,
+    /// File path or registry value that was created or removed
+    pub location: String,
+}
+
+/// Registers the app to start at login using the platform's native mechanism:
+/// a Run registry value on Windows, a LaunchAgent plist on macOS and an
+/// XDG autostart .desktop file on Linux.
+pub struct AutostartManager {
+    app_name: String,
+    identifier: String,
+    exe_path: PathBuf,
+}
+
+impl AutostartManager {
+    pub fn new(app_name: String, identifier: String, exe_path: PathBuf) -> Self {
+        Self {
+            app_name,
+            identifier,
+            exe_path,
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    const RUN_KEY: &'static str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
+    #[cfg(target_os = "windows")]
+    fn location(&self) -> String {
+        format!(r"HKEY_CURRENT_USER\{}\{}", Self::RUN_KEY, self.app_name)
+    }
+
+    #[cfg(target_os = "windows")]
+    fn command_line(&self) -> String {
+        format!("\"{}\"", self.exe_path.display())
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn is_enabled(&self) -> bool {
+        use winreg::enums::HKEY_CURRENT_USER;
+        use winreg::RegKey;
+
+        RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey(Self::RUN_KEY)
+            .and_then(|key| key.get_value::<String, _>(&self.app_name))
+            .map(|value| value == self.command_line())
+            .unwrap_or(false)
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn enable(&self) -> Result<AutostartStatus> {
+        use winreg::enums::HKEY_CURRENT_USER;
+        use winreg::RegKey;
+
+        let changed = !self.is_enabled();
+        if changed {
+            let (key, _) = RegKey::predef(HKEY_CURRENT_USER)
+                .create_subkey(Self::RUN_KEY)
+                .context("Failed to open the Run registry key")?;
+            key.set_value(&self.app_name, &self.command_line())
+                .context("Failed to write the Run registry value")?;
+        }
+
+        Ok(AutostartStatus {
+            enabled: true,
+            changed,
+            location: self.location(),
+        })
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn disable(&self) -> Result<AutostartStatus> {
+        use winreg::enums::{HKEY_CURRENT_USER, KEY_ALL_ACCESS};
+        use winreg::RegKey;
+
+        let key = RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey_with_flags(Self::RUN_KEY, KEY_ALL_ACCESS)
+            .context("Failed to open the Run registry key")?;
+        let changed = key.get_value::<String, _>(&self.app_name).is_ok();
+        if changed {
+            key.delete_value(&self.app_name)
+                .context("Failed to delete the Run registry value")?;
+        }
+
+        Ok(AutostartStatus {
+            enabled: false,
+            changed,
+            location: self.location(),
+        })
+    }
+
+    #[cfg(target_os = "macos")]
+    fn entry_path(&self) -> Result<PathBuf> {
+        let home = dirs::home_dir().context("Failed to resolve home directory")?;
+        Ok(home
+            .join("Library")
+            .join("LaunchAgents")
+            .join(format!("{}.plist", self.identifier)))
+    }
+
+    #[cfg(target_os = "macos")]
+    fn entry_content(&self) -> String {
+        self.launch_agent_plist()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn entry_path(&self) -> Result<PathBuf> {
+        let config_dir = dirs::config_dir().context("Failed to resolve config directory")?;
+        Ok(config_dir
+            .join("autostart")
+            .join(format!("{}.desktop", self.identifier)))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn entry_content(&self) -> String {
+        self.desktop_entry()
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    pub fn is_enabled(&self) -> bool {
+        self.entry_path()
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|content| content == self.entry_content())
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    pub fn enable(&self) -> Result<AutostartStatus> {
+        let path = self.entry_path()?;
+
+        let changed = !self.is_enabled();
+        if changed {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).context("Failed to create autostart directory")?;
+            }
+            std::fs::write(&path, self.entry_content())
+                .context("Failed to write autostart entry")?;
+        }
+
+        Ok(AutostartStatus {
+            enabled: true,
+            changed,
+            location: path.to_string_lossy().to_string(),
+        })
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    pub fn disable(&self) -> Result<AutostartStatus> {
+        let path = self.entry_path()?;
+
+        let changed = path.exists();
+        if changed {
+            std::fs::remove_file(&path).context("Failed to remove autostart entry")?;
+        }
+
+        Ok(AutostartStatus {
+            enabled: false,
+            changed,
+            location: path.to_string_lossy().to_string(),
+        })
+    }
+
+    /// XDG autostart entry (Linux)
+    #[cfg(any(target_os = "linux", test))]
+    fn desktop_entry(&self) -> String {
+        format!(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name={}\n\
+             Exec={}\n\
+             Terminal=false\n\
+             X-GNOME-Autostart-enabled=true\n",
+            self.app_name,
+            quote_desktop_exec(&self.exe_path)
+        )
+    }
+
+    /// LaunchAgent property list (macOS)
+    #[cfg(any(target_os = "macos", test))]
+    fn launch_agent_plist(&self) -> String {
+        let escape = |value: &str| {
+            value
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        };
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+            escape(&self.identifier),
+            escape(&self.exe_path.to_string_lossy())
+        )
+    }
+}
+
+/// Double-quote a path for a .desktop Exec key, escaping the characters the spec reserves
+#[cfg(any(target_os = "linux", test))]
+pub fn quote_desktop_exec(path: &std::path::Path) -> String {
+    let exe = path.to_string_lossy();
+    let mut quoted = String::with_capacity(exe.len() + 2);
+    quoted.push('"');
+    for c in exe.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_manager(exe_path: &str) -> AutostartManager {
+        AutostartManager::new(
+            "Cursor Account Switcher".to_string(),
+            "com.cursor.switcher".to_string(),
+            PathBuf::from(exe_path),
+        )
+    }
+
+    #[test]
+    fn test_desktop_entry_quotes_exec_path() {
+        let manager = create_test_manager("/opt/Cursor Switcher/$bin");
+        let entry = manager.desktop_entry();
+
+        assert!(entry.starts_with("[Desktop Entry]\n"));
+        assert!(entry.contains("Name=Cursor Account Switcher\n"));
+        assert!(entry.contains("Exec=\"/opt/Cursor Switcher/\\$bin\"\n"));
+    }
+
+    #[test]
+    fn test_launch_agent_plist_uses_identifier() {
+        let manager = create_test_manager("/Applications/A&B.app/Contents/MacOS/switcher");
+        let plist = manager.launch_agent_plist();
+
+        assert!(plist.contains("<string>com.cursor.switcher</string>"));
+        assert!(
+            plist.contains("<string>/Applications/A&amp;B.app/Contents/MacOS/switcher</string>")
+        );
+        assert!(plist.contains("<key>RunAtLoad</key>"));
+    }
+}
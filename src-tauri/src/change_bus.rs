@@ -0,0 +1,89 @@
+use crate::state::MutexExt;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Quiet period after the last change before listeners run
+pub const DEBOUNCE: Duration = Duration::from_millis(250);
+/// Longest a steady stream of changes can postpone the listener
+const MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// Coalesces "accounts changed" notifications from any code path into one
+/// listener call, so a bulk import rebuilds the tray once instead of per row
+#[derive(Default)]
+pub struct ChangeBus {
+    sender: Mutex<Option<Sender<()>>>,
+}
+
+impl ChangeBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report a change; does nothing until `start` has been called
+    pub fn notify(&self) {
+        if let Some(sender) = self.sender.lock_or_recover().as_ref() {
+            let _ = sender.send(());
+        }
+    }
+
+    /// Run `on_change` on a background thread after each burst of notifications
+    pub fn start<F>(&self, debounce: Duration, on_change: F)
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        *self.sender.lock_or_recover() = Some(sender);
+        thread::spawn(move || run(receiver, debounce, on_change));
+    }
+}
+
+fn run(receiver: mpsc::Receiver<()>, debounce: Duration, mut on_change: impl FnMut()) {
+    while receiver.recv().is_ok() {
+        let deadline = Instant::now() + MAX_DELAY;
+        loop {
+            let wait = debounce.min(deadline.saturating_duration_since(Instant::now()));
+            match receiver.recv_timeout(wait) {
+                Ok(()) if Instant::now() < deadline => continue,
+                Ok(()) | Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    on_change();
+                    return;
+                }
+            }
+        }
+        on_change();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_bursts_are_coalesced() {
+        let bus = ChangeBus::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        // Nothing listens yet, so this is dropped
+        bus.notify();
+
+        let counter = calls.clone();
+        bus.start(Duration::from_millis(50), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        for _ in 0..500 {
+            bus.notify();
+        }
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        bus.notify();
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
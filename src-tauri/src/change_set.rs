@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File next to the account CSV with one line per switch or reset
+pub const CHANGE_SETS_FILE: &str = "change_sets.jsonl";
+
+/// SHA-256 of a value, in hex. Values are tokens more often than not, so only
+/// this is ever recorded.
+pub fn value_hash(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// One key written to one of Cursor's files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyChange {
+    pub file: PathBuf,
+    pub key: String,
+    /// None when the key wasn't set before
+    pub old_hash: Option<String>,
+    /// None when the key was removed
+    pub new_hash: Option<String>,
+}
+
+impl KeyChange {
+    pub fn of(file: &Path, key: &str, old: Option<&str>, new: Option<&str>) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            key: key.to_string(),
+            old_hash: old.map(value_hash),
+            new_hash: new.map(value_hash),
+        }
+    }
+
+    /// Whether the value is different now, not just written again
+    pub fn is_change(&self) -> bool {
+        self.old_hash != self.new_hash
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeOperation {
+    Switch,
+    ResetMachineId,
+}
+
+/// Every key a successful switch or machine ID reset wrote
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeSet {
+    pub operation: ChangeOperation,
+    /// The account switched to
+    #[serde(default)]
+    pub email: Option<String>,
+    pub at: DateTime<Utc>,
+    pub changes: Vec<KeyChange>,
+}
+
+pub struct ChangeSetStore {
+    file_path: PathBuf,
+}
+
+impl ChangeSetStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    pub fn record(&self, change_set: &ChangeSet) -> Result<()> {
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut content = self.read()?;
+        content.push_str(&serde_json::to_string(change_set)?);
+        content.push('\n');
+        fs::write(&self.file_path, content).context("Failed to write the change log")
+    }
+
+    /// The most recent change set; unreadable lines are skipped
+    pub fn latest(&self) -> Result<Option<ChangeSet>> {
+        Ok(self
+            .read()?
+            .lines()
+            .rev()
+            .find_map(|line| serde_json::from_str(line).ok()))
+    }
+
+    fn read(&self) -> Result<String> {
+        if !self.file_path.exists() {
+            return Ok(String::new());
+        }
+        fs::read_to_string(&self.file_path).context("Failed to read the change log")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_change_log_keeps_only_hashes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = ChangeSetStore::new(temp_dir.path().join(CHANGE_SETS_FILE));
+        assert_eq!(store.latest().unwrap(), None);
+
+        let db = Path::new("state.vscdb");
+        let at = Utc.with_ymd_and_hms(2024, 5, 2, 10, 0, 0).unwrap();
+        let first = ChangeSet {
+            operation: ChangeOperation::ResetMachineId,
+            email: None,
+            at,
+            changes: vec![KeyChange::of(
+                db,
+                "telemetry.machineId",
+                None,
+                Some("new-id"),
+            )],
+        };
+        let second = ChangeSet {
+            operation: ChangeOperation::Switch,
+            email: Some("a@x.com".to_string()),
+            at: at + chrono::Duration::minutes(5),
+            changes: vec![
+                KeyChange::of(
+                    db,
+                    "cursorAuth/accessToken",
+                    Some("old_secret"),
+                    Some("new_secret"),
+                ),
+                KeyChange::of(
+                    db,
+                    "cursorAuth/cachedSignUpType",
+                    Some("Auth_0"),
+                    Some("Auth_0"),
+                ),
+            ],
+        };
+        store.record(&first).unwrap();
+        store.record(&second).unwrap();
+
+        assert_eq!(store.latest().unwrap(), Some(second.clone()));
+        assert!(second.changes[0].is_change());
+        assert!(!second.changes[1].is_change());
+        assert_eq!(
+            second.changes[0].new_hash.as_deref(),
+            Some(value_hash("new_secret").as_str())
+        );
+        let log = fs::read_to_string(temp_dir.path().join(CHANGE_SETS_FILE)).unwrap();
+        assert!(!log.contains("secret"));
+    }
+}
@@ -0,0 +1,512 @@
+use crate::account_store;
+use crate::api_client::CursorApiClient;
+use crate::app_info::{self, AppInfo};
+use crate::change_set::{self, ChangeSetStore};
+use crate::cursor_settings::{self, SettingsSnapshotStore};
+use crate::cursor_versions::{CursorVersionStore, CURSOR_VERSIONS_FILE};
+use crate::data_location;
+use crate::http::HttpClientFactory;
+use crate::intent_journal::IntentJournal;
+use crate::operations::{self, SwitchContext, SwitchFailure, SwitchOptions};
+use crate::path_detector::PathDetector;
+use crate::process_utils::ProcessManager;
+use crate::redaction;
+use crate::settings::{SettingsManager, SwitchHook};
+use crate::switch_cooldown::{SwitchHistoryStore, SWITCH_HISTORY_FILE};
+use crate::switch_hooks::{self, HookContext};
+use crate::switch_log::{SwitchLog, SwitchSource, SWITCH_LOG_FILE};
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// Exit code for a command that ran but failed
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code for invalid command-line arguments
+pub const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "Usage: cursor-account-switcher [OPTIONS]
+
+Without options the desktop app is started.
+
+Options:
+  --list                     Print all stored accounts, tokens masked
+      --show-tokens          Print the tokens in full
+  --switch <email>           Switch Cursor to a stored account
+      --reset-machine        Also reset the machine ID
+      --restart              Start Cursor again after switching
+      --no-validate          Skip checking the token with Cursor's API first
+  --sync                     Save the account currently logged into Cursor
+  --refresh-all              Refresh usage data of all stored accounts
+  --reset-machine-id         Reset Cursor's machine ID
+  --cursor-path <path>       Cursor data directory (auto-detected by default)
+  --version                  Print the app version
+      --verbose              Also print build details and data paths
+  -h, --help                 Show this help
+
+Results are printed to stdout as JSON.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliAction {
+    List {
+        show_tokens: bool,
+    },
+    Switch {
+        email: String,
+        reset_machine: bool,
+        restart: bool,
+        /// None uses `default_validate_before_switch` from the settings
+        validate: Option<bool>,
+    },
+    Sync,
+    RefreshAll,
+    ResetMachineId,
+    Version {
+        verbose: bool,
+    },
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliCommand {
+    pub action: CliAction,
+    pub cursor_path: Option<PathBuf>,
+}
+
+/// Parse command-line arguments (without the program name).
+/// Returns `Ok(None)` when no CLI flags are present and the GUI should start.
+pub fn parse_args<I, S>(args: I) -> Result<Option<CliCommand>>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut args = args.into_iter().map(Into::into);
+
+    let mut actions = Vec::new();
+    let mut reset_machine = false;
+    let mut restart = false;
+    let mut no_validate = false;
+    let mut verbose = false;
+    let mut show_tokens = false;
+    let mut cursor_path = None;
+    let mut seen_flag = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list" => actions.push(CliAction::List { show_tokens: false }),
+            "--switch" => {
+                let email = args.next().context("--switch requires an email address")?;
+                if email.starts_with("--") {
+                    bail!("--switch requires an email address");
+                }
+                actions.push(CliAction::Switch {
+                    email,
+                    reset_machine: false,
+                    restart: false,
+                    validate: None,
+                });
+            }
+            "--sync" => actions.push(CliAction::Sync),
+            "--refresh-all" => actions.push(CliAction::RefreshAll),
+            "--reset-machine-id" => actions.push(CliAction::ResetMachineId),
+            "--reset-machine" => reset_machine = true,
+            "--restart" => restart = true,
+            "--no-validate" => no_validate = true,
+            "--version" => actions.push(CliAction::Version { verbose: false }),
+            "--verbose" => verbose = true,
+            "--show-tokens" => show_tokens = true,
+            "--cursor-path" => {
+                let path = args.next().context("--cursor-path requires a path")?;
+                cursor_path = Some(PathBuf::from(path));
+            }
+            "-h" | "--help" => actions.push(CliAction::Help),
+            other if other.starts_with('-') => {
+                // OS-specific launch arguments (e.g. macOS -psn_*) are not ours to reject
+                if other.starts_with("--") {
+                    bail!("Unknown option: {}", other);
+                }
+                continue;
+            }
+            _ => continue,
+        }
+        seen_flag = true;
+    }
+
+    if !seen_flag {
+        return Ok(None);
+    }
+
+    if actions.contains(&CliAction::Help) {
+        return Ok(Some(CliCommand {
+            action: CliAction::Help,
+            cursor_path,
+        }));
+    }
+
+    let mut action = match actions.len() {
+        0 => bail!("No action given; use one of --list, --switch, --sync, --refresh-all, --reset-machine-id"),
+        1 => actions.remove(0),
+        _ => bail!("Only one action can be run at a time"),
+    };
+
+    match &mut action {
+        CliAction::Switch {
+            reset_machine: switch_reset,
+            restart: switch_restart,
+            validate: switch_validate,
+            ..
+        } => {
+            *switch_reset = reset_machine;
+            *switch_restart = restart;
+            if no_validate {
+                *switch_validate = Some(false);
+            }
+        }
+        _ if reset_machine || restart || no_validate => {
+            bail!("--reset-machine, --restart and --no-validate can only be used with --switch")
+        }
+        _ => {}
+    }
+
+    match &mut action {
+        CliAction::Version {
+            verbose: version_verbose,
+        } => *version_verbose = verbose,
+        _ if verbose => bail!("--verbose can only be used with --version"),
+        _ => {}
+    }
+
+    match &mut action {
+        CliAction::List {
+            show_tokens: list_show_tokens,
+        } => *list_show_tokens = show_tokens,
+        _ if show_tokens => bail!("--show-tokens can only be used with --list"),
+        _ => {}
+    }
+
+    Ok(Some(CliCommand {
+        action,
+        cursor_path,
+    }))
+}
+
+/// Run a parsed command and return the process exit code.
+/// `app_data_dir` is resolved from the bundle config since there is no app handle here.
+pub fn run(command: CliCommand, app_data_dir: Option<PathBuf>, app_version: &str) -> i32 {
+    attach_parent_console();
+
+    if command.action == CliAction::Help {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    match execute(&command, app_data_dir, app_version) {
+        Ok(result) => {
+            print_json(&json!({ "ok": true, "result": result }));
+            0
+        }
+        Err(e) => {
+            print_json(&failure_json(&e));
+            EXIT_FAILURE
+        }
+    }
+}
+
+// A failed switch also says which stage failed and what was rolled back
+fn failure_json(error: &anyhow::Error) -> Value {
+    match error.downcast_ref::<SwitchFailure>() {
+        Some(failure) => json!({
+            "ok": false,
+            "error": failure.to_string(),
+            "switch_failure": failure,
+        }),
+        None => json!({ "ok": false, "error": format!("{:#}", error) }),
+    }
+}
+
+/// Report invalid arguments the same way as failed commands
+pub fn report_usage_error(error: &anyhow::Error) -> i32 {
+    attach_parent_console();
+    print_json(&json!({ "ok": false, "error": error.to_string() }));
+    eprintln!("{}", USAGE);
+    EXIT_USAGE
+}
+
+fn execute(
+    command: &CliCommand,
+    app_data_dir: Option<PathBuf>,
+    app_version: &str,
+) -> Result<Value> {
+    let cursor_path = || -> Result<PathBuf> {
+        match &command.cursor_path {
+            Some(path) => Ok(path.clone()),
+            None => PathDetector::detect_cursor_path().context("Cursor path not set"),
+        }
+    };
+
+    // Reporting the version must work even where the data directory can't be created
+    if let CliAction::Version { verbose } = command.action {
+        return if verbose {
+            let info = AppInfo::collect(
+                app_version,
+                app_data_dir.as_deref(),
+                cursor_path().ok().as_deref(),
+            );
+            Ok(serde_json::to_value(info)?)
+        } else {
+            Ok(json!({ "version": app_version }))
+        };
+    }
+
+    let app_data_dir = app_data_dir.context("Failed to resolve app data directory")?;
+    std::fs::create_dir_all(&app_data_dir).context("Failed to create app data directory")?;
+    let journal = IntentJournal::new(app_data_dir.join(app_info::JOURNAL_FILE_NAME));
+    let settings = || {
+        SettingsManager::new(app_data_dir.join(app_info::SETTINGS_FILE_NAME))
+            .load()
+            .unwrap_or_default()
+    };
+    let storage_dir = settings().data_storage_dir.map(PathBuf::from);
+    let csv_path = data_location::csv_path(&app_data_dir, storage_dir.as_deref());
+    let api = || CursorApiClient::new(&HttpClientFactory::from_settings(&settings()));
+    let store = account_store::open(
+        &csv_path,
+        settings().account_store,
+        settings().account_backup_retention,
+    );
+
+    let result = match &command.action {
+        CliAction::List { show_tokens } => {
+            let mut accounts = operations::list_accounts(store.as_ref())?;
+            if !show_tokens {
+                accounts.iter_mut().for_each(redaction::mask_account);
+            }
+            serde_json::to_value(accounts)?
+        }
+        CliAction::Switch {
+            email,
+            reset_machine,
+            restart,
+            validate,
+        } => {
+            let base_path = cursor_path()?;
+            let validate_token =
+                validate.unwrap_or_else(|| settings().default_validate_before_switch);
+            let options = SwitchOptions {
+                reset_machine: *reset_machine,
+                restart: *restart,
+                validate_token,
+                preserve_settings: settings().preserve_cursor_settings,
+                state_db_backup_retention: settings().state_db_backup_retention,
+            };
+            let settings = settings();
+            // The process exits after the switch, so no hook is left running
+            let run_hook = |name: &'static str, hook: SwitchHook, context: HookContext| {
+                let reason = match switch_hooks::run(name, &hook, &context) {
+                    Ok(exit) if exit.success() => return,
+                    Ok(exit) => exit.describe(),
+                    Err(e) => format!("could not be started ({})", e.root_cause()),
+                };
+                tracing::warn!("The {} hook {}", name, reason);
+            };
+            let account = operations::run_switch(
+                &base_path,
+                store.as_ref(),
+                email,
+                &options,
+                &api()?,
+                &ProcessManager,
+                &SwitchContext {
+                    settings: &settings,
+                    journal: &journal,
+                    history: &SwitchHistoryStore::new(csv_path.with_file_name(SWITCH_HISTORY_FILE)),
+                    log: &SwitchLog::new(csv_path.with_file_name(SWITCH_LOG_FILE)),
+                    source: SwitchSource::Cli,
+                    spawn_hook: &run_hook,
+                },
+            )??;
+            let cursor_update =
+                CursorVersionStore::new(csv_path.with_file_name(CURSOR_VERSIONS_FILE))
+                    .check_installed(chrono::Utc::now())
+                    .unwrap_or_else(|e| {
+                        tracing::warn!("Failed to record the Cursor version: {:#}", e);
+                        None
+                    });
+            json!({
+                "email": account.email,
+                "reset_machine": reset_machine,
+                "restarted": restart,
+                "cursor_update": cursor_update,
+            })
+        }
+        CliAction::Sync => {
+            let base_path = cursor_path()?;
+            operations::list_accounts(store.as_ref())?;
+            serde_json::to_value(operations::sync_current_account(
+                &base_path,
+                store.as_ref(),
+                true,
+                &api()?,
+            )?)?
+        }
+        CliAction::RefreshAll => {
+            operations::list_accounts(store.as_ref())?;
+            serde_json::to_value(operations::batch_update_all_accounts_with(
+                store.as_ref(),
+                &api()?,
+                &settings().api_policy,
+            )?)?
+        }
+        CliAction::ResetMachineId => {
+            let base_path = cursor_path()?;
+            let snapshots = settings().preserve_cursor_settings.then(|| {
+                SettingsSnapshotStore::new(csv_path.with_file_name(cursor_settings::SNAPSHOTS_DIR))
+            });
+            let change_sets =
+                ChangeSetStore::new(csv_path.with_file_name(change_set::CHANGE_SETS_FILE));
+            let ids = operations::reset_machine_id(
+                &base_path,
+                snapshots,
+                &change_sets,
+                &journal,
+                &ProcessManager,
+            )?;
+            json!({ "reset_machine": true, "machine_ids": ids })
+        }
+        CliAction::Version { .. } | CliAction::Help => Value::Null,
+    };
+
+    Ok(result)
+}
+
+fn print_json(value: &Value) {
+    match serde_json::to_string_pretty(value) {
+        Ok(text) => println!("{}", text),
+        Err(e) => eprintln!("Failed to serialize output: {}", e),
+    }
+}
+
+// Release builds use the GUI subsystem on Windows, which has no console for stdout
+#[cfg(target_os = "windows")]
+fn attach_parent_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn attach_parent_console() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::SwitchStage;
+
+    #[test]
+    fn test_no_flags_starts_gui() {
+        assert_eq!(parse_args(Vec::<String>::new()).unwrap(), None);
+        assert_eq!(parse_args(["-psn_0_12345"]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_switch_with_options() {
+        let command = parse_args(["--switch", "bob@x.com", "--reset-machine", "--restart"])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            command.action,
+            CliAction::Switch {
+                email: "bob@x.com".to_string(),
+                reset_machine: true,
+                restart: true,
+                validate: None,
+            }
+        );
+
+        let command = parse_args(["--switch", "bob@x.com", "--no-validate"])
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            command.action,
+            CliAction::Switch {
+                validate: Some(false),
+                ..
+            }
+        ));
+        assert_eq!(command.cursor_path, None);
+    }
+
+    #[test]
+    fn test_parse_simple_actions() {
+        let cases = [
+            ("--list", CliAction::List { show_tokens: false }),
+            ("--sync", CliAction::Sync),
+            ("--refresh-all", CliAction::RefreshAll),
+            ("--reset-machine-id", CliAction::ResetMachineId),
+            ("--version", CliAction::Version { verbose: false }),
+            ("--help", CliAction::Help),
+        ];
+
+        for (flag, expected) in cases {
+            let command = parse_args([flag]).unwrap().unwrap();
+            assert_eq!(command.action, expected);
+        }
+
+        let command = parse_args(["--list", "--cursor-path", "/tmp/cursor"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(command.cursor_path, Some(PathBuf::from("/tmp/cursor")));
+
+        let command = parse_args(["--version", "--verbose"]).unwrap().unwrap();
+        assert_eq!(command.action, CliAction::Version { verbose: true });
+        let command = parse_args(["--show-tokens", "--list"]).unwrap().unwrap();
+        assert_eq!(command.action, CliAction::List { show_tokens: true });
+    }
+
+    #[test]
+    fn test_parse_invalid_arguments() {
+        assert!(parse_args(["--switch"]).is_err());
+        assert!(parse_args(["--switch", "--restart"]).is_err());
+        assert!(parse_args(["--list", "--sync"]).is_err());
+        assert!(parse_args(["--list", "--restart"]).is_err());
+        assert!(parse_args(["--reset-machine"]).is_err());
+        assert!(parse_args(["--bogus"]).is_err());
+        assert!(parse_args(["--list", "--verbose"]).is_err());
+        assert!(parse_args(["--sync", "--show-tokens"]).is_err());
+        assert!(parse_args(["--sync", "--no-validate"]).is_err());
+    }
+
+    #[test]
+    fn test_switch_failure_output() {
+        let failure = SwitchFailure {
+            stage: SwitchStage::WriteCredentials,
+            error: "database is locked".to_string(),
+            rolled_back: vec![SwitchStage::WriteCredentials, SwitchStage::CloseCursor],
+            rollback_errors: Vec::new(),
+            next_step: "Try again.".to_string(),
+        };
+        let output = failure_json(&anyhow::Error::new(failure));
+        assert_eq!(output["ok"], false);
+        assert_eq!(output["switch_failure"]["stage"], "write_credentials");
+        assert_eq!(
+            output["switch_failure"]["rolled_back"],
+            json!(["write_credentials", "close_cursor"])
+        );
+        assert!(output["error"]
+            .as_str()
+            .unwrap()
+            .contains("was rolled back"));
+
+        let output = failure_json(&anyhow::anyhow!("Cursor path not set"));
+        assert_eq!(
+            output,
+            json!({ "ok": false, "error": "Cursor path not set" })
+        );
+    }
+}
@@ -0,0 +1,201 @@
+// "Import from clipboard": the copied text is told apart by its shape and
+// handed to the importer for it, so the user doesn't pick a format first.
+
+use crate::clipboard_watcher;
+use crate::csv_manager::{CsvManager, MergeReport};
+use crate::json_import;
+use crate::token_auth;
+use crate::types::Account;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+
+/// What `detect` took the clipboard text for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardFormat {
+    /// Account lines, as `import_accounts` reads them
+    Lines,
+    /// An array of accounts or a single one, as `import_accounts_json` reads them
+    Json,
+    /// A lone access token
+    Jwt,
+    /// A session cookie value, with or without the cookie name
+    SessionCookie,
+}
+
+/// Returned by `import_from_clipboard`
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardImport {
+    pub format: ClipboardFormat,
+    pub accounts: Vec<Account>,
+    /// Lines or JSON entries that couldn't be read
+    pub skipped: usize,
+    /// What was stored; None for a preview
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stored: Option<MergeReport>,
+}
+
+/// The text without a byte order mark, Windows line endings or the
+/// whitespace around it
+pub fn clean(text: &str) -> String {
+    text.trim_start_matches('\u{feff}')
+        .replace("\r\n", "\n")
+        .trim()
+        .to_string()
+}
+
+/// The format of `text`; None when there is nothing but whitespace
+pub fn detect(text: &str) -> Option<ClipboardFormat> {
+    let text = clean(text);
+    if text.is_empty() {
+        return None;
+    }
+    if text.starts_with(['[', '{'])
+        && serde_json::from_str::<Value>(&text).is_ok_and(|v| v.is_array() || v.is_object())
+    {
+        return Some(ClipboardFormat::Json);
+    }
+
+    let token = clipboard_watcher::normalize_candidate(&text)
+        .and_then(|candidate| token_auth::validate_token_info(candidate).ok())
+        .filter(|info| info.is_valid);
+    match token {
+        Some(info) if info.token_type == "jwt" => Some(ClipboardFormat::Jwt),
+        // Like the clipboard watcher, this keeps text such as `std::io` out
+        Some(info)
+            if info.token_type == "session"
+                && info.user_id.as_ref().is_some_and(|id| id.contains("user_")) =>
+        {
+            Some(ClipboardFormat::SessionCookie)
+        }
+        _ => Some(ClipboardFormat::Lines),
+    }
+}
+
+/// Parse `text` with the importer for its format. A token or cookie is
+/// exchanged for its account with `convert_token`.
+pub fn parse(
+    text: &str,
+    manager: &CsvManager,
+    now: &str,
+    convert_token: impl FnOnce(&str) -> Result<Account>,
+) -> Result<ClipboardImport> {
+    let text = clean(text);
+    let format = detect(&text).ok_or_else(|| anyhow::anyhow!("The clipboard holds no text"))?;
+
+    let (accounts, skipped) = match format {
+        ClipboardFormat::Lines => {
+            let import = manager.parse_import_text(&text, false)?;
+            (import.accounts, import.errors.len())
+        }
+        ClipboardFormat::Json => {
+            // The importer reads arrays; a single account is one of one
+            let text = if text.starts_with('{') {
+                format!("[{}]", text)
+            } else {
+                text
+            };
+            let import = json_import::parse(&text, now)?;
+            (import.accounts, import.errors.len())
+        }
+        ClipboardFormat::Jwt | ClipboardFormat::SessionCookie => {
+            let token = clipboard_watcher::normalize_candidate(&text).unwrap_or(&text);
+            (vec![convert_token(token)?], 0)
+        }
+    };
+    Ok(ClipboardImport {
+        format,
+        accounts,
+        skipped,
+        stored: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
+    fn create_test_jwt(sub: &str) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(format!(r#"{{"sub":"{}"}}"#, sub));
+        format!("eyJhbGciOiJIUzI1NiJ9.{}.signature", payload)
+    }
+
+    #[test]
+    fn test_detects_each_format() {
+        let jwt = create_test_jwt("auth0|user_abc");
+        let samples = [
+            (
+                format!("a@x.com,{}\nb@x.com,{}", jwt, jwt),
+                ClipboardFormat::Lines,
+            ),
+            (format!("a@x.com\t{}\r\n", jwt), ClipboardFormat::Lines),
+            ("hello world".to_string(), ClipboardFormat::Lines),
+            ("std::io".to_string(), ClipboardFormat::Lines),
+            (
+                r#"[{"email": "a@x.com", "token": "t"}]"#.to_string(),
+                ClipboardFormat::Json,
+            ),
+            (
+                "\u{feff}  {\"email\": \"a@x.com\",\r\n\"token\": \"t\"}\r\n".to_string(),
+                ClipboardFormat::Json,
+            ),
+            // Not JSON after all, so a line that happens to start with a bracket
+            ("[a@x.com,token".to_string(), ClipboardFormat::Lines),
+            (format!("\u{feff}{}\r\n", jwt), ClipboardFormat::Jwt),
+            (format!("  {}  ", jwt), ClipboardFormat::Jwt),
+            (
+                format!("user_abc%3A%3A{}", jwt),
+                ClipboardFormat::SessionCookie,
+            ),
+            (
+                format!("WorkosCursorSessionToken=user_abc::{}\r\n", jwt),
+                ClipboardFormat::SessionCookie,
+            ),
+            (
+                format!("auth0%7Cuser_abc%3A%3A{}", jwt),
+                ClipboardFormat::SessionCookie,
+            ),
+        ];
+        for (text, format) in samples {
+            assert_eq!(detect(&text), Some(format), "{:?}", text);
+        }
+        assert_eq!(detect(""), None);
+        assert_eq!(detect("\u{feff} \r\n\t"), None);
+    }
+
+    #[test]
+    fn test_parse_dispatches_by_format() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manager = CsvManager::new(temp_dir.path().join("accounts.csv"));
+        let jwt = create_test_jwt("auth0|user_abc");
+        let no_token = |_: &str| -> Result<Account> { panic!("not a token") };
+
+        let text = format!("\u{feff}a@x.com,{}\r\nnot an account\r\n", jwt);
+        let import = parse(&text, &manager, "2024-03-01 09:00:00", no_token).unwrap();
+        assert_eq!(import.format, ClipboardFormat::Lines);
+        assert_eq!(import.accounts[0].email, "a@x.com");
+        assert_eq!(import.accounts[0].access_token.expose(), jwt);
+        assert_eq!(import.skipped, 1);
+
+        let text = format!(r#"{{"email": "b@x.com", "token": "{}"}}"#, jwt);
+        let import = parse(&text, &manager, "2024-03-01 09:00:00", no_token).unwrap();
+        assert_eq!(import.format, ClipboardFormat::Json);
+        assert_eq!(import.accounts[0].email, "b@x.com");
+        assert!(import.stored.is_none());
+
+        let text = format!("WorkosCursorSessionToken=user_abc%3A%3A{}\n", jwt);
+        let import = parse(&text, &manager, "2024-03-01 09:00:00", |token| {
+            assert_eq!(token, format!("user_abc%3A%3A{}", jwt));
+            let json = format!(r#"[{{"email": "c@x.com", "token": "{}"}}]"#, jwt);
+            json_import::parse(&json, "").map(|import| import.accounts[0].clone())
+        })
+        .unwrap();
+        assert_eq!(import.format, ClipboardFormat::SessionCookie);
+        assert_eq!(import.accounts[0].email, "c@x.com");
+
+        assert!(parse(" \r\n", &manager, "", no_token).is_err());
+    }
+}
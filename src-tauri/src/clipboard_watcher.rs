@@ -0,0 +1,234 @@
+use crate::token_auth;
+use crate::types::Account;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How often the clipboard is polled while the watcher is enabled
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Clipboard contents longer than this are never treated as a token
+const MAX_TOKEN_LENGTH: usize = 8192;
+const SESSION_COOKIE_PREFIX: &str = "WorkosCursorSessionToken=";
+
+/// SHA-256 of clipboard contents; the only thing the watcher keeps about them
+pub type Fingerprint = [u8; 32];
+
+/// A token found in the clipboard. Only metadata is kept, never the token itself.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DetectedToken {
+    /// "jwt" or "session"
+    pub token_type: String,
+    pub user_id: String,
+    #[serde(skip)]
+    pub fingerprint: Fingerprint,
+}
+
+pub fn fingerprint(content: &str) -> Fingerprint {
+    Sha256::digest(content.as_bytes()).into()
+}
+
+/// Turn clipboard text into a token candidate (strips the cookie name if copied from devtools)
+pub fn normalize_candidate(content: &str) -> Option<&str> {
+    let candidate = content.trim();
+    let candidate = candidate
+        .strip_prefix(SESSION_COOKIE_PREFIX)
+        .unwrap_or(candidate);
+
+    if candidate.is_empty()
+        || candidate.len() > MAX_TOKEN_LENGTH
+        || candidate.contains(char::is_whitespace)
+    {
+        return None;
+    }
+    Some(candidate)
+}
+
+/// User IDs of every stored account, taken from the access token or session cookie
+pub fn known_user_ids(accounts: &[Account]) -> HashSet<String> {
+    let mut user_ids = HashSet::new();
+
+    for account in accounts {
+        if let Ok(user_id) = token_auth::extract_user_id_from_jwt(account.access_token.expose()) {
+            user_ids.insert(user_id);
+        }
+        if let Some(candidate) = normalize_candidate(account.cookie.expose()) {
+            if let Ok(info) = token_auth::validate_token_info(candidate) {
+                user_ids.extend(info.user_id);
+            }
+        }
+    }
+
+    user_ids
+}
+
+/// Debounces clipboard values and recognizes new Cursor tokens
+#[derive(Default)]
+pub struct TokenDetector {
+    last_fingerprint: Option<Fingerprint>,
+}
+
+impl TokenDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inspect clipboard contents. Returns a detection only the first time a
+    /// valid token appears that doesn't belong to a stored account.
+    pub fn check<F>(&mut self, content: &str, known_user_ids: F) -> Option<DetectedToken>
+    where
+        F: FnOnce() -> HashSet<String>,
+    {
+        let fingerprint = fingerprint(content);
+        if self.last_fingerprint == Some(fingerprint) {
+            return None;
+        }
+        self.last_fingerprint = Some(fingerprint);
+
+        let candidate = normalize_candidate(content)?;
+        let info = token_auth::validate_token_info(candidate).ok()?;
+        // Cursor user IDs look like user_01H...; this keeps text such as `std::io` out
+        let user_id = match info.user_id {
+            Some(user_id) if info.is_valid && user_id.starts_with("user_") => user_id,
+            _ => return None,
+        };
+
+        if known_user_ids().contains(&user_id) {
+            return None;
+        }
+
+        Some(DetectedToken {
+            token_type: info.token_type,
+            user_id,
+            fingerprint,
+        })
+    }
+}
+
+/// Handle of the polling thread; the thread exits shortly after `stop()`
+pub struct ClipboardWatcher {
+    running: Arc<AtomicBool>,
+}
+
+impl ClipboardWatcher {
+    /// Poll `read_clipboard` on a background thread and call `on_detect` for new tokens
+    pub fn start<R, K, D>(read_clipboard: R, known_user_ids: K, on_detect: D) -> Self
+    where
+        R: Fn() -> Option<String> + Send + 'static,
+        K: Fn() -> HashSet<String> + Send + 'static,
+        D: Fn(DetectedToken) + Send + 'static,
+    {
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+
+        thread::spawn(move || {
+            tracing::info!("Clipboard watcher started");
+            let mut detector = TokenDetector::new();
+
+            // Whatever is in the clipboard when watching starts was put there before
+            if let Some(content) = read_clipboard() {
+                detector.last_fingerprint = Some(fingerprint(&content));
+            }
+
+            while flag.load(Ordering::SeqCst) {
+                thread::sleep(POLL_INTERVAL);
+                if !flag.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                if let Some(content) = read_clipboard() {
+                    if let Some(detected) = detector.check(&content, &known_user_ids) {
+                        tracing::info!("Detected {} token in clipboard", detected.token_type);
+                        on_detect(detected);
+                    }
+                }
+            }
+            tracing::info!("Clipboard watcher stopped");
+        });
+
+        Self { running }
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for ClipboardWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::MembershipStatus;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
+    fn create_test_jwt(sub: &str) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(format!(r#"{{"sub":"{}"}}"#, sub));
+        format!("eyJhbGciOiJIUzI1NiJ9.{}.signature", payload)
+    }
+
+    #[test]
+    fn test_detects_new_token_once() {
+        let mut detector = TokenDetector::new();
+        let jwt = create_test_jwt("auth0|user_new");
+
+        let detected = detector.check(&jwt, HashSet::new).unwrap();
+        assert_eq!(detected.token_type, "jwt");
+        assert_eq!(detected.user_id, "user_new");
+
+        // Identical clipboard contents are debounced
+        assert!(detector.check(&jwt, HashSet::new).is_none());
+    }
+
+    #[test]
+    fn test_ignores_known_accounts_and_plain_text() {
+        let mut detector = TokenDetector::new();
+        let known = || HashSet::from(["user_known".to_string()]);
+
+        assert!(detector
+            .check("WorkosCursorSessionToken=user_known%3A%3AeyJabc", known)
+            .is_none());
+        assert!(detector.check("hello world", HashSet::new).is_none());
+        assert!(detector.check("not-a-token", HashSet::new).is_none());
+        assert!(detector.check("std::io", HashSet::new).is_none());
+    }
+
+    #[test]
+    fn test_detects_session_cookie() {
+        let mut detector = TokenDetector::new();
+
+        let detected = detector
+            .check(
+                "  WorkosCursorSessionToken=user_abc%3A%3AeyJabc  ",
+                HashSet::new,
+            )
+            .unwrap();
+        assert_eq!(detected.token_type, "session");
+        assert_eq!(detected.user_id, "user_abc");
+    }
+
+    #[test]
+    fn test_known_user_ids_from_accounts() {
+        let account = Account {
+            index: 1,
+            email: "a@x.com".to_string(),
+            access_token: create_test_jwt("auth0|user_jwt").into(),
+            cookie: "WorkosCursorSessionToken=user_cookie%3A%3AeyJabc".into(),
+            status: MembershipStatus::Free,
+            ..Default::default()
+        };
+
+        let user_ids = known_user_ids(&[account]);
+        assert!(user_ids.contains("user_jwt"));
+        assert!(user_ids.contains("user_cookie"));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::types::{record_time, Account, MembershipStatus};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+/// Live refreshes running at once in `compare_accounts`
+pub const MAX_CONCURRENT_REFRESHES: usize = 3;
+
+/// One column of the comparison table. Money is in US dollars, percentages
+/// are 0-100 and ages are in seconds, so the frontend can render it as-is.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountComparison {
+    pub email: String,
+    /// False when the email isn't in the CSV; every other field is then empty
+    pub found: bool,
+    pub membership: Option<MembershipStatus>,
+    /// None for paid plans without a trial and for unknown values
+    pub days_remaining: Option<f64>,
+    pub used_usd: Option<f64>,
+    pub remaining_usd: Option<f64>,
+    pub limit_usd: Option<f64>,
+    pub used_percent: Option<f64>,
+    /// Time since the row was last refreshed; None when it never was
+    pub data_age_secs: Option<i64>,
+    /// Whether the data was fetched for this request
+    pub refreshed: bool,
+    pub refresh_error: Option<String>,
+}
+
+impl AccountComparison {
+    pub fn from_account(account: &Account, now: NaiveDateTime) -> Self {
+        let used_percent =
+            account
+                .usage_percentage
+                .or_else(|| match (account.usage_used, account.usage_total) {
+                    (Some(used), Some(total)) if total > 0.0 => {
+                        Some((used / total * 100.0).min(100.0))
+                    }
+                    _ => None,
+                });
+        let data_age_secs = record_time::parse(&account.record_time)
+            .map(|recorded| (now - recorded).num_seconds().max(0));
+
+        Self {
+            email: account.email.clone(),
+            found: true,
+            membership: Some(account.status.clone()).filter(|s| !s.as_str().is_empty()),
+            days_remaining: account.days_remaining.filter(|days| *days >= 0.0),
+            used_usd: account.usage_used,
+            remaining_usd: account.usage_remaining,
+            limit_usd: account.usage_total,
+            used_percent,
+            data_age_secs,
+            refreshed: false,
+            refresh_error: None,
+        }
+    }
+
+    pub fn not_found(email: &str) -> Self {
+        Self {
+            email: email.to_string(),
+            found: false,
+            membership: None,
+            days_remaining: None,
+            used_usd: None,
+            remaining_usd: None,
+            limit_usd: None,
+            used_percent: None,
+            data_age_secs: None,
+            refreshed: false,
+            refresh_error: None,
+        }
+    }
+}
+
+/// Requested emails in order, without duplicates
+pub fn dedup_emails(emails: &[String]) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    emails
+        .iter()
+        .map(|email| email.trim().to_string())
+        .filter(|email| !email.is_empty() && seen.insert(email.clone()))
+        .collect()
+}
+
+/// Compare the stored rows of `emails`, keeping the requested order
+pub fn compare(
+    accounts: &[Account],
+    emails: &[String],
+    now: NaiveDateTime,
+) -> Vec<AccountComparison> {
+    dedup_emails(emails)
+        .iter()
+        .map(|email| match accounts.iter().find(|a| &a.email == email) {
+            Some(account) => AccountComparison::from_account(account, now),
+            None => AccountComparison::not_found(email),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    use crate::types::days_remaining;
+
+    fn account(email: &str, days: &str, usage: Option<(f64, f64)>) -> Account {
+        Account {
+            index: 1,
+            days_remaining: days_remaining::parse(days),
+            status: MembershipStatus::FreeTrial,
+            record_time: "2024-03-10 11:00:00".to_string(),
+            usage_used: usage.map(|(used, _)| used),
+            usage_remaining: usage.map(|(used, total)| total - used),
+            usage_total: usage.map(|(_, total)| total),
+            ..test_support::account(email)
+        }
+    }
+
+    #[test]
+    fn test_compare_keeps_order_and_flags_missing() {
+        let now =
+            NaiveDateTime::parse_from_str("2024-03-10 12:00:00", record_time::FORMAT).unwrap();
+        let accounts = vec![
+            account("a@x.com", "7.5", Some((5.0, 20.0))),
+            account("pro@x.com", "N/A", None),
+        ];
+        let emails = ["pro@x.com", "ghost@x.com", "a@x.com", "a@x.com"].map(String::from);
+
+        let rows = compare(&accounts, &emails, now);
+        let order: Vec<&str> = rows.iter().map(|r| r.email.as_str()).collect();
+        assert_eq!(order, vec!["pro@x.com", "ghost@x.com", "a@x.com"]);
+
+        assert!(!rows[1].found);
+        assert_eq!(rows[1], AccountComparison::not_found("ghost@x.com"));
+
+        assert_eq!(rows[0].days_remaining, None);
+        assert_eq!(rows[0].used_percent, None);
+
+        assert_eq!(rows[2].days_remaining, Some(7.5));
+        assert_eq!(rows[2].remaining_usd, Some(15.0));
+        assert_eq!(rows[2].used_percent, Some(25.0));
+        assert_eq!(rows[2].data_age_secs, Some(3600));
+    }
+}
@@ -0,0 +1,109 @@
+// Offline detection. A cheap probe of Cursor's API decides whether the app is
+// offline; while it is, commands answer from the cache and the CSV instead of
+// failing, and background jobs that need the network skip their run.
+
+use crate::http::{HttpClientFactory, RetryPolicy};
+use crate::types::{record_time, Account, AccountInfo, UsageInfo};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Answers HEAD cheaply; any HTTP status means the network is up
+pub const PROBE_URL: &str = "https://api2.cursor.sh";
+/// A probe slower than this counts as offline
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often connectivity is checked in the background
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Payload of `check_connectivity` and the `connectivity-changed` event
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ConnectivityStatus {
+    pub offline: bool,
+}
+
+/// One HEAD request without retries, so a dead network is noticed within the timeout
+pub fn probe(http: &HttpClientFactory) -> bool {
+    let once = RetryPolicy {
+        max_retries: 0,
+        backoff: Duration::ZERO,
+    };
+    let reachable = http
+        .with_timeout(PROBE_TIMEOUT)
+        .with_retry(once)
+        .client()
+        .and_then(|client| client.send(client.head(PROBE_URL)));
+    if let Err(e) = &reachable {
+        tracing::debug!("Connectivity probe failed: {:#}", e);
+    }
+    reachable.is_ok()
+}
+
+/// Account info as of the account's last refresh, for when the API can't be asked
+pub fn stored_account_info(account: &Account) -> AccountInfo {
+    AccountInfo {
+        email: account.email.clone(),
+        membership_type: account.status.clone(),
+        // The API's form: -1 for plans without a day count
+        days_remaining: account.days_remaining.unwrap_or(-1.0),
+        is_student: false,
+        privacy_mode: None,
+    }
+}
+
+/// Usage as of the account's last refresh, None if it was never fetched
+pub fn stored_usage_info(account: &Account) -> Option<UsageInfo> {
+    Some(UsageInfo {
+        total_quota: account.usage_total?,
+        used: account.usage_used?,
+        remaining: account.usage_remaining?,
+        usage_percentage: account.usage_percentage?,
+    })
+}
+
+/// Time since the account was last refreshed, None when its record time is unreadable
+pub fn stored_age(account: &Account, now: NaiveDateTime) -> Option<Duration> {
+    record_time::parse(&account.record_time).and_then(|recorded| (now - recorded).to_std().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::MembershipStatus;
+
+    fn stored(record_time: &str) -> Account {
+        Account {
+            email: "a@x.com".to_string(),
+            status: MembershipStatus::Pro,
+            record_time: record_time.to_string(),
+            usage_used: Some(50.0),
+            usage_remaining: Some(450.0),
+            usage_total: Some(500.0),
+            usage_percentage: Some(10.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_stored_data_stands_in_for_the_api() {
+        let account = stored("2024-03-10 12:00:00");
+        let info = stored_account_info(&account);
+        assert_eq!(info.membership_type, MembershipStatus::Pro);
+        assert_eq!(info.days_remaining, -1.0);
+        assert_eq!(stored_usage_info(&account).unwrap().remaining, 450.0);
+
+        let now =
+            NaiveDateTime::parse_from_str("2024-03-10 13:30:00", record_time::FORMAT).unwrap();
+        assert_eq!(
+            stored_age(&account, now),
+            Some(Duration::from_secs(90 * 60))
+        );
+        assert_eq!(stored_age(&stored("never"), now), None);
+
+        let never_fetched = Account {
+            usage_total: None,
+            ..stored("")
+        };
+        assert!(stored_usage_info(&never_fetched).is_none());
+    }
+}
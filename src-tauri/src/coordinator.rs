@@ -0,0 +1,641 @@
+use crate::error::AppError;
+use crate::state::MutexExt;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Long-running operations that must not overlap: each of them rewrites the
+/// account CSV or Cursor's database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Switching,
+    Refreshing,
+    Resetting,
+    Syncing,
+    Reauthenticating,
+    Importing,
+    CloudSync,
+}
+
+impl Operation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Operation::Switching => "account switch",
+            Operation::Refreshing => "refresh",
+            Operation::Resetting => "machine ID reset",
+            Operation::Syncing => "sync",
+            Operation::Reauthenticating => "re-authentication",
+            Operation::Importing => "import",
+            Operation::CloudSync => "cloud sync",
+        }
+    }
+}
+
+/// Commands read-only mode lets through: they only read, lock the app, or
+/// change how it looks. Anything not listed is refused, so new commands are
+/// until added here. `update_settings` is how read-only mode is turned off and
+/// needs the app unlocked for anything but cosmetic changes.
+pub const READ_ONLY_ALLOWED_COMMANDS: &[&str] = &[
+    "check_connectivity",
+    "check_for_updates",
+    "check_reset_machine_id",
+    "compare_accounts",
+    "complete_onboarding_step",
+    "detect_cursor_path",
+    "find_credential_conflicts",
+    "forecast_account",
+    "forecast_all",
+    "get_account_overview",
+    "get_account_password",
+    "get_account_secrets",
+    "get_accounts_by_tag",
+    "get_all_accounts",
+    "get_app_info",
+    "get_app_lock_status",
+    "get_billing_cycle",
+    "get_current_account_info",
+    "get_cursor_update_history",
+    "get_data_storage_path",
+    "get_detailed_user_info",
+    "get_fleet_stats",
+    "get_interrupted_operation",
+    "get_invoices",
+    "get_last_change_set",
+    "get_last_refresh_info",
+    "get_log_file_path",
+    "get_logs",
+    "get_machine_ids",
+    "get_onboarding_status",
+    "get_privacy_mode",
+    "get_running_operation",
+    "get_settings",
+    "get_store_diagnostics",
+    "get_switch_history",
+    "get_sync_conflicts",
+    "get_token_health",
+    "get_usage_events",
+    "get_usage_info",
+    "is_store_locked",
+    "list_account_backups",
+    "list_cursor_settings_snapshots",
+    "list_import_jobs",
+    "list_pools",
+    "list_state_db_backups",
+    "list_switch_reverts",
+    "lock_app",
+    "open_account_details",
+    "preview_switch",
+    "query_accounts",
+    "recommend_account",
+    "run_self_test",
+    "set_locale",
+    "unlock_app",
+    "unlock_store",
+    "update_settings",
+    "validate_token",
+];
+
+/// Commands refused in read-only mode: everything that switches accounts,
+/// edits, imports or exports them, touches Cursor, or changes what runs in
+/// the background. Refused anyway for not being allowed; listed so every
+/// command is known to be classified.
+pub const READ_ONLY_BLOCKED_COMMANDS: &[&str] = &[
+    "set_cursor_path",
+    "set_data_storage_path",
+    "add_account",
+    "delete_account",
+    "archive_account",
+    "restore_archived_account",
+    "compact_account_indexes",
+    "resolve_pending_accounts",
+    "restore_account_backup",
+    "update_account",
+    "set_account_label",
+    "set_account_nickname",
+    "set_account_pool",
+    "set_account_tags",
+    "toggle_favorite",
+    "move_account",
+    "set_account_order",
+    "delete_pool",
+    "set_account_privacy_mode",
+    "set_privacy_mode",
+    "import_accounts",
+    "import_accounts_json",
+    "batch_add_accounts",
+    "set_webdav_password",
+    "set_store_passphrase",
+    "migrate_store_to_sqlite",
+    "sync_now",
+    "resolve_sync_conflict",
+    "generate_manifest_keys",
+    "import_pool_manifest",
+    "import_account_code",
+    "restore_cursor_settings_snapshot",
+    "switch_account",
+    "reset_machine_id",
+    "resume_or_discard_interrupted",
+    "kill_cursor_process",
+    "logout_current_account",
+    "restore_state_db_backup",
+    "restart_cursor_process",
+    "sync_current_account",
+    "clear_logs",
+    "clear_switch_history",
+    "sync_from_tray",
+    "import_from_token",
+    "start_import_job",
+    "resume_import_job",
+    "discard_import_job",
+    "import_detected_token",
+    "import_from_clipboard",
+    "reauthenticate_current_account",
+    "resolve_conflict",
+    "import_settings",
+    "set_app_lock",
+    "export_account_code",
+    "export_accounts_json",
+    "export_accounts_xlsx",
+    "export_pool_manifest",
+    "export_report",
+    "export_settings",
+    "enable_autostart",
+    "disable_autostart",
+    "set_clipboard_watcher",
+    "run_auto_export_now",
+];
+
+/// Commands that only refresh stored usage data, allowed in read-only mode
+/// when refreshes are
+pub const READ_ONLY_REFRESH_COMMANDS: &[&str] = &[
+    "update_account_info_from_api",
+    "batch_update_all_accounts",
+    "refresh_stale_accounts",
+    "refresh_from_tray",
+];
+
+/// What read-only mode lets through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ReadOnlyPolicy {
+    pub enabled: bool,
+    pub allow_refresh: bool,
+}
+
+impl ReadOnlyPolicy {
+    pub fn allows(&self, operation: Operation) -> bool {
+        !self.enabled || (operation == Operation::Refreshing && self.allow_refresh)
+    }
+
+    pub fn allows_command(&self, command: &str) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        if READ_ONLY_REFRESH_COMMANDS.contains(&command) {
+            return self.allow_refresh;
+        }
+        READ_ONLY_ALLOWED_COMMANDS.contains(&command)
+    }
+}
+
+/// Lets one operation run at a time. Acquiring returns a guard that marks the
+/// app idle again when dropped, including on error paths and panics. In
+/// read-only mode it is also where commands and operations are refused.
+#[derive(Default)]
+pub struct OperationCoordinator {
+    current: Mutex<Option<Operation>>,
+    idle: Condvar,
+    read_only: Mutex<ReadOnlyPolicy>,
+}
+
+pub struct OperationGuard<'a> {
+    coordinator: &'a OperationCoordinator,
+}
+
+impl Drop for OperationGuard<'_> {
+    fn drop(&mut self) {
+        *self.coordinator.current.lock_or_recover() = None;
+        self.coordinator.idle.notify_all();
+    }
+}
+
+impl OperationCoordinator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current(&self) -> Option<Operation> {
+        *self.current.lock_or_recover()
+    }
+
+    pub fn set_read_only(&self, policy: ReadOnlyPolicy) {
+        *self.read_only.lock_or_recover() = policy;
+    }
+
+    pub fn read_only(&self) -> ReadOnlyPolicy {
+        *self.read_only.lock_or_recover()
+    }
+
+    /// Fail with `AppError::ReadOnly` if read-only mode refuses the IPC `command`
+    pub fn check_command(&self, command: &str) -> Result<(), AppError> {
+        if !self.read_only().allows_command(command) {
+            return Err(AppError::ReadOnly(command.to_string()));
+        }
+        Ok(())
+    }
+
+    fn check_operation(&self, operation: Operation) -> Result<(), AppError> {
+        if !self.read_only().allows(operation) {
+            return Err(AppError::ReadOnly(operation.label().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Start `operation`, or fail right away with `AppError::Busy` if another one is running
+    pub fn try_begin(&self, operation: Operation) -> Result<OperationGuard<'_>, AppError> {
+        self.check_operation(operation)?;
+        let mut current = self.current.lock_or_recover();
+        if let Some(running) = *current {
+            return Err(AppError::Busy(running));
+        }
+        *current = Some(operation);
+        Ok(OperationGuard { coordinator: self })
+    }
+
+    /// Start `operation` once the running one has finished, waiting at most `timeout`
+    pub fn begin_when_idle(
+        &self,
+        operation: Operation,
+        timeout: Duration,
+    ) -> Result<OperationGuard<'_>, AppError> {
+        self.check_operation(operation)?;
+        let current = self.current.lock_or_recover();
+        let (mut current, _) = self
+            .idle
+            .wait_timeout_while(current, timeout, |current| current.is_some())
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(running) = *current {
+            return Err(AppError::Busy(running));
+        }
+        *current = Some(operation);
+        Ok(OperationGuard { coordinator: self })
+    }
+}
+
+/// Tray menu actions whose repeated clicks are coalesced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrayAction {
+    Refresh,
+    Sync,
+}
+
+/// What a tray action ends with: the message shown to the user
+pub type TrayActionResult = Result<String, AppError>;
+
+/// Decision for one tray click
+pub enum Admission<'a> {
+    /// Nothing identical is running; run it and hand the result to the ticket
+    Run(TrayTicket<'a>),
+    /// The same action is in flight; its result arrives on the receiver
+    Joined(Receiver<TrayActionResult>),
+    /// A refresh finished `ago`, within the cooldown
+    CoolingDown { ago: Duration },
+}
+
+/// Tray clicks arrive faster than the API tolerates. While an action runs,
+/// identical clicks wait for its result instead of queueing another run, and
+/// a refresh that completed within the cooldown isn't started again unless forced.
+#[derive(Default)]
+pub struct TrayActionQueue {
+    state: Mutex<QueueState>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Waiters of each running action
+    running: HashMap<TrayAction, Vec<Sender<TrayActionResult>>>,
+    last_refresh: Option<Instant>,
+}
+
+/// Held by the click that runs the action. Dropping it without `finish`
+/// (e.g. on a panic) lets waiters fail instead of hanging.
+pub struct TrayTicket<'a> {
+    queue: &'a TrayActionQueue,
+    action: TrayAction,
+    finished: bool,
+}
+
+impl TrayTicket<'_> {
+    /// Pass the result to every coalesced click; a successful refresh starts the cooldown
+    pub fn finish(mut self, result: &TrayActionResult, now: Instant) {
+        self.finished = true;
+        let mut state = self.queue.state.lock_or_recover();
+        if self.action == TrayAction::Refresh && result.is_ok() {
+            state.last_refresh = Some(now);
+        }
+        for waiter in state.running.remove(&self.action).unwrap_or_default() {
+            let _ = waiter.send(result.clone());
+        }
+    }
+}
+
+impl Drop for TrayTicket<'_> {
+    fn drop(&mut self) {
+        self.queue
+            .state
+            .lock_or_recover()
+            .running
+            .remove(&self.action);
+    }
+}
+
+impl TrayActionQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn admit(
+        &self,
+        action: TrayAction,
+        now: Instant,
+        cooldown: Duration,
+        force: bool,
+    ) -> Admission<'_> {
+        let mut state = self.state.lock_or_recover();
+        if let Some(waiters) = state.running.get_mut(&action) {
+            let (sender, receiver) = mpsc::channel();
+            waiters.push(sender);
+            return Admission::Joined(receiver);
+        }
+
+        if action == TrayAction::Refresh && !force {
+            let ago = state
+                .last_refresh
+                .map(|finished| now.saturating_duration_since(finished));
+            if let Some(ago) = ago.filter(|ago| *ago < cooldown) {
+                return Admission::CoolingDown { ago };
+            }
+        }
+
+        state.running.insert(action, Vec::new());
+        Admission::Run(TrayTicket {
+            queue: self,
+            action,
+            finished: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_conflicting_operation_is_busy() {
+        let coordinator = OperationCoordinator::new();
+
+        let guard = coordinator.try_begin(Operation::Refreshing).unwrap();
+        assert_eq!(coordinator.current(), Some(Operation::Refreshing));
+
+        let error = coordinator.try_begin(Operation::Switching).err().unwrap();
+        assert_eq!(error.to_string(), "Busy: refresh in progress");
+
+        drop(guard);
+        assert_eq!(coordinator.current(), None);
+        assert!(coordinator.try_begin(Operation::Switching).is_ok());
+    }
+
+    #[test]
+    fn test_read_only_mode_refuses_changes() {
+        let coordinator = OperationCoordinator::new();
+        coordinator.set_read_only(ReadOnlyPolicy {
+            enabled: true,
+            allow_refresh: false,
+        });
+
+        let mut blocked: Vec<&str> = READ_ONLY_BLOCKED_COMMANDS
+            .iter()
+            .chain(READ_ONLY_REFRESH_COMMANDS)
+            .copied()
+            .filter(|command| coordinator.check_command(command).is_err())
+            .collect();
+        blocked.sort_unstable();
+        assert_eq!(
+            blocked,
+            vec![
+                "add_account",
+                "archive_account",
+                "batch_add_accounts",
+                "batch_update_all_accounts",
+                "clear_logs",
+                "clear_switch_history",
+                "compact_account_indexes",
+                "delete_account",
+                "delete_pool",
+                "disable_autostart",
+                "discard_import_job",
+                "enable_autostart",
+                "export_account_code",
+                "export_accounts_json",
+                "export_accounts_xlsx",
+                "export_pool_manifest",
+                "export_report",
+                "export_settings",
+                "generate_manifest_keys",
+                "import_account_code",
+                "import_accounts",
+                "import_accounts_json",
+                "import_detected_token",
+                "import_from_clipboard",
+                "import_from_token",
+                "import_pool_manifest",
+                "import_settings",
+                "kill_cursor_process",
+                "logout_current_account",
+                "migrate_store_to_sqlite",
+                "move_account",
+                "reauthenticate_current_account",
+                "refresh_from_tray",
+                "refresh_stale_accounts",
+                "reset_machine_id",
+                "resolve_conflict",
+                "resolve_pending_accounts",
+                "resolve_sync_conflict",
+                "restart_cursor_process",
+                "restore_account_backup",
+                "restore_archived_account",
+                "restore_cursor_settings_snapshot",
+                "restore_state_db_backup",
+                "resume_import_job",
+                "resume_or_discard_interrupted",
+                "run_auto_export_now",
+                "set_account_label",
+                "set_account_nickname",
+                "set_account_order",
+                "set_account_pool",
+                "set_account_privacy_mode",
+                "set_account_tags",
+                "set_app_lock",
+                "set_clipboard_watcher",
+                "set_cursor_path",
+                "set_data_storage_path",
+                "set_privacy_mode",
+                "set_store_passphrase",
+                "set_webdav_password",
+                "start_import_job",
+                "switch_account",
+                "sync_current_account",
+                "sync_from_tray",
+                "sync_now",
+                "toggle_favorite",
+                "update_account",
+                "update_account_info_from_api",
+            ]
+        );
+        for command in [
+            "get_all_accounts",
+            "list_pools",
+            "get_usage_info",
+            "update_settings",
+        ] {
+            assert!(coordinator.check_command(command).is_ok(), "{}", command);
+        }
+        for command in ["export_settings", "enable_autostart", "a_new_command"] {
+            assert!(coordinator.check_command(command).is_err(), "{}", command);
+        }
+        let error = coordinator.try_begin(Operation::Switching).err().unwrap();
+        assert_eq!(error.code(), "read_only");
+        assert!(coordinator.try_begin(Operation::Refreshing).is_err());
+
+        coordinator.set_read_only(ReadOnlyPolicy {
+            enabled: true,
+            allow_refresh: true,
+        });
+        assert!(coordinator
+            .check_command("batch_update_all_accounts")
+            .is_ok());
+        assert!(coordinator.try_begin(Operation::Refreshing).is_ok());
+    }
+
+    #[test]
+    fn test_released_on_error_path() {
+        let coordinator = OperationCoordinator::new();
+
+        let failing = || -> Result<(), String> {
+            let _guard = coordinator.try_begin(Operation::Resetting)?;
+            Err("reset failed".to_string())
+        };
+        assert!(failing().is_err());
+        assert_eq!(coordinator.current(), None);
+    }
+
+    #[test]
+    fn test_queued_operation_runs_after_current() {
+        let coordinator = Arc::new(OperationCoordinator::new());
+        let guard = coordinator.try_begin(Operation::Refreshing).unwrap();
+
+        let waiter = coordinator.clone();
+        let queued = thread::spawn(move || {
+            let _guard = waiter
+                .begin_when_idle(Operation::Switching, Duration::from_secs(5))
+                .unwrap();
+            waiter.current()
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        drop(guard);
+        assert_eq!(queued.join().unwrap(), Some(Operation::Switching));
+
+        // Waiting gives up with a busy error when nothing finishes in time
+        let _guard = coordinator.try_begin(Operation::Syncing).unwrap();
+        assert!(coordinator
+            .begin_when_idle(Operation::Switching, Duration::from_millis(10))
+            .is_err());
+    }
+
+    fn ticket(admission: Admission<'_>) -> TrayTicket<'_> {
+        match admission {
+            Admission::Run(ticket) => ticket,
+            _ => panic!("expected to run"),
+        }
+    }
+
+    #[test]
+    fn test_identical_tray_actions_share_one_run() {
+        let queue = TrayActionQueue::new();
+        let start = Instant::now();
+        let cooldown = Duration::from_secs(60);
+
+        let running = ticket(queue.admit(TrayAction::Refresh, start, cooldown, false));
+        let Admission::Joined(joined) = queue.admit(TrayAction::Refresh, start, cooldown, false)
+        else {
+            panic!("expected to join the running refresh");
+        };
+        // Other actions aren't held up
+        drop(ticket(queue.admit(
+            TrayAction::Sync,
+            start,
+            cooldown,
+            false,
+        )));
+
+        running.finish(&Ok("Refreshed 3 accounts".to_string()), start);
+        assert_eq!(joined.recv().unwrap().unwrap(), "Refreshed 3 accounts");
+    }
+
+    #[test]
+    fn test_refresh_cooldown() {
+        let queue = TrayActionQueue::new();
+        let start = Instant::now();
+        let cooldown = Duration::from_secs(60);
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // A failed refresh can be retried right away
+        let failed = ticket(queue.admit(TrayAction::Refresh, start, cooldown, false));
+        failed.finish(&Err("rate limited".into()), start);
+        let done = ticket(queue.admit(TrayAction::Refresh, at(1), cooldown, false));
+        done.finish(&Ok("Refreshed".to_string()), at(10));
+
+        match queue.admit(TrayAction::Refresh, at(25), cooldown, false) {
+            Admission::CoolingDown { ago } => assert_eq!(ago, Duration::from_secs(15)),
+            _ => panic!("expected the cooldown"),
+        }
+        drop(ticket(queue.admit(
+            TrayAction::Refresh,
+            at(25),
+            cooldown,
+            true,
+        )));
+        drop(ticket(queue.admit(
+            TrayAction::Refresh,
+            at(70),
+            cooldown,
+            false,
+        )));
+    }
+
+    #[test]
+    fn test_dropped_ticket_releases_waiters() {
+        let queue = TrayActionQueue::new();
+        let now = Instant::now();
+
+        let running = ticket(queue.admit(TrayAction::Sync, now, Duration::ZERO, false));
+        let Admission::Joined(joined) = queue.admit(TrayAction::Sync, now, Duration::ZERO, false)
+        else {
+            panic!("expected to join the running sync");
+        };
+        drop(running);
+        assert!(joined.recv().is_err());
+        drop(ticket(queue.admit(
+            TrayAction::Sync,
+            now,
+            Duration::ZERO,
+            false,
+        )));
+    }
+}
@@ -0,0 +1,410 @@
+use crate::database::Database;
+use crate::path_detector::PathDetector;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory next to the account CSV with one JSON file per settings snapshot
+pub const SNAPSHOTS_DIR: &str = "cursor_settings_snapshots";
+/// Oldest snapshots are deleted beyond this many
+const MAX_SNAPSHOTS: usize = 10;
+/// Names the snapshot to compare against once Cursor has started after a reset
+const PENDING_FILE: &str = "pending";
+
+const EVERY_OS: &[&str] = &["macos", "windows", "linux"];
+
+/// Something of the user's that Cursor may wipe when a reset makes it think
+/// it runs on a new device
+enum Item {
+    /// A file in Cursor's User directory
+    UserFile(&'static str),
+    /// A key in the ItemTable of globalStorage/state.vscdb
+    StateKey(&'static str),
+}
+
+/// What a reset preserves, with the platforms where Cursor keeps it
+const PRESERVED: &[(Item, &[&str])] = &[
+    (Item::UserFile("settings.json"), EVERY_OS),
+    (Item::UserFile("keybindings.json"), EVERY_OS),
+    // Theme, and whether the welcome walkthrough and telemetry notice already ran
+    (Item::StateKey("colorThemeData"), EVERY_OS),
+    (Item::StateKey("iconThemeData"), EVERY_OS),
+    (
+        Item::StateKey("workbench.welcomePage.walkthroughMetadata"),
+        EVERY_OS,
+    ),
+    (Item::StateKey("workbench.telemetryOptOutShown"), EVERY_OS),
+];
+
+fn preserved() -> impl Iterator<Item = &'static Item> {
+    PRESERVED
+        .iter()
+        .filter(|(_, platforms)| platforms.contains(&std::env::consts::OS))
+        .map(|(item, _)| item)
+}
+
+/// Cursor's settings as they were before a machine ID reset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettingsSnapshot {
+    pub id: String,
+    pub taken_at: DateTime<Utc>,
+    /// Contents of the user files that had any, by file name
+    pub files: BTreeMap<String, String>,
+    /// The state keys that were set
+    pub state: BTreeMap<String, String>,
+}
+
+/// Returned by `list_cursor_settings_snapshots`, newest first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub taken_at: DateTime<Utc>,
+    /// File names and state keys in the snapshot
+    pub items: Vec<String>,
+}
+
+/// What a restore put back
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SettingsRestore {
+    pub snapshot_id: String,
+    /// File names and state keys written back
+    pub restored: Vec<String>,
+    /// Wiped state keys left alone because Cursor was running and would
+    /// overwrite them
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreMode {
+    /// Only what is now missing or empty, keeping changes the user made since
+    Wiped,
+    /// Everything that differs from the snapshot
+    All,
+}
+
+impl SettingsSnapshot {
+    /// Read the preserved items of the Cursor install at `base_path`
+    pub fn take(base_path: &Path, taken_at: DateTime<Utc>) -> Result<Self> {
+        let user_dir = user_dir(base_path)?;
+        let mut files = BTreeMap::new();
+        let mut keys = Vec::new();
+        for item in preserved() {
+            match item {
+                Item::UserFile(name) => {
+                    if let Some(content) = read_optional(&user_dir.join(name))? {
+                        if !is_wiped(Some(&content)) {
+                            files.insert(name.to_string(), content);
+                        }
+                    }
+                }
+                Item::StateKey(key) => keys.push(*key),
+            }
+        }
+
+        let db_path = PathDetector::get_db_path(base_path);
+        // Opening a missing database would create it
+        let state = if db_path.is_file() {
+            Database::new(db_path).get_items(&keys)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            id: taken_at.format("%Y%m%dT%H%M%S%3fZ").to_string(),
+            taken_at,
+            files,
+            state,
+        })
+    }
+
+    /// Write the snapshot back. State keys live in a database Cursor keeps in
+    /// memory, so while it runs they are only reported.
+    pub fn restore(
+        &self,
+        base_path: &Path,
+        mode: RestoreMode,
+        cursor_running: bool,
+    ) -> Result<SettingsRestore> {
+        let user_dir = user_dir(base_path)?;
+        let mut report = SettingsRestore {
+            snapshot_id: self.id.clone(),
+            ..SettingsRestore::default()
+        };
+        let needs_restore = |current: Option<&str>, saved: &str| match mode {
+            RestoreMode::Wiped => is_wiped(current),
+            RestoreMode::All => current != Some(saved),
+        };
+
+        for (name, content) in &self.files {
+            let path = user_dir.join(name);
+            if needs_restore(read_optional(&path)?.as_deref(), content) {
+                fs::write(&path, content)
+                    .with_context(|| format!("Failed to restore Cursor's {}", name))?;
+                report.restored.push(name.clone());
+            }
+        }
+
+        let db_path = PathDetector::get_db_path(base_path);
+        if self.state.is_empty() || !db_path.is_file() {
+            return Ok(report);
+        }
+        let db = Database::new(db_path);
+        let keys: Vec<&str> = self.state.keys().map(String::as_str).collect();
+        let current = db.get_items(&keys)?;
+        let changed: BTreeMap<String, String> = self
+            .state
+            .iter()
+            .filter(|(key, value)| needs_restore(current.get(*key).map(String::as_str), value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        if cursor_running {
+            report.skipped.extend(changed.into_keys());
+        } else if !changed.is_empty() {
+            db.set_items(&changed)?;
+            report.restored.extend(changed.into_keys());
+        }
+        Ok(report)
+    }
+}
+
+/// Settings snapshots, kept under the app data dir
+pub struct SettingsSnapshotStore {
+    dir: PathBuf,
+}
+
+impl SettingsSnapshotStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Snapshot the Cursor install at `base_path` and save it, dropping the
+    /// oldest snapshots beyond `MAX_SNAPSHOTS`
+    pub fn take(&self, base_path: &Path, now: DateTime<Utc>) -> Result<SettingsSnapshot> {
+        let snapshot = SettingsSnapshot::take(base_path, now)?;
+        fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string_pretty(&snapshot)?;
+        fs::write(self.snapshot_path(&snapshot.id)?, content)
+            .context("Failed to save the settings snapshot")?;
+
+        let ids = self.ids()?;
+        for id in &ids[..ids.len().saturating_sub(MAX_SNAPSHOTS)] {
+            if let Err(e) = fs::remove_file(self.snapshot_path(id)?) {
+                tracing::warn!("Failed to delete settings snapshot {}: {}", id, e);
+            }
+        }
+        Ok(snapshot)
+    }
+
+    pub fn load(&self, id: &str) -> Result<SettingsSnapshot> {
+        let path = self.snapshot_path(id)?;
+        if !path.is_file() {
+            bail!("Settings snapshot not found: {}", id);
+        }
+        let content = fs::read_to_string(&path).context("Failed to read settings snapshot")?;
+        serde_json::from_str(&content).context("Failed to parse settings snapshot")
+    }
+
+    pub fn list(&self) -> Result<Vec<SnapshotSummary>> {
+        let mut summaries = Vec::new();
+        for id in self.ids()?.iter().rev() {
+            let snapshot = match self.load(id) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    tracing::warn!("Skipping settings snapshot {}: {:#}", id, e);
+                    continue;
+                }
+            };
+            summaries.push(SnapshotSummary {
+                items: snapshot
+                    .files
+                    .into_keys()
+                    .chain(snapshot.state.into_keys())
+                    .collect(),
+                id: snapshot.id,
+                taken_at: snapshot.taken_at,
+            });
+        }
+        Ok(summaries)
+    }
+
+    /// Compare against snapshot `id` at Cursor's next start
+    pub fn set_pending(&self, id: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(PENDING_FILE), id).context("Failed to save the pending check")
+    }
+
+    pub fn pending(&self) -> Option<String> {
+        let id = fs::read_to_string(self.dir.join(PENDING_FILE)).ok()?;
+        Some(id.trim().to_string()).filter(|id| !id.is_empty())
+    }
+
+    pub fn clear_pending(&self) -> Result<()> {
+        match fs::remove_file(self.dir.join(PENDING_FILE)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).context("Failed to clear the pending check"),
+        }
+    }
+
+    // Oldest first; the IDs are timestamps, so names sort by age
+    fn ids(&self) -> Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut ids: Vec<String> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .filter(|id| is_valid_snapshot_id(id))
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    // IDs come from the frontend, so they must not name anything outside the dir
+    fn snapshot_path(&self, id: &str) -> Result<PathBuf> {
+        if !is_valid_snapshot_id(id) {
+            bail!("Invalid settings snapshot ID: {}", id);
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+/// Whether `id` could name a snapshot; IDs are timestamps like "20240311T090000000Z"
+pub fn is_valid_snapshot_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Cursor's User directory, the parent of the globalStorage `base_path`
+fn user_dir(base_path: &Path) -> Result<&Path> {
+    base_path
+        .parent()
+        .context("Cursor path has no User directory above it")
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+// A first run leaves settings missing or as an empty JSON document
+fn is_wiped(content: Option<&str>) -> bool {
+    content.is_none_or(|content| matches!(content.trim(), "" | "{}" | "[]" | "null"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use rusqlite::Connection;
+
+    fn cursor_install(temp_dir: &Path) -> PathBuf {
+        let base_path = temp_dir.join("User").join("globalStorage");
+        fs::create_dir_all(&base_path).unwrap();
+        let conn = Connection::open(PathDetector::get_db_path(&base_path)).unwrap();
+        conn.execute(
+            "CREATE TABLE ItemTable (key TEXT UNIQUE ON CONFLICT REPLACE, value BLOB)",
+            [],
+        )
+        .unwrap();
+        base_path
+    }
+
+    fn set_state(base_path: &Path, key: &str, value: Option<&str>) {
+        let conn = Connection::open(PathDetector::get_db_path(base_path)).unwrap();
+        conn.execute("DELETE FROM ItemTable WHERE key = ?1", [key])
+            .unwrap();
+        if let Some(value) = value {
+            conn.execute(
+                "INSERT INTO ItemTable (key, value) VALUES (?1, ?2)",
+                [key, value],
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_wiped_settings_are_restored_and_new_changes_kept() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_path = cursor_install(temp_dir.path());
+        let user_dir = temp_dir.path().join("User");
+        fs::write(user_dir.join("settings.json"), r#"{"editor.fontSize": 15}"#).unwrap();
+        fs::write(user_dir.join("keybindings.json"), "[]").unwrap();
+        set_state(&base_path, "colorThemeData", Some("dark"));
+        set_state(&base_path, "workbench.telemetryOptOutShown", Some("true"));
+
+        let store = SettingsSnapshotStore::new(temp_dir.path().join(SNAPSHOTS_DIR));
+        let now = Utc.with_ymd_and_hms(2024, 3, 11, 9, 0, 0).unwrap();
+        let snapshot = store.take(&base_path, now).unwrap();
+        // Empty keybindings are nothing worth restoring
+        assert_eq!(snapshot.files.keys().collect::<Vec<_>>(), ["settings.json"]);
+        assert_eq!(snapshot.state.len(), 2);
+
+        // A first run wipes the settings and onboarding state, the user picks a new theme
+        fs::write(user_dir.join("settings.json"), "{}").unwrap();
+        set_state(&base_path, "workbench.telemetryOptOutShown", None);
+        set_state(&base_path, "colorThemeData", Some("light"));
+
+        let loaded = store.load(&snapshot.id).unwrap();
+        let running = loaded
+            .restore(&base_path, RestoreMode::Wiped, true)
+            .unwrap();
+        assert_eq!(running.restored, ["settings.json"]);
+        assert_eq!(running.skipped, ["workbench.telemetryOptOutShown"]);
+        assert_eq!(
+            fs::read_to_string(user_dir.join("settings.json")).unwrap(),
+            r#"{"editor.fontSize": 15}"#
+        );
+
+        let closed = loaded
+            .restore(&base_path, RestoreMode::Wiped, false)
+            .unwrap();
+        assert_eq!(closed.restored, ["workbench.telemetryOptOutShown"]);
+        let db = Database::new(PathDetector::get_db_path(&base_path));
+        assert_eq!(
+            db.get_items(&["colorThemeData"]).unwrap()["colorThemeData"],
+            "light"
+        );
+
+        // A manual restore puts back everything
+        let all = loaded.restore(&base_path, RestoreMode::All, false).unwrap();
+        assert_eq!(all.restored, ["colorThemeData"]);
+        assert_eq!(
+            db.get_items(&["colorThemeData"]).unwrap()["colorThemeData"],
+            "dark"
+        );
+    }
+
+    #[test]
+    fn test_snapshots_are_pruned_and_ids_checked() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_path = cursor_install(temp_dir.path());
+        let store = SettingsSnapshotStore::new(temp_dir.path().join(SNAPSHOTS_DIR));
+        let start = Utc.with_ymd_and_hms(2024, 3, 11, 9, 0, 0).unwrap();
+
+        let first = store.take(&base_path, start).unwrap();
+        for minute in 1..=MAX_SNAPSHOTS as i64 {
+            store
+                .take(&base_path, start + Duration::minutes(minute))
+                .unwrap();
+        }
+        let listed = store.list().unwrap();
+        assert_eq!(listed.len(), MAX_SNAPSHOTS);
+        assert!(listed[0].taken_at > listed[1].taken_at);
+        assert!(store.load(&first.id).is_err());
+
+        assert!(store.load("../../settings").is_err());
+        assert_eq!(store.pending(), None);
+        store.set_pending(&listed[0].id).unwrap();
+        assert_eq!(store.pending(), Some(listed[0].id.clone()));
+        store.clear_pending().unwrap();
+        assert_eq!(store.pending(), None);
+    }
+}
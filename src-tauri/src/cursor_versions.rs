@@ -0,0 +1,120 @@
+use crate::path_detector::PathDetector;
+use crate::reset_machine::{self, PatchStatus};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// File next to the account CSV listing the Cursor versions seen
+pub const CURSOR_VERSIONS_FILE: &str = "cursor_versions.json";
+/// Oldest observations are dropped beyond this many
+const MAX_HISTORY: usize = 100;
+
+/// A Cursor version and when it was first seen installed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionObservation {
+    pub version: String,
+    pub first_seen: DateTime<Utc>,
+}
+
+/// Payload of the `cursor-updated` event
+#[derive(Debug, Clone, Serialize)]
+pub struct CursorUpdate {
+    pub previous: String,
+    pub version: String,
+    /// An update replaces main.js, so a reset's patch is usually gone
+    pub patch_status: PatchStatus,
+}
+
+/// Oldest-first history of the installed Cursor versions
+pub struct CursorVersionStore {
+    file_path: PathBuf,
+}
+
+impl CursorVersionStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    pub fn history(&self) -> Result<Vec<VersionObservation>> {
+        if !self.file_path.exists() {
+            return Ok(Vec::new());
+        }
+        let content =
+            fs::read_to_string(&self.file_path).context("Failed to read Cursor versions")?;
+        serde_json::from_str(&content).context("Failed to parse Cursor versions")
+    }
+
+    /// Record `version` as installed at `now`. Returns the previously seen
+    /// version when this one differs from it; the first version ever seen is
+    /// only recorded.
+    pub fn observe(&self, version: &str, now: DateTime<Utc>) -> Result<Option<String>> {
+        let mut history = self.history()?;
+        let previous = history.last().map(|seen| seen.version.clone());
+        if previous.as_deref() == Some(version) {
+            return Ok(None);
+        }
+
+        history.push(VersionObservation {
+            version: version.to_string(),
+            first_seen: now,
+        });
+        let overflow = history.len().saturating_sub(MAX_HISTORY);
+        history.drain(..overflow);
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&history)?;
+        fs::write(&self.file_path, content).context("Failed to write Cursor versions")?;
+        Ok(previous)
+    }
+
+    /// Record the installed Cursor version, as after each switch. The update
+    /// when it changed since the last one recorded; None when it didn't or
+    /// can't be found.
+    pub fn check_installed(&self, now: DateTime<Utc>) -> Result<Option<CursorUpdate>> {
+        let Some(version) = PathDetector::detect_cursor_version() else {
+            return Ok(None);
+        };
+        let Some(previous) = self.observe(&version, now)? else {
+            return Ok(None);
+        };
+        Ok(Some(CursorUpdate {
+            previous,
+            version,
+            patch_status: reset_machine::main_js_patch_status(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_changes_are_reported_against_the_last_version() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = CursorVersionStore::new(temp_dir.path().join(CURSOR_VERSIONS_FILE));
+        let monday = Utc.with_ymd_and_hms(2024, 3, 11, 9, 0, 0).unwrap();
+        let tuesday = Utc.with_ymd_and_hms(2024, 3, 12, 9, 0, 0).unwrap();
+
+        assert_eq!(store.observe("0.42.3", monday).unwrap(), None);
+        assert_eq!(store.observe("0.42.3", tuesday).unwrap(), None);
+        assert_eq!(
+            store.observe("0.43.0", tuesday).unwrap().as_deref(),
+            Some("0.42.3")
+        );
+        // A downgrade is a change too
+        assert_eq!(
+            store.observe("0.42.3", tuesday).unwrap().as_deref(),
+            Some("0.43.0")
+        );
+
+        let history = store.history().unwrap();
+        let versions: Vec<&str> = history.iter().map(|seen| seen.version.as_str()).collect();
+        assert_eq!(versions, ["0.42.3", "0.43.0", "0.42.3"]);
+        assert_eq!(history[0].first_seen, monday);
+    }
+}
@@ -0,0 +1,370 @@
+// Where the account store lives: the app data directory, unless it was moved
+// somewhere else, e.g. a synced folder, with `set_data_storage_path`. The
+// files kept next to the account CSV move along with it.
+
+use crate::account_backups;
+use crate::account_store::{self, StoreBackend};
+use crate::app_info::ACCOUNTS_FILE_NAME;
+use crate::change_set::CHANGE_SETS_FILE;
+use crate::csv_manager;
+use crate::cursor_settings::SNAPSHOTS_DIR;
+use crate::cursor_versions::CURSOR_VERSIONS_FILE;
+use crate::file_cache;
+use crate::import_jobs::JOBS_DIR_NAME;
+use crate::integrity::IDENTITIES_FILE;
+use crate::refresh_state::REFRESH_STATE_FILE;
+use crate::store_crypto;
+use crate::switch_cooldown::SWITCH_HISTORY_FILE;
+use crate::switch_log::SWITCH_LOG_FILE;
+use crate::switch_verification::SWITCH_REVERTS_FILE;
+use crate::usage_history::USAGE_HISTORY_FILE;
+use crate::webdav_sync::{SYNC_BASE_FILE, SYNC_STATE_FILE};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a move does with accounts already stored where it moves to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExistingStore {
+    /// Replace them with the ones moved, after backing them up
+    Overwrite,
+    /// Add the accounts moved, see `csv_manager::merge_accounts`
+    Merge,
+}
+
+/// The files and directories kept next to the account CSV besides the store
+/// itself, by name. A store that moves takes all of them along.
+pub const SIDECARS: &[&str] = &[
+    SWITCH_HISTORY_FILE,
+    SWITCH_LOG_FILE,
+    CHANGE_SETS_FILE,
+    SYNC_STATE_FILE,
+    SYNC_BASE_FILE,
+    IDENTITIES_FILE,
+    USAGE_HISTORY_FILE,
+    JOBS_DIR_NAME,
+    REFRESH_STATE_FILE,
+    CURSOR_VERSIONS_FILE,
+    SWITCH_REVERTS_FILE,
+    SNAPSHOTS_DIR,
+];
+
+/// The account CSV in `storage_dir` from the settings, or in the app data dir
+pub fn csv_path(app_data_dir: &Path, storage_dir: Option<&Path>) -> PathBuf {
+    storage_dir.unwrap_or(app_data_dir).join(ACCOUNTS_FILE_NAME)
+}
+
+/// An error unless `dir` is a directory files can be created in
+pub fn check_writable(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+    let probe = dir.join(".cursor_switcher_write_test");
+    fs::write(&probe, b"")
+        .and_then(|_| fs::remove_file(&probe))
+        .with_context(|| format!("Can't write to {}", dir.display()))
+}
+
+/// Whether `dir` already holds an account store
+pub fn has_store(dir: &Path) -> bool {
+    let csv_path = dir.join(ACCOUNTS_FILE_NAME);
+    csv_path.exists() || account_store::db_path(&csv_path).exists()
+}
+
+/// Move the store at `csv_path` into `dir` and return the CSV path there.
+/// With `migrate` the accounts, their backups and the `SIDECARS` are copied
+/// over, and a store already in `dir` is only replaced or merged into as
+/// `existing` says; a merge keeps the sidecars already there.
+/// Without it the accounts in `dir`, if any, are used as they are. The
+/// files at `csv_path` are left where they are either way.
+pub fn relocate(
+    csv_path: &Path,
+    dir: &Path,
+    migrate: bool,
+    existing: Option<ExistingStore>,
+    backend: StoreBackend,
+) -> Result<PathBuf> {
+    check_writable(dir)?;
+    let target = dir.join(ACCOUNTS_FILE_NAME);
+    if csv_manager::canonical(&target) == csv_manager::canonical(csv_path) || !migrate {
+        return Ok(target);
+    }
+
+    match (has_store(dir), existing) {
+        (true, None) => bail!(
+            "{} already holds accounts; choose to overwrite or merge them",
+            dir.display()
+        ),
+        (true, Some(ExistingStore::Merge)) => {
+            merge(csv_path, &target, backend)?;
+            copy_sidecars(csv_path, &target, false)?;
+        }
+        (true, Some(ExistingStore::Overwrite)) => {
+            account_backups::back_up(&target, u32::MAX, chrono::Local::now())
+                .context("Failed to back up the accounts being overwritten")?;
+            copy_store(csv_path, &target)?;
+            copy_sidecars(csv_path, &target, true)?;
+        }
+        (false, _) => {
+            copy_store(csv_path, &target)?;
+            copy_sidecars(csv_path, &target, true)?;
+        }
+    }
+    copy_backups(csv_path, &target)?;
+    tracing::info!(
+        "Account store copied from {} to {}",
+        csv_path.display(),
+        target.display()
+    );
+    Ok(target)
+}
+
+// The files a store is made of, by what they are to `csv_path`
+fn store_files(csv_path: &Path) -> [PathBuf; 3] {
+    [
+        csv_path.to_path_buf(),
+        store_crypto::key_file_path(csv_path),
+        account_store::db_path(csv_path),
+    ]
+}
+
+// Files the old store doesn't have go from the new place too, or a stale key
+// file would lock the copied accounts
+fn copy_store(csv_path: &Path, target: &Path) -> Result<()> {
+    for (from, to) in store_files(csv_path).iter().zip(store_files(target)) {
+        if from.exists() {
+            fs::copy(from, &to).with_context(|| format!("Failed to copy {}", from.display()))?;
+        } else if to.exists() {
+            fs::remove_file(&to).with_context(|| format!("Failed to remove {}", to.display()))?;
+        }
+    }
+    // The copied key file is the one already unlocked
+    if let Ok(Some(key)) = store_crypto::token_key(csv_path) {
+        store_crypto::remember(target, key);
+    }
+    Ok(())
+}
+
+fn merge(csv_path: &Path, target: &Path, backend: StoreBackend) -> Result<()> {
+    if store_crypto::is_encrypted_store(csv_path) || store_crypto::is_encrypted_store(target) {
+        bail!("Encrypted account stores can't be merged; overwrite instead");
+    }
+    let accounts = account_store::open(csv_path, backend, 0).read_accounts()?;
+    let report = account_store::open(target, backend, account_backups::DEFAULT_RETENTION)
+        .batch_merge_accounts(accounts)?;
+    tracing::info!(
+        "Merged into {}: {} added, {} merged",
+        target.display(),
+        report.added.len(),
+        report.merged.len()
+    );
+    Ok(())
+}
+
+// With `replace` the sidecars in the new place end up the old store's, ones
+// it has none of removed; a switch log or sync base of other accounts would
+// be wrong for these. Without it only missing ones are copied.
+fn copy_sidecars(csv_path: &Path, target: &Path, replace: bool) -> Result<()> {
+    for name in SIDECARS {
+        let from = csv_path.with_file_name(name);
+        let to = target.with_file_name(name);
+        if to.exists() {
+            if !replace {
+                continue;
+            }
+            remove_path(&to)?;
+        }
+        if from.exists() {
+            copy_path(&from, &to).with_context(|| format!("Failed to copy {}", from.display()))?;
+        }
+        file_cache::note_write(&to);
+    }
+    Ok(())
+}
+
+fn copy_path(from: &Path, to: &Path) -> Result<()> {
+    if !from.is_dir() {
+        fs::copy(from, to)?;
+        return Ok(());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_path(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+    .with_context(|| format!("Failed to remove {}", path.display()))
+}
+
+// Backups already there are kept, ones of the same name included
+fn copy_backups(csv_path: &Path, target: &Path) -> Result<()> {
+    let to_dir = account_backups::backup_dir(target);
+    for backup in account_backups::list(csv_path)? {
+        let Some(name) = backup.path.file_name() else {
+            continue;
+        };
+        fs::create_dir_all(&to_dir)?;
+        let to = to_dir.join(name);
+        if !to.exists() {
+            fs::copy(&backup.path, &to)
+                .with_context(|| format!("Failed to copy {}", backup.path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_manager::CsvManager;
+    use crate::test_support::account;
+
+    fn store_with(dir: &Path, emails: &[&str]) -> CsvManager {
+        let manager = CsvManager::new(dir.join(ACCOUNTS_FILE_NAME)).with_backups(5);
+        manager.ensure_csv_exists().unwrap();
+        manager
+            .batch_add_accounts(emails.iter().map(|email| account(email)).collect())
+            .unwrap();
+        manager
+    }
+
+    fn emails(dir: &Path) -> Vec<String> {
+        CsvManager::new(dir.join(ACCOUNTS_FILE_NAME))
+            .read_accounts()
+            .unwrap()
+            .into_iter()
+            .map(|a| a.email)
+            .collect()
+    }
+
+    #[test]
+    fn test_migrate_copies_the_accounts_and_backups() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        let source = store_with(from.path(), &["a@x.com"]);
+
+        let target =
+            relocate(source.file_path(), to.path(), true, None, StoreBackend::Csv).unwrap();
+        assert_eq!(target, to.path().join(ACCOUNTS_FILE_NAME));
+        assert_eq!(emails(to.path()), vec!["a@x.com"]);
+        assert_eq!(
+            account_backups::list(&target).unwrap().len(),
+            account_backups::list(source.file_path()).unwrap().len()
+        );
+        assert_eq!(emails(from.path()), vec!["a@x.com"]);
+
+        // Without migrating, the accounts found there are used as they are
+        let empty = tempfile::tempdir().unwrap();
+        let target = relocate(
+            source.file_path(),
+            empty.path(),
+            false,
+            None,
+            StoreBackend::Csv,
+        )
+        .unwrap();
+        assert!(!target.exists());
+
+        let file = from.path().join("not_a_dir");
+        fs::write(&file, "").unwrap();
+        assert!(relocate(source.file_path(), &file, true, None, StoreBackend::Csv).is_err());
+        assert!(relocate(
+            source.file_path(),
+            &from.path().join("missing"),
+            true,
+            None,
+            StoreBackend::Csv,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_existing_accounts_need_overwrite_or_merge() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        let source = store_with(from.path(), &["a@x.com", "b@x.com"]);
+        store_with(to.path(), &["b@x.com", "c@x.com"]);
+        let relocate_with = |existing| {
+            relocate(
+                source.file_path(),
+                to.path(),
+                true,
+                existing,
+                StoreBackend::Csv,
+            )
+        };
+
+        let refused = relocate_with(None).unwrap_err();
+        assert!(refused.to_string().contains("already holds accounts"));
+        assert_eq!(emails(to.path()), vec!["b@x.com", "c@x.com"]);
+
+        relocate_with(Some(ExistingStore::Merge)).unwrap();
+        assert_eq!(emails(to.path()), vec!["b@x.com", "c@x.com", "a@x.com"]);
+
+        let target = relocate_with(Some(ExistingStore::Overwrite)).unwrap();
+        assert_eq!(emails(to.path()), vec!["a@x.com", "b@x.com"]);
+        // The overwritten accounts are the newest backup there
+        let overwritten = &account_backups::list(&target).unwrap()[0];
+        assert_eq!(overwritten.rows, 3);
+    }
+
+    #[test]
+    fn test_sidecars_move_with_the_store() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        let source = store_with(from.path(), &["a@x.com"]);
+        for name in SIDECARS {
+            let path = from.path().join(name);
+            if *name == JOBS_DIR_NAME || *name == SNAPSHOTS_DIR {
+                fs::create_dir_all(path.join("nested")).unwrap();
+                fs::write(path.join("nested").join("entry.json"), *name).unwrap();
+            } else {
+                fs::write(path, *name).unwrap();
+            }
+        }
+        let relocate_with = |existing| {
+            relocate(
+                source.file_path(),
+                to.path(),
+                true,
+                existing,
+                StoreBackend::Csv,
+            )
+        };
+
+        relocate_with(None).unwrap();
+        for name in SIDECARS {
+            let path = to.path().join(name);
+            let content = if path.is_dir() {
+                fs::read_to_string(path.join("nested").join("entry.json"))
+            } else {
+                fs::read_to_string(&path)
+            };
+            assert_eq!(content.unwrap(), *name, "{} was not copied", name);
+        }
+
+        // A merge keeps what is there, an overwrite replaces it and removes
+        // what the moved store doesn't have
+        fs::write(to.path().join(SWITCH_LOG_FILE), "kept").unwrap();
+        fs::remove_file(from.path().join(SYNC_BASE_FILE)).unwrap();
+        relocate_with(Some(ExistingStore::Merge)).unwrap();
+        let switch_log = to.path().join(SWITCH_LOG_FILE);
+        assert_eq!(fs::read_to_string(&switch_log).unwrap(), "kept");
+        assert!(to.path().join(SYNC_BASE_FILE).exists());
+
+        relocate_with(Some(ExistingStore::Overwrite)).unwrap();
+        assert_eq!(fs::read_to_string(&switch_log).unwrap(), SWITCH_LOG_FILE);
+        assert!(!to.path().join(SYNC_BASE_FILE).exists());
+        assert!(from.path().join(SWITCH_LOG_FILE).exists());
+    }
+}
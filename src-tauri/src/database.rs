@@ -1,91 +1,475 @@
+use crate::change_set::KeyChange;
+use crate::file_cache;
+use crate::secret::SecretString;
 use anyhow::{Context, Result as AnyhowResult};
-use rusqlite::Connection;
-use std::path::PathBuf;
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, ErrorCode, OptionalExtension};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Cursor keeps its privacy mode ("true"/"false") under this ItemTable key
+const PRIVACY_MODE_KEY: &str = "cursorai/donotchange/privacyMode";
+/// The WorkosCursorSessionToken cookie, which the dashboard requests use
+const SESSION_TOKEN_KEY: &str = "cursorAuth/sessionToken";
+
+/// The keys a login is made of, which `clear_auth` removes
+const AUTH_KEYS: &[&str] = &[
+    "cursorAuth/cachedEmail",
+    "cursorAuth/accessToken",
+    "cursorAuth/refreshToken",
+    "cursorAuth/cachedSignUpType",
+    SESSION_TOKEN_KEY,
+];
+
+/// Every key a switch writes
+const SWITCHED_KEYS: &[&str] = &[
+    "cursorAuth/cachedEmail",
+    "cursorAuth/accessToken",
+    "cursorAuth/refreshToken",
+    "cursorAuth/cachedSignUpType",
+    SESSION_TOKEN_KEY,
+    PRIVACY_MODE_KEY,
+];
+
+/// The login and privacy mode as they were before a switch, None for keys
+/// that weren't set
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthSnapshot {
+    rows: Vec<(&'static str, Option<SecretString>)>,
+}
+
+/// Cursor's state has no email or access token, e.g. after `clear_auth`
+#[derive(Debug, thiserror::Error)]
+#[error("No account is logged into Cursor")]
+pub struct NotLoggedIn;
+
+/// Cursor has no session cookie stored, e.g. as it was never logged into
+/// cursor.com and no switch brought one along
+#[derive(Debug, thiserror::Error)]
+#[error("Cursor has no session token stored")]
+pub struct SessionTokenNotFound;
+
+/// Cursor's state.vscdb isn't there, or isn't a database SQLite can open
+#[derive(Debug, thiserror::Error)]
+#[error("Cursor's state database at {} is missing or corrupt", .0.display())]
+pub struct StateDbUnavailable(pub PathBuf);
+
+/// A login key read back different from what `update_auth` wrote; the
+/// update was rolled back
+#[derive(Debug, thiserror::Error)]
+#[error("{key} didn't read back as written; Cursor's login was left as it was")]
+pub struct AuthWriteMismatch {
+    pub key: String,
+}
+
+/// How long to wait for Cursor to let go of its database. SQLite waits up
+/// to `busy_timeout` inside each attempt; a lock that outlasts it is retried
+/// `attempts` times in all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockRetry {
+    pub busy_timeout: Duration,
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+impl Default for LockRetry {
+    fn default() -> Self {
+        Self {
+            busy_timeout: Duration::from_secs(2),
+            attempts: 5,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Whether `error` is SQLite reporting the database busy or locked, as it
+/// does while Cursor still has it open
+pub fn is_lock_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<rusqlite::Error>()
+            .and_then(rusqlite::Error::sqlite_error_code)
+            .is_some_and(|code| {
+                matches!(
+                    code,
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                )
+            })
+    })
+}
 
 pub struct Database {
     path: PathBuf,
+    retry: LockRetry,
+}
+
+/// Cursor's own schema. It declares `value` a BLOB, and rows it wrote
+/// itself may hold one, so values are read as either.
+const ITEM_TABLE_SCHEMA: &str =
+    "CREATE TABLE IF NOT EXISTS ItemTable (key TEXT PRIMARY KEY, value BLOB)";
+
+fn read_item(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    Ok(conn
+        .query_row("SELECT value FROM ItemTable WHERE key = ?1", [key], |row| {
+            Ok(match row.get_ref(0)? {
+                ValueRef::Null => None,
+                ValueRef::Text(text) | ValueRef::Blob(text) => {
+                    Some(String::from_utf8_lossy(text).into_owned())
+                }
+                ValueRef::Integer(n) => Some(n.to_string()),
+                ValueRef::Real(n) => Some(n.to_string()),
+            })
+        })
+        .optional()?
+        .flatten())
+}
+
+// Write one key, noting what it held before; the read costs one indexed lookup
+fn write_item(
+    conn: &Connection,
+    path: &Path,
+    key: &str,
+    value: &str,
+) -> rusqlite::Result<KeyChange> {
+    let old = read_item(conn, key)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO ItemTable (key, value) VALUES (?1, ?2)",
+        [key, value],
+    )?;
+    Ok(KeyChange::of(path, key, old.as_deref(), Some(value)))
 }
 
 impl Database {
     pub fn new(db_path: PathBuf) -> Self {
-        Self { path: db_path }
+        Self {
+            path: db_path,
+            retry: LockRetry::default(),
+        }
     }
 
-    pub fn get_auth_info(&self) -> AnyhowResult<(String, String)> {
-        let conn = Connection::open(&self.path).context("Failed to open database")?;
+    pub fn with_lock_retry(mut self, retry: LockRetry) -> Self {
+        self.retry = retry;
+        self
+    }
 
-        // Get email from database (stored separately)
-        let email: String = conn
-            .query_row(
-                "SELECT value FROM ItemTable WHERE key = 'cursorAuth/cachedEmail'",
-                [],
-                |row| row.get(0),
-            )
-            .context("Failed to get email from database")?;
+    // Corrupt files only show when first read, so the first statement on a
+    // connection goes through this
+    fn unavailable_if_corrupt(&self, e: rusqlite::Error) -> anyhow::Error {
+        let corrupt = matches!(
+            e.sqlite_error_code(),
+            Some(ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt)
+        );
+        let e = anyhow::Error::from(e);
+        if corrupt {
+            e.context(StateDbUnavailable(self.path.clone()))
+        } else {
+            e
+        }
+    }
 
-        // Get access token from database
-        let access_token: String = conn
+    // For writes: a fresh install may have no database or an empty one yet
+    fn open(&self) -> AnyhowResult<Connection> {
+        let conn = Connection::open(&self.path)?;
+        conn.busy_timeout(self.retry.busy_timeout)?;
+        conn.execute_batch(ITEM_TABLE_SCHEMA)
+            .map_err(|e| self.unavailable_if_corrupt(e))?;
+        Ok(conn)
+    }
+
+    // For reads, which leave Cursor's file as it is: no database is an
+    // error, and None when it has no ItemTable yet, so nothing is stored
+    fn open_existing(&self) -> AnyhowResult<Option<Connection>> {
+        if !self.path.is_file() {
+            return Err(StateDbUnavailable(self.path.clone()).into());
+        }
+        let conn = Connection::open(&self.path)?;
+        conn.busy_timeout(self.retry.busy_timeout)?;
+        let has_table = conn
             .query_row(
-                "SELECT value FROM ItemTable WHERE key = 'cursorAuth/accessToken'",
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'ItemTable'",
                 [],
-                |row| row.get(0),
+                |_| Ok(()),
             )
-            .context("Failed to get access token")?;
+            .optional()
+            .map_err(|e| self.unavailable_if_corrupt(e))?
+            .is_some();
+        Ok(has_table.then_some(conn))
+    }
+
+    // Run `op` again while the database is locked, up to the attempts allowed;
+    // any other error is returned straight away
+    fn retry_locked<T>(&self, mut op: impl FnMut() -> AnyhowResult<T>) -> AnyhowResult<T> {
+        let mut attempt = 1;
+        loop {
+            match op() {
+                Err(e) if is_lock_error(&e) => {
+                    if attempt >= self.retry.attempts {
+                        return Err(e.context(
+                            "Cursor is still running or state.vscdb is locked; quit Cursor and try again",
+                        ));
+                    }
+                    tracing::warn!(
+                        "state.vscdb is locked, retrying ({}/{})",
+                        attempt,
+                        self.retry.attempts
+                    );
+                    std::thread::sleep(self.retry.backoff);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn get_auth_info(&self) -> AnyhowResult<(String, String)> {
+        self.retry_locked(|| self.read_auth_info())
+    }
+
+    fn read_auth_info(&self) -> AnyhowResult<(String, String)> {
+        let Some(conn) = self.open_existing().context("Failed to open database")? else {
+            return Err(NotLoggedIn.into());
+        };
 
-        Ok((email, access_token))
+        // Get email from database (stored separately)
+        let email = read_item(&conn, "cursorAuth/cachedEmail")
+            .context("Failed to get email from database")?;
+        // Get access token from database
+        let access_token =
+            read_item(&conn, "cursorAuth/accessToken").context("Failed to get access token")?;
+
+        match (email, access_token) {
+            (Some(email), Some(access_token)) if !email.is_empty() && !access_token.is_empty() => {
+                Ok((email, access_token))
+            }
+            _ => Err(NotLoggedIn.into()),
+        }
     }
 
+    /// Log in as `email`; returns every key written. All or nothing: the
+    /// keys are written and read back in one transaction.
     pub fn update_auth(
         &self,
         email: &str,
         access_token: &str,
         refresh_token: Option<&str>,
-    ) -> AnyhowResult<()> {
-        let conn = Connection::open(&self.path)
-            .context(format!("Failed to open database for user {}", email))?;
-
-        // Update email (stored separately from token)
-        conn.execute(
-            "INSERT OR REPLACE INTO ItemTable (key, value) VALUES ('cursorAuth/cachedEmail', ?1)",
-            [email],
-        )?;
+    ) -> AnyhowResult<Vec<KeyChange>> {
+        self.update_auth_full(email, access_token, refresh_token, None)
+    }
 
-        // Update access token
-        conn.execute(
-            "INSERT OR REPLACE INTO ItemTable (key, value) VALUES ('cursorAuth/accessToken', ?1)",
-            [access_token],
-        )?;
+    /// `update_auth`, also storing the session cookie so the dashboard
+    /// requests work without a web login. An empty or missing one leaves the
+    /// stored cookie as it is.
+    pub fn update_auth_full(
+        &self,
+        email: &str,
+        access_token: &str,
+        refresh_token: Option<&str>,
+        session_token: Option<&str>,
+    ) -> AnyhowResult<Vec<KeyChange>> {
+        let session_token = session_token
+            .map(str::trim)
+            .filter(|token| !token.is_empty());
+        self.retry_locked(|| self.write_auth(email, access_token, refresh_token, session_token))
+    }
 
-        // Update refresh token if provided
+    fn write_auth(
+        &self,
+        email: &str,
+        access_token: &str,
+        refresh_token: Option<&str>,
+        session_token: Option<&str>,
+    ) -> AnyhowResult<Vec<KeyChange>> {
+        let mut conn = self
+            .open()
+            .context(format!("Failed to open database for user {}", email))?;
+        // One transaction, so a failure part way keeps the old login whole
+        // instead of leaving one account's email with another's token
+        let tx = conn.transaction()?;
+
+        // The email is stored separately from the token; the refresh token
+        // and cookie only when there are some, and the signup type marks the
+        // login as done
+        let mut items = vec![
+            ("cursorAuth/cachedEmail", email),
+            ("cursorAuth/accessToken", access_token),
+        ];
         if let Some(refresh_token) = refresh_token {
-            conn.execute(
-                "INSERT OR REPLACE INTO ItemTable (key, value) VALUES ('cursorAuth/refreshToken', ?1)",
-                [refresh_token],
-            )?;
+            items.push(("cursorAuth/refreshToken", refresh_token));
         }
+        if let Some(session_token) = session_token {
+            items.push((SESSION_TOKEN_KEY, session_token));
+        }
+        items.push(("cursorAuth/cachedSignUpType", "Auth_0"));
 
-        // Set signup type (indicates authentication status)
-        conn.execute(
-            "INSERT OR REPLACE INTO ItemTable (key, value) VALUES ('cursorAuth/cachedSignUpType', ?1)",
-            ["Auth_0"],
-        )?;
+        let mut changes = Vec::with_capacity(SWITCHED_KEYS.len());
+        for (key, value) in &items {
+            changes.push(write_item(&tx, &self.path, key, value)?);
+        }
+        // Dropping the transaction on a mismatch rolls every write back
+        for (key, value) in &items {
+            if read_item(&tx, key)?.as_deref() != Some(*value) {
+                return Err(AuthWriteMismatch {
+                    key: key.to_string(),
+                }
+                .into());
+            }
+        }
+        tx.commit().context("Failed to save the new login")?;
+        file_cache::note_write(&self.path);
 
-        Ok(())
+        Ok(changes)
+    }
+
+    /// Log out of Cursor by removing the login keys; returns how many were
+    /// set. Logging out again removes nothing.
+    pub fn clear_auth(&self) -> AnyhowResult<usize> {
+        self.retry_locked(|| self.remove_auth())
+    }
+
+    fn remove_auth(&self) -> AnyhowResult<usize> {
+        let mut conn = self.open().context("Failed to open database")?;
+
+        let tx = conn.transaction()?;
+        let mut removed = 0;
+        for key in AUTH_KEYS {
+            removed += tx
+                .execute("DELETE FROM ItemTable WHERE key = ?1", [key])
+                .context("Failed to remove the login")?;
+        }
+        tx.commit().context("Failed to remove the login")?;
+        file_cache::note_write(&self.path);
+        Ok(removed)
     }
 
+    /// The session cookie; `SessionTokenNotFound` when Cursor has none
     pub fn get_session_token(&self) -> AnyhowResult<String> {
-        let conn = Connection::open(&self.path).context("Failed to open database")?;
+        let Some(conn) = self.open_existing().context("Failed to open database")? else {
+            return Err(SessionTokenNotFound.into());
+        };
 
         // Get session token (cookie) from database
-        let session_token: String = conn
-            .query_row(
-                "SELECT value FROM ItemTable WHERE key = 'cursorAuth/sessionToken'",
-                [],
-                |row| row.get(0),
+        read_item(&conn, SESSION_TOKEN_KEY)
+            .context("Failed to get session token from database")?
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| SessionTokenNotFound.into())
+    }
+
+    /// Refresh token Cursor stored at login, if any
+    pub fn get_refresh_token(&self) -> AnyhowResult<Option<String>> {
+        let Some(conn) = self.open_existing().context("Failed to open database")? else {
+            return Ok(None);
+        };
+
+        let refresh_token =
+            read_item(&conn, "cursorAuth/refreshToken").context("Failed to get refresh token")?;
+
+        Ok(refresh_token.filter(|token| !token.is_empty()))
+    }
+
+    /// Whether privacy mode is on; None when Cursor never stored the setting
+    pub fn get_privacy_mode(&self) -> AnyhowResult<Option<bool>> {
+        let Some(conn) = self.open_existing().context("Failed to open database")? else {
+            return Ok(None);
+        };
+
+        let value = read_item(&conn, PRIVACY_MODE_KEY).context("Failed to get privacy mode")?;
+
+        Ok(value.and_then(|value| value.trim().parse().ok()))
+    }
+
+    /// Returns the key written
+    pub fn set_privacy_mode(&self, enabled: bool) -> AnyhowResult<KeyChange> {
+        self.retry_locked(|| self.write_privacy_mode(enabled))
+    }
+
+    fn write_privacy_mode(&self, enabled: bool) -> AnyhowResult<KeyChange> {
+        let conn = self.open().context("Failed to open database")?;
+
+        let value = if enabled { "true" } else { "false" };
+        let change = write_item(&conn, &self.path, PRIVACY_MODE_KEY, value)
+            .context("Failed to set privacy mode")?;
+        file_cache::note_write(&self.path);
+        Ok(change)
+    }
+
+    /// Values of the `keys` that are set
+    pub fn get_items(&self, keys: &[&str]) -> AnyhowResult<BTreeMap<String, String>> {
+        let mut items = BTreeMap::new();
+        let Some(conn) = self.open_existing().context("Failed to open database")? else {
+            return Ok(items);
+        };
+
+        for key in keys {
+            let value = read_item(&conn, key).context("Failed to read Cursor's state")?;
+            if let Some(value) = value {
+                items.insert(key.to_string(), value);
+            }
+        }
+        Ok(items)
+    }
+
+    /// Write `items` in one transaction. Cursor keeps these in memory, so only
+    /// call this while it is closed.
+    pub fn set_items(&self, items: &BTreeMap<String, String>) -> AnyhowResult<()> {
+        self.retry_locked(|| self.write_items(items))
+    }
+
+    fn write_items(&self, items: &BTreeMap<String, String>) -> AnyhowResult<()> {
+        let mut conn = self.open().context("Failed to open database")?;
+
+        let tx = conn.transaction()?;
+        for (key, value) in items {
+            tx.execute(
+                "INSERT OR REPLACE INTO ItemTable (key, value) VALUES (?1, ?2)",
+                [key, value],
             )
-            .context("Failed to get session token from database")?;
+            .context("Failed to write Cursor's state")?;
+        }
+        tx.commit().context("Failed to write Cursor's state")?;
+        file_cache::note_write(&self.path);
+        Ok(())
+    }
+
+    /// Save what a switch is about to overwrite
+    pub fn snapshot_auth(&self) -> AnyhowResult<AuthSnapshot> {
+        self.retry_locked(|| self.read_snapshot())
+    }
+
+    fn read_snapshot(&self) -> AnyhowResult<AuthSnapshot> {
+        let conn = self.open().context("Failed to open database")?;
+
+        let mut rows = Vec::with_capacity(SWITCHED_KEYS.len());
+        for key in SWITCHED_KEYS {
+            let value = read_item(&conn, key).context("Failed to read the current login")?;
+            rows.push((*key, value.map(SecretString::from)));
+        }
+        Ok(AuthSnapshot { rows })
+    }
+
+    /// Put a snapshot back in one transaction, removing keys it didn't have
+    pub fn restore_auth(&self, snapshot: &AuthSnapshot) -> AnyhowResult<()> {
+        self.retry_locked(|| self.write_snapshot(snapshot))
+    }
 
-        Ok(session_token)
+    fn write_snapshot(&self, snapshot: &AuthSnapshot) -> AnyhowResult<()> {
+        let mut conn = self.open().context("Failed to open database")?;
+
+        let tx = conn.transaction()?;
+        for (key, value) in &snapshot.rows {
+            match value {
+                Some(value) => tx.execute(
+                    "INSERT OR REPLACE INTO ItemTable (key, value) VALUES (?1, ?2)",
+                    [*key, value.expose()],
+                ),
+                None => tx.execute("DELETE FROM ItemTable WHERE key = ?1", [key]),
+            }
+            .context("Failed to restore the previous login")?;
+        }
+        tx.commit()
+            .context("Failed to restore the previous login")?;
+        file_cache::note_write(&self.path);
+        Ok(())
     }
 }
 
@@ -93,6 +477,7 @@ impl Database {
 mod tests {
     use super::*;
     use rusqlite::Connection;
+    use std::fs;
 
     fn create_test_db() -> (Database, tempfile::TempDir) {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -186,7 +571,181 @@ mod tests {
 
         // Try to get auth info when no data exists
         let result = db.get_auth_info();
-        assert!(result.is_err());
+        assert!(result.unwrap_err().is::<NotLoggedIn>());
+    }
+
+    fn has_item_table(db_path: &Path) -> bool {
+        Connection::open(db_path)
+            .unwrap()
+            .query_row(
+                "SELECT count(*) FROM sqlite_master WHERE name = 'ItemTable'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap()
+            > 0
+    }
+
+    #[test]
+    fn test_only_writes_create_the_schema() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("state.vscdb");
+        let db = Database::new(db_path.clone());
+
+        // No file at all is not the same as nobody logged in
+        let missing = db.get_auth_info().unwrap_err();
+        assert!(missing.is::<StateDbUnavailable>());
+        assert!(!db_path.exists());
+
+        // An empty file, and a database without the table, read as logged
+        // out and are left without one
+        fs::write(&db_path, "").unwrap();
+        assert!(db.get_auth_info().unwrap_err().is::<NotLoggedIn>());
+        assert_eq!(fs::metadata(&db_path).unwrap().len(), 0);
+        fs::remove_file(&db_path).unwrap();
+        Connection::open(&db_path)
+            .unwrap()
+            .execute_batch("CREATE TABLE other (id INTEGER)")
+            .unwrap();
+        assert!(db.get_auth_info().unwrap_err().is::<NotLoggedIn>());
+        assert!(db
+            .get_session_token()
+            .unwrap_err()
+            .is::<SessionTokenNotFound>());
+        assert_eq!(db.get_refresh_token().unwrap(), None);
+        assert_eq!(db.get_privacy_mode().unwrap(), None);
+        assert!(db.get_items(SWITCHED_KEYS).unwrap().is_empty());
+        assert!(!has_item_table(&db_path));
+
+        // A write creates it
+        db.update_auth("a@x.com", "access", None).unwrap();
+        assert!(has_item_table(&db_path));
+        assert_eq!(db.get_auth_info().unwrap().0, "a@x.com");
+
+        fs::write(&db_path, "this is not a database, just some text").unwrap();
+        let corrupt = db.get_auth_info().unwrap_err();
+        assert!(corrupt.is::<StateDbUnavailable>());
+        assert!(db.update_auth("a@x.com", "access", None).is_err());
+    }
+
+    #[test]
+    fn test_blob_values_are_read_as_text() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("state.vscdb");
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(ITEM_TABLE_SCHEMA).unwrap();
+        for (key, value) in [
+            ("cursorAuth/cachedEmail", "blob@x.com"),
+            ("cursorAuth/accessToken", "blob_token"),
+            ("cursorAuth/refreshToken", "blob_refresh"),
+            (PRIVACY_MODE_KEY, "true"),
+        ] {
+            conn.execute(
+                "INSERT INTO ItemTable (key, value) VALUES (?1, ?2)",
+                rusqlite::params![key, value.as_bytes()],
+            )
+            .unwrap();
+        }
+
+        let db = Database::new(db_path);
+        assert_eq!(
+            db.get_auth_info().unwrap(),
+            ("blob@x.com".to_string(), "blob_token".to_string())
+        );
+        assert_eq!(
+            db.get_refresh_token().unwrap().as_deref(),
+            Some("blob_refresh")
+        );
+        assert_eq!(db.get_privacy_mode().unwrap(), Some(true));
+        // Overwriting a BLOB row is verified like any other
+        db.update_auth("text@x.com", "text_token", None).unwrap();
+        assert_eq!(db.get_auth_info().unwrap().0, "text@x.com");
+    }
+
+    #[test]
+    fn test_locked_database_is_retried_then_reported() {
+        let (db, _temp_dir) = create_test_db();
+        db.update_auth("a@x.com", "access", None).unwrap();
+        let retry = LockRetry {
+            busy_timeout: Duration::from_millis(10),
+            attempts: 3,
+            backoff: Duration::from_millis(10),
+        };
+        let db = db.with_lock_retry(retry);
+
+        // As Cursor does while it is still running
+        let holder = Connection::open(&db.path).unwrap();
+        holder.execute_batch("BEGIN EXCLUSIVE").unwrap();
+
+        let error = db.update_auth("b@x.com", "other", None).unwrap_err();
+        assert!(is_lock_error(&error));
+        assert!(error.to_string().contains("Cursor is still running"));
+        assert!(is_lock_error(&db.get_auth_info().unwrap_err()));
+        assert!(!is_lock_error(&anyhow::Error::from(NotLoggedIn)));
+
+        holder.execute_batch("COMMIT").unwrap();
+        assert_eq!(db.get_auth_info().unwrap().0, "a@x.com");
+        db.update_auth("b@x.com", "other", None).unwrap();
+        assert_eq!(db.get_auth_info().unwrap().0, "b@x.com");
+    }
+
+    #[test]
+    fn test_every_write_retries_a_locked_database() {
+        let (db, _temp_dir) = create_test_db();
+        db.update_auth("a@x.com", "access", None).unwrap();
+        let snapshot = db.snapshot_auth().unwrap();
+        let db = db.with_lock_retry(LockRetry {
+            busy_timeout: Duration::from_millis(10),
+            attempts: 2,
+            backoff: Duration::from_millis(10),
+        });
+        let items = BTreeMap::from([("k".to_string(), "v".to_string())]);
+
+        let holder = Connection::open(&db.path).unwrap();
+        holder.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let errors = [
+            db.clear_auth().map(drop).unwrap_err(),
+            db.set_privacy_mode(true).map(drop).unwrap_err(),
+            db.set_items(&items).unwrap_err(),
+            db.snapshot_auth().map(drop).unwrap_err(),
+            db.restore_auth(&snapshot).unwrap_err(),
+        ];
+        for error in &errors {
+            assert!(error.to_string().contains("Cursor is still running"));
+        }
+
+        // Released while the retries still wait
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            holder.execute_batch("COMMIT").unwrap();
+        });
+        let db = db.with_lock_retry(LockRetry {
+            busy_timeout: Duration::from_millis(10),
+            attempts: 50,
+            backoff: Duration::from_millis(10),
+        });
+        db.set_items(&items).unwrap();
+        release.join().unwrap();
+        db.restore_auth(&snapshot).unwrap();
+        assert_eq!(db.get_items(&["k"]).unwrap()["k"], "v");
+    }
+
+    #[test]
+    fn test_clear_auth() {
+        let (db, _temp_dir) = create_test_db();
+        db.update_auth("a@x.com", "access", Some("refresh"))
+            .unwrap();
+        db.set_privacy_mode(true).unwrap();
+
+        assert_eq!(db.clear_auth().unwrap(), 4);
+        let left = db.get_items(AUTH_KEYS).unwrap();
+        assert!(left.is_empty(), "{:?}", left.keys());
+        assert!(db.get_auth_info().unwrap_err().is::<NotLoggedIn>());
+        // Only the login goes
+        assert_eq!(db.get_privacy_mode().unwrap(), Some(true));
+
+        assert_eq!(db.clear_auth().unwrap(), 0);
+        assert!(db.get_auth_info().unwrap_err().is::<NotLoggedIn>());
     }
 
     #[test]
@@ -219,6 +778,30 @@ mod tests {
         assert!(refresh_result.is_err() || refresh_result.unwrap().is_empty());
     }
 
+    #[test]
+    fn test_update_auth_full_writes_the_session_token() {
+        let (db, _temp_dir) = create_test_db();
+        assert!(db
+            .get_session_token()
+            .unwrap_err()
+            .is::<SessionTokenNotFound>());
+
+        let cookie = "user_abc::eyJhbGciOiJIUzI1NiJ9.e30.sig";
+        let changes = db
+            .update_auth_full("a@x.com", "access_a", None, Some(cookie))
+            .unwrap();
+        assert!(changes.iter().any(|change| change.key == SESSION_TOKEN_KEY));
+        assert_eq!(db.get_session_token().unwrap(), cookie);
+
+        // No cookie to write keeps the one Cursor has
+        for empty in [Some(""), Some("  "), None] {
+            db.update_auth_full("b@x.com", "access_b", None, empty)
+                .unwrap();
+            assert_eq!(db.get_session_token().unwrap(), cookie);
+        }
+        assert_eq!(db.get_auth_info().unwrap().0, "b@x.com");
+    }
+
     #[test]
     fn test_update_auth_sets_signup_type() {
         let (db, _temp_dir) = create_test_db();
@@ -236,6 +819,38 @@ mod tests {
         assert_eq!(signup_type, "Auth_0");
     }
 
+    #[test]
+    fn test_update_auth_is_verified_before_commit() {
+        let (db, _temp_dir) = create_test_db();
+        db.update_auth("a@x.com", "token_a", Some("refresh_a"))
+            .unwrap();
+
+        // Something else rewrites the token as it is written
+        Connection::open(&db.path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TRIGGER doctor AFTER INSERT ON ItemTable \
+                 WHEN NEW.key = 'cursorAuth/accessToken' AND NEW.value = 'token_b' \
+                 BEGIN UPDATE ItemTable SET value = 'doctored' WHERE key = NEW.key; END;",
+            )
+            .unwrap();
+        let error = db
+            .update_auth("b@x.com", "token_b", Some("refresh_b"))
+            .unwrap_err();
+        let mismatch = error.downcast_ref::<AuthWriteMismatch>().unwrap();
+        assert_eq!(mismatch.key, "cursorAuth/accessToken");
+
+        // The email written before the token was rolled back with it
+        assert_eq!(
+            db.get_auth_info().unwrap(),
+            ("a@x.com".to_string(), "token_a".to_string())
+        );
+        assert_eq!(
+            db.get_refresh_token().unwrap().as_deref(),
+            Some("refresh_a")
+        );
+    }
+
     #[test]
     fn test_update_auth_replaces_existing() {
         let (db, _temp_dir) = create_test_db();
@@ -245,12 +860,72 @@ mod tests {
             .unwrap();
 
         // Update with new data
-        db.update_auth("second@example.com", "second_token", Some("second_refresh"))
+        let changes = db
+            .update_auth("second@example.com", "second_token", Some("second_refresh"))
             .unwrap();
 
         // Verify it was replaced, not duplicated
         let (email, token) = db.get_auth_info().unwrap();
         assert_eq!(email, "second@example.com");
         assert_eq!(token, "second_token");
+
+        // Each written key is recorded by hash only
+        let token_change = changes
+            .iter()
+            .find(|change| change.key == "cursorAuth/accessToken")
+            .unwrap();
+        assert_eq!(token_change.file, db.path);
+        assert_eq!(
+            token_change.new_hash,
+            Some(crate::change_set::value_hash("second_token"))
+        );
+        assert!(token_change.is_change());
+        let signup = changes
+            .iter()
+            .find(|change| change.key == "cursorAuth/cachedSignUpType")
+            .unwrap();
+        assert!(!signup.is_change());
+        assert_eq!(changes.len(), 4);
+    }
+
+    #[test]
+    fn test_privacy_mode_round_trip() {
+        let (db, _temp_dir) = create_test_db();
+        assert_eq!(db.get_privacy_mode().unwrap(), None);
+
+        db.set_privacy_mode(true).unwrap();
+        assert_eq!(db.get_privacy_mode().unwrap(), Some(true));
+        db.set_privacy_mode(false).unwrap();
+        assert_eq!(db.get_privacy_mode().unwrap(), Some(false));
+
+        // Values Cursor might write in another shape are treated as unknown
+        let conn = Connection::open(&db.path).unwrap();
+        conn.execute(
+            "UPDATE ItemTable SET value = '{}' WHERE key = ?1",
+            [PRIVACY_MODE_KEY],
+        )
+        .unwrap();
+        assert_eq!(db.get_privacy_mode().unwrap(), None);
+    }
+
+    #[test]
+    fn test_restore_auth_snapshot() {
+        let (db, _temp_dir) = create_test_db();
+        db.update_auth("first@example.com", "first_token", None)
+            .unwrap();
+        let snapshot = db.snapshot_auth().unwrap();
+
+        db.update_auth("second@example.com", "second_token", Some("second_refresh"))
+            .unwrap();
+        db.set_privacy_mode(true).unwrap();
+        db.restore_auth(&snapshot).unwrap();
+
+        assert_eq!(
+            db.get_auth_info().unwrap(),
+            ("first@example.com".to_string(), "first_token".to_string())
+        );
+        // Keys the snapshot didn't have are removed again
+        assert_eq!(db.get_refresh_token().unwrap(), None);
+        assert_eq!(db.get_privacy_mode().unwrap(), None);
     }
 }
@@ -0,0 +1,220 @@
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::path::Path;
+use url::Url;
+
+/// URL scheme handled by the app, e.g. `cursor-switcher://switch?email=bob@x.com`
+pub const SCHEME: &str = "cursor-switcher";
+
+/// Action requested by a clicked link
+#[derive(Clone, PartialEq)]
+pub enum DeepLink {
+    Import { token: String },
+    Switch { email: String },
+}
+
+// Tokens must never end up in logs, so Debug output redacts them
+impl fmt::Debug for DeepLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeepLink::Import { .. } => f
+                .debug_struct("Import")
+                .field("token", &"<redacted>")
+                .finish(),
+            DeepLink::Switch { email } => f.debug_struct("Switch").field("email", email).finish(),
+        }
+    }
+}
+
+impl DeepLink {
+    /// Parse a `cursor-switcher://` URL. Error messages never contain query values.
+    pub fn parse(link: &str) -> Result<Self> {
+        let url = Url::parse(link.trim()).map_err(|_| anyhow::anyhow!("Invalid link"))?;
+
+        if !url.scheme().eq_ignore_ascii_case(SCHEME) {
+            bail!("Unsupported link scheme: {}", url.scheme());
+        }
+
+        // Accept both cursor-switcher://import?... and cursor-switcher:import?...
+        let action = match url.host_str() {
+            Some(host) if !host.is_empty() => host.to_string(),
+            _ => url.path().trim_matches('/').to_string(),
+        };
+
+        let param = |name: &str| -> Option<String> {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        match action.to_ascii_lowercase().as_str() {
+            "import" => {
+                let token = param("token").context("Import link is missing the token")?;
+                Ok(DeepLink::Import { token })
+            }
+            "switch" => {
+                let email = param("email").context("Switch link is missing the email")?;
+                Ok(DeepLink::Switch { email })
+            }
+            _ => bail!("Unknown link action: {}", action),
+        }
+    }
+
+    pub fn action(&self) -> &'static str {
+        match self {
+            DeepLink::Import { .. } => "import",
+            DeepLink::Switch { .. } => "switch",
+        }
+    }
+}
+
+/// Find a link among the process arguments (the OS passes it as an argument)
+pub fn find_in_args<I, S>(args: I) -> Option<String>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let prefix = format!("{}:", SCHEME);
+    args.into_iter()
+        .map(Into::into)
+        .find(|arg| arg.to_ascii_lowercase().starts_with(&prefix))
+}
+
+/// Register the app as handler of the URL scheme for the current user.
+/// On macOS the scheme is declared in the bundle's Info.plist instead; macOS
+/// delivers URLs as Apple Events rather than arguments, which Tauri 1 doesn't
+/// expose, so links only reach the app there when it is launched with them.
+#[cfg(target_os = "windows")]
+pub fn register_scheme(app_name: &str, exe_path: &Path) -> Result<()> {
+    use winreg::enums::HKEY_CURRENT_USER;
+    use winreg::RegKey;
+
+    let classes = RegKey::predef(HKEY_CURRENT_USER);
+    let (key, _) = classes
+        .create_subkey(format!(r"Software\Classes\{}", SCHEME))
+        .context("Failed to create the URL scheme registry key")?;
+    key.set_value("", &format!("URL:{}", app_name))?;
+    key.set_value("URL Protocol", &"")?;
+
+    let (command, _) = key
+        .create_subkey(r"shell\open\command")
+        .context("Failed to create the URL scheme command key")?;
+    command.set_value("", &format!("\"{}\" \"%1\"", exe_path.display()))?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn register_scheme(app_name: &str, exe_path: &Path) -> Result<()> {
+    let file_name = format!("{}-handler.desktop", SCHEME);
+    let applications_dir = dirs::data_dir()
+        .context("Failed to resolve data directory")?
+        .join("applications");
+    std::fs::create_dir_all(&applications_dir)
+        .context("Failed to create applications directory")?;
+
+    let entry = handler_desktop_entry(app_name, exe_path);
+    let path = applications_dir.join(&file_name);
+    if std::fs::read_to_string(&path).ok().as_deref() == Some(entry.as_str()) {
+        return Ok(());
+    }
+    std::fs::write(&path, entry).context("Failed to write URL handler entry")?;
+
+    // Not every desktop has xdg-mime; the MimeType entry still works for most of them
+    let scheme_type = format!("x-scheme-handler/{}", SCHEME);
+    if let Err(e) = std::process::Command::new("xdg-mime")
+        .args(["default", &file_name, &scheme_type])
+        .output()
+    {
+        tracing::debug!("xdg-mime not available: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+pub fn register_scheme(_app_name: &str, _exe_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Hidden .desktop entry that maps the scheme to the app (Linux)
+#[cfg(any(target_os = "linux", test))]
+fn handler_desktop_entry(app_name: &str, exe_path: &Path) -> String {
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name={}\n\
+         Exec={} %u\n\
+         Terminal=false\n\
+         NoDisplay=true\n\
+         MimeType=x-scheme-handler/{};\n",
+        app_name,
+        crate::autostart::quote_desktop_exec(exe_path),
+        SCHEME
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_import_link() {
+        let link =
+            DeepLink::parse("cursor-switcher://import?token=user_01%3A%3AeyJhbGci.abc").unwrap();
+        assert_eq!(
+            link,
+            DeepLink::Import {
+                token: "user_01::eyJhbGci.abc".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_switch_link() {
+        let link = DeepLink::parse("cursor-switcher://switch?email=bob%40x.com").unwrap();
+        assert_eq!(
+            link,
+            DeepLink::Switch {
+                email: "bob@x.com".to_string()
+            }
+        );
+
+        let link = DeepLink::parse("cursor-switcher:switch?email=bob@x.com").unwrap();
+        assert_eq!(link.action(), "switch");
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_links() {
+        assert!(DeepLink::parse("cursor-switcher://delete?email=bob@x.com").is_err());
+        assert!(DeepLink::parse("https://import?token=abc").is_err());
+        assert!(DeepLink::parse("cursor-switcher://import").is_err());
+        assert!(DeepLink::parse("cursor-switcher://switch?email=").is_err());
+        assert!(DeepLink::parse("not a link").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors_and_debug_hide_token() {
+        let error = DeepLink::parse("cursor-switcher://bogus?token=secret123").unwrap_err();
+        assert!(!error.to_string().contains("secret123"));
+
+        let link = DeepLink::parse("cursor-switcher://import?token=secret123").unwrap();
+        assert!(!format!("{:?}", link).contains("secret123"));
+    }
+
+    #[test]
+    fn test_find_in_args() {
+        let args = ["/usr/bin/app", "cursor-switcher://switch?email=a@b.c"];
+        assert_eq!(
+            find_in_args(args),
+            Some("cursor-switcher://switch?email=a@b.c".to_string())
+        );
+        assert_eq!(find_in_args(["/usr/bin/app", "--list"]), None);
+    }
+
+    #[test]
+    fn test_handler_desktop_entry() {
+        let entry = handler_desktop_entry("Cursor Account Switcher", Path::new("/opt/app/bin"));
+        assert!(entry.contains("Exec=\"/opt/app/bin\" %u\n"));
+        assert!(entry.contains("MimeType=x-scheme-handler/cursor-switcher;\n"));
+    }
+}
@@ -1,9 +1,8 @@
+use crate::http::{HttpClient, HttpClientFactory};
 use crate::types::{BillingCycle, DetailedUserInfo};
 use anyhow::{Context, Result};
-use reqwest::blocking::Client;
 use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, COOKIE, ORIGIN, REFERER, USER_AGENT};
 use serde_json::Value;
-use std::time::Duration;
 
 const USAGE_EVENTS_URL: &str = "https://cursor.com/api/dashboard/get-filtered-usage-events";
 const GET_ME_URL: &str = "https://cursor.com/api/dashboard/get-me";
@@ -11,21 +10,25 @@ const LIST_INVOICES_URL: &str = "https://cursor.com/api/dashboard/list-invoices"
 const CURRENT_BILLING_CYCLE_URL: &str =
     "https://cursor.com/api/dashboard/get-current-billing-cycle";
 
+/// Dashboard API client. Every call takes the account's extra headers from
+/// `http::extra_headers`.
 pub struct DetailedUsageClient {
-    client: Client,
+    client: HttpClient,
 }
 
 impl DetailedUsageClient {
-    pub fn new() -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
-
-        Self { client }
+    pub fn new(http: &HttpClientFactory) -> Result<Self> {
+        Ok(Self {
+            client: http.client()?,
+        })
     }
 
-    fn create_headers(&self, session_token: &str, referer: &str) -> Result<HeaderMap> {
+    fn create_headers(
+        &self,
+        session_token: &str,
+        referer: &str,
+        extra: &HeaderMap,
+    ) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
         headers.insert(
             USER_AGENT,
@@ -39,14 +42,18 @@ impl DetailedUsageClient {
 
         let cookie_value = format!("WorkosCursorSessionToken={}", session_token);
         headers.insert(COOKIE, HeaderValue::from_str(&cookie_value)?);
+        headers.extend(extra.clone());
 
         Ok(headers)
     }
 
     /// Get filtered usage events
-    pub fn get_usage_events(&self, session_token: &str) -> Result<Value> {
-        let headers =
-            self.create_headers(session_token, "https://cursor.com/cn/dashboard?tab=usage")?;
+    pub fn get_usage_events(&self, session_token: &str, extra: &HeaderMap) -> Result<Value> {
+        let headers = self.create_headers(
+            session_token,
+            "https://cursor.com/cn/dashboard?tab=usage",
+            extra,
+        )?;
 
         let body = serde_json::json!({});
 
@@ -54,10 +61,12 @@ impl DetailedUsageClient {
 
         let response = self
             .client
-            .post(USAGE_EVENTS_URL)
-            .headers(headers)
-            .json(&body)
-            .send()
+            .send(
+                self.client
+                    .post(USAGE_EVENTS_URL)
+                    .headers(headers)
+                    .json(&body),
+            )
             .context("Failed to fetch usage events")?;
 
         if !response.status().is_success() {
@@ -72,9 +81,16 @@ impl DetailedUsageClient {
     }
 
     /// Get detailed user info (get-me endpoint)
-    pub fn get_detailed_user_info(&self, session_token: &str) -> Result<DetailedUserInfo> {
-        let headers =
-            self.create_headers(session_token, "https://cursor.com/cn/dashboard?tab=billing")?;
+    pub fn get_detailed_user_info(
+        &self,
+        session_token: &str,
+        extra: &HeaderMap,
+    ) -> Result<DetailedUserInfo> {
+        let headers = self.create_headers(
+            session_token,
+            "https://cursor.com/cn/dashboard?tab=billing",
+            extra,
+        )?;
 
         let body = serde_json::json!({});
 
@@ -82,10 +98,7 @@ impl DetailedUsageClient {
 
         let response = self
             .client
-            .post(GET_ME_URL)
-            .headers(headers)
-            .json(&body)
-            .send()
+            .send(self.client.post(GET_ME_URL).headers(headers).json(&body))
             .context("Failed to fetch user info")?;
 
         if !response.status().is_success() {
@@ -118,9 +131,12 @@ impl DetailedUsageClient {
     }
 
     /// List invoices
-    pub fn list_invoices(&self, session_token: &str) -> Result<Value> {
-        let headers =
-            self.create_headers(session_token, "https://cursor.com/cn/dashboard?tab=billing")?;
+    pub fn list_invoices(&self, session_token: &str, extra: &HeaderMap) -> Result<Value> {
+        let headers = self.create_headers(
+            session_token,
+            "https://cursor.com/cn/dashboard?tab=billing",
+            extra,
+        )?;
 
         let body = serde_json::json!({
             "teamId": 0,
@@ -132,10 +148,12 @@ impl DetailedUsageClient {
 
         let response = self
             .client
-            .post(LIST_INVOICES_URL)
-            .headers(headers)
-            .json(&body)
-            .send()
+            .send(
+                self.client
+                    .post(LIST_INVOICES_URL)
+                    .headers(headers)
+                    .json(&body),
+            )
             .context("Failed to fetch invoices")?;
 
         if !response.status().is_success() {
@@ -150,9 +168,16 @@ impl DetailedUsageClient {
     }
 
     /// Get current billing cycle
-    pub fn get_billing_cycle(&self, session_token: &str) -> Result<BillingCycle> {
-        let headers =
-            self.create_headers(session_token, "https://cursor.com/cn/dashboard?tab=usage")?;
+    pub fn get_billing_cycle(
+        &self,
+        session_token: &str,
+        extra: &HeaderMap,
+    ) -> Result<BillingCycle> {
+        let headers = self.create_headers(
+            session_token,
+            "https://cursor.com/cn/dashboard?tab=usage",
+            extra,
+        )?;
 
         let body = serde_json::json!({});
 
@@ -160,10 +185,12 @@ impl DetailedUsageClient {
 
         let response = self
             .client
-            .post(CURRENT_BILLING_CYCLE_URL)
-            .headers(headers)
-            .json(&body)
-            .send()
+            .send(
+                self.client
+                    .post(CURRENT_BILLING_CYCLE_URL)
+                    .headers(headers)
+                    .json(&body),
+            )
             .context("Failed to fetch billing cycle")?;
 
         if !response.status().is_success() {
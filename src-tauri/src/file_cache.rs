@@ -0,0 +1,181 @@
+use crate::state::MutexExt;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::SystemTime;
+
+// How often this process wrote each file, see `note_write`
+fn write_counts() -> MutexGuard<'static, HashMap<PathBuf, u64>> {
+    static WRITES: OnceLock<Mutex<HashMap<PathBuf, u64>>> = OnceLock::new();
+    WRITES.get_or_init(Default::default).lock_or_recover()
+}
+
+/// Mark values read from `path` stale. Two writes within the timestamp
+/// resolution of the file system can leave the same size and modification
+/// time, so the stores call this once each of their writes is done.
+pub fn note_write(path: &Path) {
+    *write_counts().entry(path.to_path_buf()).or_default() += 1;
+}
+
+/// Identity, size and modification time of each file, None for a missing
+/// one, and how often the app wrote it. Checking it costs a `stat` per file
+/// instead of parsing or opening them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStamp {
+    files: Vec<Option<(FileId, u64, SystemTime)>>,
+    writes: Vec<u64>,
+}
+
+impl FileStamp {
+    pub fn of(paths: &[PathBuf]) -> Self {
+        // Counted first, so a write finishing while the files are checked
+        // leaves the stamp stale rather than the value
+        let writes = {
+            let counts = write_counts();
+            paths
+                .iter()
+                .map(|path| counts.get(path).copied().unwrap_or_default())
+                .collect()
+        };
+        Self {
+            files: paths.iter().map(|path| stamp(path)).collect(),
+            writes,
+        }
+    }
+}
+
+// Tells a file renamed over another apart from the one it replaced, as the
+// stores' atomic writes do, even when both have the same size and time
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = Option<SystemTime>;
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> FileId {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(metadata: &fs::Metadata) -> FileId {
+    metadata.created().ok()
+}
+
+fn stamp(path: &Path) -> Option<(FileId, u64, SystemTime)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((
+        file_id(&metadata),
+        metadata.len(),
+        metadata.modified().ok()?,
+    ))
+}
+
+/// A value read from files, loaded again only once they change on disk
+pub struct FileCache<T> {
+    entry: Mutex<Option<(Vec<PathBuf>, FileStamp, T)>>,
+}
+
+impl<T> Default for FileCache<T> {
+    fn default() -> Self {
+        Self {
+            entry: Mutex::new(None),
+        }
+    }
+}
+
+impl<T: Clone> FileCache<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached value if `paths` are the ones it was read from and none of
+    /// them changed since, otherwise the result of `load`. Failed loads are
+    /// not cached. The lock is not held while loading.
+    pub fn get_or_load(&self, paths: &[PathBuf], load: impl FnOnce() -> Result<T>) -> Result<T> {
+        let stamp = FileStamp::of(paths);
+        if let Some((cached_paths, cached_stamp, value)) = self.entry.lock_or_recover().as_ref() {
+            if cached_paths == paths && *cached_stamp == stamp {
+                return Ok(value.clone());
+            }
+        }
+
+        let value = load()?;
+        *self.entry.lock_or_recover() = Some((paths.to_vec(), stamp, value.clone()));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_reloads_only_after_the_file_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("accounts.csv");
+        let paths = [path.clone()];
+        fs::write(&path, "a").unwrap();
+
+        let cache = FileCache::new();
+        let loads = Cell::new(0);
+        let read = || {
+            loads.set(loads.get() + 1);
+            Ok(fs::read_to_string(&path)?)
+        };
+
+        assert_eq!(cache.get_or_load(&paths, read).unwrap(), "a");
+        assert_eq!(cache.get_or_load(&paths, read).unwrap(), "a");
+        assert_eq!(loads.get(), 1);
+
+        fs::write(&path, "ab").unwrap();
+        assert_eq!(cache.get_or_load(&paths, read).unwrap(), "ab");
+        assert_eq!(loads.get(), 2);
+
+        // A file appearing or disappearing counts as a change too
+        fs::remove_file(&path).unwrap();
+        assert!(cache.get_or_load(&paths, read).is_err());
+        assert_eq!(loads.get(), 3);
+    }
+
+    #[test]
+    fn test_same_size_and_time_still_reloads() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("accounts.csv");
+        let paths = [path.clone()];
+        fs::write(&path, "a").unwrap();
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+        let cache = FileCache::new();
+        let read = || Ok(fs::read_to_string(&path)?);
+        assert_eq!(cache.get_or_load(&paths, read).unwrap(), "a");
+
+        // Renamed over it, as the stores write, with the old time put back
+        let temp_path = temp_dir.path().join("accounts.csv.tmp");
+        fs::write(&temp_path, "b").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&temp_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        fs::rename(&temp_path, &path).unwrap();
+        // Elsewhere the replacement can keep the old file's creation time
+        #[cfg(unix)]
+        assert_eq!(cache.get_or_load(&paths, read).unwrap(), "b");
+
+        // Written in place within the same tick, then noted
+        fs::write(&path, "c").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        note_write(&path);
+        assert_eq!(cache.get_or_load(&paths, read).unwrap(), "c");
+    }
+}
@@ -0,0 +1,273 @@
+use crate::usage_history::UsageSnapshot;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+/// Days of snapshots a forecast looks back over unless asked otherwise
+pub const DEFAULT_WINDOW_DAYS: u32 = 14;
+/// Fewest snapshots a burn rate is fitted to
+const MIN_SNAPSHOTS: usize = 3;
+/// Usage must have moved within this many days for a forecast
+const IDLE_DAYS: i64 = 3;
+/// Remaining dollars going up by more than this means the quota was reset
+const RESET_THRESHOLD: f64 = 0.01;
+/// Width of the confidence band in standard errors of the burn rate
+const BAND_ERRORS: f64 = 2.0;
+
+/// Why no date could be projected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InsufficientReason {
+    /// Fewer than three snapshots since the window start or the last reset
+    TooFewSnapshots,
+    /// Nothing was used lately, so any date would be made up
+    NoRecentUsage,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Forecast {
+    Projected {
+        exhausts_at: DateTime<Utc>,
+        /// Confidence band around `exhausts_at`; `latest` is None when usage
+        /// may as well be flat
+        earliest: DateTime<Utc>,
+        latest: Option<DateTime<Utc>>,
+        dollars_per_day: f64,
+        remaining: f64,
+        /// Snapshots the rate was fitted to
+        snapshots: usize,
+    },
+    InsufficientData {
+        reason: InsufficientReason,
+    },
+}
+
+impl Forecast {
+    fn insufficient(reason: InsufficientReason) -> Self {
+        Self::InsufficientData { reason }
+    }
+
+    pub fn exhausts_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Projected { exhausts_at, .. } => Some(*exhausts_at),
+            Self::InsufficientData { .. } => None,
+        }
+    }
+}
+
+/// Returned by `forecast_account` and, sorted, by `forecast_all`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountForecast {
+    pub email: String,
+    #[serde(flatten)]
+    pub forecast: Forecast,
+}
+
+/// Fit a linear burn rate to `history`, oldest first, and project when the
+/// remaining quota reaches zero. Only snapshots since the last quota reset
+/// count, detected by the remaining amount going up.
+pub fn forecast(history: &[UsageSnapshot]) -> Forecast {
+    let start = history
+        .windows(2)
+        .rposition(|pair| pair[1].remaining > pair[0].remaining + RESET_THRESHOLD)
+        .map_or(0, |i| i + 1);
+    let period = &history[start..];
+    let Some(last) = period.last().filter(|_| period.len() >= MIN_SNAPSHOTS) else {
+        return Forecast::insufficient(InsufficientReason::TooFewSnapshots);
+    };
+
+    // Compare with the newest snapshot from before the idle window, if any
+    let idle_since = last.taken_at - Duration::days(IDLE_DAYS);
+    let before_idle = period
+        .iter()
+        .rev()
+        .find(|s| s.taken_at <= idle_since)
+        .unwrap_or(&period[0]);
+    if before_idle.remaining - last.remaining <= RESET_THRESHOLD {
+        return Forecast::insufficient(InsufficientReason::NoRecentUsage);
+    }
+
+    let days: Vec<f64> = period
+        .iter()
+        .map(|s| (s.taken_at - period[0].taken_at).num_seconds() as f64 / 86_400.0)
+        .collect();
+    let remaining: Vec<f64> = period.iter().map(|s| s.remaining).collect();
+    let Some(fit) = LinearFit::of(&days, &remaining) else {
+        return Forecast::insufficient(InsufficientReason::TooFewSnapshots);
+    };
+    let burn = -fit.slope;
+    if burn <= 0.0 {
+        return Forecast::insufficient(InsufficientReason::NoRecentUsage);
+    }
+
+    let after =
+        |rate: f64| last.taken_at + Duration::seconds((last.remaining / rate * 86_400.0) as i64);
+    let band = BAND_ERRORS * fit.slope_error;
+    Forecast::Projected {
+        exhausts_at: after(burn),
+        earliest: after(burn + band),
+        latest: (burn - band > 0.0).then(|| after(burn - band)),
+        dollars_per_day: burn,
+        remaining: last.remaining,
+        snapshots: period.len(),
+    }
+}
+
+/// Sort soonest exhaustion first; accounts without a forecast go last
+pub fn sort_by_exhaustion(forecasts: &mut [AccountForecast]) {
+    forecasts.sort_by_key(|f| (f.forecast.exhausts_at().is_none(), f.forecast.exhausts_at()));
+}
+
+/// Least squares line through the points, with the standard error of its slope
+struct LinearFit {
+    slope: f64,
+    slope_error: f64,
+}
+
+impl LinearFit {
+    fn of(x: &[f64], y: &[f64]) -> Option<Self> {
+        let n = x.len() as f64;
+        let mean_x = x.iter().sum::<f64>() / n;
+        let mean_y = y.iter().sum::<f64>() / n;
+        let sxx: f64 = x.iter().map(|x| (x - mean_x).powi(2)).sum();
+        if sxx <= f64::EPSILON {
+            // All snapshots at the same moment
+            return None;
+        }
+        let sxy: f64 = x
+            .iter()
+            .zip(y)
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let slope = sxy / sxx;
+        let intercept = mean_y - slope * mean_x;
+        let residuals: f64 = x
+            .iter()
+            .zip(y)
+            .map(|(x, y)| (y - (intercept + slope * x)).powi(2))
+            .sum();
+        let slope_error = (residuals / (n - 2.0) / sxx).sqrt();
+        Some(Self { slope, slope_error })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn history(points: &[(f64, f64)]) -> Vec<UsageSnapshot> {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        points
+            .iter()
+            .map(|&(day, remaining)| UsageSnapshot {
+                email: "a@x.com".to_string(),
+                taken_at: start + Duration::hours((day * 24.0) as i64),
+                used: 20.0 - remaining,
+                remaining,
+                total: 20.0,
+            })
+            .collect()
+    }
+
+    fn day(day: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap() + Duration::days(day)
+    }
+
+    #[test]
+    fn test_steady_burn_projects_exact_date() {
+        // $1 a day from $20: $14 left on day 6, so empty on day 20
+        let steady: Vec<(f64, f64)> = (0..7).map(|d| (d as f64, 20.0 - d as f64)).collect();
+        let Forecast::Projected {
+            exhausts_at,
+            earliest,
+            latest,
+            dollars_per_day,
+            remaining,
+            snapshots,
+        } = forecast(&history(&steady))
+        else {
+            panic!("expected a projection");
+        };
+        assert!((dollars_per_day - 1.0).abs() < 1e-9);
+        assert_eq!(remaining, 14.0);
+        assert_eq!(snapshots, 7);
+        assert_eq!(exhausts_at, day(20));
+        // A perfect fit leaves no uncertainty
+        assert_eq!(earliest, exhausts_at);
+        assert_eq!(latest, Some(exhausts_at));
+    }
+
+    #[test]
+    fn test_noisy_burn_has_a_band_around_the_date() {
+        let noisy = [
+            (0.0, 20.0),
+            (1.0, 18.5),
+            (2.0, 18.2),
+            (3.0, 16.4),
+            (4.0, 16.0),
+        ];
+        let projected = forecast(&history(&noisy));
+        let Forecast::Projected {
+            exhausts_at,
+            earliest,
+            latest,
+            ..
+        } = projected
+        else {
+            panic!("expected a projection");
+        };
+        assert!(earliest < exhausts_at);
+        assert!(latest.unwrap() > exhausts_at);
+    }
+
+    #[test]
+    fn test_burst_then_idle_is_insufficient() {
+        let burst = [(0.0, 20.0), (1.0, 12.0), (2.0, 5.0), (6.0, 5.0), (9.0, 5.0)];
+        assert_eq!(
+            forecast(&history(&burst)),
+            Forecast::insufficient(InsufficientReason::NoRecentUsage)
+        );
+    }
+
+    #[test]
+    fn test_quota_reset_restarts_the_window() {
+        // Nearly out at $2/day, then the quota resets and burns at $0.5/day
+        let reset = [
+            (0.0, 8.0),
+            (1.0, 6.0),
+            (2.0, 4.0),
+            (3.0, 20.0),
+            (4.0, 19.5),
+            (5.0, 19.0),
+        ];
+        let projected = forecast(&history(&reset));
+        assert_eq!(projected.exhausts_at(), Some(day(43)));
+        let Forecast::Projected { snapshots, .. } = projected else {
+            unreachable!()
+        };
+        assert_eq!(snapshots, 3);
+
+        // Only two snapshots since the reset
+        assert_eq!(
+            forecast(&history(&reset[..5])),
+            Forecast::insufficient(InsufficientReason::TooFewSnapshots)
+        );
+    }
+
+    #[test]
+    fn test_sort_puts_soonest_first_and_unknown_last() {
+        let account = |email: &str, points: &[(f64, f64)]| AccountForecast {
+            email: email.to_string(),
+            forecast: forecast(&history(points)),
+        };
+        let mut forecasts = vec![
+            account("idle@x.com", &[(0.0, 5.0)]),
+            account("slow@x.com", &[(0.0, 20.0), (1.0, 19.0), (2.0, 18.0)]),
+            account("fast@x.com", &[(0.0, 20.0), (1.0, 15.0), (2.0, 10.0)]),
+        ];
+        sort_by_exhaustion(&mut forecasts);
+        let order: Vec<&str> = forecasts.iter().map(|f| f.email.as_str()).collect();
+        assert_eq!(order, ["fast@x.com", "slow@x.com", "idle@x.com"]);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::redaction;
+use crate::secret::SecretString;
+use crate::settings::Settings;
+use crate::state::MutexExt;
+use anyhow::{Context, Result};
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, StatusCode};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Extra attempts after a request that never reached the server or hit a gateway error
+pub const MAX_RETRIES: u32 = 2;
+pub const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+/// Gap between consecutive requests from clients of one factory
+pub const MIN_REQUEST_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many times and how patiently a failed call is tried again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Extra attempts after the first one
+    pub max_retries: u32,
+    /// Wait before the first retry, growing linearly with each further one
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: MAX_RETRIES,
+            backoff: RETRY_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `attempt`, counting from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff * attempt
+    }
+}
+
+/// Builds every HTTP client the API modules use, so proxy, timeout, retry and
+/// throttling are configured in one place. Clones share the throttle.
+#[derive(Debug, Clone)]
+pub struct HttpClientFactory {
+    proxy: Option<String>,
+    timeout: Duration,
+    retry: RetryPolicy,
+    spacing: Duration,
+    next_request: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Default for HttpClientFactory {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            timeout: DEFAULT_TIMEOUT,
+            retry: RetryPolicy::default(),
+            spacing: MIN_REQUEST_INTERVAL,
+            next_request: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl HttpClientFactory {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            proxy: settings
+                .http_proxy
+                .as_deref()
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(String::from),
+            timeout: settings.api_policy.timeout(),
+            retry: settings.api_policy.retry_policy(),
+            spacing: settings.api_policy.request_spacing(),
+            ..Self::default()
+        }
+    }
+
+    /// Same configuration and throttle with a different request timeout
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            timeout,
+            ..self.clone()
+        }
+    }
+
+    /// Same configuration and throttle with a different retry policy
+    pub fn with_retry(&self, retry: RetryPolicy) -> Self {
+        Self {
+            retry,
+            ..self.clone()
+        }
+    }
+
+    pub fn client(&self) -> Result<HttpClient> {
+        self.build(|builder| builder)
+    }
+
+    /// A client with extra options on top of the shared ones, e.g. a user agent
+    pub fn build(
+        &self,
+        configure: impl FnOnce(ClientBuilder) -> ClientBuilder,
+    ) -> Result<HttpClient> {
+        let mut builder = Client::builder().timeout(self.timeout);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(parse_proxy(proxy)?);
+        }
+        let client = configure(builder)
+            .build()
+            .context("Failed to create HTTP client")?;
+        Ok(HttpClient {
+            client,
+            retry: self.retry,
+            spacing: self.spacing,
+            next_request: self.next_request.clone(),
+        })
+    }
+
+    // Claim the next free slot, then sleep until it without holding the lock
+    fn wait_for_turn(next_request: &Mutex<Option<Instant>>, spacing: Duration) {
+        let now = Instant::now();
+        let slot = {
+            let mut next = next_request.lock_or_recover();
+            let slot = next.map_or(now, |next| next.max(now));
+            *next = Some(slot + spacing);
+            slot
+        };
+        thread::sleep(slot - now);
+    }
+}
+
+/// Check a proxy URL before it is saved
+pub fn validate_proxy(proxy: Option<&str>) -> Result<()> {
+    match proxy.map(str::trim).filter(|proxy| !proxy.is_empty()) {
+        Some(proxy) => parse_proxy(proxy).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+fn parse_proxy(proxy: &str) -> Result<reqwest::Proxy> {
+    // The URL may carry credentials, so it never goes into the message
+    reqwest::Proxy::all(proxy).context("Invalid proxy URL")
+}
+
+/// A configured reqwest client whose requests all go through `send`
+pub struct HttpClient {
+    client: Client,
+    retry: RetryPolicy,
+    spacing: Duration,
+    next_request: Arc<Mutex<Option<Instant>>>,
+}
+
+impl HttpClient {
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    pub fn head(&self, url: &str) -> RequestBuilder {
+        self.client.head(url)
+    }
+
+    pub fn put(&self, url: &str) -> RequestBuilder {
+        self.client.put(url)
+    }
+
+    /// For methods without a shortcut, e.g. WebDAV's MKCOL
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// Send with throttling and retries. Connection failures are retried for
+    /// any request since it never reached the server; gateway errors only for
+    /// GET and HEAD. Errors carry the URL without its query string and with
+    /// tokens masked, and keep the reqwest error for classification.
+    pub fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let mut request = request.build().context("Failed to build request")?;
+        let method = request.method().clone();
+        let url = redacted_url(request.url());
+        let idempotent = matches!(method, Method::GET | Method::HEAD);
+        let retry = self.retry;
+
+        let mut attempt = 0;
+        loop {
+            // Requests with a streaming body can't be cloned and are sent once
+            let retry_copy = (attempt < retry.max_retries)
+                .then(|| request.try_clone())
+                .flatten();
+            HttpClientFactory::wait_for_turn(&self.next_request, self.spacing);
+            let outcome = self.client.execute(request);
+            let should_retry = match &outcome {
+                Ok(response) => idempotent && is_gateway_error(response.status()),
+                Err(e) => e.is_connect(),
+            };
+
+            match retry_copy {
+                Some(copy) if should_retry => {
+                    request = copy;
+                    attempt += 1;
+                    tracing::debug!("Retrying {} {} (attempt {})", method, url, attempt + 1);
+                    thread::sleep(retry.delay(attempt));
+                }
+                _ => {
+                    return outcome.map_err(|e| {
+                        let e = e.without_url();
+                        let message = redaction::scrub_secrets(&e.to_string());
+                        anyhow::Error::new(e)
+                            .context(format!("{} {} failed: {}", method, url, message))
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Headers the clients set themselves, which an account's extra headers can't replace
+const RESERVED_HEADERS: [&str; 4] = ["authorization", "cookie", "host", "content-length"];
+
+/// An account's extra headers for a request. Entries that aren't valid
+/// headers are skipped with a warning naming only the header; the values are
+/// secrets and marked sensitive.
+pub fn extra_headers(headers: Option<&HashMap<String, SecretString>>) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers.into_iter().flatten() {
+        let Ok(header) = HeaderName::from_bytes(name.trim().as_bytes()) else {
+            tracing::warn!("Skipping extra header with an invalid name: {:?}", name);
+            continue;
+        };
+        if RESERVED_HEADERS.contains(&header.as_str()) {
+            tracing::warn!("Skipping extra header {}: it is set by the app", header);
+            continue;
+        }
+        let Ok(mut value) = HeaderValue::from_str(value.expose().trim()) else {
+            tracing::warn!("Skipping extra header {}: the value is not valid", header);
+            continue;
+        };
+        value.set_sensitive(true);
+        map.insert(header, value);
+    }
+    map
+}
+
+fn is_gateway_error(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Query strings carry things like the login verifier, so only scheme, host and path are kept
+fn redacted_url(url: &reqwest::Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    let _ = url.set_password(None);
+    redaction::scrub_secrets(url.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bad_proxy_is_an_error_not_a_panic() {
+        let settings = Settings {
+            http_proxy: Some("not a url".to_string()),
+            ..Settings::default()
+        };
+        let error = HttpClientFactory::from_settings(&settings)
+            .client()
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("Invalid proxy URL"));
+        assert!(validate_proxy(Some("not a url")).is_err());
+        assert!(validate_proxy(Some("http://127.0.0.1:8080")).is_ok());
+        assert!(validate_proxy(Some("  ")).is_ok());
+
+        let empty = Settings {
+            http_proxy: Some(" ".to_string()),
+            ..Settings::default()
+        };
+        assert!(HttpClientFactory::from_settings(&empty).client().is_ok());
+    }
+
+    #[test]
+    fn test_extra_headers_skip_invalid_and_reserved_entries() {
+        let headers: HashMap<String, SecretString> = [
+            ("X-Org-Token", "org-secret"),
+            ("Bad Name", "value"),
+            ("Authorization", "Bearer other"),
+            ("X-Newline", "a\nb"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.into()))
+        .collect();
+
+        let map = extra_headers(Some(&headers));
+        assert_eq!(map.len(), 1);
+        let value = &map["x-org-token"];
+        assert_eq!(value, "org-secret");
+        assert!(value.is_sensitive());
+        assert!(extra_headers(None).is_empty());
+    }
+
+    #[test]
+    fn test_send_retries_connection_failures_and_redacts_url() {
+        // Nothing listens on the port of a listener that was just dropped
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = HttpClientFactory::default()
+            .with_timeout(Duration::from_secs(2))
+            .client()
+            .unwrap();
+
+        let started = Instant::now();
+        let url = format!("http://127.0.0.1:{}/poll?verifier=secret", port);
+        let error = client.send(client.get(&url)).unwrap_err();
+        assert!(started.elapsed() >= RETRY_BACKOFF * 3);
+
+        let message = format!("{:#}", error);
+        assert!(message.contains(&format!("GET http://127.0.0.1:{}/poll", port)));
+        assert!(!message.contains("secret"));
+        let reqwest_error = error.downcast_ref::<reqwest::Error>().unwrap();
+        assert!(reqwest_error.is_connect());
+    }
+}
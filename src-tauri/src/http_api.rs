@@ -0,0 +1,328 @@
+use anyhow::{Context, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Largest request body accepted by the API
+const MAX_BODY_BYTES: usize = 64 * 1024;
+/// How long `stop()` waits for in-flight requests
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Endpoints of the local automation API
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiRoute {
+    ListAccounts,
+    Current,
+    Switch { email: String, reset_machine: bool },
+    Sync,
+    Refresh,
+}
+
+#[derive(Debug, Deserialize)]
+struct SwitchRequest {
+    email: String,
+    #[serde(default)]
+    reset_machine: bool,
+}
+
+/// Runs a route and returns its JSON result; called on a blocking thread
+pub type ApiHandler = dyn Fn(ApiRoute) -> Result<Value, String> + Send + Sync;
+
+/// Handle of a running server; dropping it without `stop()` leaves the server running
+pub struct HttpApiServer {
+    port: u16,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl HttpApiServer {
+    /// Bind to 127.0.0.1:`port` and serve requests authenticated with `token`
+    pub fn start(port: u16, token: String, handler: Arc<ApiHandler>) -> Result<Self> {
+        // Bind synchronously so a port conflict is reported to the caller
+        let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+            .with_context(|| format!("Failed to bind 127.0.0.1:{}", port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        let token = Arc::new(token);
+
+        let task = tauri::async_runtime::spawn(async move {
+            let make_service = make_service_fn(move |_| {
+                let token = token.clone();
+                let handler = handler.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        handle_request(request, token.clone(), handler.clone())
+                    }))
+                }
+            });
+
+            let server = match Server::from_tcp(listener) {
+                Ok(builder) => builder.serve(make_service),
+                Err(e) => {
+                    tracing::error!("Failed to start local API server: {}", e);
+                    return;
+                }
+            };
+
+            tracing::info!("Local API listening on 127.0.0.1:{}", port);
+            let graceful = server.with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+
+            if let Err(e) = graceful.await {
+                tracing::error!("Local API server error: {}", e);
+            }
+            tracing::info!("Local API server stopped");
+        });
+
+        Ok(Self {
+            port,
+            shutdown: Some(shutdown),
+            task,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Stop accepting connections and wait (bounded) for in-flight requests to finish
+    pub fn stop(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+
+        let task = self.task;
+        let result = tauri::async_runtime::block_on(async move {
+            tokio::time::timeout(SHUTDOWN_TIMEOUT, task).await
+        });
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::error!("Local API server task failed: {}", e),
+            Err(_) => tracing::warn!(
+                "Local API server did not stop within {:?}",
+                SHUTDOWN_TIMEOUT
+            ),
+        }
+    }
+}
+
+/// Generate a random bearer token for the API
+pub fn generate_token() -> String {
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Check an Authorization header against the expected token
+pub fn is_authorized(authorization: Option<&str>, token: &str) -> bool {
+    let provided = match authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+        Some(provided) => provided.trim(),
+        None => return false,
+    };
+
+    // Compare digests so the check doesn't leak how much of the token matched
+    Sha256::digest(provided.as_bytes()) == Sha256::digest(token.as_bytes())
+}
+
+/// Resolve a method, path and body to a route.
+/// Returns the HTTP status and message to send back when the request doesn't map to one.
+pub fn parse_route(
+    method: &Method,
+    path: &str,
+    body: &[u8],
+) -> Result<ApiRoute, (StatusCode, String)> {
+    let path = path.trim_end_matches('/');
+
+    let route = match (method, path) {
+        (&Method::GET, "/accounts") => ApiRoute::ListAccounts,
+        (&Method::GET, "/current") => ApiRoute::Current,
+        (&Method::POST, "/switch") => {
+            let request: SwitchRequest = serde_json::from_slice(body).map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid request body: {}", e),
+                )
+            })?;
+            ApiRoute::Switch {
+                email: request.email,
+                reset_machine: request.reset_machine,
+            }
+        }
+        (&Method::POST, "/sync") => ApiRoute::Sync,
+        (&Method::POST, "/refresh") => ApiRoute::Refresh,
+        (_, "/accounts" | "/current" | "/switch" | "/sync" | "/refresh") => {
+            return Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed".to_string(),
+            ))
+        }
+        _ => return Err((StatusCode::NOT_FOUND, "Not found".to_string())),
+    };
+
+    Ok(route)
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    token: Arc<String>,
+    handler: Arc<ApiHandler>,
+) -> Result<Response<Body>, Infallible> {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    if !is_authorized(authorization, &token) {
+        tracing::warn!(
+            "Rejected unauthorized local API request: {} {}",
+            request.method(),
+            request.uri().path()
+        );
+        return Ok(error_response(
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid bearer token",
+        ));
+    }
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) if body.len() <= MAX_BODY_BYTES => body,
+        Ok(_) => {
+            return Ok(error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Request body too large",
+            ))
+        }
+        Err(e) => return Ok(error_response(StatusCode::BAD_REQUEST, &e.to_string())),
+    };
+
+    let route = match parse_route(&method, &path, &body) {
+        Ok(route) => route,
+        Err((status, message)) => return Ok(error_response(status, &message)),
+    };
+
+    tracing::info!("Local API request: {} {}", method, path);
+
+    // Handlers do blocking file and network I/O
+    let result = tokio::task::spawn_blocking(move || handler(route)).await;
+
+    let response = match result {
+        Ok(Ok(result)) => json_response(StatusCode::OK, json!({ "ok": true, "result": result })),
+        Ok(Err(e)) => {
+            tracing::error!("Local API request failed: {}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, &e)
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    };
+
+    Ok(response)
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    json_response(status, json!({ "ok": false, "error": message }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_authorized() {
+        assert!(is_authorized(Some("Bearer secret"), "secret"));
+        assert!(!is_authorized(Some("Bearer wrong"), "secret"));
+        assert!(!is_authorized(Some("secret"), "secret"));
+        assert!(!is_authorized(None, "secret"));
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token();
+        assert_eq!(token.len(), 64);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn test_parse_route() {
+        assert_eq!(
+            parse_route(&Method::GET, "/accounts", b""),
+            Ok(ApiRoute::ListAccounts)
+        );
+        assert_eq!(
+            parse_route(&Method::POST, "/switch", br#"{"email":"bob@x.com"}"#),
+            Ok(ApiRoute::Switch {
+                email: "bob@x.com".to_string(),
+                reset_machine: false,
+            })
+        );
+        assert_eq!(
+            parse_route(&Method::POST, "/refresh/", b""),
+            Ok(ApiRoute::Refresh)
+        );
+    }
+
+    #[test]
+    fn test_parse_route_errors() {
+        let status = |result: Result<ApiRoute, (StatusCode, String)>| result.unwrap_err().0;
+
+        assert_eq!(
+            status(parse_route(&Method::POST, "/switch", b"{}")),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(parse_route(&Method::GET, "/switch", b"")),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            status(parse_route(&Method::GET, "/unknown", b"")),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn test_server_authenticates_and_shuts_down() {
+        let handler: Arc<ApiHandler> = Arc::new(|route| match route {
+            ApiRoute::ListAccounts => Ok(json!([])),
+            _ => Err("unsupported".to_string()),
+        });
+        let server = HttpApiServer::start(0, "secret".to_string(), handler).unwrap();
+        let url = format!("http://127.0.0.1:{}/accounts", server.port());
+
+        let client = reqwest::blocking::Client::new();
+        let unauthorized = client.get(&url).send().unwrap();
+        assert_eq!(unauthorized.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = client.get(&url).bearer_auth("secret").send().unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let body: Value = response.json().unwrap();
+        assert_eq!(body, json!({ "ok": true, "result": [] }));
+
+        let port = server.port();
+        server.stop();
+        assert!(TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port))).is_ok());
+    }
+}
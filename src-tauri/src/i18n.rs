@@ -0,0 +1,491 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Languages available for tray menu, tooltip, dialog and notification strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Locale {
+    #[serde(rename = "en")]
+    En,
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::ZhCn];
+
+    /// Parse a locale code such as "en", "zh-CN" or "zh_cn"
+    pub fn parse(code: &str) -> Option<Self> {
+        match code.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "en" | "en-us" | "en-gb" => Some(Locale::En),
+            "zh" | "zh-cn" | "zh-hans" => Some(Locale::ZhCn),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::ZhCn => "zh-CN",
+        }
+    }
+
+    fn table(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::En => EN,
+            Locale::ZhCn => ZH_CN,
+        }
+    }
+}
+
+static CURRENT_LOCALE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_locale(locale: Locale) {
+    let index = Locale::ALL.iter().position(|l| *l == locale).unwrap_or(0);
+    CURRENT_LOCALE.store(index as u8, Ordering::Relaxed);
+}
+
+pub fn current_locale() -> Locale {
+    Locale::ALL
+        .get(CURRENT_LOCALE.load(Ordering::Relaxed) as usize)
+        .copied()
+        .unwrap_or(Locale::En)
+}
+
+/// Translate a key in the given locale, falling back to English and then to the key itself
+pub fn translate(locale: Locale, key: &'static str) -> &'static str {
+    let find = |table: &'static [(&'static str, &'static str)]| {
+        table
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| *value)
+    };
+
+    find(locale.table()).or_else(|| find(EN)).unwrap_or(key)
+}
+
+/// Translate a key in the current locale
+pub fn t(key: &'static str) -> &'static str {
+    translate(current_locale(), key)
+}
+
+/// Translate a key and fill `{name}` placeholders
+pub fn tf(key: &'static str, args: &[(&str, &str)]) -> String {
+    fill(t(key), args)
+}
+
+fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    args.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+const EN: &[(&str, &str)] = &[
+    ("tray.show", "Show Window"),
+    ("tray.hide", "Hide Window"),
+    ("tray.sync", "Sync Current Account"),
+    ("tray.refresh", "Refresh All Accounts"),
+    ("tray.quit", "Quit"),
+    ("tray.accounts_header", "Switch Account"),
+    ("tray.loading", "  Loading..."),
+    ("tray.no_accounts", "  No accounts available"),
+    ("tray.more_accounts", "  ... and {count} more"),
+    ("tray.pool", "{name} ({count})"),
+    ("tray.current_account", "Current: {email}"),
+    (
+        "tray.current_account_expiring",
+        "Current: {email} ⚠ token expiring",
+    ),
+    ("tray.reauth", "Re-authenticate Current Account"),
+    ("tray.no_current_account", "Current: No account logged in"),
+    ("tray.account_details", "Account Details..."),
+    ("tray.watch_clipboard", "Watch Clipboard for Tokens"),
+    ("tray.import_detected", "Import Detected Token"),
+    ("tray.update_available", "Update available: v{version}"),
+    ("tray.cooldown", "({time} cooldown)"),
+    ("tray.stale", "(stale)"),
+    (
+        "tooltip.no_account",
+        "Cursor Account Switcher — no account logged in",
+    ),
+    ("tooltip.usage", "{percentage}% used"),
+    ("window.account_details", "Account Details"),
+    ("tooltip.days_left", "{days} days left"),
+    ("tooltip.stale", "data may be outdated"),
+    ("tooltip.refreshed_at", "data as of {time}"),
+    ("notify.token_detected.title", "Cursor token detected"),
+    (
+        "notify.token_detected.body",
+        "Choose \"Import Detected Token\" in the tray menu to add the account.",
+    ),
+    ("notify.imported.title", "Account imported"),
+    (
+        "notify.imported.body",
+        "{email} was added to your accounts.",
+    ),
+    ("notify.import_failed.title", "Import failed"),
+    ("notify.sync_added.title", "Account added"),
+    (
+        "notify.sync_added.body",
+        "{email} is logged into Cursor and was added to your accounts.",
+    ),
+    ("notify.sync_updated.title", "Account synced"),
+    ("notify.sync_updated.body", "Updated the stored tokens of {email}."),
+    ("notify.sync_nothing.title", "Nothing to sync"),
+    ("notify.sync_nothing.body", "No account is logged into Cursor."),
+    ("notify.sync_failed.title", "Sync failed"),
+    ("notify.switched.title", "Account switched"),
+    ("notify.switched.body", "Switched to {email}."),
+    (
+        "notify.switched.body_with_options",
+        "Switched to {email} ({options}).",
+    ),
+    ("switch.option.reset_machine", "machine ID reset"),
+    ("switch.option.restart", "Cursor restarted"),
+    ("switch.option.validate", "token checked"),
+    ("notify.switch_failed.title", "Switch failed"),
+    ("notify.refreshed.title", "Accounts refreshed"),
+    ("notify.refreshed.body", "Updated usage data of {count} accounts."),
+    ("notify.recently_refreshed.title", "Accounts were just refreshed"),
+    (
+        "notify.recently_refreshed.body",
+        "Accounts were refreshed {seconds} seconds ago. Refresh from the app window to run it again now.",
+    ),
+    ("notify.cursor_updated.title", "Cursor was updated"),
+    (
+        "notify.cursor_updated.body",
+        "Cursor updated to {version}. The machine ID patch may have been reverted.",
+    ),
+    (
+        "notify.cursor_updated.body_patched",
+        "Cursor updated to {version}. The machine ID patch is still in place.",
+    ),
+    ("notify.hook_failed.title", "Switch hook failed"),
+    ("notify.hook_failed.body", "The {hook} hook {reason}."),
+    (
+        "reason.cooling_down",
+        "This account was used recently; its cooldown ends in {time}",
+    ),
+    ("notify.settings_restored.title", "Cursor settings restored"),
+    (
+        "notify.settings_restored.body",
+        "Cursor reset {count} of your settings after the machine ID reset; they were put back",
+    ),
+    (
+        "notify.settings_restored.body_skipped",
+        "{count} settings Cursor reset were put back. Close Cursor and restore the latest settings snapshot for the other {skipped}",
+    ),
+    ("notify.switch_reverted.title", "Switch didn't stick"),
+    (
+        "notify.switch_reverted.body",
+        "Cursor started with {found} instead of {target}; the switch was undone",
+    ),
+    ("notify.switch_reverted.logged_out", "no account"),
+    ("notify.refresh_failed.title", "Refresh failed"),
+    ("notify.token_expiring.title", "Cursor session expiring"),
+    (
+        "notify.token_expiring.body",
+        "The token of {email} expires soon. Choose \"Re-authenticate Current Account\" in the tray menu.",
+    ),
+    ("notify.token_expired.title", "Cursor session expired"),
+    (
+        "notify.token_expired.body",
+        "The token of {email} has expired. Choose \"Re-authenticate Current Account\" in the tray menu.",
+    ),
+    ("notify.reauth_done.title", "Account re-authenticated"),
+    ("notify.reauth_done.body", "{email} has a fresh token."),
+    ("notify.interrupted.title", "Last switch didn't finish"),
+    (
+        "notify.interrupted.body",
+        "The app closed while changing Cursor's login. Open it to finish or discard the change.",
+    ),
+    ("notify.reauth_failed.title", "Re-authentication failed"),
+    (
+        "reason.cursor_path_not_set",
+        "Cursor's data folder is not set; choose it in the app",
+    ),
+    (
+        "reason.database_locked",
+        "Cursor's database is in use; close Cursor and try again",
+    ),
+    ("reason.app_locked", "The app is locked; unlock it first"),
+    (
+        "reason.store_locked",
+        "The account store is locked; enter its passphrase first",
+    ),
+    (
+        "reason.read_only",
+        "The app is in read-only mode; an admin has to turn it off first",
+    ),
+    (
+        "reason.busy",
+        "Another operation is still running; try again when it finishes",
+    ),
+    (
+        "reason.token_invalid",
+        "The account's token is invalid or expired; Cursor was left untouched",
+    ),
+    ("reason.account_not_found", "The account is no longer stored"),
+    ("reason.not_logged_in", "No account is logged into Cursor"),
+    ("reason.network", "Could not reach Cursor's servers"),
+    (
+        "reason.switch_rolled_back",
+        "Switching failed while {stage}; Cursor is back on the previous account",
+    ),
+    (
+        "reason.switch_rollback_failed",
+        "Switching failed while {stage} and Cursor could not be restored; open the app to recover",
+    ),
+    (
+        "reason.switch_restart_failed",
+        "Switched, but Cursor could not be started; start it yourself",
+    ),
+    ("switch.stage.close_cursor", "closing Cursor"),
+    ("switch.stage.write_credentials", "writing Cursor's login"),
+    ("switch.stage.reset_machine_id", "resetting the machine ID"),
+    ("switch.stage.apply_privacy_mode", "applying the privacy mode"),
+    ("notify.export_failed.title", "Automatic export failed"),
+    ("notify.locked.title", "App is locked"),
+    (
+        "notify.locked.body",
+        "Unlock the switcher in its window to switch accounts.",
+    ),
+    ("dialog.import_link.title", "Import Account"),
+    (
+        "dialog.import_link.body",
+        "A link wants to import an account from a token.\n\nImport it?",
+    ),
+    ("dialog.switch_link.title", "Switch Account"),
+    (
+        "dialog.switch_link.body",
+        "A link wants to switch Cursor to {email}.\n\nCursor will be closed. Continue?",
+    ),
+    ("dialog.link_failed.title", "Link Failed"),
+    ("dialog.confirm_switch.title", "Switch Account"),
+    (
+        "dialog.confirm_switch.body",
+        "Switch Cursor to {email}?\n\nCursor will be closed and any unsaved work in it may be lost.",
+    ),
+];
+
+const ZH_CN: &[(&str, &str)] = &[
+    ("tray.show", "显示窗口"),
+    ("tray.hide", "隐藏窗口"),
+    ("tray.sync", "同步当前账号"),
+    ("tray.refresh", "刷新所有账号"),
+    ("tray.quit", "退出"),
+    ("tray.accounts_header", "切换账号"),
+    ("tray.loading", "  加载中..."),
+    ("tray.no_accounts", "  暂无账号"),
+    ("tray.more_accounts", "  ……还有 {count} 个"),
+    ("tray.pool", "{name}（{count}）"),
+    ("tray.current_account", "当前：{email}"),
+    (
+        "tray.current_account_expiring",
+        "当前：{email} ⚠ 令牌即将过期",
+    ),
+    ("tray.reauth", "重新验证当前账号"),
+    ("tray.no_current_account", "当前：未登录账号"),
+    ("tray.account_details", "账号详情..."),
+    ("tray.watch_clipboard", "监听剪贴板中的令牌"),
+    ("tray.import_detected", "导入检测到的令牌"),
+    ("tray.update_available", "有可用更新：v{version}"),
+    ("tray.cooldown", "（冷却 {time}）"),
+    ("tray.stale", "（已过期）"),
+    ("tooltip.no_account", "Cursor 账号切换器 — 未登录账号"),
+    ("tooltip.usage", "已用 {percentage}%"),
+    ("window.account_details", "账号详情"),
+    ("tooltip.days_left", "剩余 {days} 天"),
+    ("tooltip.stale", "数据可能已过期"),
+    ("tooltip.refreshed_at", "数据更新于 {time}"),
+    ("notify.token_detected.title", "检测到 Cursor 令牌"),
+    (
+        "notify.token_detected.body",
+        "在托盘菜单中选择“导入检测到的令牌”即可添加该账号。",
+    ),
+    ("notify.imported.title", "账号已导入"),
+    ("notify.imported.body", "{email} 已添加到账号列表。"),
+    ("notify.import_failed.title", "导入失败"),
+    ("notify.sync_added.title", "账号已添加"),
+    (
+        "notify.sync_added.body",
+        "{email} 已登录 Cursor，并已添加到账号列表。",
+    ),
+    ("notify.sync_updated.title", "账号已同步"),
+    ("notify.sync_updated.body", "已更新 {email} 保存的令牌。"),
+    ("notify.sync_nothing.title", "无需同步"),
+    ("notify.sync_nothing.body", "Cursor 当前没有登录任何账号。"),
+    ("notify.sync_failed.title", "同步失败"),
+    ("notify.switched.title", "账号已切换"),
+    ("notify.switched.body", "已切换到 {email}。"),
+    (
+        "notify.switched.body_with_options",
+        "已切换到 {email}（{options}）。",
+    ),
+    ("switch.option.reset_machine", "已重置机器 ID"),
+    ("switch.option.restart", "已重启 Cursor"),
+    ("switch.option.validate", "已验证令牌"),
+    ("notify.switch_failed.title", "切换失败"),
+    ("notify.refreshed.title", "账号已刷新"),
+    ("notify.refreshed.body", "已更新 {count} 个账号的用量数据。"),
+    ("notify.recently_refreshed.title", "账号刚刚刷新过"),
+    (
+        "notify.recently_refreshed.body",
+        "账号已在 {seconds} 秒前刷新。如需立即再次刷新，请在应用窗口中操作。",
+    ),
+    ("notify.cursor_updated.title", "Cursor 已更新"),
+    (
+        "notify.cursor_updated.body",
+        "Cursor 已更新到 {version}，机器 ID 补丁可能已被还原。",
+    ),
+    (
+        "notify.cursor_updated.body_patched",
+        "Cursor 已更新到 {version}，机器 ID 补丁仍然有效。",
+    ),
+    ("notify.hook_failed.title", "切换钩子执行失败"),
+    ("notify.hook_failed.body", "{hook} 钩子：{reason}。"),
+    (
+        "reason.cooling_down",
+        "该账号刚使用过，冷却将在 {time} 后结束",
+    ),
+    ("notify.settings_restored.title", "已恢复 Cursor 设置"),
+    (
+        "notify.settings_restored.body",
+        "重置机器 ID 后 Cursor 重置了 {count} 项设置，已为你恢复",
+    ),
+    (
+        "notify.settings_restored.body_skipped",
+        "已恢复 Cursor 重置的 {count} 项设置。关闭 Cursor 后恢复最新的设置快照即可找回其余 {skipped} 项",
+    ),
+    ("notify.switch_reverted.title", "切换未生效"),
+    (
+        "notify.switch_reverted.body",
+        "Cursor 启动后登录的是 {found}，而不是 {target}，切换已被撤销",
+    ),
+    ("notify.switch_reverted.logged_out", "未登录账号"),
+    ("notify.refresh_failed.title", "刷新失败"),
+    ("notify.token_expiring.title", "Cursor 会话即将过期"),
+    (
+        "notify.token_expiring.body",
+        "{email} 的令牌即将过期，请在托盘菜单中选择“重新验证当前账号”。",
+    ),
+    ("notify.token_expired.title", "Cursor 会话已过期"),
+    (
+        "notify.token_expired.body",
+        "{email} 的令牌已过期，请在托盘菜单中选择“重新验证当前账号”。",
+    ),
+    ("notify.reauth_done.title", "账号已重新验证"),
+    ("notify.reauth_done.body", "{email} 已获得新的令牌。"),
+    ("notify.interrupted.title", "上次切换未完成"),
+    (
+        "notify.interrupted.body",
+        "应用在修改 Cursor 登录信息时关闭。请打开应用完成或放弃此更改。",
+    ),
+    ("notify.reauth_failed.title", "重新验证失败"),
+    (
+        "reason.cursor_path_not_set",
+        "未设置 Cursor 数据目录，请在应用中选择",
+    ),
+    (
+        "reason.database_locked",
+        "Cursor 数据库正被占用，请关闭 Cursor 后重试",
+    ),
+    ("reason.app_locked", "应用已锁定，请先解锁"),
+    ("reason.store_locked", "账号存储已锁定，请先输入密码短语"),
+    ("reason.read_only", "应用处于只读模式，需由管理员先关闭"),
+    ("reason.busy", "另一项操作仍在进行，请稍后重试"),
+    (
+        "reason.token_invalid",
+        "账号令牌无效或已过期，Cursor 未做更改",
+    ),
+    ("reason.account_not_found", "该账号已不存在"),
+    ("reason.not_logged_in", "Cursor 当前未登录账号"),
+    ("reason.network", "无法连接 Cursor 服务器"),
+    (
+        "reason.switch_rolled_back",
+        "{stage}时切换失败，Cursor 已恢复为之前的账号",
+    ),
+    (
+        "reason.switch_rollback_failed",
+        "{stage}时切换失败且无法恢复 Cursor，请打开应用处理",
+    ),
+    (
+        "reason.switch_restart_failed",
+        "已切换，但无法启动 Cursor，请手动启动",
+    ),
+    ("switch.stage.close_cursor", "关闭 Cursor"),
+    ("switch.stage.write_credentials", "写入 Cursor 登录信息"),
+    ("switch.stage.reset_machine_id", "重置机器 ID"),
+    ("switch.stage.apply_privacy_mode", "应用隐私模式"),
+    ("notify.export_failed.title", "自动导出失败"),
+    ("notify.locked.title", "应用已锁定"),
+    ("notify.locked.body", "请在窗口中解锁后再切换账号。"),
+    ("dialog.import_link.title", "导入账号"),
+    (
+        "dialog.import_link.body",
+        "有链接请求通过令牌导入账号。\n\n是否导入？",
+    ),
+    ("dialog.switch_link.title", "切换账号"),
+    (
+        "dialog.switch_link.body",
+        "有链接请求将 Cursor 切换到 {email}。\n\nCursor 将被关闭，是否继续？",
+    ),
+    ("dialog.link_failed.title", "链接处理失败"),
+    ("dialog.confirm_switch.title", "切换账号"),
+    (
+        "dialog.confirm_switch.body",
+        "确定将 Cursor 切换到 {email} 吗？\n\nCursor 将被关闭，其中未保存的内容可能会丢失。",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_locale() {
+        assert_eq!(Locale::parse("en"), Some(Locale::En));
+        assert_eq!(Locale::parse("zh-CN"), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse("zh_cn"), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse("fr"), None);
+    }
+
+    #[test]
+    fn test_translate_with_fallback() {
+        assert_eq!(translate(Locale::ZhCn, "tray.quit"), "退出");
+        assert_eq!(translate(Locale::En, "tray.quit"), "Quit");
+        assert_eq!(translate(Locale::ZhCn, "missing.key"), "missing.key");
+    }
+
+    #[test]
+    fn test_all_locales_cover_english_keys() {
+        for locale in Locale::ALL {
+            for (key, _) in EN {
+                assert!(
+                    locale.table().iter().any(|(k, _)| k == key),
+                    "{} is missing {}",
+                    locale.code(),
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fill_placeholders() {
+        assert_eq!(
+            fill(
+                translate(Locale::En, "tray.current_account"),
+                &[("email", "a@x.com")]
+            ),
+            "Current: a@x.com"
+        );
+        assert_eq!(
+            fill(
+                translate(Locale::ZhCn, "tray.more_accounts"),
+                &[("count", "3")]
+            ),
+            "  ……还有 3 个"
+        );
+    }
+}
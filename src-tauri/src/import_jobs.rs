@@ -0,0 +1,399 @@
+use crate::secret::SecretString;
+use crate::store_crypto::{self, StoreError};
+use crate::types::Account;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// Folder inside the app data dir holding one JSON file per job
+pub const JOBS_DIR_NAME: &str = "import_jobs";
+/// Finished jobs are deleted this long after their last entry
+pub const FINISHED_JOB_RETENTION_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    Pending,
+    Imported,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportEntry {
+    /// SHA-256 of the trimmed input line, so a user can tell which lines were handled
+    pub line_hash: String,
+    pub status: EntryStatus,
+    pub email: Option<String>,
+    pub error: Option<String>,
+    /// Kept only until the entry is processed, sealed with the account store's
+    /// key on disk once the store has a passphrase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<SecretString>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportJob {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub entries: Vec<ImportEntry>,
+}
+
+/// What `list_import_jobs` shows; carries no tokens
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportJobSummary {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub total: usize,
+    pub imported: usize,
+    pub failed: usize,
+    pub pending: usize,
+}
+
+pub fn line_hash(line: &str) -> String {
+    Sha256::digest(line.trim().as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl ImportJob {
+    /// One entry per distinct non-empty line
+    pub fn new(tokens: &[String], now: DateTime<Utc>) -> Self {
+        let mut seen = HashSet::new();
+        let entries = tokens
+            .iter()
+            .map(|token| token.trim())
+            .filter(|token| !token.is_empty())
+            .filter_map(|token| {
+                let line_hash = line_hash(token);
+                seen.insert(line_hash.clone()).then(|| ImportEntry {
+                    line_hash,
+                    status: EntryStatus::Pending,
+                    email: None,
+                    error: None,
+                    token: Some(token.into()),
+                })
+            })
+            .collect();
+
+        Self {
+            id: format!(
+                "{}-{:04x}",
+                now.format("%Y%m%d%H%M%S"),
+                rand::random::<u16>()
+            ),
+            created_at: now,
+            updated_at: now,
+            entries,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|entry| entry.status != EntryStatus::Pending)
+    }
+
+    pub fn summary(&self) -> ImportJobSummary {
+        let count = |status| self.entries.iter().filter(|e| e.status == status).count();
+        ImportJobSummary {
+            id: self.id.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            total: self.entries.len(),
+            imported: count(EntryStatus::Imported),
+            failed: count(EntryStatus::Failed),
+            pending: count(EntryStatus::Pending),
+        }
+    }
+}
+
+pub struct ImportJobStore {
+    dir: PathBuf,
+}
+
+impl ImportJobStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        // IDs come from the frontend, so never let one leave the jobs folder
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            bail!("Invalid import job ID: {}", id);
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    // The key of the account store the jobs folder sits next to
+    fn token_key(&self) -> Result<Option<store_crypto::StoreKey>> {
+        store_crypto::token_key(&self.dir)
+    }
+
+    /// Write `job`; its tokens are sealed when the account store is encrypted,
+    /// which then must be unlocked
+    pub fn save(&self, job: &ImportJob) -> Result<()> {
+        fs::create_dir_all(&self.dir).context("Failed to create import jobs folder")?;
+        let path = self.path(&job.id)?;
+        let mut stored = job.clone();
+        if stored.entries.iter().any(|entry| entry.token.is_some()) {
+            if let Some(key) = self.token_key()? {
+                for token in stored.entries.iter_mut().filter_map(|e| e.token.as_mut()) {
+                    *token = key.seal(token.expose())?.into();
+                }
+            }
+        }
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(&stored)?)
+            .context("Failed to write import job")?;
+        fs::rename(&temp_path, &path).context("Failed to write import job")?;
+        Ok(())
+    }
+
+    /// The job with its tokens; sealed ones need the account store unlocked
+    pub fn load(&self, id: &str) -> Result<ImportJob> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            bail!("Import job not found: {}", id);
+        }
+        let content = fs::read_to_string(&path).context("Failed to read import job")?;
+        let mut job: ImportJob =
+            serde_json::from_str(&content).context("Failed to parse import job")?;
+        let sealed = job.entries.iter().any(|entry| {
+            entry
+                .token
+                .as_ref()
+                .is_some_and(|token| store_crypto::is_encrypted(token.expose()))
+        });
+        if sealed {
+            let key = self.token_key()?.ok_or(StoreError::Locked)?;
+            for token in job.entries.iter_mut().filter_map(|e| e.token.as_mut()) {
+                *token = key.open(token.expose())?.into();
+            }
+        }
+        Ok(job)
+    }
+
+    /// All readable jobs, newest first, without their tokens
+    pub fn list(&self) -> Result<Vec<ImportJob>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut jobs = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let parsed = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(serde_json::from_str::<ImportJob>(&content)?));
+            match parsed {
+                Ok(mut job) => {
+                    job.entries.iter_mut().for_each(|entry| entry.token = None);
+                    jobs.push(job);
+                }
+                Err(e) => {
+                    tracing::warn!("Skipping unreadable import job {}: {}", path.display(), e)
+                }
+            }
+        }
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.created_at));
+        Ok(jobs)
+    }
+
+    pub fn discard(&self, id: &str) -> Result<()> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            bail!("Import job not found: {}", id);
+        }
+        fs::remove_file(path).context("Failed to delete import job")
+    }
+
+    /// Save the unfinished jobs again, sealing tokens written before the
+    /// account store had a passphrase
+    pub fn seal_tokens(&self) -> Result<()> {
+        for job in self.list()? {
+            if !job.is_finished() {
+                self.save(&self.load(&job.id)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete finished jobs untouched for `FINISHED_JOB_RETENTION_DAYS`; returns how many
+    pub fn prune(&self, now: DateTime<Utc>) -> Result<usize> {
+        let cutoff = now - Duration::days(FINISHED_JOB_RETENTION_DAYS);
+        let mut pruned = 0;
+        for job in self.list()? {
+            if job.is_finished() && job.updated_at < cutoff {
+                self.discard(&job.id)?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Import the pending entries of `job` one by one, saving after each so a
+    /// crash loses at most the entry in flight. `import` turns a token into
+    /// the stored account; `on_progress` runs after every entry.
+    pub fn run(
+        &self,
+        job: &mut ImportJob,
+        mut import: impl FnMut(&str) -> Result<Account>,
+        mut on_progress: impl FnMut(&ImportJob),
+    ) -> Result<()> {
+        for index in 0..job.entries.len() {
+            let entry = &mut job.entries[index];
+            if entry.status != EntryStatus::Pending {
+                continue;
+            }
+            let Some(token) = entry.token.take() else {
+                entry.status = EntryStatus::Failed;
+                entry.error = Some("Token is no longer available".to_string());
+                continue;
+            };
+
+            match import(token.expose()) {
+                Ok(account) => {
+                    entry.status = EntryStatus::Imported;
+                    entry.email = Some(account.email);
+                }
+                Err(e) => {
+                    entry.status = EntryStatus::Failed;
+                    entry.error = Some(crate::redaction::scrub_secrets(&format!("{:#}", e)));
+                }
+            }
+            job.updated_at = Utc::now();
+            self.save(job)?;
+            on_progress(job);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_support::account;
+
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_resume_skips_processed_entries_and_forgets_tokens() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = ImportJobStore::new(temp_dir.path().join(JOBS_DIR_NAME));
+        let tokens = ["token_a", "token_b", " token_a ", "", "token_c"].map(String::from);
+        let mut job = ImportJob::new(&tokens, Utc::now());
+        assert_eq!(job.entries.len(), 3);
+        store.save(&job).unwrap();
+
+        // The app dies while importing the second entry
+        let mut calls = 0;
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            store.run(
+                &mut job,
+                |token| {
+                    calls += 1;
+                    if calls == 2 {
+                        panic!("crash");
+                    }
+                    Ok(account(&format!(
+                        "{}@x.com",
+                        token.replace("token_", "user_")
+                    )))
+                },
+                |_| {},
+            )
+        }));
+
+        let content = fs::read_to_string(store.path(&job.id).unwrap()).unwrap();
+        assert!(!content.contains("token_a"));
+        assert!(content.contains("token_b"));
+
+        let mut resumed = store.load(&job.id).unwrap();
+        let mut seen = Vec::new();
+        store
+            .run(
+                &mut resumed,
+                |token| {
+                    seen.push(token.to_string());
+                    if token == "token_c" {
+                        bail!("Token rejected");
+                    }
+                    Ok(account(&format!(
+                        "{}@x.com",
+                        token.replace("token_", "user_")
+                    )))
+                },
+                |_| {},
+            )
+            .unwrap();
+        assert_eq!(seen, vec!["token_b", "token_c"]);
+
+        let summary = store.load(&job.id).unwrap().summary();
+        assert_eq!(
+            (summary.imported, summary.failed, summary.pending),
+            (2, 1, 0)
+        );
+        let content = fs::read_to_string(store.path(&job.id).unwrap()).unwrap();
+        assert!(!content.contains("\"token\""));
+    }
+
+    #[test]
+    fn test_tokens_are_sealed_with_the_store_passphrase() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("accounts.csv");
+        let store = ImportJobStore::new(temp_dir.path().join(JOBS_DIR_NAME));
+
+        let job = ImportJob::new(&["secret_token".to_string()], Utc::now());
+        store.save(&job).unwrap();
+        let key = store_crypto::create(&csv_path, "correct horse").unwrap();
+        store_crypto::remember(&csv_path, key);
+        store.seal_tokens().unwrap();
+        let content = fs::read_to_string(store.path(&job.id).unwrap()).unwrap();
+        assert!(!content.contains("secret_token"));
+        assert_eq!(store.load(&job.id).unwrap(), job);
+
+        // Locked, the job can be listed but not resumed
+        store_crypto::forget(&csv_path);
+        assert!(store.load(&job.id).unwrap_err().is::<StoreError>());
+        let listed = store.list().unwrap();
+        assert_eq!(listed[0].summary(), job.summary());
+        assert!(listed[0].entries[0].token.is_none());
+        assert!(store.save(&job).is_err());
+    }
+
+    #[test]
+    fn test_prune_keeps_recent_and_unfinished_jobs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = ImportJobStore::new(temp_dir.path().join(JOBS_DIR_NAME));
+        let old = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let now = old + Duration::days(30);
+
+        let mut finished = ImportJob::new(&["a".to_string()], old);
+        finished.id = "finished".to_string();
+        finished.entries[0].status = EntryStatus::Imported;
+        let mut unfinished = ImportJob::new(&["b".to_string()], old);
+        unfinished.id = "unfinished".to_string();
+        let mut recent = finished.clone();
+        recent.id = "recent".to_string();
+        recent.updated_at = now;
+        for job in [&finished, &unfinished, &recent] {
+            store.save(job).unwrap();
+        }
+
+        assert_eq!(store.prune(now).unwrap(), 1);
+        let ids: Vec<String> = store.list().unwrap().into_iter().map(|j| j.id).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(!ids.contains(&"finished".to_string()));
+        assert!(store.load("../settings").is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use crate::redaction::mask_token;
+use crate::token_auth::extract_user_id_from_jwt;
+use crate::types::Account;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+/// File next to the account CSV that `IdentityStore` keeps
+pub const IDENTITIES_FILE: &str = "verified_identities.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Several emails store the exact same access token
+    SharedToken,
+    /// Different tokens, but all issued to the same Cursor user
+    SharedUserId,
+    /// The token belongs to another user than the one verified for this email
+    IdentityMismatch,
+}
+
+/// Accounts whose credentials don't match the email they are stored under
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CredentialConflict {
+    pub kind: ConflictKind,
+    /// User ID from the token, or the masked token when it isn't a readable JWT
+    pub subject: String,
+    pub emails: Vec<String>,
+}
+
+/// Emails mapped to the user ID seen when the account was verified against Cursor
+/// (a sync from Cursor's own login, a token import or a resolved conflict)
+pub struct IdentityStore {
+    file_path: PathBuf,
+}
+
+impl IdentityStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    pub fn load(&self) -> Result<HashMap<String, String>> {
+        if !self.file_path.exists() {
+            return Ok(HashMap::new());
+        }
+        let content =
+            fs::read_to_string(&self.file_path).context("Failed to read verified identities")?;
+        serde_json::from_str(&content).context("Failed to parse verified identities")
+    }
+
+    /// Remember who owns `access_token`; tokens that aren't JWTs are ignored
+    pub fn record(&self, email: &str, access_token: &str) -> Result<()> {
+        let Ok(user_id) = extract_user_id_from_jwt(access_token) else {
+            return Ok(());
+        };
+        let mut identities = self.load()?;
+        if identities.get(email) == Some(&user_id) {
+            return Ok(());
+        }
+        identities.insert(email.to_string(), user_id);
+
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&identities)?;
+        fs::write(&self.file_path, content).context("Failed to write verified identities")
+    }
+}
+
+/// Group accounts that share credentials across emails, plus accounts whose token
+/// no longer matches the identity verified for their email
+pub fn find_credential_conflicts(
+    accounts: &[Account],
+    verified: &HashMap<String, String>,
+) -> Vec<CredentialConflict> {
+    // Sorted keys keep the report stable between runs
+    let mut groups: BTreeMap<String, Vec<&Account>> = BTreeMap::new();
+    let mut conflicts = Vec::new();
+
+    for account in accounts.iter().filter(|a| !a.access_token.is_empty()) {
+        match extract_user_id_from_jwt(account.access_token.expose()) {
+            Ok(user_id) => {
+                if let Some(expected) = verified.get(&account.email) {
+                    if *expected != user_id {
+                        conflicts.push(CredentialConflict {
+                            kind: ConflictKind::IdentityMismatch,
+                            subject: user_id.clone(),
+                            emails: vec![account.email.clone()],
+                        });
+                    }
+                }
+                groups.entry(user_id).or_default().push(account);
+            }
+            Err(_) => groups
+                .entry(mask_token(account.access_token.expose()))
+                .or_default()
+                .push(account),
+        }
+    }
+
+    for (subject, members) in groups {
+        let mut emails: Vec<String> = members.iter().map(|a| a.email.clone()).collect();
+        emails.sort();
+        emails.dedup();
+        if emails.len() < 2 {
+            continue;
+        }
+
+        let same_token = members
+            .iter()
+            .all(|a| a.access_token == members[0].access_token);
+        conflicts.push(CredentialConflict {
+            kind: if same_token {
+                ConflictKind::SharedToken
+            } else {
+                ConflictKind::SharedUserId
+            },
+            subject,
+            emails,
+        });
+    }
+
+    conflicts
+}
+
+/// Keep `keep_email` and drop the other rows it conflicts with, filling any
+/// credentials the kept row lacks from them. Returns the removed emails.
+pub fn resolve_conflict(
+    accounts: &mut Vec<Account>,
+    conflicts: &[CredentialConflict],
+    keep_email: &str,
+) -> Vec<String> {
+    let mut removed: Vec<String> = conflicts
+        .iter()
+        .filter(|c| c.emails.iter().any(|e| e == keep_email))
+        .flat_map(|c| c.emails.iter())
+        .filter(|e| *e != keep_email)
+        .cloned()
+        .collect();
+    removed.sort();
+    removed.dedup();
+
+    let (dropped, kept): (Vec<Account>, Vec<Account>) = std::mem::take(accounts)
+        .into_iter()
+        .partition(|a| removed.contains(&a.email));
+    *accounts = kept;
+
+    if let Some(target) = accounts.iter_mut().find(|a| a.email == keep_email) {
+        for other in &dropped {
+            if target.refresh_token.is_empty() {
+                target.refresh_token = other.refresh_token.clone();
+            }
+            if target.cookie.is_empty() {
+                target.cookie = other.cookie.clone();
+            }
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_support::account;
+
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
+    fn jwt(sub: &str, nonce: &str) -> String {
+        let payload =
+            URL_SAFE_NO_PAD.encode(format!(r#"{{"sub":"auth0|{}","n":"{}"}}"#, sub, nonce));
+        format!("eyJhbGciOiJIUzI1NiJ9.{}.signature", payload)
+    }
+
+    #[test]
+    fn test_find_shared_credentials() {
+        let accounts = vec![
+            Account {
+                access_token: jwt("user_a", "1").into(),
+                ..account("a@x.com")
+            },
+            Account {
+                access_token: jwt("user_a", "1").into(),
+                ..account("mislabeled@x.com")
+            },
+            Account {
+                access_token: jwt("user_b", "1").into(),
+                ..account("b@x.com")
+            },
+            Account {
+                access_token: jwt("user_b", "2").into(),
+                ..account("b2@x.com")
+            },
+            Account {
+                access_token: jwt("user_c", "1").into(),
+                ..account("c@x.com")
+            },
+        ];
+
+        let conflicts = find_credential_conflicts(&accounts, &HashMap::new());
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].kind, ConflictKind::SharedToken);
+        assert_eq!(conflicts[0].emails, vec!["a@x.com", "mislabeled@x.com"]);
+        assert_eq!(conflicts[1].kind, ConflictKind::SharedUserId);
+        assert_eq!(conflicts[1].subject, "user_b");
+    }
+
+    #[test]
+    fn test_identity_mismatch() {
+        let accounts = vec![Account {
+            access_token: jwt("user_b", "1").into(),
+            ..account("a@x.com")
+        }];
+        let verified = HashMap::from([("a@x.com".to_string(), "user_a".to_string())]);
+
+        let conflicts = find_credential_conflicts(&accounts, &verified);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::IdentityMismatch);
+        assert_eq!(conflicts[0].emails, vec!["a@x.com"]);
+    }
+
+    #[test]
+    fn test_resolve_conflict_keeps_one_row() {
+        let token = jwt("user_a", "1");
+        let mut accounts = vec![
+            Account {
+                access_token: token.as_str().into(),
+                refresh_token: Default::default(),
+                ..account("a@x.com")
+            },
+            Account {
+                access_token: token.as_str().into(),
+                ..account("mislabeled@x.com")
+            },
+            Account {
+                access_token: jwt("user_c", "1").into(),
+                ..account("c@x.com")
+            },
+        ];
+        accounts[1].refresh_token = "refresh".into();
+
+        let conflicts = find_credential_conflicts(&accounts, &HashMap::new());
+        let removed = resolve_conflict(&mut accounts, &conflicts, "a@x.com");
+
+        assert_eq!(removed, vec!["mislabeled@x.com"]);
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].refresh_token.expose(), "refresh");
+        assert!(find_credential_conflicts(&accounts, &HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_identity_store_records_jwt_subject() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = IdentityStore::new(temp_dir.path().join("identities.json"));
+
+        store.record("a@x.com", &jwt("user_a", "1")).unwrap();
+        store.record("b@x.com", "not-a-jwt").unwrap();
+
+        let identities = store.load().unwrap();
+        assert_eq!(identities.len(), 1);
+        assert_eq!(identities["a@x.com"], "user_a");
+    }
+}
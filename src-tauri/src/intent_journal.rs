@@ -0,0 +1,135 @@
+use crate::operations::SwitchOptions;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// A destructive operation that was started but not yet known to have finished
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum JournaledOperation {
+    Switch {
+        email: String,
+        options: SwitchOptions,
+    },
+    ResetMachineId,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    #[serde(flatten)]
+    pub operation: JournaledOperation,
+    pub started_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterruptedAction {
+    /// Run the operation again from the start
+    Resume,
+    /// Forget about it and leave Cursor as it is
+    Discard,
+}
+
+/// Single-entry journal in the app data dir. An entry is written right before
+/// Cursor's files are touched and removed once they are consistent again, so a
+/// leftover entry means the app died halfway through.
+pub struct IntentJournal {
+    file_path: PathBuf,
+}
+
+/// Proof that an entry was written; `complete` removes it
+#[must_use = "dropping the guard leaves the entry behind as interrupted"]
+pub struct JournalGuard<'a> {
+    journal: &'a IntentJournal,
+}
+
+impl IntentJournal {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    pub fn begin(&self, operation: JournaledOperation) -> Result<JournalGuard<'_>> {
+        let entry = JournalEntry {
+            operation,
+            started_at: Utc::now(),
+        };
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Written next to the journal and renamed so a crash never leaves half an entry
+        let temp_path = self.file_path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(&entry)?)
+            .context("Failed to write operation journal")?;
+        fs::rename(&temp_path, &self.file_path).context("Failed to write operation journal")?;
+        Ok(JournalGuard { journal: self })
+    }
+
+    /// The entry left by an interrupted operation, if any
+    pub fn pending(&self) -> Result<Option<JournalEntry>> {
+        if !self.file_path.exists() {
+            return Ok(None);
+        }
+        let content =
+            fs::read_to_string(&self.file_path).context("Failed to read operation journal")?;
+        let entry = serde_json::from_str(&content).context("Operation journal is corrupt")?;
+        Ok(Some(entry))
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.file_path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).context("Failed to clear operation journal"),
+        }
+    }
+}
+
+impl JournalGuard<'_> {
+    pub fn complete(self) -> Result<()> {
+        self.journal.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_survives_until_completed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let journal = IntentJournal::new(temp_dir.path().join("pending_operation.json"));
+        assert_eq!(journal.pending().unwrap(), None);
+
+        let operation = JournaledOperation::Switch {
+            email: "alice@example.com".to_string(),
+            options: SwitchOptions {
+                reset_machine: true,
+                ..Default::default()
+            },
+        };
+        let guard = journal.begin(operation.clone()).unwrap();
+
+        // A second handle, as after a restart, sees the entry
+        let reopened = IntentJournal::new(temp_dir.path().join("pending_operation.json"));
+        assert_eq!(reopened.pending().unwrap().unwrap().operation, operation);
+
+        guard.complete().unwrap();
+        assert_eq!(journal.pending().unwrap(), None);
+        journal.clear().unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_journal_is_an_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("pending_operation.json");
+        fs::write(&path, "{ not json").unwrap();
+
+        let journal = IntentJournal::new(path);
+        assert!(journal.pending().is_err());
+        journal.clear().unwrap();
+        assert_eq!(journal.pending().unwrap(), None);
+    }
+}
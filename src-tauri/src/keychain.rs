@@ -0,0 +1,38 @@
+// Passwords kept in the OS keychain (Keychain on macOS, Credential Manager on
+// Windows, the kernel keyring on Linux) instead of the settings file.
+
+use anyhow::{Context, Result};
+use keyring::Entry;
+
+/// Service name every entry of this app is filed under
+const SERVICE: &str = "cursor-account-switcher";
+
+/// Keychain entry holding the WebDAV sync password
+pub const WEBDAV_PASSWORD: &str = "webdav-password";
+
+fn entry(name: &str) -> Result<Entry> {
+    Entry::new(SERVICE, name).context("Failed to open the OS keychain")
+}
+
+/// The stored password, None if it was never set
+pub fn get_password(name: &str) -> Result<Option<String>> {
+    match entry(name)?.get_password() {
+        Ok(password) => Ok(Some(password)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e).context("Failed to read from the OS keychain"),
+    }
+}
+
+/// Store `password`, or remove the entry when it is None
+pub fn set_password(name: &str, password: Option<&str>) -> Result<()> {
+    let entry = entry(name)?;
+    match password {
+        Some(password) => entry
+            .set_password(password)
+            .context("Failed to write to the OS keychain"),
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e).context("Failed to remove from the OS keychain"),
+        },
+    }
+}
@@ -0,0 +1,79 @@
+//! Account storage, Cursor integration and the switch, sync and refresh flows.
+//! The Tauri commands in main.rs are thin wrappers around these modules, so the
+//! flows can be tested against temp dirs without a running app.
+
+pub mod account_backups;
+pub mod account_code;
+pub mod account_label;
+pub mod account_store;
+pub mod api_client;
+pub mod app_info;
+pub mod app_lock;
+pub mod auto_export;
+pub mod autostart;
+pub mod change_bus;
+pub mod change_set;
+pub mod cli;
+pub mod clipboard_import;
+pub mod clipboard_watcher;
+pub mod comparison;
+pub mod connectivity;
+pub mod coordinator;
+pub mod csv_manager;
+pub mod cursor_settings;
+pub mod cursor_versions;
+pub mod data_location;
+pub mod database;
+pub mod deep_link;
+pub mod detailed_usage_client;
+pub mod error;
+pub mod file_cache;
+pub mod forecast;
+pub mod http;
+pub mod http_api;
+pub mod i18n;
+pub mod import_jobs;
+pub mod integrity;
+pub mod intent_journal;
+pub mod json_import;
+pub mod keychain;
+pub mod logger;
+pub mod machine_id;
+pub mod notifier;
+pub mod onboarding;
+pub mod operations;
+pub mod path_detector;
+pub mod pool_manifest;
+pub mod pools;
+pub mod process_utils;
+pub mod recommendation;
+pub mod redaction;
+pub mod refresh_state;
+pub mod report;
+pub mod reset_machine;
+pub mod scheduler;
+pub mod secret;
+pub mod self_test;
+pub mod settings;
+pub mod single_instance;
+pub mod sqlite_store;
+pub mod state;
+pub mod state_backups;
+pub mod store_crypto;
+pub mod switch_cooldown;
+pub mod switch_hooks;
+pub mod switch_log;
+pub mod switch_verification;
+pub mod tags;
+pub mod token_auth;
+pub mod token_health;
+pub mod tray;
+pub mod types;
+pub mod update_checker;
+pub mod usage_history;
+pub mod validation;
+pub mod webdav_sync;
+pub mod xlsx_export;
+
+#[cfg(test)]
+mod test_support;
@@ -1,4 +1,8 @@
-use crate::types::MachineIds;
+use crate::types::{CurrentMachineIds, MachineIdSource, MachineIds};
+use anyhow::Context;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
 use uuid::Uuid;
 
 pub struct MachineIdGenerator;
@@ -19,15 +23,59 @@ impl MachineIdGenerator {
             mac_machine_id,
             dev_device_id,
             sqm_id,
+            generated_at: Some(chrono::Utc::now()),
+            applied_to: Vec::new(),
+            source: MachineIdSource::Generated,
+            preserved_settings: None,
+            changes: Vec::new(),
         }
     }
 }
 
+/// The IDs in the storage.json at `storage_path`, plus the registry GUID.
+/// A missing file reads as no IDs at all; one that isn't JSON is an error.
+pub fn read_current(storage_path: &Path) -> anyhow::Result<CurrentMachineIds> {
+    let storage: Value = if storage_path.exists() {
+        let content = fs::read_to_string(storage_path)
+            .with_context(|| format!("Failed to read {}", storage_path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("{} is not valid JSON", storage_path.display()))?
+    } else {
+        Value::Null
+    };
+    let get = |key: &str| storage.get(key).and_then(Value::as_str).map(str::to_string);
+
+    Ok(CurrentMachineIds {
+        machine_id: get("telemetry.machineId"),
+        mac_machine_id: get("telemetry.macMachineId"),
+        dev_device_id: get("telemetry.devDeviceId"),
+        sqm_id: get("telemetry.sqmId"),
+        registry_guid: read_registry_machine_guid(),
+    })
+}
+
+/// Read the registry machine GUID (Windows only). Reading needs no
+/// administrator privileges; None if it can't be read.
+#[cfg(target_os = "windows")]
+pub fn read_registry_machine_guid() -> Option<String> {
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags(r"SOFTWARE\Microsoft\Cryptography", KEY_READ)
+        .and_then(|key| key.get_value("MachineGuid"))
+        .ok()
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn read_registry_machine_guid() -> Option<String> {
+    None
+}
+
 /// Update registry machine GUID (Windows only)
 /// On non-Windows platforms, this is a no-op that returns Ok(())
 #[cfg(target_os = "windows")]
 pub fn update_registry_machine_guid() -> anyhow::Result<()> {
-    use anyhow::Context;
     use winreg::enums::*;
     use winreg::RegKey;
 
@@ -105,4 +153,64 @@ mod tests {
         let deserialized: Result<MachineIds, _> = serde_json::from_str(&json.unwrap());
         assert!(deserialized.is_ok());
     }
+
+    #[test]
+    fn test_read_current_ids() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path().join("storage.json");
+        fs::write(
+            &storage_path,
+            r#"{
+                "telemetry.machineId": "m",
+                "telemetry.macMachineId": "mac",
+                "telemetry.devDeviceId": "dev",
+                "telemetry.sqmId": "{SQM}",
+                "theme": "dark"
+            }"#,
+        )
+        .unwrap();
+        let ids = read_current(&storage_path).unwrap();
+        assert_eq!(ids.machine_id.as_deref(), Some("m"));
+        assert_eq!(ids.mac_machine_id.as_deref(), Some("mac"));
+        assert_eq!(ids.dev_device_id.as_deref(), Some("dev"));
+        assert_eq!(ids.sqm_id.as_deref(), Some("{SQM}"));
+
+        // Keys that aren't there, or not strings, are None
+        fs::write(
+            &storage_path,
+            r#"{"telemetry.machineId": "m", "telemetry.sqmId": 5}"#,
+        )
+        .unwrap();
+        let ids = read_current(&storage_path).unwrap();
+        assert_eq!(ids.machine_id.as_deref(), Some("m"));
+        assert_eq!(ids.mac_machine_id, None);
+        assert_eq!(ids.dev_device_id, None);
+        assert_eq!(ids.sqm_id, None);
+
+        let missing = read_current(&temp_dir.path().join("missing.json")).unwrap();
+        assert_eq!(missing.machine_id, None);
+        assert_eq!(missing.sqm_id, None);
+
+        fs::write(&storage_path, "not json").unwrap();
+        assert!(read_current(&storage_path).is_err());
+    }
+
+    #[test]
+    fn test_plain_machine_ids_json_still_loads() {
+        let json = r#"{
+            "machine_id": "a",
+            "mac_machine_id": "b",
+            "dev_device_id": "c",
+            "sqm_id": "{D}"
+        }"#;
+        let ids: MachineIds = serde_json::from_str(json).unwrap();
+        assert_eq!(ids.sqm_id, "{D}");
+        assert_eq!(ids.generated_at, None);
+        assert!(ids.applied_to.is_empty());
+        assert_eq!(ids.source, MachineIdSource::Generated);
+
+        let generated = MachineIdGenerator::generate();
+        assert!(generated.generated_at.is_some());
+        assert_eq!(generated.source, MachineIdSource::Generated);
+    }
 }
@@ -1,564 +1,4492 @@
 // Prevents additional console window on Windows in release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod api_client;
-mod csv_manager;
-mod database;
-mod detailed_usage_client;
-mod logger;
-mod machine_id;
-mod path_detector;
-mod process_utils;
-mod reset_machine;
-mod token_auth;
-mod types;
+use cursor_switcher::{
+    account_backups, account_code, account_label, account_store, api_client, app_info, app_lock,
+    auto_export, autostart, change_bus, change_set, cli, clipboard_import, clipboard_watcher,
+    comparison, connectivity, coordinator, csv_manager, cursor_settings, cursor_versions,
+    data_location, database, deep_link, detailed_usage_client, error, forecast, http, http_api,
+    i18n, import_jobs, integrity, intent_journal, json_import, keychain, logger, machine_id,
+    notifier, onboarding, operations, path_detector, pool_manifest, pools, process_utils,
+    recommendation, redaction, refresh_state, report, reset_machine, scheduler, secret, self_test,
+    settings, single_instance, state, state_backups, switch_cooldown, switch_hooks, switch_log,
+    switch_verification, tags, token_auth, token_health, tray, types, update_checker,
+    usage_history, validation, webdav_sync, xlsx_export,
+};
 
+use account_code::AccountCodeImport;
+use account_store::{AccountStore, StoreBackend};
 use api_client::CursorApiClient;
-use csv_manager::CsvManager;
+use app_info::AppInfo;
+use app_lock::AppLockStatus;
+use auto_export::ExportReport;
+use autostart::{AutostartManager, AutostartStatus};
+use change_set::{ChangeOperation, ChangeSet, ChangeSetStore, CHANGE_SETS_FILE};
+use clipboard_import::{ClipboardFormat, ClipboardImport};
+use clipboard_watcher::{ClipboardWatcher, DetectedToken};
+use comparison::AccountComparison;
+use connectivity::ConnectivityStatus;
+use coordinator::{Admission, Operation, TrayAction, TrayActionResult};
+use csv_manager::{AccountPage, AccountQuery, CorruptRow, CsvManager, MergeReport, TextImport};
+use cursor_settings::{RestoreMode, SettingsRestore, SettingsSnapshotStore, SnapshotSummary};
+use cursor_versions::{CursorVersionStore, VersionObservation, CURSOR_VERSIONS_FILE};
+use data_location::ExistingStore;
 use database::Database;
+use deep_link::DeepLink;
 use detailed_usage_client::DetailedUsageClient;
+use error::{AppError, ErrorText};
+use forecast::AccountForecast;
+use http::HttpClientFactory;
+use http_api::{ApiRoute, HttpApiServer};
+use i18n::{t, tf, Locale};
+use import_jobs::{ImportJob, ImportJobStore, ImportJobSummary};
+use integrity::{CredentialConflict, IdentityStore};
+use intent_journal::{InterruptedAction, JournalEntry, JournaledOperation};
 use logger::{LogEntry, Logger};
+use onboarding::{OnboardingStatus, OnboardingStep};
+use operations::{
+    BatchRefresh, SwitchOptions, SwitchOutcome, SwitchPlan, SwitchReport, SyncOutcome,
+};
 use path_detector::PathDetector;
+use pool_manifest::{GeneratedKeys, PoolImportReport, PublicKeys};
+use pools::PoolSummary;
 use process_utils::ProcessManager;
-use reset_machine::MachineIdResetter;
+use recommendation::Recommendation;
+use refresh_state::{RefreshInfo, RefreshStateStore, RefreshTrigger};
+use report::{FleetStats, ReportColumn, ReportFormat};
+use reqwest::header::HeaderMap;
+use reset_machine::{MachineIdResetter, PatchStatus, ResetCheck};
+use scheduler::PeriodicTask;
+use secret::SecretString;
+use self_test::{CheckResult, SelfTestContext};
+use settings::{Settings, SettingsImportReport, SettingsManager, SwitchHook};
+use single_instance::{InstanceMessage, SingleInstance};
+use state::{AppState, MutexExt};
+use switch_cooldown::{CooldownMode, SwitchHistoryStore, SWITCH_HISTORY_FILE};
+use switch_hooks::HookContext;
+use switch_log::{SwitchLog, SwitchLogEntry, SwitchSource, SWITCH_LOG_FILE};
+use switch_verification::{SettleWait, SwitchRevert, SwitchRevertStore, SWITCH_REVERTS_FILE};
+use token_auth::TokenAuthClient;
+use token_health::{TokenHealth, TokenHealthReport};
+use tray::{TrayAccountGroup, TrayMenuExtras, TrayMenuModel, TrayOperationFailure};
 use types::*;
+use update_checker::{UpdateChecker, UpdateInfo};
+use usage_history::{UsageHistoryStore, UsageSnapshot, USAGE_HISTORY_FILE};
+use validation::Platform;
+use webdav_sync::{SyncConflict, SyncReport, SyncSide, WebDavClient};
 
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::collections::BTreeMap;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tauri::{
-    CustomMenuItem, Manager, State, SystemTray, SystemTrayEvent, SystemTrayMenu,
-    SystemTrayMenuItem, WindowEvent,
+    ClipboardManager, CustomMenuItem, Manager, State, SystemTray, SystemTrayEvent, SystemTrayMenu,
+    SystemTrayMenuItem, SystemTraySubmenu, WindowEvent,
 };
-use tracing_appender::non_blocking::WorkerGuard;
 
-// Global state
-struct AppState {
-    csv_path: Mutex<PathBuf>,
-    cursor_base_path: Mutex<Option<PathBuf>>,
-    log_path: Mutex<PathBuf>,
-    _log_guard: Mutex<Option<WorkerGuard>>,
+#[tauri::command]
+fn get_data_storage_path(state: State<AppState>) -> Result<String, AppError> {
+    Ok(open_store(&state).path().to_string_lossy().to_string())
 }
 
-// Initialize app state with placeholder
-fn init_app_state() -> AppState {
-    // Placeholder - will be set properly in setup()
-    AppState {
-        csv_path: Mutex::new(PathBuf::from(".")),
-        cursor_base_path: Mutex::new(None),
-        log_path: Mutex::new(PathBuf::from(".")),
-        _log_guard: Mutex::new(None),
+/// Keep the accounts in `path` from now on, e.g. a synced folder. With
+/// `migrate` the current ones, their backups and the files kept with them,
+/// see `data_location::SIDECARS`, are copied there; accounts
+/// already there are only replaced or merged into as `existing` says.
+/// Returns the new `get_data_storage_path`.
+#[tauri::command]
+fn set_data_storage_path(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    path: String,
+    migrate: bool,
+    existing: Option<ExistingStore>,
+) -> Result<String, AppError> {
+    ensure_unlocked(&state)?;
+    let dir = std::path::PathBuf::from(path.trim());
+    if !dir.is_absolute() {
+        return Err(AppError::InvalidInput {
+            field: "path",
+            reason: "must be an absolute path".to_string(),
+        });
+    }
+    let settings = load_settings(&state);
+    let previous = state.csv_path();
+    {
+        let _operation = state
+            .operations
+            .begin_when_idle(Operation::Importing, IMPORT_WRITE_TIMEOUT)?;
+        let csv_path =
+            data_location::relocate(&previous, &dir, migrate, existing, settings.account_store)
+                .map_err(AppError::from)?;
+        state.set_csv_path(csv_path);
+    }
+
+    let settings = Settings {
+        data_storage_dir: Some(dir.to_string_lossy().into_owned()),
+        ..settings
+    };
+    if let Err(e) = apply_settings(&app, &state, settings) {
+        state.set_csv_path(previous);
+        return Err(e);
     }
+    tracing::info!("Data will be stored at: {}", state.csv_path().display());
+    state.accounts_changed.notify();
+    get_data_storage_path(state)
+}
+
+/// Version, build and data locations for the about screen and bug reports
+#[tauri::command]
+fn get_app_info(app: tauri::AppHandle, state: State<AppState>) -> Result<AppInfo, AppError> {
+    let app_data_dir = tauri::api::path::app_data_dir(&app.config());
+    let cursor_path = state.cursor_base().ok();
+    Ok(AppInfo {
+        is_offline: state.is_offline(),
+        csv_path: Some(state.csv_path().to_string_lossy().to_string()),
+        ..AppInfo::collect(
+            &app.package_info().version.to_string(),
+            app_data_dir.as_deref(),
+            cursor_path.as_deref(),
+        )
+    })
 }
 
+/// Probe the network now instead of waiting for the next background check
 #[tauri::command]
-fn get_data_storage_path(state: State<AppState>) -> Result<String, String> {
-    let csv_path = state.csv_path.lock().unwrap();
-    Ok(csv_path.to_string_lossy().to_string())
+fn check_connectivity(app: tauri::AppHandle) -> Result<ConnectivityStatus, AppError> {
+    Ok(update_connectivity(&app))
+}
+
+// Probe and record the result. Going offline or back online is announced with
+// `connectivity-changed`, so the frontend can refresh once the network is back.
+fn update_connectivity(app: &tauri::AppHandle) -> ConnectivityStatus {
+    let state: State<AppState> = app.state();
+    let status = ConnectivityStatus {
+        offline: !connectivity::probe(&state.http()),
+    };
+    if state.set_offline(status.offline) {
+        if status.offline {
+            tracing::warn!("Cursor's API is unreachable, switching to offline mode");
+        } else {
+            tracing::info!("Connection restored, leaving offline mode");
+        }
+        let _ = app.emit_all("connectivity-changed", status);
+    }
+    status
+}
+
+fn start_connectivity_check(app: &tauri::AppHandle) {
+    let state: State<AppState> = app.state();
+    let handle = app.clone();
+    *state.connectivity_task.lock_or_recover() = Some(PeriodicTask::spawn(
+        "connectivity",
+        Duration::ZERO,
+        connectivity::CHECK_INTERVAL,
+        move || {
+            update_connectivity(&handle);
+        },
+    ));
 }
 
 #[tauri::command]
-fn detect_cursor_path() -> Result<String, String> {
+fn detect_cursor_path() -> Result<String, AppError> {
     PathDetector::detect_cursor_path()
         .map(|p| p.to_string_lossy().to_string())
-        .map_err(|e| e.to_string())
+        .map_err(AppError::from)
 }
 
 #[tauri::command]
-fn set_cursor_path(state: State<AppState>, path: String) -> Result<(), String> {
-    let path_buf = PathBuf::from(path);
-    let mut cursor_path = state.cursor_base_path.lock().unwrap();
-    *cursor_path = Some(path_buf);
+fn set_cursor_path(state: State<AppState>, path: String) -> Result<(), AppError> {
+    let path = validation::existing_dir("path", &path)?;
+    state.set_cursor_base(path);
     Ok(())
 }
 
 #[tauri::command]
-fn get_current_account_info(state: State<AppState>) -> Result<AccountInfo, String> {
-    tracing::info!("Fetching current account info");
-    let cursor_path = state.cursor_base_path.lock().unwrap();
-    let base_path = cursor_path.as_ref().ok_or("Cursor path not set")?;
+fn get_current_account_info(
+    state: State<AppState>,
+    refresh: Option<bool>,
+) -> Result<MaybeStale<AccountInfo>, AppError> {
+    current_account_info(&state, refresh.unwrap_or(false))
+}
+
+#[tauri::command]
+fn get_usage_info(
+    state: State<AppState>,
+    refresh: Option<bool>,
+) -> Result<MaybeStale<UsageInfo>, AppError> {
+    current_usage_info(&state, refresh.unwrap_or(false))
+}
+
+fn self_test(state: &AppState) -> Vec<CheckResult> {
+    let context = SelfTestContext {
+        settings_path: state.settings_path(),
+        csv_path: state.csv_path(),
+        cursor_base: state.cursor_base().ok(),
+    };
+    let http = state.http();
+    let results = self_test::run(
+        &context,
+        |url| self_test::probe_url(&http, url),
+        |access_token| {
+            TokenAuthClient::check_with_timeout(&http, self_test::NETWORK_TIMEOUT, access_token)
+        },
+    );
+
+    let failed = results.iter().filter(|r| !r.passed).count();
+    tracing::info!(
+        "Self-test finished: {} of {} checks passed",
+        results.len() - failed,
+        results.len()
+    );
+    results
+}
+
+/// Non-destructive checks of everything a switch depends on, each with a hint on failure
+#[tauri::command]
+fn run_self_test(state: State<AppState>) -> Result<Vec<CheckResult>, AppError> {
+    Ok(self_test(&state))
+}
+
+/// Cursor's privacy mode; None when it was never set
+#[tauri::command]
+fn get_privacy_mode(state: State<AppState>) -> Result<Option<bool>, AppError> {
+    let base_path = state.cursor_base()?;
+    Database::new(PathDetector::get_db_path(&base_path))
+        .get_privacy_mode()
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn set_privacy_mode(state: State<AppState>, enabled: bool) -> Result<PrivacyModeChange, AppError> {
+    ensure_unlocked(&state)?;
+    let base_path = state.cursor_base()?;
+    Database::new(PathDetector::get_db_path(&base_path))
+        .set_privacy_mode(enabled)
+        .map_err(AppError::from)?;
+
+    let restart_required = ProcessManager::is_cursor_running();
+    if restart_required {
+        tracing::warn!("Privacy mode set to {} while Cursor is running", enabled);
+    } else {
+        tracing::info!("Privacy mode set to {}", enabled);
+    }
+    Ok(PrivacyModeChange {
+        enabled,
+        restart_required,
+    })
+}
 
-    let db_path = PathDetector::get_db_path(base_path);
+fn current_auth_info(state: &AppState) -> Result<(String, String), AppError> {
+    let base_path = state.cursor_base()?;
+
+    let db_path = PathDetector::get_db_path(&base_path);
     let db = Database::new(db_path);
 
-    let (email, access_token) = db.get_auth_info().map_err(|e| {
+    db.get_auth_info().map_err(|e| {
         tracing::error!("Failed to get auth info: {}", e);
-        e.to_string()
-    })?;
+        AppError::from(e)
+    })
+}
+
+// Served from memory for CURRENT_ACCOUNT_TTL unless `refresh` is set. While
+// offline the last fetched or stored data is served, flagged as stale.
+fn current_account_info(
+    state: &AppState,
+    refresh: bool,
+) -> Result<MaybeStale<AccountInfo>, AppError> {
+    tracing::info!("Fetching current account info");
+    let (email, access_token) = current_auth_info(state)?;
+
+    // Read from Cursor's database every time since it can change without an API call
+    let privacy_mode = state.cursor_base().ok().and_then(|base| {
+        Database::new(PathDetector::get_db_path(&base))
+            .get_privacy_mode()
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to read privacy mode: {}", e);
+                None
+            })
+    });
+    let with_privacy = |info: AccountInfo| AccountInfo {
+        privacy_mode,
+        ..info
+    };
+
+    let _fetch = state.current_account_fetch.lock_or_recover();
+    if state.is_offline() {
+        tracing::debug!("Offline, serving stored account info for: {}", email);
+        if let Some((info, age)) = state.last_account_info(&email) {
+            return Ok(MaybeStale::stale(with_privacy(info), Some(age)));
+        }
+        let account = offline_stored_account(state, &email)?;
+        return Ok(MaybeStale::stale(
+            with_privacy(connectivity::stored_account_info(&account)),
+            connectivity::stored_age(&account, chrono::Local::now().naive_local()),
+        ));
+    }
+    if let Some((info, age)) = state.cached_account_info(&email).filter(|_| !refresh) {
+        tracing::debug!("Serving cached account info for: {}", email);
+        return Ok(MaybeStale::cached(with_privacy(info), age));
+    }
 
     tracing::debug!("Fetching account info for: {}", email);
-    let api_client = CursorApiClient::new();
-    api_client
-        .get_account_info(&email, &access_token)
+    let info = CursorApiClient::new(&state.http())
+        .and_then(|api_client| {
+            api_client.get_account_info(&email, &access_token, &account_headers(state, &email))
+        })
         .map_err(|e| {
             tracing::error!("Failed to fetch account info: {}", e);
-            e.to_string()
-        })
+            AppError::from(e)
+        })?;
+    state.cache_account_info(&email, info.clone());
+    Ok(MaybeStale::fresh(with_privacy(info)))
 }
 
-#[tauri::command]
-fn get_usage_info(state: State<AppState>) -> Result<UsageInfo, String> {
-    let cursor_path = state.cursor_base_path.lock().unwrap();
-    let base_path = cursor_path.as_ref().ok_or("Cursor path not set")?;
+fn current_usage_info(state: &AppState, refresh: bool) -> Result<MaybeStale<UsageInfo>, AppError> {
+    let (email, access_token) = current_auth_info(state)?;
 
-    let db_path = PathDetector::get_db_path(base_path);
-    let db = Database::new(db_path);
+    let _fetch = state.current_account_fetch.lock_or_recover();
+    if state.is_offline() {
+        if let Some((usage, age)) = state.last_usage_info(&email) {
+            return Ok(MaybeStale::stale(usage, Some(age)));
+        }
+        let account = offline_stored_account(state, &email)?;
+        let usage = connectivity::stored_usage_info(&account)
+            .ok_or_else(|| format!("Offline, and no usage is stored for {}", email))?;
+        return Ok(MaybeStale::stale(
+            usage,
+            connectivity::stored_age(&account, chrono::Local::now().naive_local()),
+        ));
+    }
+    if let Some((usage, age)) = state.cached_usage_info(&email).filter(|_| !refresh) {
+        return Ok(MaybeStale::cached(usage, age));
+    }
+
+    let usage = CursorApiClient::new(&state.http())
+        .and_then(|api_client| {
+            api_client.get_usage_info(&access_token, &account_headers(state, &email))
+        })
+        .map_err(AppError::from)?;
+    state.cache_usage_info(&email, usage.clone());
+    Ok(MaybeStale::fresh(usage))
+}
 
-    let (_, access_token) = db.get_auth_info().map_err(|e| e.to_string())?;
+// Extra headers of the stored account, none when it isn't stored
+fn account_headers(state: &AppState, email: &str) -> HeaderMap {
+    let account = open_store(state)
+        .find_account_by_email(email)
+        .ok()
+        .flatten();
+    http::extra_headers(account.and_then(|account| account.extra_headers).as_ref())
+}
 
-    let api_client = CursorApiClient::new();
-    api_client
-        .get_usage_info(&access_token)
-        .map_err(|e| e.to_string())
+// Session token of the logged-in account, with the extra headers of its stored row
+fn dashboard_auth(state: &AppState) -> Result<(String, HeaderMap), AppError> {
+    let base_path = state.cursor_base()?;
+    let db = Database::new(PathDetector::get_db_path(&base_path));
+    let (session_token, email) =
+        operations::dashboard_session(&db, open_store(state).as_ref()).map_err(AppError::from)?;
+    let headers = match email {
+        Some(email) => account_headers(state, &email),
+        None => HeaderMap::new(),
+    };
+    Ok((session_token, headers))
 }
 
-#[tauri::command]
-fn get_all_accounts(state: State<AppState>) -> Result<Vec<Account>, String> {
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
+// The CSV row of the logged-in account, the last resort while offline
+fn offline_stored_account(state: &AppState, email: &str) -> Result<Account, AppError> {
+    open_store(state)
+        .find_account_by_email(email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| format!("Offline, and {} is not a stored account", email).into())
+}
 
-    csv_manager.ensure_csv_exists().map_err(|e| e.to_string())?;
+// Fetch what the home page shows first while the window is still loading.
+// No login or no network just means the frontend fetches it itself later.
+fn prefetch_current_account(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state: State<AppState> = app.state();
+        let account_info = current_account_info(&state, false);
+        let usage_info = current_usage_info(&state, false);
 
-    csv_manager.read_accounts().map_err(|e| e.to_string())
+        if let (Err(e), _) | (_, Err(e)) = (&account_info, &usage_info) {
+            tracing::debug!("Startup prefetch incomplete: {}", e);
+        } else {
+            tracing::info!("Startup prefetch finished");
+        }
+        let _ = app.emit_all(
+            "startup-data-ready",
+            serde_json::json!({
+                "account_info": account_info.ok(),
+                "usage_info": usage_info.ok(),
+            }),
+        );
+    });
 }
 
-#[tauri::command]
-fn add_account(state: State<AppState>, account: Account) -> Result<(), String> {
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
+// Accounts with their real tokens, for use inside the backend only
+fn live_api(state: &AppState) -> Result<CursorApiClient, AppError> {
+    CursorApiClient::new(&state.http()).map_err(AppError::from)
+}
 
-    csv_manager.add_account(account).map_err(|e| e.to_string())
+// The store the accounts are kept in, as picked by the account_store setting
+fn open_store(state: &AppState) -> Box<dyn AccountStore> {
+    let settings = load_settings(state);
+    account_store::open(
+        &state.csv_path(),
+        settings.account_store,
+        settings.account_backup_retention,
+    )
 }
 
-#[tauri::command]
-fn delete_account(state: State<AppState>, email: String) -> Result<bool, String> {
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
+fn stored_accounts(state: &AppState) -> Result<Vec<Account>, AppError> {
+    operations::list_accounts(open_store(state).as_ref()).map_err(AppError::from)
+}
 
-    csv_manager
-        .delete_account(&email)
-        .map_err(|e| e.to_string())
+/// The stored accounts in `pool` if given; archived ones only with `include_archived`
+fn listed_accounts(
+    state: &AppState,
+    pool: Option<&str>,
+    include_archived: Option<bool>,
+) -> Result<Vec<Account>, AppError> {
+    let include_archived = include_archived.unwrap_or(false);
+    let mut accounts = stored_accounts(state)?;
+    accounts.retain(|a| (include_archived || !a.archived) && pools::in_pool(a, pool));
+    Ok(accounts)
 }
 
+/// List accounts, only the members of `pool` if given and archived ones only
+/// with `include_archived`. Tokens are masked unless `redacted` is false and
+/// the app is unlocked; use `get_account_secrets` to reveal a single account's
+/// credentials.
 #[tauri::command]
-fn update_account(state: State<AppState>, email: String, account: Account) -> Result<bool, String> {
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
-
-    csv_manager
-        .update_account(&email, account)
-        .map_err(|e| e.to_string())
+fn get_all_accounts(
+    state: State<AppState>,
+    redacted: Option<bool>,
+    pool: Option<String>,
+    sort: Option<AccountSort>,
+    include_archived: Option<bool>,
+) -> Result<Vec<AccountRow>, AppError> {
+    let mut accounts = listed_accounts(&state, pool.as_deref(), include_archived)?;
+    match sort {
+        // Stable, so favorites and the others each keep their stored order
+        Some(AccountSort::Favorites) => accounts.sort_by_key(|a| !a.favorite),
+        None => {}
+    }
+    if redacted.unwrap_or(true) || app_lock_status(&state).locked {
+        accounts.iter_mut().for_each(redaction::mask_account);
+    }
+    let stale_after_hours = load_settings(&state).stale_after_hours;
+    let now = chrono::Local::now().naive_local();
+    Ok(accounts
+        .into_iter()
+        .map(|account| AccountRow {
+            stale: tray::is_stale(&account, stale_after_hours, now),
+            data_age_hours: record_time::age_hours(&account.record_time, now),
+            account,
+        })
+        .collect())
 }
 
 #[tauri::command]
-fn import_accounts(state: State<AppState>, text: String) -> Result<Vec<Account>, String> {
-    tracing::info!("Importing accounts from text");
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
+fn get_account_secrets(state: State<AppState>, email: String) -> Result<AccountSecrets, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
 
-    let result = csv_manager.parse_import_text(&text).map_err(|e| {
-        tracing::error!("Failed to parse import text: {}", e);
-        e.to_string()
-    })?;
+    let account = open_store(&state)
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| format!("Account not found: {}", email))?;
 
-    tracing::info!("Successfully parsed {} account(s)", result.len());
-    Ok(result)
+    tracing::info!("Revealed credentials of {}", account.email);
+    Ok(AccountSecrets {
+        email: account.email,
+        access_token: account.access_token,
+        refresh_token: account.refresh_token,
+        cookie: account.cookie,
+    })
 }
 
+/// The stored password of `email`, which no other command returns
 #[tauri::command]
-fn batch_add_accounts(state: State<AppState>, accounts: Vec<Account>) -> Result<(), String> {
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
+fn get_account_password(
+    state: State<AppState>,
+    email: String,
+) -> Result<Option<SecretString>, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
 
-    // Use the optimized batch add method instead of adding one by one
-    csv_manager
-        .batch_add_accounts(accounts)
-        .map_err(|e| e.to_string())?;
+    let account = open_store(&state)
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| format!("Account not found: {}", email))?;
+
+    tracing::info!("Revealed the password of {}", account.email);
+    Ok(account.password)
+}
+
+#[tauri::command]
+fn add_account(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    mut account: Account,
+) -> Result<(), AppError> {
+    validation::email("email", &account.email)?;
+    validation::token("access_token", account.access_token.expose())?;
+    account.pool = pools::normalize(account.pool)?;
+    account.password = account.password.filter(|password| !password.is_empty());
+    let store = open_store(&state);
 
+    store.add_account(account).map_err(AppError::from)?;
+    state.accounts_changed.notify();
+    warn_credential_conflicts(&app, &state);
     Ok(())
 }
 
+/// Archive the account, or with `permanent` remove it and its tokens for good
 #[tauri::command]
-fn switch_account(
+fn delete_account(
     state: State<AppState>,
     email: String,
-    access_token: String,
-    refresh_token: String,
-    reset_machine: bool,
-) -> Result<(), String> {
-    tracing::info!("Switching to account: {}", email);
-    let cursor_path = state.cursor_base_path.lock().unwrap();
-    let base_path = cursor_path.as_ref().ok_or("Cursor path not set")?.clone();
-
-    // Kill Cursor process
-    tracing::info!("Killing Cursor process");
-    ProcessManager::kill_cursor().map_err(|e| {
-        tracing::error!("Failed to kill Cursor process: {}", e);
-        e.to_string()
-    })?;
+    permanent: Option<bool>,
+) -> Result<bool, AppError> {
+    if !permanent.unwrap_or(false) {
+        return set_archived(&state, &email, true);
+    }
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
+    let store = open_store(&state);
 
-    // Update database
-    tracing::info!("Updating database with new credentials");
-    let db_path = PathDetector::get_db_path(&base_path);
-    let db = Database::new(db_path);
+    let deleted = store.delete_account(&email).map_err(AppError::from)?;
+    if deleted {
+        state.accounts_changed.notify();
+    }
+    Ok(deleted)
+}
 
-    db.update_auth(&email, &access_token, Some(&refresh_token))
-        .map_err(|e| {
-            tracing::error!("Failed to update database: {}", e);
-            e.to_string()
-        })?;
+/// Hide the account from the list, the tray and batch refreshes, keeping its
+/// tokens so it can be restored
+#[tauri::command]
+fn archive_account(state: State<AppState>, email: String) -> Result<bool, AppError> {
+    set_archived(&state, &email, true)
+}
 
-    // Reset machine ID if requested
-    if reset_machine {
-        tracing::info!("Resetting machine ID");
-        let resetter = MachineIdResetter::new(base_path.clone());
-        resetter.reset().map_err(|e| {
-            tracing::error!("Machine ID reset failed: {}", e);
-            format!("Machine ID reset failed: {}", e)
-        })?;
+#[tauri::command]
+fn restore_archived_account(state: State<AppState>, email: String) -> Result<bool, AppError> {
+    set_archived(&state, &email, false)
+}
+
+fn set_archived(state: &AppState, email: &str, archived: bool) -> Result<bool, AppError> {
+    validation::email("email", email)?;
+    ensure_unlocked(state)?;
+
+    let changed = operations::set_archived(open_store(state).as_ref(), email, archived)
+        .map_err(AppError::from)?;
+    if changed {
+        state.accounts_changed.notify();
     }
+    Ok(changed)
+}
 
-    tracing::info!("Account switch completed successfully");
-    Ok(())
+/// Rows of the account store that reads skip, for the window to warn about
+#[tauri::command]
+fn get_store_diagnostics(state: State<AppState>) -> Result<Vec<CorruptRow>, AppError> {
+    open_store(&state).corrupt_rows().map_err(AppError::from)
 }
 
 #[tauri::command]
-fn reset_machine_id(state: State<AppState>) -> Result<(), String> {
-    tracing::info!("Resetting machine ID");
-    let cursor_path = state.cursor_base_path.lock().unwrap();
-    let base_path = cursor_path.as_ref().ok_or("Cursor path not set")?.clone();
+fn list_account_backups(
+    state: State<AppState>,
+) -> Result<Vec<account_backups::BackupInfo>, AppError> {
+    account_backups::list(&state.csv_path()).map_err(AppError::from)
+}
 
-    let resetter = MachineIdResetter::new(base_path);
-    resetter.reset().map_err(|e| {
-        tracing::error!("Failed to reset machine ID: {}", e);
-        e.to_string()
-    })
+/// Replace the stored accounts with a backup from `list_account_backups`,
+/// after backing up the current ones
+#[tauri::command]
+fn restore_account_backup(state: State<AppState>, path: String) -> Result<usize, AppError> {
+    ensure_unlocked(&state)?;
+    let settings = load_settings(&state);
+    if settings.account_store != StoreBackend::Csv {
+        return Err(AppError::InvalidInput {
+            field: "path",
+            reason: "backups are only taken of the CSV store".to_string(),
+        });
+    }
+    let csv_path = state.csv_path();
+    let backup = account_backups::resolve(&csv_path, std::path::Path::new(&path)).map_err(|e| {
+        AppError::InvalidInput {
+            field: "path",
+            reason: e.to_string(),
+        }
+    })?;
+
+    let rows = CsvManager::new(csv_path)
+        .with_backups(settings.account_backup_retention)
+        .restore_backup(&backup)
+        .map_err(AppError::from)?;
+    tracing::info!("Restored {} account(s) from {}", rows, backup.display());
+    state.accounts_changed.notify();
+    Ok(rows)
 }
 
+/// Number the accounts 1..N again; deletes already do this, files edited by
+/// hand or from older versions may still have gaps
 #[tauri::command]
-fn kill_cursor_process() -> Result<(), String> {
-    ProcessManager::kill_cursor().map_err(|e| e.to_string())
+fn compact_account_indexes(state: State<AppState>) -> Result<bool, AppError> {
+    ensure_unlocked(&state)?;
+    let store = open_store(&state);
+
+    let changed = store.compact_indexes().map_err(AppError::from)?;
+    if changed {
+        state.accounts_changed.notify();
+    }
+    Ok(changed)
 }
 
 #[tauri::command]
-fn restart_cursor_process(cursor_app_path: Option<String>) -> Result<(), String> {
-    ProcessManager::restart_cursor(cursor_app_path).map_err(|e| e.to_string())
+fn update_account(
+    state: State<AppState>,
+    email: String,
+    mut account: Account,
+) -> Result<bool, AppError> {
+    validation::email("email", &email)?;
+    validation::email("account.email", &account.email)?;
+    // Masked tokens are swapped for the stored ones below
+    if !account.access_token.expose().contains('…') {
+        validation::token("account.access_token", account.access_token.expose())?;
+    }
+    ensure_unlocked(&state)?;
+    let store = open_store(&state);
+
+    // The frontend only sees masked tokens; never write the masks back
+    let stored = store
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?;
+    if let Some(stored) = &stored {
+        redaction::restore_masked(&mut account, stored);
+        // Labels only change through set_account_label, pools through
+        // set_account_pool, nicknames through set_account_nickname
+        account.label_emoji = stored.label_emoji.clone();
+        account.label_color = stored.label_color.clone();
+        account.nickname = stored.nickname.clone();
+        account.privacy_mode = stored.privacy_mode;
+        account.pool = stored.pool.clone();
+        account.status_reason = stored.status_reason.clone();
+        // The frontend never gets the password, so one left out is kept
+        if account.password.is_none() {
+            account.password = stored.password.clone();
+        }
+    }
+    account.password = account.password.filter(|password| !password.is_empty());
+
+    let updated = store
+        .update_account(&email, account)
+        .map_err(AppError::from)?;
+    if updated {
+        state.accounts_changed.notify();
+    }
+    Ok(updated)
 }
 
+/// Set or clear the privacy mode applied when switching to `email`
 #[tauri::command]
-fn update_account_info_from_api(
+fn set_account_privacy_mode(
     state: State<AppState>,
     email: String,
-    access_token: String,
-) -> Result<Account, String> {
-    let api_client = CursorApiClient::new();
-    let account_info = api_client
-        .get_account_info(&email, &access_token)
-        .map_err(|e| e.to_string())?;
-
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
-
-    let mut accounts = csv_manager.read_accounts().map_err(|e| e.to_string())?;
-
-    // Find and update the account
-    let updated_account = if let Some(account) = accounts.iter_mut().find(|a| a.email == email) {
-        account.days_remaining = if account_info.days_remaining < 0.0 {
-            "N/A".to_string()
-        } else {
-            format!("{:.1}", account_info.days_remaining)
-        };
-        account.status = account_info.membership_type.clone();
-        account.record_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        account.clone()
-    } else {
-        return Err("Account not found".to_string());
-    };
+    enabled: Option<bool>,
+) -> Result<Account, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
+    let store = open_store(&state);
+    let mut account = store
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::from(format!("Account not found: {}", email)))?;
+    account.privacy_mode = enabled;
 
-    csv_manager
-        .write_accounts(&accounts)
-        .map_err(|e| e.to_string())?;
+    store
+        .update_account(&email, account.clone())
+        .map_err(AppError::from)?;
+    state.accounts_changed.notify();
 
-    Ok(updated_account)
+    redaction::mask_account(&mut account);
+    Ok(account)
 }
 
+/// Set or clear (with None or "") the emoji and color that mark an account
 #[tauri::command]
-fn batch_update_all_accounts(state: State<AppState>) -> Result<Vec<Account>, String> {
-    tracing::info!("Starting batch update for all accounts");
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
+fn set_account_label(
+    state: State<AppState>,
+    email: String,
+    emoji: Option<String>,
+    color: Option<String>,
+) -> Result<Account, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
+    let emoji = account_label::normalize_emoji(emoji)?;
+    let color = account_label::normalize_color(color)?;
 
-    let mut accounts = csv_manager.read_accounts().map_err(|e| e.to_string())?;
-    tracing::info!("Updating {} account(s)", accounts.len());
+    let store = open_store(&state);
+    let mut account = store
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::from(format!("Account not found: {}", email)))?;
+    account.label_emoji = emoji;
+    account.label_color = color;
 
-    let api_client = CursorApiClient::new();
-    let mut success_count = 0;
-    let mut error_count = 0;
+    store
+        .update_account(&email, account.clone())
+        .map_err(AppError::from)?;
+    state.accounts_changed.notify();
 
-    for account in &mut accounts {
-        match api_client.get_account_info(&account.email, &account.access_token) {
-            Ok(account_info) => {
-                account.days_remaining = if account_info.days_remaining < 0.0 {
-                    "N/A".to_string()
-                } else {
-                    format!("{:.1}", account_info.days_remaining)
-                };
-                account.status = account_info.membership_type;
-                account.record_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-
-                // Fetch usage info
-                match api_client.get_usage_info(&account.access_token) {
-                    Ok(usage_info) => {
-                        account.usage_used = Some(usage_info.used);
-                        account.usage_remaining = Some(usage_info.remaining);
-                        account.usage_total = Some(usage_info.total_quota);
-                        account.usage_percentage = Some(usage_info.usage_percentage);
-                    }
-                    Err(e) => {
-                        tracing::warn!("Failed to fetch usage info for {}: {}", account.email, e);
-                        account.usage_used = None;
-                        account.usage_remaining = None;
-                        account.usage_total = None;
-                        account.usage_percentage = None;
-                    }
-                }
-                success_count += 1;
-                tracing::debug!("Updated account: {}", account.email);
-            }
-            Err(e) => {
-                tracing::error!("Failed to update account {}: {}", account.email, e);
-                account.status = "error".to_string();
-                error_count += 1;
-            }
-        }
-    }
+    redaction::mask_account(&mut account);
+    Ok(account)
+}
 
-    csv_manager
-        .write_accounts(&accounts)
-        .map_err(|e| e.to_string())?;
+/// Set or clear (with None or "") the nickname shown with the email in the tray
+#[tauri::command]
+fn set_account_nickname(
+    state: State<AppState>,
+    email: String,
+    nickname: Option<String>,
+) -> Result<Account, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
+    let nickname = account_label::normalize_nickname(nickname)?;
 
-    tracing::info!(
-        "Batch update completed: {} successful, {} failed",
-        success_count,
-        error_count
-    );
-    Ok(accounts)
+    let store = open_store(&state);
+    let mut account = store
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::from(format!("Account not found: {}", email)))?;
+    account.nickname = nickname;
+
+    store
+        .update_account(&email, account.clone())
+        .map_err(AppError::from)?;
+    state.accounts_changed.notify();
+
+    redaction::mask_account(&mut account);
+    Ok(account)
 }
 
+/// Move an account into a pool; None, "" or "default" puts it back in the default pool
 #[tauri::command]
-fn sync_current_account(state: State<AppState>) -> Result<(), String> {
-    let cursor_path = state.cursor_base_path.lock().unwrap();
-    let base_path = cursor_path.as_ref().ok_or("Cursor path not set")?;
+fn set_account_pool(
+    state: State<AppState>,
+    email: String,
+    pool: Option<String>,
+) -> Result<Account, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
+    let pool = pools::normalize(pool)?;
 
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
+    let store = open_store(&state);
+    let mut account = store
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::from(format!("Account not found: {}", email)))?;
+    account.pool = pool;
 
-    // Get current account from Cursor's database
-    let db_path = PathDetector::get_db_path(base_path);
-    let db = Database::new(db_path);
+    store
+        .update_account(&email, account.clone())
+        .map_err(AppError::from)?;
+    state.accounts_changed.notify();
 
-    let (email, access_token) = match db.get_auth_info() {
-        Ok(info) => info,
-        Err(_) => {
-            // No account logged in, just return
-            return Ok(());
-        }
-    };
+    redaction::mask_account(&mut account);
+    Ok(account)
+}
 
-    // Read existing accounts
-    let mut accounts = csv_manager.read_accounts().map_err(|e| e.to_string())?;
+/// Replace the tags of an account; they are trimmed, and empty ones and
+/// repeats dropped
+#[tauri::command]
+fn set_account_tags(
+    state: State<AppState>,
+    email: String,
+    tags: Vec<String>,
+) -> Result<Account, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
 
-    // Check if account already exists
-    let existing_account = accounts.iter_mut().find(|a| a.email == email);
+    let store = open_store(&state);
+    let mut account = store
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::from(format!("Account not found: {}", email)))?;
+    account.tags = tags::normalize(&tags);
 
-    if let Some(account) = existing_account {
-        // Update tokens but preserve source
-        account.access_token = access_token.clone();
-        account.refresh_token = access_token.clone();
-        account.record_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    store
+        .update_account(&email, account.clone())
+        .map_err(AppError::from)?;
+    state.accounts_changed.notify();
 
-        csv_manager
-            .write_accounts(&accounts)
-            .map_err(|e| e.to_string())?;
-    } else {
-        // Add new account with source="web_login"
-        let new_account = Account {
-            index: 0, // Will be auto-assigned
-            email: email.clone(),
-            access_token: access_token.clone(),
-            refresh_token: access_token,
-            cookie: String::new(),
-            days_remaining: "N/A".to_string(),
-            status: "unknown".to_string(),
-            record_time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-            source: "web_login".to_string(),
-            usage_used: None,
-            usage_remaining: None,
-            usage_total: None,
-            usage_percentage: None,
-        };
+    redaction::mask_account(&mut account);
+    Ok(account)
+}
 
-        csv_manager
-            .add_account(new_account)
-            .map_err(|e| e.to_string())?;
-    }
+/// Star an account, or unstar a starred one. Only the flag changes, so the
+/// account keeps its place and index in the CSV.
+#[tauri::command]
+fn toggle_favorite(state: State<AppState>, email: String) -> Result<Account, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
 
-    Ok(())
+    let store = open_store(&state);
+    let mut account = store
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::from(format!("Account not found: {}", email)))?;
+    account.favorite = !account.favorite;
+
+    store
+        .update_account(&email, account.clone())
+        .map_err(AppError::from)?;
+    state.accounts_changed.notify();
+
+    redaction::mask_account(&mut account);
+    Ok(account)
 }
 
+/// A page of the accounts whose email or tags contain `filter`, ignoring case,
+/// and whose status is `status`, in stored order; archived ones only with
+/// `include_archived`. Tokens are masked unless `mask_tokens` is false and
+/// the app is unlocked.
 #[tauri::command]
-fn get_logs(state: State<AppState>) -> Result<Vec<LogEntry>, String> {
-    let log_path = state.log_path.lock().unwrap();
-    let logger = Logger::new(log_path.clone());
+fn query_accounts(
+    state: State<AppState>,
+    filter: Option<String>,
+    status: Option<String>,
+    offset: usize,
+    limit: usize,
+    mask_tokens: Option<bool>,
+    include_archived: Option<bool>,
+) -> Result<AccountPage, AppError> {
+    let query = AccountQuery {
+        filter,
+        status,
+        include_archived: include_archived.unwrap_or(false),
+        offset,
+        limit,
+    };
+    account_page(&state, &query, mask_tokens)
+}
 
-    logger.read_logs().map_err(|e| e.to_string())
+fn account_page(
+    state: &AppState,
+    query: &AccountQuery,
+    mask_tokens: Option<bool>,
+) -> Result<AccountPage, AppError> {
+    let store = open_store(state);
+    store.ensure_exists().map_err(AppError::from)?;
+    let mut page = store.query_accounts(query).map_err(AppError::from)?;
+    if mask_tokens.unwrap_or(true) || app_lock_status(state).locked {
+        page.items.iter_mut().for_each(redaction::mask_account);
+    }
+    Ok(page)
 }
 
+/// Move an account one row up or down in the stored order, which the table and
+/// the tray follow. False when it is at that end already.
 #[tauri::command]
-fn clear_logs(state: State<AppState>) -> Result<(), String> {
-    let log_path = state.log_path.lock().unwrap();
-    let logger = Logger::new(log_path.clone());
+fn move_account(
+    state: State<AppState>,
+    email: String,
+    direction: MoveDirection,
+) -> Result<bool, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
 
-    logger.clear_logs().map_err(|e| e.to_string())
+    let moved = open_store(&state)
+        .move_account(&email, direction)
+        .map_err(AppError::from)?;
+    if moved {
+        state.accounts_changed.notify();
+    }
+    Ok(moved)
 }
 
+/// Store the accounts in the order of `emails`; accounts missing from it
+/// follow in the order they had
 #[tauri::command]
-fn get_log_file_path(state: State<AppState>) -> Result<String, String> {
-    let log_path = state.log_path.lock().unwrap();
-    let logger = Logger::new(log_path.clone());
+fn set_account_order(state: State<AppState>, emails: Vec<String>) -> Result<(), AppError> {
+    ensure_unlocked(&state)?;
 
-    Ok(logger.get_log_path().to_string_lossy().to_string())
+    open_store(&state)
+        .set_account_order(&emails)
+        .map_err(AppError::from)?;
+    state.accounts_changed.notify();
+    Ok(())
 }
 
+/// The accounts carrying `tag`, ignoring case, with masked tokens; archived
+/// ones only with `include_archived`
 #[tauri::command]
-fn sync_from_tray(state: State<AppState>) -> Result<String, String> {
-    tracing::info!("Syncing current account from tray");
-    sync_current_account(state)?;
-    Ok("Account synced successfully".to_string())
+fn get_accounts_by_tag(
+    state: State<AppState>,
+    tag: String,
+    include_archived: Option<bool>,
+) -> Result<Vec<Account>, AppError> {
+    accounts_by_tag(&state, &tag, include_archived)
+}
+
+fn accounts_by_tag(
+    state: &AppState,
+    tag: &str,
+    include_archived: Option<bool>,
+) -> Result<Vec<Account>, AppError> {
+    let mut accounts = listed_accounts(state, None, include_archived)?;
+    accounts.retain(|a| tags::has_tag(a, tag));
+    accounts.iter_mut().for_each(redaction::mask_account);
+    Ok(accounts)
 }
 
 #[tauri::command]
-fn refresh_from_tray(state: State<AppState>) -> Result<String, String> {
-    tracing::info!("Refreshing all accounts from tray");
-    let accounts = batch_update_all_accounts(state)?;
-    Ok(format!("Refreshed {} accounts", accounts.len()))
+fn list_pools(state: State<AppState>) -> Result<Vec<PoolSummary>, AppError> {
+    Ok(pools::summarize(&stored_accounts(&state)?))
 }
 
+/// Delete a pool by moving its members back to the default pool; the accounts
+/// themselves are kept. Returns how many accounts were moved.
 #[tauri::command]
-fn validate_token(token: String) -> Result<TokenInfo, String> {
-    tracing::info!("Validating token");
-    token_auth::validate_token_info(&token).map_err(|e| {
-        tracing::error!("Token validation failed: {}", e);
-        e.to_string()
-    })
+fn delete_pool(state: State<AppState>, name: String) -> Result<usize, AppError> {
+    ensure_unlocked(&state)?;
+    let Some(name) = pools::normalize(Some(name))? else {
+        return Err(AppError::InvalidInput {
+            field: "name",
+            reason: "the default pool can't be deleted".to_string(),
+        });
+    };
+
+    let moved = open_store(&state)
+        .modify(|accounts| {
+            let mut moved = 0;
+            for account in accounts
+                .iter_mut()
+                .filter(|a| a.pool.as_deref() == Some(name.as_str()))
+            {
+                account.pool = None;
+                moved += 1;
+            }
+            Ok(moved)
+        })
+        .map_err(AppError::from)?;
+    if moved > 0 {
+        state.accounts_changed.notify();
+    }
+    tracing::info!(
+        "Deleted pool {}, {} account(s) moved to the default pool",
+        name,
+        moved
+    );
+    Ok(moved)
 }
 
+/// Parse pasted account lines. Lines that can't be read come back as errors
+/// next to the accounts, unless `strict` fails the paste on the first one.
 #[tauri::command]
-fn import_from_token(state: State<AppState>, token: String) -> Result<Account, String> {
-    tracing::info!("Importing account from token");
-    let csv_path = state.csv_path.lock().unwrap();
-    let csv_manager = CsvManager::new(csv_path.clone());
+fn import_accounts(
+    state: State<AppState>,
+    text: String,
+    strict: Option<bool>,
+) -> Result<TextImport, AppError> {
+    tracing::info!("Importing accounts from text");
+    let result = CsvManager::new(state.csv_path())
+        .parse_import_text(&text, strict.unwrap_or(false))
+        .map_err(|e| {
+            tracing::error!("Failed to parse import text: {}", e);
+            AppError::from(e)
+        })?;
 
-    let client = token_auth::TokenAuthClient::new();
-    let mut account = client.convert_token_to_account(&token).map_err(|e| {
-        tracing::error!("Token conversion failed: {}", e);
-        e.to_string()
-    })?;
+    tracing::info!(
+        "Parsed {} account(s), {} line(s) skipped",
+        result.accounts.len(),
+        result.errors.len()
+    );
+    Ok(result)
+}
 
-    // Set metadata
-    account.source = "token_import".to_string();
-    account.record_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+#[tauri::command]
+fn import_accounts_json(text: String) -> Result<json_import::JsonImport, AppError> {
+    let now = chrono::Local::now().format(record_time::FORMAT).to_string();
+    let import = json_import::parse(&text, &now).map_err(AppError::from)?;
+    tracing::info!(
+        "Parsed {} account(s) from JSON, {} entries skipped",
+        import.accounts.len(),
+        import.errors.len()
+    );
+    Ok(import)
+}
 
-    // Add to CSV
-    csv_manager.add_account(account.clone()).map_err(|e| {
-        tracing::error!("Failed to add account to CSV: {}", e);
-        e.to_string()
-    })?;
+/// Store imported accounts. With `merge` an email already stored, in any
+/// case, gets the new tokens instead of a second row.
+#[tauri::command]
+fn batch_add_accounts(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    accounts: Vec<Account>,
+    merge: Option<bool>,
+) -> Result<MergeReport, AppError> {
+    let store = open_store(&state);
 
+    // Use the optimized batch add method instead of adding one by one
+    let report = {
+        let _operation = state
+            .operations
+            .begin_when_idle(Operation::Importing, IMPORT_WRITE_TIMEOUT)?;
+        if merge.unwrap_or(false) {
+            store
+                .batch_merge_accounts(accounts)
+                .map_err(AppError::from)?
+        } else {
+            let added = accounts.iter().map(|a| a.email.clone()).collect();
+            store.batch_add_accounts(accounts).map_err(AppError::from)?;
+            MergeReport {
+                added,
+                merged: Vec::new(),
+            }
+        }
+    };
     tracing::info!(
-        "Successfully imported account from token: {}",
-        account.email
+        "Imported {} new account(s), merged {}",
+        report.added.len(),
+        report.merged.len()
     );
-    Ok(account)
+
+    state.accounts_changed.notify();
+    warn_credential_conflicts(&app, &state);
+    Ok(report)
+}
+
+fn identity_store(state: &AppState) -> IdentityStore {
+    IdentityStore::new(state.csv_path().with_file_name(integrity::IDENTITIES_FILE))
+}
+
+fn credential_conflicts(state: &AppState) -> Result<Vec<CredentialConflict>, AppError> {
+    let accounts = stored_accounts(state)?;
+    let verified = identity_store(state).load().map_err(AppError::from)?;
+    Ok(integrity::find_credential_conflicts(&accounts, &verified))
 }
 
+// Run after every import so mislabeled rows are caught before someone switches to them
+fn warn_credential_conflicts(app: &tauri::AppHandle, state: &AppState) {
+    match credential_conflicts(state) {
+        Ok(conflicts) if !conflicts.is_empty() => {
+            tracing::warn!("Found {} credential conflict(s)", conflicts.len());
+            let _ = app.emit_all("credential-conflicts", &conflicts);
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Credential conflict check failed: {}", e),
+    }
+}
+
+/// Accounts sharing credentials across emails, or whose token belongs to someone else
 #[tauri::command]
-fn get_usage_events(state: State<AppState>) -> Result<serde_json::Value, String> {
-    tracing::info!("Fetching usage events");
+fn find_credential_conflicts(state: State<AppState>) -> Result<Vec<CredentialConflict>, AppError> {
+    credential_conflicts(&state)
+}
+
+/// Keep `keep_email` and remove the rows that conflict with it. Returns the removed emails.
+#[tauri::command]
+fn resolve_conflict(state: State<AppState>, keep_email: String) -> Result<Vec<String>, AppError> {
+    validation::email("keep_email", &keep_email)?;
+    ensure_unlocked(&state)?;
+    let conflicts = credential_conflicts(&state)?;
+    let (removed, kept) = open_store(&state)
+        .modify(|accounts| {
+            let removed = integrity::resolve_conflict(accounts, &conflicts, &keep_email);
+            let kept = accounts.iter().find(|a| a.email == keep_email).cloned();
+            Ok((removed, kept))
+        })
+        .map_err(AppError::from)?;
+
+    // The user vouched for this row, so its token is now the verified identity
+    if let Some(kept) = kept {
+        identity_store(&state)
+            .record(&kept.email, kept.access_token.expose())
+            .map_err(AppError::from)?;
+    }
+
+    tracing::info!(
+        "Resolved conflict for {}, removed {:?}",
+        keep_email,
+        removed
+    );
+    state.accounts_changed.notify();
+    Ok(removed)
+}
+
+#[tauri::command]
+fn switch_account(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    email: String,
+    reset_machine: bool,
+    restart: Option<bool>,
+    validate: Option<bool>,
+) -> Result<SwitchReport, AppError> {
+    switch_account_from(
+        &app,
+        &state,
+        &email,
+        switch_options(&state, reset_machine, restart, validate),
+        SwitchSource::Window,
+    )
+}
+
+// `switch_account` for callers other than the window, which the switch log tells apart
+fn switch_account_from(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    email: &str,
+    mut options: SwitchOptions,
+    source: SwitchSource,
+) -> Result<SwitchReport, AppError> {
+    validation::email("email", email)?;
+    let _operation = state.operations.try_begin(Operation::Switching)?;
+    let mut warnings = offline_switch_warnings(state, &mut options);
+    warnings.extend(cooldown_warning(state, email));
+    perform_switch(app, state, email, &options, source)?;
+    // Still holding the Switching operation, so the latest change set is this switch's
+    let change_set = change_sets(state)
+        .latest()
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to read the change log: {:#}", e);
+            None
+        })
+        .filter(|set| {
+            set.operation == ChangeOperation::Switch && set.email.as_deref() == Some(email)
+        });
+    Ok(SwitchReport {
+        options,
+        warnings,
+        change_set,
+    })
+}
+
+fn change_sets(state: &AppState) -> ChangeSetStore {
+    ChangeSetStore::new(state.csv_path().with_file_name(CHANGE_SETS_FILE))
+}
+
+/// The keys the most recent switch or machine ID reset wrote, by hash
+#[tauri::command]
+fn get_last_change_set(state: State<AppState>) -> Result<Option<ChangeSet>, AppError> {
+    change_sets(&state).latest().map_err(AppError::from)
+}
+
+fn switch_history(state: &AppState) -> SwitchHistoryStore {
+    SwitchHistoryStore::new(state.csv_path().with_file_name(SWITCH_HISTORY_FILE))
+}
+
+fn switch_log(state: &AppState) -> SwitchLog {
+    SwitchLog::new(state.csv_path().with_file_name(SWITCH_LOG_FILE))
+}
+
+/// The newest `limit` switches, newest first
+#[tauri::command]
+fn get_switch_history(
+    state: State<AppState>,
+    limit: usize,
+) -> Result<Vec<SwitchLogEntry>, AppError> {
+    switch_log(&state).recent(limit).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn clear_switch_history(state: State<AppState>) -> Result<(), AppError> {
+    ensure_unlocked(&state)?;
+    switch_log(&state).clear().map_err(AppError::from)?;
+    tracing::info!("Switch history cleared");
+    Ok(())
+}
+
+/// Accounts in their switch cooldown with the time left; empty when cooldowns are off
+fn cooling_down(state: &AppState) -> BTreeMap<String, chrono::Duration> {
+    let Some(window) = load_settings(state).cooldown_window() else {
+        return BTreeMap::new();
+    };
+    switch_history(state)
+        .cooling_down(window, chrono::Utc::now())
+        .unwrap_or_else(|e| {
+            tracing::warn!("Ignoring switch cooldowns: {:#}", e);
+            BTreeMap::new()
+        })
+}
+
+// Switching to the account in use again, e.g. to reset the machine ID, has no cooldown
+fn target_cooldown(
+    state: &AppState,
+    current_email: Option<&str>,
+    email: &str,
+) -> Option<chrono::Duration> {
+    if current_email == Some(email) {
+        return None;
+    }
+    cooling_down(state).remove(email)
+}
+
+fn cooldown_warning(state: &AppState, email: &str) -> Option<String> {
+    if load_settings(state).cooldown_mode != CooldownMode::Warn {
+        return None;
+    }
+    let current_email = current_auth_info(state).ok().map(|(email, _)| email);
+    let left = target_cooldown(state, current_email.as_deref(), email)?;
+    Some(format!(
+        "{} was used here recently; its cooldown ends in {}",
+        email,
+        switch_cooldown::format_remaining(left)
+    ))
+}
+
+// The token can't be checked without the API, so offline switches go ahead without it
+fn offline_switch_warnings(state: &AppState, options: &mut SwitchOptions) -> Vec<String> {
+    if !(options.validate_token && state.is_offline()) {
+        return Vec::new();
+    }
+    tracing::warn!("Offline, switching without validating the token");
+    options.validate_token = false;
+    vec!["Offline: the account's token was not validated before switching".to_string()]
+}
+
+// Pass `validate: false` to skip the token check, e.g. when offline
+fn switch_options(
+    state: &AppState,
+    reset_machine: bool,
+    restart: Option<bool>,
+    validate: Option<bool>,
+) -> SwitchOptions {
+    // Options the frontend leaves out come from the configured defaults
+    let defaults = load_settings(state).default_switch_options();
+    SwitchOptions {
+        reset_machine,
+        restart: restart.unwrap_or(defaults.restart),
+        validate_token: validate.unwrap_or(defaults.validate_token),
+        preserve_settings: defaults.preserve_settings,
+        state_db_backup_retention: defaults.state_db_backup_retention,
+    }
+}
+
+/// What `switch_account` would do with the same arguments, without doing any of it
+#[tauri::command]
+fn preview_switch(
+    state: State<AppState>,
+    email: String,
+    reset_machine: bool,
+    restart: Option<bool>,
+    validate: Option<bool>,
+) -> Result<SwitchPlan, AppError> {
+    validation::email("email", &email)?;
+    let base_path = state.cursor_base()?;
+    let mut options = switch_options(&state, reset_machine, restart, validate);
+    offline_switch_warnings(&state, &mut options);
+    operations::plan_switch(
+        &base_path,
+        open_store(&state).as_ref(),
+        &email,
+        &options,
+        &ProcessManager,
+    )
+    .map_err(AppError::from)
+}
+
+// Callers must hold the Switching operation
+fn perform_switch(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    email: &str,
+    options: &SwitchOptions,
+    source: SwitchSource,
+) -> Result<(), AppError> {
+    run_switch(app, state, email, options, source)?.map_err(AppError::from)?;
+    Ok(())
+}
+
+// Errors before the switch starts come back as AppError, the switch's own result as the outcome
+fn run_switch(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    email: &str,
+    options: &SwitchOptions,
+    source: SwitchSource,
+) -> Result<SwitchOutcome, AppError> {
+    ensure_unlocked(state)?;
+    let base_path = state.cursor_base()?;
+
+    // Tokens are looked up here so they never travel over IPC
+    let store = open_store(state);
+    let settings = load_settings(state);
+    let spawn_hook = |name, hook, context| spawn_switch_hook(app, name, hook, context);
+    let outcome = operations::run_switch(
+        &base_path,
+        store.as_ref(),
+        email,
+        options,
+        &live_api(state)?,
+        &ProcessManager,
+        &operations::SwitchContext {
+            settings: &settings,
+            journal: &state.journal(),
+            history: &switch_history(state),
+            log: &switch_log(state),
+            source,
+            spawn_hook: &spawn_hook,
+        },
+    )
+    .map_err(AppError::from)?;
+
+    // Reflect the new current account in the tray, or the restored one
+    update_tray_menu(app);
+    if outcome.is_ok() {
+        spawn_cursor_version_check(app);
+        spawn_settings_check(app);
+        let serial = state.next_switch_serial();
+        if options.restart {
+            spawn_switch_verification(app, serial, email, settings.switch_verify_timeout_secs);
+        }
+    }
+    Ok(outcome)
+}
+
+/// How long Cursor's database must stay unchanged before the account in it counts
+const SWITCH_VERIFY_QUIET: Duration = Duration::from_secs(5);
+const SWITCH_VERIFY_POLL: Duration = Duration::from_secs(1);
+
+fn switch_reverts(state: &AppState) -> SwitchRevertStore {
+    SwitchRevertStore::new(state.csv_path().with_file_name(SWITCH_REVERTS_FILE))
+}
+
+/// Switches Cursor undid when it started, oldest first
+#[tauri::command]
+fn list_switch_reverts(state: State<AppState>) -> Result<Vec<SwitchRevert>, AppError> {
+    switch_reverts(&state).list().map_err(AppError::from)
+}
+
+// Cursor may log back in with the previous account as it starts, e.g. when the
+// server no longer accepts the refresh token written by the switch. Waiting for
+// that runs on its own thread; a newer switch makes this one's result moot.
+fn spawn_switch_verification(app: &tauri::AppHandle, serial: u64, email: &str, timeout_secs: u64) {
+    if timeout_secs == 0 {
+        return;
+    }
+    let Ok(base_path) = app.state::<AppState>().cursor_base() else {
+        return;
+    };
+    let app = app.clone();
+    let email = email.to_string();
+    let switched_at = chrono::Utc::now();
+    std::thread::spawn(move || {
+        let db_path = PathDetector::get_db_path(&base_path);
+        let wal_path = db_path.with_file_name("state.vscdb-wal");
+        let wait = SettleWait {
+            quiet: SWITCH_VERIFY_QUIET,
+            poll: SWITCH_VERIFY_POLL,
+            timeout: Duration::from_secs(timeout_secs),
+        };
+        if !wait.wait(&[db_path, wal_path], ProcessManager::is_cursor_running) {
+            tracing::info!(
+                "Cursor didn't settle in time; the switch to {} is unverified",
+                email
+            );
+            return;
+        }
+        let state = app.state::<AppState>();
+        if !state.is_latest_switch(serial) {
+            return;
+        }
+        let found = current_auth_info(&state).ok().map(|(found, _)| found);
+        verify_switch(&app, &state, &email, found, switched_at);
+    });
+}
+
+fn verify_switch(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    email: &str,
+    found: Option<String>,
+    switched_at: chrono::DateTime<chrono::Utc>,
+) {
+    let reverted = switch_verification::is_reverted(email, found.as_deref());
+    let status_reason = reverted.then(|| switch_verification::REVERT_ON_LAUNCH.to_string());
+    let store = open_store(state);
+    match store.find_account_by_email(email) {
+        // A verified switch clears an earlier revert
+        Ok(Some(mut account)) if account.status_reason != status_reason => {
+            account.status_reason = status_reason;
+            if let Err(e) = store.update_account(email, account) {
+                tracing::warn!("Failed to mark {}: {:#}", email, e);
+            }
+            state.accounts_changed.notify();
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Failed to mark {}: {:#}", email, e),
+    }
+    if !reverted {
+        tracing::info!("Cursor kept {} after starting", email);
+        return;
+    }
+
+    let revert = SwitchRevert {
+        target_email: email.to_string(),
+        found_email: found,
+        switched_at,
+        detected_at: chrono::Utc::now(),
+    };
+    tracing::warn!(
+        "Cursor reverted the switch to {} on launch, found {:?}",
+        email,
+        revert.found_email
+    );
+    if let Err(e) = switch_reverts(state).record(&revert) {
+        tracing::warn!("Failed to log the reverted switch: {:#}", e);
+    }
+    update_tray_menu(app);
+    let _ = app.emit_all("switch-reverted", &revert);
+    let found = revert
+        .found_email
+        .clone()
+        .unwrap_or_else(|| t("notify.switch_reverted.logged_out").to_string());
+    notifier::notify(
+        app,
+        t("notify.switch_reverted.title"),
+        &tf(
+            "notify.switch_reverted.body",
+            &[("found", &found), ("target", email)],
+        ),
+    );
+}
+
+// Failures are only reported; the switch has its own outcome
+fn spawn_switch_hook(
+    app: &tauri::AppHandle,
+    name: &'static str,
+    hook: SwitchHook,
+    context: HookContext,
+) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let reason = match switch_hooks::run(name, &hook, &context) {
+            Ok(exit) if exit.success() => return,
+            Ok(exit) => exit.describe(),
+            Err(e) => format!("could not be started ({})", e.root_cause()),
+        };
+        tracing::warn!("The {} hook {}", name, reason);
+        notifier::notify(
+            &app,
+            t("notify.hook_failed.title"),
+            &tf(
+                "notify.hook_failed.body",
+                &[("hook", name), ("reason", &reason)],
+            ),
+        );
+    });
+}
+
+#[tauri::command]
+fn reset_machine_id(app: tauri::AppHandle, state: State<AppState>) -> Result<MachineIds, AppError> {
+    ensure_unlocked(&state)?;
+    let _operation = state.operations.try_begin(Operation::Resetting)?;
+    let base_path = state.cursor_base()?;
+
+    let ids = operations::reset_machine_id(
+        &base_path,
+        preserving_settings(&state),
+        &change_sets(&state),
+        &state.journal(),
+        &ProcessManager,
+    )
+    .map_err(AppError::from)?;
+    spawn_settings_check(&app);
+    Ok(ids)
+}
+
+/// The telemetry IDs Cursor has now, for comparing before and after a reset
+#[tauri::command]
+fn get_machine_ids(state: State<AppState>) -> Result<CurrentMachineIds, AppError> {
+    let storage_path = PathDetector::get_storage_path(&state.cursor_base()?);
+    Ok(machine_id::read_current(&storage_path)?)
+}
+
+/// The pre-flight checks `reset_machine_id` would run, without resetting
+#[tauri::command]
+fn check_reset_machine_id(state: State<AppState>) -> Result<Vec<ResetCheck>, AppError> {
+    Ok(MachineIdResetter::new(state.cursor_base()?).preflight())
+}
+
+fn settings_snapshots(state: &AppState) -> SettingsSnapshotStore {
+    SettingsSnapshotStore::new(
+        state
+            .csv_path()
+            .with_file_name(cursor_settings::SNAPSHOTS_DIR),
+    )
+}
+
+// None unless settings are preserved across resets
+fn preserving_settings(state: &AppState) -> Option<SettingsSnapshotStore> {
+    load_settings(state)
+        .preserve_cursor_settings
+        .then(|| settings_snapshots(state))
+}
+
+/// Settings snapshots taken before machine ID resets, newest first
+#[tauri::command]
+fn list_cursor_settings_snapshots(
+    state: State<AppState>,
+) -> Result<Vec<SnapshotSummary>, AppError> {
+    settings_snapshots(&state).list().map_err(AppError::from)
+}
+
+/// Put back everything in snapshot `id` that differs from Cursor's current
+/// settings. Cursor must be closed, or it would write its state back on exit.
+#[tauri::command]
+fn restore_cursor_settings_snapshot(
+    state: State<AppState>,
+    id: String,
+) -> Result<SettingsRestore, AppError> {
+    let id = id.trim();
+    if !cursor_settings::is_valid_snapshot_id(id) {
+        return Err(AppError::InvalidInput {
+            field: "id",
+            reason: "must be the ID of a settings snapshot".to_string(),
+        });
+    }
+    ensure_unlocked(&state)?;
+    let _operation = state.operations.try_begin(Operation::Resetting)?;
+    let base_path = state.cursor_base()?;
+    if ProcessManager::is_cursor_running() {
+        return Err("Close Cursor before restoring its settings".into());
+    }
+
+    let snapshot = settings_snapshots(&state)
+        .load(id)
+        .map_err(AppError::from)?;
+    let restore = snapshot
+        .restore(&base_path, RestoreMode::All, false)
+        .map_err(AppError::from)?;
+    tracing::info!(
+        "Restored Cursor settings from snapshot {}: {:?}",
+        id,
+        restore.restored
+    );
+    Ok(restore)
+}
+
+/// How long Cursor must keep running after a reset before its settings are compared
+const SETTINGS_CHECK_SETTLE: Duration = Duration::from_secs(20);
+const SETTINGS_CHECK_POLL: Duration = Duration::from_secs(5);
+/// Waiting for Cursor stops after this; the check then runs after the next launch
+const SETTINGS_CHECK_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+// Runs when a reset left a snapshot to compare against and no check is waiting yet
+fn spawn_settings_check(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    if settings_snapshots(&state).pending().is_none() || !state.begin_settings_check() {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state = app.state::<AppState>();
+        if wait_for_cursor_start() {
+            check_settings_after_start(&app, &state);
+        } else {
+            tracing::info!("Cursor didn't start; its settings are checked after the next launch");
+        }
+        state.end_settings_check();
+    });
+}
+
+// A process that appears and exits again, like a crash at launch, doesn't count
+fn wait_for_cursor_start() -> bool {
+    let waiting_since = Instant::now();
+    let mut running_since: Option<Instant> = None;
+    while waiting_since.elapsed() < SETTINGS_CHECK_TIMEOUT {
+        running_since = if ProcessManager::is_cursor_running() {
+            running_since.or_else(|| Some(Instant::now()))
+        } else {
+            None
+        };
+        if running_since.is_some_and(|since| since.elapsed() >= SETTINGS_CHECK_SETTLE) {
+            return true;
+        }
+        std::thread::sleep(SETTINGS_CHECK_POLL);
+    }
+    false
+}
+
+fn check_settings_after_start(app: &tauri::AppHandle, state: &AppState) {
+    let store = settings_snapshots(state);
+    let Some(id) = store.pending() else {
+        return;
+    };
+    let result = state
+        .cursor_base()
+        .map_err(anyhow::Error::from)
+        .and_then(|base_path| {
+            store
+                .load(&id)?
+                .restore(&base_path, RestoreMode::Wiped, true)
+        });
+    if let Err(e) = store.clear_pending() {
+        tracing::warn!("{:#}", e);
+    }
+    let restore = match result {
+        Ok(restore) => restore,
+        Err(e) => {
+            tracing::warn!("Failed to check Cursor's settings after the reset: {:#}", e);
+            return;
+        }
+    };
+    if restore.restored.is_empty() && restore.skipped.is_empty() {
+        tracing::info!("Cursor kept its settings across the reset");
+        return;
+    }
+
+    tracing::info!(
+        "Cursor's first start after the reset wiped settings; restored {:?}, skipped {:?}",
+        restore.restored,
+        restore.skipped
+    );
+    let _ = app.emit_all("cursor-settings-restored", &restore);
+    let restored = restore.restored.len().to_string();
+    let body = if restore.skipped.is_empty() {
+        tf("notify.settings_restored.body", &[("count", &restored)])
+    } else {
+        tf(
+            "notify.settings_restored.body_skipped",
+            &[
+                ("count", &restored),
+                ("skipped", &restore.skipped.len().to_string()),
+            ],
+        )
+    };
+    notifier::notify(app, t("notify.settings_restored.title"), &body);
+}
+
+fn cursor_versions(state: &AppState) -> CursorVersionStore {
+    CursorVersionStore::new(state.csv_path().with_file_name(CURSOR_VERSIONS_FILE))
+}
+
+/// Cursor versions seen installed, oldest first, to match breakages to updates
+#[tauri::command]
+fn get_cursor_update_history(state: State<AppState>) -> Result<Vec<VersionObservation>, AppError> {
+    cursor_versions(&state).history().map_err(AppError::from)
+}
+
+fn spawn_cursor_version_check(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || check_cursor_version(&app));
+}
+
+// Cursor updates replace main.js, undoing a reset's patch, and sometimes move
+// storage keys, so a new version is worth a warning
+fn check_cursor_version(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let update = match cursor_versions(&state).check_installed(chrono::Utc::now()) {
+        Ok(Some(update)) => update,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("Failed to record the Cursor version: {:#}", e);
+            return;
+        }
+    };
+    tracing::info!(
+        "Cursor updated from {} to {}, machine ID patch: {:?}",
+        update.previous,
+        update.version,
+        update.patch_status
+    );
+    let _ = app.emit_all("cursor-updated", &update);
+    let body = match update.patch_status {
+        PatchStatus::Patched => "notify.cursor_updated.body_patched",
+        _ => "notify.cursor_updated.body",
+    };
+    notifier::notify(
+        app,
+        t("notify.cursor_updated.title"),
+        &tf(body, &[("version", &update.version)]),
+    );
+}
+
+/// The switch or reset a crash or power loss cut short, if any
+#[tauri::command]
+fn get_interrupted_operation(state: State<AppState>) -> Result<Option<JournalEntry>, AppError> {
+    state.journal().pending().map_err(AppError::from)
+}
+
+#[tauri::command]
+fn resume_or_discard_interrupted(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    action: InterruptedAction,
+) -> Result<(), AppError> {
+    let journal = state.journal();
+    if let InterruptedAction::Discard = action {
+        // Also gets rid of a journal too corrupt to resume
+        journal.clear().map_err(AppError::from)?;
+        tracing::warn!("Interrupted operation abandoned by the user");
+        return Ok(());
+    }
+
+    ensure_unlocked(&state)?;
+    let entry = journal
+        .pending()
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::from("No operation was interrupted"))?;
+    tracing::info!(
+        "Resuming operation interrupted at {}: {:?}",
+        entry.started_at,
+        entry.operation
+    );
+
+    // Both operations start over and clear the journal when they finish
+    let result = match entry.operation {
+        JournaledOperation::Switch { email, options } => {
+            let _operation = state.operations.try_begin(Operation::Switching)?;
+            perform_switch(&app, &state, &email, &options, SwitchSource::Window)
+        }
+        JournaledOperation::ResetMachineId => {
+            let _operation = state.operations.try_begin(Operation::Resetting)?;
+            let base_path = state.cursor_base()?;
+            operations::reset_machine_id(
+                &base_path,
+                preserving_settings(&state),
+                &change_sets(&state),
+                &journal,
+                &ProcessManager,
+            )
+            .map(|_| spawn_settings_check(&app))
+            .map_err(AppError::from)
+        }
+    };
+    match &result {
+        Ok(()) => tracing::info!("Interrupted operation completed"),
+        Err(e) => tracing::error!("Resuming the interrupted operation failed: {}", e),
+    }
+    result
+}
+
+/// Sign Cursor out without switching to another account. With `kill_cursor`
+/// Cursor is closed first, so it can't write the login back on exit.
+#[tauri::command]
+fn logout_current_account(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    kill_cursor: bool,
+) -> Result<(), AppError> {
+    logout(&state, kill_cursor)?;
+    update_tray_menu(&app);
+    Ok(())
+}
+
+// Returns how many login keys were removed
+fn logout(state: &AppState, kill_cursor: bool) -> Result<usize, AppError> {
+    ensure_unlocked(state)?;
+    let _operation = state.operations.try_begin(Operation::Switching)?;
+    let base_path = state.cursor_base()?;
+    if kill_cursor {
+        ProcessManager::kill_cursor().map_err(AppError::from)?;
+    }
+
+    let removed = Database::new(PathDetector::get_db_path(&base_path))
+        .clear_auth()
+        .map_err(AppError::from)?;
+    tracing::info!("Logged out of Cursor, {} login key(s) removed", removed);
+    Ok(removed)
+}
+
+/// The copies of state.vscdb switches took, newest first
+#[tauri::command]
+fn list_state_db_backups(
+    state: State<AppState>,
+) -> Result<Vec<state_backups::StateBackupInfo>, AppError> {
+    state_backups::list(&state.cursor_base()?).map_err(AppError::from)
+}
+
+/// Close Cursor and put back a copy of state.vscdb from `list_state_db_backups`
+#[tauri::command]
+fn restore_state_db_backup(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    path: String,
+) -> Result<(), AppError> {
+    ensure_unlocked(&state)?;
+    let _operation = state.operations.try_begin(Operation::Switching)?;
+    let base_path = state.cursor_base()?;
+    let backup = state_backups::resolve(&base_path, std::path::Path::new(&path)).map_err(|e| {
+        AppError::InvalidInput {
+            field: "path",
+            reason: e.to_string(),
+        }
+    })?;
+
+    ProcessManager::kill_cursor().map_err(AppError::from)?;
+    state_backups::restore(&base_path, &backup).map_err(AppError::from)?;
+    tracing::info!("Restored state.vscdb from {}", backup.display());
+    update_tray_menu(&app);
+    Ok(())
+}
+
+#[tauri::command]
+fn kill_cursor_process() -> Result<(), AppError> {
+    ProcessManager::kill_cursor().map_err(AppError::from)
+}
+
+#[tauri::command]
+fn restart_cursor_process(
+    state: State<AppState>,
+    cursor_app_path: Option<String>,
+) -> Result<(), AppError> {
+    let cursor_app_path = match cursor_app_path {
+        Some(path) => {
+            let allow_custom = load_settings(&state).allow_custom_cursor_executable;
+            let path = validation::cursor_executable(
+                "cursor_app_path",
+                &path,
+                Platform::current(),
+                allow_custom,
+            )?;
+            Some(path.to_string_lossy().into_owned())
+        }
+        None => None,
+    };
+    ProcessManager::restart_cursor(cursor_app_path).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn update_account_info_from_api(
+    state: State<AppState>,
+    email: String,
+    access_token: Option<String>,
+) -> Result<Account, AppError> {
+    validation::email("email", &email)?;
+    // The frontend only holds masked tokens, so those fall back to the stored one
+    let access_token = access_token.filter(|token| !token.contains('…'));
+    if let Some(token) = &access_token {
+        validation::token("access_token", token)?;
+    }
+    let account = operations::update_account_info(
+        open_store(&state).as_ref(),
+        &email,
+        access_token.as_deref(),
+        &live_api(&state)?,
+    )
+    .map_err(AppError::from)?;
+
+    state.accounts_changed.notify();
+    Ok(account)
+}
+
+#[tauri::command]
+fn batch_update_all_accounts(
+    state: State<AppState>,
+    pool: Option<String>,
+) -> Result<Vec<Account>, AppError> {
+    refresh_all_accounts(&state, RefreshTrigger::Manual, &|a| {
+        pools::in_pool(a, pool.as_deref())
+    })
+}
+
+/// Refresh only the accounts that are stale and not banned
+#[tauri::command]
+fn refresh_stale_accounts(state: State<AppState>) -> Result<Vec<Account>, AppError> {
+    let stale_after_hours = load_settings(&state).stale_after_hours;
+    let now = chrono::Local::now().naive_local();
+    refresh_all_accounts(&state, RefreshTrigger::Manual, &|a| {
+        operations::is_refresh_due(a, stale_after_hours, now)
+    })
+}
+
+fn refresh_state_store(state: &AppState) -> RefreshStateStore {
+    RefreshStateStore::new(
+        state
+            .csv_path()
+            .with_file_name(refresh_state::REFRESH_STATE_FILE),
+    )
+}
+
+// Refresh the `selected` accounts, recording the run in the refresh state file:
+// as running before the first request and with its outcome once it ends
+fn refresh_all_accounts(
+    state: &AppState,
+    trigger: RefreshTrigger,
+    selected: &(dyn Fn(&Account) -> bool + Sync),
+) -> Result<Vec<Account>, AppError> {
+    if state.is_offline() {
+        tracing::info!("Skipping batch update: offline");
+        return Err("Offline: accounts can't be refreshed until the connection is back".into());
+    }
+    let accounts = open_store(state);
+    let refresh = {
+        let _operation = state.operations.try_begin(Operation::Refreshing)?;
+        let store = refresh_state_store(state);
+        if let Err(e) = store.start(trigger, chrono::Utc::now()) {
+            tracing::warn!("Failed to record the refresh start: {}", e);
+        }
+
+        let policy = load_settings(state).api_policy;
+        let result = live_api(state).and_then(|api| {
+            operations::batch_refresh(accounts.as_ref(), &api, &policy, selected)
+                .map_err(AppError::from)
+        });
+        let outcome = result.as_ref().map_err(|e| e.to_string());
+        if let Err(e) = store.finish(outcome, chrono::Utc::now()) {
+            tracing::warn!("Failed to record the refresh outcome: {}", e);
+        }
+        result?
+    };
+    record_usage(state, &refresh);
+
+    // Refresh tray icon and tooltip with the new usage data
+    state.accounts_changed.notify();
+    Ok(refresh.accounts)
+}
+
+fn usage_history(state: &AppState) -> UsageHistoryStore {
+    UsageHistoryStore::new(state.csv_path().with_file_name(USAGE_HISTORY_FILE))
+}
+
+// Accounts whose refresh failed still carry their old usage and are left out
+fn record_usage(state: &AppState, refresh: &BatchRefresh) {
+    let now = chrono::Utc::now();
+    let snapshots: Vec<UsageSnapshot> = refresh
+        .accounts
+        .iter()
+        .filter(|account| !refresh.failed.contains(&account.email))
+        .filter_map(|account| UsageSnapshot::of(account, now))
+        .collect();
+    if let Err(e) = usage_history(state).record(&snapshots, now) {
+        tracing::warn!("Failed to record usage history: {}", e);
+    }
+}
+
+fn forecast_for(
+    state: &AppState,
+    email: &str,
+    days: Option<u32>,
+) -> Result<AccountForecast, AppError> {
+    let days = days.unwrap_or(forecast::DEFAULT_WINDOW_DAYS);
+    if !(1..=90).contains(&days) {
+        return Err(AppError::InvalidInput {
+            field: "days",
+            reason: "must be between 1 and 90".to_string(),
+        });
+    }
+    let since = chrono::Utc::now() - chrono::Duration::days(days.into());
+    let history = usage_history(state)
+        .history(email, since)
+        .map_err(AppError::from)?;
+    Ok(AccountForecast {
+        email: email.to_string(),
+        forecast: forecast::forecast(&history),
+    })
+}
+
+/// When `email` runs out of quota at its recent burn rate, from the usage
+/// snapshots of the last `days` days
+#[tauri::command]
+fn forecast_account(
+    state: State<AppState>,
+    email: String,
+    days: Option<u32>,
+) -> Result<AccountForecast, AppError> {
+    validation::email("email", &email)?;
+    forecast_for(&state, &email, days)
+}
+
+/// Forecasts of every stored account, the first to run out first; archived
+/// ones only with `include_archived`
+#[tauri::command]
+fn forecast_all(
+    state: State<AppState>,
+    days: Option<u32>,
+    include_archived: Option<bool>,
+) -> Result<Vec<AccountForecast>, AppError> {
+    forecasts(&state, days, include_archived)
+}
+
+fn forecasts(
+    state: &AppState,
+    days: Option<u32>,
+    include_archived: Option<bool>,
+) -> Result<Vec<AccountForecast>, AppError> {
+    let mut forecasts = listed_accounts(state, None, include_archived)?
+        .iter()
+        .map(|account| forecast_for(state, &account.email, days))
+        .collect::<Result<Vec<_>, _>>()?;
+    forecast::sort_by_exhaustion(&mut forecasts);
+    Ok(forecasts)
+}
+
+/// When the last batch refresh ran, how it went and what started it
+#[tauri::command]
+fn get_last_refresh_info(state: State<AppState>) -> Result<Option<RefreshInfo>, AppError> {
+    let refreshing = state.operations.current() == Some(Operation::Refreshing);
+    refresh_state_store(&state)
+        .load(refreshing)
+        .map_err(AppError::from)
+}
+
+/// Stored (or with `live`, freshly fetched) stats of a few accounts side by side
+#[tauri::command]
+fn compare_accounts(
+    state: State<AppState>,
+    emails: Vec<String>,
+    live: Option<bool>,
+) -> Result<Vec<AccountComparison>, AppError> {
+    for email in &emails {
+        validation::email("emails", email)?;
+    }
+    let store = open_store(&state);
+    if !live.unwrap_or(false) {
+        return operations::compare_accounts(store.as_ref(), &emails, false, &live_api(&state)?)
+            .map_err(AppError::from);
+    }
+
+    let rows = {
+        let _operation = state.operations.try_begin(Operation::Refreshing)?;
+        operations::compare_accounts(store.as_ref(), &emails, true, &live_api(&state)?)
+            .map_err(AppError::from)?
+    };
+    state.accounts_changed.notify();
+    Ok(rows)
+}
+
+#[tauri::command]
+fn recommend_account(
+    state: State<AppState>,
+    min_remaining: Option<f64>,
+    live: Option<bool>,
+    pool: Option<String>,
+) -> Result<Recommendation, AppError> {
+    let current_email = current_auth_info(&state).ok().map(|(email, _)| email);
+    let store = open_store(&state);
+
+    // Refreshing writes the accounts; while another operation runs, rank what's stored
+    let operation = live
+        .unwrap_or(true)
+        .then(|| state.operations.try_begin(Operation::Refreshing).ok())
+        .flatten();
+    let live = operation.is_some();
+    let recommendation = operations::recommend_account(
+        store.as_ref(),
+        current_email.as_deref(),
+        min_remaining,
+        pool.as_deref(),
+        &cooling_down(&state),
+        live,
+        &live_api(&state)?,
+    );
+    drop(operation);
+
+    if live {
+        state.accounts_changed.notify();
+    }
+    recommendation.map_err(AppError::from)
+}
+
+#[tauri::command]
+fn sync_current_account(
+    state: State<AppState>,
+    fetch_info: Option<bool>,
+) -> Result<SyncOutcome, AppError> {
+    let _operation = state.operations.try_begin(Operation::Syncing)?;
+    let base_path = state.cursor_base()?;
+
+    let outcome = operations::sync_current_account(
+        &base_path,
+        open_store(&state).as_ref(),
+        fetch_info.unwrap_or(true),
+        &live_api(&state)?,
+    )
+    .map_err(AppError::from)?;
+
+    // Cursor's own login is the best proof of who a token belongs to
+    if let SyncOutcome::Added { account, .. } | SyncOutcome::Updated { account, .. } = &outcome {
+        if let Err(e) = identity_store(&state).record(&account.email, account.access_token.expose())
+        {
+            tracing::warn!("Failed to record verified identity: {}", e);
+        }
+    }
+
+    if !matches!(outcome, SyncOutcome::NothingLoggedIn) {
+        state.accounts_changed.notify();
+    }
+    Ok(outcome)
+}
+
+// Notification text describing a sync result
+fn sync_message(outcome: &SyncOutcome) -> (&'static str, String) {
+    match outcome {
+        SyncOutcome::Added { account, .. } => (
+            t("notify.sync_added.title"),
+            tf("notify.sync_added.body", &[("email", &account.email)]),
+        ),
+        SyncOutcome::Updated { account, .. } => (
+            t("notify.sync_updated.title"),
+            tf("notify.sync_updated.body", &[("email", &account.email)]),
+        ),
+        SyncOutcome::NothingLoggedIn => (
+            t("notify.sync_nothing.title"),
+            t("notify.sync_nothing.body").to_string(),
+        ),
+    }
+}
+
+#[tauri::command]
+fn get_logs(state: State<AppState>) -> Result<Vec<LogEntry>, AppError> {
+    let log_path = state.log_path();
+    let logger = Logger::new(log_path);
+
+    logger.read_logs().map_err(AppError::from)
+}
+
+#[tauri::command]
+fn clear_logs(state: State<AppState>) -> Result<(), AppError> {
+    let log_path = state.log_path();
+    let logger = Logger::new(log_path);
+
+    logger.clear_logs().map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_log_file_path(state: State<AppState>) -> Result<String, AppError> {
+    let log_path = state.log_path();
+    let logger = Logger::new(log_path);
+
+    Ok(logger.get_log_path().to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn sync_from_tray(app: tauri::AppHandle, state: State<AppState>) -> Result<String, AppError> {
+    tracing::info!("Syncing current account from tray");
+    let run = run_tray_action(&state, TrayAction::Sync, false, || {
+        let outcome = sync_current_account(app.state(), None)?;
+        Ok(sync_message(&outcome).1)
+    });
+    tray_run_result(run)
+}
+
+/// Refresh every account unless a tray refresh ran within the cooldown;
+/// `force` skips the cooldown
+#[tauri::command]
+fn refresh_from_tray(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    force: Option<bool>,
+) -> Result<String, AppError> {
+    tracing::info!("Refreshing all accounts from tray");
+    let run = run_tray_action(&state, TrayAction::Refresh, force.unwrap_or(false), || {
+        let accounts = refresh_all_accounts(&app.state(), RefreshTrigger::Tray, &|_| true)?;
+        Ok(refreshed_message(accounts.len()))
+    });
+    tray_run_result(run)
+}
+
+/// How a tray action went through the tray queue
+enum TrayRun {
+    Ran(TrayActionResult),
+    /// An identical action was already running; its result arrives here
+    Joined(Receiver<TrayActionResult>),
+    /// A refresh finished this long ago, within the cooldown
+    CoolingDown(Duration),
+}
+
+// Run `action` unless the same one is in flight or a refresh just finished
+fn run_tray_action(
+    state: &AppState,
+    action: TrayAction,
+    force: bool,
+    run: impl FnOnce() -> TrayActionResult,
+) -> TrayRun {
+    let cooldown = load_settings(state).tray_refresh_cooldown();
+    match state
+        .tray_actions
+        .admit(action, Instant::now(), cooldown, force)
+    {
+        Admission::Run(ticket) => {
+            let result = run();
+            ticket.finish(&result, Instant::now());
+            TrayRun::Ran(result)
+        }
+        Admission::Joined(receiver) => {
+            tracing::info!("{:?} already running from the tray, waiting for it", action);
+            TrayRun::Joined(receiver)
+        }
+        Admission::CoolingDown { ago } => {
+            tracing::info!("Skipping tray refresh: last one finished {:?} ago", ago);
+            TrayRun::CoolingDown(ago)
+        }
+    }
+}
+
+// For commands, which answer with the result of the run they joined
+fn tray_run_result(run: TrayRun) -> TrayActionResult {
+    match run {
+        TrayRun::Ran(result) => result,
+        TrayRun::Joined(receiver) => receiver
+            .recv()
+            .unwrap_or_else(|_| Err("The running tray action was interrupted".into())),
+        TrayRun::CoolingDown(ago) => Ok(recently_refreshed_message(ago)),
+    }
+}
+
+fn refreshed_message(count: usize) -> String {
+    tf("notify.refreshed.body", &[("count", &count.to_string())])
+}
+
+fn recently_refreshed_message(ago: Duration) -> String {
+    tf(
+        "notify.recently_refreshed.body",
+        &[("seconds", &ago.as_secs().to_string())],
+    )
+}
+
+fn onboarding_status(state: &AppState, settings: &Settings) -> OnboardingStatus {
+    let cursor_base = state.cursor_base().ok();
+    onboarding::check(
+        cursor_base.as_deref(),
+        open_store(state).as_ref(),
+        ProcessManager::is_cursor_running(),
+        &settings.onboarding_steps,
+    )
+}
+
+/// Path, accounts, running Cursor and current login in one payload for the setup wizard
+#[tauri::command]
+fn get_onboarding_status(state: State<AppState>) -> Result<OnboardingStatus, AppError> {
+    Ok(onboarding_status(&state, &load_settings(&state)))
+}
+
+/// Record a finished wizard step so it isn't shown again
+#[tauri::command]
+fn complete_onboarding_step(
+    state: State<AppState>,
+    step: OnboardingStep,
+) -> Result<OnboardingStatus, AppError> {
+    let settings = SettingsManager::new(state.settings_path())
+        .update(|settings| {
+            if !settings.onboarding_steps.contains(&step) {
+                settings.onboarding_steps.push(step);
+            }
+        })
+        .map_err(AppError::from)?;
+    Ok(onboarding_status(&state, &settings))
+}
+
+#[tauri::command]
+fn get_settings(state: State<AppState>) -> Result<Settings, AppError> {
+    let settings_path = state.settings_path();
+    SettingsManager::new(settings_path)
+        .load()
+        .map(Settings::without_secrets)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn update_settings(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    settings: Settings,
+) -> Result<Settings, AppError> {
+    apply_settings(&app, &state, settings)
+}
+
+// Validate new settings against the stored ones, which are returned as well.
+// Anything beyond cosmetic changes needs the app unlocked.
+fn prepare_settings(
+    state: &AppState,
+    mut settings: Settings,
+) -> Result<(Settings, Settings), AppError> {
+    let previous = SettingsManager::new(state.settings_path())
+        .load()
+        .map_err(AppError::from)?;
+    settings.app_lock_hash = previous.app_lock_hash.clone();
+    settings.manifest_signing_key = previous.manifest_signing_key.clone();
+    settings.manifest_encryption_key = previous.manifest_encryption_key.clone();
+
+    let thresholds = (settings.tray_usage_warning, settings.tray_usage_critical);
+    if !(0.0..=100.0).contains(&thresholds.0)
+        || !(0.0..=100.0).contains(&thresholds.1)
+        || thresholds.0 >= thresholds.1
+    {
+        return Err(
+            "Tray usage thresholds must be between 0 and 100, warning below critical".into(),
+        );
+    }
+    if settings.tray_refresh_cooldown_secs > 3600 {
+        return Err("Tray refresh cooldown must be at most 3600 seconds".into());
+    }
+    http::validate_proxy(settings.http_proxy.as_deref()).map_err(AppError::from)?;
+    settings.api_policy.validate().map_err(AppError::from)?;
+    settings.webdav.validate().map_err(AppError::from)?;
+    settings.switch_hooks.validate().map_err(AppError::from)?;
+    if !(1..=168).contains(&settings.cooldown_hours) {
+        return Err("Cooldown must be between 1 and 168 hours".into());
+    }
+    if settings.switch_verify_timeout_secs > 3600 {
+        return Err("Switch verification must time out within 3600 seconds".into());
+    }
+    if !(1..=720).contains(&settings.stale_after_hours) {
+        return Err("Accounts must turn stale after 1 to 720 hours".into());
+    }
+    if settings.account_backup_retention > 1000 {
+        return Err("At most 1000 account backups can be kept".into());
+    }
+    if !(1..=100_000).contains(&settings.switch_history_max_rows) {
+        return Err("The switch history must keep 1 to 100000 rows".into());
+    }
+    let tag_filter = settings
+        .tray_tag_filter
+        .as_deref()
+        .map(|tag| tags::normalize(&[tag]))
+        .unwrap_or_default();
+    if tag_filter.len() > 1 {
+        return Err(AppError::InvalidInput {
+            field: "tray_tag_filter",
+            reason: "must be a single tag".to_string(),
+        });
+    }
+    settings.tray_tag_filter = tag_filter.into_iter().next();
+    let storage_csv = settings
+        .data_storage_dir
+        .as_deref()
+        .map(|dir| std::path::Path::new(dir).join(app_info::ACCOUNTS_FILE_NAME));
+    if settings.data_storage_dir != previous.data_storage_dir
+        && storage_csv != Some(state.csv_path())
+    {
+        return Err(AppError::InvalidInput {
+            field: "data_storage_dir",
+            reason: "move the accounts with set_data_storage_path".to_string(),
+        });
+    }
+    if settings.account_store == StoreBackend::Sqlite
+        && !account_store::db_path(&state.csv_path()).exists()
+    {
+        return Err(AppError::InvalidInput {
+            field: "account_store",
+            reason: "migrate the accounts with migrate_store_to_sqlite first".to_string(),
+        });
+    }
+    // Keep the API token stable across saves
+    if settings.http_api_token.is_none() {
+        settings.http_api_token = previous.http_api_token.clone();
+    }
+    // Viewers may not leave read-only mode, nor change what it allows, and
+    // while locked only the looks of the app change
+    if previous.read_only_mode
+        || settings.read_only_policy() != previous.read_only_policy()
+        || settings.changes_beyond_cosmetic(&previous)
+    {
+        ensure_unlocked(state)?;
+    }
+    if settings.read_only_mode && settings.app_lock_hash.is_none() {
+        return Err("Set an app lock first, or anyone could turn read-only mode off".into());
+    }
+    Ok((settings, previous))
+}
+
+// Validate, save and apply new settings, restarting whatever depends on a changed value
+fn apply_settings(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    settings: Settings,
+) -> Result<Settings, AppError> {
+    let (mut settings, previous) = prepare_settings(state, settings)?;
+    let manager = SettingsManager::new(state.settings_path());
+
+    // Create the API token on first enable
+    if settings.http_api_enabled && settings.http_api_token.is_none() {
+        settings.http_api_token = Some(http_api::generate_token());
+    }
+
+    // Apply launch-at-login immediately so the toggle doesn't need a reinstall
+    if settings.launch_at_login != previous.launch_at_login {
+        let autostart = autostart_manager(app)?;
+        let result = if settings.launch_at_login {
+            autostart.enable()
+        } else {
+            autostart.disable()
+        };
+        let status = result.map_err(|e| {
+            tracing::error!("Failed to update autostart entry: {}", e);
+            AppError::from(e)
+        })?;
+        tracing::info!("Autostart entry updated at: {}", status.location);
+    }
+
+    if settings.http_api_enabled != previous.http_api_enabled
+        || settings.http_api_port != previous.http_api_port
+        || settings.http_api_token != previous.http_api_token
+    {
+        apply_http_api_settings(app, &settings)?;
+    }
+
+    let clipboard_watcher_changed =
+        settings.clipboard_watcher_enabled != previous.clipboard_watcher_enabled;
+    if clipboard_watcher_changed {
+        apply_clipboard_watcher(app, settings.clipboard_watcher_enabled);
+    }
+    if settings.auto_update_check != previous.auto_update_check {
+        apply_update_check(app, settings.auto_update_check);
+    }
+    if settings.auto_export_enabled != previous.auto_export_enabled {
+        apply_auto_export(app, settings.auto_export_enabled);
+    }
+    if settings.webdav != previous.webdav {
+        apply_webdav_sync(app, settings.webdav.interval());
+    }
+    let token_health_changed = settings.token_health_check != previous.token_health_check;
+    if token_health_changed {
+        apply_token_health_check(app, settings.token_health_check);
+    }
+    let locale_changed = settings.locale != previous.locale;
+    if locale_changed {
+        i18n::set_locale(settings.locale);
+    }
+
+    manager.save(&settings).map_err(AppError::from)?;
+    tracing::info!("Settings updated");
+    if settings.account_store != previous.account_store {
+        tracing::info!("Accounts are now kept in {:?}", settings.account_store);
+        state.accounts_changed.notify();
+    }
+    let read_only_changed = settings.read_only_policy() != previous.read_only_policy();
+    state.operations.set_read_only(settings.read_only_policy());
+    if settings.http_proxy != previous.http_proxy || settings.api_policy != previous.api_policy {
+        state.set_http(HttpClientFactory::from_settings(&settings));
+    }
+
+    let tray_style_changed = settings.tray_label_style() != previous.tray_label_style()
+        || settings.tray_tag_filter != previous.tray_tag_filter;
+    if clipboard_watcher_changed
+        || locale_changed
+        || tray_style_changed
+        || token_health_changed
+        || read_only_changed
+    {
+        update_tray_menu(app);
+    }
+    Ok(settings.without_secrets())
+}
+
+/// Totals over every account, or the members of `pool`; archived accounts
+/// only count with `include_archived`
+#[tauri::command]
+fn get_fleet_stats(
+    state: State<AppState>,
+    pool: Option<String>,
+    include_archived: Option<bool>,
+) -> Result<FleetStats, AppError> {
+    let accounts = listed_accounts(&state, pool.as_deref(), include_archived)?;
+    Ok(FleetStats::of(&accounts))
+}
+
+/// Write a Markdown or HTML status report of the accounts to `path`; reports
+/// never contain tokens. `columns` defaults to every column; archived
+/// accounts are only reported with `include_archived`.
+#[tauri::command]
+fn export_report(
+    state: State<AppState>,
+    path: String,
+    format: ReportFormat,
+    columns: Option<Vec<ReportColumn>>,
+    pool: Option<String>,
+    include_archived: Option<bool>,
+) -> Result<(), AppError> {
+    write_report(
+        &state,
+        &path,
+        format,
+        columns,
+        pool.as_deref(),
+        include_archived,
+    )
+}
+
+fn write_report(
+    state: &AppState,
+    path: &str,
+    format: ReportFormat,
+    columns: Option<Vec<ReportColumn>>,
+    pool: Option<&str>,
+    include_archived: Option<bool>,
+) -> Result<(), AppError> {
+    let columns = columns
+        .filter(|columns| !columns.is_empty())
+        .unwrap_or_else(|| report::DEFAULT_COLUMNS.to_vec());
+    let accounts = listed_accounts(state, pool, include_archived)?;
+
+    let content = report::render(
+        &accounts,
+        &FleetStats::of(&accounts),
+        &columns,
+        format,
+        chrono::Local::now().naive_local(),
+    );
+    std::fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    tracing::info!(
+        "Report of {} account(s) written to {}",
+        accounts.len(),
+        path
+    );
+    Ok(())
+}
+
+/// Every stored account as JSON, e.g. to move them to another machine. With a
+/// `path` the JSON is written there and the path is returned, otherwise the
+/// JSON itself. Real tokens need the app unlocked; `mask_tokens` keeps only
+/// their last 6 characters. Passwords are only written with `include_passwords`.
+#[tauri::command]
+fn export_accounts_json(
+    state: State<AppState>,
+    path: Option<String>,
+    mask_tokens: Option<bool>,
+    include_passwords: Option<bool>,
+) -> Result<String, AppError> {
+    let mask_tokens = mask_tokens.unwrap_or(false);
+    let include_passwords = include_passwords.unwrap_or(false);
+    if !mask_tokens || include_passwords {
+        ensure_unlocked(&state)?;
+    }
+    let accounts = stored_accounts(&state)?;
+    let content = auto_export::accounts_json(&accounts, mask_tokens, include_passwords)
+        .map_err(AppError::from)?;
+
+    let Some(path) = path else {
+        return Ok(content);
+    };
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    tracing::info!("Exported {} account(s) to {}", accounts.len(), path);
+    Ok(path)
+}
+
+/// Every stored account as an Excel workbook at `path`, creating its folder.
+/// `include_tokens` adds a sheet with the real tokens and `include_passwords`
+/// one with the passwords, both of which need the app unlocked. Returns the
+/// absolute path written.
+#[tauri::command]
+fn export_accounts_xlsx(
+    state: State<AppState>,
+    path: String,
+    include_tokens: bool,
+    include_passwords: Option<bool>,
+) -> Result<String, AppError> {
+    let include_passwords = include_passwords.unwrap_or(false);
+    if include_tokens || include_passwords {
+        ensure_unlocked(&state)?;
+    }
+    let accounts = stored_accounts(&state)?;
+    let written = xlsx_export::write_workbook(
+        std::path::Path::new(&path),
+        &accounts,
+        include_tokens,
+        include_passwords,
+    )
+    .map_err(AppError::from)?;
+    tracing::info!(
+        "Exported {} account(s) to {}",
+        accounts.len(),
+        written.display()
+    );
+    Ok(written.display().to_string())
+}
+
+/// Write the settings, minus secrets, to `path` for use on another machine
+#[tauri::command]
+fn export_settings(
+    state: State<AppState>,
+    path: String,
+    include_machine_specific: Option<bool>,
+) -> Result<(), AppError> {
+    let document = settings::export_settings(
+        &load_settings(&state),
+        include_machine_specific.unwrap_or(false),
+    )
+    .map_err(AppError::from)?;
+    let content = serde_json::to_string_pretty(&document).map_err(AppError::from)?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    tracing::info!("Settings exported to {}", path);
+    Ok(())
+}
+
+/// Public halves of the account pool keys, generated on first use or when `regenerate` is set.
+/// Regenerating means teammates need the new verify and encryption keys.
+#[tauri::command]
+fn generate_manifest_keys(
+    state: State<AppState>,
+    regenerate: Option<bool>,
+) -> Result<PublicKeys, AppError> {
+    ensure_unlocked(&state)?;
+    let settings = SettingsManager::new(state.settings_path())
+        .update(|settings| {
+            let missing = settings.manifest_signing_key.is_none()
+                || settings.manifest_encryption_key.is_none();
+            if missing || regenerate.unwrap_or(false) {
+                let keys = GeneratedKeys::generate();
+                settings.manifest_signing_key = Some(keys.signing_key.expose().to_string());
+                settings.manifest_encryption_key = Some(keys.encryption_key.expose().to_string());
+                tracing::info!("Generated new account pool keys");
+            }
+        })
+        .map_err(AppError::from)?;
+
+    match (
+        &settings.manifest_signing_key,
+        &settings.manifest_encryption_key,
+    ) {
+        (Some(signing_key), Some(encryption_key)) => {
+            PublicKeys::of(signing_key, encryption_key).map_err(AppError::from)
+        }
+        _ => Err("Account pool keys are missing".into()),
+    }
+}
+
+/// Write the given accounts into a signed manifest for teammates. The stored
+/// signing key is used unless `signing_key` is given; `recipient_key` seals the tokens.
+#[tauri::command]
+fn export_pool_manifest(
+    state: State<AppState>,
+    emails: Vec<String>,
+    path: String,
+    signing_key: Option<String>,
+    recipient_key: Option<String>,
+) -> Result<usize, AppError> {
+    for email in &emails {
+        validation::email("emails", email)?;
+    }
+    ensure_unlocked(&state)?;
+
+    let signing_key = signing_key
+        .or_else(|| load_settings(&state).manifest_signing_key)
+        .ok_or("No signing key; generate the account pool keys first")?;
+    let signing_key =
+        pool_manifest::parse_signing_key(&signing_key).map_err(|e| AppError::InvalidInput {
+            field: "signing_key",
+            reason: e.to_string(),
+        })?;
+    let recipient = recipient_key
+        .as_deref()
+        .map(pool_manifest::parse_encryption_key)
+        .transpose()
+        .map_err(|e| AppError::InvalidInput {
+            field: "recipient_key",
+            reason: e.to_string(),
+        })?;
+
+    let wanted = comparison::dedup_emails(&emails);
+    let accounts: Vec<Account> = stored_accounts(&state)?
+        .into_iter()
+        .filter(|account| wanted.contains(&account.email))
+        .collect();
+    if let Some(missing) = wanted
+        .iter()
+        .find(|email| !accounts.iter().any(|account| &account.email == *email))
+    {
+        return Err(format!("Account not found: {}", missing).into());
+    }
+
+    let exporter = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    let content = pool_manifest::export(
+        &accounts,
+        &exporter,
+        &signing_key,
+        recipient.as_ref(),
+        chrono::Utc::now(),
+    )
+    .map_err(AppError::from)?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    tracing::info!(
+        "Exported {} accounts to pool manifest {}",
+        accounts.len(),
+        path
+    );
+    Ok(accounts.len())
+}
+
+/// Verify a manifest against the sender's `verify_key` and add the accounts
+/// that aren't stored yet
+#[tauri::command]
+fn import_pool_manifest(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    path: String,
+    verify_key: String,
+) -> Result<PoolImportReport, AppError> {
+    let verify_key =
+        pool_manifest::parse_verify_key(&verify_key).map_err(|e| AppError::InvalidInput {
+            field: "verify_key",
+            reason: e.to_string(),
+        })?;
+    let content =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    let decryption_key = load_settings(&state)
+        .manifest_encryption_key
+        .as_deref()
+        .map(pool_manifest::parse_decryption_key)
+        .transpose()
+        .map_err(AppError::from)?;
+    let pool = pool_manifest::import(&content, &verify_key, decryption_key.as_ref())
+        .map_err(AppError::from)?;
+
+    let emails: Vec<String> = pool.accounts.iter().map(|a| a.email.clone()).collect();
+    let added = {
+        let _operation = state
+            .operations
+            .begin_when_idle(Operation::Importing, IMPORT_WRITE_TIMEOUT)?;
+        open_store(&state)
+            .batch_add_new_accounts(pool.accounts)
+            .map_err(AppError::from)?
+    };
+    let skipped = emails
+        .into_iter()
+        .filter(|email| !added.contains(email))
+        .collect();
+    tracing::info!(
+        "Imported {} accounts from a pool manifest by {} ({})",
+        added.len(),
+        pool.exporter,
+        pool.signer
+    );
+
+    if !added.is_empty() {
+        state.accounts_changed.notify();
+        warn_credential_conflicts(&app, &state);
+    }
+    Ok(PoolImportReport {
+        signer: pool.signer,
+        exporter: pool.exporter,
+        created_at: pool.created_at,
+        added,
+        skipped,
+    })
+}
+
+/// One account as a compact code for pasting or a QR code on another machine
+#[tauri::command]
+fn export_account_code(state: State<AppState>, email: String) -> Result<String, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
+    let account = stored_accounts(&state)?
+        .into_iter()
+        .find(|account| account.email == email)
+        .ok_or_else(|| format!("Account not found: {}", email))?;
+    account_code::encode(&account).map_err(AppError::from)
+}
+
+/// Add the account in a code from `export_account_code` unless it is already stored
+#[tauri::command]
+fn import_account_code(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    code: String,
+) -> Result<AccountCodeImport, AppError> {
+    let now = chrono::Local::now().format(record_time::FORMAT).to_string();
+    let account = account_code::decode(&code, &now).map_err(|e| AppError::InvalidInput {
+        field: "code",
+        reason: e.to_string(),
+    })?;
+    validation::email("code", &account.email)?;
+    validation::token("code", account.access_token.expose())?;
+
+    let email = account.email.clone();
+    let added = {
+        let _operation = state
+            .operations
+            .begin_when_idle(Operation::Importing, IMPORT_WRITE_TIMEOUT)?;
+        open_store(&state)
+            .batch_add_new_accounts(vec![account])
+            .map_err(AppError::from)?
+    };
+    let added = !added.is_empty();
+    if added {
+        tracing::info!("Imported {} from an account code", email);
+        state.accounts_changed.notify();
+        warn_credential_conflicts(&app, &state);
+    }
+    Ok(AccountCodeImport { email, added })
+}
+
+/// Load settings exported by `export_settings`, merged into the current ones or replacing them
+#[tauri::command]
+fn import_settings(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    path: String,
+    merge: bool,
+) -> Result<SettingsImportReport, AppError> {
+    let imported = read_settings_import(&state, &path, merge)?;
+    if !imported.unknown_keys.is_empty() {
+        tracing::warn!("Ignoring unknown settings: {:?}", imported.unknown_keys);
+    }
+
+    let backup_path = SettingsManager::new(state.settings_path())
+        .backup()
+        .map_err(AppError::from)?;
+    let settings = apply_settings(&app, &state, imported.settings)?;
+    tracing::info!("Settings imported from {}", path);
+
+    Ok(SettingsImportReport {
+        settings,
+        unknown_keys: imported.unknown_keys,
+        ignored_keys: imported.ignored_keys,
+        backup_path: backup_path.map(|p| p.to_string_lossy().to_string()),
+    })
+}
+
+// The settings `path` would give, checked like `update_settings` before
+// anything is backed up
+fn read_settings_import(
+    state: &AppState,
+    path: &str,
+    merge: bool,
+) -> Result<settings::ImportedSettings, AppError> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let document: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid settings file: {}", e))?;
+
+    let current = SettingsManager::new(state.settings_path())
+        .load()
+        .map_err(AppError::from)?;
+    let imported = settings::import_settings(&current, &document, merge).map_err(AppError::from)?;
+    prepare_settings(state, imported.settings.clone())?;
+    Ok(imported)
+}
+
+#[tauri::command]
+fn set_locale(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    locale: String,
+) -> Result<Settings, AppError> {
+    let locale = Locale::parse(&locale).ok_or_else(|| format!("Unsupported locale: {}", locale))?;
+
+    let manager = SettingsManager::new(state.settings_path());
+    let settings = manager
+        .update(|settings| settings.locale = locale)
+        .map_err(AppError::from)?;
+
+    i18n::set_locale(locale);
+    tracing::info!("Locale set to {}", locale.code());
+    update_tray_menu(&app);
+    Ok(settings.without_secrets())
+}
+
+#[tauri::command]
+fn set_clipboard_watcher(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    enabled: bool,
+) -> Result<Settings, AppError> {
+    let settings_path = state.settings_path();
+    let settings = SettingsManager::new(settings_path)
+        .update(|s| s.clipboard_watcher_enabled = enabled)
+        .map_err(AppError::from)?;
+
+    apply_clipboard_watcher(&app, enabled);
+    update_tray_menu(&app);
+    Ok(settings.without_secrets())
+}
+
+fn app_lock_status(state: &AppState) -> AppLockStatus {
+    let enabled = load_settings(state).app_lock_hash.is_some();
+    let remaining = state.unlock_remaining().filter(|_| enabled);
+
+    AppLockStatus {
+        enabled,
+        locked: enabled && remaining.is_none(),
+        unlocked_for_secs: remaining.map(|r| r.as_secs()),
+    }
+}
+
+// Commands that reveal tokens or change Cursor call this first
+fn ensure_unlocked(state: &AppState) -> Result<(), AppError> {
+    if app_lock_status(state).locked {
+        return Err(AppError::Locked);
+    }
+    Ok(())
+}
+
+/// The switch, refresh, sync or reset currently running, if any
+#[tauri::command]
+fn get_running_operation(state: State<AppState>) -> Result<Option<Operation>, AppError> {
+    Ok(state.operations.current())
+}
+
+#[tauri::command]
+fn get_app_lock_status(state: State<AppState>) -> Result<AppLockStatus, AppError> {
+    Ok(app_lock_status(&state))
+}
+
+/// Set, change or (with an empty password) remove the app lock.
+/// Changing an existing lock requires an unlocked session.
+#[tauri::command]
+fn set_app_lock(
+    state: State<AppState>,
+    password: Option<String>,
+) -> Result<AppLockStatus, AppError> {
+    ensure_unlocked(&state)?;
+
+    let password = password.filter(|p| !p.is_empty());
+    if let Some(password) = &password {
+        if password.chars().count() < 4 {
+            return Err("The password must be at least 4 characters".into());
+        }
+    }
+
+    let hash = password.as_deref().map(app_lock::hash_password);
+    let enabled = hash.is_some();
+    let settings = SettingsManager::new(state.settings_path())
+        .update(|settings| settings.app_lock_hash = hash)
+        .map_err(AppError::from)?;
+
+    if enabled {
+        // Whoever just set the password stays unlocked for one session
+        state.unlock_for(Duration::from_secs(
+            u64::from(settings.app_lock_minutes) * 60,
+        ));
+        tracing::info!("App lock enabled");
+    } else {
+        state.lock();
+        tracing::info!("App lock removed");
+    }
+    Ok(app_lock_status(&state))
+}
+
+#[tauri::command]
+fn unlock_app(state: State<AppState>, password: String) -> Result<AppLockStatus, AppError> {
+    let settings = load_settings(&state);
+    let hash = match &settings.app_lock_hash {
+        Some(hash) => hash,
+        None => return Ok(app_lock_status(&state)),
+    };
+
+    if !app_lock::verify_password(&password, hash) {
+        tracing::warn!("Failed unlock attempt");
+        return Err(AppError::Unauthorized(ErrorText::plain(
+            "Incorrect password",
+        )));
+    }
+
+    state.unlock_for(Duration::from_secs(
+        u64::from(settings.app_lock_minutes) * 60,
+    ));
+    tracing::info!("App unlocked for {} minutes", settings.app_lock_minutes);
+    Ok(app_lock_status(&state))
+}
+
+#[tauri::command]
+fn lock_app(state: State<AppState>) -> Result<AppLockStatus, AppError> {
+    state.lock();
+    Ok(app_lock_status(&state))
+}
+
+/// Encrypt the stored tokens with a key derived from `passphrase` from now on
+#[tauri::command]
+fn set_store_passphrase(state: State<AppState>, passphrase: String) -> Result<(), AppError> {
+    ensure_unlocked(&state)?;
+    if passphrase.chars().count() < 8 {
+        return Err(AppError::InvalidInput {
+            field: "passphrase",
+            reason: "must be at least 8 characters".to_string(),
+        });
+    }
+
+    open_store(&state)
+        .set_passphrase(&passphrase)
+        .map_err(AppError::from)?;
+    if let Err(e) = import_job_store(&state).seal_tokens() {
+        tracing::warn!("Failed to encrypt the tokens of import jobs: {:#}", e);
+    }
+    tracing::info!("Account store encryption enabled");
+    Ok(())
+}
+
+#[tauri::command]
+fn unlock_store(state: State<AppState>, passphrase: String) -> Result<(), AppError> {
+    open_store(&state).unlock(&passphrase).map_err(|e| {
+        tracing::warn!("Failed to unlock the account store: {}", e);
+        AppError::from(e)
+    })?;
+    tracing::info!("Account store unlocked");
+    state.accounts_changed.notify();
+    Ok(())
+}
+
+/// Copy every account of the CSV into accounts.db and keep them there from
+/// now on. Whatever the database held is replaced; the CSV stays as it was.
+/// Returns the number of accounts migrated.
+#[tauri::command]
+fn migrate_store_to_sqlite(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+) -> Result<usize, AppError> {
+    ensure_unlocked(&state)?;
+    let migrated = {
+        let _operation = state
+            .operations
+            .begin_when_idle(Operation::Importing, IMPORT_WRITE_TIMEOUT)?;
+        account_store::migrate_csv_to_sqlite(&state.csv_path()).map_err(AppError::from)?
+    };
+    let settings = Settings {
+        account_store: StoreBackend::Sqlite,
+        ..load_settings(&state)
+    };
+    apply_settings(&app, &state, settings)?;
+    state.accounts_changed.notify();
+    Ok(migrated)
+}
+
+#[tauri::command]
+fn is_store_locked(state: State<AppState>) -> Result<bool, AppError> {
+    Ok(open_store(&state).is_locked())
+}
+
+/// Import whatever was copied: account lines, JSON, a token or a session
+/// cookie, told apart by `clipboard_import::detect`. With `preview_only` the
+/// accounts are only parsed, though a token is exchanged for its account
+/// either way. Stored accounts of the same email, in any case, get the new
+/// tokens instead of a second row.
+#[tauri::command]
+fn import_from_clipboard(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    preview_only: bool,
+) -> Result<ClipboardImport, AppError> {
+    let content = app
+        .clipboard_manager()
+        .read_text()
+        .map_err(|e| AppError::from(e.to_string()))?
+        .unwrap_or_default();
+    let now = chrono::Local::now().format(record_time::FORMAT).to_string();
+    let mut import = clipboard_import::parse(
+        &content,
+        &CsvManager::new(state.csv_path()),
+        &now,
+        |token| convert_token(&state, token),
+    )
+    .map_err(AppError::from)?;
+    tracing::info!(
+        "Parsed {} account(s) from the clipboard as {:?}, {} skipped",
+        import.accounts.len(),
+        import.format,
+        import.skipped
+    );
+
+    if !preview_only && !import.accounts.is_empty() {
+        let report = {
+            let _operation = state
+                .operations
+                .begin_when_idle(Operation::Importing, IMPORT_WRITE_TIMEOUT)?;
+            open_store(&state)
+                .batch_merge_accounts(import.accounts.clone())
+                .map_err(AppError::from)?
+        };
+        if matches!(
+            import.format,
+            ClipboardFormat::Jwt | ClipboardFormat::SessionCookie
+        ) {
+            // As for import_token, the email came from Cursor's API for the token
+            for account in &import.accounts {
+                if let Err(e) =
+                    identity_store(&state).record(&account.email, account.access_token.expose())
+                {
+                    tracing::warn!("Failed to record verified identity: {}", e);
+                }
+            }
+        }
+        tracing::info!(
+            "Imported {} new account(s), merged {}",
+            report.added.len(),
+            report.merged.len()
+        );
+        state.accounts_changed.notify();
+        warn_credential_conflicts(&app, &state);
+        import.stored = Some(report);
+    }
+    import.accounts.iter_mut().for_each(redaction::mask_account);
+    Ok(import)
+}
+
+#[tauri::command]
+fn import_detected_token(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+) -> Result<Account, AppError> {
+    let detected = state
+        .detected_token
+        .lock_or_recover()
+        .clone()
+        .ok_or("No token has been detected in the clipboard")?;
+
+    // The token itself was never kept, so read it again and make sure it's the same one
+    let content = app
+        .clipboard_manager()
+        .read_text()
+        .map_err(|e| AppError::from(e.to_string()))?
+        .unwrap_or_default();
+    if clipboard_watcher::fingerprint(&content) != detected.fingerprint {
+        return Err("The clipboard no longer contains the detected token".into());
+    }
+    let token = clipboard_watcher::normalize_candidate(&content)
+        .ok_or("The clipboard no longer contains the detected token")?
+        .to_string();
+
+    let account = import_from_token(app.clone(), state.clone(), token)?;
+    *state.detected_token.lock_or_recover() = None;
+
+    // The import already rebuilds the tray, which also drops the import entry
+
+    if let Some(window) = app.get_window("main") {
+        let _ = window.emit("account-imported", &account.email);
+    }
+    Ok(account)
+}
+
+#[tauri::command]
+fn check_for_updates(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+) -> Result<UpdateInfo, AppError> {
+    tracing::info!("Checking for updates");
+    let current = app.package_info().version.clone();
+    let info = UpdateChecker::new(&state.http())
+        .and_then(|checker| checker.check(&current))
+        .map_err(|e| {
+            tracing::error!("Update check failed: {:#}", e);
+            AppError::from(e)
+        })?;
+
+    record_update_result(&app, &info);
+    Ok(info)
+}
+
+// Remember the latest release so the tray can show it, and tell the frontend once per version
+fn record_update_result(app: &tauri::AppHandle, info: &UpdateInfo) {
+    let state: State<AppState> = app.state();
+    let announced = {
+        let mut available = state.available_update.lock_or_recover();
+        let previous_latest = available.as_ref().map(|u| u.latest.clone());
+        *available = info.update_available.then(|| info.clone());
+        info.update_available && previous_latest.as_deref() != Some(info.latest.as_str())
+    };
+
+    if announced {
+        tracing::info!("Update available: v{}", info.latest);
+        let _ = app.emit_all("update-available", info);
+    }
+    update_tray_menu(app);
+}
+
+// Start or stop the daily background update check; failures there are only logged
+fn apply_update_check(app: &tauri::AppHandle, enabled: bool) {
+    const INITIAL_DELAY: Duration = Duration::from_secs(30);
+    const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+    let state: State<AppState> = app.state();
+    let mut task = state.update_task.lock_or_recover();
+
+    if !enabled {
+        *task = None;
+        return;
+    }
+    if task.is_some() {
+        return;
+    }
+
+    let handle = app.clone();
+    *task = Some(PeriodicTask::spawn(
+        "update-check",
+        INITIAL_DELAY,
+        INTERVAL,
+        move || {
+            let current = handle.package_info().version.clone();
+            let state: State<AppState> = handle.state();
+            if state.is_offline() {
+                tracing::debug!("Skipping update check: offline");
+                return;
+            }
+            match UpdateChecker::new(&state.http()).and_then(|checker| checker.check(&current)) {
+                Ok(info) => record_update_result(&handle, &info),
+                Err(e) => tracing::debug!("Automatic update check failed: {:#}", e),
+            }
+        },
+    ));
+}
+
+// Check the active account's token every few hours
+fn apply_token_health_check(app: &tauri::AppHandle, enabled: bool) {
+    const INITIAL_DELAY: Duration = Duration::from_secs(20);
+
+    let state: State<AppState> = app.state();
+    let mut task = state.token_health_task.lock_or_recover();
+
+    if !enabled {
+        *task = None;
+        *state.token_health.lock_or_recover() = None;
+        return;
+    }
+    if task.is_some() {
+        return;
+    }
+
+    let handle = app.clone();
+    *task = Some(PeriodicTask::spawn(
+        "token-health",
+        INITIAL_DELAY,
+        token_health::CHECK_INTERVAL,
+        move || {
+            if check_token_health(&handle) {
+                update_tray_menu(&handle);
+            }
+        },
+    ));
+}
+
+// Record whether the logged-in account's token is about to expire and notify
+// once when it starts needing re-authentication. Returns true when the tray
+// warning changed.
+fn check_token_health(app: &tauri::AppHandle) -> bool {
+    let state: State<AppState> = app.state();
+    if state.is_offline() {
+        tracing::debug!("Skipping token health check: offline");
+        return false;
+    }
+    let Some((email, access_token)) = state.cursor_base().ok().and_then(|base_path| {
+        Database::new(PathDetector::get_db_path(&base_path))
+            .get_auth_info()
+            .ok()
+    }) else {
+        return false;
+    };
+
+    // Nothing waits on the background check, so it gets the full API timeout
+    let http = state.http();
+    let timeout = load_settings(&state).api_policy.timeout();
+    let Some(health) = token_health::assess(&access_token, chrono::Utc::now(), || {
+        TokenAuthClient::check_with_timeout(&http, timeout, &access_token)
+    }) else {
+        return false;
+    };
+
+    let report = TokenHealthReport {
+        email,
+        health,
+        checked_at: chrono::Utc::now(),
+    };
+    let previous = state.token_health.lock_or_recover().replace(report.clone());
+    let warned = |report: &TokenHealthReport| (report.email.clone(), report.health.needs_reauth());
+    let changed = previous.as_ref().map(warned) != Some(warned(&report));
+    if !changed {
+        return false;
+    }
+
+    tracing::info!("Token health of {}: {:?}", report.email, report.health);
+    let _ = app.emit_all("token-health-changed", &report);
+    match report.health {
+        TokenHealth::Healthy => {}
+        TokenHealth::Expiring { .. } => notifier::notify(
+            app,
+            t("notify.token_expiring.title"),
+            &tf("notify.token_expiring.body", &[("email", &report.email)]),
+        ),
+        TokenHealth::Expired => notifier::notify(
+            app,
+            t("notify.token_expired.title"),
+            &tf("notify.token_expired.body", &[("email", &report.email)]),
+        ),
+    }
+    true
+}
+
+#[tauri::command]
+fn get_token_health(state: State<AppState>) -> Result<Option<TokenHealthReport>, AppError> {
+    Ok(state.token_health.lock_or_recover().clone())
+}
+
+/// Exchange the logged-in account's session token for fresh tokens
+#[tauri::command]
+fn reauthenticate_current_account(state: State<AppState>) -> Result<Account, AppError> {
+    ensure_unlocked(&state)?;
+    let _operation = state.operations.try_begin(Operation::Reauthenticating)?;
+    let base_path = state.cursor_base()?;
+
+    let mut account = operations::reauthenticate_current_account(
+        &base_path,
+        open_store(&state).as_ref(),
+        |session| TokenAuthClient::new(&state.http())?.convert_token_to_account(session),
+    )
+    .map_err(AppError::from)?;
+
+    if let Err(e) = identity_store(&state).record(&account.email, account.access_token.expose()) {
+        tracing::warn!("Failed to record verified identity: {}", e);
+    }
+    // The accounts-changed handler checks the token again and clears the warning
+    state.accounts_changed.notify();
+
+    redaction::mask_account(&mut account);
+    Ok(account)
+}
+
+fn spawn_tray_reauth(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state: State<AppState> = app.state();
+        match reauthenticate_current_account(state) {
+            Ok(account) => notify_tray_success(
+                &app,
+                t("notify.reauth_done.title"),
+                &tf("notify.reauth_done.body", &[("email", &account.email)]),
+            ),
+            Err(e) => {
+                tracing::error!("Failed to re-authenticate: {}", e);
+                report_tray_failure(
+                    &app,
+                    t("notify.reauth_failed.title"),
+                    TrayOperationFailure::new("reauth", None, &e),
+                );
+            }
+        }
+    });
+}
+
+// Export all accounts into the configured folder
+fn run_auto_export(state: &AppState, settings: &Settings) -> Result<ExportReport, AppError> {
+    let dir = settings
+        .auto_export_dir
+        .as_deref()
+        .filter(|dir| !dir.is_empty())
+        .ok_or("No export folder configured")?;
+    let accounts = stored_accounts(state)?;
+
+    let report = auto_export::run_export(
+        &accounts,
+        std::path::Path::new(dir),
+        settings.auto_export_format,
+        settings.auto_export_retention,
+        settings.auto_export_include_passwords,
+        chrono::Local::now(),
+    )
+    .map_err(AppError::from)?;
+
+    tracing::info!(
+        "Exported {} accounts to {} ({} old exports pruned)",
+        report.accounts,
+        report.path.display(),
+        report.pruned
+    );
+    Ok(report)
+}
+
+/// Run the auto export once with the saved settings, ignoring whether one ran today
+#[tauri::command]
+fn run_auto_export_now(state: State<AppState>) -> Result<ExportReport, AppError> {
+    // Exports contain full tokens
+    ensure_unlocked(&state)?;
+    run_auto_export(&state, &load_settings(&state)).map_err(|e| {
+        tracing::error!("Auto export failed: {}", e);
+        e
+    })
+}
+
+// Start or stop the daily export. The task wakes hourly and exports once per
+// calendar day, so a missed night is caught up soon after the app starts.
+fn apply_auto_export(app: &tauri::AppHandle, enabled: bool) {
+    const INITIAL_DELAY: Duration = Duration::from_secs(60);
+    const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    let state: State<AppState> = app.state();
+    let mut task = state.auto_export_task.lock_or_recover();
+
+    if !enabled {
+        *task = None;
+        return;
+    }
+    if task.is_some() {
+        return;
+    }
+
+    let handle = app.clone();
+    *task = Some(PeriodicTask::spawn(
+        "auto-export",
+        INITIAL_DELAY,
+        INTERVAL,
+        move || {
+            let state: State<AppState> = handle.state();
+            let settings = load_settings(&state);
+            if !settings.auto_export_enabled {
+                return;
+            }
+
+            let today = chrono::Local::now().date_naive();
+            if let Some(dir) = &settings.auto_export_dir {
+                if auto_export::has_export_on(std::path::Path::new(dir), today) {
+                    return;
+                }
+            }
+
+            if let Err(e) = run_auto_export(&state, &settings) {
+                tracing::error!("Auto export failed: {}", e);
+                let mut notified = state.export_failure_notified.lock_or_recover();
+                if *notified != Some(today) {
+                    *notified = Some(today);
+                    drop(notified);
+                    notifier::notify(
+                        &handle,
+                        t("notify.export_failed.title"),
+                        &redaction::scrub_secrets(&e.to_string()),
+                    );
+                }
+            }
+        },
+    ));
+}
+
+/// Store the WebDAV password in the OS keychain; None or an empty one removes it
+#[tauri::command]
+fn set_webdav_password(state: State<AppState>, password: Option<String>) -> Result<(), AppError> {
+    ensure_unlocked(&state)?;
+    let password = password.filter(|password| !password.is_empty());
+    keychain::set_password(keychain::WEBDAV_PASSWORD, password.as_deref()).map_err(AppError::from)
+}
+
+fn mask_sync_conflicts(conflicts: &mut [SyncConflict]) {
+    for conflict in conflicts {
+        redaction::mask_account(&mut conflict.local);
+        redaction::mask_account(&mut conflict.remote);
+    }
+}
+
+// One sync with the WebDAV copy; the caller holds the cloud sync operation
+fn run_webdav_sync(app: &tauri::AppHandle, state: &AppState) -> Result<SyncReport, AppError> {
+    let settings = load_settings(state);
+    if !settings.webdav.enabled {
+        return Err("WebDAV sync is not enabled".into());
+    }
+    let password = keychain::get_password(keychain::WEBDAV_PASSWORD).map_err(AppError::from)?;
+    let client =
+        WebDavClient::new(&state.http(), &settings.webdav, password).map_err(AppError::from)?;
+
+    let now = chrono::Local::now().format(record_time::FORMAT).to_string();
+    let mut report =
+        webdav_sync::sync(&client, open_store(state).as_ref(), &now).map_err(AppError::from)?;
+    tracing::info!(
+        "WebDAV sync pulled {} and pushed {} row(s), {} conflict(s)",
+        report.pulled,
+        report.pushed,
+        report.conflicts.len()
+    );
+
+    if report.pulled > 0 {
+        state.accounts_changed.notify();
+    }
+    mask_sync_conflicts(&mut report.conflicts);
+    if !report.conflicts.is_empty() {
+        let _ = app.emit_all("sync-conflicts", &report.conflicts);
+    }
+    Ok(report)
+}
+
+/// Merge the account CSV with the WebDAV copy now. Rows edited differently on
+/// both sides are reported as conflicts and also sent as `sync-conflicts`.
+#[tauri::command]
+fn sync_now(app: tauri::AppHandle, state: State<AppState>) -> Result<SyncReport, AppError> {
+    ensure_unlocked(&state)?;
+    let _operation = state.operations.try_begin(Operation::CloudSync)?;
+    run_webdav_sync(&app, &state)
+}
+
+/// Conflicts left by the last sync, with tokens masked
+#[tauri::command]
+fn get_sync_conflicts(state: State<AppState>) -> Result<Vec<SyncConflict>, AppError> {
+    let mut conflicts = webdav_sync::SyncStore::new(&state.csv_path())
+        .load_state()
+        .map_err(AppError::from)?
+        .conflicts;
+    mask_sync_conflicts(&mut conflicts);
+    Ok(conflicts)
+}
+
+/// Keep one version of a conflicting row; the next sync uploads it.
+/// Returns false when `email` has no conflict.
+#[tauri::command]
+fn resolve_sync_conflict(
+    state: State<AppState>,
+    email: String,
+    keep: SyncSide,
+) -> Result<bool, AppError> {
+    validation::email("email", &email)?;
+    ensure_unlocked(&state)?;
+    let _operation = state.operations.try_begin(Operation::CloudSync)?;
+    let resolved = webdav_sync::resolve_conflict(open_store(&state).as_ref(), &email, keep)
+        .map_err(AppError::from)?;
+    if resolved {
+        tracing::info!("Resolved sync conflict for {} keeping {:?}", email, keep);
+        if keep == SyncSide::Remote {
+            state.accounts_changed.notify();
+        }
+    }
+    Ok(resolved)
+}
+
+// Restart the background sync with a new interval, or stop it for None. A run
+// is skipped while offline or while another operation writes the accounts.
+fn apply_webdav_sync(app: &tauri::AppHandle, interval: Option<Duration>) {
+    const INITIAL_DELAY: Duration = Duration::from_secs(30);
+
+    let state: State<AppState> = app.state();
+    let mut task = state.webdav_sync_task.lock_or_recover();
+    *task = None;
+    let Some(interval) = interval else {
+        return;
+    };
+
+    let handle = app.clone();
+    *task = Some(PeriodicTask::spawn(
+        "webdav-sync",
+        INITIAL_DELAY,
+        interval,
+        move || {
+            let state: State<AppState> = handle.state();
+            if state.is_offline() {
+                tracing::debug!("Skipping WebDAV sync: offline");
+                return;
+            }
+            let Ok(_operation) = state.operations.try_begin(Operation::CloudSync) else {
+                tracing::debug!("Skipping WebDAV sync: another operation is running");
+                return;
+            };
+            if let Err(e) = run_webdav_sync(&handle, &state) {
+                tracing::warn!("WebDAV sync failed: {}", e);
+            }
+        },
+    ));
+}
+
+// Start or stop polling the clipboard for tokens
+fn apply_clipboard_watcher(app: &tauri::AppHandle, enabled: bool) {
+    let state: State<AppState> = app.state();
+    let mut watcher = state.clipboard_watcher.lock_or_recover();
+
+    if !enabled {
+        if let Some(running) = watcher.take() {
+            running.stop();
+        }
+        *state.detected_token.lock_or_recover() = None;
+        return;
+    }
+    if watcher.is_some() {
+        return;
+    }
+
+    let reader = app.clone();
+    let accounts_source = app.clone();
+    let notifier_handle = app.clone();
+
+    *watcher = Some(ClipboardWatcher::start(
+        move || reader.clipboard_manager().read_text().ok().flatten(),
+        move || {
+            let state: State<AppState> = accounts_source.state();
+            let accounts = stored_accounts(&state).unwrap_or_default();
+            clipboard_watcher::known_user_ids(&accounts)
+        },
+        move |detected| on_token_detected(&notifier_handle, detected),
+    ));
+}
+
+fn on_token_detected(app: &tauri::AppHandle, detected: DetectedToken) {
+    let state: State<AppState> = app.state();
+    *state.detected_token.lock_or_recover() = Some(detected.clone());
+
+    let _ = app.emit_all("token-detected", &detected);
+    notifier::notify(
+        app,
+        t("notify.token_detected.title"),
+        t("notify.token_detected.body"),
+    );
+    update_tray_menu(app);
+}
+
+// Start, restart or stop the local API server so it matches the settings
+fn apply_http_api_settings(app: &tauri::AppHandle, settings: &Settings) -> Result<(), AppError> {
+    let state: State<AppState> = app.state();
+    let mut server = state.http_api.lock_or_recover();
+
+    if let Some(running) = server.take() {
+        running.stop();
+    }
+
+    if !settings.http_api_enabled {
+        return Ok(());
+    }
+
+    let token = settings
+        .http_api_token
+        .clone()
+        .ok_or("Local API token not set")?;
+    let handle = app.clone();
+    let handler: Arc<http_api::ApiHandler> =
+        Arc::new(move |route| handle_api_route(&handle, route).map_err(String::from));
+
+    let started = HttpApiServer::start(settings.http_api_port, token, handler).map_err(|e| {
+        tracing::error!("Failed to start local API: {:#}", e);
+        AppError::from(e)
+    })?;
+    tracing::info!("Local API enabled on port {}", started.port());
+    *server = Some(started);
+    Ok(())
+}
+
+// Serve local API requests through the same commands the frontend uses
+fn handle_api_route(
+    app: &tauri::AppHandle,
+    route: ApiRoute,
+) -> Result<serde_json::Value, AppError> {
+    let state: State<AppState> = app.state();
+
+    let result = match route {
+        ApiRoute::ListAccounts => {
+            serde_json::to_value(get_all_accounts(state, None, None, None, None)?)
+        }
+        ApiRoute::Current => serde_json::to_value(current_account_info(&state, false)?),
+        ApiRoute::Switch {
+            email,
+            reset_machine,
+        } => {
+            let options = switch_options(&state, reset_machine, None, None);
+            let report = switch_account_from(app, &state, &email, options, SwitchSource::Api)?;
+
+            if let Some(window) = app.get_window("main") {
+                let _ = window.emit("account-switched", &email);
+            }
+            Ok(serde_json::json!({
+                "email": email,
+                "options": report.options,
+                "warnings": report.warnings,
+            }))
+        }
+        ApiRoute::Sync => {
+            let outcome = sync_current_account(state, None)?;
+            if let Some(window) = app.get_window("main") {
+                let _ = window.emit("account-synced", ());
+            }
+            serde_json::to_value(outcome)
+        }
+        ApiRoute::Refresh => {
+            let accounts = refresh_all_accounts(&state, RefreshTrigger::Api, &|_| true)?;
+            if let Some(window) = app.get_window("main") {
+                let _ = window.emit("accounts-refreshed", ());
+            }
+            serde_json::to_value(accounts)
+        }
+    };
+
+    result.map_err(AppError::from)
+}
+
+// Build the autostart manager from the Tauri config so names stay in sync with packaging
+fn autostart_manager(app: &tauri::AppHandle) -> Result<AutostartManager, AppError> {
+    let identifier = app.config().tauri.bundle.identifier.clone();
+    let exe_path = std::env::current_exe().map_err(AppError::from)?;
+
+    Ok(AutostartManager::new(
+        product_name(app),
+        identifier,
+        exe_path,
+    ))
+}
+
+fn product_name(app: &tauri::AppHandle) -> String {
+    app.config()
+        .package
+        .product_name
+        .clone()
+        .unwrap_or_else(|| app.package_info().name.clone())
+}
+
+fn show_main_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+// Handle a request forwarded by a second launch of the app
+fn handle_instance_message(app: &tauri::AppHandle, message: InstanceMessage) {
+    match message {
+        InstanceMessage::Focus => show_main_window(app),
+        InstanceMessage::OpenUrl(url) => handle_deep_link(app, &url),
+    }
+}
+
+// Read-only mode is enforced here for every IPC command, before it runs
+fn read_only_guard(
+    handler: impl Fn(tauri::Invoke) + Send + Sync + 'static,
+) -> impl Fn(tauri::Invoke) + Send + Sync + 'static {
+    move |invoke| {
+        let refused = {
+            let window = invoke.message.window();
+            let state = window.state::<AppState>();
+            state
+                .operations
+                .check_command(invoke.message.command())
+                .err()
+        };
+        match refused {
+            Some(error) => {
+                tracing::warn!("{}", error);
+                invoke.resolver.reject(error);
+            }
+            None => handler(invoke),
+        }
+    }
+}
+
+// Route a cursor-switcher:// link; every action needs the user's confirmation first
+fn handle_deep_link(app: &tauri::AppHandle, url: &str) {
+    let link = match DeepLink::parse(url) {
+        Ok(link) => link,
+        Err(e) => {
+            tracing::warn!("Ignoring link: {}", e);
+            return;
+        }
+    };
+    tracing::info!("Received {} link", link.action());
+
+    // Make sure the dialog isn't attached to a hidden window
+    show_main_window(app);
+    let window = app.get_window("main");
+
+    let (title, message) = match &link {
+        DeepLink::Import { .. } => (
+            t("dialog.import_link.title"),
+            t("dialog.import_link.body").to_string(),
+        ),
+        DeepLink::Switch { email } => (
+            t("dialog.switch_link.title"),
+            tf("dialog.switch_link.body", &[("email", email)]),
+        ),
+    };
+
+    let handle = app.clone();
+    tauri::api::dialog::ask(window.as_ref(), title, message, move |confirmed| {
+        if confirmed {
+            run_deep_link(&handle, link);
+        } else {
+            tracing::info!("Link action cancelled by user");
+        }
+    });
+}
+
+fn run_deep_link(app: &tauri::AppHandle, link: DeepLink) {
+    let state: State<AppState> = app.state();
+
+    // Links call the commands directly, past the IPC read-only check
+    let command = match &link {
+        DeepLink::Import { .. } => "import_from_token",
+        DeepLink::Switch { .. } => "switch_account",
+    };
+    if let Err(e) = state.operations.check_command(command) {
+        tauri::api::dialog::message(
+            app.get_window("main").as_ref(),
+            t("dialog.link_failed.title"),
+            e.to_string(),
+        );
+        return;
+    }
+    let result = match link {
+        DeepLink::Import { token } => import_from_token(app.clone(), state, token).map(|account| {
+            if let Some(window) = app.get_window("main") {
+                let _ = window.emit("account-imported", &account.email);
+            }
+        }),
+        DeepLink::Switch { email } => {
+            // Links can't choose options, so they switch like the tray does
+            let reset_machine = load_settings(&state).default_reset_machine;
+            let options = switch_options(&state, reset_machine, None, None);
+            switch_account_from(app, &state, &email, options, SwitchSource::DeepLink).map(
+                |_report| {
+                    if let Some(window) = app.get_window("main") {
+                        let _ = window.emit("account-switched", &email);
+                    }
+                },
+            )
+        }
+    };
+
+    if let Err(e) = result {
+        tracing::error!("Link action failed: {}", e);
+        let window = app.get_window("main");
+        tauri::api::dialog::message(
+            window.as_ref(),
+            t("dialog.link_failed.title"),
+            e.to_string(),
+        );
+    }
+}
+
+#[tauri::command]
+fn enable_autostart(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+) -> Result<AutostartStatus, AppError> {
+    tracing::info!("Enabling launch at login");
+    let status = autostart_manager(&app)?.enable().map_err(|e| {
+        tracing::error!("Failed to enable autostart: {}", e);
+        AppError::from(e)
+    })?;
+
+    let settings_path = state.settings_path();
+    SettingsManager::new(settings_path)
+        .update(|s| s.launch_at_login = true)
+        .map_err(AppError::from)?;
+
+    tracing::info!("Autostart enabled at: {}", status.location);
+    Ok(status)
+}
+
+#[tauri::command]
+fn disable_autostart(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+) -> Result<AutostartStatus, AppError> {
+    tracing::info!("Disabling launch at login");
+    let status = autostart_manager(&app)?.disable().map_err(|e| {
+        tracing::error!("Failed to disable autostart: {}", e);
+        AppError::from(e)
+    })?;
+
+    let settings_path = state.settings_path();
+    SettingsManager::new(settings_path)
+        .update(|s| s.launch_at_login = false)
+        .map_err(AppError::from)?;
+
+    tracing::info!("Autostart disabled at: {}", status.location);
+    Ok(status)
+}
+
+#[tauri::command]
+fn validate_token(token: String) -> Result<TokenInfo, AppError> {
+    tracing::info!("Validating token");
+    token_auth::validate_token_info(&token).map_err(|e| {
+        tracing::error!("Token validation failed: {}", e);
+        AppError::from(e)
+    })
+}
+
+#[tauri::command]
+fn import_from_token(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    token: String,
+) -> Result<Account, AppError> {
+    validation::token("token", &token)?;
+    let account = import_token(&state, &token)?;
+    state.accounts_changed.notify();
+    warn_credential_conflicts(&app, &state);
+    Ok(account)
+}
+
+/// Ask Cursor for the tokens and email of the accounts imported from a lone
+/// session cookie; the ones it refuses keep their placeholder email
+#[tauri::command]
+fn resolve_pending_accounts(
+    state: State<AppState>,
+) -> Result<operations::PendingResolution, AppError> {
+    ensure_unlocked(&state)?;
+    let client = TokenAuthClient::new(&state.http()).map_err(AppError::from)?;
+    let store = open_store(&state);
+
+    let resolution = operations::resolve_pending(store.as_ref(), &|cookie| {
+        client.convert_token_to_account(cookie)
+    })
+    .map_err(AppError::from)?;
+    tracing::info!(
+        "Resolved {} pending account(s), {} failed",
+        resolution.resolved.len(),
+        resolution.failed.len()
+    );
+    if !resolution.resolved.is_empty() {
+        state.accounts_changed.notify();
+    }
+    Ok(resolution)
+}
+
+/// How long a token import waits for a running operation before writing its row
+const IMPORT_WRITE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Exchange a token for its account, marked as imported from a token now
+fn convert_token(state: &AppState, token: &str) -> anyhow::Result<Account> {
+    let client = TokenAuthClient::new(&state.http())?;
+    let mut account = client
+        .convert_token_to_account(token)
+        .inspect_err(|e| tracing::error!("Token conversion failed: {}", e))?;
+
+    account.source = AccountSource::TokenImport;
+    account.record_time = chrono::Local::now().format(record_time::FORMAT).to_string();
+    Ok(account)
+}
+
+// Exchange a token for an account and store it
+fn import_token(state: &AppState, token: &str) -> Result<Account, AppError> {
+    tracing::info!("Importing account from token");
+    let account = convert_token(state, token).map_err(AppError::from)?;
+
+    // Add to CSV; the slow exchange above runs without blocking other operations
+    {
+        let _operation = state
+            .operations
+            .begin_when_idle(Operation::Importing, IMPORT_WRITE_TIMEOUT)?;
+        open_store(state)
+            .add_account(account.clone())
+            .map_err(|e| {
+                tracing::error!("Failed to add account to CSV: {}", e);
+                AppError::from(e)
+            })?;
+    }
+
+    // The email came from Cursor's API for this very token
+    if let Err(e) = identity_store(state).record(&account.email, account.access_token.expose()) {
+        tracing::warn!("Failed to record verified identity: {}", e);
+    }
+
+    tracing::info!(
+        "Successfully imported account from token: {}",
+        account.email
+    );
+    Ok(account)
+}
+
+fn import_job_store(state: &AppState) -> ImportJobStore {
+    ImportJobStore::new(state.csv_path().with_file_name(import_jobs::JOBS_DIR_NAME))
+}
+
+/// Import many tokens in the background, recording each outcome so the job
+/// can be resumed after a crash. Progress arrives as `import-job-progress`.
+#[tauri::command]
+fn start_import_job(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    tokens: Vec<String>,
+) -> Result<ImportJobSummary, AppError> {
+    let job = ImportJob::new(&tokens, chrono::Utc::now());
+    if job.entries.is_empty() {
+        return Err("No tokens to import".into());
+    }
+    import_job_store(&state)
+        .save(&job)
+        .map_err(AppError::from)?;
+    tracing::info!(
+        "Created import job {} with {} token(s)",
+        job.id,
+        job.entries.len()
+    );
+    spawn_import_job(&app, &state, job)
+}
+
+#[tauri::command]
+fn list_import_jobs(state: State<AppState>) -> Result<Vec<ImportJobSummary>, AppError> {
+    let store = import_job_store(&state);
+    if let Err(e) = store.prune(chrono::Utc::now()) {
+        tracing::warn!("Failed to prune import jobs: {}", e);
+    }
+    let jobs = store.list().map_err(AppError::from)?;
+    Ok(jobs.iter().map(ImportJob::summary).collect())
+}
+
+/// Continue a job with its pending entries; processed ones are skipped
+#[tauri::command]
+fn resume_import_job(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    id: String,
+) -> Result<ImportJobSummary, AppError> {
+    let job = import_job_store(&state).load(&id).map_err(AppError::from)?;
+    if job.is_finished() {
+        return Ok(job.summary());
+    }
+    tracing::info!("Resuming import job {}", id);
+    spawn_import_job(&app, &state, job)
+}
+
+#[tauri::command]
+fn discard_import_job(state: State<AppState>, id: String) -> Result<(), AppError> {
+    if state.running_import_job.lock_or_recover().as_deref() == Some(id.as_str()) {
+        return Err("This import job is still running".into());
+    }
+    import_job_store(&state)
+        .discard(&id)
+        .map_err(AppError::from)?;
+    tracing::info!("Discarded import job {}", id);
+    Ok(())
+}
+
+fn spawn_import_job(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    mut job: ImportJob,
+) -> Result<ImportJobSummary, AppError> {
+    {
+        let mut running = state.running_import_job.lock_or_recover();
+        if let Some(running) = running.as_ref() {
+            return Err(format!("Import job {} is already running", running).into());
+        }
+        *running = Some(job.id.clone());
+    }
+
+    let summary = job.summary();
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state: State<AppState> = app.state();
+        let result = import_job_store(&state).run(
+            &mut job,
+            |token| Ok(import_token(&state, token)?),
+            |job| {
+                let _ = app.emit_all("import-job-progress", job.summary());
+            },
+        );
+        *state.running_import_job.lock_or_recover() = None;
+
+        let summary = job.summary();
+        match result {
+            Ok(()) => tracing::info!(
+                "Import job {} finished: {} imported, {} failed",
+                summary.id,
+                summary.imported,
+                summary.failed
+            ),
+            Err(e) => tracing::error!("Import job {} stopped: {}", summary.id, e),
+        }
+        state.accounts_changed.notify();
+        warn_credential_conflicts(&app, &state);
+        let _ = app.emit_all("import-job-finished", summary);
+    });
+    Ok(summary)
+}
+
+#[tauri::command]
+fn get_usage_events(state: State<AppState>) -> Result<serde_json::Value, AppError> {
+    tracing::info!("Fetching usage events");
+
+    let (session_token, headers) = dashboard_auth(&state)?;
+    let client = DetailedUsageClient::new(&state.http()).map_err(AppError::from)?;
+    client
+        .get_usage_events(&session_token, &headers)
+        .map_err(|e| {
+            tracing::error!("Failed to get usage events: {}", e);
+            AppError::from(e)
+        })
+}
+
+#[tauri::command]
+fn get_detailed_user_info(state: State<AppState>) -> Result<DetailedUserInfo, AppError> {
+    tracing::info!("Fetching detailed user info");
+
+    let (session_token, headers) = dashboard_auth(&state)?;
+    let client = DetailedUsageClient::new(&state.http()).map_err(AppError::from)?;
+    client
+        .get_detailed_user_info(&session_token, &headers)
+        .map_err(|e| {
+            tracing::error!("Failed to get detailed user info: {}", e);
+            AppError::from(e)
+        })
+}
+
+#[tauri::command]
+fn get_invoices(state: State<AppState>) -> Result<serde_json::Value, AppError> {
+    tracing::info!("Fetching invoices");
+
+    let (session_token, headers) = dashboard_auth(&state)?;
+    let client = DetailedUsageClient::new(&state.http()).map_err(AppError::from)?;
+    client.list_invoices(&session_token, &headers).map_err(|e| {
+        tracing::error!("Failed to get invoices: {}", e);
+        AppError::from(e)
+    })
+}
+
+#[tauri::command]
+fn get_billing_cycle(state: State<AppState>) -> Result<BillingCycle, AppError> {
+    tracing::info!("Fetching billing cycle");
+
+    let (session_token, headers) = dashboard_auth(&state)?;
+    let client = DetailedUsageClient::new(&state.http()).map_err(AppError::from)?;
+    client
+        .get_billing_cycle(&session_token, &headers)
+        .map_err(|e| {
+            tracing::error!("Failed to get billing cycle: {}", e);
+            AppError::from(e)
+        })
+}
+
+/// How long a tray switch waits for a running refresh or sync to finish
+const TRAY_SWITCH_QUEUE_TIMEOUT: Duration = Duration::from_secs(300);
+const ACCOUNT_DETAILS_WINDOW: &str = "account-details";
+
+/// Open the small always-on-top detail window for `email` (the current account when
+/// omitted), or retarget and focus it if it is already open
+#[tauri::command]
+fn open_account_details(app: tauri::AppHandle, email: Option<String>) -> Result<(), AppError> {
+    if let Some(window) = app.get_window(ACCOUNT_DETAILS_WINDOW) {
+        let _ = window.emit("account-details-target", &email);
+        let _ = window.unminimize();
+        window.show().map_err(AppError::from)?;
+        return window.set_focus().map_err(AppError::from);
+    }
+
+    let mut url = "index.html?view=account-details".to_string();
+    if let Some(email) = &email {
+        url.push_str("&email=");
+        url.extend(url::form_urlencoded::byte_serialize(email.as_bytes()));
+    }
+
+    tauri::WindowBuilder::new(
+        &app,
+        ACCOUNT_DETAILS_WINDOW,
+        tauri::WindowUrl::App(url.into()),
+    )
+    .title(t("window.account_details"))
+    .inner_size(380.0, 460.0)
+    .resizable(false)
+    .always_on_top(true)
+    .build()
+    .map_err(|e| {
+        tracing::error!("Failed to open account details window: {}", e);
+        AppError::from(e)
+    })?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_account_overview(
+    state: State<AppState>,
+    email: Option<String>,
+) -> Result<AccountOverview, AppError> {
+    if let Some(email) = &email {
+        validation::email("email", email)?;
+    }
+    let current_email = state
+        .cursor_base()
+        .ok()
+        .and_then(|base| {
+            Database::new(PathDetector::get_db_path(&base))
+                .get_auth_info()
+                .ok()
+        })
+        .map(|(email, _)| email);
+    let email = email
+        .or_else(|| current_email.clone())
+        .ok_or("No account logged in")?;
+
+    let account = open_store(&state)
+        .find_account_by_email(&email)
+        .map_err(AppError::from)?
+        .ok_or_else(|| format!("Account not found: {}", email))?;
+    let is_current = current_email.as_deref() == Some(email.as_str());
+    let extra = http::extra_headers(account.extra_headers.as_ref());
+    let mut errors = Vec::new();
+
+    let cached_usage = match (
+        account.usage_total,
+        account.usage_used,
+        account.usage_remaining,
+        account.usage_percentage,
+    ) {
+        (Some(total_quota), Some(used), Some(remaining), Some(usage_percentage)) => {
+            Some(UsageInfo {
+                total_quota,
+                used,
+                remaining,
+                usage_percentage,
+            })
+        }
+        _ => None,
+    };
+
+    // Stored usage is good enough unless it's missing or stale
+    let (usage, usage_cached) = match cached_usage {
+        Some(usage)
+            if !tray::is_stale(
+                &account,
+                load_settings(&state).stale_after_hours,
+                chrono::Local::now().naive_local(),
+            ) =>
+        {
+            (Some(usage), true)
+        }
+        cached => match CursorApiClient::new(&state.http())
+            .and_then(|api_client| api_client.get_usage_info(account.access_token.expose(), &extra))
+        {
+            Ok(usage) => (Some(usage), false),
+            Err(e) => {
+                errors.push(redaction::scrub_secrets(&format!("Usage: {}", e)));
+                let is_cached = cached.is_some();
+                (cached, is_cached)
+            }
+        },
+    };
 
-    let cursor_path = state.cursor_base_path.lock().unwrap();
-    let base_path = cursor_path.as_ref().ok_or("Cursor path not set")?;
+    let billing_cycle = match state.cached_billing_cycle(&email) {
+        Some(cycle) => Some(cycle),
+        None => {
+            let session_token = clipboard_watcher::normalize_candidate(account.cookie.expose())
+                .map(str::to_string)
+                .or_else(|| {
+                    // Fall back to Cursor's own session for the logged-in account
+                    is_current
+                        .then(|| state.cursor_base().ok())
+                        .flatten()
+                        .and_then(|base| {
+                            Database::new(PathDetector::get_db_path(&base))
+                                .get_session_token()
+                                .ok()
+                        })
+                });
 
-    let db_path = PathDetector::get_db_path(base_path);
-    let db = Database::new(db_path);
-    let session_token = db.get_session_token().map_err(|e| e.to_string())?;
+            match session_token {
+                Some(token) => match DetailedUsageClient::new(&state.http())
+                    .and_then(|client| client.get_billing_cycle(&token, &extra))
+                {
+                    Ok(cycle) => {
+                        state.cache_billing_cycle(&email, cycle.clone());
+                        Some(cycle)
+                    }
+                    Err(e) => {
+                        errors.push(redaction::scrub_secrets(&format!("Billing cycle: {}", e)));
+                        None
+                    }
+                },
+                None => None,
+            }
+        }
+    };
 
-    let client = DetailedUsageClient::new();
-    client.get_usage_events(&session_token).map_err(|e| {
-        tracing::error!("Failed to get usage events: {}", e);
-        e.to_string()
+    let mut account = account;
+    redaction::mask_account(&mut account);
+    Ok(AccountOverview {
+        account,
+        is_current,
+        usage,
+        usage_cached,
+        billing_cycle,
+        errors,
     })
 }
 
-#[tauri::command]
-fn get_detailed_user_info(state: State<AppState>) -> Result<DetailedUserInfo, String> {
-    tracing::info!("Fetching detailed user info");
-
-    let cursor_path = state.cursor_base_path.lock().unwrap();
-    let base_path = cursor_path.as_ref().ok_or("Cursor path not set")?;
+fn load_settings(state: &AppState) -> Settings {
+    SettingsManager::new(state.settings_path())
+        .load()
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to load settings, using defaults: {}", e);
+            Settings::default()
+        })
+}
 
-    let db_path = PathDetector::get_db_path(base_path);
-    let db = Database::new(db_path);
-    let session_token = db.get_session_token().map_err(|e| e.to_string())?;
+// Tray clicks are easy to misfire and a switch kills Cursor, so ask first unless disabled.
+// The dialog has no parent window so it also shows while the main window is hidden.
+fn confirm_tray_switch(app: &tauri::AppHandle, account: Account) {
+    let state: State<AppState> = app.state();
+    if app_lock_status(&state).locked {
+        tracing::info!("Tray switch blocked by the app lock");
+        show_main_window(app);
+        let _ = app.emit_all("unlock-required", &account.email);
+        notifier::notify(app, t("notify.locked.title"), t("notify.locked.body"));
+        return;
+    }
+    if !load_settings(&state).confirm_tray_switch {
+        switch_from_tray(app, account);
+        return;
+    }
 
-    let client = DetailedUsageClient::new();
-    client.get_detailed_user_info(&session_token).map_err(|e| {
-        tracing::error!("Failed to get detailed user info: {}", e);
-        e.to_string()
-    })
+    let handle = app.clone();
+    tauri::api::dialog::ask(
+        None::<&tauri::Window>,
+        t("dialog.confirm_switch.title"),
+        tf("dialog.confirm_switch.body", &[("email", &account.email)]),
+        move |confirmed| {
+            if confirmed {
+                switch_from_tray(&handle, account);
+            } else {
+                tracing::info!("Tray switch cancelled by user");
+            }
+        },
+    );
 }
 
-#[tauri::command]
-fn get_invoices(state: State<AppState>) -> Result<serde_json::Value, String> {
-    tracing::info!("Fetching invoices");
+fn switch_from_tray(app: &tauri::AppHandle, account: Account) {
+    tracing::info!("Switching to account from tray: {}", account.email);
 
-    let cursor_path = state.cursor_base_path.lock().unwrap();
-    let base_path = cursor_path.as_ref().ok_or("Cursor path not set")?;
+    let app = app.clone();
+    // Runs off the main thread so a switch queued behind a refresh doesn't block the tray
+    std::thread::spawn(move || {
+        let state: State<AppState> = app.state();
+        let result = state
+            .operations
+            .begin_when_idle(Operation::Switching, TRAY_SWITCH_QUEUE_TIMEOUT)
+            .and_then(|_operation| {
+                let mut options = load_settings(&state).default_switch_options();
+                offline_switch_warnings(&state, &mut options);
+                let outcome =
+                    run_switch(&app, &state, &account.email, &options, SwitchSource::Tray)?;
+                Ok((outcome, options))
+            });
+        report_tray_switch(&app, account, result);
+    });
+}
 
-    let db_path = PathDetector::get_db_path(base_path);
-    let db = Database::new(db_path);
-    let session_token = db.get_session_token().map_err(|e| e.to_string())?;
+fn report_tray_switch(
+    app: &tauri::AppHandle,
+    account: Account,
+    result: Result<(SwitchOutcome, SwitchOptions), AppError>,
+) {
+    match result {
+        Ok((Ok(_), options)) => {
+            tracing::info!("Successfully switched to account: {}", account.email);
+            // Notify frontend if window is open
+            if let Some(window) = app.get_window("main") {
+                let _ = window.emit("account-switched", &account.email);
+            }
+            notify_tray_success(
+                app,
+                t("notify.switched.title"),
+                &switched_message(&account.email, &options),
+            );
+        }
+        Ok((Err(failure), _)) => {
+            tracing::error!("Failed to switch account: {}", failure);
+            let error = AppError::from(failure.clone());
+            report_tray_failure(
+                app,
+                t("notify.switch_failed.title"),
+                TrayOperationFailure::new("switch", Some(account.email), &error)
+                    .with_switch_failure(failure),
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to switch account: {}", e);
+            report_tray_failure(
+                app,
+                t("notify.switch_failed.title"),
+                TrayOperationFailure::new("switch", Some(account.email), &e),
+            );
+        }
+    }
+}
 
-    let client = DetailedUsageClient::new();
-    client.list_invoices(&session_token).map_err(|e| {
-        tracing::error!("Failed to get invoices: {}", e);
-        e.to_string()
-    })
+// "Switched to a@x.com (machine ID reset, Cursor restarted)."
+fn switched_message(email: &str, options: &SwitchOptions) -> String {
+    let applied: Vec<&str> = [
+        (options.reset_machine, "switch.option.reset_machine"),
+        (options.restart, "switch.option.restart"),
+        (options.validate_token, "switch.option.validate"),
+    ]
+    .into_iter()
+    .filter(|(applied, _)| *applied)
+    .map(|(_, key)| t(key))
+    .collect();
+
+    if applied.is_empty() {
+        tf("notify.switched.body", &[("email", email)])
+    } else {
+        tf(
+            "notify.switched.body_with_options",
+            &[("email", email), ("options", &applied.join(", "))],
+        )
+    }
 }
 
-#[tauri::command]
-fn get_billing_cycle(state: State<AppState>) -> Result<BillingCycle, String> {
-    tracing::info!("Fetching billing cycle");
+fn notify_tray_success(app: &tauri::AppHandle, title: &str, body: &str) {
+    let state: State<AppState> = app.state();
+    if load_settings(&state).notify_tray_success {
+        notifier::notify(app, title, body);
+    }
+}
 
-    let cursor_path = state.cursor_base_path.lock().unwrap();
-    let base_path = cursor_path.as_ref().ok_or("Cursor path not set")?;
+// Tray actions have no window to show errors in, so always tell the user and the frontend
+fn report_tray_failure(app: &tauri::AppHandle, title: &str, failure: TrayOperationFailure) {
+    notifier::notify(app, title, &failure.reason);
+    let _ = app.emit_all("tray-operation-failed", &failure);
+}
 
-    let db_path = PathDetector::get_db_path(base_path);
-    let db = Database::new(db_path);
-    let session_token = db.get_session_token().map_err(|e| e.to_string())?;
+fn tray_sync(app: &tauri::AppHandle) -> TrayActionResult {
+    match sync_current_account(app.state(), None) {
+        Ok(outcome) => {
+            tracing::info!("Synced current account from tray");
+            let (title, body) = sync_message(&outcome);
+            // "Nothing to sync" explains why nothing happened, so always show it
+            if matches!(outcome, SyncOutcome::NothingLoggedIn) {
+                notifier::notify(app, title, &body);
+            } else {
+                notify_tray_success(app, title, &body);
+            }
+            // Notify frontend if window is open
+            if let Some(window) = app.get_window("main") {
+                let _ = window.emit("account-synced", ());
+            }
+            Ok(body)
+        }
+        Err(e) => {
+            tracing::error!("Failed to sync account: {}", e);
+            report_tray_failure(
+                app,
+                t("notify.sync_failed.title"),
+                TrayOperationFailure::new("sync", None, &e),
+            );
+            Err(e)
+        }
+    }
+}
 
-    let client = DetailedUsageClient::new();
-    client.get_billing_cycle(&session_token).map_err(|e| {
-        tracing::error!("Failed to get billing cycle: {}", e);
-        e.to_string()
-    })
+// Runs on its own thread so the tray stays responsive while every account is
+// queried. Clicks during a refresh are covered by its notification.
+fn spawn_tray_refresh(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state: State<AppState> = app.state();
+        let run = run_tray_action(&state, TrayAction::Refresh, false, || {
+            let result = refresh_all_accounts(&app.state(), RefreshTrigger::Tray, &|_| true);
+            match &result {
+                Ok(accounts) => {
+                    tracing::info!("Refreshed {} accounts from tray", accounts.len());
+                    // Notify frontend if window is open
+                    if let Some(window) = app.get_window("main") {
+                        let _ = window.emit("accounts-refreshed", ());
+                    }
+                    notify_tray_success(
+                        &app,
+                        t("notify.refreshed.title"),
+                        &refreshed_message(accounts.len()),
+                    );
+                }
+                Err(e) => {
+                    tracing::error!("Failed to refresh accounts: {}", e);
+                    report_tray_failure(
+                        &app,
+                        t("notify.refresh_failed.title"),
+                        TrayOperationFailure::new("refresh", None, e),
+                    );
+                }
+            }
+            result.map(|accounts| refreshed_message(accounts.len()))
+        });
+        if let TrayRun::CoolingDown(ago) = run {
+            notifier::notify(
+                &app,
+                t("notify.recently_refreshed.title"),
+                &recently_refreshed_message(ago),
+            );
+        }
+    });
 }
 
 fn build_system_tray() -> SystemTray {
-    let show = CustomMenuItem::new("show".to_string(), "Show Window");
-    let hide = CustomMenuItem::new("hide".to_string(), "Hide Window");
-    let sync = CustomMenuItem::new("sync".to_string(), "Sync Current Account");
-    let refresh = CustomMenuItem::new("refresh".to_string(), "Refresh All Accounts");
-    let quit = CustomMenuItem::new("quit".to_string(), "Quit");
+    let show = CustomMenuItem::new("show".to_string(), t("tray.show"));
+    let hide = CustomMenuItem::new("hide".to_string(), t("tray.hide"));
+    let sync = CustomMenuItem::new("sync".to_string(), t("tray.sync"));
+    let refresh = CustomMenuItem::new("refresh".to_string(), t("tray.refresh"));
+    let quit = CustomMenuItem::new("quit".to_string(), t("tray.quit"));
 
     let tray_menu = SystemTrayMenu::new()
         .add_item(show)
@@ -567,75 +4495,118 @@ fn build_system_tray() -> SystemTray {
         .add_item(sync)
         .add_item(refresh)
         .add_native_item(SystemTrayMenuItem::Separator)
-        .add_item(CustomMenuItem::new("accounts_header".to_string(), "Switch Account").disabled())
-        .add_item(CustomMenuItem::new("no_accounts".to_string(), "  Loading...").disabled())
+        .add_item(
+            CustomMenuItem::new("accounts_header".to_string(), t("tray.accounts_header"))
+                .disabled(),
+        )
+        .add_item(CustomMenuItem::new("no_accounts".to_string(), t("tray.loading")).disabled())
         .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(quit);
 
     SystemTray::new().with_menu(tray_menu)
 }
 
-// Build tray menu with account list and current account
-fn build_tray_menu_with_accounts(
-    accounts: &[Account],
-    current_email: Option<String>,
-) -> SystemTrayMenu {
-    let show = CustomMenuItem::new("show".to_string(), "Show Window");
-    let hide = CustomMenuItem::new("hide".to_string(), "Hide Window");
-    let sync = CustomMenuItem::new("sync".to_string(), "Sync Current Account");
-    let refresh = CustomMenuItem::new("refresh".to_string(), "Refresh All Accounts");
-    let quit = CustomMenuItem::new("quit".to_string(), "Quit");
+fn build_tray_menu_from_model(model: &TrayMenuModel) -> SystemTrayMenu {
+    let show = CustomMenuItem::new("show".to_string(), t("tray.show"));
+    let hide = CustomMenuItem::new("hide".to_string(), t("tray.hide"));
+    let mut sync = CustomMenuItem::new("sync".to_string(), t("tray.sync"));
+    let mut refresh = CustomMenuItem::new("refresh".to_string(), t("tray.refresh"));
+    // Read-only mode keeps these visible but greyed out
+    let read_only = model.read_only;
+    if read_only.enabled {
+        sync = sync.disabled();
+        if !read_only.allow_refresh {
+            refresh = refresh.disabled();
+        }
+    }
+    let quit = CustomMenuItem::new("quit".to_string(), t("tray.quit"));
+
+    let mut tray_menu = SystemTrayMenu::new();
+    if let Some(version) = &model.update_version {
+        tray_menu = tray_menu
+            .add_item(CustomMenuItem::new(
+                "update_available".to_string(),
+                tf("tray.update_available", &[("version", version)]),
+            ))
+            .add_native_item(SystemTrayMenuItem::Separator);
+    }
 
-    let mut tray_menu = SystemTrayMenu::new()
+    tray_menu = tray_menu
         .add_item(show)
         .add_item(hide)
         .add_native_item(SystemTrayMenuItem::Separator);
 
     // Add current account display
-    if let Some(email) = current_email {
-        let current_account_text = format!("Current: {}", email);
+    if let Some(email) = &model.current_email {
+        let key = if model.token_warning {
+            "tray.current_account_expiring"
+        } else {
+            "tray.current_account"
+        };
+        let current_account_text = tf(key, &[("email", email)]);
         tray_menu = tray_menu.add_item(
             CustomMenuItem::new("current_account".to_string(), current_account_text).disabled(),
         );
+        if model.token_warning && !read_only.enabled {
+            tray_menu = tray_menu.add_item(CustomMenuItem::new(
+                "reauth_current".to_string(),
+                t("tray.reauth"),
+            ));
+        }
     } else {
         tray_menu = tray_menu.add_item(
-            CustomMenuItem::new(
-                "current_account".to_string(),
-                "Current: No account logged in",
-            )
-            .disabled(),
+            CustomMenuItem::new("current_account".to_string(), t("tray.no_current_account"))
+                .disabled(),
         );
     }
 
+    tray_menu = tray_menu.add_item(CustomMenuItem::new(
+        "account_details".to_string(),
+        t("tray.account_details"),
+    ));
+
+    let mut watch_clipboard =
+        CustomMenuItem::new("watch_clipboard".to_string(), t("tray.watch_clipboard"));
+    if model.clipboard_watch {
+        watch_clipboard = watch_clipboard.selected();
+    }
+
+    tray_menu = tray_menu.add_native_item(SystemTrayMenuItem::Separator);
+    if model.token_detected && !read_only.enabled {
+        tray_menu = tray_menu.add_item(CustomMenuItem::new(
+            "import_detected".to_string(),
+            t("tray.import_detected"),
+        ));
+    }
     tray_menu = tray_menu
-        .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(sync)
         .add_item(refresh)
-        .add_native_item(SystemTrayMenuItem::Separator)
-        .add_item(CustomMenuItem::new("accounts_header".to_string(), "Switch Account").disabled());
-
-    // Add accounts to menu
-    if accounts.is_empty() {
-        tray_menu = tray_menu.add_item(
-            CustomMenuItem::new("no_accounts".to_string(), "  No accounts available").disabled(),
-        );
-    } else {
-        // Limit to first 10 accounts to avoid overcrowding
-        for (idx, account) in accounts.iter().take(10).enumerate() {
-            let display_text = format!("  {}", account.email);
-            let item_id = format!("account_{}", idx);
-            tray_menu = tray_menu.add_item(CustomMenuItem::new(item_id, display_text));
-        }
-
-        if accounts.len() > 10 {
-            tray_menu = tray_menu.add_item(
-                CustomMenuItem::new(
-                    "more_accounts".to_string(),
-                    format!("  ... and {} more", accounts.len() - 10),
-                )
-                .disabled(),
+        .add_item(watch_clipboard);
+    if !read_only.enabled {
+        tray_menu = tray_menu
+            .add_native_item(SystemTrayMenuItem::Separator)
+            .add_item(
+                CustomMenuItem::new("accounts_header".to_string(), t("tray.accounts_header"))
+                    .disabled(),
             );
-        }
+    }
+
+    // Add accounts to menu, in a submenu per pool once there are several
+    for (group_idx, group) in model.account_groups.iter().enumerate() {
+        tray_menu = match &group.pool {
+            Some(pool) => {
+                let title = tf(
+                    "tray.pool",
+                    &[
+                        ("name", pool),
+                        ("count", &(group.accounts.len() + group.hidden).to_string()),
+                    ],
+                );
+                let items = add_tray_accounts(SystemTrayMenu::new(), group, group_idx);
+                tray_menu.add_submenu(SystemTraySubmenu::new(title, items))
+            }
+            None => add_tray_accounts(tray_menu, group, group_idx),
+        };
     }
 
     tray_menu = tray_menu
@@ -645,41 +4616,187 @@ fn build_tray_menu_with_accounts(
     tray_menu
 }
 
-// Update the system tray menu with current accounts
+// Item ids are the account's position in the CSV, whichever group lists it;
+// the other ids are numbered per group so they stay unique
+fn add_tray_accounts(
+    mut menu: SystemTrayMenu,
+    group: &TrayAccountGroup,
+    group_idx: usize,
+) -> SystemTrayMenu {
+    if group.accounts.is_empty() {
+        return menu.add_item(
+            CustomMenuItem::new(format!("no_accounts_{}", group_idx), t("tray.no_accounts"))
+                .disabled(),
+        );
+    }
+    // Only the first few accounts are listed to avoid overcrowding
+    for (idx, label) in &group.accounts {
+        let item_id = format!("account_{}", idx);
+        menu = menu.add_item(CustomMenuItem::new(item_id, label.clone()));
+    }
+    if group.hidden > 0 {
+        menu = menu.add_item(
+            CustomMenuItem::new(
+                format!("more_accounts_{}", group_idx),
+                tf(
+                    "tray.more_accounts",
+                    &[("count", &group.hidden.to_string())],
+                ),
+            )
+            .disabled(),
+        );
+    }
+    menu
+}
+
+// Update the system tray menu with current accounts. The account list and the
+// current email are cached until their files change, and the native menu is
+// only replaced when what it shows changed.
 fn update_tray_menu(app: &tauri::AppHandle) {
+    let started = Instant::now();
     let state: State<AppState> = app.state();
 
-    // Get accounts
-    let accounts = match get_all_accounts(state.clone()) {
-        Ok(accounts) => accounts,
-        Err(e) => {
+    let store = open_store(&state);
+    let accounts = state
+        .tray_accounts
+        .get_or_load(&[store.path().to_path_buf()], || {
+            operations::list_accounts(store.as_ref()).map(Arc::new)
+        })
+        .unwrap_or_else(|e| {
             tracing::error!("Failed to get accounts for tray menu: {}", e);
-            Vec::new()
-        }
-    };
+            Arc::default()
+        });
 
-    // Get current account email
-    let current_email = {
-        let cursor_path = state.cursor_base_path.lock().unwrap();
-        if let Some(base_path) = cursor_path.as_ref() {
-            let db_path = PathDetector::get_db_path(base_path);
-            let db = Database::new(db_path);
+    // Cursor writes through a WAL file, so a login can change it without touching the DB
+    let current_email = state.cursor_base().ok().and_then(|base_path| {
+        let db_path = PathDetector::get_db_path(&base_path);
+        let mut wal_path = db_path.clone().into_os_string();
+        wal_path.push("-wal");
+        state
+            .tray_current_email
+            .get_or_load(&[db_path.clone(), wal_path.into()], || {
+                Ok(Database::new(db_path.clone())
+                    .get_auth_info()
+                    .ok()
+                    .map(|(email, _)| email))
+            })
+            .ok()
+            .flatten()
+    });
 
-            match db.get_auth_info() {
-                Ok((email, _)) => Some(email),
-                Err(_) => None,
-            }
-        } else {
-            None
-        }
+    // Update tooltip and icon from the current account's cached data
+    let current_account = current_email
+        .as_ref()
+        .and_then(|email| accounts.iter().find(|a| &a.email == email));
+    let refresh_path = state
+        .csv_path()
+        .with_file_name(refresh_state::REFRESH_STATE_FILE);
+    let refreshed_at = state
+        .tray_last_refresh
+        .get_or_load(std::slice::from_ref(&refresh_path), || {
+            Ok(RefreshStateStore::new(refresh_path.clone())
+                .load(false)?
+                .and_then(|info| info.finished_at))
+        })
+        .ok()
+        .flatten();
+    let settings = load_settings(&state);
+    let label_style = settings.tray_label_style();
+    update_tray_status(
+        app,
+        current_email.as_deref(),
+        current_account,
+        refreshed_at.map(|at| at.with_timezone(&chrono::Local)),
+        &label_style.thresholds,
+        settings.stale_after_hours,
+    );
+
+    let extras = TrayMenuExtras {
+        label_style,
+        clipboard_watch: settings.clipboard_watcher_enabled,
+        token_detected: state.detected_token.lock_or_recover().is_some(),
+        update_version: state
+            .available_update
+            .lock_or_recover()
+            .as_ref()
+            .map(|u| u.latest.clone()),
+        token_warning: state
+            .token_health
+            .lock_or_recover()
+            .as_ref()
+            .is_some_and(|report| {
+                Some(&report.email) == current_email.as_ref() && report.health.needs_reauth()
+            }),
+        cooling_down: cooling_down(&state),
+        read_only: settings.read_only_policy(),
+        tag_filter: settings.tray_tag_filter.clone(),
     };
+    let model = TrayMenuModel::new(&accounts, current_email, &extras);
 
-    // Build new menu
-    let new_menu = build_tray_menu_with_accounts(&accounts, current_email);
+    let fingerprint = Some(model.fingerprint());
+    let unchanged = {
+        let mut last = state.tray_menu_fingerprint.lock_or_recover();
+        std::mem::replace(&mut *last, fingerprint) == fingerprint
+    };
+    if unchanged {
+        tracing::debug!("Tray menu unchanged, checked in {:?}", started.elapsed());
+        return;
+    }
 
-    // Update tray
-    if let Err(e) = app.tray_handle().set_menu(new_menu) {
+    if let Err(e) = app
+        .tray_handle()
+        .set_menu(build_tray_menu_from_model(&model))
+    {
         tracing::error!("Failed to update tray menu: {}", e);
+        *state.tray_menu_fingerprint.lock_or_recover() = None;
+    }
+    tracing::debug!(
+        "Tray menu rebuilt with {} account(s) in {:?}",
+        accounts.len(),
+        started.elapsed()
+    );
+}
+
+// Update the tray tooltip and swap the icon when the usage band changes
+fn update_tray_status(
+    app: &tauri::AppHandle,
+    current_email: Option<&str>,
+    current_account: Option<&Account>,
+    refreshed_at: Option<chrono::DateTime<chrono::Local>>,
+    thresholds: &tray::UsageThresholds,
+    stale_after_hours: u32,
+) {
+    let tray_handle = app.tray_handle();
+
+    let tooltip = tray::build_tooltip(
+        current_email,
+        current_account,
+        refreshed_at,
+        stale_after_hours,
+    );
+    if let Err(e) = tray_handle.set_tooltip(&tooltip) {
+        tracing::debug!("Failed to set tray tooltip: {}", e);
+    }
+
+    let band = tray::usage_band(current_account, thresholds, stale_after_hours);
+    let state: State<AppState> = app.state();
+    let mut last_band = state.tray_band.lock_or_recover();
+    if *last_band == Some(band) {
+        return;
+    }
+
+    match tray_handle.set_icon(tauri::Icon::Raw(band.icon_bytes().to_vec())) {
+        Ok(_) => {
+            // Colored badges would be flattened by macOS template rendering
+            #[cfg(target_os = "macos")]
+            let _ = tray_handle.set_icon_as_template(band == tray::UsageBand::Neutral);
+
+            tracing::debug!("Tray icon updated to {:?} band", band);
+            *last_band = Some(band);
+        }
+        Err(e) => {
+            tracing::error!("Failed to update tray icon: {}", e);
+        }
     }
 }
 
@@ -718,42 +4835,61 @@ fn handle_system_tray_event(app: &tauri::AppHandle, event: SystemTrayEvent) {
                         let _ = window.set_focus();
                     }
                 }
+                "account_details" => {
+                    if let Err(e) = open_account_details(app.clone(), None) {
+                        tracing::error!("Failed to open account details: {}", e);
+                    }
+                }
                 "hide" => {
                     if let Some(window) = app.get_window("main") {
                         let _ = window.hide();
                     }
                 }
                 "sync" => {
-                    // Sync current account
+                    // Sync current account and report what happened; clicks
+                    // during a sync are covered by its notification
                     let state: State<AppState> = app.state();
-                    match sync_current_account(state) {
-                        Ok(_) => {
-                            tracing::info!("Synced current account from tray");
-                            // Notify frontend if window is open
-                            if let Some(window) = app.get_window("main") {
-                                let _ = window.emit("account-synced", ());
-                            }
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to sync account: {}", e);
+                    run_tray_action(&state, TrayAction::Sync, false, || tray_sync(app));
+                }
+                "refresh" => {
+                    // A refresh that overlaps another operation reports "busy"
+                    spawn_tray_refresh(app);
+                }
+                "reauth_current" => spawn_tray_reauth(app),
+                "update_available" => {
+                    let state: State<AppState> = app.state();
+                    let release_url = state
+                        .available_update
+                        .lock_or_recover()
+                        .as_ref()
+                        .map(|u| u.release_url.clone());
+                    if let Some(url) = release_url {
+                        if let Err(e) = tauri::api::shell::open(&app.shell_scope(), url, None) {
+                            tracing::error!("Failed to open release page: {}", e);
                         }
                     }
                 }
-                "refresh" => {
-                    // Refresh all accounts
+                "watch_clipboard" => {
                     let state: State<AppState> = app.state();
-                    match batch_update_all_accounts(state) {
-                        Ok(accounts) => {
-                            tracing::info!("Refreshed {} accounts from tray", accounts.len());
-                            // Update tray menu with refreshed accounts
-                            update_tray_menu(app);
-                            // Notify frontend if window is open
-                            if let Some(window) = app.get_window("main") {
-                                let _ = window.emit("accounts-refreshed", ());
-                            }
+                    let enabled = state.clipboard_watcher.lock_or_recover().is_none();
+                    if let Err(e) = set_clipboard_watcher(app.clone(), state, enabled) {
+                        tracing::error!("Failed to toggle clipboard watcher: {}", e);
+                    }
+                }
+                "import_detected" => {
+                    let state: State<AppState> = app.state();
+                    match import_detected_token(app.clone(), state) {
+                        Ok(account) => {
+                            tracing::info!("Imported detected token from tray");
+                            notifier::notify(
+                                app,
+                                t("notify.imported.title"),
+                                &tf("notify.imported.body", &[("email", &account.email)]),
+                            );
                         }
                         Err(e) => {
-                            tracing::error!("Failed to refresh accounts: {}", e);
+                            tracing::error!("Failed to import detected token: {}", e);
+                            notifier::notify(app, t("notify.import_failed.title"), &e.to_string());
                         }
                     }
                 }
@@ -764,45 +4900,30 @@ fn handle_system_tray_event(app: &tauri::AppHandle, event: SystemTrayEvent) {
                     // Extract account index from id
                     if let Some(idx_str) = id.strip_prefix("account_") {
                         if let Ok(idx) = idx_str.parse::<usize>() {
-                            // Get accounts and switch to the selected one
                             let state: State<AppState> = app.state();
-                            match get_all_accounts(state.clone()) {
-                                Ok(accounts) => {
-                                    if let Some(account) = accounts.get(idx) {
-                                        tracing::info!(
-                                            "Switching to account from tray: {}",
-                                            account.email
-                                        );
-
-                                        // Switch account with default reset_machine = false
-                                        match switch_account(
-                                            state,
-                                            account.email.clone(),
-                                            account.access_token.clone(),
-                                            account.refresh_token.clone(),
-                                            false,
-                                        ) {
-                                            Ok(_) => {
-                                                tracing::info!(
-                                                    "Successfully switched to account: {}",
-                                                    account.email
-                                                );
-                                                // Update tray menu to show new current account
-                                                update_tray_menu(app);
-                                                // Notify frontend if window is open
-                                                if let Some(window) = app.get_window("main") {
-                                                    let _ = window
-                                                        .emit("account-switched", &account.email);
-                                                }
-                                            }
-                                            Err(e) => {
-                                                tracing::error!("Failed to switch account: {}", e);
-                                            }
-                                        }
-                                    }
-                                }
+                            // Menu items are numbered in file order
+                            let account = open_store(&state)
+                                .read_accounts()
+                                .map(|accounts| accounts.into_iter().nth(idx))
+                                .map_err(AppError::from);
+                            match account {
+                                Ok(Some(account)) => confirm_tray_switch(app, account),
+                                Ok(None) => report_tray_failure(
+                                    app,
+                                    t("notify.switch_failed.title"),
+                                    TrayOperationFailure::new(
+                                        "switch",
+                                        None,
+                                        &AppError::from("Account not found"),
+                                    ),
+                                ),
                                 Err(e) => {
                                     tracing::error!("Failed to get accounts: {}", e);
+                                    report_tray_failure(
+                                        app,
+                                        t("notify.switch_failed.title"),
+                                        TrayOperationFailure::new("switch", None, &e),
+                                    );
                                 }
                             }
                         }
@@ -816,46 +4937,171 @@ fn handle_system_tray_event(app: &tauri::AppHandle, event: SystemTrayEvent) {
 }
 
 fn main() {
+    let context = tauri::generate_context!();
+    let app_data_dir = tauri::api::path::app_data_dir(context.config());
+
+    // Recognized flags run headless and exit instead of opening the window
+    match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Some(command)) => {
+            let version = context.package_info().version.to_string();
+            std::process::exit(cli::run(command, app_data_dir, &version))
+        }
+        Ok(None) => {}
+        Err(e) => std::process::exit(cli::report_usage_error(&e)),
+    }
+
+    // A clicked link or a second launch goes to the running instance
+    let startup_link = deep_link::find_in_args(std::env::args().skip(1));
+    let single_instance = app_data_dir
+        .as_ref()
+        .map(|dir| SingleInstance::new(dir.join("instance.port")));
+    if let Some(instance) = &single_instance {
+        let message = match &startup_link {
+            Some(url) => InstanceMessage::OpenUrl(url.clone()),
+            None => InstanceMessage::Focus,
+        };
+        if instance.forward(&message) {
+            return;
+        }
+    }
+
     tauri::Builder::default()
-        .manage(init_app_state())
+        .manage(AppState::new())
         .system_tray(build_system_tray())
         .on_system_tray_event(handle_system_tray_event)
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(read_only_guard(tauri::generate_handler![
             get_data_storage_path,
+            set_data_storage_path,
+            get_app_info,
+            check_connectivity,
             detect_cursor_path,
             set_cursor_path,
             get_current_account_info,
             get_usage_info,
             get_all_accounts,
+            get_account_secrets,
+            get_account_password,
             add_account,
             delete_account,
+            archive_account,
+            restore_archived_account,
+            compact_account_indexes,
+            get_store_diagnostics,
+            list_account_backups,
+            restore_account_backup,
             update_account,
+            set_account_label,
+            set_account_nickname,
+            set_account_pool,
+            set_account_tags,
+            get_accounts_by_tag,
+            toggle_favorite,
+            query_accounts,
+            move_account,
+            set_account_order,
+            list_pools,
+            delete_pool,
+            set_account_privacy_mode,
+            get_privacy_mode,
+            run_self_test,
+            set_privacy_mode,
             import_accounts,
+            import_accounts_json,
             batch_add_accounts,
+            set_webdav_password,
+            sync_now,
+            get_sync_conflicts,
+            resolve_sync_conflict,
+            generate_manifest_keys,
+            export_pool_manifest,
+            import_pool_manifest,
+            get_cursor_update_history,
+            forecast_account,
+            forecast_all,
+            export_account_code,
+            import_account_code,
+            list_cursor_settings_snapshots,
+            list_switch_reverts,
+            restore_cursor_settings_snapshot,
             switch_account,
             reset_machine_id,
+            check_reset_machine_id,
+            get_machine_ids,
+            get_last_change_set,
+            get_interrupted_operation,
+            resume_or_discard_interrupted,
             kill_cursor_process,
+            list_state_db_backups,
+            restore_state_db_backup,
+            logout_current_account,
             restart_cursor_process,
             update_account_info_from_api,
             batch_update_all_accounts,
+            refresh_stale_accounts,
+            get_last_refresh_info,
+            recommend_account,
+            compare_accounts,
             sync_current_account,
             get_logs,
             clear_logs,
+            get_switch_history,
+            clear_switch_history,
             get_log_file_path,
             sync_from_tray,
             refresh_from_tray,
             validate_token,
             import_from_token,
+            resolve_pending_accounts,
+            start_import_job,
+            list_import_jobs,
+            resume_import_job,
+            discard_import_job,
             get_usage_events,
             get_detailed_user_info,
             get_invoices,
             get_billing_cycle,
-        ])
+            open_account_details,
+            get_account_overview,
+            get_settings,
+            update_settings,
+            export_settings,
+            get_fleet_stats,
+            export_report,
+            export_accounts_json,
+            export_accounts_xlsx,
+            import_settings,
+            enable_autostart,
+            disable_autostart,
+            set_clipboard_watcher,
+            import_detected_token,
+            import_from_clipboard,
+            check_for_updates,
+            run_auto_export_now,
+            set_locale,
+            get_running_operation,
+            get_token_health,
+            reauthenticate_current_account,
+            preview_switch,
+            get_onboarding_status,
+            find_credential_conflicts,
+            resolve_conflict,
+            complete_onboarding_step,
+            get_app_lock_status,
+            set_app_lock,
+            unlock_app,
+            lock_app,
+            set_store_passphrase,
+            unlock_store,
+            migrate_store_to_sqlite,
+            is_store_locked,
+        ]))
         .on_window_event(|event| {
             if let WindowEvent::CloseRequested { api, .. } = event.event() {
-                // Prevent window from closing, hide it instead
-                event.window().hide().unwrap();
-                api.prevent_close();
+                // Keep the main window alive in the tray; other windows are destroyed
+                if event.window().label() == "main" {
+                    event.window().hide().unwrap();
+                    api.prevent_close();
+                }
             }
         })
         .setup(|app| {
@@ -870,14 +5116,11 @@ fn main() {
                 }
 
                 // Initialize logging
-                let log_dir = app_data_dir.join("logs");
+                let log_dir = app_data_dir.join(app_info::LOG_DIR_NAME);
                 match Logger::init(log_dir.clone()) {
                     Ok(guard) => {
-                        let mut log_guard = state._log_guard.lock().unwrap();
-                        *log_guard = Some(guard);
-
-                        let mut log_path_guard = state.log_path.lock().unwrap();
-                        *log_path_guard = log_dir;
+                        *state.log_guard.lock_or_recover() = Some(guard);
+                        state.set_log_path(log_dir);
 
                         tracing::info!("Cursor Account Switcher started");
                     }
@@ -886,9 +5129,15 @@ fn main() {
                     }
                 }
 
-                let csv_path = app_data_dir.join("cursor_auth_total.csv");
-                let mut csv_path_guard = state.csv_path.lock().unwrap();
-                *csv_path_guard = csv_path.clone();
+                state.set_settings_path(app_data_dir.join(app_info::SETTINGS_FILE_NAME));
+                state.set_journal_path(app_data_dir.join(app_info::JOURNAL_FILE_NAME));
+
+                // The accounts may have been moved out of the app data dir
+                let storage_dir = load_settings(&state)
+                    .data_storage_dir
+                    .map(std::path::PathBuf::from);
+                let csv_path = data_location::csv_path(&app_data_dir, storage_dir.as_deref());
+                state.set_csv_path(csv_path.clone());
 
                 tracing::info!("Data will be stored at: {}", csv_path.display());
             } else {
@@ -897,19 +5146,393 @@ fn main() {
 
             // Auto-detect Cursor path on startup
             if let Ok(path) = PathDetector::detect_cursor_path() {
-                let mut cursor_path = state.cursor_base_path.lock().unwrap();
-                *cursor_path = Some(path.clone());
+                state.set_cursor_base(path.clone());
                 tracing::info!("Cursor path auto-detected: {}", path.display());
             } else {
                 tracing::warn!("Failed to auto-detect Cursor path");
             }
 
+            start_connectivity_check(&app.handle());
+            prefetch_current_account(&app.handle());
+
+            let import_jobs = import_job_store(&state);
+            if let Err(e) = import_jobs.prune(chrono::Utc::now()) {
+                tracing::warn!("Failed to prune import jobs: {}", e);
+            }
+            match import_jobs.list() {
+                Ok(jobs) => {
+                    let unfinished = jobs.iter().filter(|job| !job.is_finished()).count();
+                    if unfinished > 0 {
+                        tracing::info!("{} import job(s) can be resumed", unfinished);
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to list import jobs: {}", e),
+            }
+
+            let settings = load_settings(&state);
+
+            // The tray is labelled in the saved language from the first build
+            i18n::set_locale(settings.locale);
+            state.set_http(HttpClientFactory::from_settings(&settings));
+            state.operations.set_read_only(settings.read_only_policy());
+
+            // A leftover journal entry means the last switch or reset never finished
+            match state.journal().pending() {
+                Ok(Some(entry)) => {
+                    tracing::warn!(
+                        "Found operation interrupted at {}: {:?}",
+                        entry.started_at,
+                        entry.operation
+                    );
+                    notifier::notify(
+                        &app.handle(),
+                        t("notify.interrupted.title"),
+                        t("notify.interrupted.body"),
+                    );
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to read operation journal: {}", e),
+            }
+
             // Initialize tray menu with current accounts
             update_tray_menu(&app.handle());
+
+            let handle = app.handle();
+            state.accounts_changed.start(change_bus::DEBOUNCE, move || {
+                // A sync or re-authentication may have fixed an expiring token
+                if load_settings(&handle.state()).token_health_check {
+                    check_token_health(&handle);
+                }
+                update_tray_menu(&handle);
+                let _ = handle.emit_all("accounts-changed", ());
+            });
             tracing::info!("Tray menu initialized with accounts");
 
+            if let Some(instance) = single_instance {
+                let handle = app.handle();
+                if let Err(e) =
+                    instance.listen(move |message| handle_instance_message(&handle, message))
+                {
+                    tracing::warn!("Failed to start single-instance listener: {}", e);
+                }
+            }
+
+            match std::env::current_exe() {
+                Ok(exe_path) => {
+                    if let Err(e) =
+                        deep_link::register_scheme(&product_name(&app.handle()), &exe_path)
+                    {
+                        tracing::warn!("Failed to register URL scheme: {}", e);
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to resolve executable path: {}", e),
+            }
+
+            // Re-register autostart if the executable moved (e.g. after an update)
+            if settings.launch_at_login {
+                match autostart_manager(&app.handle()) {
+                    Ok(autostart) if !autostart.is_enabled() => {
+                        if let Err(e) = autostart.enable() {
+                            tracing::warn!("Failed to refresh autostart entry: {}", e);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Failed to resolve autostart entry: {}", e),
+                }
+            }
+
+            if settings.clipboard_watcher_enabled {
+                apply_clipboard_watcher(&app.handle(), true);
+                update_tray_menu(&app.handle());
+            }
+
+            if settings.auto_update_check {
+                apply_update_check(&app.handle(), true);
+            }
+
+            if settings.auto_export_enabled {
+                apply_auto_export(&app.handle(), true);
+            }
+
+            if settings.token_health_check {
+                apply_token_health_check(&app.handle(), true);
+            }
+
+            if let Some(interval) = settings.webdav.interval() {
+                apply_webdav_sync(&app.handle(), Some(interval));
+            }
+
+            if settings.http_api_enabled {
+                if let Err(e) = apply_http_api_settings(&app.handle(), &settings) {
+                    tracing::warn!("Local API not started: {}", e);
+                }
+            }
+
+            // The main window starts hidden; only show it unless starting minimized
+            if settings.start_minimized {
+                tracing::info!("Starting minimized to tray");
+            } else if let Some(window) = app.get_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+
+            let onboarding = onboarding_status(&state, &settings);
+            if onboarding.show_wizard {
+                tracing::info!("Setup incomplete, requesting onboarding");
+                let _ = app.emit_all("onboarding-required", &onboarding);
+            }
+
+            let first_launch = !settings
+                .onboarding_steps
+                .contains(&OnboardingStep::Finished);
+            if settings.startup_self_test && first_launch {
+                let handle = app.handle();
+                std::thread::spawn(move || {
+                    let results = self_test(&handle.state());
+                    let _ = handle.emit_all("self-test-results", &results);
+                });
+            }
+
+            spawn_cursor_version_check(&app.handle());
+            spawn_settings_check(&app.handle());
+
+            if let Some(url) = startup_link {
+                handle_deep_link(&app.handle(), &url);
+            }
+
             Ok(())
         })
-        .run(tauri::generate_context!())
+        .run(context)
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    // State under `dir` with an app lock set and not unlocked; whether the
+    // lock is on only depends on there being a hash
+    fn locked_state(dir: &std::path::Path) -> AppState {
+        let state = AppState::new();
+        state.set_settings_path(dir.join("settings.json"));
+        state.set_cursor_base(dir.to_path_buf());
+        state.set_csv_path(dir.join(app_info::ACCOUNTS_FILE_NAME));
+        SettingsManager::new(state.settings_path())
+            .save(&Settings {
+                app_lock_hash: Some("hash".to_string()),
+                ..Settings::default()
+            })
+            .unwrap();
+        state
+    }
+
+    // State under `dir` holding a@x.com and the archived b@x.com, both tagged
+    // "work"
+    fn state_with_archived_account(dir: &std::path::Path) -> AppState {
+        let state = AppState::new();
+        state.set_settings_path(dir.join("settings.json"));
+        state.set_cursor_base(dir.to_path_buf());
+        state.set_csv_path(dir.join(app_info::ACCOUNTS_FILE_NAME));
+        let account = |email: &str, archived| Account {
+            email: email.to_string(),
+            tags: vec!["work".to_string()],
+            archived,
+            ..Default::default()
+        };
+        let store = open_store(&state);
+        store.ensure_exists().unwrap();
+        store
+            .batch_add_accounts(vec![account("a@x.com", false), account("b@x.com", true)])
+            .unwrap();
+        state
+    }
+
+    #[test]
+    fn test_account_page_leaves_out_archived_accounts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = state_with_archived_account(temp_dir.path());
+        let mut query = AccountQuery {
+            limit: 10,
+            ..AccountQuery::default()
+        };
+
+        let page = account_page(&state, &query, None).unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].email, "a@x.com");
+        query.include_archived = true;
+        assert_eq!(account_page(&state, &query, None).unwrap().total, 2);
+    }
+
+    #[test]
+    fn test_accounts_by_tag_leave_out_archived_accounts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = state_with_archived_account(temp_dir.path());
+
+        let emails = |include_archived| -> Vec<String> {
+            accounts_by_tag(&state, "WORK", include_archived)
+                .unwrap()
+                .into_iter()
+                .map(|a| a.email)
+                .collect()
+        };
+        assert_eq!(emails(None), vec!["a@x.com"]);
+        assert_eq!(emails(Some(true)), vec!["a@x.com", "b@x.com"]);
+    }
+
+    #[test]
+    fn test_forecasts_leave_out_archived_accounts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = state_with_archived_account(temp_dir.path());
+
+        let listed = forecasts(&state, None, None).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].email, "a@x.com");
+        assert_eq!(forecasts(&state, None, Some(true)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_fleet_stats_leave_out_archived_accounts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = state_with_archived_account(temp_dir.path());
+
+        let stats = |include_archived| {
+            FleetStats::of(&listed_accounts(&state, None, include_archived).unwrap())
+        };
+        assert_eq!(stats(None).accounts, 1);
+        assert_eq!(stats(Some(true)).accounts, 2);
+    }
+
+    #[test]
+    fn test_report_leaves_out_archived_accounts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = state_with_archived_account(temp_dir.path());
+        let path = temp_dir.path().join("report.md");
+        let path = path.to_str().unwrap();
+
+        write_report(&state, path, ReportFormat::Markdown, None, None, None).unwrap();
+        let report = std::fs::read_to_string(path).unwrap();
+        assert!(report.contains("a@x.com") && !report.contains("b@x.com"));
+
+        write_report(&state, path, ReportFormat::Markdown, None, None, Some(true)).unwrap();
+        assert!(std::fs::read_to_string(path).unwrap().contains("b@x.com"));
+    }
+
+    #[test]
+    fn test_logout_is_refused_while_locked() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = locked_state(temp_dir.path());
+        let db_path = PathDetector::get_db_path(temp_dir.path());
+        Connection::open(&db_path)
+            .unwrap()
+            .execute_batch("CREATE TABLE ItemTable (key TEXT PRIMARY KEY, value BLOB)")
+            .unwrap();
+        let db = Database::new(db_path);
+        db.update_auth("user@x.com", "token", None).unwrap();
+
+        assert!(matches!(logout(&state, false), Err(AppError::Locked)));
+        assert_eq!(db.get_auth_info().unwrap().0, "user@x.com");
+
+        state.unlock_for(Duration::from_secs(60));
+        assert!(logout(&state, false).unwrap() > 0);
+        assert!(db.get_auth_info().is_err());
+    }
+
+    #[test]
+    fn test_every_command_is_classified_for_read_only_mode() {
+        use coordinator::{
+            READ_ONLY_ALLOWED_COMMANDS, READ_ONLY_BLOCKED_COMMANDS, READ_ONLY_REFRESH_COMMANDS,
+        };
+        let source = include_str!("main.rs");
+        let start = source.find("generate_handler![").unwrap() + "generate_handler![".len();
+        let end = start + source[start..].find(']').unwrap();
+        let handlers: Vec<&str> = source[start..end]
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect();
+        assert!(handlers.len() > 100);
+
+        for handler in &handlers {
+            let lists = [
+                READ_ONLY_ALLOWED_COMMANDS,
+                READ_ONLY_BLOCKED_COMMANDS,
+                READ_ONLY_REFRESH_COMMANDS,
+            ];
+            let found = lists.iter().filter(|list| list.contains(handler)).count();
+            assert_eq!(
+                found, 1,
+                "{} must be in exactly one read-only list",
+                handler
+            );
+        }
+        for command in READ_ONLY_ALLOWED_COMMANDS
+            .iter()
+            .chain(READ_ONLY_BLOCKED_COMMANDS)
+            .chain(READ_ONLY_REFRESH_COMMANDS)
+        {
+            assert!(handlers.contains(command), "{} is not a command", command);
+        }
+    }
+
+    #[test]
+    fn test_locked_settings_only_take_cosmetic_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = locked_state(temp_dir.path());
+        let current = load_settings(&state);
+
+        let cosmetic = Settings {
+            locale: Locale::ZhCn,
+            ..current.clone().without_secrets()
+        };
+        assert!(prepare_settings(&state, cosmetic).is_ok());
+        let exporting = Settings {
+            auto_export_enabled: true,
+            auto_export_dir: Some(temp_dir.path().to_string_lossy().into_owned()),
+            ..current.clone().without_secrets()
+        };
+        assert!(matches!(
+            prepare_settings(&state, exporting.clone()),
+            Err(AppError::Locked)
+        ));
+
+        state.unlock_for(Duration::from_secs(60));
+        assert!(prepare_settings(&state, exporting).is_ok());
+    }
+
+    #[test]
+    fn test_locked_settings_import_is_refused() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = locked_state(temp_dir.path());
+        let path = temp_dir.path().join("exported.json");
+        let document = settings::export_settings(
+            &Settings {
+                webdav: settings::WebDavSettings {
+                    enabled: true,
+                    url: "https://dav.example.com/files/me".to_string(),
+                    ..Default::default()
+                },
+                ..Settings::default()
+            },
+            false,
+        )
+        .unwrap();
+        std::fs::write(&path, document.to_string()).unwrap();
+        let path = path.to_string_lossy();
+
+        assert!(matches!(
+            read_settings_import(&state, &path, true),
+            Err(AppError::Locked)
+        ));
+        assert!(!load_settings(&state).webdav.enabled);
+
+        state.unlock_for(Duration::from_secs(60));
+        assert!(
+            read_settings_import(&state, &path, true)
+                .unwrap()
+                .settings
+                .webdav
+                .enabled
+        );
+    }
+}
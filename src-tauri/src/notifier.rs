@@ -0,0 +1,77 @@
+use serde::Serialize;
+use std::process::Command;
+use tauri::Manager;
+
+/// Payload of the `notification` event sent to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+}
+
+/// Show a desktop notification and mirror it to the frontend as a `notification` event.
+/// Uses the platform's own notifier (notify-send, osascript, PowerShell toast) so it
+/// works while the window is hidden; failures are logged and otherwise ignored.
+pub fn notify(app: &tauri::AppHandle, title: &str, body: &str) {
+    let notification = Notification {
+        title: title.to_string(),
+        body: body.to_string(),
+    };
+    let _ = app.emit_all("notification", &notification);
+
+    if let Err(e) = show_native(title, body) {
+        tracing::debug!("Failed to show desktop notification: {}", e);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn show_native(title: &str, body: &str) -> std::io::Result<()> {
+    Command::new("notify-send")
+        .args(["--app-name", "Cursor Account Switcher", "--", title, body])
+        .spawn()
+        .map(|_| ())
+}
+
+#[cfg(target_os = "macos")]
+fn show_native(title: &str, body: &str) -> std::io::Result<()> {
+    // Pass the text as arguments so it never has to be escaped into the script
+    Command::new("osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+            title,
+            body,
+        ])
+        .spawn()
+        .map(|_| ())
+}
+
+#[cfg(target_os = "windows")]
+fn show_native(title: &str, body: &str) -> std::io::Result<()> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    // Text is passed through the environment and XML-escaped inside PowerShell
+    let script = r#"
+[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] | Out-Null
+[Windows.Data.Xml.Dom.XmlDocument, Windows.Data.Xml.Dom.XmlDocument, ContentType = WindowsRuntime] | Out-Null
+$title = [Security.SecurityElement]::Escape($env:NOTIFY_TITLE)
+$body = [Security.SecurityElement]::Escape($env:NOTIFY_BODY)
+$xml = New-Object Windows.Data.Xml.Dom.XmlDocument
+$xml.LoadXml("<toast><visual><binding template='ToastGeneric'><text>$title</text><text>$body</text></binding></visual></toast>")
+$appId = '{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe'
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier($appId).Show([Windows.UI.Notifications.ToastNotification]::new($xml))
+"#;
+
+    Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .env("NOTIFY_TITLE", title)
+        .env("NOTIFY_BODY", body)
+        .creation_flags(CREATE_NO_WINDOW)
+        .spawn()
+        .map(|_| ())
+}
@@ -0,0 +1,137 @@
+use crate::account_store::AccountStore;
+use crate::database::Database;
+use crate::path_detector::PathDetector;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Steps of the first-run wizard the frontend can mark as done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+    CursorPath,
+    ImportAccounts,
+    Finished,
+}
+
+/// Everything the setup wizard needs to decide which step to show
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OnboardingStatus {
+    pub cursor_path: Option<String>,
+    /// The path points at a directory containing Cursor's `state.vscdb`
+    pub cursor_path_valid: bool,
+    pub account_count: usize,
+    pub cursor_running: bool,
+    /// Email of the account currently logged into Cursor
+    pub current_login: Option<String>,
+    pub completed_steps: Vec<OnboardingStep>,
+    /// A valid Cursor path or at least one account is missing
+    pub setup_required: bool,
+    /// Show the wizard: setup is required and the user hasn't finished it before
+    pub show_wizard: bool,
+}
+
+/// A Cursor data directory is usable once its database exists
+pub fn is_valid_cursor_path(base_path: &Path) -> bool {
+    PathDetector::get_db_path(base_path).is_file()
+}
+
+pub fn check(
+    cursor_base: Option<&Path>,
+    accounts: &dyn AccountStore,
+    cursor_running: bool,
+    completed_steps: &[OnboardingStep],
+) -> OnboardingStatus {
+    let cursor_path_valid = cursor_base.is_some_and(is_valid_cursor_path);
+
+    // Only probe a database that exists, opening a missing one would create it
+    let current_login = cursor_base
+        .filter(|_| cursor_path_valid)
+        .and_then(|base| {
+            Database::new(PathDetector::get_db_path(base))
+                .get_auth_info()
+                .ok()
+        })
+        .map(|(email, _)| email);
+
+    // A missing or unreadable store counts as no accounts without creating the file
+    let account_count = if accounts.path().exists() {
+        accounts
+            .read_accounts()
+            .map(|accounts| accounts.len())
+            .unwrap_or(0)
+    } else {
+        0
+    };
+
+    let setup_required = !cursor_path_valid || account_count == 0;
+    OnboardingStatus {
+        cursor_path: cursor_base.map(|base| base.to_string_lossy().to_string()),
+        cursor_path_valid,
+        account_count,
+        cursor_running,
+        current_login,
+        completed_steps: completed_steps.to_vec(),
+        setup_required,
+        show_wizard: setup_required && !completed_steps.contains(&OnboardingStep::Finished),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_manager::CsvManager;
+
+    use crate::test_support::account;
+
+    use rusqlite::Connection;
+
+    fn create_cursor_dir(temp_dir: &Path, email: &str) {
+        let conn = Connection::open(PathDetector::get_db_path(temp_dir)).unwrap();
+        conn.execute(
+            "CREATE TABLE ItemTable (key TEXT PRIMARY KEY, value TEXT)",
+            [],
+        )
+        .unwrap();
+        Database::new(PathDetector::get_db_path(temp_dir))
+            .update_auth(email, "access", None)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_fresh_install_requires_setup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let missing_base = temp_dir.path().join("Cursor");
+        let csv_path = temp_dir.path().join("accounts.csv");
+        let csv = CsvManager::new(csv_path.clone());
+
+        let status = check(Some(&missing_base), &csv, false, &[]);
+        assert!(!status.cursor_path_valid);
+        assert_eq!(status.current_login, None);
+        assert_eq!(status.account_count, 0);
+        assert!(status.setup_required && status.show_wizard);
+        // Checking must not create the database or the CSV
+        assert!(!missing_base.exists());
+        assert!(!csv_path.exists());
+
+        // Finishing the wizard once hides it even while setup is incomplete
+        let status = check(None, &csv, false, &[OnboardingStep::Finished]);
+        assert!(status.setup_required);
+        assert!(!status.show_wizard);
+    }
+
+    #[test]
+    fn test_configured_install() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        create_cursor_dir(temp_dir.path(), "me@x.com");
+        let csv_path = temp_dir.path().join("accounts.csv");
+        let csv = CsvManager::new(csv_path);
+        csv.write_accounts(&[account("me@x.com")]).unwrap();
+
+        let status = check(Some(temp_dir.path()), &csv, true, &[]);
+        assert!(status.cursor_path_valid);
+        assert_eq!(status.current_login.as_deref(), Some("me@x.com"));
+        assert_eq!(status.account_count, 1);
+        assert!(status.cursor_running);
+        assert!(!status.setup_required && !status.show_wizard);
+    }
+}
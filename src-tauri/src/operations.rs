@@ -0,0 +1,2335 @@
+// Account operations shared by the Tauri commands and the command-line interface.
+// These take plain paths instead of `State<AppState>`, and reach Cursor's API and
+// process through `CursorApi` and `CursorProcess`, so they can run without a Tauri
+// app and against fakes in tests.
+
+use crate::account_store::AccountStore;
+use crate::api_client::{ApiError, CursorApi};
+use crate::change_set::{self, ChangeOperation, ChangeSet, ChangeSetStore, KeyChange};
+use crate::clipboard_watcher;
+use crate::comparison::{self, AccountComparison};
+use crate::csv_manager;
+use crate::cursor_settings::{self, SettingsSnapshotStore};
+use crate::database::{AuthSnapshot, Database, SessionTokenNotFound};
+use crate::http::{self, RetryPolicy};
+use crate::intent_journal::{IntentJournal, JournaledOperation};
+use crate::path_detector::PathDetector;
+use crate::pools;
+use crate::process_utils::CursorProcess;
+use crate::recommendation::{self, Recommendation};
+use crate::redaction;
+use crate::reset_machine::{MachineIdResetter, ResetCheck, ResetCheckKind, StorageSnapshot};
+use crate::settings::{ApiPolicy, Settings, SwitchHook};
+use crate::state::MutexExt;
+use crate::state_backups;
+use crate::switch_cooldown::{CooldownMode, CoolingDown, SwitchHistoryStore};
+use crate::switch_hooks::{self, HookContext};
+use crate::switch_log::{SwitchLog, SwitchLogEntry, SwitchSource};
+use crate::token_auth::TokenCheck;
+use crate::tray;
+use crate::types::{
+    days_remaining, record_time, Account, AccountSource, MachineIds, MembershipStatus,
+};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bound for the token check so a slow network can't stall a switch
+pub const TOKEN_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a switch waits for Cursor to exit once it was asked to
+const CURSOR_EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Read all stored accounts, creating the CSV if it doesn't exist yet
+pub fn list_accounts(store: &dyn AccountStore) -> Result<Vec<Account>> {
+    store.ensure_exists()?;
+    store.read_accounts()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SwitchOptions {
+    pub reset_machine: bool,
+    /// Start Cursor again once the switch is done
+    pub restart: bool,
+    /// Try the target token against the API before touching Cursor
+    pub validate_token: bool,
+    /// With `reset_machine`, snapshot Cursor's settings first and put back what the reset wipes
+    #[serde(default)]
+    pub preserve_settings: bool,
+    /// Backups of state.vscdb to keep; the switch takes one before writing
+    /// the new login unless this is 0
+    #[serde(default)]
+    pub state_db_backup_retention: u32,
+}
+
+/// Returned by a switch: the options it ran with and anything the user should know,
+/// e.g. that the token check was skipped while offline
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwitchReport {
+    #[serde(flatten)]
+    pub options: SwitchOptions,
+    pub warnings: Vec<String>,
+    /// The keys the switch wrote in Cursor's files, by hash
+    pub change_set: Option<ChangeSet>,
+}
+
+/// Steps of a switch, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchStage {
+    /// Checking the account and its token; nothing has been changed yet
+    Prepare,
+    CloseCursor,
+    WriteCredentials,
+    ResetMachineId,
+    ApplyPrivacyMode,
+    /// Runs after the switch is complete, so a failure here is not rolled back
+    RestartCursor,
+}
+
+impl SwitchStage {
+    pub fn label(self) -> &'static str {
+        match self {
+            SwitchStage::Prepare => "checking the account",
+            SwitchStage::CloseCursor => "closing Cursor",
+            SwitchStage::WriteCredentials => "writing Cursor's login",
+            SwitchStage::ResetMachineId => "resetting the machine ID",
+            SwitchStage::ApplyPrivacyMode => "applying the privacy mode",
+            SwitchStage::RestartCursor => "restarting Cursor",
+        }
+    }
+
+    fn next_step(self) -> &'static str {
+        match self {
+            SwitchStage::Prepare => "Nothing was changed. Fix the problem and try again.",
+            SwitchStage::CloseCursor => "Nothing was changed. Quit Cursor yourself and try again.",
+            SwitchStage::WriteCredentials => {
+                "Cursor is back on the previous account. Close anything else using its database and try again."
+            }
+            SwitchStage::ResetMachineId => {
+                "Cursor is back on the previous account. Check that storage.json is writable, or switch without resetting the machine ID."
+            }
+            SwitchStage::ApplyPrivacyMode => {
+                "Cursor is back on the previous account. Try again, or clear the account's preferred privacy mode."
+            }
+            SwitchStage::RestartCursor => "The account was switched. Start Cursor yourself.",
+        }
+    }
+}
+
+/// Why a switch failed and what was undone. Failures after Cursor was closed
+/// restore its previous login, machine IDs and process before they are reported.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwitchFailure {
+    pub stage: SwitchStage,
+    pub error: String,
+    /// Stages whose changes were undone, most recent first
+    pub rolled_back: Vec<SwitchStage>,
+    /// Undo steps that failed too; Cursor may be left half switched when not empty
+    pub rollback_errors: Vec<String>,
+    pub next_step: String,
+}
+
+impl SwitchFailure {
+    fn unchanged(error: anyhow::Error) -> Self {
+        Self::without_rollback(SwitchStage::Prepare, error)
+    }
+
+    fn without_rollback(stage: SwitchStage, error: anyhow::Error) -> Self {
+        Self {
+            stage,
+            error: format!("{:#}", error),
+            rolled_back: Vec::new(),
+            rollback_errors: Vec::new(),
+            next_step: stage.next_step().to_string(),
+        }
+    }
+
+    fn record_undo(&mut self, undone: SwitchStage, result: Result<()>) {
+        match result {
+            Ok(()) => self.rolled_back.push(undone),
+            Err(e) => {
+                tracing::error!("Failed to undo {}: {:#}", undone.label(), e);
+                self.rollback_errors
+                    .push(format!("Undoing {} failed: {:#}", undone.label(), e));
+            }
+        }
+    }
+
+    /// Whether Cursor is exactly as it was before the switch, or fully switched
+    /// when only the restart failed
+    pub fn is_consistent(&self) -> bool {
+        self.rollback_errors.is_empty()
+    }
+}
+
+impl fmt::Display for SwitchFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stage {
+            SwitchStage::Prepare => f.write_str(&self.error),
+            SwitchStage::RestartCursor => {
+                write!(f, "Switched, but restarting Cursor failed: {}", self.error)
+            }
+            stage if self.is_consistent() => write!(
+                f,
+                "Switch failed while {} and was rolled back: {}",
+                stage.label(),
+                self.error
+            ),
+            stage => write!(
+                f,
+                "Switch failed while {} and could not be rolled back: {}",
+                stage.label(),
+                self.error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SwitchFailure {}
+
+/// The switched-to account, or where the switch stopped
+pub type SwitchOutcome = std::result::Result<Account, SwitchFailure>;
+
+/// Something worth telling the user before a switch
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SwitchProblem {
+    AccountNotFound,
+    MissingToken,
+    /// Banned, expired, or a trial with no days left
+    Inactive {
+        status: MembershipStatus,
+    },
+    AlreadyActive,
+    /// The machine ID reset would abort; see `SwitchPlan::reset_checks`
+    ResetCheckFailed {
+        check: ResetCheckKind,
+        detail: String,
+    },
+}
+
+impl SwitchProblem {
+    /// Blocking problems make the switch fail; the others are warnings
+    pub fn is_blocking(&self) -> bool {
+        matches!(
+            self,
+            SwitchProblem::AccountNotFound
+                | SwitchProblem::MissingToken
+                | SwitchProblem::ResetCheckFailed { .. }
+        )
+    }
+}
+
+/// Everything a switch will do, gathered without side effects
+#[derive(Debug, Clone, Serialize)]
+pub struct SwitchPlan {
+    pub current_email: Option<String>,
+    pub target_email: String,
+    /// Cursor is always closed before its database is written
+    pub cursor_running: bool,
+    pub reset_machine: bool,
+    pub restart: bool,
+    pub validate_token: bool,
+    pub preserve_settings: bool,
+    /// Backups of the database kept; one is taken before it is written
+    /// unless this is 0
+    pub state_db_backup_retention: u32,
+    pub database_path: PathBuf,
+    /// Written only when the machine ID is reset
+    pub storage_path: Option<PathBuf>,
+    /// Pre-flight checks of the machine ID reset, empty without one
+    pub reset_checks: Vec<ResetCheck>,
+    pub problems: Vec<SwitchProblem>,
+    pub can_switch: bool,
+    // Carries the tokens to `execute_switch`, never to the frontend
+    #[serde(skip)]
+    target: Option<Account>,
+    #[serde(skip)]
+    settings_snapshots: PathBuf,
+    #[serde(skip)]
+    change_sets: PathBuf,
+}
+
+impl SwitchPlan {
+    fn resetter(&self, base_path: &Path) -> MachineIdResetter {
+        let resetter = MachineIdResetter::new(base_path.to_path_buf());
+        if self.preserve_settings {
+            resetter
+                .preserving_settings(SettingsSnapshotStore::new(self.settings_snapshots.clone()))
+        } else {
+            resetter
+        }
+    }
+}
+
+/// Work out what switching to `email` would change
+pub fn plan_switch(
+    base_path: &Path,
+    store: &dyn AccountStore,
+    email: &str,
+    options: &SwitchOptions,
+    cursor: &dyn CursorProcess,
+) -> Result<SwitchPlan> {
+    store.ensure_exists()?;
+    let target = store.find_account_by_email(email)?;
+
+    let database_path = PathDetector::get_db_path(base_path);
+    // Opening a missing database would create it, so only read one that exists
+    let current_email = if database_path.is_file() {
+        Database::new(database_path.clone())
+            .get_auth_info()
+            .ok()
+            .map(|(email, _)| email)
+    } else {
+        None
+    };
+
+    let mut problems = Vec::new();
+    match &target {
+        None => problems.push(SwitchProblem::AccountNotFound),
+        Some(account) => {
+            if account.access_token.expose().trim().is_empty() {
+                problems.push(SwitchProblem::MissingToken);
+            }
+            if tray::is_inactive(account) {
+                problems.push(SwitchProblem::Inactive {
+                    status: account.status.clone(),
+                });
+            }
+        }
+    }
+    if current_email.as_deref() == Some(email) {
+        problems.push(SwitchProblem::AlreadyActive);
+    }
+    let reset_checks = if options.reset_machine {
+        MachineIdResetter::new(base_path.to_path_buf()).preflight()
+    } else {
+        Vec::new()
+    };
+    problems.extend(
+        reset_checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| SwitchProblem::ResetCheckFailed {
+                check: check.kind,
+                detail: check.detail.clone(),
+            }),
+    );
+
+    Ok(SwitchPlan {
+        current_email,
+        target_email: email.to_string(),
+        cursor_running: cursor.is_running(),
+        reset_machine: options.reset_machine,
+        restart: options.restart,
+        validate_token: options.validate_token,
+        preserve_settings: options.preserve_settings,
+        state_db_backup_retention: options.state_db_backup_retention,
+        database_path,
+        storage_path: options
+            .reset_machine
+            .then(|| PathDetector::get_storage_path(base_path)),
+        reset_checks,
+        can_switch: !problems.iter().any(SwitchProblem::is_blocking),
+        problems,
+        target,
+        settings_snapshots: store.path().with_file_name(cursor_settings::SNAPSHOTS_DIR),
+        change_sets: store.path().with_file_name(change_set::CHANGE_SETS_FILE),
+    })
+}
+
+/// Carry out a plan from `plan_switch`, refusing plans with blocking problems.
+/// The switch is recorded in `journal` while Cursor's files are being written,
+/// and stays there only if a failure couldn't be rolled back.
+pub fn execute_switch(
+    base_path: &Path,
+    plan: SwitchPlan,
+    journal: &IntentJournal,
+    api: &dyn CursorApi,
+    cursor: &dyn CursorProcess,
+) -> SwitchOutcome {
+    let account = prepare_switch(&plan, api).map_err(SwitchFailure::unchanged)?;
+    tracing::info!("Switching to account: {}", account.email);
+
+    let entry = journal
+        .begin(JournaledOperation::Switch {
+            email: account.email.clone(),
+            options: SwitchOptions {
+                reset_machine: plan.reset_machine,
+                restart: plan.restart,
+                validate_token: plan.validate_token,
+                preserve_settings: plan.preserve_settings,
+                state_db_backup_retention: plan.state_db_backup_retention,
+            },
+        })
+        .map_err(SwitchFailure::unchanged)?;
+
+    let mut run = SwitchRun::new(base_path, cursor, plan.state_db_backup_retention);
+    let resetter = plan.reset_machine.then(|| plan.resetter(base_path));
+    if let Err((stage, error)) = run.apply(&account, resetter) {
+        tracing::error!("Switch failed while {}: {:#}", stage.label(), error);
+        let failure = run.roll_back(stage, error);
+        if failure.is_consistent() {
+            if let Err(e) = entry.complete() {
+                tracing::warn!("Failed to clear the operation journal: {:#}", e);
+            }
+        } else {
+            // Left behind so the app offers to resume or discard the switch
+            drop(entry);
+        }
+        return Err(failure);
+    }
+    if let Err(e) = entry.complete() {
+        tracing::warn!("Failed to clear the operation journal: {:#}", e);
+    }
+    tracing::info!("Account switch completed successfully");
+    let change_set = ChangeSet {
+        operation: ChangeOperation::Switch,
+        email: Some(account.email.clone()),
+        at: chrono::Utc::now(),
+        changes: run.changes,
+    };
+    if let Err(e) = ChangeSetStore::new(plan.change_sets.clone()).record(&change_set) {
+        tracing::warn!("Failed to record what the switch changed: {:#}", e);
+    }
+
+    if plan.restart {
+        cursor.restart().map_err(|e| {
+            tracing::error!("Failed to restart Cursor: {:#}", e);
+            SwitchFailure::without_rollback(SwitchStage::RestartCursor, e)
+        })?;
+    }
+    Ok(account)
+}
+
+// Everything that can fail before Cursor is touched
+fn prepare_switch(plan: &SwitchPlan, api: &dyn CursorApi) -> Result<Account> {
+    let account = match &plan.target {
+        Some(account) if plan.can_switch => account.clone(),
+        Some(_) => bail!("Account has no access token: {}", plan.target_email),
+        None => bail!("Account not found: {}", plan.target_email),
+    };
+
+    // Runs before Cursor is killed, so a dead token leaves everything as it was
+    if plan.validate_token {
+        match api.check_access_token(account.access_token.expose()) {
+            TokenCheck::Valid => {}
+            TokenCheck::Rejected { status } => bail!(
+                "Target account's token is invalid or expired (HTTP {}); Cursor was left untouched",
+                status
+            ),
+            TokenCheck::Unreachable(error) => bail!(
+                "Could not verify the target account's token ({}); Cursor was left untouched. \
+                 Switch without token validation to skip this check when offline",
+                error
+            ),
+        }
+    }
+    Ok(account)
+}
+
+/// The stages that change Cursor, with what is needed to undo them
+struct SwitchRun<'a> {
+    base_path: &'a Path,
+    cursor: &'a dyn CursorProcess,
+    /// Backups of the database kept, 0 to take none
+    state_db_backup_retention: u32,
+    /// Cursor was running and has been closed, so rolling back starts it again
+    closed_running_cursor: bool,
+    auth: Option<AuthSnapshot>,
+    storage: Option<StorageSnapshot>,
+    /// Keys written so far
+    changes: Vec<KeyChange>,
+}
+
+impl<'a> SwitchRun<'a> {
+    fn new(
+        base_path: &'a Path,
+        cursor: &'a dyn CursorProcess,
+        state_db_backup_retention: u32,
+    ) -> Self {
+        Self {
+            base_path,
+            cursor,
+            state_db_backup_retention,
+            closed_running_cursor: false,
+            auth: None,
+            storage: None,
+            changes: Vec::new(),
+        }
+    }
+
+    fn apply(
+        &mut self,
+        account: &Account,
+        resetter: Option<MachineIdResetter>,
+    ) -> std::result::Result<(), (SwitchStage, anyhow::Error)> {
+        let at = |stage: SwitchStage| move |error: anyhow::Error| (stage, error);
+
+        tracing::info!("Killing Cursor process");
+        let was_running = self.cursor.is_running();
+        self.cursor.kill().map_err(at(SwitchStage::CloseCursor))?;
+        // Until it has exited, Cursor holds the database and may write it
+        // on the way out
+        if was_running && !self.cursor.wait_for_exit(CURSOR_EXIT_TIMEOUT) {
+            return Err((
+                SwitchStage::CloseCursor,
+                anyhow::anyhow!("Cursor is still running after being closed"),
+            ));
+        }
+        self.closed_running_cursor = was_running;
+
+        // A copy of the whole file, for when the rollback below can't help,
+        // e.g. because the write was cut off halfway
+        if let Some(backup) = state_backups::back_up(
+            self.base_path,
+            self.state_db_backup_retention,
+            chrono::Local::now(),
+        )
+        .context("Failed to back up state.vscdb")
+        .map_err(at(SwitchStage::WriteCredentials))?
+        {
+            tracing::info!("Backed up state.vscdb to {}", backup.display());
+        }
+
+        // Taken once Cursor is closed, so it can't change the login afterwards
+        tracing::info!("Updating database with new credentials");
+        let db = Database::new(PathDetector::get_db_path(self.base_path));
+        self.auth = Some(
+            db.snapshot_auth()
+                .map_err(at(SwitchStage::WriteCredentials))?,
+        );
+        let written = db
+            .update_auth_full(
+                &account.email,
+                account.access_token.expose(),
+                Some(account.refresh_token.expose()),
+                Some(account.cookie.expose()),
+            )
+            .map_err(at(SwitchStage::WriteCredentials))?;
+        self.changes.extend(written);
+
+        if let Some(resetter) = resetter {
+            tracing::info!("Resetting machine ID");
+            self.storage = Some(
+                resetter
+                    .snapshot_storage()
+                    .map_err(at(SwitchStage::ResetMachineId))?,
+            );
+            let ids = resetter
+                .reset()
+                .context("Machine ID reset failed")
+                .map_err(at(SwitchStage::ResetMachineId))?;
+            self.changes.extend(ids.changes);
+        }
+
+        // Cursor is closed at this point, so it can't overwrite the setting on exit
+        if let Some(enabled) = account.privacy_mode {
+            tracing::info!("Applying preferred privacy mode: {}", enabled);
+            let written = db
+                .set_privacy_mode(enabled)
+                .map_err(at(SwitchStage::ApplyPrivacyMode))?;
+            self.changes.push(written);
+        }
+        Ok(())
+    }
+
+    // Undo in reverse order. The auth snapshot also covers the privacy mode.
+    fn roll_back(self, stage: SwitchStage, error: anyhow::Error) -> SwitchFailure {
+        let mut failure = SwitchFailure::without_rollback(stage, error);
+        if let Some(storage) = &self.storage {
+            failure.record_undo(SwitchStage::ResetMachineId, storage.restore());
+        }
+        if let Some(auth) = &self.auth {
+            let db = Database::new(PathDetector::get_db_path(self.base_path));
+            failure.record_undo(SwitchStage::WriteCredentials, db.restore_auth(auth));
+        }
+        if failure.is_consistent() && self.closed_running_cursor {
+            failure.record_undo(SwitchStage::CloseCursor, self.cursor.restart());
+        }
+
+        if !failure.is_consistent() {
+            failure.next_step = "Cursor could not be put back as it was. Open the switcher to resume or discard the interrupted switch.".to_string();
+        }
+        failure
+    }
+}
+
+/// Look up a stored account by email and switch to it
+pub fn switch_to_stored_account(
+    base_path: &Path,
+    store: &dyn AccountStore,
+    email: &str,
+    options: &SwitchOptions,
+    journal: &IntentJournal,
+    api: &dyn CursorApi,
+    cursor: &dyn CursorProcess,
+) -> SwitchOutcome {
+    let plan =
+        plan_switch(base_path, store, email, options, cursor).map_err(SwitchFailure::unchanged)?;
+    let account = execute_switch(base_path, plan, journal, api, cursor)?;
+    // Cursor has the account already, so failing to note it only costs the stamp
+    let now = chrono::Local::now().format(record_time::FORMAT).to_string();
+    if let Err(e) = mark_used(store, email, &now) {
+        tracing::warn!("Failed to record when {} was used: {:#}", email, e);
+    }
+    Ok(account)
+}
+
+/// Add a switch that went through to the switch log, keeping at most
+/// `max_rows`; failed switches leave no row. Whether a row was added.
+pub fn log_switch(
+    log: &SwitchLog,
+    outcome: &SwitchOutcome,
+    options: &SwitchOptions,
+    source: SwitchSource,
+    max_rows: usize,
+) -> Result<bool> {
+    let Ok(account) = outcome else {
+        return Ok(false);
+    };
+    log.record(
+        &SwitchLogEntry {
+            timestamp: chrono::Local::now().format(record_time::FORMAT).to_string(),
+            email: account.email.clone(),
+            reset_machine: options.reset_machine,
+            source,
+        },
+        max_rows,
+    )?;
+    Ok(true)
+}
+
+/// What a switch from any entry point records and runs besides the switch
+/// itself, see `run_switch`
+pub struct SwitchContext<'a> {
+    /// Cooldowns, switch hooks and how many switch log rows are kept
+    pub settings: &'a Settings,
+    pub journal: &'a IntentJournal,
+    /// When accounts were last used, for cooldowns
+    pub history: &'a SwitchHistoryStore,
+    pub log: &'a SwitchLog,
+    pub source: SwitchSource,
+    /// Runs a hook the switch doesn't wait for, reporting its failure
+    pub spawn_hook: &'a dyn Fn(&'static str, SwitchHook, HookContext),
+}
+
+/// `switch_to_stored_account` the way the window, the tray, the API and the
+/// CLI all switch. A switch to an account cooling down is refused when
+/// cooldowns are enforced, and so is one a blocking pre-switch hook fails;
+/// both are errors, and Cursor is left alone. Whatever the switch did is its
+/// outcome; one that went through is logged, starts the cooldown of the
+/// account left and the one switched to, and runs the post-switch hook.
+pub fn run_switch(
+    base_path: &Path,
+    store: &dyn AccountStore,
+    email: &str,
+    options: &SwitchOptions,
+    api: &dyn CursorApi,
+    cursor: &dyn CursorProcess,
+    context: &SwitchContext,
+) -> Result<SwitchOutcome> {
+    let settings = context.settings;
+    let previous_email = Database::new(PathDetector::get_db_path(base_path))
+        .get_auth_info()
+        .ok()
+        .map(|(email, _)| email);
+    // Switching to the account in use again, e.g. to reset the machine ID,
+    // has no cooldown
+    if settings.cooldown_mode == CooldownMode::Enforce && previous_email.as_deref() != Some(email) {
+        if let Some(remaining) = cooldown_left(context.history, settings, email) {
+            tracing::warn!("Not switching to {}, it is cooling down", email);
+            return Err(CoolingDown {
+                email: email.to_string(),
+                remaining,
+            }
+            .into());
+        }
+    }
+
+    let hooks = &settings.switch_hooks;
+    let hook_context = HookContext {
+        email: email.to_string(),
+        status: store
+            .find_account_by_email(email)
+            .ok()
+            .flatten()
+            .map_or_else(
+                || "unknown".to_string(),
+                |account| account.status.as_str().to_string(),
+            ),
+        reset_machine: options.reset_machine,
+    };
+    if let Some(hook) = &hooks.pre {
+        if hook.blocking {
+            let exit = switch_hooks::run("pre-switch", hook, &hook_context)?;
+            if !exit.success() {
+                tracing::warn!("Pre-switch hook {}, switch cancelled", exit.describe());
+                bail!("Switch cancelled: the pre-switch hook {}", exit.describe());
+            }
+        } else {
+            (context.spawn_hook)("pre-switch", hook.clone(), hook_context.clone());
+        }
+    }
+
+    let outcome = switch_to_stored_account(
+        base_path,
+        store,
+        email,
+        options,
+        context.journal,
+        api,
+        cursor,
+    );
+    if let Err(e) = log_switch(
+        context.log,
+        &outcome,
+        options,
+        context.source,
+        settings.switch_history_max_rows,
+    ) {
+        tracing::warn!("Failed to add the switch to the switch history: {:#}", e);
+    }
+    if outcome.is_ok() {
+        // The account left was in use until now
+        let used: Vec<&str> = previous_email
+            .iter()
+            .map(String::as_str)
+            .chain([email])
+            .collect();
+        if let Err(e) = context.history.record(&used, chrono::Utc::now()) {
+            tracing::warn!("Failed to record the switch for cooldowns: {:#}", e);
+        }
+        if let Some(hook) = &hooks.post {
+            (context.spawn_hook)("post-switch", hook.clone(), hook_context);
+        }
+    }
+    Ok(outcome)
+}
+
+/// Cooldown `email` has left, None when it has none or cooldowns are off.
+/// An unreadable history counts as no cooldowns.
+pub fn cooldown_left(
+    history: &SwitchHistoryStore,
+    settings: &Settings,
+    email: &str,
+) -> Option<chrono::Duration> {
+    let window = settings.cooldown_window()?;
+    history
+        .cooling_down(window, chrono::Utc::now())
+        .unwrap_or_else(|e| {
+            tracing::warn!("Ignoring switch cooldowns: {:#}", e);
+            BTreeMap::new()
+        })
+        .remove(email)
+}
+
+/// Returned by `resolve_pending_accounts`: the real emails found and the
+/// placeholders whose cookie couldn't be exchanged
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PendingResolution {
+    pub resolved: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// Exchange the cookie of every account stored under a placeholder email, see
+/// `csv_manager::pending_email`, for its tokens and email with `exchange`.
+/// The row takes them in place; when the email is stored already, that row
+/// gets the tokens and the placeholder row goes.
+pub fn resolve_pending(
+    store: &dyn AccountStore,
+    exchange: &dyn Fn(&str) -> Result<Account>,
+) -> Result<PendingResolution> {
+    let mut resolution = PendingResolution::default();
+    let pending: Vec<Account> = list_accounts(store)?
+        .into_iter()
+        .filter(|account| csv_manager::is_pending_email(&account.email))
+        .collect();
+
+    for account in pending {
+        let found = match exchange(account.cookie.expose()) {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::warn!("Failed to resolve {}: {:#}", account.email, e);
+                resolution.failed.push(account.email);
+                continue;
+            }
+        };
+        store.modify(|accounts| {
+            let Some(position) = accounts.iter().position(|a| a.email == account.email) else {
+                return Ok(());
+            };
+            if accounts
+                .iter()
+                .any(|a| a.email.eq_ignore_ascii_case(&found.email))
+            {
+                accounts.remove(position);
+                csv_manager::merge_accounts(accounts, vec![found.clone()]);
+                csv_manager::renumber(accounts);
+            } else {
+                let row = &mut accounts[position];
+                row.email = found.email.clone();
+                row.access_token = found.access_token.clone();
+                row.refresh_token = found.refresh_token.clone();
+                row.cookie = found.cookie.clone();
+            }
+            Ok(())
+        })?;
+        resolution.resolved.push(found.email);
+    }
+    Ok(resolution)
+}
+
+/// Archive every row of `email`, or bring archived rows back; false when
+/// nothing changed
+pub fn set_archived(store: &dyn AccountStore, email: &str, archived: bool) -> Result<bool> {
+    store.modify(|accounts| {
+        let mut changed = false;
+        for account in accounts.iter_mut().filter(|a| a.email == email) {
+            changed |= account.archived != archived;
+            account.archived = archived;
+        }
+        Ok(changed)
+    })
+}
+
+/// Set `last_used` of the first row stored under `email`; false without one
+pub fn mark_used(store: &dyn AccountStore, email: &str, at: &str) -> Result<bool> {
+    store.modify(|accounts| {
+        let account = accounts.iter_mut().find(|a| a.email == email);
+        let found = account.is_some();
+        if let Some(account) = account {
+            account.last_used = at.to_string();
+        }
+        Ok(found)
+    })
+}
+
+/// Write a new set of machine IDs; returns it with where it was applied. With
+/// `settings_snapshots`, Cursor's settings are preserved across the reset.
+pub fn reset_machine_id(
+    base_path: &Path,
+    settings_snapshots: Option<SettingsSnapshotStore>,
+    change_sets: &ChangeSetStore,
+    journal: &IntentJournal,
+    cursor: &dyn CursorProcess,
+) -> Result<MachineIds> {
+    tracing::info!("Resetting machine ID");
+    let mut resetter = MachineIdResetter::new(base_path.to_path_buf());
+    if let Some(store) = settings_snapshots {
+        resetter = resetter.preserving_settings(store);
+    }
+    // Before Cursor is closed for nothing
+    resetter.ensure_preflight()?;
+    let entry = journal.begin(JournaledOperation::ResetMachineId)?;
+    cursor.kill()?;
+    let ids = resetter.reset().map_err(|e| {
+        tracing::error!("Failed to reset machine ID: {}", e);
+        e
+    })?;
+    entry.complete()?;
+    let change_set = ChangeSet {
+        operation: ChangeOperation::ResetMachineId,
+        email: None,
+        at: chrono::Utc::now(),
+        changes: ids.changes.clone(),
+    };
+    if let Err(e) = change_sets.record(&change_set) {
+        tracing::warn!("Failed to record what the reset changed: {:#}", e);
+    }
+    Ok(ids)
+}
+
+/// Fetch membership and usage data for one account. Missing usage data is
+/// cleared rather than treated as a failure.
+fn refresh_account_info(api: &dyn CursorApi, account: &mut Account) -> Result<()> {
+    let extra = http::extra_headers(account.extra_headers.as_ref());
+    let account_info =
+        api.get_account_info(&account.email, account.access_token.expose(), &extra)?;
+    account.days_remaining = days_remaining::from_api(account_info.days_remaining);
+    account.status = account_info.membership_type;
+    account.record_time = chrono::Local::now().format(record_time::FORMAT).to_string();
+
+    // Fetch usage info
+    match api.get_usage_info(account.access_token.expose(), &extra) {
+        Ok(usage_info) => {
+            account.usage_used = Some(usage_info.used);
+            account.usage_remaining = Some(usage_info.remaining);
+            account.usage_total = Some(usage_info.total_quota);
+            account.usage_percentage = Some(usage_info.usage_percentage);
+        }
+        Err(e) => {
+            tracing::warn!("Failed to fetch usage info for {}: {}", account.email, e);
+            account.usage_used = None;
+            account.usage_remaining = None;
+            account.usage_total = None;
+            account.usage_percentage = None;
+        }
+    }
+    Ok(())
+}
+
+// Store what a refresh found without losing what was written while it ran:
+// only the refreshed fields of the accounts still in the file change
+fn store_refreshed<'a>(
+    store: &dyn AccountStore,
+    refreshed: impl IntoIterator<Item = &'a Account>,
+) -> Result<()> {
+    let refreshed: HashMap<&str, &Account> = refreshed
+        .into_iter()
+        .map(|account| (account.email.as_str(), account))
+        .collect();
+    store.modify(|stored| {
+        for account in stored.iter_mut() {
+            let Some(fresh) = refreshed.get(account.email.as_str()) else {
+                continue;
+            };
+            account.days_remaining = fresh.days_remaining;
+            account.status = fresh.status.clone();
+            account.record_time = fresh.record_time.clone();
+            account.usage_used = fresh.usage_used;
+            account.usage_remaining = fresh.usage_remaining;
+            account.usage_total = fresh.usage_total;
+            account.usage_percentage = fresh.usage_percentage;
+        }
+        Ok(())
+    })
+}
+
+// Rate limits pass after a while, so those are tried again; anything else is final
+fn refresh_with_retry(
+    api: &dyn CursorApi,
+    account: &mut Account,
+    retry: &RetryPolicy,
+) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        match refresh_account_info(api, account) {
+            Err(e)
+                if attempt < retry.max_retries
+                    && ApiError::of(&e) == Some(&ApiError::RateLimited) =>
+            {
+                attempt += 1;
+                tracing::debug!(
+                    "Rate limited refreshing {}, retry {}",
+                    account.email,
+                    attempt
+                );
+                std::thread::sleep(retry.delay(attempt));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Refresh membership data of one stored account. Without `access_token` the
+/// stored token is used.
+pub fn update_account_info(
+    store: &dyn AccountStore,
+    email: &str,
+    access_token: Option<&str>,
+    api: &dyn CursorApi,
+) -> Result<Account> {
+    let account = store
+        .find_account_by_email(email)?
+        .context("Account not found")?;
+
+    let access_token = access_token.unwrap_or(account.access_token.expose());
+    let extra = http::extra_headers(account.extra_headers.as_ref());
+    let account_info = api.get_account_info(email, access_token, &extra)?;
+    let now = chrono::Local::now().format(record_time::FORMAT).to_string();
+
+    // Like `store_refreshed`: edits made during the request are kept
+    store.modify(|stored| {
+        let account = stored
+            .iter_mut()
+            .find(|account| account.email == email)
+            .context("Account not found")?;
+        account.days_remaining = days_remaining::from_api(account_info.days_remaining);
+        account.status = account_info.membership_type;
+        account.record_time = now;
+        Ok(account.clone())
+    })
+}
+
+/// Refresh membership and usage data of every stored account from the API
+pub fn batch_update_all_accounts(
+    store: &dyn AccountStore,
+    api: &dyn CursorApi,
+) -> Result<Vec<Account>> {
+    batch_update_all_accounts_with(store, api, &ApiPolicy::default())
+}
+
+/// `batch_update_all_accounts` with the user's API policy
+pub fn batch_update_all_accounts_with(
+    store: &dyn AccountStore,
+    api: &dyn CursorApi,
+    policy: &ApiPolicy,
+) -> Result<Vec<Account>> {
+    Ok(batch_refresh(store, api, policy, &|_| true)?.accounts)
+}
+
+/// Whether a stale-only refresh picks `account`: banned accounts stay banned,
+/// so refreshing them would only spend requests
+pub fn is_refresh_due(
+    account: &Account,
+    stale_after_hours: u32,
+    now: chrono::NaiveDateTime,
+) -> bool {
+    account.status != MembershipStatus::Banned && tray::is_stale(account, stale_after_hours, now)
+}
+
+/// The refreshed accounts after a batch refresh, and the ones that couldn't be refreshed
+#[derive(Debug)]
+pub struct BatchRefresh {
+    pub accounts: Vec<Account>,
+    pub failed: Vec<String>,
+}
+
+/// Refresh every stored account with the user's API policy: up to
+/// `max_concurrency` accounts are refreshed at once and rate limits are
+/// retried by its retry settings. Accounts whose token is rejected are marked
+/// expired, other failures as errors. With a `pool` only its members are
+/// refreshed and returned. Archived accounts are never refreshed.
+pub fn batch_refresh(
+    store: &dyn AccountStore,
+    api: &dyn CursorApi,
+    policy: &ApiPolicy,
+    selected: &(dyn Fn(&Account) -> bool + Sync),
+) -> Result<BatchRefresh> {
+    tracing::info!("Starting batch update for all accounts");
+
+    let mut accounts = store.read_accounts()?;
+    let picked: Vec<bool> = accounts
+        .iter()
+        .map(|account| !account.archived && selected(account))
+        .collect();
+    let members = picked.iter().filter(|picked| **picked).count();
+    tracing::info!("Updating {} account(s)", members);
+
+    let retry = policy.retry_policy();
+    let workers = policy.max_concurrency.clamp(1, members.max(1));
+    let queue = Mutex::new(
+        accounts
+            .iter_mut()
+            .zip(&picked)
+            .filter(|(_, picked)| **picked)
+            .map(|(account, _)| account),
+    );
+    let failed = Mutex::new(Vec::new());
+
+    // Each worker takes the next account off the queue until it is empty
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let Some(account) = queue.lock_or_recover().next() else {
+                    break;
+                };
+                match refresh_with_retry(api, account, &retry) {
+                    Ok(()) => tracing::debug!("Updated account: {}", account.email),
+                    Err(e) => {
+                        tracing::error!("Failed to update account {}: {}", account.email, e);
+                        account.status = match ApiError::of(&e) {
+                            Some(ApiError::Unauthorized { .. }) => MembershipStatus::Expired,
+                            _ => MembershipStatus::Error,
+                        };
+                        failed.lock_or_recover().push(account.email.clone());
+                    }
+                }
+            });
+        }
+    });
+
+    // Picked before the refresh, which may change what `selected` says
+    let mut picked = picked.into_iter();
+    accounts.retain(|_| picked.next().unwrap_or(false));
+    store_refreshed(store, &accounts)?;
+
+    let mut failed = failed
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    failed.sort();
+    tracing::info!(
+        "Batch update completed: {} successful, {} failed",
+        accounts.len() - failed.len(),
+        failed.len()
+    );
+    Ok(BatchRefresh { accounts, failed })
+}
+
+/// Pick the stored account with the most headroom for a big task. With `live`
+/// the top candidates are refreshed from the API first; candidates that can't
+/// be reached (e.g. offline) keep their stored data. Accounts in
+/// `cooling_down` are only picked when no other candidate is left. With a
+/// `pool` only its members are candidates.
+pub fn recommend_account<T>(
+    store: &dyn AccountStore,
+    current_email: Option<&str>,
+    min_remaining: Option<f64>,
+    pool: Option<&str>,
+    cooling_down: &BTreeMap<String, T>,
+    live: bool,
+    api: &dyn CursorApi,
+) -> Result<Recommendation> {
+    let mut accounts = store.read_accounts()?;
+    let now = || chrono::Local::now().naive_local();
+    let members = |accounts: &[Account]| -> Vec<Account> {
+        accounts
+            .iter()
+            .filter(|a| !a.archived && pools::in_pool(a, pool))
+            .cloned()
+            .collect()
+    };
+
+    let mut refreshed = Vec::new();
+    if live {
+        let mut candidates =
+            recommendation::rank_accounts(&members(&accounts), current_email, None, now());
+        recommendation::rest_cooling_down(&mut candidates, cooling_down);
+        for candidate in candidates.iter().take(recommendation::LIVE_CANDIDATES) {
+            let Some(account) = accounts.iter_mut().find(|a| a.email == candidate.email) else {
+                continue;
+            };
+            match refresh_account_info(api, account) {
+                Ok(()) => refreshed.push(candidate.email.clone()),
+                Err(e) => tracing::warn!(
+                    "Using stored data for {}, refresh failed: {}",
+                    candidate.email,
+                    e
+                ),
+            }
+        }
+        if !refreshed.is_empty() {
+            store_refreshed(
+                store,
+                accounts.iter().filter(|a| refreshed.contains(&a.email)),
+            )?;
+        }
+    }
+
+    let mut ranked =
+        recommendation::rank_accounts(&members(&accounts), current_email, min_remaining, now());
+    if ranked.is_empty() {
+        bail!("No account is eligible for a recommendation");
+    }
+    recommendation::rest_cooling_down(&mut ranked, cooling_down);
+    let best = ranked.remove(0);
+    ranked.truncate(recommendation::LIVE_CANDIDATES - 1);
+    Ok(Recommendation {
+        live: refreshed.contains(&best.email),
+        all_cooling_down: cooling_down.contains_key(&best.email),
+        email: best.email,
+        score: best.score,
+        alternatives: ranked,
+    })
+}
+
+/// Side-by-side data for `emails`, optionally refreshed from the API first at
+/// most `comparison::MAX_CONCURRENT_REFRESHES` at a time. Rows that fail to
+/// refresh keep their stored data and carry the error.
+pub fn compare_accounts(
+    store: &dyn AccountStore,
+    emails: &[String],
+    live: bool,
+    api: &dyn CursorApi,
+) -> Result<Vec<AccountComparison>> {
+    let mut accounts = store.read_accounts()?;
+    let wanted = comparison::dedup_emails(emails);
+
+    let mut outcomes: Vec<(String, Result<()>)> = Vec::new();
+    if live {
+        let mut targets: Vec<&mut Account> = accounts
+            .iter_mut()
+            .filter(|a| wanted.contains(&a.email))
+            .collect();
+        for batch in targets.chunks_mut(comparison::MAX_CONCURRENT_REFRESHES) {
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .iter_mut()
+                    .map(|account| {
+                        let email = account.email.clone();
+                        let handle = scope.spawn(move || refresh_account_info(api, account));
+                        (email, handle)
+                    })
+                    .collect();
+                for (email, handle) in handles {
+                    let result = handle
+                        .join()
+                        .unwrap_or_else(|_| Err(anyhow::anyhow!("Refresh panicked")));
+                    outcomes.push((email, result));
+                }
+            });
+        }
+        let refreshed: Vec<&String> = outcomes
+            .iter()
+            .filter(|(_, result)| result.is_ok())
+            .map(|(email, _)| email)
+            .collect();
+        if !refreshed.is_empty() {
+            store_refreshed(
+                store,
+                accounts.iter().filter(|a| refreshed.contains(&&a.email)),
+            )?;
+        }
+    }
+
+    let mut rows = comparison::compare(&accounts, &wanted, chrono::Local::now().naive_local());
+    for (email, result) in outcomes {
+        let Some(row) = rows.iter_mut().find(|row| row.email == email) else {
+            continue;
+        };
+        match result {
+            Ok(()) => row.refreshed = true,
+            Err(e) => {
+                tracing::warn!("Failed to refresh {} for comparison: {}", email, e);
+                row.refresh_error = Some(redaction::scrub_secrets(&e.to_string()));
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// What `sync_current_account` did
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum SyncOutcome {
+    /// The logged-in account wasn't stored yet and was added
+    Added {
+        account: Account,
+        info_fetched: bool,
+    },
+    /// The stored row of the logged-in account got its tokens refreshed
+    Updated {
+        account: Account,
+        info_fetched: bool,
+    },
+    /// Cursor has no account logged in
+    NothingLoggedIn,
+}
+
+/// Copy the account currently logged into Cursor into the CSV, optionally
+/// fetching its membership and usage data so the row is complete right away
+pub fn sync_current_account(
+    base_path: &Path,
+    store: &dyn AccountStore,
+    fetch_info: bool,
+    api: &dyn CursorApi,
+) -> Result<SyncOutcome> {
+    // Get current account from Cursor's database
+    let db_path = PathDetector::get_db_path(base_path);
+    let db = Database::new(db_path);
+
+    let (email, access_token) = match db.get_auth_info() {
+        Ok(info) => info,
+        Err(e) => {
+            tracing::info!("No account logged into Cursor: {}", e);
+            return Ok(SyncOutcome::NothingLoggedIn);
+        }
+    };
+    let stored_refresh_token = db.get_refresh_token().unwrap_or_else(|e| {
+        tracing::warn!("Failed to read refresh token: {}", e);
+        None
+    });
+
+    // Read existing accounts
+    let accounts = store.read_accounts()?;
+    let existing = accounts.iter().position(|a| a.email == email);
+
+    let mut account = match existing {
+        Some(position) => {
+            // Update tokens but preserve source; keep a distinct refresh token
+            // unless Cursor has a newer one
+            let mut account = accounts[position].clone();
+            account.refresh_token = match stored_refresh_token {
+                Some(refresh_token) => refresh_token.into(),
+                None if account.refresh_token.is_empty() => access_token.clone().into(),
+                None => account.refresh_token,
+            };
+            account.access_token = access_token.into();
+            account.record_time = chrono::Local::now().format(record_time::FORMAT).to_string();
+            account
+        }
+        None => Account {
+            index: accounts.iter().map(|a| a.index).max().unwrap_or(0) + 1,
+            email: email.clone(),
+            refresh_token: stored_refresh_token
+                .unwrap_or_else(|| access_token.clone())
+                .into(),
+            access_token: access_token.into(),
+            record_time: chrono::Local::now().format(record_time::FORMAT).to_string(),
+            // Accounts found this way were logged in through the browser
+            source: AccountSource::WebLogin,
+            ..Default::default()
+        },
+    };
+
+    // The account is logged into Cursor, so it is in use right now
+    account.last_used = chrono::Local::now().format(record_time::FORMAT).to_string();
+
+    let info_fetched = fetch_info && {
+        match refresh_account_info(api, &mut account) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Failed to fetch account info for {}: {}", email, e);
+                false
+            }
+        }
+    };
+
+    // The file may have changed while the account info was fetched
+    let added = store.modify(
+        |accounts| match accounts.iter().position(|a| a.email == email) {
+            Some(position) => {
+                accounts[position] = account.clone();
+                Ok(false)
+            }
+            None => {
+                account.index = accounts.iter().map(|a| a.index).max().unwrap_or(0) + 1;
+                accounts.push(account.clone());
+                Ok(true)
+            }
+        },
+    )?;
+
+    if added {
+        tracing::info!("Added current account: {}", email);
+        Ok(SyncOutcome::Added {
+            account,
+            info_fetched,
+        })
+    } else {
+        tracing::info!("Synced current account: {}", email);
+        Ok(SyncOutcome::Updated {
+            account,
+            info_fetched,
+        })
+    }
+}
+
+/// The session cookie the dashboard requests send, and the email of the
+/// account logged into Cursor if there is one. Cursor's own cookie comes
+/// first, then the one stored with that account.
+pub fn dashboard_session(
+    db: &Database,
+    store: &dyn AccountStore,
+) -> Result<(String, Option<String>)> {
+    let email = db.get_auth_info().ok().map(|(email, _)| email);
+    match db.get_session_token() {
+        Ok(token) => return Ok((token, email)),
+        Err(e) if e.is::<SessionTokenNotFound>() => {}
+        Err(e) => tracing::warn!("Failed to read Cursor's session token: {:#}", e),
+    }
+
+    let stored = match &email {
+        Some(email) => store.find_account_by_email(email)?,
+        None => None,
+    };
+    match stored
+        .as_ref()
+        .and_then(|account| clipboard_watcher::normalize_candidate(account.cookie.expose()))
+    {
+        Some(cookie) => Ok((cookie.to_string(), email)),
+        None => bail!(
+            "No session token for {}. Log into cursor.com once, \
+             or import the account's WorkosCursorSessionToken cookie",
+            email.as_deref().unwrap_or("the current account")
+        ),
+    }
+}
+
+/// Get fresh tokens for the account logged into Cursor from its session token
+/// and store them in both the CSV and Cursor's database. `exchange` turns a
+/// session token into an account, normally `TokenAuthClient::convert_token_to_account`.
+pub fn reauthenticate_current_account(
+    base_path: &Path,
+    store: &dyn AccountStore,
+    exchange: impl FnOnce(&str) -> Result<Account>,
+) -> Result<Account> {
+    let db = Database::new(PathDetector::get_db_path(base_path));
+    let (email, _) = db
+        .get_auth_info()
+        .context("No account is logged into Cursor")?;
+
+    let stored = store
+        .find_account_by_email(&email)?
+        .with_context(|| format!("Account not found: {}", email))?;
+
+    // The stored session cookie outlives the access token; Cursor keeps one too
+    let session_token = match stored.cookie.expose() {
+        "" => db.get_session_token()?,
+        cookie => cookie.to_string(),
+    };
+    let fresh = exchange(&session_token).context("Failed to re-authenticate")?;
+    if fresh.email != email {
+        bail!(
+            "The session belongs to {} instead of {}",
+            fresh.email,
+            email
+        );
+    }
+
+    let account = store.modify(|accounts| {
+        let account = accounts
+            .iter_mut()
+            .find(|a| a.email == email)
+            .with_context(|| format!("Account not found: {}", email))?;
+        account.access_token = fresh.access_token;
+        account.refresh_token = fresh.refresh_token;
+        account.cookie = fresh.cookie;
+        account.record_time = chrono::Local::now().format(record_time::FORMAT).to_string();
+        Ok(account.clone())
+    })?;
+
+    db.update_auth(
+        &email,
+        account.access_token.expose(),
+        Some(account.refresh_token.expose()),
+    )?;
+    tracing::info!("Re-authenticated current account: {}", email);
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_manager::CsvManager;
+    use crate::test_support::{account, MockCursorApi, MockResponse, MOCK_USAGE};
+    use rusqlite::Connection;
+
+    /// Cursor is never running, so nothing needs to be closed
+    struct NoCursor;
+
+    impl CursorProcess for NoCursor {
+        fn is_running(&self) -> bool {
+            false
+        }
+
+        fn kill(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn restart(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn setup_test_env() -> (tempfile::TempDir, std::path::PathBuf, CsvManager) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_path = temp_dir.path().to_path_buf();
+        let csv_path = temp_dir.path().join("accounts.csv");
+
+        let conn = Connection::open(PathDetector::get_db_path(&base_path)).unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ItemTable (key TEXT PRIMARY KEY, value TEXT)",
+            [],
+        )
+        .unwrap();
+
+        let store = CsvManager::new(csv_path);
+        store.ensure_csv_exists().unwrap();
+        (temp_dir, base_path, store)
+    }
+
+    #[test]
+    fn test_sync_current_account_adds_web_login() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+        Database::new(PathDetector::get_db_path(&base_path))
+            .update_auth("web@example.com", "web_token", None)
+            .unwrap();
+
+        let outcome =
+            sync_current_account(&base_path, &store, false, &MockCursorApi::new()).unwrap();
+        assert!(matches!(
+            outcome,
+            SyncOutcome::Added {
+                info_fetched: false,
+                ..
+            }
+        ));
+
+        let accounts = list_accounts(&store).unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].index, 1);
+        assert_eq!(accounts[0].refresh_token.expose(), "web_token");
+        assert_eq!(accounts[0].email, "web@example.com");
+        assert_eq!(accounts[0].source, AccountSource::WebLogin);
+    }
+
+    #[test]
+    fn test_reauthenticate_current_account() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+        let db = Database::new(PathDetector::get_db_path(&base_path));
+        db.update_auth("a@x.com", "expired_access", None).unwrap();
+        let stored = Account {
+            email: "a@x.com".to_string(),
+            access_token: "expired_access".into(),
+            refresh_token: "old_refresh".into(),
+            cookie: "user_1::session".into(),
+            status: MembershipStatus::Pro,
+            ..Default::default()
+        };
+        store.add_account(stored.clone()).unwrap();
+
+        let account = reauthenticate_current_account(&base_path, &store, |session| {
+            assert_eq!(session, "user_1::session");
+            Ok(Account {
+                access_token: "fresh_access".into(),
+                refresh_token: "fresh_refresh".into(),
+                ..stored.clone()
+            })
+        })
+        .unwrap();
+        assert_eq!(account.status, MembershipStatus::Pro);
+        assert_eq!(db.get_auth_info().unwrap().1, "fresh_access");
+        assert_eq!(
+            list_accounts(&store).unwrap()[0].refresh_token,
+            "fresh_refresh".into()
+        );
+
+        // A session that resolves to someone else must not overwrite the login
+        let result = reauthenticate_current_account(&base_path, &store, |_| {
+            Ok(Account {
+                email: "b@x.com".to_string(),
+                ..stored.clone()
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(db.get_auth_info().unwrap().1, "fresh_access");
+    }
+
+    #[test]
+    fn test_sync_current_account_without_login() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+
+        assert!(matches!(
+            sync_current_account(&base_path, &store, false, &MockCursorApi::new()).unwrap(),
+            SyncOutcome::NothingLoggedIn
+        ));
+        assert!(list_accounts(&store).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sync_current_account_keeps_distinct_refresh_token() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+        Database::new(PathDetector::get_db_path(&base_path))
+            .update_auth("web@example.com", "new_access", None)
+            .unwrap();
+        store
+            .add_account(Account {
+                email: "web@example.com".to_string(),
+                access_token: "old_access".into(),
+                refresh_token: "stored_refresh".into(),
+                status: MembershipStatus::Pro,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let outcome =
+            sync_current_account(&base_path, &store, false, &MockCursorApi::new()).unwrap();
+        assert!(matches!(outcome, SyncOutcome::Updated { .. }));
+
+        let accounts = list_accounts(&store).unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].access_token.expose(), "new_access");
+        assert_eq!(accounts[0].refresh_token.expose(), "stored_refresh");
+        assert_eq!(accounts[0].source, AccountSource::Imported);
+    }
+
+    #[test]
+    fn test_switch_to_unknown_account_fails() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+
+        let result = switch_to_stored_account(
+            &base_path,
+            &store,
+            "missing@example.com",
+            &SwitchOptions::default(),
+            &IntentJournal::new(base_path.join("pending_operation.json")),
+            &MockCursorApi::new(),
+            &NoCursor,
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Account not found"));
+    }
+
+    #[test]
+    fn test_switch_stamps_last_used() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+        store
+            .batch_add_accounts(vec![
+                Account {
+                    access_token: "token_a".into(),
+                    ..account("a@x.com")
+                },
+                Account {
+                    access_token: "token_b".into(),
+                    ..account("b@x.com")
+                },
+            ])
+            .unwrap();
+
+        switch_to_stored_account(
+            &base_path,
+            &store,
+            "b@x.com",
+            &SwitchOptions::default(),
+            &IntentJournal::new(base_path.join("pending_operation.json")),
+            &MockCursorApi::new(),
+            &NoCursor,
+        )
+        .unwrap();
+
+        let accounts = list_accounts(&store).unwrap();
+        assert_eq!(accounts[0].last_used, "");
+        assert!(
+            chrono::NaiveDateTime::parse_from_str(&accounts[1].last_used, record_time::FORMAT)
+                .is_ok()
+        );
+        assert!(!mark_used(&store, "missing@x.com", "2024-01-01 00:00:00").unwrap());
+    }
+
+    #[test]
+    fn test_only_switches_that_went_through_are_logged() {
+        let (temp_dir, base_path, store) = setup_test_env();
+        store
+            .batch_add_accounts(vec![Account {
+                access_token: "token_a".into(),
+                ..account("a@x.com")
+            }])
+            .unwrap();
+        let log = SwitchLog::new(temp_dir.path().join(crate::switch_log::SWITCH_LOG_FILE));
+        let options = SwitchOptions::default();
+        let switch = |email: &str| {
+            switch_to_stored_account(
+                &base_path,
+                &store,
+                email,
+                &options,
+                &IntentJournal::new(base_path.join("pending_operation.json")),
+                &MockCursorApi::new(),
+                &NoCursor,
+            )
+        };
+
+        let failed = switch("missing@x.com");
+        assert!(!log_switch(&log, &failed, &options, SwitchSource::Window, 10).unwrap());
+        assert!(log.recent(10).unwrap().is_empty());
+
+        let switched = switch("a@x.com");
+        assert!(log_switch(&log, &switched, &options, SwitchSource::Tray, 10).unwrap());
+        let entries = log.recent(10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].email, "a@x.com");
+        assert!(!entries[0].reset_machine);
+        assert_eq!(entries[0].source, SwitchSource::Tray);
+    }
+
+    #[test]
+    fn test_run_switch_refuses_accounts_cooling_down() {
+        let (temp_dir, base_path, store) = setup_test_env();
+        store
+            .batch_add_accounts(vec![Account {
+                access_token: "token_a".into(),
+                ..account("a@x.com")
+            }])
+            .unwrap();
+        let mut settings = Settings {
+            cooldown_mode: CooldownMode::Enforce,
+            ..Settings::default()
+        };
+        settings.switch_hooks.post = Some(SwitchHook {
+            path: "/bin/true".to_string(),
+            args: Vec::new(),
+            timeout_secs: 5,
+            blocking: false,
+        });
+        let history = SwitchHistoryStore::new(temp_dir.path().join("switch_history.json"));
+        history.record(&["a@x.com"], chrono::Utc::now()).unwrap();
+        let log = SwitchLog::new(temp_dir.path().join(crate::switch_log::SWITCH_LOG_FILE));
+        let hooks_run = std::cell::RefCell::new(Vec::new());
+        let spawn_hook = |name: &'static str, _: SwitchHook, context: HookContext| {
+            hooks_run.borrow_mut().push((name, context.email));
+        };
+        let journal = IntentJournal::new(base_path.join("pending_operation.json"));
+        let context = SwitchContext {
+            settings: &settings,
+            journal: &journal,
+            history: &history,
+            log: &log,
+            source: SwitchSource::Cli,
+            spawn_hook: &spawn_hook,
+        };
+        let switch = || {
+            run_switch(
+                &base_path,
+                &store,
+                "a@x.com",
+                &SwitchOptions::default(),
+                &MockCursorApi::new(),
+                &NoCursor,
+                &context,
+            )
+        };
+
+        let error = switch().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<CoolingDown>().unwrap().email,
+            "a@x.com"
+        );
+        assert!(log.recent(10).unwrap().is_empty());
+        assert!(hooks_run.borrow().is_empty());
+        assert!(Database::new(PathDetector::get_db_path(&base_path))
+            .get_auth_info()
+            .is_err());
+
+        // Warned about only, the switch goes through and is recorded
+        let settings = Settings {
+            cooldown_mode: CooldownMode::Warn,
+            ..settings.clone()
+        };
+        let context = SwitchContext {
+            settings: &settings,
+            ..context
+        };
+        let outcome = run_switch(
+            &base_path,
+            &store,
+            "a@x.com",
+            &SwitchOptions::default(),
+            &MockCursorApi::new(),
+            &NoCursor,
+            &context,
+        )
+        .unwrap();
+        assert_eq!(outcome.unwrap().email, "a@x.com");
+        let entries = log.recent(10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].source, SwitchSource::Cli);
+        assert_eq!(
+            *hooks_run.borrow(),
+            vec![("post-switch", "a@x.com".to_string())]
+        );
+        assert!(cooldown_left(&history, &settings, "a@x.com").is_some());
+    }
+
+    #[test]
+    fn test_resolve_pending_accounts() {
+        let (_temp_dir, _base_path, store) = setup_test_env();
+        let pending = |user_id: &str| Account {
+            cookie: format!("{}::jwt", user_id).into(),
+            source: AccountSource::CookieImport,
+            access_token: "jwt".into(),
+            ..account(&csv_manager::pending_email(user_id))
+        };
+        store
+            .batch_add_accounts(vec![
+                Account {
+                    access_token: "old_token".into(),
+                    ..account("known@x.com")
+                },
+                pending("user_new"),
+                pending("user_known"),
+                pending("user_refused"),
+            ])
+            .unwrap();
+
+        let exchange = |cookie: &str| -> Result<Account> {
+            let email = match cookie {
+                "user_new::jwt" => "new@x.com",
+                "user_known::jwt" => "Known@x.com",
+                _ => bail!("Unauthorized"),
+            };
+            Ok(Account {
+                cookie: cookie.into(),
+                access_token: format!("{}_token", cookie).into(),
+                ..account(email)
+            })
+        };
+        let resolution = resolve_pending(&store, &exchange).unwrap();
+        assert_eq!(resolution.resolved, vec!["new@x.com", "Known@x.com"]);
+        assert_eq!(
+            resolution.failed,
+            vec![csv_manager::pending_email("user_refused")]
+        );
+
+        let accounts = list_accounts(&store).unwrap();
+        let rows: Vec<(i32, &str, &str)> = accounts
+            .iter()
+            .map(|a| (a.index, a.email.as_str(), a.access_token.expose()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (1, "known@x.com", "user_known::jwt_token"),
+                (2, "new@x.com", "user_new::jwt_token"),
+                (3, "pending+user_refused@unknown", "jwt"),
+            ]
+        );
+        assert_eq!(accounts[1].source, AccountSource::CookieImport);
+    }
+
+    #[test]
+    fn test_plan_switch_reports_problems() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+        Database::new(PathDetector::get_db_path(&base_path))
+            .update_auth("current@example.com", "current_token", None)
+            .unwrap();
+        let account = |email: &str, access_token: &str, status: &str| Account {
+            email: email.to_string(),
+            access_token: access_token.into(),
+            status: MembershipStatus::from(status),
+            ..Default::default()
+        };
+        let csv_manager = &store;
+        csv_manager
+            .batch_add_accounts(vec![
+                account("good@example.com", "token", "pro"),
+                account("banned@example.com", "token", "banned"),
+                account("empty@example.com", "", "pro"),
+            ])
+            .unwrap();
+        let options = SwitchOptions {
+            reset_machine: true,
+            restart: false,
+            validate_token: true,
+            preserve_settings: false,
+            state_db_backup_retention: 0,
+        };
+
+        // Without storage.json the machine ID reset would abort
+        let plan =
+            plan_switch(&base_path, &store, "good@example.com", &options, &NoCursor).unwrap();
+        assert!(!plan.can_switch);
+        assert!(matches!(
+            plan.problems[..],
+            [
+                SwitchProblem::ResetCheckFailed {
+                    check: ResetCheckKind::DiskSpace,
+                    ..
+                },
+                SwitchProblem::ResetCheckFailed {
+                    check: ResetCheckKind::Writable,
+                    ..
+                }
+            ]
+        ));
+
+        std::fs::write(PathDetector::get_storage_path(&base_path), "{}").unwrap();
+        let plan =
+            plan_switch(&base_path, &store, "good@example.com", &options, &NoCursor).unwrap();
+        assert_eq!(plan.current_email.as_deref(), Some("current@example.com"));
+        assert!(plan.can_switch && plan.problems.is_empty());
+        assert!(plan.reset_checks.iter().all(|check| check.passed));
+        // Tokens stay in the backend
+        assert!(!serde_json::to_string(&plan).unwrap().contains("\"token\""));
+        assert_eq!(
+            plan.storage_path,
+            Some(PathDetector::get_storage_path(&base_path))
+        );
+
+        let plan = plan_switch(
+            &base_path,
+            &store,
+            "banned@example.com",
+            &options,
+            &NoCursor,
+        )
+        .unwrap();
+        assert!(plan.can_switch);
+        assert!(matches!(
+            plan.problems[..],
+            [SwitchProblem::Inactive { .. }]
+        ));
+
+        let plan =
+            plan_switch(&base_path, &store, "empty@example.com", &options, &NoCursor).unwrap();
+        assert!(!plan.can_switch);
+        assert!(execute_switch(
+            &base_path,
+            plan,
+            &IntentJournal::new(base_path.join("pending_operation.json")),
+            &MockCursorApi::new(),
+            &NoCursor
+        )
+        .is_err());
+
+        let plan = plan_switch(
+            &base_path,
+            &store,
+            "missing@example.com",
+            &options,
+            &NoCursor,
+        )
+        .unwrap();
+        assert_eq!(plan.problems, vec![SwitchProblem::AccountNotFound]);
+
+        // Planning never writes anything
+        let (email, _) = Database::new(PathDetector::get_db_path(&base_path))
+            .get_auth_info()
+            .unwrap();
+        assert_eq!(email, "current@example.com");
+    }
+
+    #[test]
+    fn test_rejected_token_leaves_cursor_untouched() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+        let db = Database::new(PathDetector::get_db_path(&base_path));
+        db.update_auth("current@example.com", "current_token", None)
+            .unwrap();
+        store
+            .add_account(Account {
+                email: "dead@example.com".to_string(),
+                access_token: "dead_token".into(),
+                status: MembershipStatus::Pro,
+                ..Default::default()
+            })
+            .unwrap();
+        let options = SwitchOptions {
+            validate_token: true,
+            ..SwitchOptions::default()
+        };
+
+        let journal = IntentJournal::new(base_path.join("pending_operation.json"));
+        let plan =
+            plan_switch(&base_path, &store, "dead@example.com", &options, &NoCursor).unwrap();
+        let api = MockCursorApi::new().script(
+            "dead@example.com",
+            "dead_token",
+            [MockResponse::Unauthorized],
+        );
+        let error = execute_switch(&base_path, plan, &journal, &api, &NoCursor).unwrap_err();
+        assert!(error.to_string().contains("invalid or expired"));
+
+        let plan =
+            plan_switch(&base_path, &store, "dead@example.com", &options, &NoCursor).unwrap();
+        let api =
+            MockCursorApi::new().script("dead@example.com", "dead_token", [MockResponse::Offline]);
+        let error = execute_switch(&base_path, plan, &journal, &api, &NoCursor).unwrap_err();
+        assert!(error.to_string().contains("Could not verify"));
+
+        assert_eq!(db.get_auth_info().unwrap().0, "current@example.com");
+        // Nothing was started, so nothing is left to resume
+        assert_eq!(journal.pending().unwrap(), None);
+    }
+
+    #[test]
+    fn test_dashboard_session_falls_back_to_the_stored_cookie() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+        let db = Database::new(PathDetector::get_db_path(&base_path));
+
+        // Nobody logged in and no cookie anywhere
+        let error = dashboard_session(&db, &store).unwrap_err().to_string();
+        assert!(error.contains("Log into cursor.com once"), "{}", error);
+
+        db.update_auth("a@x.com", "access", None).unwrap();
+        let error = dashboard_session(&db, &store).unwrap_err().to_string();
+        assert!(
+            error.starts_with("No session token for a@x.com"),
+            "{}",
+            error
+        );
+
+        // The stored cookie, without its name, when Cursor has none
+        store
+            .add_account(Account {
+                cookie: "WorkosCursorSessionToken=user_1::stored".into(),
+                access_token: "access".into(),
+                ..account("a@x.com")
+            })
+            .unwrap();
+        assert_eq!(
+            dashboard_session(&db, &store).unwrap(),
+            ("user_1::stored".to_string(), Some("a@x.com".to_string()))
+        );
+
+        // Cursor's own cookie wins
+        db.update_auth_full("a@x.com", "access", None, Some("user_1::cursor"))
+            .unwrap();
+        assert_eq!(dashboard_session(&db, &store).unwrap().0, "user_1::cursor");
+    }
+
+    const NO_WAIT: ApiPolicy = ApiPolicy {
+        timeout_secs: 30,
+        max_retries: 2,
+        backoff_base_ms: 0,
+        max_concurrency: 1,
+        min_request_spacing_ms: 0,
+    };
+
+    #[test]
+    fn test_archive_restore_and_delete() {
+        let (_temp_dir, _base_path, store) = setup_test_env();
+        store
+            .batch_add_accounts(vec![
+                Account {
+                    access_token: "token_a".into(),
+                    ..account("a@x.com")
+                },
+                Account {
+                    access_token: "token_b".into(),
+                    ..account("b@x.com")
+                },
+            ])
+            .unwrap();
+        let api = MockCursorApi::new()
+            .script("a@x.com", "token_a", [MockResponse::pro()])
+            .script("b@x.com", "token_b", [MockResponse::pro()]);
+
+        assert!(set_archived(&store, "a@x.com", true).unwrap());
+        assert!(!set_archived(&store, "a@x.com", true).unwrap());
+        let refresh = batch_refresh(&store, &api, &NO_WAIT, &|_| true).unwrap();
+        let refreshed: Vec<&str> = refresh.accounts.iter().map(|a| a.email.as_str()).collect();
+        assert_eq!(refreshed, vec!["b@x.com"]);
+        assert_eq!(api.calls("a@x.com"), 0);
+        let archived = store.find_account_by_email("a@x.com").unwrap().unwrap();
+        assert!(archived.archived);
+        assert_eq!(archived.access_token.expose(), "token_a");
+
+        assert!(set_archived(&store, "a@x.com", false).unwrap());
+        assert!(!list_accounts(&store).unwrap()[0].archived);
+
+        assert!(store.delete_account("a@x.com").unwrap());
+        let emails: Vec<String> = list_accounts(&store)
+            .unwrap()
+            .into_iter()
+            .map(|a| a.email)
+            .collect();
+        assert_eq!(emails, vec!["b@x.com"]);
+        assert!(!set_archived(&store, "a@x.com", true).unwrap());
+    }
+
+    #[test]
+    fn test_batch_update_keeps_going_after_failures() {
+        let (_temp_dir, _base_path, store) = setup_test_env();
+        store
+            .batch_add_accounts(vec![
+                Account {
+                    access_token: "token_a".into(),
+                    ..account("a@x.com")
+                },
+                Account {
+                    access_token: "token_offline".into(),
+                    ..account("offline@x.com")
+                },
+                Account {
+                    access_token: "token_expired".into(),
+                    ..account("expired@x.com")
+                },
+                Account {
+                    access_token: "token_c".into(),
+                    ..account("c@x.com")
+                },
+            ])
+            .unwrap();
+        let api = MockCursorApi::new()
+            .script("a@x.com", "token_a", [MockResponse::pro()])
+            .script("offline@x.com", "token_offline", [MockResponse::Offline])
+            .script(
+                "expired@x.com",
+                "token_expired",
+                [MockResponse::Unauthorized],
+            )
+            .script(
+                "c@x.com",
+                "token_c",
+                [MockResponse::Account {
+                    status: MembershipStatus::FreeTrial,
+                    days_remaining: 6.0,
+                }],
+            );
+
+        let refresh = batch_refresh(&store, &api, &NO_WAIT, &|_| true).unwrap();
+        assert_eq!(refresh.failed, vec!["expired@x.com", "offline@x.com"]);
+
+        let accounts = list_accounts(&store).unwrap();
+        assert_eq!(accounts[0].status, MembershipStatus::Pro);
+        assert_eq!(accounts[0].usage_remaining, Some(MOCK_USAGE.remaining));
+        assert_eq!(accounts[1].status, MembershipStatus::Error);
+        assert_eq!(accounts[2].status, MembershipStatus::Expired);
+        assert_eq!(accounts[3].status, MembershipStatus::FreeTrial);
+        assert_eq!(accounts[3].days_remaining, Some(6.0));
+        // Failures are not retried unless rate limited
+        assert_eq!(api.calls("offline@x.com"), 1);
+        assert_eq!(api.calls("expired@x.com"), 1);
+    }
+
+    #[test]
+    fn test_batch_update_of_one_pool() {
+        let (_temp_dir, _base_path, store) = setup_test_env();
+        let work = Account {
+            pool: Some("work".to_string()),
+            access_token: "token_w".into(),
+            ..account("w@x.com")
+        };
+        store
+            .batch_add_accounts(vec![
+                work,
+                Account {
+                    access_token: "token_a".into(),
+                    ..account("a@x.com")
+                },
+            ])
+            .unwrap();
+        let api = MockCursorApi::new()
+            .script("w@x.com", "token_w", [MockResponse::pro()])
+            .script("a@x.com", "token_a", [MockResponse::pro()]);
+
+        let refresh =
+            batch_refresh(&store, &api, &NO_WAIT, &|a| pools::in_pool(a, Some("work"))).unwrap();
+        assert_eq!(refresh.accounts.len(), 1);
+        assert_eq!(refresh.accounts[0].email, "w@x.com");
+        assert_eq!(api.calls("a@x.com"), 0);
+        // Accounts outside the pool are written back untouched
+        assert_eq!(list_accounts(&store).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_batch_update_of_stale_accounts() {
+        let (_temp_dir, _base_path, store) = setup_test_env();
+        let now = chrono::Local::now().naive_local();
+        let at = |hours: i64| {
+            (now - chrono::Duration::hours(hours))
+                .format(record_time::FORMAT)
+                .to_string()
+        };
+        let fresh = Account {
+            record_time: at(1),
+            access_token: "token_f".into(),
+            ..account("fresh@x.com")
+        };
+        let stale = Account {
+            record_time: at(30),
+            access_token: "token_s".into(),
+            ..account("stale@x.com")
+        };
+        let banned = Account {
+            record_time: at(30),
+            status: MembershipStatus::Banned,
+            access_token: "token_b".into(),
+            ..account("banned@x.com")
+        };
+        let never = Account {
+            access_token: "token_n".into(),
+            ..account("never@x.com")
+        };
+        store
+            .batch_add_accounts(vec![fresh, stale, banned, never])
+            .unwrap();
+        let api = MockCursorApi::new()
+            .script("stale@x.com", "token_s", [MockResponse::pro()])
+            .script("never@x.com", "token_n", [MockResponse::pro()]);
+
+        let refresh =
+            batch_refresh(&store, &api, &NO_WAIT, &|a| is_refresh_due(a, 24, now)).unwrap();
+        let refreshed: Vec<&str> = refresh.accounts.iter().map(|a| a.email.as_str()).collect();
+        assert_eq!(refreshed, vec!["stale@x.com", "never@x.com"]);
+        assert_eq!(api.calls("fresh@x.com"), 0);
+        assert_eq!(api.calls("banned@x.com"), 0);
+        assert!(refresh.failed.is_empty());
+    }
+
+    /// Adds and edits accounts mid-refresh, like the tray or the window would
+    struct EditingApi<'a> {
+        api: MockCursorApi,
+        store: &'a CsvManager,
+    }
+
+    impl CursorApi for EditingApi<'_> {
+        fn get_account_info(
+            &self,
+            email: &str,
+            access_token: &str,
+            extra: &reqwest::header::HeaderMap,
+        ) -> Result<crate::types::AccountInfo> {
+            let csv_manager = self.store;
+            csv_manager.add_account(Account {
+                access_token: "token".into(),
+                ..account(&format!("added-{}", email))
+            })?;
+            let mut account = csv_manager.find_account_by_email(email)?.unwrap();
+            account.label_emoji = Some("🐱".to_string());
+            csv_manager.update_account(email, account)?;
+            self.api.get_account_info(email, access_token, extra)
+        }
+
+        fn get_usage_info(
+            &self,
+            access_token: &str,
+            extra: &reqwest::header::HeaderMap,
+        ) -> Result<crate::types::UsageInfo> {
+            self.api.get_usage_info(access_token, extra)
+        }
+
+        fn get_email(&self, access_token: &str) -> Result<String> {
+            self.api.get_email(access_token)
+        }
+
+        fn check_access_token(&self, access_token: &str) -> TokenCheck {
+            self.api.check_access_token(access_token)
+        }
+    }
+
+    #[test]
+    fn test_batch_update_keeps_writes_made_while_it_runs() {
+        let (_temp_dir, _base_path, store) = setup_test_env();
+        store
+            .batch_add_accounts(vec![
+                Account {
+                    access_token: "token_a".into(),
+                    ..account("a@x.com")
+                },
+                Account {
+                    access_token: "token_b".into(),
+                    ..account("b@x.com")
+                },
+            ])
+            .unwrap();
+        let api = EditingApi {
+            api: MockCursorApi::new()
+                .script("a@x.com", "token_a", [MockResponse::pro()])
+                .script("b@x.com", "token_b", [MockResponse::pro()]),
+            store: &store,
+        };
+
+        let refresh = batch_refresh(&store, &api, &NO_WAIT, &|_| true).unwrap();
+        assert!(refresh.failed.is_empty());
+
+        let accounts = store.read_accounts().unwrap();
+        let emails: Vec<&str> = accounts.iter().map(|a| a.email.as_str()).collect();
+        assert_eq!(
+            emails,
+            vec!["a@x.com", "b@x.com", "added-a@x.com", "added-b@x.com"]
+        );
+        for account in &accounts[..2] {
+            assert_eq!(account.status, MembershipStatus::Pro);
+            assert_eq!(account.label_emoji.as_deref(), Some("🐱"));
+        }
+    }
+
+    #[test]
+    fn test_update_account_info_keeps_writes_made_while_it_runs() {
+        let (_temp_dir, _base_path, store) = setup_test_env();
+        store
+            .add_account(Account {
+                access_token: "token_a".into(),
+                ..account("a@x.com")
+            })
+            .unwrap();
+        let api = EditingApi {
+            api: MockCursorApi::new().script("a@x.com", "token_a", [MockResponse::pro()]),
+            store: &store,
+        };
+
+        let account = update_account_info(&store, "a@x.com", None, &api).unwrap();
+        assert_eq!(account.status, MembershipStatus::Pro);
+        assert_eq!(account.label_emoji.as_deref(), Some("🐱"));
+
+        let accounts = store.read_accounts().unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].status, MembershipStatus::Pro);
+        assert_eq!(accounts[0].label_emoji.as_deref(), Some("🐱"));
+    }
+
+    #[test]
+    fn test_batch_update_respects_max_concurrency() {
+        let (_temp_dir, _base_path, store) = setup_test_env();
+        let emails: Vec<String> = (0..8).map(|i| format!("user{}@x.com", i)).collect();
+        store
+            .batch_add_accounts(
+                emails
+                    .iter()
+                    .map(|email| Account {
+                        access_token: email.as_str().into(),
+                        ..account(email)
+                    })
+                    .collect(),
+            )
+            .unwrap();
+        let api = emails
+            .iter()
+            .fold(MockCursorApi::new(), |api, email| {
+                api.script(email, email, [MockResponse::pro()])
+            })
+            .with_latency(Duration::from_millis(30));
+
+        let policy = ApiPolicy {
+            max_concurrency: 3,
+            ..NO_WAIT
+        };
+        let accounts = batch_update_all_accounts_with(&store, &api, &policy).unwrap();
+        assert!(accounts.iter().all(|a| a.status == MembershipStatus::Pro));
+        assert!(emails.iter().all(|email| api.calls(email) == 1));
+        let peak = api.peak_in_flight();
+        assert!(peak <= 3, "{} requests ran at once", peak);
+        assert!(peak > 1, "accounts were refreshed one at a time");
+    }
+
+    #[test]
+    fn test_batch_update_retries_rate_limits_up_to_the_limit() {
+        let (_temp_dir, _base_path, store) = setup_test_env();
+        store
+            .batch_add_accounts(vec![
+                Account {
+                    access_token: "token_busy".into(),
+                    ..account("busy@x.com")
+                },
+                Account {
+                    access_token: "token_later".into(),
+                    ..account("later@x.com")
+                },
+            ])
+            .unwrap();
+        let api = MockCursorApi::new()
+            .script("busy@x.com", "token_busy", [MockResponse::RateLimited])
+            .script(
+                "later@x.com",
+                "token_later",
+                [MockResponse::RateLimited, MockResponse::pro()],
+            );
+
+        let accounts = batch_update_all_accounts_with(&store, &api, &NO_WAIT).unwrap();
+        assert_eq!(api.calls("busy@x.com"), 1 + NO_WAIT.max_retries as usize);
+        assert_eq!(accounts[0].status, MembershipStatus::Error);
+        assert_eq!(api.calls("later@x.com"), 2);
+        assert_eq!(accounts[1].status, MembershipStatus::Pro);
+    }
+
+    #[test]
+    fn test_refresh_and_sync_keep_the_nickname() {
+        let (_temp_dir, base_path, store) = setup_test_env();
+        store
+            .add_account(Account {
+                nickname: Some("Main".to_string()),
+                access_token: "token_main".into(),
+                ..account("main@x.com")
+            })
+            .unwrap();
+        let nickname = || {
+            store
+                .find_account_by_email("main@x.com")
+                .unwrap()
+                .unwrap()
+                .nickname
+        };
+
+        let api = MockCursorApi::new().script("main@x.com", "token_main", [MockResponse::pro()]);
+        let accounts = batch_update_all_accounts_with(&store, &api, &NO_WAIT).unwrap();
+        assert_eq!(accounts[0].status, MembershipStatus::Pro);
+        assert_eq!(accounts[0].nickname.as_deref(), Some("Main"));
+        assert_eq!(nickname().as_deref(), Some("Main"));
+
+        Database::new(PathDetector::get_db_path(&base_path))
+            .update_auth("main@x.com", "new_access", None)
+            .unwrap();
+        let outcome =
+            sync_current_account(&base_path, &store, false, &MockCursorApi::new()).unwrap();
+        assert!(matches!(outcome, SyncOutcome::Updated { .. }));
+        assert_eq!(nickname().as_deref(), Some("Main"));
+    }
+}
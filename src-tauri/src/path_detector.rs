@@ -52,6 +52,50 @@ impl PathDetector {
         }
     }
 
+    #[cfg(target_os = "windows")]
+    fn cursor_install_candidates() -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        if let Ok(local) = std::env::var("LOCALAPPDATA") {
+            candidates.push(PathBuf::from(local).join("Programs").join("cursor"));
+        }
+        candidates.push(PathBuf::from(r"C:\Program Files\Cursor"));
+        candidates
+            .into_iter()
+            .map(|dir| dir.join("resources").join("app"))
+            .collect()
+    }
+
+    #[cfg(target_os = "macos")]
+    fn cursor_install_candidates() -> Vec<PathBuf> {
+        vec![PathBuf::from(
+            "/Applications/Cursor.app/Contents/Resources/app",
+        )]
+    }
+
+    #[cfg(target_os = "linux")]
+    fn cursor_install_candidates() -> Vec<PathBuf> {
+        ["/usr/share/cursor", "/opt/Cursor", "/opt/cursor"]
+            .iter()
+            .map(|dir| PathBuf::from(dir).join("resources").join("app"))
+            .collect()
+    }
+
+    /// Version of the installed Cursor app, read from its bundled package.json
+    pub fn detect_cursor_version() -> Option<String> {
+        Self::cursor_install_candidates()
+            .into_iter()
+            .find_map(|dir| Self::read_app_version(&dir))
+    }
+
+    fn read_app_version(app_dir: &Path) -> Option<String> {
+        let content = std::fs::read_to_string(app_dir.join("package.json")).ok()?;
+        let package: serde_json::Value = serde_json::from_str(&content).ok()?;
+        package
+            .get("version")
+            .and_then(|version| version.as_str())
+            .map(str::to_string)
+    }
+
     pub fn get_db_path(base_path: &Path) -> PathBuf {
         base_path.join("state.vscdb")
     }
@@ -60,3 +104,24 @@ impl PathDetector {
         base_path.join("storage.json")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_app_version() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        assert_eq!(PathDetector::read_app_version(temp_dir.path()), None);
+
+        std::fs::write(
+            temp_dir.path().join("package.json"),
+            r#"{"name": "cursor", "version": "0.42.3"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            PathDetector::read_app_version(temp_dir.path()).as_deref(),
+            Some("0.42.3")
+        );
+    }
+}
@@ -0,0 +1,409 @@
+// Signed manifests for sharing a pool of accounts with a team. The manifest is
+// serialized once and signed with ed25519 as those exact bytes, so verifying
+// never depends on re-serializing it. Token fields can also be sealed to a
+// recipient's X25519 key, so only that recipient can read them.
+
+use crate::secret::SecretString;
+use crate::types::{record_time, Account, AccountSource, MembershipStatus};
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+
+/// Marks files written by `export`
+pub const FORMAT: &str = "cursor-account-switcher-pool";
+const VERSION: u32 = 1;
+
+/// A new signing and encryption key pair, base64 encoded
+pub struct GeneratedKeys {
+    pub signing_key: SecretString,
+    pub encryption_key: SecretString,
+}
+
+impl GeneratedKeys {
+    pub fn generate() -> Self {
+        let signing = SigningKey::generate(&mut OsRng);
+        let encryption = crypto_box::SecretKey::generate(&mut OsRng);
+        Self {
+            signing_key: STANDARD.encode(signing.to_bytes()).into(),
+            encryption_key: STANDARD.encode(encryption.to_bytes()).into(),
+        }
+    }
+}
+
+/// The halves of the stored keys that are handed to teammates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PublicKeys {
+    /// Recipients verify manifests against this
+    pub verify_key: String,
+    /// Exporters seal tokens to this
+    pub encryption_key: String,
+}
+
+impl PublicKeys {
+    pub fn of(signing_key: &str, encryption_key: &str) -> Result<Self> {
+        Ok(Self {
+            verify_key: STANDARD.encode(parse_signing_key(signing_key)?.verifying_key().as_bytes()),
+            encryption_key: STANDARD.encode(
+                parse_decryption_key(encryption_key)?
+                    .public_key()
+                    .as_bytes(),
+            ),
+        })
+    }
+}
+
+fn key_bytes<const N: usize>(value: &str, what: &str) -> Result<[u8; N]> {
+    let bytes = STANDARD
+        .decode(value.trim())
+        .with_context(|| format!("{} is not valid base64", what))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("{} must be {} bytes", what, N))
+}
+
+pub fn parse_signing_key(value: &str) -> Result<SigningKey> {
+    Ok(SigningKey::from_bytes(&key_bytes(value, "Signing key")?))
+}
+
+pub fn parse_verify_key(value: &str) -> Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&key_bytes(value, "Verify key")?).context("Verify key is not valid")
+}
+
+pub fn parse_encryption_key(value: &str) -> Result<crypto_box::PublicKey> {
+    Ok(crypto_box::PublicKey::from(key_bytes::<32>(
+        value,
+        "Recipient key",
+    )?))
+}
+
+pub fn parse_decryption_key(value: &str) -> Result<crypto_box::SecretKey> {
+    Ok(crypto_box::SecretKey::from(key_bytes::<32>(
+        value,
+        "Encryption key",
+    )?))
+}
+
+/// The signed part of a manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    exporter: String,
+    created_at: DateTime<Utc>,
+    /// Public key the token fields are sealed to, None when they are in the clear
+    encrypted_to: Option<String>,
+    accounts: Vec<PoolEntry>,
+}
+
+/// What is shared of an account; usage and history stay with the exporter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PoolEntry {
+    email: String,
+    access_token: SecretString,
+    refresh_token: SecretString,
+    cookie: SecretString,
+    label_emoji: Option<String>,
+    label_color: Option<String>,
+}
+
+/// The file: the manifest's exact bytes, who signed them and the signature
+#[derive(Debug, Deserialize, Serialize)]
+struct SignedDocument {
+    format: String,
+    payload: String,
+    signer: Option<String>,
+    signature: Option<String>,
+}
+
+/// Serialize and sign `accounts`, sealing their tokens to `recipient` when given
+pub fn export(
+    accounts: &[Account],
+    exporter: &str,
+    signing_key: &SigningKey,
+    recipient: Option<&crypto_box::PublicKey>,
+    now: DateTime<Utc>,
+) -> Result<String> {
+    let seal = |value: &SecretString| -> Result<SecretString> {
+        match recipient {
+            Some(recipient) => {
+                let sealed = recipient
+                    .seal(&mut OsRng, value.expose().as_bytes())
+                    .map_err(|_| anyhow::anyhow!("Failed to encrypt a token"))?;
+                Ok(STANDARD.encode(sealed).into())
+            }
+            None => Ok(value.clone()),
+        }
+    };
+
+    let entries = accounts
+        .iter()
+        .map(|account| {
+            Ok(PoolEntry {
+                email: account.email.clone(),
+                access_token: seal(&account.access_token)?,
+                refresh_token: seal(&account.refresh_token)?,
+                cookie: seal(&account.cookie)?,
+                label_emoji: account.label_emoji.clone(),
+                label_color: account.label_color.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let manifest = Manifest {
+        version: VERSION,
+        exporter: exporter.to_string(),
+        created_at: now,
+        encrypted_to: recipient.map(|key| STANDARD.encode(key.as_bytes())),
+        accounts: entries,
+    };
+
+    let payload = serde_json::to_string(&manifest)?;
+    let signature = signing_key.sign(payload.as_bytes());
+    let document = SignedDocument {
+        format: FORMAT.to_string(),
+        signer: Some(STANDARD.encode(signing_key.verifying_key().as_bytes())),
+        signature: Some(STANDARD.encode(signature.to_bytes())),
+        payload,
+    };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// A manifest whose signature checked out
+#[derive(Debug)]
+pub struct VerifiedPool {
+    /// Verify key of the signer, base64 encoded
+    pub signer: String,
+    pub exporter: String,
+    pub created_at: DateTime<Utc>,
+    pub accounts: Vec<Account>,
+}
+
+/// Check that `text` was signed by `verify_key` and unpack its accounts.
+/// Sealed tokens need the matching `decryption_key`.
+pub fn import(
+    text: &str,
+    verify_key: &VerifyingKey,
+    decryption_key: Option<&crypto_box::SecretKey>,
+) -> Result<VerifiedPool> {
+    let document: SignedDocument = serde_json::from_str(text)
+        .ok()
+        .filter(|document: &SignedDocument| document.format == FORMAT)
+        .context("Not an account pool manifest")?;
+    let (Some(signer), Some(signature)) = (&document.signer, &document.signature) else {
+        bail!("Manifest is not signed; ask the exporter for a signed copy");
+    };
+
+    let expected_signer = STANDARD.encode(verify_key.as_bytes());
+    if *signer != expected_signer {
+        bail!(
+            "Manifest was signed by {}, not by the expected key {}",
+            signer,
+            expected_signer
+        );
+    }
+    let signature = key_bytes::<64>(signature, "Signature")
+        .map(|bytes| Signature::from_bytes(&bytes))
+        .context("Manifest signature doesn't match")?;
+    verify_key
+        .verify_strict(document.payload.as_bytes(), &signature)
+        .map_err(|_| {
+            anyhow::anyhow!("Manifest signature doesn't match; the file was changed after signing")
+        })?;
+
+    let manifest: Manifest =
+        serde_json::from_str(&document.payload).context("Manifest content is invalid")?;
+    if manifest.version > VERSION {
+        bail!(
+            "Manifest version {} needs a newer version of the app",
+            manifest.version
+        );
+    }
+
+    let decryption_key = match &manifest.encrypted_to {
+        None => None,
+        Some(recipient) => {
+            let key = decryption_key
+                .filter(|key| STANDARD.encode(key.public_key().as_bytes()) == *recipient)
+                .context("Manifest tokens are encrypted to another key")?;
+            Some(key)
+        }
+    };
+    let open = |value: &SecretString| -> Result<SecretString> {
+        let Some(key) = decryption_key else {
+            return Ok(value.clone());
+        };
+        // Empty fields were sealed too, so every field decrypts
+        let sealed = STANDARD
+            .decode(value.expose())
+            .context("Encrypted token is not valid base64")?;
+        let opened = key
+            .unseal(&sealed)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt a token"))?;
+        Ok(String::from_utf8(opened)
+            .context("Decrypted token is not text")?
+            .into())
+    };
+
+    let record_time = chrono::Local::now().format(record_time::FORMAT).to_string();
+    let accounts = manifest
+        .accounts
+        .iter()
+        .map(|entry| {
+            Ok(Account {
+                index: 0,
+                email: entry.email.clone(),
+                access_token: open(&entry.access_token)?,
+                refresh_token: open(&entry.refresh_token)?,
+                cookie: open(&entry.cookie)?,
+                days_remaining: None,
+                status: MembershipStatus::Unknown,
+                record_time: record_time.clone(),
+                source: AccountSource::Imported,
+                usage_used: None,
+                usage_remaining: None,
+                usage_total: None,
+                usage_percentage: None,
+                label_emoji: entry.label_emoji.clone(),
+                label_color: entry.label_color.clone(),
+                privacy_mode: None,
+                extra_headers: None,
+                pool: None,
+                status_reason: None,
+                tags: Vec::new(),
+                favorite: false,
+                last_used: String::new(),
+                archived: false,
+                password: None,
+                nickname: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(VerifiedPool {
+        signer: signer.clone(),
+        exporter: manifest.exporter,
+        created_at: manifest.created_at,
+        accounts,
+    })
+}
+
+/// Returned by `import_pool_manifest`
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolImportReport {
+    pub signer: String,
+    pub exporter: String,
+    pub created_at: DateTime<Utc>,
+    pub added: Vec<String>,
+    /// Already stored, so left as they are
+    pub skipped: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::Value;
+
+    fn account(email: &str) -> Account {
+        Account {
+            index: 3,
+            days_remaining: Some(5.0),
+            status: MembershipStatus::Pro,
+            source: AccountSource::WebLogin,
+            usage_used: Some(1.0),
+            usage_remaining: Some(2.0),
+            usage_total: Some(3.0),
+            usage_percentage: Some(33.0),
+            label_emoji: Some("🚀".to_string()),
+            privacy_mode: Some(true),
+            ..test_support::account(email)
+        }
+    }
+
+    fn keys() -> (SigningKey, VerifyingKey) {
+        let signing = SigningKey::generate(&mut OsRng);
+        let verify = signing.verifying_key();
+        (signing, verify)
+    }
+
+    #[test]
+    fn test_signed_round_trip() {
+        let (signing, verify) = keys();
+        let text = export(&[account("a@x.com")], "alice", &signing, None, Utc::now()).unwrap();
+        assert!(text.contains("a@x.com_access"));
+
+        let pool = import(&text, &verify, None).unwrap();
+        assert_eq!(pool.exporter, "alice");
+        assert_eq!(pool.signer, STANDARD.encode(verify.as_bytes()));
+        let imported = &pool.accounts[0];
+        assert_eq!(imported.access_token.expose(), "a@x.com_access");
+        assert_eq!(imported.label_emoji.as_deref(), Some("🚀"));
+        // Only credentials and labels travel
+        assert_eq!(imported.status, MembershipStatus::Unknown);
+        assert_eq!(imported.usage_total, None);
+        assert_eq!(imported.source, AccountSource::Imported);
+    }
+
+    #[test]
+    fn test_rejects_unsigned_tampered_and_foreign_files() {
+        let (signing, verify) = keys();
+        let text = export(&[account("a@x.com")], "alice", &signing, None, Utc::now()).unwrap();
+        let mut document: Value = serde_json::from_str(&text).unwrap();
+
+        let mut unsigned = document.clone();
+        unsigned["signature"] = Value::Null;
+        let error = import(&unsigned.to_string(), &verify, None).unwrap_err();
+        assert!(error.to_string().contains("not signed"));
+
+        let payload = document["payload"]
+            .as_str()
+            .unwrap()
+            .replace("a@x.com", "b@x.com");
+        document["payload"] = Value::String(payload);
+        let error = import(&document.to_string(), &verify, None).unwrap_err();
+        assert!(error.to_string().contains("signature doesn't match"));
+
+        let (_, other) = keys();
+        let error = import(&text, &other, None).unwrap_err();
+        assert!(error.to_string().contains("not by the expected key"));
+
+        assert!(import("email,token\n", &verify, None).is_err());
+    }
+
+    #[test]
+    fn test_tokens_sealed_to_recipient() {
+        let (signing, verify) = keys();
+        let recipient = crypto_box::SecretKey::generate(&mut OsRng);
+        let text = export(
+            &[account("a@x.com")],
+            "alice",
+            &signing,
+            Some(&recipient.public_key()),
+            Utc::now(),
+        )
+        .unwrap();
+        assert!(!text.contains("a@x.com_access"));
+
+        let pool = import(&text, &verify, Some(&recipient)).unwrap();
+        assert_eq!(pool.accounts[0].refresh_token.expose(), "a@x.com_refresh");
+        assert!(pool.accounts[0].cookie.is_empty());
+
+        let stranger = crypto_box::SecretKey::generate(&mut OsRng);
+        assert!(import(&text, &verify, Some(&stranger)).is_err());
+        assert!(import(&text, &verify, None).is_err());
+    }
+
+    #[test]
+    fn test_public_keys_from_generated() {
+        let generated = GeneratedKeys::generate();
+        let public = PublicKeys::of(
+            generated.signing_key.expose(),
+            generated.encryption_key.expose(),
+        )
+        .unwrap();
+        assert!(parse_verify_key(&public.verify_key).is_ok());
+        assert!(parse_encryption_key(&public.encryption_key).is_ok());
+        assert!(parse_signing_key("short").is_err());
+    }
+}
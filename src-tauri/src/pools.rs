@@ -0,0 +1,94 @@
+use crate::types::Account;
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Pool of every account without one
+pub const DEFAULT_POOL: &str = "default";
+const MAX_NAME_LEN: usize = 40;
+
+/// Check a pool name; empty input and "default" clear the account's pool
+pub fn normalize(pool: Option<String>) -> Result<Option<String>> {
+    let Some(pool) = pool
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty() && !p.eq_ignore_ascii_case(DEFAULT_POOL))
+    else {
+        return Ok(None);
+    };
+    if pool.chars().count() > MAX_NAME_LEN {
+        bail!("The pool name must be at most {} characters", MAX_NAME_LEN);
+    }
+    if pool.chars().any(char::is_control) {
+        bail!("The pool name can't contain control characters");
+    }
+    Ok(Some(pool))
+}
+
+/// Name of the pool `account` belongs to
+pub fn pool_of(account: &Account) -> &str {
+    account.pool.as_deref().unwrap_or(DEFAULT_POOL)
+}
+
+/// Whether `account` is in `pool`; every account is in None
+pub fn in_pool(account: &Account, pool: Option<&str>) -> bool {
+    pool.is_none_or(|pool| pool_of(account) == pool)
+}
+
+/// A pool and how many accounts are in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PoolSummary {
+    pub name: String,
+    pub accounts: usize,
+}
+
+/// Every pool with members, the default pool first and the others by name
+pub fn summarize(accounts: &[Account]) -> Vec<PoolSummary> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for account in accounts {
+        *counts.entry(pool_of(account)).or_default() += 1;
+    }
+    let default = counts.remove(DEFAULT_POOL).map(|accounts| PoolSummary {
+        name: DEFAULT_POOL.to_string(),
+        accounts,
+    });
+    default
+        .into_iter()
+        .chain(counts.into_iter().map(|(name, accounts)| PoolSummary {
+            name: name.to_string(),
+            accounts,
+        }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::account;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(Some(" work ".to_string())).unwrap().as_deref(),
+            Some("work")
+        );
+        assert_eq!(normalize(Some("Default".to_string())).unwrap(), None);
+        assert_eq!(normalize(Some(" ".to_string())).unwrap(), None);
+        assert_eq!(normalize(None).unwrap(), None);
+        assert!(normalize(Some("x".repeat(MAX_NAME_LEN + 1))).is_err());
+        assert!(normalize(Some("a\nb".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_summary_puts_the_default_pool_first() {
+        let mut accounts = vec![account("a@x.com"), account("b@x.com"), account("c@x.com")];
+        accounts[0].pool = Some("work".to_string());
+        accounts[1].pool = Some("archive".to_string());
+
+        let summary = summarize(&accounts);
+        let names: Vec<&str> = summary.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec![DEFAULT_POOL, "archive", "work"]);
+        assert!(in_pool(&accounts[2], Some(DEFAULT_POOL)));
+        assert!(!in_pool(&accounts[0], Some(DEFAULT_POOL)));
+        assert!(in_pool(&accounts[0], None));
+    }
+}
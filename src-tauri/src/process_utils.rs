@@ -1,8 +1,44 @@
 use anyhow::Result;
 use std::process::Command;
+use std::time::{Duration, Instant};
 
 pub struct ProcessManager;
 
+/// Closing and starting Cursor, so the account flows can run in tests without
+/// touching real processes. `ProcessManager` is the real thing.
+pub trait CursorProcess: Sync {
+    fn is_running(&self) -> bool;
+    fn kill(&self) -> Result<()>;
+    fn restart(&self) -> Result<()>;
+
+    /// Poll until Cursor has exited, e.g. after `kill`, which only asks it
+    /// to. False if it is still running after `timeout`.
+    fn wait_for_exit(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.is_running() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(250));
+        }
+        true
+    }
+}
+
+impl CursorProcess for ProcessManager {
+    fn is_running(&self) -> bool {
+        Self::is_cursor_running()
+    }
+
+    fn kill(&self) -> Result<()> {
+        Self::kill_cursor()
+    }
+
+    fn restart(&self) -> Result<()> {
+        Self::restart_cursor(None)
+    }
+}
+
 impl ProcessManager {
     #[cfg(target_os = "windows")]
     pub fn kill_cursor() -> Result<()> {
@@ -49,6 +85,33 @@ impl ProcessManager {
         }
     }
 
+    #[cfg(target_os = "windows")]
+    pub fn is_cursor_running() -> bool {
+        Command::new("tasklist")
+            .args(["/FI", "IMAGENAME eq Cursor.exe", "/NH"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains("Cursor.exe"))
+            .unwrap_or(false)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn is_cursor_running() -> bool {
+        Command::new("pgrep")
+            .args(["-x", "Cursor"])
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn is_cursor_running() -> bool {
+        Command::new("pgrep")
+            .args(["-x", "cursor"])
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
     #[cfg(target_os = "windows")]
     pub fn restart_cursor(cursor_path: Option<String>) -> Result<()> {
         let default_path = r"C:\Users\%USERNAME%\AppData\Local\Programs\cursor\Cursor.exe";
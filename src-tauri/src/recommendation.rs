@@ -0,0 +1,238 @@
+use crate::tray;
+use crate::types::Account;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Candidates refreshed from the API before the winner is picked
+pub const LIVE_CANDIDATES: usize = 3;
+/// Scores of accounts whose data is older than `tray::STALE_AFTER_HOURS` are multiplied by this
+const STALE_FACTOR: f64 = 0.5;
+/// Days remaining count one point each, up to this many
+const MAX_DAY_POINTS: f64 = 30.0;
+
+/// How an account's score was computed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoreBreakdown {
+    pub remaining_quota: Option<f64>,
+    pub quota_points: f64,
+    /// None for paid plans, which don't run out
+    pub days_remaining: Option<f64>,
+    pub days_points: f64,
+    pub stale: bool,
+    pub staleness_factor: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RankedAccount {
+    pub email: String,
+    pub score: ScoreBreakdown,
+}
+
+/// Returned by `recommend_account`
+#[derive(Debug, Clone, Serialize)]
+pub struct Recommendation {
+    pub email: String,
+    pub score: ScoreBreakdown,
+    /// Whether the winner's data was just fetched rather than read from the CSV
+    pub live: bool,
+    /// Runners-up, best first
+    pub alternatives: Vec<RankedAccount>,
+    /// Every candidate is in its switch cooldown, so the winner is one of them
+    pub all_cooling_down: bool,
+}
+
+pub fn score_account(account: &Account, now: NaiveDateTime) -> ScoreBreakdown {
+    let remaining_quota = account.usage_remaining;
+    let quota_points = remaining_quota.unwrap_or(0.0).max(0.0);
+
+    let days_remaining = account.days_remaining;
+    let days_points = match days_remaining {
+        Some(days) => days.clamp(0.0, MAX_DAY_POINTS),
+        None => MAX_DAY_POINTS,
+    };
+
+    let stale = tray::is_stale(account, tray::STALE_AFTER_HOURS, now);
+    let staleness_factor = if stale { STALE_FACTOR } else { 1.0 };
+
+    ScoreBreakdown {
+        remaining_quota,
+        quota_points,
+        days_remaining,
+        days_points,
+        stale,
+        staleness_factor,
+        total: (quota_points + days_points) * staleness_factor,
+    }
+}
+
+/// Accounts worth switching to: not the current one, still active, not
+/// expiring today and, if asked, with at least `min_remaining` quota left
+fn is_candidate(
+    account: &Account,
+    current_email: Option<&str>,
+    min_remaining: Option<f64>,
+) -> bool {
+    let expiring_today = account.days_remaining.is_some_and(|days| days < 1.0);
+    let enough_quota = match min_remaining {
+        Some(min) => account.usage_remaining.is_some_and(|left| left >= min),
+        None => true,
+    };
+
+    Some(account.email.as_str()) != current_email
+        && !tray::is_inactive(account)
+        && !expiring_today
+        && enough_quota
+}
+
+/// Candidates ordered best first; ties are broken by email so the order is stable
+pub fn rank_accounts(
+    accounts: &[Account],
+    current_email: Option<&str>,
+    min_remaining: Option<f64>,
+    now: NaiveDateTime,
+) -> Vec<RankedAccount> {
+    let mut ranked: Vec<RankedAccount> = accounts
+        .iter()
+        .filter(|account| is_candidate(account, current_email, min_remaining))
+        .map(|account| RankedAccount {
+            email: account.email.clone(),
+            score: score_account(account, now),
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.score
+            .total
+            .total_cmp(&a.score.total)
+            .then_with(|| a.email.cmp(&b.email))
+    });
+    ranked
+}
+
+/// Move accounts in their switch cooldown behind the rested ones, keeping
+/// each group's order, so they are only picked when nothing else is left
+pub fn rest_cooling_down<T>(ranked: &mut [RankedAccount], cooling_down: &BTreeMap<String, T>) {
+    ranked.sort_by_key(|candidate| cooling_down.contains_key(&candidate.email));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    use crate::types::{days_remaining, record_time, MembershipStatus};
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2024-03-10 12:00:00", record_time::FORMAT).unwrap()
+    }
+
+    fn account(email: &str, remaining: Option<f64>, days: &str, record_time: &str) -> Account {
+        Account {
+            index: 1,
+            days_remaining: days_remaining::parse(days),
+            status: MembershipStatus::FreeTrial,
+            record_time: record_time.to_string(),
+            usage_remaining: remaining,
+            ..test_support::account(email)
+        }
+    }
+
+    const FRESH: &str = "2024-03-10 10:00:00";
+    const OLD: &str = "2024-03-08 10:00:00";
+
+    fn emails(ranked: &[RankedAccount]) -> Vec<&str> {
+        ranked.iter().map(|r| r.email.as_str()).collect()
+    }
+
+    #[test]
+    fn test_most_remaining_quota_wins_and_current_is_skipped() {
+        let accounts = vec![
+            account("current@x.com", Some(500.0), "10", FRESH),
+            account("big@x.com", Some(400.0), "10", FRESH),
+            account("small@x.com", Some(100.0), "10", FRESH),
+        ];
+
+        let ranked = rank_accounts(&accounts, Some("current@x.com"), None, now());
+        assert_eq!(emails(&ranked), vec!["big@x.com", "small@x.com"]);
+        assert_eq!(ranked[0].score.total, 410.0);
+    }
+
+    #[test]
+    fn test_stale_data_is_penalized() {
+        let accounts = vec![
+            account("old@x.com", Some(400.0), "10", OLD),
+            account("fresh@x.com", Some(300.0), "10", FRESH),
+        ];
+
+        let ranked = rank_accounts(&accounts, None, None, now());
+        assert_eq!(emails(&ranked), vec!["fresh@x.com", "old@x.com"]);
+        assert!(ranked[1].score.stale);
+        assert_eq!(ranked[1].score.total, 205.0);
+
+        // When everything is stale the quota still decides
+        let all_stale = vec![
+            account("a@x.com", Some(100.0), "10", OLD),
+            account("b@x.com", Some(200.0), "10", "not a date"),
+        ];
+        let ranked = rank_accounts(&all_stale, None, None, now());
+        assert_eq!(emails(&ranked), vec!["b@x.com", "a@x.com"]);
+    }
+
+    #[test]
+    fn test_ties_break_by_email() {
+        let accounts = vec![
+            account("b@x.com", Some(100.0), "5", FRESH),
+            account("a@x.com", Some(100.0), "5", FRESH),
+        ];
+
+        let ranked = rank_accounts(&accounts, None, None, now());
+        assert_eq!(emails(&ranked), vec!["a@x.com", "b@x.com"]);
+    }
+
+    #[test]
+    fn test_expired_and_low_quota_accounts_are_excluded() {
+        let mut expired = account("expired@x.com", Some(900.0), "10", FRESH);
+        expired.status = MembershipStatus::Expired;
+        let accounts = vec![
+            expired,
+            account("today@x.com", Some(900.0), "0.5", FRESH),
+            account("unknown@x.com", None, "10", FRESH),
+        ];
+        assert!(rank_accounts(&accounts[..2], None, None, now()).is_empty());
+
+        // Unknown quota can't satisfy a minimum
+        assert!(rank_accounts(&accounts, None, Some(1.0), now()).is_empty());
+        assert_eq!(
+            emails(&rank_accounts(&accounts, None, None, now())),
+            vec!["unknown@x.com"]
+        );
+    }
+
+    #[test]
+    fn test_cooling_down_accounts_go_last() {
+        let accounts = vec![
+            account("best@x.com", Some(400.0), "10", FRESH),
+            account("good@x.com", Some(300.0), "10", FRESH),
+            account("okay@x.com", Some(200.0), "10", FRESH),
+        ];
+        let mut ranked = rank_accounts(&accounts, None, None, now());
+        let cooling = BTreeMap::from([
+            ("best@x.com".to_string(), ()),
+            ("good@x.com".to_string(), ()),
+        ]);
+        rest_cooling_down(&mut ranked, &cooling);
+        assert_eq!(
+            emails(&ranked),
+            vec!["okay@x.com", "best@x.com", "good@x.com"]
+        );
+    }
+
+    #[test]
+    fn test_paid_plans_get_full_day_points() {
+        let score = score_account(&account("pro@x.com", Some(0.0), "N/A", FRESH), now());
+        assert_eq!(score.days_points, MAX_DAY_POINTS);
+        assert_eq!(score.days_remaining, None);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::file_cache;
+use crate::operations::BatchRefresh;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// File next to the account CSV describing the last batch refresh
+pub const REFRESH_STATE_FILE: &str = "refresh_state.json";
+
+/// What started a batch refresh
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshTrigger {
+    /// The refresh button in the app window
+    Manual,
+    Tray,
+    /// A background job
+    Scheduled,
+    /// The local automation API
+    Api,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshStatus {
+    Running,
+    /// Every account was tried; some may still have failed
+    Completed,
+    /// The run stopped early, e.g. the CSV couldn't be read
+    Failed,
+    /// Written as running but never finished: the app was closed or crashed
+    Interrupted,
+}
+
+/// Last batch refresh, returned by `get_last_refresh_info`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefreshInfo {
+    pub trigger: RefreshTrigger,
+    pub status: RefreshStatus,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub success_count: usize,
+    pub error_count: usize,
+    pub failed_emails: Vec<String>,
+    /// Why a failed run stopped
+    pub error: Option<String>,
+}
+
+/// Single-entry record of the last batch refresh. It is written as running
+/// when a refresh starts and finalized when it ends, so an entry still running
+/// when nothing refreshes means that run never got to the end.
+pub struct RefreshStateStore {
+    file_path: PathBuf,
+}
+
+impl RefreshStateStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    /// The last run, None if there never was one. `refreshing` tells whether a
+    /// refresh is going on right now; without one a running entry is reported
+    /// as interrupted.
+    pub fn load(&self, refreshing: bool) -> Result<Option<RefreshInfo>> {
+        if !self.file_path.exists() {
+            return Ok(None);
+        }
+        let content =
+            fs::read_to_string(&self.file_path).context("Failed to read refresh state")?;
+        let mut info: RefreshInfo =
+            serde_json::from_str(&content).context("Failed to parse refresh state")?;
+        if info.status == RefreshStatus::Running && !refreshing {
+            info.status = RefreshStatus::Interrupted;
+        }
+        Ok(Some(info))
+    }
+
+    pub fn start(&self, trigger: RefreshTrigger, now: DateTime<Utc>) -> Result<()> {
+        self.save(&RefreshInfo {
+            trigger,
+            status: RefreshStatus::Running,
+            started_at: now,
+            finished_at: None,
+            success_count: 0,
+            error_count: 0,
+            failed_emails: Vec::new(),
+            error: None,
+        })
+    }
+
+    /// Finalize the running entry with the outcome of the run
+    pub fn finish(
+        &self,
+        outcome: std::result::Result<&BatchRefresh, String>,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let Some(mut info) = self.load(true)? else {
+            return Ok(());
+        };
+        info.finished_at = Some(now);
+        match outcome {
+            Ok(refresh) => {
+                info.status = RefreshStatus::Completed;
+                info.success_count = refresh.accounts.len().saturating_sub(refresh.failed.len());
+                info.error_count = refresh.failed.len();
+                info.failed_emails = refresh.failed.clone();
+            }
+            Err(error) => {
+                info.status = RefreshStatus::Failed;
+                info.error = Some(error);
+            }
+        }
+        self.save(&info)
+    }
+
+    fn save(&self, info: &RefreshInfo) -> Result<()> {
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(info)?;
+        fs::write(&self.file_path, content).context("Failed to write refresh state")?;
+        file_cache::note_write(&self.file_path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_run_is_written_at_start_and_finalized() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = RefreshStateStore::new(temp_dir.path().join(REFRESH_STATE_FILE));
+        let started = Utc.with_ymd_and_hms(2024, 3, 10, 14, 30, 0).unwrap();
+        let finished = Utc.with_ymd_and_hms(2024, 3, 10, 14, 32, 0).unwrap();
+        assert_eq!(store.load(false).unwrap(), None);
+
+        store.start(RefreshTrigger::Tray, started).unwrap();
+        let running = store.load(true).unwrap().unwrap();
+        assert_eq!(running.status, RefreshStatus::Running);
+        // Nothing is refreshing, so the app died during that run
+        let left_over = store.load(false).unwrap().unwrap();
+        assert_eq!(left_over.status, RefreshStatus::Interrupted);
+
+        let refresh = BatchRefresh {
+            accounts: Vec::new(),
+            failed: vec!["a@x.com".to_string()],
+        };
+        store.finish(Ok(&refresh), finished).unwrap();
+        let info = store.load(false).unwrap().unwrap();
+        assert_eq!(info.status, RefreshStatus::Completed);
+        assert_eq!(info.trigger, RefreshTrigger::Tray);
+        assert_eq!(info.finished_at, Some(finished));
+        assert_eq!(info.error_count, 1);
+        assert_eq!(info.failed_emails, vec!["a@x.com"]);
+
+        store.start(RefreshTrigger::Manual, finished).unwrap();
+        store
+            .finish(Err("CSV unreadable".to_string()), finished)
+            .unwrap();
+        let failed = store.load(false).unwrap().unwrap();
+        assert_eq!(failed.status, RefreshStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("CSV unreadable"));
+    }
+}
@@ -0,0 +1,319 @@
+// Account status reports for pasting into a wiki. Tokens are never part of a
+// report: no column reads them.
+
+use crate::comparison::AccountComparison;
+use crate::pools;
+use crate::types::Account;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportColumn {
+    Email,
+    Status,
+    DaysRemaining,
+    UsageUsed,
+    UsageRemaining,
+    UsagePercentage,
+    LastRefresh,
+    Pool,
+}
+
+/// Columns of a report that doesn't pick any
+pub const DEFAULT_COLUMNS: &[ReportColumn] = &[
+    ReportColumn::Email,
+    ReportColumn::Status,
+    ReportColumn::DaysRemaining,
+    ReportColumn::UsageUsed,
+    ReportColumn::UsageRemaining,
+    ReportColumn::UsagePercentage,
+    ReportColumn::LastRefresh,
+    ReportColumn::Pool,
+];
+
+impl ReportColumn {
+    fn title(&self) -> &'static str {
+        match self {
+            ReportColumn::Email => "Email",
+            ReportColumn::Status => "Status",
+            ReportColumn::DaysRemaining => "Days left",
+            ReportColumn::UsageUsed => "Used",
+            ReportColumn::UsageRemaining => "Remaining",
+            ReportColumn::UsagePercentage => "Used %",
+            ReportColumn::LastRefresh => "Last refresh",
+            ReportColumn::Pool => "Pool",
+        }
+    }
+
+    fn cell(&self, account: &Account, row: &AccountComparison) -> String {
+        let dollars =
+            |value: Option<f64>| value.map_or_else(|| "-".to_string(), |v| format!("${:.2}", v));
+        match self {
+            ReportColumn::Email => account.email.clone(),
+            ReportColumn::Status => account.status.as_str().to_string(),
+            ReportColumn::DaysRemaining => row
+                .days_remaining
+                .map_or_else(|| "-".to_string(), |days| format!("{:.0}", days)),
+            ReportColumn::UsageUsed => dollars(row.used_usd),
+            ReportColumn::UsageRemaining => dollars(row.remaining_usd),
+            ReportColumn::UsagePercentage => row
+                .used_percent
+                .map_or_else(|| "-".to_string(), |percent| format!("{:.0}%", percent)),
+            ReportColumn::LastRefresh => row
+                .data_age_secs
+                .map_or_else(|| "never".to_string(), format_age),
+            ReportColumn::Pool => pools::pool_of(account).to_string(),
+        }
+    }
+}
+
+/// Totals over a set of accounts; money is in US dollars
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FleetStats {
+    pub accounts: usize,
+    /// Accounts per membership status, e.g. "pro"
+    pub by_status: BTreeMap<String, usize>,
+    pub used_usd: f64,
+    pub remaining_usd: f64,
+    /// Accounts without usage data, e.g. never refreshed
+    pub without_usage: usize,
+}
+
+impl FleetStats {
+    pub fn of(accounts: &[Account]) -> Self {
+        let mut by_status = BTreeMap::new();
+        for account in accounts {
+            *by_status
+                .entry(account.status.as_str().to_string())
+                .or_default() += 1;
+        }
+        Self {
+            accounts: accounts.len(),
+            by_status,
+            used_usd: accounts.iter().filter_map(|a| a.usage_used).sum(),
+            remaining_usd: accounts.iter().filter_map(|a| a.usage_remaining).sum(),
+            without_usage: accounts
+                .iter()
+                .filter(|a| a.usage_remaining.is_none())
+                .count(),
+        }
+    }
+
+    fn summary(&self) -> Vec<String> {
+        let statuses: Vec<String> = self
+            .by_status
+            .iter()
+            .map(|(status, count)| format!("{} {}", count, status))
+            .collect();
+        let mut lines = vec![
+            format!("{} accounts: {}", self.accounts, statuses.join(", ")),
+            format!(
+                "${:.2} used, ${:.2} remaining",
+                self.used_usd, self.remaining_usd
+            ),
+        ];
+        if self.without_usage > 0 {
+            lines.push(format!("{} without usage data", self.without_usage));
+        }
+        lines
+    }
+}
+
+/// "45s", "12m", "5h" or "3d", rounded down
+pub fn format_age(secs: i64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s.max(0)),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86400 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86400),
+    }
+}
+
+/// The report of `accounts` with `columns`, generated at `now`
+pub fn render(
+    accounts: &[Account],
+    stats: &FleetStats,
+    columns: &[ReportColumn],
+    format: ReportFormat,
+    now: NaiveDateTime,
+) -> String {
+    let generated = now.format("%Y-%m-%d %H:%M").to_string();
+    let rows: Vec<Vec<String>> = accounts
+        .iter()
+        .map(|account| {
+            let row = AccountComparison::from_account(account, now);
+            columns.iter().map(|c| c.cell(account, &row)).collect()
+        })
+        .collect();
+    match format {
+        ReportFormat::Markdown => markdown(&generated, stats, columns, &rows),
+        ReportFormat::Html => html(&generated, stats, columns, &rows),
+    }
+}
+
+fn markdown(
+    generated: &str,
+    stats: &FleetStats,
+    columns: &[ReportColumn],
+    rows: &[Vec<String>],
+) -> String {
+    let cell = |text: &str| text.replace('|', "\\|");
+    let mut out = format!("# Cursor accounts\n\nGenerated {}\n\n", generated);
+    for line in stats.summary() {
+        let _ = writeln!(out, "- {}", line);
+    }
+    let titles: Vec<&str> = columns.iter().map(|c| c.title()).collect();
+    let _ = write!(
+        out,
+        "\n| {} |\n|{}\n",
+        titles.join(" | "),
+        " --- |".repeat(columns.len())
+    );
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|text| cell(text)).collect();
+        let _ = writeln!(out, "| {} |", cells.join(" | "));
+    }
+    out
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse}\
+th,td{border:1px solid #ccc;padding:4px 10px;text-align:left}\
+th{background:#f3f3f3}";
+
+fn html(
+    generated: &str,
+    stats: &FleetStats,
+    columns: &[ReportColumn],
+    rows: &[Vec<String>],
+) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Cursor accounts</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<h1>Cursor accounts</h1>\n<p>Generated {}</p>\n<ul>\n",
+        HTML_STYLE,
+        escape(generated)
+    );
+    for line in stats.summary() {
+        let _ = writeln!(out, "<li>{}</li>", escape(&line));
+    }
+    out.push_str("</ul>\n<table>\n<tr>");
+    for column in columns {
+        let _ = write!(out, "<th>{}</th>", column.title());
+    }
+    out.push_str("</tr>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for text in row {
+            let _ = write!(out, "<td>{}</td>", escape(text));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{record_time, MembershipStatus};
+
+    fn account(email: &str, status: MembershipStatus, usage: Option<(f64, f64)>) -> Account {
+        Account {
+            index: 1,
+            email: email.to_string(),
+            access_token: "secret_access_token".into(),
+            refresh_token: "secret_refresh_token".into(),
+            days_remaining: Some(12.0),
+            status,
+            record_time: "2024-03-10 09:00:00".to_string(),
+            usage_used: usage.map(|(used, _)| used),
+            usage_remaining: usage.map(|(used, total)| total - used),
+            usage_total: usage.map(|(_, total)| total),
+            ..Default::default()
+        }
+    }
+
+    fn accounts() -> Vec<Account> {
+        vec![
+            Account {
+                pool: Some("team|a".to_string()),
+                ..account("a@x.com", MembershipStatus::Pro, Some((5.0, 20.0)))
+            },
+            account("<b>@x.com", MembershipStatus::FreeTrial, None),
+        ]
+    }
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2024-03-10 12:30:00", record_time::FORMAT).unwrap()
+    }
+
+    #[test]
+    fn test_markdown_report() {
+        let accounts = accounts();
+        let report = render(
+            &accounts,
+            &FleetStats::of(&accounts),
+            DEFAULT_COLUMNS,
+            ReportFormat::Markdown,
+            now(),
+        );
+        assert_eq!(
+            report,
+            "# Cursor accounts\n\
+             \n\
+             Generated 2024-03-10 12:30\n\
+             \n\
+             - 2 accounts: 1 free_trial, 1 pro\n\
+             - $5.00 used, $15.00 remaining\n\
+             - 1 without usage data\n\
+             \n\
+             | Email | Status | Days left | Used | Remaining | Used % | Last refresh | Pool |\n\
+             | --- | --- | --- | --- | --- | --- | --- | --- |\n\
+             | a@x.com | pro | 12 | $5.00 | $15.00 | 25% | 3h | team\\|a |\n\
+             | <b>@x.com | free_trial | 12 | - | - | - | 3h | default |\n"
+        );
+        assert!(!report.contains("secret"));
+    }
+
+    #[test]
+    fn test_html_report_with_chosen_columns() {
+        let accounts = accounts();
+        let report = render(
+            &accounts,
+            &FleetStats::of(&accounts),
+            &[ReportColumn::Email, ReportColumn::UsagePercentage],
+            ReportFormat::Html,
+            now(),
+        );
+        assert!(report.starts_with("<!DOCTYPE html>"));
+        assert!(report.contains("<tr><th>Email</th><th>Used %</th></tr>"));
+        assert!(report.contains("<tr><td>a@x.com</td><td>25%</td></tr>"));
+        assert!(report.contains("<td>&lt;b&gt;@x.com</td>"));
+        assert!(!report.contains("secret"));
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(45), "45s");
+        assert_eq!(format_age(12 * 60 + 5), "12m");
+        assert_eq!(format_age(5 * 3600), "5h");
+        assert_eq!(format_age(3 * 86400 + 1), "3d");
+    }
+}
@@ -1,56 +1,421 @@
+use crate::change_set::KeyChange;
+use crate::cursor_settings::{RestoreMode, SettingsSnapshotStore};
 use crate::machine_id::MachineIdGenerator;
 use crate::path_detector::PathDetector;
-use crate::process_utils::ProcessManager;
+use crate::types::MachineIds;
 use anyhow::{Context, Result};
-use chrono::Local;
+use chrono::{Local, Utc};
+use serde::Serialize;
 use serde_json::Value;
-use std::fs;
-use std::path::PathBuf;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+
+// The hardware ID lookup in main.js and what a reset replaces it with
+#[cfg(target_os = "macos")]
+const MACOS_ID_COMMANDS: &[&str] = &["ioreg -rd1 -c IOPlatformExpertDevice"];
+#[cfg(target_os = "macos")]
+const MACOS_ID_PATCH: &str =
+    r#"UUID=$(uuidgen | tr '[:upper:]' '[:lower:]');echo \"IOPlatformUUID = \"$UUID\";"#;
+// The variable name (e.g., v5[s$()], u5[bM()]) varies between Cursor versions
+#[cfg(target_os = "windows")]
+const WINDOWS_ID_COMMANDS: &[&str] = &[
+    r#"${v5[s$()]}\\REG.exe QUERY HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Cryptography /v MachineGuid"#,
+    r#"${u5[bM()]}\\REG.exe QUERY HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Cryptography /v MachineGuid"#,
+];
+#[cfg(target_os = "windows")]
+const WINDOWS_ID_PATCH: &str = r#"powershell -Command "[guid]::NewGuid().ToString().ToLower()""#;
+
+/// Free space a reset keeps on top of its backups and rewrites
+const FREE_SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+const WRITE_PROBE_FILE: &str = ".cursor-switcher-write-check";
+
+/// Whether Cursor's main.js still carries the machine ID patch of a reset.
+/// Cursor updates replace main.js, which silently undoes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchStatus {
+    Patched,
+    /// main.js reads the hardware ID again, e.g. after an update
+    Unpatched,
+    /// Neither form was found; this Cursor version may read the ID differently
+    Unrecognized,
+    /// main.js wasn't found where Cursor is normally installed
+    Missing,
+    /// Only macOS and Windows builds of Cursor are patched
+    NotApplicable,
+}
+
+/// Check the installed main.js without changing it
+#[cfg(target_os = "macos")]
+pub fn main_js_patch_status() -> PatchStatus {
+    main_js_path().map_or(PatchStatus::Missing, |path| {
+        patch_status_at(&path, MACOS_ID_COMMANDS, MACOS_ID_PATCH)
+    })
+}
+
+/// Check the installed main.js without changing it
+#[cfg(target_os = "windows")]
+pub fn main_js_patch_status() -> PatchStatus {
+    main_js_path().map_or(PatchStatus::Missing, |path| {
+        patch_status_at(&path, WINDOWS_ID_COMMANDS, WINDOWS_ID_PATCH)
+    })
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub fn main_js_patch_status() -> PatchStatus {
+    PatchStatus::NotApplicable
+}
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+fn patch_status_at(main_js: &Path, id_commands: &[&str], patch: &str) -> PatchStatus {
+    let Ok(content) = fs::read_to_string(main_js) else {
+        return PatchStatus::Missing;
+    };
+    if content.contains(patch) {
+        PatchStatus::Patched
+    } else if id_commands.iter().any(|command| content.contains(command)) {
+        PatchStatus::Unpatched
+    } else {
+        PatchStatus::Unrecognized
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn main_js_path() -> Result<PathBuf> {
+    Ok(PathBuf::from(
+        "/Applications/Cursor.app/Contents/Resources/app/out/main.js",
+    ))
+}
+
+#[cfg(target_os = "windows")]
+fn main_js_path() -> Result<PathBuf> {
+    let local_appdata =
+        std::env::var("LOCALAPPDATA").context("Failed to get LOCALAPPDATA environment variable")?;
+    Ok(PathBuf::from(local_appdata)
+        .join("Programs")
+        .join("cursor")
+        .join("resources")
+        .join("app")
+        .join("out")
+        .join("main.js"))
+}
+
+/// What a pre-flight check of a reset looked at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetCheckKind {
+    /// Room for the backup and the rewrite of a file, plus a margin
+    DiskSpace,
+    /// The file and the directory its backup goes to can be written
+    Writable,
+    /// The newest earlier backup of the file can be read back
+    LatestBackup,
+}
+
+impl ResetCheckKind {
+    fn label(&self) -> &'static str {
+        match self {
+            ResetCheckKind::DiskSpace => "disk space",
+            ResetCheckKind::Writable => "write permission",
+            ResetCheckKind::LatestBackup => "latest backup",
+        }
+    }
+}
+
+/// One check a reset passes before it writes anything
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResetCheck {
+    pub kind: ResetCheckKind,
+    pub path: PathBuf,
+    pub passed: bool,
+    /// What was found, e.g. "3 MB free, 70 MB needed"
+    pub detail: String,
+}
+
+impl fmt::Display for ResetCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} check of {} failed: {}",
+            self.kind.label(),
+            self.path.display(),
+            self.detail
+        )
+    }
+}
+
+/// A file a reset backs up and rewrites
+struct ResetTarget {
+    path: PathBuf,
+    backup_dir: PathBuf,
+    backup_prefix: &'static str,
+    /// Err when a backup of this file is unusable
+    validate: fn(&Path) -> Result<()>,
+}
+
+fn validate_storage_backup(path: &Path) -> Result<()> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str::<Value>(&content).context("it isn't valid JSON")?;
+    Ok(())
+}
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+fn validate_main_js_backup(path: &Path) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    let (id_commands, patch) = (MACOS_ID_COMMANDS, MACOS_ID_PATCH);
+    #[cfg(target_os = "windows")]
+    let (id_commands, patch) = (WINDOWS_ID_COMMANDS, WINDOWS_ID_PATCH);
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let (id_commands, patch): (&[&str], &str) = (&[], "");
+
+    match patch_status_at(path, id_commands, patch) {
+        PatchStatus::Patched | PatchStatus::Unpatched => Ok(()),
+        PatchStatus::Missing => anyhow::bail!("it can't be read"),
+        _ => anyhow::bail!("it has no machine ID lookup, so it may be truncated"),
+    }
+}
+
+/// Bytes free for this user on the volume holding `dir`, None when unknown
+#[cfg(unix)]
+// The field widths of `statvfs` differ between platforms
+#[allow(clippy::unnecessary_cast)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stats` is only read once filled in
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stats = unsafe { stats.assume_init() };
+    Some((stats.f_bavail as u64).saturating_mul(stats.f_frsize as u64))
+}
+
+/// Bytes free for this user on the volume holding `dir`, None when unknown
+#[cfg(windows)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            free_to_caller: *mut u64,
+            total: *mut u64,
+            total_free: *mut u64,
+        ) -> i32;
+    }
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut free = 0u64;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut free,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(free)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+// The directory itself, or the closest parent a missing one would be created in
+fn existing_dir(dir: &Path) -> &Path {
+    dir.ancestors().find(|d| d.is_dir()).unwrap_or(dir)
+}
+
+fn format_size(bytes: u64) -> String {
+    format!("{} MB", bytes.div_ceil(1024 * 1024))
+}
+
+fn check_target(target: &ResetTarget, available: &dyn Fn(&Path) -> Option<u64>) -> [ResetCheck; 3] {
+    let check = |kind, path: &Path, outcome: std::result::Result<String, String>| ResetCheck {
+        kind,
+        path: path.to_path_buf(),
+        passed: outcome.is_ok(),
+        detail: outcome.unwrap_or_else(|e| e),
+    };
+    let size = fs::metadata(&target.path).map(|m| m.len());
+    let backup_dir = existing_dir(&target.backup_dir);
+
+    // The backup is a full copy and the rewrite is about as big as the original
+    let space = match &size {
+        Err(e) => Err(format!("can't read its size: {}", e)),
+        Ok(size) => {
+            let needed = size.saturating_mul(2).saturating_add(FREE_SPACE_MARGIN);
+            match available(backup_dir) {
+                Some(free) if free < needed => Err(format!(
+                    "{} free, {} needed",
+                    format_size(free),
+                    format_size(needed)
+                )),
+                Some(free) => Ok(format!("{} free", format_size(free))),
+                None => Ok("free space unknown".to_string()),
+            }
+        }
+    };
+
+    // Opening without truncation leaves the file as it is
+    let writable = OpenOptions::new()
+        .write(true)
+        .open(&target.path)
+        .map_err(|e| format!("can't open it for writing: {}", e))
+        .and_then(|_| {
+            let probe = backup_dir.join(WRITE_PROBE_FILE);
+            fs::write(&probe, b"")
+                .map_err(|e| format!("can't write to {}: {}", backup_dir.display(), e))?;
+            let _ = fs::remove_file(&probe);
+            Ok("writable".to_string())
+        });
+
+    // Backup names end in a timestamp, so the newest sorts last
+    let latest = fs::read_dir(&target.backup_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(target.backup_prefix))
+        })
+        .max();
+    let backup = match &latest {
+        None => Ok("no earlier backup".to_string()),
+        Some(path) => (target.validate)(path)
+            .map(|()| format!("{} is readable", path.display()))
+            .map_err(|e| format!("{} is unusable: {:#}", path.display(), e)),
+    };
+
+    [
+        check(ResetCheckKind::DiskSpace, &target.path, space),
+        check(ResetCheckKind::Writable, &target.path, writable),
+        check(
+            ResetCheckKind::LatestBackup,
+            latest.as_deref().unwrap_or(&target.backup_dir),
+            backup,
+        ),
+    ]
+}
 
 pub struct MachineIdResetter {
     base_path: PathBuf,
+    settings_snapshots: Option<SettingsSnapshotStore>,
 }
 
 impl MachineIdResetter {
     pub fn new(base_path: PathBuf) -> Self {
-        Self { base_path }
+        Self {
+            base_path,
+            settings_snapshots: None,
+        }
     }
 
-    pub fn reset(&self) -> Result<()> {
-        // Kill Cursor process first
-        ProcessManager::kill_cursor()?;
+    /// Snapshot the user's Cursor settings into `store` before the reset and put
+    /// back whatever the reset wiped. Cursor's next start is checked again;
+    /// see `SettingsSnapshotStore::pending`.
+    pub fn preserving_settings(mut self, store: SettingsSnapshotStore) -> Self {
+        self.settings_snapshots = Some(store);
+        self
+    }
+
+    fn targets(&self) -> Vec<ResetTarget> {
+        let storage = ResetTarget {
+            path: PathDetector::get_storage_path(&self.base_path),
+            backup_dir: self.base_path.join("backups"),
+            backup_prefix: "storage.json.backup_",
+            validate: validate_storage_backup,
+        };
+        // A main.js that isn't there is skipped by the reset as well
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        let main_js = main_js_path()
+            .ok()
+            .filter(|path| path.exists())
+            .map(|main_js| ResetTarget {
+                backup_dir: main_js.parent().map(Path::to_path_buf).unwrap_or_default(),
+                path: main_js,
+                backup_prefix: "main.js.backup_",
+                validate: validate_main_js_backup,
+            });
+        #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+        let main_js = None;
+        std::iter::once(storage).chain(main_js).collect()
+    }
 
+    /// Disk space, write permission and the newest backup of every file the
+    /// reset rewrites, checked without changing any of them
+    pub fn preflight(&self) -> Vec<ResetCheck> {
+        self.targets()
+            .iter()
+            .flat_map(|target| check_target(target, &available_space))
+            .collect()
+    }
+
+    /// Err naming the first failed pre-flight check
+    pub fn ensure_preflight(&self) -> Result<()> {
+        match self.preflight().into_iter().find(|check| !check.passed) {
+            Some(check) => anyhow::bail!("Machine ID reset aborted before any change: {}", check),
+            None => Ok(()),
+        }
+    }
+
+    /// Cursor must be closed first, or it writes its old IDs back on exit.
+    /// Returns the new IDs with the files they were written to.
+    pub fn reset(&self) -> Result<MachineIds> {
         // Get storage.json path
         let storage_path = PathDetector::get_storage_path(&self.base_path);
 
         if !storage_path.exists() {
             anyhow::bail!("storage.json not found at: {:?}", storage_path);
         }
+        self.ensure_preflight()?;
 
         // Backup storage.json
         self.backup_storage_file(&storage_path)?;
 
+        let settings_snapshot = match &self.settings_snapshots {
+            Some(store) => Some(
+                store
+                    .take(&self.base_path, Utc::now())
+                    .context("Failed to snapshot Cursor's settings")?,
+            ),
+            None => None,
+        };
+
         // Generate new machine IDs
-        let new_ids = MachineIdGenerator::generate();
+        let mut new_ids = MachineIdGenerator::generate();
 
         // Update storage.json
-        self.update_storage_file(&storage_path, &new_ids)?;
+        new_ids.changes = self.update_storage_file(&storage_path, &new_ids)?;
+        new_ids.applied_to.push(storage_path);
 
         // Update main.js file on macOS to replace ioreg command
         #[cfg(target_os = "macos")]
         {
-            if let Err(e) = self.update_main_js_file_macos() {
-                eprintln!("Warning: Failed to update main.js: {}", e);
-                eprintln!("Machine ID reset will continue, but main.js modification failed.");
+            match self.update_main_js_file_macos() {
+                Ok(main_js_path) => new_ids.applied_to.push(main_js_path),
+                Err(e) => {
+                    eprintln!("Warning: Failed to update main.js: {}", e);
+                    eprintln!("Machine ID reset will continue, but main.js modification failed.");
+                }
             }
         }
 
         // Update main.js file on Windows to replace registry command
         #[cfg(target_os = "windows")]
         {
-            if let Err(e) = self.update_main_js_file_windows() {
-                eprintln!("Warning: Failed to update main.js: {}", e);
-                eprintln!("Machine ID reset will continue, but main.js modification failed.");
+            match self.update_main_js_file_windows() {
+                Ok(main_js_path) => new_ids.applied_to.push(main_js_path),
+                Err(e) => {
+                    eprintln!("Warning: Failed to update main.js: {}", e);
+                    eprintln!("Machine ID reset will continue, but main.js modification failed.");
+                }
             }
         }
 
@@ -60,7 +425,21 @@ impl MachineIdResetter {
             eprintln!("Machine ID reset will continue, but may require administrator privileges for full effect.");
         }
 
-        Ok(())
+        if let (Some(store), Some(snapshot)) = (&self.settings_snapshots, settings_snapshot) {
+            // Cursor is closed during a reset, so its state can be written too
+            match snapshot.restore(&self.base_path, RestoreMode::Wiped, false) {
+                Ok(restore) => new_ids.preserved_settings = Some(restore),
+                Err(e) => tracing::warn!("Failed to restore Cursor's settings: {:#}", e),
+            }
+            if let Err(e) = store.set_pending(&snapshot.id) {
+                tracing::warn!(
+                    "Cursor's settings won't be checked at its next start: {:#}",
+                    e
+                );
+            }
+        }
+
+        Ok(new_ids)
     }
 
     fn backup_storage_file(&self, storage_path: &PathBuf) -> Result<()> {
@@ -76,35 +455,50 @@ impl MachineIdResetter {
         Ok(())
     }
 
+    // Returns the keys written
     fn update_storage_file(
         &self,
         storage_path: &PathBuf,
-        new_ids: &crate::types::MachineIds,
-    ) -> Result<()> {
+        new_ids: &MachineIds,
+    ) -> Result<Vec<KeyChange>> {
         // Read current storage.json
         let content = fs::read_to_string(storage_path)?;
         let mut storage: Value = serde_json::from_str(&content)?;
 
         // Update machine IDs using flat keys (not nested objects)
         // The correct format is "telemetry.machineId" as a key, not storage["telemetry"]["machineId"]
-        storage["telemetry.machineId"] = Value::String(new_ids.machine_id.clone());
-        storage["telemetry.macMachineId"] = Value::String(new_ids.mac_machine_id.clone());
-        storage["telemetry.devDeviceId"] = Value::String(new_ids.dev_device_id.clone());
-        storage["telemetry.sqmId"] = Value::String(new_ids.sqm_id.clone());
+        let mut changes = Vec::with_capacity(4);
+        for (key, value) in [
+            ("telemetry.machineId", &new_ids.machine_id),
+            ("telemetry.macMachineId", &new_ids.mac_machine_id),
+            ("telemetry.devDeviceId", &new_ids.dev_device_id),
+            ("telemetry.sqmId", &new_ids.sqm_id),
+        ] {
+            let old = storage.get(key).map(|old| match old {
+                Value::String(old) => old.clone(),
+                other => other.to_string(),
+            });
+            changes.push(KeyChange::of(
+                storage_path,
+                key,
+                old.as_deref(),
+                Some(value),
+            ));
+            storage[key] = Value::String(value.clone());
+        }
 
         // Write back to file
         let updated_content = serde_json::to_string_pretty(&storage)?;
         fs::write(storage_path, updated_content)?;
 
-        Ok(())
+        Ok(changes)
     }
 
     /// Update main.js file on macOS to replace ioreg command with uuidgen
     /// This prevents Cursor from reading hardware-based machine ID
     #[cfg(target_os = "macos")]
-    fn update_main_js_file_macos(&self) -> Result<()> {
-        let main_js_path =
-            PathBuf::from("/Applications/Cursor.app/Contents/Resources/app/out/main.js");
+    fn update_main_js_file_macos(&self) -> Result<PathBuf> {
+        let main_js_path = main_js_path()?;
 
         if !main_js_path.exists() {
             anyhow::bail!("main.js not found at: {:?}", main_js_path);
@@ -123,9 +517,8 @@ impl MachineIdResetter {
         // This matches the working implementation exactly
         // Original: ioreg -rd1 -c IOPlatformExpertDevice
         // Replacement: UUID=$(uuidgen | tr '[:upper:]' '[:lower:]');echo \"IOPlatformUUID = \"$UUID\";
-        let old_pattern = "ioreg -rd1 -c IOPlatformExpertDevice";
-        let new_pattern =
-            r#"UUID=$(uuidgen | tr '[:upper:]' '[:lower:]');echo \"IOPlatformUUID = \"$UUID\";"#;
+        let old_pattern = MACOS_ID_COMMANDS[0];
+        let new_pattern = MACOS_ID_PATCH;
 
         let updated_content = content.replace(old_pattern, new_pattern);
 
@@ -141,24 +534,14 @@ impl MachineIdResetter {
             eprintln!("You can restore from backup: {:?}", backup_path);
         }
 
-        Ok(())
+        Ok(main_js_path)
     }
 
     /// Update main.js file on Windows to replace registry query command with PowerShell
     /// This prevents Cursor from reading hardware-based machine GUID from registry
     #[cfg(target_os = "windows")]
-    fn update_main_js_file_windows(&self) -> Result<()> {
-        // Get LOCALAPPDATA path
-        let local_appdata = std::env::var("LOCALAPPDATA")
-            .context("Failed to get LOCALAPPDATA environment variable")?;
-
-        let main_js_path = PathBuf::from(local_appdata)
-            .join("Programs")
-            .join("cursor")
-            .join("resources")
-            .join("app")
-            .join("out")
-            .join("main.js");
+    fn update_main_js_file_windows(&self) -> Result<PathBuf> {
+        let main_js_path = main_js_path()?;
 
         if !main_js_path.exists() {
             anyhow::bail!("main.js not found at: {:?}", main_js_path);
@@ -175,13 +558,9 @@ impl MachineIdResetter {
 
         // Replace registry query command with PowerShell command
         // This matches the working implementation exactly
-        // Note: The variable name (e.g., v5[s$()], u5[bM()]) may vary between Cursor versions
         // We'll try multiple patterns to handle different versions
-        let patterns_to_try = vec![
-            r#"${v5[s$()]}\\REG.exe QUERY HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Cryptography /v MachineGuid"#,
-            r#"${u5[bM()]}\\REG.exe QUERY HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Cryptography /v MachineGuid"#,
-        ];
-        let new_pattern = r#"powershell -Command "[guid]::NewGuid().ToString().ToLower()""#;
+        let patterns_to_try = WINDOWS_ID_COMMANDS;
+        let new_pattern = WINDOWS_ID_PATCH;
 
         let mut updated_content = content.clone();
         let mut replaced = false;
@@ -209,6 +588,185 @@ impl MachineIdResetter {
             eprintln!("You can restore from backup: {:?}", backup_path);
         }
 
-        Ok(())
+        Ok(main_js_path)
+    }
+
+    /// Save storage.json before a reset
+    pub fn snapshot_storage(&self) -> Result<StorageSnapshot> {
+        let path = PathDetector::get_storage_path(&self.base_path);
+        let content = match fs::read(&path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).context("Failed to read storage.json"),
+        };
+        Ok(StorageSnapshot { path, content })
+    }
+}
+
+/// storage.json as it was before a reset, None when it didn't exist. Restoring
+/// it puts the old machine IDs back.
+#[derive(Debug, Clone)]
+pub struct StorageSnapshot {
+    path: PathBuf,
+    content: Option<Vec<u8>>,
+}
+
+impl StorageSnapshot {
+    pub fn restore(&self) -> Result<()> {
+        match &self.content {
+            Some(content) => {
+                fs::write(&self.path, content).context("Failed to restore storage.json")
+            }
+            None => match fs::remove_file(&self.path) {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e).context("Failed to restore storage.json"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MachineIdSource;
+
+    #[test]
+    fn test_reset_records_where_ids_were_applied() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = PathDetector::get_storage_path(temp_dir.path());
+        fs::create_dir_all(storage_path.parent().unwrap()).unwrap();
+        fs::write(&storage_path, r#"{"telemetry.machineId": "old"}"#).unwrap();
+
+        let ids = MachineIdResetter::new(temp_dir.path().to_path_buf())
+            .reset()
+            .unwrap();
+        assert_eq!(ids.source, MachineIdSource::Generated);
+        assert!(ids.generated_at.is_some());
+        assert_eq!(ids.applied_to.first(), Some(&storage_path));
+
+        let storage: Value =
+            serde_json::from_str(&fs::read_to_string(&storage_path).unwrap()).unwrap();
+        assert_eq!(storage["telemetry.machineId"], ids.machine_id.as_str());
+        let keys: Vec<&str> = ids.changes.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "telemetry.machineId",
+                "telemetry.macMachineId",
+                "telemetry.devDeviceId",
+                "telemetry.sqmId"
+            ]
+        );
+        assert_eq!(
+            ids.changes[0].old_hash,
+            Some(crate::change_set::value_hash("old"))
+        );
+        assert_eq!(ids.changes[1].old_hash, None);
+    }
+
+    #[test]
+    fn test_reset_preserving_settings_leaves_a_pending_check() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_path = temp_dir.path().join("User").join("globalStorage");
+        fs::create_dir_all(&base_path).unwrap();
+        fs::write(PathDetector::get_storage_path(&base_path), "{}").unwrap();
+        let settings_path = temp_dir.path().join("User").join("settings.json");
+        fs::write(&settings_path, r#"{"editor.fontSize": 15}"#).unwrap();
+
+        let store_dir = temp_dir.path().join("snapshots");
+        let ids = MachineIdResetter::new(base_path)
+            .preserving_settings(SettingsSnapshotStore::new(store_dir.clone()))
+            .reset()
+            .unwrap();
+        // Nothing was wiped while Cursor was closed
+        let restore = ids.preserved_settings.unwrap();
+        assert!(restore.restored.is_empty());
+
+        let store = SettingsSnapshotStore::new(store_dir);
+        assert_eq!(store.pending(), Some(restore.snapshot_id.clone()));
+        let snapshot = store.load(&restore.snapshot_id).unwrap();
+        assert!(snapshot.files.contains_key("settings.json"));
+    }
+
+    #[test]
+    fn test_patch_status_of_main_js() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let main_js = temp_dir.path().join("main.js");
+        let commands = ["ioreg -rd1 -c IOPlatformExpertDevice"];
+        let patch = "UUID=$(uuidgen)";
+        let status = |content: Option<&str>| {
+            if let Some(content) = content {
+                fs::write(&main_js, content).unwrap();
+            }
+            patch_status_at(&main_js, &commands, patch)
+        };
+        assert_eq!(status(None), PatchStatus::Missing);
+        assert_eq!(status(Some("x=`UUID=$(uuidgen)`")), PatchStatus::Patched);
+        assert_eq!(
+            status(Some("x=`ioreg -rd1 -c IOPlatformExpertDevice`")),
+            PatchStatus::Unpatched
+        );
+        assert_eq!(status(Some("x=readMachineId()")), PatchStatus::Unrecognized);
+    }
+
+    #[test]
+    fn test_reset_refuses_a_corrupted_latest_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = PathDetector::get_storage_path(temp_dir.path());
+        fs::create_dir_all(storage_path.parent().unwrap()).unwrap();
+        fs::write(&storage_path, r#"{"telemetry.machineId": "old"}"#).unwrap();
+        let backups = temp_dir.path().join("backups");
+        fs::create_dir_all(&backups).unwrap();
+        fs::write(backups.join("storage.json.backup_20240101_000000"), "{}").unwrap();
+        fs::write(
+            backups.join("storage.json.backup_20240301_000000"),
+            r#"{"tele"#,
+        )
+        .unwrap();
+
+        let resetter = MachineIdResetter::new(temp_dir.path().to_path_buf());
+        let failed: Vec<ResetCheckKind> = resetter
+            .preflight()
+            .into_iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.kind)
+            .collect();
+        assert_eq!(failed, vec![ResetCheckKind::LatestBackup]);
+
+        let error = resetter.reset().unwrap_err().to_string();
+        assert!(error.contains("latest backup check"), "{}", error);
+        assert!(error.contains("storage.json.backup_20240301_000000"));
+        // Nothing was written, not even another backup
+        assert_eq!(
+            fs::read_to_string(&storage_path).unwrap(),
+            r#"{"telemetry.machineId": "old"}"#
+        );
+        assert_eq!(fs::read_dir(&backups).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_disk_space_check_counts_backup_and_rewrite() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("main.js");
+        fs::write(&path, vec![b'x'; 1024 * 1024]).unwrap();
+        let target = ResetTarget {
+            path: path.clone(),
+            backup_dir: temp_dir.path().to_path_buf(),
+            backup_prefix: "main.js.backup_",
+            validate: validate_storage_backup,
+        };
+        let needed = 2 * 1024 * 1024 + FREE_SPACE_MARGIN;
+
+        let [space, writable, backup] = check_target(&target, &|_| Some(FREE_SPACE_MARGIN));
+        assert!(!space.passed);
+        assert_eq!(space.detail, "64 MB free, 66 MB needed");
+        assert!(writable.passed);
+        assert!(backup.passed);
+
+        let [space, ..] = check_target(&target, &|_| Some(needed));
+        assert!(space.passed);
+        let [space, ..] = check_target(&target, &|_| None);
+        assert!(space.passed);
     }
 }
@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Granularity at which a sleeping task notices `stop()`
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A background job that runs on its own thread at a fixed interval.
+/// The thread exits shortly after `stop()` or when the handle is dropped.
+pub struct PeriodicTask {
+    running: Arc<AtomicBool>,
+}
+
+impl PeriodicTask {
+    /// Run `task` after `initial_delay` and then every `interval`
+    pub fn spawn<F>(name: &str, initial_delay: Duration, interval: Duration, task: F) -> Self
+    where
+        F: Fn() + Send + 'static,
+    {
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+        let name = name.to_string();
+
+        thread::spawn(move || {
+            tracing::debug!("Scheduled task '{}' started", name);
+            let mut delay = initial_delay;
+
+            while sleep_while_running(&flag, delay) {
+                task();
+                delay = interval;
+            }
+            tracing::debug!("Scheduled task '{}' stopped", name);
+        });
+
+        Self { running }
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for PeriodicTask {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Sleep for `duration` in short steps; returns false as soon as the flag is cleared
+fn sleep_while_running(flag: &AtomicBool, duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    loop {
+        if !flag.load(Ordering::SeqCst) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep(STOP_POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_periodic_task_runs_until_stopped() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+
+        let task = PeriodicTask::spawn(
+            "test",
+            Duration::ZERO,
+            Duration::from_millis(10),
+            move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+        thread::sleep(Duration::from_millis(100));
+        task.stop();
+        thread::sleep(Duration::from_millis(50));
+
+        let runs = count.load(Ordering::SeqCst);
+        assert!(runs >= 2);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(count.load(Ordering::SeqCst), runs);
+    }
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// A token or cookie. Debug prints `[REDACTED]`, the bytes are wiped when it
+/// is dropped, and reading the value takes an explicit `expose()`, so every
+/// place a secret leaves this type can be found with grep.
+///
+/// Serializes as the plain string since the CSV and exports store it as is;
+/// mask with `redaction::mask_account` before anything goes to the frontend.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(Zeroizing::new(value.into()))
+    }
+
+    /// The secret itself, for the few places that must send or store it
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted_but_serde_keeps_value() {
+        let secret = SecretString::from("eyJhbGciOiJIUzI1NiJ9.payload.sig");
+        assert_eq!(format!("{:?}", secret), "[REDACTED]");
+        assert_eq!(format!("{:?}", Some(secret.clone())), "Some([REDACTED])");
+
+        let json = serde_json::to_string(&secret).unwrap();
+        assert_eq!(json, "\"eyJhbGciOiJIUzI1NiJ9.payload.sig\"");
+        let back: SecretString = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.expose(), secret.expose());
+    }
+}
@@ -0,0 +1,277 @@
+use crate::csv_manager::CsvManager;
+use crate::database::Database;
+use crate::http::HttpClientFactory;
+use crate::path_detector::PathDetector;
+use crate::redaction;
+use crate::settings::SettingsManager;
+use crate::store_crypto;
+use crate::token_auth::TokenCheck;
+use crate::types::{Account, AccountSource, MembershipStatus};
+use anyhow::{bail, Context, Result};
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Hosts the app talks to, probed with one HEAD request each
+pub const NETWORK_HOSTS: [&str; 2] = ["https://api2.cursor.sh", "https://cursor.com"];
+pub const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of one step of `run_self_test`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub check: String,
+    pub passed: bool,
+    pub duration_ms: u64,
+    /// What the user can do about a failure
+    pub hint: Option<String>,
+    /// The underlying error, with secrets scrubbed
+    pub detail: Option<String>,
+}
+
+/// Where the checks look; nothing in here is modified
+pub struct SelfTestContext {
+    pub settings_path: PathBuf,
+    pub csv_path: PathBuf,
+    pub cursor_base: Option<PathBuf>,
+}
+
+/// Run every check in order. `probe` performs a network request to a URL and
+/// `check_token` asks the API about an access token, so tests can stay offline.
+pub fn run(
+    context: &SelfTestContext,
+    probe: impl Fn(&str) -> Result<()>,
+    check_token: impl Fn(&str) -> TokenCheck,
+) -> Vec<CheckResult> {
+    let mut results = vec![
+        timed("settings", || {
+            SettingsManager::new(context.settings_path.clone()).load()?;
+            Ok(())
+        }),
+        timed("accounts_csv", || check_csv(&context.csv_path)),
+    ];
+
+    let base = context.cursor_base.as_deref();
+    results.push(timed("cursor_path", || match base {
+        Some(base) if base.is_dir() => Ok(()),
+        Some(base) => bail!("Not a directory: {}", base.display()),
+        None => bail!("Cursor path is not set"),
+    }));
+    results.push(timed("cursor_database", || check_database(base)));
+    results.push(timed("cursor_storage", || check_storage(base)));
+
+    for url in NETWORK_HOSTS {
+        let host = url.trim_start_matches("https://");
+        results.push(timed(&format!("network:{}", host), || probe(url)));
+    }
+
+    results.push(timed("current_token", || {
+        let base = base.context("Cursor path is not set")?;
+        let (_, access_token) = Database::new(PathDetector::get_db_path(base))
+            .get_auth_info()
+            .context("No account is logged into Cursor")?;
+        match check_token(&access_token) {
+            TokenCheck::Valid => Ok(()),
+            TokenCheck::Rejected { status } => bail!("Token rejected with HTTP {}", status),
+            TokenCheck::Unreachable(reason) => bail!("Token check failed: {}", reason),
+        }
+    }));
+    results
+}
+
+fn timed(name: &str, check: impl FnOnce() -> Result<()>) -> CheckResult {
+    let started = Instant::now();
+    let outcome = check();
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    match outcome {
+        Ok(()) => CheckResult {
+            check: name.to_string(),
+            passed: true,
+            duration_ms,
+            hint: None,
+            detail: None,
+        },
+        Err(e) => {
+            let detail = redaction::scrub_secrets(&format!("{:#}", e));
+            tracing::warn!("Self-test {} failed: {}", name, detail);
+            CheckResult {
+                check: name.to_string(),
+                passed: false,
+                duration_ms,
+                hint: Some(hint(name, &e).to_string()),
+                detail: Some(detail),
+            }
+        }
+    }
+}
+
+fn hint(check: &str, error: &anyhow::Error) -> &'static str {
+    let permission_denied = error.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+    });
+    if permission_denied {
+        return "Run the app as administrator or fix the permissions of the folder";
+    }
+
+    match check {
+        "settings" => "Fix or delete settings.json in the app data folder; defaults are used when it is missing",
+        "accounts_csv" => "Check that the accounts CSV isn't open in another program and that the disk isn't full",
+        "cursor_path" | "cursor_storage" => "Set the Cursor path in settings",
+        "cursor_database" => "Open Cursor once so it creates its database, or set the Cursor path in settings",
+        "current_token" => "Log into Cursor again or re-authenticate the current account",
+        _ => "Check your internet connection, proxy and firewall",
+    }
+}
+
+/// Add a row to a copy of the CSV and read it back, leaving the real file alone
+fn check_csv(csv_path: &Path) -> Result<()> {
+    let copy_path = std::env::temp_dir().join(format!(
+        "cursor-switcher-self-test-{}-{:08x}.csv",
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+    // Encrypted tokens can only be read under the file's own name, so an
+    // encrypted store is tested with an empty copy
+    if csv_path.exists() && !store_crypto::is_encrypted_store(csv_path) {
+        std::fs::copy(csv_path, &copy_path).context("Failed to copy the accounts CSV")?;
+    }
+
+    let result = (|| {
+        let copy = CsvManager::new(copy_path.clone());
+        copy.ensure_csv_exists()?;
+        let before = copy.read_accounts()?.len();
+        copy.add_account(Account {
+            email: "self-test@localhost".to_string(),
+            days_remaining: Some(0.0),
+            status: MembershipStatus::from("self_test"),
+            source: AccountSource::from("self_test"),
+            ..Default::default()
+        })?;
+        if copy.read_accounts()?.len() != before + 1 {
+            bail!("The test row was not written");
+        }
+        Ok(())
+    })();
+    let _ = std::fs::remove_file(&copy_path);
+
+    // The real file must be writable too, which the copy can't tell
+    if csv_path.exists() && std::fs::metadata(csv_path)?.permissions().readonly() {
+        bail!("The accounts CSV is read-only");
+    }
+    result
+}
+
+// Read-only, so a missing database is reported instead of created
+fn check_database(base: Option<&Path>) -> Result<()> {
+    let db_path = PathDetector::get_db_path(base.context("Cursor path is not set")?);
+    if !db_path.is_file() {
+        bail!("No database at {}", db_path.display());
+    }
+    let conn = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .context("Failed to open Cursor's database")?;
+    conn.query_row("SELECT COUNT(*) FROM ItemTable", [], |row| {
+        row.get::<_, i64>(0)
+    })
+    .context("Cursor's database has no ItemTable")?;
+    Ok(())
+}
+
+fn check_storage(base: Option<&Path>) -> Result<()> {
+    let storage_path = PathDetector::get_storage_path(base.context("Cursor path is not set")?);
+    let content = std::fs::read_to_string(&storage_path)
+        .with_context(|| format!("Failed to read {}", storage_path.display()))?;
+    serde_json::from_str::<serde_json::Value>(&content)
+        .context("storage.json is not valid JSON")?;
+    Ok(())
+}
+
+/// One HEAD request through the configured proxy; any HTTP answer counts as reachable
+pub fn probe_url(http: &HttpClientFactory, url: &str) -> Result<()> {
+    let client = http.with_timeout(NETWORK_TIMEOUT).client()?;
+    client
+        .send(client.head(url))
+        .with_context(|| format!("Could not reach {}", url))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result<'a>(results: &'a [CheckResult], check: &str) -> &'a CheckResult {
+        results.iter().find(|r| r.check == check).unwrap()
+    }
+
+    #[test]
+    fn test_self_test_reports_each_failure_with_a_hint() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base = temp_dir.path().join("cursor");
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(PathDetector::get_storage_path(&base), "{ broken").unwrap();
+        let conn = Connection::open(PathDetector::get_db_path(&base)).unwrap();
+        conn.execute(
+            "CREATE TABLE ItemTable (key TEXT PRIMARY KEY, value TEXT)",
+            [],
+        )
+        .unwrap();
+        Database::new(PathDetector::get_db_path(&base))
+            .update_auth("alice@example.com", "token", None)
+            .unwrap();
+
+        let context = SelfTestContext {
+            settings_path: temp_dir.path().join("settings.json"),
+            csv_path: temp_dir.path().join("accounts.csv"),
+            cursor_base: Some(base),
+        };
+        let results = run(
+            &context,
+            |url| match url {
+                "https://cursor.com" => bail!("connection refused"),
+                _ => Ok(()),
+            },
+            |token| {
+                assert_eq!(token, "token");
+                TokenCheck::Rejected { status: 401 }
+            },
+        );
+
+        for check in ["settings", "accounts_csv", "cursor_path", "cursor_database"] {
+            assert!(result(&results, check).passed, "{} failed", check);
+        }
+        assert!(result(&results, "network:api2.cursor.sh").passed);
+
+        let storage = result(&results, "cursor_storage");
+        assert!(!storage.passed);
+        assert!(storage.detail.as_deref().unwrap().contains("JSON"));
+
+        let network = result(&results, "network:cursor.com");
+        assert!(network.hint.as_deref().unwrap().contains("proxy"));
+        assert!(!result(&results, "current_token").passed);
+
+        // Nothing was created next to the settings or the CSV
+        assert!(!context.settings_path.exists());
+        assert!(!context.csv_path.exists());
+    }
+
+    #[test]
+    fn test_missing_cursor_path_points_to_settings() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = SelfTestContext {
+            settings_path: temp_dir.path().join("settings.json"),
+            csv_path: temp_dir.path().join("accounts.csv"),
+            cursor_base: None,
+        };
+        let results = run(&context, |_| Ok(()), |_| TokenCheck::Valid);
+
+        let cursor_path = result(&results, "cursor_path");
+        assert!(!cursor_path.passed);
+        assert_eq!(
+            cursor_path.hint.as_deref(),
+            Some("Set the Cursor path in settings")
+        );
+        assert!(!result(&results, "cursor_database").passed);
+    }
+}
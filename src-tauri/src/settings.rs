@@ -0,0 +1,944 @@
+use crate::account_backups;
+use crate::account_store::StoreBackend;
+use crate::app_lock::DEFAULT_UNLOCK_MINUTES;
+use crate::auto_export::{self, ExportFormat};
+use crate::coordinator::ReadOnlyPolicy;
+use crate::http::{self, RetryPolicy};
+use crate::i18n::Locale;
+use crate::onboarding::OnboardingStep;
+use crate::operations::SwitchOptions;
+use crate::state_backups;
+use crate::switch_cooldown::CooldownMode;
+use crate::switch_log;
+use crate::tray::{self, TrayLabelStyle, UsageThresholds};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// User-configurable application settings, persisted as JSON in the app data dir.
+/// Every field has a default so settings files written by older versions keep loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Keep the main window hidden on launch and live in the tray only
+    pub start_minimized: bool,
+    /// Start the app automatically when the user logs in
+    pub launch_at_login: bool,
+    /// Serve the local automation API on 127.0.0.1
+    pub http_api_enabled: bool,
+    pub http_api_port: u16,
+    /// Bearer token required by the local API, generated the first time it is enabled
+    pub http_api_token: Option<String>,
+    /// Offer to import Cursor tokens that appear in the clipboard
+    pub clipboard_watcher_enabled: bool,
+    /// Check GitHub for a newer release once a day
+    pub auto_update_check: bool,
+    /// Ask before switching accounts from the tray
+    pub confirm_tray_switch: bool,
+    /// Options used by switches that don't choose their own, like the ones from the tray.
+    /// `default_validate_before_switch` checks the target token with Cursor's API
+    /// before the switch kills Cursor.
+    pub default_reset_machine: bool,
+    pub default_auto_restart: bool,
+    #[serde(alias = "validate_before_switch")]
+    pub default_validate_before_switch: bool,
+    /// Check every few hours whether the active account's token is about to expire
+    pub token_health_check: bool,
+    /// Run the self-test in the background on first launch
+    pub startup_self_test: bool,
+    /// Also notify when a tray switch, sync or refresh succeeds (failures always notify)
+    pub notify_tray_success: bool,
+    /// Write a daily export of all accounts into `auto_export_dir`
+    pub auto_export_enabled: bool,
+    pub auto_export_dir: Option<String>,
+    pub auto_export_format: ExportFormat,
+    /// Number of exports kept in the folder; 0 keeps all of them
+    pub auto_export_retention: u32,
+    /// Write the stored passwords into the exports as well
+    pub auto_export_include_passwords: bool,
+    /// Hash of the app lock password; the lock is off when unset.
+    /// Only `set_app_lock` changes it, `update_settings` keeps the stored value.
+    pub app_lock_hash: Option<String>,
+    /// Minutes an unlock lasts before the app locks again
+    pub app_lock_minutes: u32,
+    /// Prefix tray accounts with colored dots instead of [OK]/[LOW]/[OUT] tags
+    pub tray_status_emoji: bool,
+    /// Usage percentages where tray accounts turn yellow and red
+    pub tray_usage_warning: f64,
+    pub tray_usage_critical: f64,
+    /// Language of the tray menu, dialogs and notifications
+    pub locale: Locale,
+    /// First-run wizard steps the user has completed
+    pub onboarding_steps: Vec<OnboardingStep>,
+    /// Proxy for every request to Cursor and GitHub, e.g. "http://127.0.0.1:8080"
+    pub http_proxy: Option<String>,
+    /// Timeouts, retries and pacing of requests to Cursor's API
+    pub api_policy: ApiPolicy,
+    /// Let "Restart Cursor" launch executables whose name doesn't start with "cursor"
+    pub allow_custom_cursor_executable: bool,
+    /// Private keys for account pool manifests: ed25519 for signing, X25519 for
+    /// reading tokens sealed to this app. Only `generate_manifest_keys` changes them.
+    pub manifest_signing_key: Option<String>,
+    pub manifest_encryption_key: Option<String>,
+    /// Keep the account CSV in sync with a copy on a WebDAV server
+    pub webdav: WebDavSettings,
+    /// Seconds after a tray refresh during which further tray refresh clicks are ignored
+    pub tray_refresh_cooldown_secs: u64,
+    /// List only the accounts with this tag in the tray; None lists all of them
+    pub tray_tag_filter: Option<String>,
+    /// Programs run around every switch; read at each switch, so changes need no restart
+    pub switch_hooks: SwitchHooks,
+    /// What a switch to an account used here less than `cooldown_hours` ago does
+    pub cooldown_mode: CooldownMode,
+    pub cooldown_hours: u32,
+    /// Snapshot Cursor's settings before each machine ID reset and put back
+    /// whatever Cursor wipes, also once it has started again
+    pub preserve_cursor_settings: bool,
+    /// After a switch that restarts Cursor, check for this long that Cursor
+    /// kept the account switched to; 0 turns the check off
+    pub switch_verify_timeout_secs: u64,
+    /// Refuse every command but the reads `READ_ONLY_ALLOWED_COMMANDS` lists;
+    /// turning it off needs the app unlocked
+    pub read_only_mode: bool,
+    /// Whether refreshing usage data is still allowed in read-only mode
+    pub read_only_allow_refresh: bool,
+    /// Accounts last refreshed this many hours ago or earlier are stale
+    pub stale_after_hours: u32,
+    /// Where the accounts are kept; SQLite once the CSV was migrated into it
+    pub account_store: StoreBackend,
+    /// Backups of the account CSV kept in `backups/accounts`; 0 takes none
+    pub account_backup_retention: u32,
+    /// Backups of Cursor's state.vscdb a switch takes in `backups/state`
+    /// before writing the new login; 0 takes none
+    pub state_db_backup_retention: u32,
+    /// Rows kept in `switch_history.csv`, the oldest go first
+    pub switch_history_max_rows: usize,
+    /// Folder the accounts are kept in instead of the app data directory;
+    /// only `set_data_storage_path` changes it
+    pub data_storage_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SwitchHooks {
+    /// Run before the switch starts
+    pub pre: Option<SwitchHook>,
+    /// Run in the background after a successful switch
+    pub post: Option<SwitchHook>,
+}
+
+/// A program run around a switch, see `switch_hooks`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SwitchHook {
+    /// Absolute path of the executable; it is run directly, not through a shell
+    pub path: String,
+    /// Arguments, where `{email}`, `{status}` and `{reset_machine}` are filled in
+    pub args: Vec<String>,
+    /// Seconds before the hook is killed and counted as failed
+    pub timeout_secs: u64,
+    /// Pre-switch only: wait for the hook and cancel the switch if it fails
+    pub blocking: bool,
+}
+
+impl Default for SwitchHook {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            args: Vec::new(),
+            timeout_secs: 30,
+            blocking: false,
+        }
+    }
+}
+
+impl SwitchHook {
+    pub fn validate(&self) -> Result<()> {
+        if !Path::new(self.path.trim()).is_absolute() {
+            bail!("Switch hook path must be absolute");
+        }
+        if !(1..=600).contains(&self.timeout_secs) {
+            bail!("Switch hook timeout must be between 1 and 600 seconds");
+        }
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+impl SwitchHooks {
+    pub fn validate(&self) -> Result<()> {
+        self.pre
+            .iter()
+            .chain(&self.post)
+            .try_for_each(SwitchHook::validate)
+    }
+}
+
+/// Where the synced copy of the accounts lives. The password is kept in the
+/// OS keychain, see `set_webdav_password`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebDavSettings {
+    pub enabled: bool,
+    /// Server URL, e.g. "https://cloud.example.com/remote.php/dav/files/me"
+    pub url: String,
+    /// File below `url` holding the accounts
+    pub path: String,
+    pub username: String,
+    /// Minutes between background syncs; 0 syncs only on request
+    pub interval_minutes: u32,
+}
+
+impl Default for WebDavSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            path: "cursor-account-switcher/accounts.csv".to_string(),
+            username: String::new(),
+            interval_minutes: 30,
+        }
+    }
+}
+
+impl WebDavSettings {
+    /// Reject settings a sync could never work with; disabled settings are only saved
+    pub fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        match url::Url::parse(self.url.trim()) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => bail!("WebDAV URL must be an http or https address"),
+        }
+        let path = self.path.trim().trim_start_matches('/');
+        if path.is_empty() || path.ends_with('/') {
+            bail!("WebDAV path must name a file");
+        }
+        if self.interval_minutes != 0 && !(5..=1440).contains(&self.interval_minutes) {
+            bail!("WebDAV sync interval must be 0 or between 5 and 1440 minutes");
+        }
+        Ok(())
+    }
+
+    /// How often the background sync runs, None when it is off
+    pub fn interval(&self) -> Option<Duration> {
+        (self.enabled && self.interval_minutes > 0)
+            .then(|| Duration::from_secs(u64::from(self.interval_minutes) * 60))
+    }
+}
+
+/// How the app talks to Cursor's API. Changes apply to the next request
+/// without a restart.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiPolicy {
+    /// Seconds before a request is given up
+    pub timeout_secs: u64,
+    /// Extra attempts after a connection failure, gateway error or rate limit
+    pub max_retries: u32,
+    /// Wait before the first retry, growing linearly with each further one
+    pub backoff_base_ms: u64,
+    /// Accounts a batch refresh works on at the same time
+    pub max_concurrency: usize,
+    /// Gap between consecutive requests, shared by all clients
+    pub min_request_spacing_ms: u64,
+}
+
+impl Default for ApiPolicy {
+    fn default() -> Self {
+        Self {
+            timeout_secs: http::DEFAULT_TIMEOUT.as_secs(),
+            max_retries: http::MAX_RETRIES,
+            backoff_base_ms: http::RETRY_BACKOFF.as_millis() as u64,
+            max_concurrency: 4,
+            min_request_spacing_ms: http::MIN_REQUEST_INTERVAL.as_millis() as u64,
+        }
+    }
+}
+
+impl ApiPolicy {
+    /// Reject values that would hang the app or hammer the API
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=300).contains(&self.timeout_secs) {
+            bail!("API timeout must be between 1 and 300 seconds");
+        }
+        if self.max_retries > 10 {
+            bail!("API retries must be at most 10");
+        }
+        if self.backoff_base_ms > 60_000 {
+            bail!("API retry backoff must be at most 60000 ms");
+        }
+        if !(1..=16).contains(&self.max_concurrency) {
+            bail!("API concurrency must be between 1 and 16");
+        }
+        if self.min_request_spacing_ms > 10_000 {
+            bail!("API request spacing must be at most 10000 ms");
+        }
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries,
+            backoff: Duration::from_millis(self.backoff_base_ms),
+        }
+    }
+
+    pub fn request_spacing(&self) -> Duration {
+        Duration::from_millis(self.min_request_spacing_ms)
+    }
+}
+
+/// Default port of the local automation API
+pub const DEFAULT_HTTP_API_PORT: u16 = 47821;
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            start_minimized: false,
+            launch_at_login: false,
+            http_api_enabled: false,
+            http_api_port: DEFAULT_HTTP_API_PORT,
+            http_api_token: None,
+            clipboard_watcher_enabled: false,
+            auto_update_check: true,
+            confirm_tray_switch: true,
+            default_reset_machine: false,
+            default_auto_restart: false,
+            default_validate_before_switch: true,
+            token_health_check: true,
+            startup_self_test: true,
+            notify_tray_success: true,
+            auto_export_enabled: false,
+            auto_export_dir: None,
+            auto_export_format: ExportFormat::Csv,
+            auto_export_retention: auto_export::DEFAULT_RETENTION,
+            auto_export_include_passwords: false,
+            app_lock_hash: None,
+            app_lock_minutes: DEFAULT_UNLOCK_MINUTES,
+            tray_status_emoji: true,
+            tray_usage_warning: tray::USAGE_WARNING_THRESHOLD,
+            tray_usage_critical: tray::USAGE_CRITICAL_THRESHOLD,
+            locale: Locale::En,
+            onboarding_steps: Vec::new(),
+            http_proxy: None,
+            api_policy: ApiPolicy::default(),
+            allow_custom_cursor_executable: false,
+            manifest_signing_key: None,
+            manifest_encryption_key: None,
+            webdav: WebDavSettings::default(),
+            tray_refresh_cooldown_secs: 60,
+            tray_tag_filter: None,
+            switch_hooks: SwitchHooks::default(),
+            cooldown_mode: CooldownMode::Off,
+            cooldown_hours: 4,
+            preserve_cursor_settings: false,
+            switch_verify_timeout_secs: 90,
+            read_only_mode: false,
+            read_only_allow_refresh: true,
+            stale_after_hours: tray::STALE_AFTER_HOURS,
+            account_store: StoreBackend::Csv,
+            account_backup_retention: account_backups::DEFAULT_RETENTION,
+            state_db_backup_retention: state_backups::DEFAULT_RETENTION,
+            switch_history_max_rows: switch_log::DEFAULT_MAX_ROWS,
+            data_storage_dir: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Copy safe to hand to the frontend: the app lock hash and manifest keys stay on disk
+    pub fn without_secrets(mut self) -> Self {
+        self.app_lock_hash = None;
+        self.manifest_signing_key = None;
+        self.manifest_encryption_key = None;
+        self
+    }
+
+    pub fn tray_label_style(&self) -> TrayLabelStyle {
+        TrayLabelStyle {
+            emoji: self.tray_status_emoji,
+            thresholds: UsageThresholds {
+                warning: self.tray_usage_warning,
+                critical: self.tray_usage_critical,
+            },
+            stale_after_hours: Some(self.stale_after_hours),
+        }
+    }
+
+    pub fn tray_refresh_cooldown(&self) -> Duration {
+        Duration::from_secs(self.tray_refresh_cooldown_secs)
+    }
+
+    /// How long an account rests after use, None when cooldowns are off
+    pub fn cooldown_window(&self) -> Option<chrono::Duration> {
+        (self.cooldown_mode != CooldownMode::Off)
+            .then(|| chrono::Duration::hours(i64::from(self.cooldown_hours)))
+    }
+
+    pub fn read_only_policy(&self) -> ReadOnlyPolicy {
+        ReadOnlyPolicy {
+            enabled: self.read_only_mode,
+            allow_refresh: self.read_only_allow_refresh,
+        }
+    }
+
+    /// Whether saving `self` over `previous` changes more than the cosmetic fields
+    pub fn changes_beyond_cosmetic(&self, previous: &Settings) -> bool {
+        let without_cosmetic = |settings: &Settings| match serde_json::to_value(settings) {
+            Ok(Value::Object(mut fields)) => {
+                for field in COSMETIC_FIELDS {
+                    fields.remove(*field);
+                }
+                Some(fields)
+            }
+            _ => None,
+        };
+        match (without_cosmetic(self), without_cosmetic(previous)) {
+            (Some(fields), Some(previous)) => fields != previous,
+            _ => true,
+        }
+    }
+
+    /// What a switch does when the caller doesn't say, e.g. from the tray
+    pub fn default_switch_options(&self) -> SwitchOptions {
+        SwitchOptions {
+            reset_machine: self.default_reset_machine,
+            restart: self.default_auto_restart,
+            validate_token: self.default_validate_before_switch,
+            preserve_settings: self.preserve_cursor_settings,
+            state_db_backup_retention: self.state_db_backup_retention,
+        }
+    }
+}
+
+/// Only change how the app looks. With an app lock set, saving any other
+/// field needs the app unlocked: exports, WebDAV, hooks and the HTTP API all
+/// send tokens somewhere or run programs.
+const COSMETIC_FIELDS: &[&str] = &[
+    "start_minimized",
+    "notify_tray_success",
+    "tray_status_emoji",
+    "tray_usage_warning",
+    "tray_usage_critical",
+    "locale",
+    "onboarding_steps",
+    "tray_tag_filter",
+    "stale_after_hours",
+];
+
+/// Marks settings files written by `export_settings`
+const EXPORT_FORMAT: &str = "cursor-account-switcher-settings";
+/// Never leave this machine: the HTTP API token, the app lock hash, the
+/// manifest keys, and the switch hooks since importing them would run programs
+const SECRET_FIELDS: &[&str] = &[
+    "http_api_token",
+    "app_lock_hash",
+    "manifest_signing_key",
+    "manifest_encryption_key",
+    "switch_hooks",
+];
+/// Only meaningful on the machine they were set on; exported on request
+const MACHINE_SPECIFIC_FIELDS: &[&str] = &[
+    "launch_at_login",
+    "auto_export_dir",
+    "data_storage_dir",
+    "onboarding_steps",
+    "http_proxy",
+    "allow_custom_cursor_executable",
+];
+
+/// Export document for sharing settings without any account data or secrets
+pub fn export_settings(settings: &Settings, include_machine_specific: bool) -> Result<Value> {
+    let mut fields = match serde_json::to_value(settings)? {
+        Value::Object(fields) => fields,
+        _ => bail!("Settings did not serialize to an object"),
+    };
+    for field in SECRET_FIELDS {
+        fields.remove(*field);
+    }
+    if !include_machine_specific {
+        for field in MACHINE_SPECIFIC_FIELDS {
+            fields.remove(*field);
+        }
+    }
+
+    Ok(serde_json::json!({
+        "format": EXPORT_FORMAT,
+        "settings": fields,
+    }))
+}
+
+/// Result of applying an exported settings document
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedSettings {
+    pub settings: Settings,
+    /// Keys this version doesn't know, e.g. from a newer release
+    pub unknown_keys: Vec<String>,
+    /// Secrets found in the file, which are never imported
+    pub ignored_keys: Vec<String>,
+}
+
+/// Apply an exported document (or a bare settings object) on top of `current`.
+/// Merging keeps every field the document doesn't mention; otherwise those
+/// fields go back to their defaults. Secrets always stay as they are.
+pub fn import_settings(
+    current: &Settings,
+    document: &Value,
+    merge: bool,
+) -> Result<ImportedSettings> {
+    let imported = match document.get("settings") {
+        Some(settings) if document.get("format").and_then(Value::as_str) == Some(EXPORT_FORMAT) => {
+            settings
+        }
+        _ => document,
+    };
+    let Value::Object(imported) = imported else {
+        bail!("Settings file must contain a JSON object");
+    };
+
+    let base = if merge {
+        current.clone()
+    } else {
+        Settings::default()
+    };
+    let Value::Object(mut fields) = serde_json::to_value(&base)? else {
+        bail!("Settings did not serialize to an object");
+    };
+
+    let mut unknown_keys = Vec::new();
+    let mut ignored_keys = Vec::new();
+    for (key, value) in imported {
+        if SECRET_FIELDS.contains(&key.as_str()) {
+            ignored_keys.push(key.clone());
+        } else if fields.contains_key(key) {
+            fields.insert(key.clone(), value.clone());
+        } else {
+            unknown_keys.push(key.clone());
+        }
+    }
+
+    let mut settings: Settings = serde_json::from_value(Value::Object(fields))
+        .context("Settings file doesn't match the settings schema")?;
+    settings.http_api_token = current.http_api_token.clone();
+    settings.app_lock_hash = current.app_lock_hash.clone();
+    settings.manifest_signing_key = current.manifest_signing_key.clone();
+    settings.manifest_encryption_key = current.manifest_encryption_key.clone();
+    settings.switch_hooks = current.switch_hooks.clone();
+    // Moving the accounts takes `set_data_storage_path`
+    settings.data_storage_dir = current.data_storage_dir.clone();
+
+    Ok(ImportedSettings {
+        settings,
+        unknown_keys,
+        ignored_keys,
+    })
+}
+
+/// Returned to the frontend after `import_settings`
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsImportReport {
+    pub settings: Settings,
+    pub unknown_keys: Vec<String>,
+    pub ignored_keys: Vec<String>,
+    /// Copy of the settings as they were before the import
+    pub backup_path: Option<String>,
+}
+
+pub struct SettingsManager {
+    file_path: PathBuf,
+}
+
+impl SettingsManager {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    /// Load settings from disk, falling back to defaults when the file doesn't exist yet
+    pub fn load(&self) -> Result<Settings> {
+        if !self.file_path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let content =
+            fs::read_to_string(&self.file_path).context("Failed to read settings file")?;
+        let settings = serde_json::from_str(&content).context("Failed to parse settings file")?;
+        Ok(settings)
+    }
+
+    pub fn save(&self, settings: &Settings) -> Result<()> {
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(settings)?;
+        fs::write(&self.file_path, content).context("Failed to write settings file")?;
+        Ok(())
+    }
+
+    /// Copy the current settings file next to itself before it gets replaced
+    pub fn backup(&self) -> Result<Option<PathBuf>> {
+        if !self.file_path.exists() {
+            return Ok(None);
+        }
+        let backup_path = self.file_path.with_extension("pre-import.json");
+        fs::copy(&self.file_path, &backup_path).context("Failed to back up settings")?;
+        Ok(Some(backup_path))
+    }
+
+    /// Load, modify and save the settings in one step
+    pub fn update<F: FnOnce(&mut Settings)>(&self, f: F) -> Result<Settings> {
+        let mut settings = self.load()?;
+        f(&mut settings);
+        self.save(&settings)?;
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_manager() -> (SettingsManager, tempfile::TempDir) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manager = SettingsManager::new(temp_dir.path().join("settings.json"));
+        (manager, temp_dir)
+    }
+
+    #[test]
+    fn test_load_missing_file_returns_defaults() {
+        let (manager, _temp_dir) = create_test_manager();
+
+        let settings = manager.load().unwrap();
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn test_save_and_load_settings() {
+        let (manager, _temp_dir) = create_test_manager();
+
+        let settings = Settings {
+            start_minimized: true,
+            launch_at_login: true,
+            http_api_enabled: true,
+            http_api_port: 8080,
+            http_api_token: Some("token".to_string()),
+            clipboard_watcher_enabled: true,
+            auto_update_check: false,
+            confirm_tray_switch: false,
+            default_reset_machine: true,
+            default_auto_restart: true,
+            default_validate_before_switch: false,
+            token_health_check: false,
+            startup_self_test: false,
+            notify_tray_success: false,
+            auto_export_enabled: true,
+            auto_export_dir: Some("/backups".to_string()),
+            auto_export_format: ExportFormat::Json,
+            auto_export_retention: 3,
+            auto_export_include_passwords: true,
+            app_lock_hash: Some("hash".to_string()),
+            app_lock_minutes: 5,
+            tray_status_emoji: false,
+            tray_usage_warning: 40.0,
+            tray_usage_critical: 90.0,
+            locale: Locale::ZhCn,
+            onboarding_steps: vec![OnboardingStep::CursorPath],
+            http_proxy: Some("http://127.0.0.1:8080".to_string()),
+            api_policy: ApiPolicy {
+                timeout_secs: 10,
+                max_retries: 0,
+                backoff_base_ms: 250,
+                max_concurrency: 2,
+                min_request_spacing_ms: 0,
+            },
+            allow_custom_cursor_executable: true,
+            manifest_signing_key: Some("signing".to_string()),
+            manifest_encryption_key: Some("encryption".to_string()),
+            webdav: WebDavSettings {
+                enabled: true,
+                url: "https://dav.example.com/files/me".to_string(),
+                path: "accounts.csv".to_string(),
+                username: "me".to_string(),
+                interval_minutes: 0,
+            },
+            tray_refresh_cooldown_secs: 0,
+            tray_tag_filter: Some("work".to_string()),
+            switch_hooks: SwitchHooks {
+                pre: Some(SwitchHook {
+                    path: "/usr/local/bin/close-editors".to_string(),
+                    args: vec!["{email}".to_string()],
+                    timeout_secs: 5,
+                    blocking: true,
+                }),
+                post: None,
+            },
+            cooldown_mode: CooldownMode::Enforce,
+            cooldown_hours: 12,
+            preserve_cursor_settings: true,
+            switch_verify_timeout_secs: 120,
+            read_only_mode: true,
+            read_only_allow_refresh: false,
+            stale_after_hours: 48,
+            account_store: StoreBackend::Sqlite,
+            account_backup_retention: 5,
+            state_db_backup_retention: 0,
+            switch_history_max_rows: 200,
+            data_storage_dir: Some("/synced/accounts".to_string()),
+        };
+        manager.save(&settings).unwrap();
+
+        assert_eq!(manager.load().unwrap(), settings);
+    }
+
+    #[test]
+    fn test_load_partial_settings_file() {
+        let (manager, _temp_dir) = create_test_manager();
+        fs::write(&manager.file_path, r#"{"start_minimized": true}"#).unwrap();
+
+        let settings = manager.load().unwrap();
+        assert!(settings.start_minimized);
+        assert!(!settings.launch_at_login);
+        assert_eq!(settings.http_api_port, DEFAULT_HTTP_API_PORT);
+        assert!(settings.auto_update_check);
+        assert!(settings.confirm_tray_switch);
+        assert_eq!(settings.locale, Locale::En);
+    }
+
+    #[test]
+    fn test_default_switch_options() {
+        let (manager, _temp_dir) = create_test_manager();
+        // Files written before the defaults were configurable used the old key
+        fs::write(
+            &manager.file_path,
+            r#"{"validate_before_switch": false, "default_auto_restart": true}"#,
+        )
+        .unwrap();
+
+        let settings = manager.load().unwrap();
+        assert_eq!(
+            settings.default_switch_options(),
+            SwitchOptions {
+                reset_machine: false,
+                restart: true,
+                validate_token: false,
+                preserve_settings: false,
+                state_db_backup_retention: state_backups::DEFAULT_RETENTION,
+            }
+        );
+    }
+
+    #[test]
+    fn test_export_strips_secrets_and_machine_paths() {
+        let settings = Settings {
+            http_api_token: Some("token".to_string()),
+            app_lock_hash: Some("hash".to_string()),
+            auto_export_dir: Some("/backups".to_string()),
+            tray_usage_warning: 30.0,
+            ..Settings::default()
+        };
+
+        let exported = export_settings(&settings, false).unwrap();
+        let fields = exported["settings"].as_object().unwrap();
+        assert_eq!(fields["tray_usage_warning"], 30.0);
+        assert!(!fields.contains_key("http_api_token"));
+        assert!(!fields.contains_key("app_lock_hash"));
+        assert!(!fields.contains_key("auto_export_dir"));
+
+        let exported = export_settings(&settings, true).unwrap();
+        assert_eq!(exported["settings"]["auto_export_dir"], "/backups");
+        assert!(exported["settings"].get("http_api_token").is_none());
+    }
+
+    #[test]
+    fn test_import_merge_and_replace() {
+        let current = Settings {
+            start_minimized: true,
+            app_lock_hash: Some("hash".to_string()),
+            ..Settings::default()
+        };
+        let document = serde_json::json!({
+            "format": EXPORT_FORMAT,
+            "settings": {
+                "tray_usage_critical": 95.0,
+                "app_lock_hash": "attacker",
+                "proxy_url": "http://proxy",
+            }
+        });
+
+        let merged = import_settings(&current, &document, true).unwrap();
+        assert!(merged.settings.start_minimized);
+        assert_eq!(merged.settings.tray_usage_critical, 95.0);
+        assert_eq!(merged.settings.app_lock_hash.as_deref(), Some("hash"));
+        assert_eq!(merged.unknown_keys, vec!["proxy_url"]);
+        assert_eq!(merged.ignored_keys, vec!["app_lock_hash"]);
+
+        let replaced = import_settings(&current, &document, false).unwrap();
+        assert!(!replaced.settings.start_minimized);
+        assert_eq!(replaced.settings.tray_usage_critical, 95.0);
+        assert_eq!(replaced.settings.app_lock_hash.as_deref(), Some("hash"));
+
+        let invalid = serde_json::json!({ "http_api_port": "not a port" });
+        assert!(import_settings(&current, &invalid, true).is_err());
+        assert!(import_settings(&current, &serde_json::json!([1]), true).is_err());
+    }
+
+    #[test]
+    fn test_api_policy_bounds() {
+        assert!(ApiPolicy::default().validate().is_ok());
+        let rejected = |policy: ApiPolicy| policy.validate().unwrap_err().to_string();
+        let default = ApiPolicy::default();
+
+        assert_eq!(
+            rejected(ApiPolicy {
+                timeout_secs: 0,
+                ..default
+            }),
+            "API timeout must be between 1 and 300 seconds"
+        );
+        assert_eq!(
+            rejected(ApiPolicy {
+                max_retries: 11,
+                ..default
+            }),
+            "API retries must be at most 10"
+        );
+        assert_eq!(
+            rejected(ApiPolicy {
+                backoff_base_ms: 60_001,
+                ..default
+            }),
+            "API retry backoff must be at most 60000 ms"
+        );
+        assert_eq!(
+            rejected(ApiPolicy {
+                max_concurrency: 0,
+                ..default
+            }),
+            "API concurrency must be between 1 and 16"
+        );
+        assert_eq!(
+            rejected(ApiPolicy {
+                min_request_spacing_ms: 10_001,
+                ..default
+            }),
+            "API request spacing must be at most 10000 ms"
+        );
+    }
+
+    #[test]
+    fn test_switch_hooks_are_validated_and_never_imported() {
+        let hook = SwitchHook {
+            path: "/usr/local/bin/prompt-update".to_string(),
+            ..SwitchHook::default()
+        };
+        let hooks = SwitchHooks {
+            pre: None,
+            post: Some(hook.clone()),
+        };
+        assert!(hooks.validate().is_ok());
+        let relative = SwitchHooks {
+            pre: Some(SwitchHook {
+                path: "prompt-update".to_string(),
+                ..hook.clone()
+            }),
+            ..hooks.clone()
+        };
+        assert!(relative.validate().is_err());
+        let endless = SwitchHook {
+            timeout_secs: 0,
+            ..hook
+        };
+        assert!(endless.validate().is_err());
+
+        let document = serde_json::json!({
+            "switch_hooks": { "post": { "path": "/tmp/evil" } },
+        });
+        let imported = import_settings(&Settings::default(), &document, true).unwrap();
+        assert_eq!(imported.settings.switch_hooks, SwitchHooks::default());
+        assert_eq!(imported.ignored_keys, vec!["switch_hooks"]);
+    }
+
+    #[test]
+    fn test_cosmetic_changes_are_told_apart() {
+        let previous = Settings::default();
+        let cosmetic = Settings {
+            locale: Locale::ZhCn,
+            tray_status_emoji: false,
+            tray_tag_filter: Some("work".to_string()),
+            ..previous.clone()
+        };
+        assert!(!cosmetic.changes_beyond_cosmetic(&previous));
+
+        let exporting = Settings {
+            auto_export_enabled: true,
+            auto_export_dir: Some("/tmp/exports".to_string()),
+            ..cosmetic.clone()
+        };
+        assert!(exporting.changes_beyond_cosmetic(&previous));
+        let syncing = Settings {
+            webdav: WebDavSettings {
+                url: "https://dav.example.com".to_string(),
+                ..WebDavSettings::default()
+            },
+            ..previous.clone()
+        };
+        assert!(syncing.changes_beyond_cosmetic(&previous));
+    }
+
+    #[test]
+    fn test_webdav_settings_bounds() {
+        let enabled = WebDavSettings {
+            enabled: true,
+            url: "https://dav.example.com/files/me".to_string(),
+            ..WebDavSettings::default()
+        };
+        assert!(enabled.validate().is_ok());
+        assert_eq!(enabled.interval(), Some(Duration::from_secs(30 * 60)));
+        // Nothing is checked until sync is turned on
+        assert!(WebDavSettings::default().validate().is_ok());
+        assert_eq!(WebDavSettings::default().interval(), None);
+
+        let rejected = |settings: WebDavSettings| settings.validate().unwrap_err().to_string();
+        assert_eq!(
+            rejected(WebDavSettings {
+                url: "ftp://dav.example.com".to_string(),
+                ..enabled.clone()
+            }),
+            "WebDAV URL must be an http or https address"
+        );
+        assert_eq!(
+            rejected(WebDavSettings {
+                path: "backups/".to_string(),
+                ..enabled.clone()
+            }),
+            "WebDAV path must name a file"
+        );
+        assert_eq!(
+            rejected(WebDavSettings {
+                interval_minutes: 1,
+                ..enabled.clone()
+            }),
+            "WebDAV sync interval must be 0 or between 5 and 1440 minutes"
+        );
+    }
+
+    #[test]
+    fn test_update_settings() {
+        let (manager, _temp_dir) = create_test_manager();
+
+        manager.update(|s| s.launch_at_login = true).unwrap();
+        assert!(manager.load().unwrap().launch_at_login);
+    }
+}
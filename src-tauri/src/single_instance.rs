@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const ACK: &str = "ok";
+
+/// Keeps a single running app instance. The primary instance listens on a
+/// loopback port recorded in a file inside the app data dir; later launches
+/// (e.g. from clicking a link) hand their arguments to it and exit.
+pub struct SingleInstance {
+    port_file: PathBuf,
+}
+
+/// What a secondary launch asks the running instance to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstanceMessage {
+    Focus,
+    OpenUrl(String),
+}
+
+impl InstanceMessage {
+    fn encode(&self) -> String {
+        match self {
+            InstanceMessage::Focus => "focus".to_string(),
+            InstanceMessage::OpenUrl(url) => format!("open {}", url),
+        }
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        match line.split_once(' ') {
+            Some(("open", url)) if !url.is_empty() => {
+                Some(InstanceMessage::OpenUrl(url.to_string()))
+            }
+            None if line == "focus" => Some(InstanceMessage::Focus),
+            _ => None,
+        }
+    }
+}
+
+impl SingleInstance {
+    pub fn new(port_file: PathBuf) -> Self {
+        Self { port_file }
+    }
+
+    /// Try to hand the message to an already running instance.
+    /// Returns true when it was accepted and this process should exit.
+    pub fn forward(&self, message: &InstanceMessage) -> bool {
+        let port = match std::fs::read_to_string(&self.port_file)
+            .ok()
+            .and_then(|content| content.trim().parse::<u16>().ok())
+        {
+            Some(port) => port,
+            None => return false,
+        };
+
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let stream = match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => stream,
+            // Stale port file from an instance that didn't exit cleanly
+            Err(_) => return false,
+        };
+
+        let _ = stream.set_read_timeout(Some(CONNECT_TIMEOUT));
+        let mut writer = &stream;
+        if writeln!(writer, "{}", message.encode()).is_err() {
+            return false;
+        }
+
+        // Only trust the port if the listener speaks our protocol
+        let mut reply = String::new();
+        BufReader::new(&stream).read_line(&mut reply).is_ok() && reply.trim() == ACK
+    }
+
+    /// Become the primary instance and pass forwarded messages to `on_message`
+    pub fn listen<F>(&self, on_message: F) -> Result<()>
+    where
+        F: Fn(InstanceMessage) + Send + 'static,
+    {
+        let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .context("Failed to bind single-instance listener")?;
+        let port = listener.local_addr()?.port();
+
+        if let Some(parent) = self.port_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.port_file, port.to_string())
+            .context("Failed to write single-instance port file")?;
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        tracing::debug!("Single-instance connection failed: {}", e);
+                        continue;
+                    }
+                };
+
+                let _ = stream.set_read_timeout(Some(CONNECT_TIMEOUT));
+                let mut line = String::new();
+                if BufReader::new(&stream).read_line(&mut line).is_err() {
+                    continue;
+                }
+
+                match InstanceMessage::decode(&line) {
+                    Some(message) => {
+                        let mut writer = &stream;
+                        let _ = writeln!(writer, "{}", ACK);
+                        on_message(message);
+                    }
+                    None => tracing::debug!("Ignoring unknown single-instance message"),
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_message_roundtrip() {
+        let open = InstanceMessage::OpenUrl("cursor-switcher://switch?email=a@b.c".to_string());
+        assert_eq!(InstanceMessage::decode(&open.encode()), Some(open));
+        assert_eq!(
+            InstanceMessage::decode("focus\n"),
+            Some(InstanceMessage::Focus)
+        );
+        assert_eq!(InstanceMessage::decode("GET / HTTP/1.1"), None);
+    }
+
+    #[test]
+    fn test_forward_without_running_instance() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let instance = SingleInstance::new(temp_dir.path().join("instance.port"));
+        assert!(!instance.forward(&InstanceMessage::Focus));
+    }
+
+    #[test]
+    fn test_forward_to_running_instance() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let port_file = temp_dir.path().join("instance.port");
+
+        let (tx, rx) = mpsc::channel();
+        SingleInstance::new(port_file.clone())
+            .listen(move |message| tx.send(message).unwrap())
+            .unwrap();
+
+        let message = InstanceMessage::OpenUrl("cursor-switcher://import?token=x".to_string());
+        assert!(SingleInstance::new(port_file).forward(&message));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), message);
+    }
+}
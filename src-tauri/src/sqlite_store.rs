@@ -0,0 +1,613 @@
+// The accounts in a SQLite database, one row per account in the order they
+// were stored. Rows keep the CSV's semantics: an email may appear more than
+// once, updates touch its first row and deletes remove all of them.
+
+use crate::account_store::AccountStore;
+use crate::csv_manager::{append, format_extra_headers, parse_extra_headers, CorruptRow};
+use crate::file_cache;
+use crate::secret::SecretString;
+use crate::store_crypto::{self, StoreKey};
+use crate::tags;
+use crate::types::{Account, AccountSource, MembershipStatus};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction, TransactionBehavior};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS accounts (
+    position INTEGER PRIMARY KEY,
+    idx INTEGER NOT NULL,
+    email TEXT NOT NULL,
+    access_token TEXT NOT NULL,
+    refresh_token TEXT NOT NULL,
+    cookie TEXT NOT NULL,
+    days_remaining REAL,
+    status TEXT NOT NULL,
+    record_time TEXT NOT NULL,
+    source TEXT NOT NULL,
+    usage_used REAL,
+    usage_remaining REAL,
+    usage_total REAL,
+    usage_percentage REAL,
+    label_emoji TEXT,
+    label_color TEXT,
+    privacy_mode INTEGER,
+    extra_headers TEXT,
+    pool TEXT,
+    status_reason TEXT,
+    tags TEXT,
+    favorite INTEGER,
+    last_used TEXT,
+    archived INTEGER,
+    password TEXT,
+    nickname TEXT
+);
+CREATE INDEX IF NOT EXISTS accounts_email ON accounts (email);";
+
+const COLUMNS: &str = "idx, email, access_token, refresh_token, cookie, days_remaining, status, \
+    record_time, source, usage_used, usage_remaining, usage_total, usage_percentage, \
+    label_emoji, label_color, privacy_mode, extra_headers, pool, status_reason, tags, \
+    favorite, last_used, archived, password, nickname";
+
+// Columns added after the first version of the table, created in older
+// databases on open
+const ADDED_COLUMNS: [(&str, &str); 6] = [
+    ("tags", "TEXT"),
+    ("favorite", "INTEGER"),
+    ("last_used", "TEXT"),
+    ("archived", "INTEGER"),
+    ("password", "TEXT"),
+    ("nickname", "TEXT"),
+];
+
+// How long a write waits for another process's write to finish
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct SqliteAccountStore {
+    path: PathBuf,
+}
+
+impl SqliteAccountStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let conn = Connection::open(&self.path).context("Failed to open the account database")?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create the account table")?;
+        add_missing_columns(&conn).context("Failed to upgrade the account table")?;
+        Ok(conn)
+    }
+
+    fn token_key(&self) -> Result<Option<StoreKey>> {
+        store_crypto::token_key(&self.path)
+    }
+
+    // Run `f` in a transaction that holds the write lock from the start, so
+    // read-modify-write cycles of the tray and the window take turns
+    fn write<T>(&self, f: impl FnOnce(&Transaction) -> Result<T>) -> Result<T> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let result = f(&tx)?;
+        tx.commit()
+            .context("Failed to write the account database")?;
+        file_cache::note_write(&self.path);
+        Ok(result)
+    }
+
+    fn has_plaintext_tokens(&self) -> Result<bool> {
+        let conn = self.connect()?;
+        let mut statement =
+            conn.prepare("SELECT access_token, refresh_token, cookie FROM accounts")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            for i in 0..3 {
+                let value: String = row.get(i)?;
+                if !value.is_empty() && !store_crypto::is_encrypted(&value) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn add_missing_columns(conn: &Connection) -> Result<()> {
+    let existing: HashSet<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('accounts')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (name, kind) in ADDED_COLUMNS {
+        if !existing.contains(name) {
+            conn.execute_batch(&format!(
+                "ALTER TABLE accounts ADD COLUMN {} {}",
+                name, kind
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+fn read_all(conn: &Connection, key: Option<&StoreKey>) -> Result<Vec<Account>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT {} FROM accounts ORDER BY position",
+        COLUMNS
+    ))?;
+    let accounts = statement
+        .query_map([], account_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .context("Failed to read the account database")?;
+    accounts
+        .into_iter()
+        .map(|account| store_crypto::open_tokens(account, key))
+        .collect()
+}
+
+// Number the rows 1..N by position; returns how many changed
+fn renumber(tx: &Transaction) -> Result<usize> {
+    Ok(tx.execute(
+        "UPDATE accounts SET idx = (SELECT COUNT(*) FROM accounts AS earlier \
+         WHERE earlier.position <= accounts.position) \
+         WHERE idx != (SELECT COUNT(*) FROM accounts AS earlier \
+         WHERE earlier.position <= accounts.position)",
+        [],
+    )?)
+}
+
+fn replace_all(tx: &Transaction, accounts: &[Account], key: Option<&StoreKey>) -> Result<()> {
+    tx.execute("DELETE FROM accounts", [])?;
+    let mut statement = tx.prepare(&format!(
+        "INSERT INTO accounts ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, \
+         ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25)",
+        COLUMNS
+    ))?;
+    for account in accounts {
+        let row = StoredRow::of(account, key)?;
+        statement.execute(params![
+            account.index,
+            account.email,
+            row.access_token,
+            row.refresh_token,
+            row.cookie,
+            account.days_remaining,
+            account.status.as_str(),
+            account.record_time,
+            account.source.as_str(),
+            account.usage_used,
+            account.usage_remaining,
+            account.usage_total,
+            account.usage_percentage,
+            account.label_emoji,
+            account.label_color,
+            account.privacy_mode,
+            row.extra_headers,
+            account.pool,
+            account.status_reason,
+            row.tags,
+            account.favorite,
+            account.last_used,
+            account.archived,
+            row.password,
+            account.nickname,
+        ])?;
+    }
+    Ok(())
+}
+
+// The columns that are stored differently from the account's fields
+struct StoredRow {
+    access_token: String,
+    refresh_token: String,
+    cookie: String,
+    extra_headers: Option<String>,
+    tags: Option<String>,
+    password: Option<String>,
+}
+
+impl StoredRow {
+    fn of(account: &Account, key: Option<&StoreKey>) -> Result<Self> {
+        let token = |value: &str| match key {
+            Some(key) => key.seal(value),
+            None => Ok(value.to_string()),
+        };
+        Ok(Self {
+            access_token: token(account.access_token.expose())?,
+            refresh_token: token(account.refresh_token.expose())?,
+            cookie: token(account.cookie.expose())?,
+            extra_headers: account
+                .extra_headers
+                .as_ref()
+                .map(format_extra_headers)
+                .transpose()?,
+            tags: Some(tags::format(&account.tags)).filter(|tags| !tags.is_empty()),
+            password: account
+                .password
+                .as_ref()
+                .map(|password| token(password.expose()))
+                .transpose()?,
+        })
+    }
+}
+
+fn account_from_row(row: &Row) -> rusqlite::Result<Account> {
+    Ok(Account {
+        index: row.get(0)?,
+        email: row.get(1)?,
+        access_token: row.get::<_, String>(2)?.into(),
+        refresh_token: row.get::<_, String>(3)?.into(),
+        cookie: row.get::<_, String>(4)?.into(),
+        days_remaining: row.get(5)?,
+        status: MembershipStatus::from(row.get::<_, String>(6)?.as_str()),
+        record_time: row.get(7)?,
+        source: AccountSource::from(row.get::<_, String>(8)?.as_str()),
+        usage_used: row.get(9)?,
+        usage_remaining: row.get(10)?,
+        usage_total: row.get(11)?,
+        usage_percentage: row.get(12)?,
+        label_emoji: row.get(13)?,
+        label_color: row.get(14)?,
+        privacy_mode: row.get(15)?,
+        extra_headers: row
+            .get::<_, Option<String>>(16)?
+            .and_then(|text| parse_extra_headers(&text)),
+        pool: row.get(17)?,
+        status_reason: row.get(18)?,
+        tags: row
+            .get::<_, Option<String>>(19)?
+            .map(|text| tags::parse(&text))
+            .unwrap_or_default(),
+        favorite: row.get::<_, Option<bool>>(20)?.unwrap_or(false),
+        last_used: row.get::<_, Option<String>>(21)?.unwrap_or_default(),
+        archived: row.get::<_, Option<bool>>(22)?.unwrap_or(false),
+        password: row
+            .get::<_, Option<String>>(23)?
+            .filter(|password| !password.is_empty())
+            .map(SecretString::from),
+        nickname: row.get(24)?,
+    })
+}
+
+impl AccountStore for SqliteAccountStore {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn ensure_exists(&self) -> Result<()> {
+        self.connect().map(drop)
+    }
+
+    fn read_accounts(&self) -> Result<Vec<Account>> {
+        let key = self.token_key()?;
+        read_all(&self.connect()?, key.as_ref())
+    }
+
+    // Every row of the table is one account, so none can be malformed
+    fn corrupt_rows(&self) -> Result<Vec<CorruptRow>> {
+        Ok(Vec::new())
+    }
+
+    fn find_account_by_email(&self, email: &str) -> Result<Option<Account>> {
+        let key = self.token_key()?;
+        let account = self
+            .connect()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM accounts WHERE email = ?1 ORDER BY position LIMIT 1",
+                    COLUMNS
+                ),
+                [email],
+                account_from_row,
+            )
+            .optional()
+            .context("Failed to read the account database")?;
+        account
+            .map(|account| store_crypto::open_tokens(account, key.as_ref()))
+            .transpose()
+    }
+
+    fn write_accounts(&self, accounts: &[Account]) -> Result<()> {
+        let key = self.token_key()?;
+        self.write(|tx| replace_all(tx, accounts, key.as_ref()))
+    }
+
+    fn modify_accounts(
+        &self,
+        change: &mut dyn FnMut(&mut Vec<Account>) -> Result<()>,
+    ) -> Result<()> {
+        let key = self.token_key()?;
+        self.write(|tx| {
+            let mut accounts = read_all(tx, key.as_ref())?;
+            change(&mut accounts)?;
+            replace_all(tx, &accounts, key.as_ref())
+        })
+    }
+
+    fn add_account(&self, account: Account) -> Result<()> {
+        self.batch_add_accounts(vec![account])
+    }
+
+    fn batch_add_accounts(&self, new_accounts: Vec<Account>) -> Result<()> {
+        let mut new_accounts = Some(new_accounts);
+        self.modify_accounts(&mut |accounts| {
+            append(accounts, new_accounts.take().unwrap_or_default());
+            Ok(())
+        })
+    }
+
+    fn batch_add_new_accounts(&self, new_accounts: Vec<Account>) -> Result<Vec<String>> {
+        let mut new_accounts = Some(new_accounts);
+        let mut added = Vec::new();
+        self.modify_accounts(&mut |accounts| {
+            let mut seen: HashSet<String> = accounts
+                .iter()
+                .map(|account| account.email.clone())
+                .collect();
+            let fresh: Vec<Account> = new_accounts
+                .take()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|account| seen.insert(account.email.clone()))
+                .collect();
+            added = fresh.iter().map(|account| account.email.clone()).collect();
+            append(accounts, fresh);
+            Ok(())
+        })?;
+        Ok(added)
+    }
+
+    fn delete_account(&self, email: &str) -> Result<bool> {
+        self.write(|tx| {
+            let deleted = tx.execute("DELETE FROM accounts WHERE email = ?1", [email])? > 0;
+            if deleted {
+                renumber(tx)?;
+            }
+            Ok(deleted)
+        })
+    }
+
+    fn compact_indexes(&self) -> Result<bool> {
+        self.write(|tx| Ok(renumber(tx)? > 0))
+    }
+
+    fn update_account(&self, email: &str, updated_account: Account) -> Result<bool> {
+        let key = self.token_key()?;
+        let row = StoredRow::of(&updated_account, key.as_ref())?;
+        let account = &updated_account;
+        self.write(|tx| {
+            let updated = tx.execute(
+                "UPDATE accounts SET idx = ?1, email = ?2, access_token = ?3, refresh_token = ?4, \
+                 cookie = ?5, days_remaining = ?6, status = ?7, record_time = ?8, source = ?9, \
+                 usage_used = ?10, usage_remaining = ?11, usage_total = ?12, \
+                 usage_percentage = ?13, label_emoji = ?14, label_color = ?15, \
+                 privacy_mode = ?16, extra_headers = ?17, pool = ?18, status_reason = ?19, \
+                 tags = ?20, favorite = ?21, last_used = ?22, archived = ?23, \
+                 password = ?24, nickname = ?25 \
+                 WHERE position = (SELECT MIN(position) FROM accounts WHERE email = ?26)",
+                params![
+                    account.index,
+                    account.email,
+                    row.access_token,
+                    row.refresh_token,
+                    row.cookie,
+                    account.days_remaining,
+                    account.status.as_str(),
+                    account.record_time,
+                    account.source.as_str(),
+                    account.usage_used,
+                    account.usage_remaining,
+                    account.usage_total,
+                    account.usage_percentage,
+                    account.label_emoji,
+                    account.label_color,
+                    account.privacy_mode,
+                    row.extra_headers,
+                    account.pool,
+                    account.status_reason,
+                    row.tags,
+                    account.favorite,
+                    account.last_used,
+                    account.archived,
+                    row.password,
+                    account.nickname,
+                    email,
+                ],
+            )?;
+            Ok(updated > 0)
+        })
+    }
+
+    fn is_locked(&self) -> bool {
+        self.token_key().is_err()
+    }
+
+    fn set_passphrase(&self, passphrase: &str) -> Result<()> {
+        if store_crypto::is_encrypted_store(&self.path) {
+            anyhow::bail!("The account store already has a passphrase");
+        }
+        let accounts = self.read_accounts()?;
+        let key = store_crypto::create(&self.path, passphrase)?;
+        store_crypto::remember(&self.path, key.clone());
+        self.write(|tx| replace_all(tx, &accounts, Some(&key)))
+    }
+
+    fn unlock(&self, passphrase: &str) -> Result<()> {
+        let key = store_crypto::unlock(&self.path, passphrase)?;
+        store_crypto::remember(&self.path, key.clone());
+        if self.path.exists() && self.has_plaintext_tokens()? {
+            self.write(|tx| {
+                let accounts = read_all(tx, Some(&key))?;
+                replace_all(tx, &accounts, Some(&key))
+            })?;
+            tracing::info!("Encrypted the tokens that were stored in plaintext");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::SecretString;
+    use crate::test_support::account;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_rows_behave_like_the_csv() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = SqliteAccountStore::new(temp_dir.path().join("accounts.db"));
+        store.ensure_exists().unwrap();
+        assert!(store.read_accounts().unwrap().is_empty());
+
+        let headers: HashMap<String, SecretString> =
+            [("X-Org".to_string(), "org_token".into())].into();
+        let full = Account {
+            days_remaining: Some(3.5),
+            status: MembershipStatus::Pro,
+            usage_used: Some(1.25),
+            privacy_mode: Some(true),
+            extra_headers: Some(headers),
+            pool: Some("work".to_string()),
+            access_token: "token_a".into(),
+            ..account("a@x.com")
+        };
+        store
+            .batch_add_accounts(vec![
+                full,
+                Account {
+                    access_token: "token_b".into(),
+                    ..account("b@x.com")
+                },
+            ])
+            .unwrap();
+        let added = store
+            .batch_add_new_accounts(vec![
+                Account {
+                    access_token: "other".into(),
+                    ..account("b@x.com")
+                },
+                Account {
+                    access_token: "token_c".into(),
+                    ..account("c@x.com")
+                },
+            ])
+            .unwrap();
+        assert_eq!(added, vec!["c@x.com"]);
+        store
+            .add_account(Account {
+                access_token: "token_a2".into(),
+                ..account("a@x.com")
+            })
+            .unwrap();
+
+        let accounts = store.read_accounts().unwrap();
+        let indices: Vec<i32> = accounts.iter().map(|a| a.index).collect();
+        assert_eq!(indices, vec![1, 2, 3, 4]);
+        assert_eq!(accounts[0].days_remaining, Some(3.5));
+        assert_eq!(accounts[0].privacy_mode, Some(true));
+        assert_eq!(
+            accounts[0].extra_headers.as_ref().unwrap()["X-Org"].expose(),
+            "org_token"
+        );
+        assert_eq!(accounts[1].usage_used, None);
+
+        let mut updated = accounts[0].clone();
+        updated.status = MembershipStatus::FreeTrial;
+        assert!(store.update_account("a@x.com", updated).unwrap());
+        let accounts = store.read_accounts().unwrap();
+        assert_eq!(accounts[0].status, MembershipStatus::FreeTrial);
+        assert_eq!(accounts[3].status, MembershipStatus::Unknown);
+        assert!(!store
+            .update_account(
+                "nobody@x.com",
+                Account {
+                    access_token: "t".into(),
+                    ..account("nobody@x.com")
+                }
+            )
+            .unwrap());
+
+        assert!(store.delete_account("a@x.com").unwrap());
+        let rows: Vec<(i32, String)> = store
+            .read_accounts()
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.index, a.email))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![(1, "b@x.com".to_string()), (2, "c@x.com".to_string())]
+        );
+        assert!(!store.compact_indexes().unwrap());
+        assert!(!store.delete_account("a@x.com").unwrap());
+    }
+
+    #[test]
+    fn test_tokens_are_sealed_with_the_store_key() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("accounts.db");
+        let store = SqliteAccountStore::new(path.clone());
+        store
+            .add_account(Account {
+                access_token: "secret_token".into(),
+                ..account("a@x.com")
+            })
+            .unwrap();
+        store.set_passphrase("correct horse").unwrap();
+
+        let stored: String = Connection::open(&path)
+            .unwrap()
+            .query_row("SELECT access_token FROM accounts", [], |row| row.get(0))
+            .unwrap();
+        assert!(store_crypto::is_encrypted(&stored));
+        assert_eq!(
+            store.read_accounts().unwrap()[0].access_token.expose(),
+            "secret_token"
+        );
+
+        store_crypto::forget(&path);
+        assert!(store.is_locked());
+        assert!(store.read_accounts().is_err());
+        store.unlock("correct horse").unwrap();
+        assert_eq!(
+            store
+                .find_account_by_email("a@x.com")
+                .unwrap()
+                .unwrap()
+                .access_token
+                .expose(),
+            "secret_token"
+        );
+    }
+
+    #[test]
+    fn test_older_tables_get_the_tags_column() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("accounts.db");
+        let old_schema = SCHEMA.replace(
+            "status_reason TEXT,\n    tags TEXT,\n    favorite INTEGER,\n    last_used TEXT,\n    archived INTEGER,\n    password TEXT,\n    nickname TEXT",
+            "status_reason TEXT",
+        );
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(&old_schema).unwrap();
+        conn.execute(
+            "INSERT INTO accounts (idx, email, access_token, refresh_token, cookie, status, \
+             record_time, source) VALUES (1, 'a@x.com', 'token_a', '', '', 'pro', '', 'imported')",
+            [],
+        )
+        .unwrap();
+        drop(conn);
+
+        let store = SqliteAccountStore::new(path);
+        let mut stored = store.read_accounts().unwrap().remove(0);
+        assert!(stored.tags.is_empty());
+        stored.tags = vec!["work".to_string(), "trial".to_string()];
+        store.update_account("a@x.com", stored).unwrap();
+        assert_eq!(
+            store.read_accounts().unwrap()[0].tags,
+            vec!["work".to_string(), "trial".to_string()]
+        );
+    }
+}
@@ -0,0 +1,413 @@
+use crate::app_info;
+use crate::change_bus::ChangeBus;
+use crate::clipboard_watcher::{ClipboardWatcher, DetectedToken};
+use crate::coordinator::{OperationCoordinator, TrayActionQueue};
+use crate::error::AppError;
+use crate::file_cache::FileCache;
+use crate::http::HttpClientFactory;
+use crate::http_api::HttpApiServer;
+use crate::intent_journal::IntentJournal;
+use crate::scheduler::PeriodicTask;
+use crate::token_health::TokenHealthReport;
+use crate::tray::UsageBand;
+use crate::types::{Account, AccountInfo, BillingCycle, UsageInfo};
+use crate::update_checker::UpdateInfo;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing_appender::non_blocking::WorkerGuard;
+
+/// Lock a mutex even if a thread panicked while holding it.
+/// None of the guarded values can be left half-updated, so the data is still valid.
+pub trait MutexExt<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> MutexExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Recovering from a poisoned state lock");
+            poisoned.into_inner()
+        })
+    }
+}
+
+/// Global app state. Paths are only reachable through accessors that clone
+/// them out, so no lock is ever held across file or network I/O.
+pub struct AppState {
+    csv_path: Mutex<PathBuf>,
+    cursor_base_path: Mutex<Option<PathBuf>>,
+    log_path: Mutex<PathBuf>,
+    settings_path: Mutex<PathBuf>,
+    journal_path: Mutex<PathBuf>,
+    /// Rebuilt whenever the proxy or API policy setting changes
+    http: Mutex<HttpClientFactory>,
+    pub tray_band: Mutex<Option<UsageBand>>,
+    /// Fingerprint of the tray menu last set, so unchanged menus aren't rebuilt
+    pub tray_menu_fingerprint: Mutex<Option<u64>>,
+    /// What the tray reads from the CSV and from Cursor's database, reloaded
+    /// only when those files change
+    pub tray_accounts: FileCache<Arc<Vec<Account>>>,
+    pub tray_current_email: FileCache<Option<String>>,
+    pub tray_last_refresh: FileCache<Option<chrono::DateTime<chrono::Utc>>>,
+    pub http_api: Mutex<Option<HttpApiServer>>,
+    pub clipboard_watcher: Mutex<Option<ClipboardWatcher>>,
+    /// Last token seen in the clipboard; holds metadata only, never the token
+    pub detected_token: Mutex<Option<DetectedToken>>,
+    pub available_update: Mutex<Option<UpdateInfo>>,
+    pub update_task: Mutex<Option<PeriodicTask>>,
+    pub auto_export_task: Mutex<Option<PeriodicTask>>,
+    pub token_health_task: Mutex<Option<PeriodicTask>>,
+    pub connectivity_task: Mutex<Option<PeriodicTask>>,
+    pub webdav_sync_task: Mutex<Option<PeriodicTask>>,
+    /// Set by the connectivity probe; while set, cached data is served instead of failing
+    offline: AtomicBool,
+    /// Set while a thread waits for Cursor to start to check its settings after a reset
+    settings_check: AtomicBool,
+    /// Counts successful switches, so a verification can tell a newer switch ran
+    switch_serial: AtomicU64,
+    /// Last expiry check of the logged-in account's token
+    pub token_health: Mutex<Option<TokenHealthReport>>,
+    /// Day the last auto export failure was notified, to notify at most once a day
+    pub export_failure_notified: Mutex<Option<chrono::NaiveDate>>,
+    pub log_guard: Mutex<Option<WorkerGuard>>,
+    /// Serializes switches, refreshes, syncs and machine ID resets
+    pub operations: OperationCoordinator,
+    /// Coalesces repeated tray clicks and holds the refresh cooldown
+    pub tray_actions: TrayActionQueue,
+    /// Notified by everything that writes the account CSV; rebuilds the tray
+    pub accounts_changed: ChangeBus,
+    /// End of the current app lock session
+    unlocked_until: Mutex<Option<Instant>>,
+    /// Billing cycles by email, so the detail window doesn't refetch on every open
+    billing_cycles: Mutex<HashMap<String, (Instant, BillingCycle)>>,
+    /// Info and usage of the logged-in account, keyed by its email
+    current_account_info: Mutex<Option<Cached<AccountInfo>>>,
+    current_usage_info: Mutex<Option<Cached<UsageInfo>>>,
+    /// Held while the current account is fetched from the API, so a request
+    /// arriving during the startup prefetch waits for it instead of repeating it
+    pub current_account_fetch: Mutex<()>,
+    /// ID of the import job being processed in the background
+    pub running_import_job: Mutex<Option<String>>,
+}
+
+struct Cached<T> {
+    fetched_at: Instant,
+    email: String,
+    value: T,
+}
+
+impl<T: Clone> Cached<T> {
+    // However old it is, with its age
+    fn get(entry: &Option<Self>, email: &str) -> Option<(T, Duration)> {
+        entry
+            .as_ref()
+            .filter(|cached| cached.email == email)
+            .map(|cached| (cached.value.clone(), cached.fetched_at.elapsed()))
+    }
+
+    fn new(email: &str, value: T) -> Option<Self> {
+        Some(Self {
+            fetched_at: Instant::now(),
+            email: email.to_string(),
+            value,
+        })
+    }
+}
+
+/// How long a fetched billing cycle is reused
+pub const BILLING_CYCLE_TTL: Duration = Duration::from_secs(15 * 60);
+/// How long the current account's info and usage are reused
+pub const CURRENT_ACCOUNT_TTL: Duration = Duration::from_secs(60);
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AppState {
+    /// Placeholder state; the real paths are set in setup()
+    pub fn new() -> Self {
+        Self {
+            csv_path: Mutex::new(PathBuf::from(".")),
+            cursor_base_path: Mutex::new(None),
+            log_path: Mutex::new(PathBuf::from(".")),
+            settings_path: Mutex::new(PathBuf::from("settings.json")),
+            journal_path: Mutex::new(PathBuf::from(app_info::JOURNAL_FILE_NAME)),
+            http: Mutex::new(HttpClientFactory::default()),
+            tray_band: Mutex::new(None),
+            tray_menu_fingerprint: Mutex::new(None),
+            tray_accounts: FileCache::new(),
+            tray_current_email: FileCache::new(),
+            tray_last_refresh: FileCache::new(),
+            http_api: Mutex::new(None),
+            clipboard_watcher: Mutex::new(None),
+            detected_token: Mutex::new(None),
+            available_update: Mutex::new(None),
+            update_task: Mutex::new(None),
+            auto_export_task: Mutex::new(None),
+            token_health_task: Mutex::new(None),
+            connectivity_task: Mutex::new(None),
+            webdav_sync_task: Mutex::new(None),
+            offline: AtomicBool::new(false),
+            settings_check: AtomicBool::new(false),
+            switch_serial: AtomicU64::new(0),
+            token_health: Mutex::new(None),
+            export_failure_notified: Mutex::new(None),
+            log_guard: Mutex::new(None),
+            operations: OperationCoordinator::new(),
+            tray_actions: TrayActionQueue::new(),
+            accounts_changed: ChangeBus::new(),
+            unlocked_until: Mutex::new(None),
+            billing_cycles: Mutex::new(HashMap::new()),
+            current_account_info: Mutex::new(None),
+            current_usage_info: Mutex::new(None),
+            current_account_fetch: Mutex::new(()),
+            running_import_job: Mutex::new(None),
+        }
+    }
+
+    pub fn csv_path(&self) -> PathBuf {
+        self.csv_path.lock_or_recover().clone()
+    }
+
+    pub fn set_csv_path(&self, path: PathBuf) {
+        *self.csv_path.lock_or_recover() = path;
+    }
+
+    /// Cursor data directory, or an error when it hasn't been detected or set
+    pub fn cursor_base(&self) -> Result<PathBuf, AppError> {
+        self.cursor_base_path
+            .lock_or_recover()
+            .clone()
+            .ok_or(AppError::CursorPathNotSet)
+    }
+
+    pub fn set_cursor_base(&self, path: PathBuf) {
+        *self.cursor_base_path.lock_or_recover() = Some(path);
+    }
+
+    pub fn log_path(&self) -> PathBuf {
+        self.log_path.lock_or_recover().clone()
+    }
+
+    pub fn set_log_path(&self, path: PathBuf) {
+        *self.log_path.lock_or_recover() = path;
+    }
+
+    pub fn settings_path(&self) -> PathBuf {
+        self.settings_path.lock_or_recover().clone()
+    }
+
+    pub fn set_settings_path(&self, path: PathBuf) {
+        *self.settings_path.lock_or_recover() = path;
+    }
+
+    pub fn journal(&self) -> IntentJournal {
+        IntentJournal::new(self.journal_path.lock_or_recover().clone())
+    }
+
+    pub fn set_journal_path(&self, path: PathBuf) {
+        *self.journal_path.lock_or_recover() = path;
+    }
+
+    /// Shares the request throttle with every other clone
+    pub fn http(&self) -> HttpClientFactory {
+        self.http.lock_or_recover().clone()
+    }
+
+    pub fn set_http(&self, http: HttpClientFactory) {
+        *self.http.lock_or_recover() = http;
+    }
+
+    pub fn unlock_for(&self, duration: Duration) {
+        *self.unlocked_until.lock_or_recover() = Some(Instant::now() + duration);
+    }
+
+    pub fn lock(&self) {
+        *self.unlocked_until.lock_or_recover() = None;
+    }
+
+    pub fn cached_billing_cycle(&self, email: &str) -> Option<BillingCycle> {
+        self.billing_cycles
+            .lock_or_recover()
+            .get(email)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < BILLING_CYCLE_TTL)
+            .map(|(_, cycle)| cycle.clone())
+    }
+
+    pub fn cache_billing_cycle(&self, email: &str, cycle: BillingCycle) {
+        self.billing_cycles
+            .lock_or_recover()
+            .insert(email.to_string(), (Instant::now(), cycle));
+    }
+
+    /// Cached within CURRENT_ACCOUNT_TTL, with its age
+    pub fn cached_account_info(&self, email: &str) -> Option<(AccountInfo, Duration)> {
+        self.last_account_info(email)
+            .filter(|(_, age)| *age < CURRENT_ACCOUNT_TTL)
+    }
+
+    /// Whatever was last fetched for `email`, however old
+    pub fn last_account_info(&self, email: &str) -> Option<(AccountInfo, Duration)> {
+        Cached::get(&self.current_account_info.lock_or_recover(), email)
+    }
+
+    pub fn cache_account_info(&self, email: &str, info: AccountInfo) {
+        *self.current_account_info.lock_or_recover() = Cached::new(email, info);
+    }
+
+    pub fn cached_usage_info(&self, email: &str) -> Option<(UsageInfo, Duration)> {
+        self.last_usage_info(email)
+            .filter(|(_, age)| *age < CURRENT_ACCOUNT_TTL)
+    }
+
+    pub fn last_usage_info(&self, email: &str) -> Option<(UsageInfo, Duration)> {
+        Cached::get(&self.current_usage_info.lock_or_recover(), email)
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::SeqCst)
+    }
+
+    /// Record the probe result; true when it differs from the previous one
+    pub fn set_offline(&self, offline: bool) -> bool {
+        self.offline.swap(offline, Ordering::SeqCst) != offline
+    }
+
+    /// Claim the settings check; false when another thread already runs it
+    pub fn begin_settings_check(&self) -> bool {
+        !self.settings_check.swap(true, Ordering::SeqCst)
+    }
+
+    pub fn end_settings_check(&self) {
+        self.settings_check.store(false, Ordering::SeqCst);
+    }
+
+    /// Number a switch that just finished
+    pub fn next_switch_serial(&self) -> u64 {
+        self.switch_serial.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn is_latest_switch(&self, serial: u64) -> bool {
+        self.switch_serial.load(Ordering::SeqCst) == serial
+    }
+
+    pub fn cache_usage_info(&self, email: &str, usage: UsageInfo) {
+        *self.current_usage_info.lock_or_recover() = Cached::new(email, usage);
+    }
+
+    /// Time left in the unlocked session, None once it has expired
+    pub fn unlock_remaining(&self) -> Option<Duration> {
+        self.unlocked_until
+            .lock_or_recover()
+            .and_then(|until| until.checked_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_cursor_base_not_set() {
+        let state = AppState::new();
+        assert!(matches!(
+            state.cursor_base(),
+            Err(AppError::CursorPathNotSet)
+        ));
+
+        state.set_cursor_base(PathBuf::from("/tmp/cursor"));
+        assert_eq!(state.cursor_base().unwrap(), PathBuf::from("/tmp/cursor"));
+    }
+
+    #[test]
+    fn test_unlock_session() {
+        let state = AppState::new();
+        assert!(state.unlock_remaining().is_none());
+
+        state.unlock_for(Duration::from_secs(60));
+        assert!(state.unlock_remaining().unwrap() > Duration::from_secs(50));
+
+        state.lock();
+        assert!(state.unlock_remaining().is_none());
+
+        state.unlock_for(Duration::ZERO);
+        assert!(state.unlock_remaining().is_none());
+    }
+
+    #[test]
+    fn test_billing_cycle_cache() {
+        let state = AppState::new();
+        assert!(state.cached_billing_cycle("a@x.com").is_none());
+
+        state.cache_billing_cycle(
+            "a@x.com",
+            BillingCycle {
+                start_date: Some("2024-01-01".to_string()),
+                end_date: None,
+                usage: None,
+                limit: None,
+            },
+        );
+        let cycle = state.cached_billing_cycle("a@x.com").unwrap();
+        assert_eq!(cycle.start_date.as_deref(), Some("2024-01-01"));
+        assert!(state.cached_billing_cycle("b@x.com").is_none());
+    }
+
+    #[test]
+    fn test_current_account_cache_follows_login() {
+        let state = AppState::new();
+        state.cache_usage_info(
+            "a@x.com",
+            UsageInfo {
+                total_quota: 500.0,
+                used: 50.0,
+                remaining: 450.0,
+                usage_percentage: 10.0,
+            },
+        );
+        assert_eq!(state.cached_usage_info("a@x.com").unwrap().0.used, 50.0);
+
+        // After a switch the cached data belongs to someone else
+        assert!(state.cached_usage_info("b@x.com").is_none());
+        assert!(state.cached_account_info("a@x.com").is_none());
+    }
+
+    #[test]
+    fn test_offline_flag_reports_changes_only() {
+        let state = AppState::new();
+        assert!(!state.is_offline());
+        assert!(!state.set_offline(false));
+        assert!(state.set_offline(true));
+        assert!(!state.set_offline(true));
+        assert!(state.is_offline());
+        assert!(state.set_offline(false));
+    }
+
+    #[test]
+    fn test_state_usable_after_panic_while_locked() {
+        let state = Arc::new(AppState::new());
+        state.set_csv_path(PathBuf::from("/tmp/accounts.csv"));
+
+        // Simulate a command panicking while it holds the lock
+        let panicking = state.clone();
+        let result = thread::spawn(move || {
+            let _guard = panicking.csv_path.lock().unwrap();
+            panic!("command failed");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(state.csv_path.is_poisoned());
+
+        assert_eq!(state.csv_path(), PathBuf::from("/tmp/accounts.csv"));
+        state.set_csv_path(PathBuf::from("/tmp/other.csv"));
+        assert_eq!(state.csv_path(), PathBuf::from("/tmp/other.csv"));
+    }
+}
@@ -0,0 +1,249 @@
+// Copies of Cursor's state.vscdb taken before a switch writes new auth into
+// it, kept in `backups/state` under the Cursor base path so a write that went
+// wrong halfway, e.g. because Cursor came back and locked the file, can be
+// undone.
+
+use crate::path_detector::PathDetector;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Backups kept unless configured otherwise
+pub const DEFAULT_RETENTION: u32 = 10;
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+/// SQLite keeps recent writes next to the database until they are checkpointed
+const SIDE_FILE_SUFFIXES: [&str; 2] = ["-wal", "-shm"];
+
+/// A backup as listed by `list_state_db_backups`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateBackupInfo {
+    /// The folder holding the copied files
+    pub path: PathBuf,
+    /// When it was taken, as `record_time::FORMAT`
+    pub timestamp: String,
+    /// File names copied, the database first
+    pub files: Vec<String>,
+}
+
+/// Where the backups of the database under `base_path` go
+pub fn backup_dir(base_path: &Path) -> PathBuf {
+    base_path.join("backups").join("state")
+}
+
+// The database and the WAL and shared memory files that go with it
+fn db_file_names(db_path: &Path) -> Vec<String> {
+    let name = db_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    std::iter::once(name.clone())
+        .chain(
+            SIDE_FILE_SUFFIXES
+                .iter()
+                .map(|suffix| name.clone() + suffix),
+        )
+        .collect()
+}
+
+/// Copy the database under `base_path`, with its WAL and shared memory files
+/// if there are any, into a new backup folder and prune all but the newest
+/// `retention` backups; 0 keeps none and takes none. None when there is no
+/// database to copy.
+pub fn back_up(base_path: &Path, retention: u32, now: DateTime<Local>) -> Result<Option<PathBuf>> {
+    let db_path = PathDetector::get_db_path(base_path);
+    if retention == 0 || !db_path.exists() {
+        return Ok(None);
+    }
+    let dir = backup_dir(base_path);
+    // Backups within the same millisecond get the next free one, as with the
+    // account backups
+    let mut at = now;
+    while dir.join(at.format(TIMESTAMP_FORMAT).to_string()).exists() {
+        at += chrono::Duration::milliseconds(1);
+    }
+    let backup = dir.join(at.format(TIMESTAMP_FORMAT).to_string());
+    fs::create_dir_all(&backup)
+        .with_context(|| format!("Failed to create {}", backup.display()))?;
+
+    for name in db_file_names(&db_path) {
+        let from = db_path.with_file_name(&name);
+        if from.exists() {
+            fs::copy(&from, backup.join(&name))
+                .with_context(|| format!("Failed to back up {}", from.display()))?;
+        }
+    }
+
+    for name in backup_names(base_path)?.iter().skip(retention as usize) {
+        fs::remove_dir_all(dir.join(name)).with_context(|| format!("Failed to remove {}", name))?;
+    }
+    Ok(Some(backup))
+}
+
+/// Names of the backup folders, newest first (timestamps sort lexically)
+fn backup_names(base_path: &Path) -> Result<Vec<String>> {
+    let dir = backup_dir(base_path);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| NaiveDateTime::parse_from_str(name, TIMESTAMP_FORMAT).is_ok())
+        .collect();
+    names.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(names)
+}
+
+/// The backups of the database under `base_path`, newest first
+pub fn list(base_path: &Path) -> Result<Vec<StateBackupInfo>> {
+    let dir = backup_dir(base_path);
+    let db_path = PathDetector::get_db_path(base_path);
+    Ok(backup_names(base_path)?
+        .into_iter()
+        .map(|name| {
+            let path = dir.join(&name);
+            let timestamp = NaiveDateTime::parse_from_str(&name, TIMESTAMP_FORMAT)
+                .map(|at| at.format(crate::types::record_time::FORMAT).to_string())
+                .unwrap_or_default();
+            let files = db_file_names(&db_path)
+                .into_iter()
+                .filter(|file| path.join(file).exists())
+                .collect();
+            StateBackupInfo {
+                path,
+                timestamp,
+                files,
+            }
+        })
+        .collect())
+}
+
+/// `path` if it names one of the backups under `base_path`; restores take
+/// nothing from elsewhere
+pub fn resolve(base_path: &Path, path: &Path) -> Result<PathBuf> {
+    let dir = fs::canonicalize(backup_dir(base_path)).context("There are no backups yet")?;
+    let path =
+        fs::canonicalize(path).with_context(|| format!("No backup at {}", path.display()))?;
+    let is_backup = path.parent() == Some(dir.as_path())
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                backup_names(base_path).is_ok_and(|names| names.iter().any(|n| n == name))
+            });
+    let db_name = &db_file_names(&PathDetector::get_db_path(base_path))[0];
+    if !is_backup || !path.join(db_name).is_file() {
+        bail!("{} is not a backup of the Cursor database", path.display());
+    }
+    Ok(path)
+}
+
+/// Put the files in `backup` back in place of the database under
+/// `base_path`. A WAL or shared memory file the backup doesn't have is
+/// removed, or SQLite would replay it over the restored database. Cursor
+/// must be closed.
+pub fn restore(base_path: &Path, backup: &Path) -> Result<()> {
+    let db_path = PathDetector::get_db_path(base_path);
+    for name in db_file_names(&db_path) {
+        let from = backup.join(&name);
+        let to = db_path.with_file_name(&name);
+        if from.exists() {
+            fs::copy(&from, &to).with_context(|| format!("Failed to restore {}", to.display()))?;
+        } else if to.exists() {
+            fs::remove_file(&to).with_context(|| format!("Failed to remove {}", to.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use chrono::TimeZone;
+    use rusqlite::Connection;
+
+    fn create_test_db(base_path: &Path) -> Database {
+        let db_path = PathDetector::get_db_path(base_path);
+        Connection::open(&db_path)
+            .unwrap()
+            .execute(
+                "CREATE TABLE IF NOT EXISTS ItemTable (key TEXT PRIMARY KEY, value TEXT)",
+                [],
+            )
+            .unwrap();
+        Database::new(db_path)
+    }
+
+    fn at_second(second: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 1, 2, 0, second).unwrap()
+    }
+
+    #[test]
+    fn test_backups_copy_the_wal_and_are_pruned() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_path = temp_dir.path();
+        let db_path = PathDetector::get_db_path(base_path);
+        assert_eq!(back_up(base_path, 3, at_second(0)).unwrap(), None);
+
+        let db = create_test_db(base_path);
+        db.update_auth("before@x.com", "old_token", None).unwrap();
+        fs::write(db_path.with_file_name("state.vscdb-wal"), "wal").unwrap();
+        let first = back_up(base_path, 3, at_second(1)).unwrap().unwrap();
+        assert!(first.ends_with("backups/state/20240301-020001-000"));
+        assert_eq!(
+            fs::read_to_string(first.join("state.vscdb-wal")).unwrap(),
+            "wal"
+        );
+        assert!(!first.join("state.vscdb-shm").exists());
+
+        fs::remove_file(db_path.with_file_name("state.vscdb-wal")).unwrap();
+        for second in 2..=4 {
+            back_up(base_path, 3, at_second(second)).unwrap().unwrap();
+        }
+        fs::create_dir_all(backup_dir(base_path).join("unrelated")).unwrap();
+        assert_eq!(back_up(base_path, 0, at_second(5)).unwrap(), None);
+
+        let backups = list(base_path).unwrap();
+        let stamps: Vec<&str> = backups.iter().map(|b| b.timestamp.as_str()).collect();
+        assert_eq!(
+            stamps,
+            vec![
+                "2024-03-01 02:00:04",
+                "2024-03-01 02:00:03",
+                "2024-03-01 02:00:02"
+            ]
+        );
+        assert_eq!(backups[0].files, vec!["state.vscdb"]);
+        assert!(!first.exists());
+        assert!(backup_dir(base_path).join("unrelated").exists());
+
+        assert!(resolve(base_path, &backup_dir(base_path).join("unrelated")).is_err());
+        assert!(resolve(base_path, base_path).is_err());
+    }
+
+    #[test]
+    fn test_restore_puts_the_database_back() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_path = temp_dir.path();
+        let db_path = PathDetector::get_db_path(base_path);
+        let db = create_test_db(base_path);
+        db.update_auth("before@x.com", "old_token", None).unwrap();
+        let backup = back_up(base_path, 3, at_second(0)).unwrap().unwrap();
+
+        db.update_auth("after@x.com", "new_token", None).unwrap();
+        // Left over from the write the restore undoes
+        fs::write(db_path.with_file_name("state.vscdb-wal"), "stale").unwrap();
+
+        let backup = resolve(base_path, &backup).unwrap();
+        restore(base_path, &backup).unwrap();
+        assert!(!db_path.with_file_name("state.vscdb-wal").exists());
+        let (email, token) = db.get_auth_info().unwrap();
+        assert_eq!(email, "before@x.com");
+        assert_eq!(token, "old_token");
+    }
+}
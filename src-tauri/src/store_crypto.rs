@@ -0,0 +1,214 @@
+// Encryption of the token columns of the account CSV with a key derived from
+// the user's passphrase. Every value is sealed on its own, so plaintext and
+// encrypted rows can share a file while an older one is migrated.
+
+use crate::app_lock::{from_hex, pbkdf2_sha256, to_hex};
+use crate::csv_manager::canonical;
+use crate::state::MutexExt;
+use crate::types::Account;
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crypto_secretbox::aead::{Aead, AeadCore, KeyInit, OsRng};
+use crypto_secretbox::{Key, Nonce, XSalsa20Poly1305};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use thiserror::Error;
+
+/// File next to the account CSV with the salt of its passphrase. Tokens are
+/// encrypted once it exists.
+pub const STORE_KEY_FILE: &str = "store_key.json";
+/// Start of an encrypted column; anything else is plaintext
+const ENCRYPTED_PREFIX: &str = "enc1:";
+// Unoptimized test builds would spend seconds on every derivation
+const ITERATIONS: u32 = if cfg!(test) { 1_000 } else { 210_000 };
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+/// Sealed into the key file to tell a right passphrase from a wrong one
+const CHECK_VALUE: &str = "cursor-account-switcher";
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("The account store is locked, unlock it with its passphrase")]
+    Locked,
+    #[error("Incorrect passphrase")]
+    WrongPassphrase,
+}
+
+pub fn key_file_path(csv_path: &Path) -> PathBuf {
+    csv_path.with_file_name(STORE_KEY_FILE)
+}
+
+/// Whether the tokens of the CSV at `csv_path` are stored encrypted
+pub fn is_encrypted_store(csv_path: &Path) -> bool {
+    key_file_path(csv_path).exists()
+}
+
+/// Whether a stored column holds a sealed value
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    iterations: u32,
+    salt: String,
+    check: String,
+}
+
+/// Key of an unlocked store
+#[derive(Clone)]
+pub struct StoreKey(Key);
+
+impl StoreKey {
+    fn derive(passphrase: &str, salt: &[u8], iterations: u32) -> Self {
+        Self(pbkdf2_sha256(passphrase.as_bytes(), salt, iterations).into())
+    }
+
+    /// `value` encrypted under a fresh nonce; empty values stay empty
+    pub fn seal(&self, value: &str) -> Result<String> {
+        if value.is_empty() {
+            return Ok(String::new());
+        }
+        let nonce = XSalsa20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XSalsa20Poly1305::new(&self.0)
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| anyhow!("Failed to encrypt a token"))?;
+        let mut bytes = nonce.to_vec();
+        bytes.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(bytes)))
+    }
+
+    /// The value `seal` was given; plaintext comes back as it is
+    pub fn open(&self, value: &str) -> Result<String> {
+        let Some(encoded) = value.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let bytes = STANDARD
+            .decode(encoded)
+            .ok()
+            .filter(|bytes| bytes.len() > NONCE_LENGTH)
+            .context("Unreadable encrypted token")?;
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+        let plaintext = XSalsa20Poly1305::new(&self.0)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("An encrypted token doesn't match the store's passphrase"))?;
+        String::from_utf8(plaintext).context("Unreadable encrypted token")
+    }
+}
+
+/// Give the plaintext store at `csv_path` a passphrase and return its key
+pub fn create(csv_path: &Path, passphrase: &str) -> Result<StoreKey> {
+    let mut salt = [0u8; SALT_LENGTH];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let key = StoreKey::derive(passphrase, &salt, ITERATIONS);
+    let key_file = KeyFile {
+        iterations: ITERATIONS,
+        salt: to_hex(&salt),
+        check: key.seal(CHECK_VALUE)?,
+    };
+
+    let path = key_file_path(csv_path);
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_string_pretty(&key_file)?)
+        .and_then(|()| fs::rename(&temp_path, &path))
+        .context("Failed to write the store key file")?;
+    Ok(key)
+}
+
+/// The key of the encrypted store at `csv_path` if `passphrase` is its passphrase
+pub fn unlock(csv_path: &Path, passphrase: &str) -> Result<StoreKey> {
+    let text = fs::read_to_string(key_file_path(csv_path))
+        .context("The account store has no passphrase")?;
+    let key_file: KeyFile = serde_json::from_str(&text).context("Unreadable store key file")?;
+    let salt = from_hex(&key_file.salt).context("Unreadable store key file")?;
+
+    let key = StoreKey::derive(passphrase, &salt, key_file.iterations);
+    match key.open(&key_file.check) {
+        Ok(check) if check == CHECK_VALUE => Ok(key),
+        _ => Err(StoreError::WrongPassphrase.into()),
+    }
+}
+
+// Keys of the stores unlocked in this process, by key file
+fn unlocked_keys() -> MutexGuard<'static, HashMap<PathBuf, StoreKey>> {
+    static KEYS: OnceLock<Mutex<HashMap<PathBuf, StoreKey>>> = OnceLock::new();
+    KEYS.get_or_init(Default::default).lock_or_recover()
+}
+
+/// What the tokens of the store at `store_path` are read and written with:
+/// None for a plaintext store, an error for an encrypted one that wasn't
+/// unlocked yet
+pub fn token_key(store_path: &Path) -> Result<Option<StoreKey>> {
+    let key_file = key_file_path(store_path);
+    if !key_file.exists() {
+        return Ok(None);
+    }
+    match unlocked_keys().get(&canonical(&key_file)) {
+        Some(key) => Ok(Some(key.clone())),
+        None => Err(StoreError::Locked.into()),
+    }
+}
+
+/// Keep `key` for the stores next to `store_path` until the app quits
+pub fn remember(store_path: &Path, key: StoreKey) {
+    unlocked_keys().insert(canonical(&key_file_path(store_path)), key);
+}
+
+#[cfg(test)]
+pub fn forget(store_path: &Path) {
+    unlocked_keys().remove(&canonical(&key_file_path(store_path)));
+}
+
+/// Decrypt the token columns; encrypted ones need the store's key
+pub fn open_tokens(mut account: Account, key: Option<&StoreKey>) -> Result<Account> {
+    for value in [
+        &mut account.access_token,
+        &mut account.refresh_token,
+        &mut account.cookie,
+    ]
+    .into_iter()
+    .chain(account.password.as_mut())
+    {
+        if is_encrypted(value.expose()) {
+            let key = key.ok_or(StoreError::Locked)?;
+            *value = key.open(value.expose())?.into();
+        }
+    }
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_values_need_the_passphrase() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("accounts.csv");
+        assert!(!is_encrypted_store(&csv_path));
+
+        let key = create(&csv_path, "correct horse").unwrap();
+        assert!(is_encrypted_store(&csv_path));
+        let sealed = key.seal("secret_token").unwrap();
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.contains("secret_token"));
+        assert_ne!(sealed, key.seal("secret_token").unwrap());
+        assert_eq!(key.seal("").unwrap(), "");
+
+        let unlocked = unlock(&csv_path, "correct horse").unwrap();
+        assert_eq!(unlocked.open(&sealed).unwrap(), "secret_token");
+        assert_eq!(unlocked.open("plain_token").unwrap(), "plain_token");
+
+        let error = unlock(&csv_path, "wrong horse").err().unwrap();
+        assert!(matches!(
+            error.downcast_ref::<StoreError>(),
+            Some(StoreError::WrongPassphrase)
+        ));
+        let other = StoreKey::derive("wrong horse", b"0123456789abcdef", 10);
+        assert!(other.open(&sealed).is_err());
+    }
+}
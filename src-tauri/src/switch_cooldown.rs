@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// File next to the account CSV with when each account was last used here
+pub const SWITCH_HISTORY_FILE: &str = "switch_history.json";
+
+/// What a switch to an account in its cooldown does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CooldownMode {
+    #[default]
+    Off,
+    /// Switch anyway and say how long the cooldown has left
+    Warn,
+    /// Refuse the switch until the cooldown is over
+    Enforce,
+}
+
+/// A switch refused because cooldowns are enforced and the account was used
+/// here too recently
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{email} is cooling down for another {}", format_remaining(*.remaining))]
+pub struct CoolingDown {
+    pub email: String,
+    pub remaining: Duration,
+}
+
+/// When each account was last switched to or away from on this machine
+pub struct SwitchHistoryStore {
+    file_path: PathBuf,
+}
+
+impl SwitchHistoryStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    pub fn last_used(&self) -> Result<BTreeMap<String, DateTime<Utc>>> {
+        if !self.file_path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content =
+            fs::read_to_string(&self.file_path).context("Failed to read switch history")?;
+        serde_json::from_str(&content).context("Failed to parse switch history")
+    }
+
+    /// Mark `emails` as used at `now`: on a switch, the account left and the one switched to
+    pub fn record(&self, emails: &[&str], now: DateTime<Utc>) -> Result<()> {
+        let mut last_used = self.last_used()?;
+        for email in emails {
+            last_used.insert(email.to_string(), now);
+        }
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&last_used)?;
+        fs::write(&self.file_path, content).context("Failed to write switch history")
+    }
+
+    /// Accounts used less than `window` before `now`, with the cooldown they have left
+    pub fn cooling_down(
+        &self,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<BTreeMap<String, Duration>> {
+        Ok(self
+            .last_used()?
+            .into_iter()
+            .filter_map(|(email, used)| Some((email, remaining(used, window, now)?)))
+            .collect())
+    }
+}
+
+/// Cooldown left of an account last used at `used`, None once it is over
+pub fn remaining(used: DateTime<Utc>, window: Duration, now: DateTime<Utc>) -> Option<Duration> {
+    let left = used + window - now;
+    (left > Duration::zero()).then_some(left)
+}
+
+/// "3h" from an hour on, "25m" below it, both rounded up
+pub fn format_remaining(remaining: Duration) -> String {
+    let minutes = (remaining.num_seconds() + 59) / 60;
+    if minutes >= 60 {
+        format!("{}h", (minutes + 59) / 60)
+    } else {
+        format!("{}m", minutes.max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cooldown_runs_from_the_last_use() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = SwitchHistoryStore::new(temp_dir.path().join(SWITCH_HISTORY_FILE));
+        let morning = Utc.with_ymd_and_hms(2024, 3, 11, 9, 0, 0).unwrap();
+        let noon = Utc.with_ymd_and_hms(2024, 3, 11, 12, 0, 0).unwrap();
+        let window = Duration::hours(4);
+
+        store.record(&["a@x.com", "b@x.com"], morning).unwrap();
+        store.record(&["b@x.com", "c@x.com"], noon).unwrap();
+
+        let cooling = store.cooling_down(window, noon).unwrap();
+        assert_eq!(cooling.get("a@x.com"), Some(&Duration::hours(1)));
+        assert_eq!(cooling.get("b@x.com"), Some(&Duration::hours(4)));
+        assert_eq!(cooling.len(), 3);
+
+        let later = noon + Duration::hours(2);
+        let cooling = store.cooling_down(window, later).unwrap();
+        assert!(!cooling.contains_key("a@x.com"));
+        assert_eq!(cooling.get("c@x.com"), Some(&Duration::hours(2)));
+    }
+
+    #[test]
+    fn test_remaining_is_rounded_up() {
+        assert_eq!(format_remaining(Duration::minutes(150)), "3h");
+        assert_eq!(format_remaining(Duration::minutes(60)), "1h");
+        assert_eq!(format_remaining(Duration::seconds(59 * 60 + 1)), "1h");
+        assert_eq!(format_remaining(Duration::minutes(25)), "25m");
+        assert_eq!(format_remaining(Duration::seconds(5)), "1m");
+    }
+}
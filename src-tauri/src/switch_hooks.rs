@@ -0,0 +1,180 @@
+use crate::redaction;
+use crate::settings::SwitchHook;
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What a hook's argument placeholders are filled in with
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    pub email: String,
+    /// Membership status of the stored account, e.g. "pro"
+    pub status: String,
+    pub reset_machine: bool,
+}
+
+impl HookContext {
+    fn expand(&self, arg: &str) -> String {
+        arg.replace("{email}", &self.email)
+            .replace("{status}", &self.status)
+            .replace("{reset_machine}", &self.reset_machine.to_string())
+    }
+}
+
+/// How a hook ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookExit {
+    /// The exit code, None when killed by a signal
+    Exited(Option<i32>),
+    TimedOut,
+}
+
+impl HookExit {
+    pub fn success(&self) -> bool {
+        *self == Self::Exited(Some(0))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Exited(Some(code)) => format!("exited with code {}", code),
+            Self::Exited(None) => "was killed".to_string(),
+            Self::TimedOut => "timed out".to_string(),
+        }
+    }
+}
+
+/// Run `hook` and wait for it, killing it after its timeout. Each argument is
+/// filled in separately and no shell is involved, so account fields can't
+/// inject commands. Output goes to the log at debug level; `name` labels it.
+pub fn run(name: &str, hook: &SwitchHook, context: &HookContext) -> Result<HookExit> {
+    let args: Vec<String> = hook.args.iter().map(|arg| context.expand(arg)).collect();
+    let mut command = Command::new(hook.path.trim());
+    command
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to start the {} hook {}", name, hook.path))?;
+
+    let stdout = capture(child.stdout.take());
+    let stderr = capture(child.stderr.take());
+    let exit = wait(&mut child, hook.timeout())?;
+
+    // A timed-out hook may have left children holding the pipes open
+    if exit != HookExit::TimedOut {
+        for (stream, output) in [("stdout", stdout), ("stderr", stderr)] {
+            let output = output.join().unwrap_or_default();
+            if !output.trim().is_empty() {
+                tracing::debug!(
+                    "{} hook {}: {}",
+                    name,
+                    stream,
+                    redaction::scrub_secrets(output.trim_end())
+                );
+            }
+        }
+    }
+    Ok(exit)
+}
+
+fn capture(stream: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut stream) = stream {
+            let _ = stream.read_to_end(&mut output);
+        }
+        String::from_utf8_lossy(&output).into_owned()
+    })
+}
+
+fn wait(child: &mut Child, timeout: Duration) -> Result<HookExit> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(HookExit::Exited(status.code()));
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(HookExit::TimedOut);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell(script: &str, timeout_secs: u64) -> SwitchHook {
+        SwitchHook {
+            path: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string(), "hook".to_string()],
+            timeout_secs,
+            blocking: true,
+        }
+    }
+
+    fn context() -> HookContext {
+        HookContext {
+            email: "a@x.com".to_string(),
+            status: "pro".to_string(),
+            reset_machine: true,
+        }
+    }
+
+    #[test]
+    fn test_placeholders_are_filled_per_argument() {
+        let context = HookContext {
+            email: "a@x.com; rm -rf ~".to_string(),
+            ..context()
+        };
+        assert_eq!(
+            context.expand("--user={email} {status} {reset_machine}"),
+            "--user=a@x.com; rm -rf ~ pro true"
+        );
+
+        // The email arrives as one argument, never as shell syntax
+        let hook = SwitchHook {
+            args: vec![
+                "-c".to_string(),
+                r#"[ "$1" = "$2" ]"#.to_string(),
+                "hook".to_string(),
+                "{email}".to_string(),
+                "a@x.com; rm -rf ~".to_string(),
+            ],
+            ..shell("", 5)
+        };
+        assert!(run("pre-switch", &hook, &context).unwrap().success());
+    }
+
+    #[test]
+    fn test_exit_codes_and_timeouts() {
+        let exit = run("post-switch", &shell("echo done; exit 3", 5), &context()).unwrap();
+        assert_eq!(exit, HookExit::Exited(Some(3)));
+        assert_eq!(exit.describe(), "exited with code 3");
+
+        let started = Instant::now();
+        let exit = run("post-switch", &shell("sleep 10", 1), &context()).unwrap();
+        assert_eq!(exit, HookExit::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let missing = SwitchHook {
+            path: "/nonexistent/hook".to_string(),
+            ..shell("", 5)
+        };
+        assert!(run("pre-switch", &missing, &context()).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+// One row per switch in `switch_history.csv`, for telling which account was
+// in use on which day. `switch_cooldown` only keeps each account's last use.
+
+use anyhow::{Context, Result};
+use csv::{ReaderBuilder, StringRecord, Writer};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// File next to the account CSV with a row per successful switch
+pub const SWITCH_LOG_FILE: &str = "switch_history.csv";
+/// Rows kept unless configured otherwise
+pub const DEFAULT_MAX_ROWS: usize = 5000;
+
+const HEADER: [&str; 4] = ["Timestamp", "Email", "Machine ID Reset", "Source"];
+
+/// Where a switch was started from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchSource {
+    Window,
+    Tray,
+    /// The local HTTP API
+    Api,
+    DeepLink,
+    /// `--switch` on the command line
+    Cli,
+}
+
+impl SwitchSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Window => "window",
+            Self::Tray => "tray",
+            Self::Api => "api",
+            Self::DeepLink => "deep_link",
+            Self::Cli => "cli",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        [
+            Self::Window,
+            Self::Tray,
+            Self::Api,
+            Self::DeepLink,
+            Self::Cli,
+        ]
+        .into_iter()
+        .find(|source| source.as_str() == s)
+    }
+}
+
+/// A switch as returned by `get_switch_history`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwitchLogEntry {
+    /// Local time, as `record_time::FORMAT`
+    pub timestamp: String,
+    pub email: String,
+    pub reset_machine: bool,
+    pub source: SwitchSource,
+}
+
+pub struct SwitchLog {
+    file_path: PathBuf,
+}
+
+impl SwitchLog {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    /// Append `entry`, creating the file if needed, and drop the oldest rows
+    /// beyond `max_rows`
+    pub fn record(&self, entry: &SwitchLogEntry, max_rows: usize) -> Result<()> {
+        let mut entries = self.read_all()?;
+        entries.push(entry.clone());
+        let excess = entries.len().saturating_sub(max_rows);
+
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = Writer::from_path(&self.file_path)
+            .with_context(|| format!("Failed to write {}", self.file_path.display()))?;
+        writer.write_record(HEADER)?;
+        for entry in &entries[excess..] {
+            writer.write_record([
+                entry.timestamp.as_str(),
+                &entry.email,
+                &entry.reset_machine.to_string(),
+                entry.source.as_str(),
+            ])?;
+        }
+        writer.flush().context("Failed to write switch history")
+    }
+
+    /// The newest `limit` switches, newest first
+    pub fn recent(&self, limit: usize) -> Result<Vec<SwitchLogEntry>> {
+        Ok(self.read_all()?.into_iter().rev().take(limit).collect())
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        if self.file_path.exists() {
+            fs::remove_file(&self.file_path).context("Failed to clear switch history")?;
+        }
+        Ok(())
+    }
+
+    // Oldest first; a row that can't be read is skipped rather than losing the log
+    fn read_all(&self) -> Result<Vec<SwitchLogEntry>> {
+        if !self.file_path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut reader = ReaderBuilder::new()
+            .flexible(true)
+            .from_path(&self.file_path)
+            .with_context(|| format!("Failed to read {}", self.file_path.display()))?;
+        let mut entries = Vec::new();
+        let mut record = StringRecord::new();
+        while reader.read_record(&mut record)? {
+            if let Some(entry) = entry_from_record(&record) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn entry_from_record(record: &StringRecord) -> Option<SwitchLogEntry> {
+    Some(SwitchLogEntry {
+        timestamp: record.get(0)?.to_string(),
+        email: record.get(1)?.to_string(),
+        reset_machine: record.get(2)?.parse().ok()?,
+        source: SwitchSource::parse(record.get(3)?)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(second: u32, email: &str) -> SwitchLogEntry {
+        SwitchLogEntry {
+            timestamp: format!("2024-03-01 09:00:{:02}", second),
+            email: email.to_string(),
+            reset_machine: second.is_multiple_of(2),
+            source: SwitchSource::Tray,
+        }
+    }
+
+    #[test]
+    fn test_oldest_rows_are_trimmed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = SwitchLog::new(temp_dir.path().join("data").join(SWITCH_LOG_FILE));
+        assert!(log.recent(10).unwrap().is_empty());
+
+        for second in 0..5 {
+            log.record(&entry(second, &format!("user{}@x.com", second)), 3)
+                .unwrap();
+        }
+        let emails: Vec<String> = log
+            .recent(10)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.email)
+            .collect();
+        assert_eq!(emails, vec!["user4@x.com", "user3@x.com", "user2@x.com"]);
+        assert_eq!(log.recent(1).unwrap(), vec![entry(4, "user4@x.com")]);
+
+        let content = fs::read_to_string(&log.file_path).unwrap();
+        assert!(content.starts_with("Timestamp,Email,Machine ID Reset,Source\n"));
+        assert!(content.ends_with("2024-03-01 09:00:04,user4@x.com,true,tray\n"));
+
+        log.clear().unwrap();
+        assert!(log.recent(10).unwrap().is_empty());
+        log.clear().unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use crate::file_cache::FileStamp;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// File next to the account CSV with one line per switch Cursor reverted
+pub const SWITCH_REVERTS_FILE: &str = "switch_reverts.jsonl";
+/// `status_reason` of an account Cursor replaced with another one on launch
+pub const REVERT_ON_LAUNCH: &str = "revert_on_launch";
+
+/// A switch whose account was gone once Cursor had started, e.g. because
+/// Cursor re-authenticated with a refresh token the server no longer accepts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwitchRevert {
+    pub target_email: String,
+    /// Logged in once Cursor settled, None when nobody was
+    pub found_email: Option<String>,
+    pub switched_at: DateTime<Utc>,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Every reverted switch, to tell accounts that keep reverting apart
+pub struct SwitchRevertStore {
+    file_path: PathBuf,
+}
+
+impl SwitchRevertStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    pub fn record(&self, revert: &SwitchRevert) -> Result<()> {
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut content = self.read()?;
+        content.push_str(&serde_json::to_string(revert)?);
+        content.push('\n');
+        fs::write(&self.file_path, content).context("Failed to write the switch revert log")
+    }
+
+    /// Reverted switches, oldest first; unreadable lines are skipped
+    pub fn list(&self) -> Result<Vec<SwitchRevert>> {
+        Ok(self
+            .read()?
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    fn read(&self) -> Result<String> {
+        if !self.file_path.exists() {
+            return Ok(String::new());
+        }
+        fs::read_to_string(&self.file_path).context("Failed to read the switch revert log")
+    }
+}
+
+/// How long to wait for Cursor's database to settle after it starts
+#[derive(Debug, Clone, Copy)]
+pub struct SettleWait {
+    /// The files must stay unchanged this long while Cursor runs
+    pub quiet: Duration,
+    pub poll: Duration,
+    pub timeout: Duration,
+}
+
+impl SettleWait {
+    /// True once Cursor runs and `paths` haven't changed for `quiet`, false at
+    /// the timeout. Cursor logs in again as it starts, so until then its
+    /// database may still hold the account written by the switch.
+    pub fn wait(&self, paths: &[PathBuf], is_running: impl Fn() -> bool) -> bool {
+        let started = Instant::now();
+        let mut last: Option<(FileStamp, Instant)> = None;
+        while started.elapsed() < self.timeout {
+            if is_running() {
+                let stamp = FileStamp::of(paths);
+                match &last {
+                    Some((seen, since)) if *seen == stamp => {
+                        if since.elapsed() >= self.quiet {
+                            return true;
+                        }
+                    }
+                    _ => last = Some((stamp, Instant::now())),
+                }
+            } else {
+                last = None;
+            }
+            thread::sleep(self.poll);
+        }
+        false
+    }
+}
+
+/// Whether Cursor settled on another account than the one switched to, or on none
+pub fn is_reverted(target_email: &str, found_email: Option<&str>) -> bool {
+    found_email.is_none_or(|found| !found.eq_ignore_ascii_case(target_email))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const WAIT: SettleWait = SettleWait {
+        quiet: Duration::from_millis(50),
+        poll: Duration::from_millis(10),
+        timeout: Duration::from_millis(500),
+    };
+
+    #[test]
+    fn test_revert_log_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = SwitchRevertStore::new(temp_dir.path().join(SWITCH_REVERTS_FILE));
+        let at = Utc.with_ymd_and_hms(2024, 5, 2, 10, 0, 0).unwrap();
+        let revert = SwitchRevert {
+            target_email: "a@x.com".to_string(),
+            found_email: Some("b@x.com".to_string()),
+            switched_at: at,
+            detected_at: at + chrono::Duration::seconds(40),
+        };
+
+        assert!(store.list().unwrap().is_empty());
+        store.record(&revert).unwrap();
+        store.record(&revert).unwrap();
+        assert_eq!(store.list().unwrap(), vec![revert.clone(), revert]);
+
+        assert!(is_reverted("a@x.com", Some("b@x.com")));
+        assert!(is_reverted("a@x.com", None));
+        assert!(!is_reverted("a@x.com", Some("A@x.com")));
+    }
+
+    #[test]
+    fn test_settle_wait_needs_cursor_running() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("state.vscdb");
+        fs::write(&db_path, "db").unwrap();
+        let paths = [db_path];
+
+        assert!(WAIT.wait(&paths, || true));
+        let started = Instant::now();
+        assert!(!WAIT.wait(&paths, || false));
+        assert!(started.elapsed() >= WAIT.timeout);
+    }
+}
@@ -0,0 +1,55 @@
+use crate::types::Account;
+
+/// Joins the tags of an account in its CSV column
+pub const SEPARATOR: char = ';';
+
+/// Trimmed tags without empty ones or repeats, in their first spelling. A
+/// tag containing the separator counts as several.
+pub fn normalize<S: AsRef<str>>(tags: &[S]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().flat_map(|tag| tag.as_ref().split(SEPARATOR)) {
+        let tag: String = tag.trim().chars().filter(|c| !c.is_control()).collect();
+        if !tag.is_empty() && !normalized.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// The tags stored in a CSV column; empty for rows from before tags
+pub fn parse(column: &str) -> Vec<String> {
+    normalize(&[column])
+}
+
+/// The CSV column form of `tags`
+pub fn format<S: AsRef<str>>(tags: &[S]) -> String {
+    normalize(tags).join(&SEPARATOR.to_string())
+}
+
+/// Whether `account` carries `tag`, ignoring case
+pub fn has_tag(account: &Account, tag: &str) -> bool {
+    let tag = tag.trim();
+    account.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+/// Whether `account` is shown under `filter`; every account is shown under None
+pub fn matches(account: &Account, filter: Option<&str>) -> bool {
+    filter.is_none_or(|tag| has_tag(account, tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_trims_and_deduplicates() {
+        assert_eq!(
+            normalize(&[" work ", "", "trial", "Work", "  ", "burned;trial; x "]),
+            vec!["work", "trial", "burned", "x"]
+        );
+        assert!(normalize::<&str>(&[]).is_empty());
+        assert_eq!(format(&["b ", "a", "b"]), "b;a");
+        assert_eq!(parse("work;;trial;"), vec!["work", "trial"]);
+        assert!(parse("").is_empty());
+    }
+}
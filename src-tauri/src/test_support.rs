@@ -0,0 +1,206 @@
+// Test doubles shared by the unit tests
+
+use crate::api_client::{ApiError, CursorApi};
+use crate::state::MutexExt;
+use crate::token_auth::TokenCheck;
+use crate::types::{Account, AccountInfo, MembershipStatus, UsageInfo};
+use anyhow::{anyhow, Result};
+use reqwest::header::HeaderMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Usage every account answering with `MockResponse::Account` reports
+pub const MOCK_USAGE: UsageInfo = UsageInfo {
+    total_quota: 20.0,
+    used: 5.0,
+    remaining: 15.0,
+    usage_percentage: 25.0,
+};
+
+/// An account that was never refreshed, with tokens made from its email.
+/// Tests set what they check on top: `Account { pool, ..account(email) }`
+pub fn account(email: &str) -> Account {
+    Account {
+        email: email.to_string(),
+        access_token: format!("{}_access", email).into(),
+        refresh_token: format!("{}_refresh", email).into(),
+        ..Default::default()
+    }
+}
+
+/// What the mock answers for an account
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockResponse {
+    Account {
+        status: MembershipStatus,
+        days_remaining: f64,
+    },
+    /// The token is refused with HTTP 401
+    Unauthorized,
+    /// HTTP 429
+    RateLimited,
+    /// The request never reaches the API
+    Offline,
+}
+
+impl MockResponse {
+    pub fn pro() -> Self {
+        MockResponse::Account {
+            status: MembershipStatus::Pro,
+            days_remaining: -1.0,
+        }
+    }
+
+    fn into_error(self) -> anyhow::Error {
+        match self {
+            MockResponse::Account { .. } => unreachable!("not a failure"),
+            MockResponse::Unauthorized => ApiError::Unauthorized { status: 401 }.into(),
+            MockResponse::RateLimited => ApiError::RateLimited.into(),
+            MockResponse::Offline => anyhow!("error sending request: connection refused"),
+        }
+    }
+}
+
+struct Script {
+    access_token: String,
+    responses: VecDeque<MockResponse>,
+    calls: usize,
+}
+
+impl Script {
+    // Hand out the next response; the last one repeats forever
+    fn next(&mut self) -> MockResponse {
+        self.calls += 1;
+        match self.responses.len() {
+            0 | 1 => self.current(),
+            _ => self.responses.pop_front().unwrap(),
+        }
+    }
+
+    fn current(&self) -> MockResponse {
+        self.responses
+            .front()
+            .cloned()
+            .unwrap_or(MockResponse::Offline)
+    }
+}
+
+/// A `CursorApi` scripted per email. Account info calls step through the
+/// account's responses; usage and token calls answer with the current one
+/// without advancing. Unknown emails and tokens are unauthorized.
+#[derive(Default)]
+pub struct MockCursorApi {
+    scripts: Mutex<HashMap<String, Script>>,
+    latency: Duration,
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+}
+
+impl MockCursorApi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn script(
+        self,
+        email: &str,
+        access_token: &str,
+        responses: impl IntoIterator<Item = MockResponse>,
+    ) -> Self {
+        self.scripts.lock_or_recover().insert(
+            email.to_string(),
+            Script {
+                access_token: access_token.to_string(),
+                responses: responses.into_iter().collect(),
+                calls: 0,
+            },
+        );
+        self
+    }
+
+    /// Make every account info call take `latency`, so overlapping calls can be counted
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Most account info calls that were running at the same time
+    pub fn peak_in_flight(&self) -> usize {
+        self.peak_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// How often account info was asked for `email`
+    pub fn calls(&self, email: &str) -> usize {
+        self.scripts
+            .lock_or_recover()
+            .get(email)
+            .map_or(0, |script| script.calls)
+    }
+
+    fn by_token(&self, access_token: &str) -> Option<(String, MockResponse)> {
+        self.scripts
+            .lock_or_recover()
+            .iter()
+            .find(|(_, script)| script.access_token == access_token)
+            .map(|(email, script)| (email.clone(), script.current()))
+    }
+}
+
+impl CursorApi for MockCursorApi {
+    fn get_account_info(
+        &self,
+        email: &str,
+        access_token: &str,
+        _extra: &HeaderMap,
+    ) -> Result<AccountInfo> {
+        let running = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak_in_flight.fetch_max(running, Ordering::SeqCst);
+        std::thread::sleep(self.latency);
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let response = match self.scripts.lock_or_recover().get_mut(email) {
+            Some(script) if script.access_token == access_token => script.next(),
+            _ => MockResponse::Unauthorized,
+        };
+        match response {
+            MockResponse::Account {
+                status,
+                days_remaining,
+            } => Ok(AccountInfo {
+                email: email.to_string(),
+                membership_type: status,
+                days_remaining,
+                is_student: false,
+                privacy_mode: None,
+            }),
+            failure => Err(failure.into_error()),
+        }
+    }
+
+    fn get_usage_info(&self, access_token: &str, _extra: &HeaderMap) -> Result<UsageInfo> {
+        match self.by_token(access_token) {
+            Some((_, MockResponse::Account { .. })) => Ok(MOCK_USAGE),
+            Some((_, failure)) => Err(failure.into_error()),
+            None => Err(MockResponse::Unauthorized.into_error()),
+        }
+    }
+
+    fn get_email(&self, access_token: &str) -> Result<String> {
+        match self.by_token(access_token) {
+            Some((email, MockResponse::Account { .. })) => Ok(email),
+            Some((_, failure)) => Err(failure.into_error()),
+            None => Err(MockResponse::Unauthorized.into_error()),
+        }
+    }
+
+    fn check_access_token(&self, access_token: &str) -> TokenCheck {
+        match self.by_token(access_token).map(|(_, response)| response) {
+            Some(MockResponse::Account { .. }) => TokenCheck::Valid,
+            Some(MockResponse::RateLimited) => TokenCheck::Rejected { status: 429 },
+            Some(MockResponse::Offline) => TokenCheck::Unreachable("offline".to_string()),
+            Some(MockResponse::Unauthorized) | None => TokenCheck::Rejected { status: 401 },
+        }
+    }
+}
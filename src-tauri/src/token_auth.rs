@@ -1,8 +1,11 @@
-use crate::types::{Account, TokenInfo, TokenResponse};
+use crate::http::{HttpClient, HttpClientFactory};
+use crate::redaction;
+use crate::secret::SecretString;
+use crate::types::{record_time, Account, AccountSource, TokenInfo, TokenResponse};
 use anyhow::{anyhow, Context, Result};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
 use rand::Rng;
-use reqwest::blocking::Client;
 use reqwest::header::{
     HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, COOKIE, ORIGIN, REFERER,
     USER_AGENT,
@@ -26,6 +29,11 @@ struct JwtClaims {
     sub: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct ExpiryClaims {
+    exp: Option<i64>,
+}
+
 #[derive(Debug, Serialize)]
 struct AuthCallbackRequest {
     uuid: String,
@@ -35,11 +43,11 @@ struct AuthCallbackRequest {
 #[derive(Debug, Deserialize)]
 struct PollResponse {
     #[serde(rename = "accessToken")]
-    access_token: Option<String>,
+    access_token: Option<SecretString>,
     #[serde(rename = "refreshToken")]
-    refresh_token: Option<String>,
+    refresh_token: Option<SecretString>,
     #[serde(rename = "workSessionToken")]
-    work_session_token: Option<String>,
+    work_session_token: Option<SecretString>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,8 +55,8 @@ struct EmailResponse {
     email: Option<String>,
 }
 
-/// Extract user ID from JWT token
-pub fn extract_user_id_from_jwt(token: &str) -> Result<String> {
+/// Decode the claims (second part) of a JWT
+fn decode_jwt_payload(token: &str) -> Result<Vec<u8>> {
     // JWT format: header.payload.signature
     let parts: Vec<&str> = token.split('.').collect();
     if parts.len() != 3 {
@@ -59,7 +67,7 @@ pub fn extract_user_id_from_jwt(token: &str) -> Result<String> {
     let payload = parts[1];
 
     // Base64 URL decode (handle padding)
-    let decoded = URL_SAFE_NO_PAD
+    URL_SAFE_NO_PAD
         .decode(payload)
         .or_else(|_| {
             // Try with padding if needed
@@ -69,7 +77,12 @@ pub fn extract_user_id_from_jwt(token: &str) -> Result<String> {
             }
             base64::engine::general_purpose::URL_SAFE.decode(padded.as_bytes())
         })
-        .context("Failed to decode JWT payload")?;
+        .context("Failed to decode JWT payload")
+}
+
+/// Extract user ID from JWT token
+pub fn extract_user_id_from_jwt(token: &str) -> Result<String> {
+    let decoded = decode_jwt_payload(token)?;
 
     // Parse JSON to extract 'sub' claim
     let claims: JwtClaims =
@@ -90,6 +103,21 @@ pub fn extract_user_id_from_jwt(token: &str) -> Result<String> {
     Ok(user_id)
 }
 
+/// Expiry of a JWT or session token, None when the token carries no `exp` claim
+pub fn token_expiry(token: &str) -> Result<Option<DateTime<Utc>>> {
+    let decoded_token = token.trim().replace("%3A%3A", "::").replace("%3a%3a", "::");
+    // Session tokens wrap the JWT: user_xxx::eyJ...
+    let jwt = decoded_token
+        .split_once("::")
+        .map_or(decoded_token.as_str(), |(_, jwt)| jwt);
+
+    let claims: ExpiryClaims =
+        serde_json::from_slice(&decode_jwt_payload(jwt)?).context("Failed to parse JWT claims")?;
+    Ok(claims
+        .exp
+        .and_then(|exp| DateTime::<Utc>::from_timestamp(exp, 0)))
+}
+
 /// Generate PKCE code verifier and challenge
 pub fn generate_pkce() -> Result<(String, String)> {
     // Generate random verifier (43-128 characters)
@@ -165,19 +193,29 @@ pub fn validate_token_info(token: &str) -> Result<TokenInfo> {
     }
 }
 
+/// Result of trying an access token against the GetEmail endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenCheck {
+    Valid,
+    /// The API answered and refused the token
+    Rejected {
+        status: u16,
+    },
+    /// The API couldn't be reached in time, so nothing is known about the token
+    Unreachable(String),
+}
+
 /// Token authentication client for Cursor API
 pub struct TokenAuthClient {
-    client: Client,
+    client: HttpClient,
 }
 
 impl TokenAuthClient {
-    pub fn new() -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
-
-        Self { client }
+    /// Use `HttpClientFactory::with_timeout` for quick checks such as `check_access_token`
+    pub fn new(http: &HttpClientFactory) -> Result<Self> {
+        Ok(Self {
+            client: http.client()?,
+        })
     }
 
     /// Authorize login with session token
@@ -209,15 +247,17 @@ impl TokenAuthClient {
         );
 
         tracing::info!("Sending authorization request with UUID: {}", uuid);
-        tracing::debug!("Session token: {}", session_token);
+        tracing::debug!("Session token: {}", redaction::mask_token(session_token));
         tracing::debug!("Challenge: {}", code_challenge);
 
         let response = self
             .client
-            .post(CURSOR_AUTH_CALLBACK_URL)
-            .headers(headers)
-            .json(&request_body)
-            .send()
+            .send(
+                self.client
+                    .post(CURSOR_AUTH_CALLBACK_URL)
+                    .headers(headers)
+                    .json(&request_body),
+            )
             .context("Failed to send authorization request")?;
 
         let status = response.status();
@@ -247,9 +287,11 @@ impl TokenAuthClient {
         for attempt in 1..=POLL_MAX_ATTEMPTS {
             let response = self
                 .client
-                .get(&poll_url)
-                .header(USER_AGENT, "Mozilla/5.0 Cursor/1.0")
-                .send()
+                .send(
+                    self.client
+                        .get(&poll_url)
+                        .header(USER_AGENT, "Mozilla/5.0 Cursor/1.0"),
+                )
                 .context("Failed to poll for tokens")?;
 
             if response.status().is_success() {
@@ -278,8 +320,30 @@ impl TokenAuthClient {
         ))
     }
 
-    /// Get email from access token
-    fn get_email(&self, access_token: &str) -> Result<String> {
+    /// One cheap authenticated request to find out whether a token still works
+    pub fn check_access_token(&self, access_token: &str) -> TokenCheck {
+        match self.send_get_email(access_token) {
+            Ok(response) if response.status().is_success() => TokenCheck::Valid,
+            Ok(response) => TokenCheck::Rejected {
+                status: response.status().as_u16(),
+            },
+            Err(e) => TokenCheck::Unreachable(format!("{:#}", e)),
+        }
+    }
+
+    /// `check_access_token` with its own timeout, for checks that must not hold up a switch
+    pub fn check_with_timeout(
+        http: &HttpClientFactory,
+        timeout: Duration,
+        access_token: &str,
+    ) -> TokenCheck {
+        match Self::new(&http.with_timeout(timeout)) {
+            Ok(client) => client.check_access_token(access_token),
+            Err(e) => TokenCheck::Unreachable(format!("{:#}", e)),
+        }
+    }
+
+    fn send_get_email(&self, access_token: &str) -> Result<reqwest::blocking::Response> {
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         headers.insert(
@@ -292,13 +356,19 @@ impl TokenAuthClient {
             HeaderValue::from_static("1"),
         );
 
-        let response = self
-            .client
-            .post(CURSOR_GET_EMAIL_URL)
-            .headers(headers)
-            .json(&json!({}))
-            .send()
-            .context("Failed to get email")?;
+        self.client
+            .send(
+                self.client
+                    .post(CURSOR_GET_EMAIL_URL)
+                    .headers(headers)
+                    .json(&json!({})),
+            )
+            .context("Failed to get email")
+    }
+
+    /// Get email from access token
+    pub fn get_email(&self, access_token: &str) -> Result<String> {
+        let response = self.send_get_email(access_token)?;
 
         if !response.status().is_success() {
             return Err(anyhow!(
@@ -338,24 +408,20 @@ impl TokenAuthClient {
 
         // Step 5: Get email
         let email = self
-            .get_email(&token_response.access_token)
+            .get_email(token_response.access_token.expose())
             .context("Failed to get email from token")?;
 
         // Step 6: Build account
         let account = Account {
             index: 0, // Will be assigned by CSV manager
             email,
-            access_token: token_response.access_token.clone(),
-            refresh_token: token_response.refresh_token.clone(),
-            cookie: session_token,
-            days_remaining: "0".to_string(),
-            status: "unknown".to_string(),
-            record_time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-            source: "token_import".to_string(),
-            usage_used: None,
-            usage_remaining: None,
-            usage_total: None,
-            usage_percentage: None,
+            access_token: token_response.access_token,
+            refresh_token: token_response.refresh_token,
+            cookie: session_token.into(),
+            days_remaining: Some(0.0),
+            record_time: chrono::Local::now().format(record_time::FORMAT).to_string(),
+            source: AccountSource::TokenImport,
+            ..Default::default()
         };
 
         Ok(account)
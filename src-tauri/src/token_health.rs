@@ -0,0 +1,120 @@
+use crate::token_auth::{self, TokenCheck};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Warn about the active account this long before its token expires
+pub const EXPIRY_WARNING_HOURS: i64 = 24;
+/// How often the active account's token is checked in the background
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(3 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TokenHealth {
+    Healthy,
+    Expiring { expires_at: DateTime<Utc> },
+    Expired,
+}
+
+impl TokenHealth {
+    /// Whether the user should re-authenticate before Cursor stops working
+    pub fn needs_reauth(&self) -> bool {
+        !matches!(self, TokenHealth::Healthy)
+    }
+}
+
+/// Outcome of the last check of the account logged into Cursor
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenHealthReport {
+    pub email: String,
+    pub health: TokenHealth,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Judge a token by its `exp` claim, asking the API through `check` only when
+/// the token has no readable expiry. None when nothing can be said, e.g. offline.
+pub fn assess(
+    access_token: &str,
+    now: DateTime<Utc>,
+    check: impl FnOnce() -> TokenCheck,
+) -> Option<TokenHealth> {
+    if let Ok(Some(expires_at)) = token_auth::token_expiry(access_token) {
+        return Some(if expires_at <= now {
+            TokenHealth::Expired
+        } else if expires_at - now <= chrono::Duration::hours(EXPIRY_WARNING_HOURS) {
+            TokenHealth::Expiring { expires_at }
+        } else {
+            TokenHealth::Healthy
+        });
+    }
+
+    match check() {
+        TokenCheck::Valid => Some(TokenHealth::Healthy),
+        TokenCheck::Rejected { status: 401 | 403 } => Some(TokenHealth::Expired),
+        TokenCheck::Rejected { status } => {
+            tracing::debug!("Token check answered with HTTP {}", status);
+            None
+        }
+        TokenCheck::Unreachable(reason) => {
+            tracing::debug!("Token check skipped: {}", reason);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use chrono::TimeZone;
+
+    fn jwt_expiring_at(exp: i64) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(format!(r#"{{"sub":"auth0|user_1","exp":{}}}"#, exp));
+        format!("eyJhbGciOiJIUzI1NiJ9.{}.c2ln", payload)
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_assess_by_expiry_claim() {
+        let unused = || panic!("the API is not needed when the token has an expiry");
+        let hours = |h: i64| (now() + chrono::Duration::hours(h)).timestamp();
+
+        assert_eq!(
+            assess(&jwt_expiring_at(hours(72)), now(), unused),
+            Some(TokenHealth::Healthy)
+        );
+        assert_eq!(
+            assess(&jwt_expiring_at(hours(-1)), now(), unused),
+            Some(TokenHealth::Expired)
+        );
+
+        let expiring = assess(&jwt_expiring_at(hours(5)), now(), unused).unwrap();
+        assert!(matches!(expiring, TokenHealth::Expiring { .. }));
+        assert!(expiring.needs_reauth());
+
+        // Session tokens carry the JWT after the user ID
+        let session = format!("user_1%3A%3A{}", jwt_expiring_at(hours(72)));
+        assert_eq!(assess(&session, now(), unused), Some(TokenHealth::Healthy));
+    }
+
+    #[test]
+    fn test_assess_falls_back_to_api() {
+        assert_eq!(
+            assess("opaque", now(), || TokenCheck::Rejected { status: 401 }),
+            Some(TokenHealth::Expired)
+        );
+        assert_eq!(
+            assess("opaque", now(), || TokenCheck::Valid),
+            Some(TokenHealth::Healthy)
+        );
+        assert_eq!(
+            assess("opaque", now(), || TokenCheck::Unreachable(
+                "offline".to_string()
+            )),
+            None
+        );
+    }
+}
@@ -1,17 +1,29 @@
-use serde::{Deserialize, Serialize};
+use crate::change_set::KeyChange;
+use crate::cursor_settings::SettingsRestore;
+use crate::secret::SecretString;
+use chrono::{DateTime, Utc};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Serialized by hand so the JSON can carry `days_remaining_display` next to
+/// the numeric `days_remaining`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
 pub struct Account {
     pub index: i32,
     pub email: String,
-    pub access_token: String,
-    pub refresh_token: String,
-    pub cookie: String,
-    pub days_remaining: String,
-    pub status: String,
+    pub access_token: SecretString,
+    pub refresh_token: SecretString,
+    pub cookie: SecretString,
+    /// None for paid plans without a trial ("N/A" in the CSV) and unknown values
+    #[serde(default, deserialize_with = "days_remaining::deserialize")]
+    pub days_remaining: Option<f64>,
+    pub status: MembershipStatus,
     pub record_time: String,
-    #[serde(default = "default_source")]
-    pub source: String, // "imported" or "web_login"
+    #[serde(default)]
+    pub source: AccountSource,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage_used: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -20,18 +32,403 @@ pub struct Account {
     pub usage_total: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage_percentage: Option<f64>,
+    /// Emoji shown ahead of the account in the tray and the account list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label_emoji: Option<String>,
+    /// Chip color in the account list, as "#rrggbb"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label_color: Option<String>,
+    /// Privacy mode applied to Cursor when switching to this account; None leaves it alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub privacy_mode: Option<bool>,
+    /// Sent with every Cursor API request for this account, e.g. the org token
+    /// an enterprise gateway wants
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_headers: Option<HashMap<String, SecretString>>,
+    /// Named group the account belongs to, e.g. "work"; None is the default pool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    /// Why the account needs attention beyond its status, e.g. "revert_on_launch"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_reason: Option<String>,
+    /// Free-form groups such as "work" or "burned", trimmed and without repeats
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Listed ahead of the others in the tray, with a star
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub favorite: bool,
+    /// When a switch last wrote the account into Cursor, in the form of
+    /// `record_time`; empty when it never was
+    #[serde(default)]
+    pub last_used: String,
+    /// Kept with its tokens but out of the list, the tray and batch refreshes
+    /// until restored; see `archive_account`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    /// The account's Cursor login password, if kept. Accepted from the
+    /// frontend but never serialized back; `get_account_password` returns it
+    #[serde(default)]
+    pub password: Option<SecretString>,
+    /// Shown with the email in the tray, e.g. "Main"; see `set_account_nickname`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
 }
 
-fn default_source() -> String {
-    "imported".to_string()
+/// Returned by set_privacy_mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyModeChange {
+    pub enabled: bool,
+    /// Cursor was running and only picks the change up after a restart
+    pub restart_required: bool,
+}
+
+/// Full credentials of one account, only returned on explicit request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSecrets {
+    pub email: String,
+    pub access_token: SecretString,
+    pub refresh_token: SecretString,
+    pub cookie: SecretString,
+}
+
+impl Serialize for Account {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Account", 26)?;
+        state.serialize_field("index", &self.index)?;
+        state.serialize_field("email", &self.email)?;
+        state.serialize_field("access_token", &self.access_token)?;
+        state.serialize_field("refresh_token", &self.refresh_token)?;
+        state.serialize_field("cookie", &self.cookie)?;
+        state.serialize_field("days_remaining", &self.days_remaining)?;
+        // Kept while the frontend moves off the old string field
+        state.serialize_field(
+            "days_remaining_display",
+            &days_remaining::format(self.days_remaining),
+        )?;
+        state.serialize_field("status", &self.status)?;
+        state.serialize_field("record_time", &self.record_time)?;
+        state.serialize_field("last_used", &self.last_used)?;
+        state.serialize_field("source", &self.source)?;
+
+        macro_rules! optional {
+            ($($field:ident),*) => {$(
+                match &self.$field {
+                    Some(value) => state.serialize_field(stringify!($field), value)?,
+                    None => state.skip_field(stringify!($field))?,
+                }
+            )*};
+        }
+        optional!(
+            usage_used,
+            usage_remaining,
+            usage_total,
+            usage_percentage,
+            label_emoji,
+            label_color,
+            privacy_mode,
+            extra_headers,
+            pool,
+            status_reason,
+            nickname
+        );
+        if self.tags.is_empty() {
+            state.skip_field("tags")?;
+        } else {
+            state.serialize_field("tags", &self.tags)?;
+        }
+        if self.favorite {
+            state.serialize_field("favorite", &true)?;
+        } else {
+            state.skip_field("favorite")?;
+        }
+        if self.archived {
+            state.serialize_field("archived", &true)?;
+        } else {
+            state.skip_field("archived")?;
+        }
+        state.skip_field("password")?;
+        state.end()
+    }
+}
+
+/// Conversions between `Account::days_remaining` and the strings older
+/// versions stored: "N/A" for paid plans, otherwise a number such as "12.5"
+pub mod days_remaining {
+    use super::*;
+
+    /// "N/A", empty and unreadable values all mean there is no day count
+    pub fn parse(text: &str) -> Option<f64> {
+        text.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|days| days.is_finite())
+    }
+
+    /// The CSV and display form, "N/A" when there is no day count
+    pub fn format(days: Option<f64>) -> String {
+        match days {
+            Some(days) => days.to_string(),
+            None => "N/A".to_string(),
+        }
+    }
+
+    /// The API reports -1 for paid plans; trial days are kept to one decimal
+    pub fn from_api(days: f64) -> Option<f64> {
+        (days >= 0.0).then(|| (days * 10.0).round() / 10.0)
+    }
+
+    /// Accepts a number, null, or one of the legacy strings
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<f64>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Legacy {
+            Number(f64),
+            Text(String),
+        }
+
+        Ok(match Option::<Legacy>::deserialize(deserializer)? {
+            Some(Legacy::Number(days)) => Some(days),
+            Some(Legacy::Text(text)) => parse(&text),
+            None => None,
+        })
+    }
+}
+
+/// `Account::record_time`, local time as text
+pub mod record_time {
+    use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+
+    /// The form this app writes, e.g. "2024-03-10 12:00:00"
+    pub const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+    /// Forms found in CSVs from older versions and other tools
+    const LEGACY_FORMATS: &[&str] = &[
+        "%Y-%m-%d %H:%M",
+        "%Y/%m/%d %H:%M:%S",
+        "%Y/%m/%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S%.f",
+    ];
+    const LEGACY_DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d"];
+
+    /// None when the text is empty or no known form; RFC 3339 times with an
+    /// offset are converted to local time, a bare date means its midnight
+    pub fn parse(text: &str) -> Option<NaiveDateTime> {
+        let text = text.trim();
+        std::iter::once(FORMAT)
+            .chain(LEGACY_FORMATS.iter().copied())
+            .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+            .or_else(|| {
+                DateTime::parse_from_rfc3339(text)
+                    .ok()
+                    .map(|time| time.with_timezone(&Local).naive_local())
+            })
+            .or_else(|| {
+                LEGACY_DATE_FORMATS
+                    .iter()
+                    .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            })
+    }
+
+    /// Hours from the record time to `now`, None when it can't be read
+    pub fn age_hours(text: &str, now: NaiveDateTime) -> Option<f64> {
+        parse(text).map(|recorded| ((now - recorded).num_seconds().max(0)) as f64 / 3600.0)
+    }
+}
+
+/// Order of the accounts `get_all_accounts` returns; stored order without one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountSort {
+    /// Favorites first, each part in stored order
+    Favorites,
+}
+
+/// Where `move_account` moves an account: one row towards the top or the bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveDirection {
+    Up,
+    Down,
+}
+
+/// An account as `get_all_accounts` lists it, with how fresh its data is
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountRow {
+    #[serde(flatten)]
+    pub account: Account,
+    /// Last refreshed `stale_after_hours` ago or earlier, or never
+    pub stale: bool,
+    /// None when the record time can't be read
+    pub data_age_hours: Option<f64>,
+}
+
+/// Membership reported by the API, plus the states this app records itself.
+/// Values it doesn't know are kept verbatim in `Other`, so they survive a
+/// round-trip through the CSV and the frontend.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum MembershipStatus {
+    Free,
+    FreeTrial,
+    Pro,
+    Premium,
+    Ultra,
+    Expired,
+    Banned,
+    /// The last refresh failed
+    Error,
+    /// Never refreshed
+    #[default]
+    Unknown,
+    Other(String),
+}
+
+impl MembershipStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Free => "free",
+            Self::FreeTrial => "free_trial",
+            Self::Pro => "pro",
+            Self::Premium => "premium",
+            Self::Ultra => "ultra",
+            Self::Expired => "expired",
+            Self::Banned => "banned",
+            Self::Error => "error",
+            Self::Unknown => "unknown",
+            Self::Other(raw) => raw,
+        }
+    }
+
+    /// Trials run out once their days are used up
+    pub fn is_trial(&self) -> bool {
+        match self {
+            Self::FreeTrial => true,
+            Self::Other(raw) => raw.to_lowercase().contains("trial"),
+            _ => false,
+        }
+    }
+
+    /// Banned or expired, including unknown values that say so
+    pub fn is_revoked(&self) -> bool {
+        match self {
+            Self::Banned | Self::Expired => true,
+            Self::Other(raw) => {
+                let raw = raw.to_lowercase();
+                raw.contains("banned") || raw.contains("expired")
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<&str> for MembershipStatus {
+    fn from(raw: &str) -> Self {
+        match raw {
+            "free" => Self::Free,
+            "free_trial" => Self::FreeTrial,
+            "pro" => Self::Pro,
+            "premium" => Self::Premium,
+            "ultra" => Self::Ultra,
+            "expired" => Self::Expired,
+            "banned" => Self::Banned,
+            "error" => Self::Error,
+            "unknown" => Self::Unknown,
+            _ => Self::Other(raw.to_string()),
+        }
+    }
+}
+
+impl From<String> for MembershipStatus {
+    fn from(raw: String) -> Self {
+        Self::from(raw.as_str())
+    }
+}
+
+impl From<MembershipStatus> for String {
+    fn from(status: MembershipStatus) -> Self {
+        match status {
+            MembershipStatus::Other(raw) => raw,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for MembershipStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How an account got into the CSV; unknown values are kept in `Other`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum AccountSource {
+    /// The default for rows written before the column existed
+    #[default]
+    Imported,
+    WebLogin,
+    TokenImport,
+    /// Imported from a session cookie alone, see `csv_manager::pending_email`
+    CookieImport,
+    Other(String),
+}
+
+impl AccountSource {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Imported => "imported",
+            Self::WebLogin => "web_login",
+            Self::TokenImport => "token_import",
+            Self::CookieImport => "cookie_import",
+            Self::Other(raw) => raw,
+        }
+    }
+}
+
+impl From<&str> for AccountSource {
+    fn from(raw: &str) -> Self {
+        match raw {
+            "imported" => Self::Imported,
+            "web_login" => Self::WebLogin,
+            "token_import" => Self::TokenImport,
+            "cookie_import" => Self::CookieImport,
+            _ => Self::Other(raw.to_string()),
+        }
+    }
+}
+
+impl From<String> for AccountSource {
+    fn from(raw: String) -> Self {
+        Self::from(raw.as_str())
+    }
+}
+
+impl From<AccountSource> for String {
+    fn from(source: AccountSource) -> Self {
+        match source {
+            AccountSource::Other(raw) => raw,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for AccountSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccountInfo {
     pub email: String,
-    pub membership_type: String,
+    pub membership_type: MembershipStatus,
     pub days_remaining: f64,
     pub is_student: bool,
+    /// Cursor's privacy mode while this account is logged in, when known
+    #[serde(default)]
+    pub privacy_mode: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,12 +439,87 @@ pub struct UsageInfo {
     pub usage_percentage: f64,
 }
 
+/// A value that may come from a cache. `stale` is set when it was served
+/// because the app is offline; `cache_age_secs` says how old cached data is.
+#[derive(Debug, Clone, Serialize)]
+pub struct MaybeStale<T> {
+    #[serde(flatten)]
+    pub value: T,
+    pub stale: bool,
+    pub cache_age_secs: Option<u64>,
+}
+
+impl<T> MaybeStale<T> {
+    pub fn fresh(value: T) -> Self {
+        Self {
+            value,
+            stale: false,
+            cache_age_secs: None,
+        }
+    }
+
+    pub fn cached(value: T, age: Duration) -> Self {
+        Self {
+            value,
+            stale: false,
+            cache_age_secs: Some(age.as_secs()),
+        }
+    }
+
+    pub fn stale(value: T, age: Option<Duration>) -> Self {
+        Self {
+            value,
+            stale: true,
+            cache_age_secs: age.map(|age| age.as_secs()),
+        }
+    }
+}
+
+/// A set of Cursor telemetry IDs and where it came from. The provenance fields
+/// default so the plain four-field JSON of older versions still loads.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MachineIds {
     pub machine_id: String,
     pub mac_machine_id: String,
     pub dev_device_id: String,
     pub sqm_id: String,
+    #[serde(default)]
+    pub generated_at: Option<DateTime<Utc>>,
+    /// Files the set was written to
+    #[serde(default)]
+    pub applied_to: Vec<PathBuf>,
+    #[serde(default)]
+    pub source: MachineIdSource,
+    /// What was put back from the settings snapshot taken before the reset
+    #[serde(default)]
+    pub preserved_settings: Option<SettingsRestore>,
+    /// Keys the reset wrote, by hash
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<KeyChange>,
+}
+
+/// The telemetry IDs Cursor currently has, as returned by `get_machine_ids`.
+/// A key that isn't there is None.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CurrentMachineIds {
+    pub machine_id: Option<String>,
+    pub mac_machine_id: Option<String>,
+    pub dev_device_id: Option<String>,
+    pub sqm_id: Option<String>,
+    /// `MachineGuid` from the registry; always None outside Windows
+    pub registry_guid: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MachineIdSource {
+    /// Freshly made by `MachineIdGenerator`
+    #[default]
+    Generated,
+    /// Put back from a backup
+    Restored,
+    /// Entered by the user
+    UserProvided,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,9 +531,9 @@ pub struct TokenInfo {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenResponse {
-    pub access_token: String,
-    pub refresh_token: String,
-    pub work_session_token: Option<String>,
+    pub access_token: SecretString,
+    pub refresh_token: SecretString,
+    pub work_session_token: Option<SecretString>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +544,20 @@ pub struct BillingCycle {
     pub limit: Option<f64>,
 }
 
+/// Everything the account detail window shows, fetched in one call
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountOverview {
+    /// Stored row with masked tokens
+    pub account: Account,
+    pub is_current: bool,
+    pub usage: Option<UsageInfo>,
+    /// True when `usage` comes from the stored row instead of a fresh API call
+    pub usage_cached: bool,
+    pub billing_cycle: Option<BillingCycle>,
+    /// Parts that couldn't be loaded, with tokens masked
+    pub errors: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DetailedUserInfo {
     pub email: Option<String>,
@@ -79,3 +565,159 @@ pub struct DetailedUserInfo {
     pub membership_type: Option<String>,
     pub subscription_status: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_json(days_remaining: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "index": 1,
+            "email": "a@x.com",
+            "access_token": "access",
+            "refresh_token": "refresh",
+            "cookie": "",
+            "days_remaining": days_remaining,
+            "status": "free_trial",
+            "record_time": "",
+        })
+    }
+
+    #[test]
+    fn test_days_remaining_accepts_legacy_strings() {
+        let days = |value| {
+            serde_json::from_value::<Account>(account_json(value))
+                .unwrap()
+                .days_remaining
+        };
+        assert_eq!(days(serde_json::json!("12.5")), Some(12.5));
+        assert_eq!(days(serde_json::json!("N/A")), None);
+        assert_eq!(days(serde_json::json!("")), None);
+        assert_eq!(days(serde_json::json!(3)), Some(3.0));
+        assert_eq!(days(serde_json::Value::Null), None);
+
+        assert_eq!(days_remaining::from_api(-1.0), None);
+        assert_eq!(days_remaining::from_api(6.66), Some(6.7));
+        assert_eq!(days_remaining::format(Some(30.0)), "30");
+    }
+
+    #[test]
+    fn test_record_time_accepts_legacy_formats() {
+        let expected = chrono::NaiveDate::from_ymd_opt(2024, 3, 10)
+            .unwrap()
+            .and_hms_opt(12, 30, 0)
+            .unwrap();
+        for text in [
+            "2024-03-10 12:30:00",
+            " 2024-03-10 12:30 ",
+            "2024/03/10 12:30:00",
+            "2024-03-10T12:30:00",
+        ] {
+            assert_eq!(record_time::parse(text), Some(expected), "{}", text);
+        }
+        assert_eq!(
+            record_time::parse("2024-03-10"),
+            expected.date().and_hms_opt(0, 0, 0)
+        );
+        assert!(record_time::parse("2024-03-10T12:30:00Z").is_some());
+        assert_eq!(record_time::parse(""), None);
+        assert_eq!(record_time::parse("yesterday"), None);
+
+        let now = expected + chrono::Duration::minutes(90);
+        assert_eq!(
+            record_time::age_hours("2024-03-10 12:30:00", now),
+            Some(1.5)
+        );
+        assert_eq!(record_time::age_hours("", now), None);
+    }
+
+    #[test]
+    fn test_serialized_account_has_number_and_display() {
+        let mut account: Account = serde_json::from_value(account_json("7.5".into())).unwrap();
+        let json = serde_json::to_value(&account).unwrap();
+        assert_eq!(json["days_remaining"], 7.5);
+        assert_eq!(json["days_remaining_display"], "7.5");
+        assert!(json.get("usage_used").is_none());
+
+        account.days_remaining = None;
+        account.usage_used = Some(1.5);
+        let json = serde_json::to_value(&account).unwrap();
+        assert!(json["days_remaining"].is_null());
+        assert_eq!(json["days_remaining_display"], "N/A");
+        assert_eq!(json["usage_used"], 1.5);
+
+        // What the frontend sends back, display field included, still parses
+        let round_trip: Account = serde_json::from_value(json).unwrap();
+        assert_eq!(round_trip.days_remaining, None);
+    }
+
+    #[test]
+    fn test_password_is_read_but_never_serialized() {
+        let mut json = account_json(serde_json::Value::Null);
+        assert!(serde_json::from_value::<Account>(json.clone())
+            .unwrap()
+            .password
+            .is_none());
+
+        json["password"] = "secret_password".into();
+        let account: Account = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            account.password.as_ref().map(SecretString::expose),
+            Some("secret_password")
+        );
+        let serialized = serde_json::to_string(&account).unwrap();
+        assert!(!serialized.contains("password"));
+        assert!(!format!("{:?}", account).contains("secret_password"));
+    }
+
+    #[test]
+    fn test_debug_output_hides_credentials() {
+        let mut json = account_json(serde_json::Value::Null);
+        json["access_token"] = "eyJhbGciOiJIUzI1NiJ9.secret_access".into();
+        json["cookie"] = "user_01ABC::secret_session".into();
+        let account: Account = serde_json::from_value(json).unwrap();
+
+        let debug = format!("{:?}", account);
+        assert!(debug.contains("a@x.com"));
+        assert!(debug.contains("access_token: [REDACTED]"));
+        assert!(!debug.contains("secret"));
+        assert!(!debug.contains("refresh\""));
+        assert_eq!(
+            account.access_token.expose(),
+            "eyJhbGciOiJIUzI1NiJ9.secret_access"
+        );
+    }
+
+    #[test]
+    fn test_status_and_source_keep_unknown_values() {
+        let mut json = account_json(serde_json::Value::Null);
+        json["status"] = "pro_plus".into();
+        json["source"] = "browser_extension".into();
+        let account: Account = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            account.status,
+            MembershipStatus::Other("pro_plus".to_string())
+        );
+        assert_eq!(
+            account.source,
+            AccountSource::Other("browser_extension".to_string())
+        );
+
+        let json = serde_json::to_value(&account).unwrap();
+        assert_eq!(json["status"], "pro_plus");
+        assert_eq!(json["source"], "browser_extension");
+
+        for status in ["free", "free_trial", "pro", "expired", "error", "unknown"] {
+            let parsed = MembershipStatus::from(status);
+            assert!(!matches!(parsed, MembershipStatus::Other(_)), "{}", status);
+            assert_eq!(String::from(parsed), status);
+        }
+        // Matching is exact, so a differently cased value is kept as it was
+        assert_eq!(MembershipStatus::from("Pro").as_str(), "Pro");
+        assert!(MembershipStatus::from("Free_Trial").is_trial());
+        assert!(MembershipStatus::from("account_banned").is_revoked());
+
+        let legacy: Account = serde_json::from_value(account_json("1".into())).unwrap();
+        assert_eq!(legacy.source, AccountSource::Imported);
+    }
+}
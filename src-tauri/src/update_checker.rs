@@ -0,0 +1,218 @@
+use crate::http::{HttpClient, HttpClientFactory};
+use anyhow::{anyhow, Context, Result};
+use reqwest::StatusCode;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/6639835/cursor-account-switcher/releases/latest";
+
+/// Result of comparing the running version with the latest GitHub release
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateInfo {
+    pub current: String,
+    pub latest: String,
+    pub update_available: bool,
+    pub release_notes: String,
+    /// Installer for this platform, or the release page when none matches
+    pub download_url: String,
+    pub release_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    assets: Vec<GithubAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+pub struct UpdateChecker {
+    client: HttpClient,
+}
+
+impl UpdateChecker {
+    pub fn new(http: &HttpClientFactory) -> Result<Self> {
+        let client = http
+            .with_timeout(Duration::from_secs(15))
+            .build(|builder| {
+                builder.user_agent(concat!(
+                    "cursor-account-switcher/",
+                    env!("CARGO_PKG_VERSION")
+                ))
+            })?;
+
+        Ok(Self { client })
+    }
+
+    /// Fetch the latest release and compare it with `current`
+    pub fn check(&self, current: &Version) -> Result<UpdateInfo> {
+        let request = self
+            .client
+            .get(LATEST_RELEASE_URL)
+            .header("Accept", "application/vnd.github+json");
+        let response = self
+            .client
+            .send(request)
+            .context("Failed to reach GitHub")?;
+
+        let status = response.status();
+        if is_rate_limited(status, response.headers()) {
+            let reset = response
+                .headers()
+                .get("x-ratelimit-reset")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<i64>().ok())
+                .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0))
+                .map(|reset| {
+                    reset
+                        .with_timezone(&chrono::Local)
+                        .format("%H:%M")
+                        .to_string()
+                });
+
+            return Err(match reset {
+                Some(reset) => anyhow!("GitHub API rate limit reached, try again after {}", reset),
+                None => anyhow!("GitHub API rate limit reached, try again later"),
+            });
+        }
+        if status == StatusCode::NOT_FOUND {
+            return Err(anyhow!("No published releases found"));
+        }
+        if !status.is_success() {
+            return Err(anyhow!("GitHub API returned status {}", status));
+        }
+
+        let release: GithubRelease = response.json().context("Failed to parse GitHub release")?;
+        build_update_info(current, release)
+    }
+}
+
+fn is_rate_limited(status: StatusCode, headers: &reqwest::header::HeaderMap) -> bool {
+    let remaining_zero = headers
+        .get("x-ratelimit-remaining")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim() == "0")
+        .unwrap_or(false);
+
+    status == StatusCode::TOO_MANY_REQUESTS || (status == StatusCode::FORBIDDEN && remaining_zero)
+}
+
+/// Parse a release tag such as "v2.1.0" or "2.1.0"
+pub fn parse_tag(tag: &str) -> Result<Version> {
+    let trimmed = tag.trim().trim_start_matches(['v', 'V']);
+    Version::parse(trimmed).with_context(|| format!("Invalid release tag: {}", tag))
+}
+
+fn build_update_info(current: &Version, release: GithubRelease) -> Result<UpdateInfo> {
+    let latest = parse_tag(&release.tag_name)?;
+
+    let download_url = release
+        .assets
+        .iter()
+        .find(|asset| is_platform_asset(&asset.name))
+        .map(|asset| asset.browser_download_url.clone())
+        .unwrap_or_else(|| release.html_url.clone());
+
+    Ok(UpdateInfo {
+        current: current.to_string(),
+        latest: latest.to_string(),
+        update_available: latest > *current,
+        release_notes: release.body.unwrap_or_default(),
+        download_url,
+        release_url: release.html_url,
+    })
+}
+
+/// Check whether a release asset is the installer for the running platform
+fn is_platform_asset(name: &str) -> bool {
+    let name = name.to_lowercase();
+    let extensions: &[&str] = if cfg!(target_os = "windows") {
+        &[".msi", "-setup.exe"]
+    } else if cfg!(target_os = "macos") {
+        &[".dmg"]
+    } else {
+        &[".appimage", ".deb"]
+    };
+
+    let arch_matches = if cfg!(target_arch = "aarch64") {
+        name.contains("aarch64") || name.contains("arm64") || name.contains("universal")
+    } else {
+        !(name.contains("aarch64") || name.contains("arm64"))
+    };
+
+    arch_matches && extensions.iter().any(|ext| name.ends_with(ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_release(tag: &str) -> GithubRelease {
+        GithubRelease {
+            tag_name: tag.to_string(),
+            html_url: "https://github.com/6639835/cursor-account-switcher/releases/tag/v2.2.0"
+                .to_string(),
+            body: Some("Bug fixes".to_string()),
+            assets: vec![GithubAsset {
+                name: "checksums.txt".to_string(),
+                browser_download_url: "https://example.com/checksums.txt".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_parse_tag() {
+        assert_eq!(parse_tag("v2.1.0").unwrap(), Version::new(2, 1, 0));
+        assert_eq!(parse_tag("2.10.3").unwrap(), Version::new(2, 10, 3));
+        assert!(parse_tag("latest").is_err());
+    }
+
+    #[test]
+    fn test_build_update_info_newer_release() {
+        let current = Version::new(2, 1, 0);
+        let info = build_update_info(&current, create_test_release("v2.2.0")).unwrap();
+
+        assert!(info.update_available);
+        assert_eq!(info.current, "2.1.0");
+        assert_eq!(info.latest, "2.2.0");
+        assert_eq!(info.release_notes, "Bug fixes");
+        // No installer for this platform, so fall back to the release page
+        assert_eq!(info.download_url, info.release_url);
+    }
+
+    #[test]
+    fn test_build_update_info_same_or_older_release() {
+        let current = Version::new(2, 10, 0);
+        assert!(
+            !build_update_info(&current, create_test_release("v2.9.0"))
+                .unwrap()
+                .update_available
+        );
+        assert!(
+            !build_update_info(&current, create_test_release("v2.10.0"))
+                .unwrap()
+                .update_available
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_detection() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert!(!is_rate_limited(StatusCode::FORBIDDEN, &headers));
+        assert!(is_rate_limited(StatusCode::TOO_MANY_REQUESTS, &headers));
+
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        assert!(is_rate_limited(StatusCode::FORBIDDEN, &headers));
+        assert!(!is_rate_limited(StatusCode::OK, &headers));
+    }
+}
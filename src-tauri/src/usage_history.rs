@@ -0,0 +1,136 @@
+use crate::types::Account;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// File next to the account CSV with one usage snapshot per line
+pub const USAGE_HISTORY_FILE: &str = "usage_history.jsonl";
+/// Snapshots older than this are dropped
+const RETENTION_DAYS: i64 = 90;
+
+/// An account's plan usage in dollars at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageSnapshot {
+    pub email: String,
+    pub taken_at: DateTime<Utc>,
+    pub used: f64,
+    pub remaining: f64,
+    pub total: f64,
+}
+
+impl UsageSnapshot {
+    /// None when the account has no usage data, e.g. its refresh failed
+    pub fn of(account: &Account, taken_at: DateTime<Utc>) -> Option<Self> {
+        Some(Self {
+            email: account.email.clone(),
+            taken_at,
+            used: account.usage_used?,
+            remaining: account.usage_remaining?,
+            total: account.usage_total?,
+        })
+    }
+}
+
+/// Usage snapshots taken by batch refreshes, the input of `forecast`
+pub struct UsageHistoryStore {
+    file_path: PathBuf,
+}
+
+impl UsageHistoryStore {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self { file_path }
+    }
+
+    /// Add `snapshots` taken at `now`, dropping the ones past retention
+    pub fn record(&self, snapshots: &[UsageSnapshot], now: DateTime<Utc>) -> Result<()> {
+        if snapshots.is_empty() {
+            return Ok(());
+        }
+        let cutoff = now - Duration::days(RETENTION_DAYS);
+        let mut content = String::new();
+        for snapshot in self
+            .read_all()?
+            .iter()
+            .filter(|s| s.taken_at >= cutoff)
+            .chain(snapshots)
+        {
+            content.push_str(&serde_json::to_string(snapshot)?);
+            content.push('\n');
+        }
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.file_path, content).context("Failed to write usage history")
+    }
+
+    /// Snapshots of `email` taken at or after `since`, oldest first
+    pub fn history(&self, email: &str, since: DateTime<Utc>) -> Result<Vec<UsageSnapshot>> {
+        let mut snapshots: Vec<UsageSnapshot> = self
+            .read_all()?
+            .into_iter()
+            .filter(|s| s.email == email && s.taken_at >= since)
+            .collect();
+        snapshots.sort_by_key(|s| s.taken_at);
+        Ok(snapshots)
+    }
+
+    // An unreadable line is skipped rather than losing the whole history
+    fn read_all(&self) -> Result<Vec<UsageSnapshot>> {
+        if !self.file_path.exists() {
+            return Ok(Vec::new());
+        }
+        let content =
+            fs::read_to_string(&self.file_path).context("Failed to read usage history")?;
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn snapshot(email: &str, taken_at: DateTime<Utc>, used: f64) -> UsageSnapshot {
+        UsageSnapshot {
+            email: email.to_string(),
+            taken_at,
+            used,
+            remaining: 20.0 - used,
+            total: 20.0,
+        }
+    }
+
+    #[test]
+    fn test_history_per_account_with_retention() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = UsageHistoryStore::new(temp_dir.path().join(USAGE_HISTORY_FILE));
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+        let later = start + Duration::days(RETENTION_DAYS + 1);
+
+        store
+            .record(&[snapshot("a@x.com", start, 1.0)], start)
+            .unwrap();
+        store
+            .record(
+                &[
+                    snapshot("a@x.com", later, 2.0),
+                    snapshot("b@x.com", later, 3.0),
+                ],
+                later,
+            )
+            .unwrap();
+
+        // The first snapshot aged out when the second batch came in
+        let history = store.history("a@x.com", start).unwrap();
+        assert_eq!(history, vec![snapshot("a@x.com", later, 2.0)]);
+        assert!(store
+            .history("b@x.com", later + Duration::hours(1))
+            .unwrap()
+            .is_empty());
+    }
+}
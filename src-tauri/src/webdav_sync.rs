@@ -0,0 +1,703 @@
+// Sync of the account CSV with a copy on a WebDAV server. Each sync merges
+// three versions of every row: the local one, the remote one and the one both
+// agreed on after the last sync (the base, kept next to the CSV). A side that
+// still matches the base takes the other side's change; when both changed,
+// refreshed stats go to the newer `record_time` and differing edits of
+// tokens, labels or privacy mode become conflicts for the user to settle.
+
+use crate::account_store::AccountStore;
+use crate::csv_manager::{self, CsvManager};
+use crate::http::{HttpClient, HttpClientFactory};
+use crate::settings::WebDavSettings;
+use crate::types::Account;
+use anyhow::{bail, Context, Result};
+use reqwest::header::{
+    HeaderMap, ETAG, IF_MATCH, IF_NONE_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Files next to the account CSV with the sync state and the base copy
+pub const SYNC_STATE_FILE: &str = "webdav_sync.json";
+pub const SYNC_BASE_FILE: &str = "webdav_base.csv";
+
+/// Upload attempts when someone else writes the remote file in between
+const MAX_ATTEMPTS: usize = 3;
+
+/// Identifies one version of the remote file. Servers without ETags still
+/// send Last-Modified, which is used the same way.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteVersion {
+    ETag(String),
+    Modified(String),
+}
+
+impl RemoteVersion {
+    fn of(headers: &HeaderMap) -> Option<Self> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        header(ETAG)
+            .map(RemoteVersion::ETag)
+            .or_else(|| header(LAST_MODIFIED).map(RemoteVersion::Modified))
+    }
+}
+
+pub struct RemoteFile {
+    pub content: String,
+    pub version: Option<RemoteVersion>,
+}
+
+enum Upload {
+    Stored(Option<RemoteVersion>),
+    /// The remote file is no longer the version the merge started from
+    Changed,
+}
+
+/// The remote file of `WebDavSettings`, with basic auth on every request
+pub struct WebDavClient {
+    http: HttpClient,
+    base_url: Url,
+    path: String,
+    file_url: Url,
+    username: String,
+    password: Option<String>,
+}
+
+impl WebDavClient {
+    pub fn new(
+        http: &HttpClientFactory,
+        settings: &WebDavSettings,
+        password: Option<String>,
+    ) -> Result<Self> {
+        let (base_url, path) = split_url(&settings.url, &settings.path)?;
+        Ok(Self {
+            http: http.client()?,
+            file_url: base_url.join(&path).context("Invalid WebDAV path")?,
+            base_url,
+            path,
+            username: settings.username.clone(),
+            password,
+        })
+    }
+
+    fn request(&self, method: Method, url: &Url) -> reqwest::blocking::RequestBuilder {
+        let request = self.http.request(method, url.as_str());
+        if self.username.is_empty() {
+            request
+        } else {
+            request.basic_auth(&self.username, self.password.as_deref())
+        }
+    }
+
+    /// Version of the remote file without downloading it, None when it doesn't exist
+    fn version(&self) -> Result<Option<RemoteVersion>> {
+        let response = self.http.send(self.request(Method::HEAD, &self.file_url))?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(RemoteVersion::of(response.headers())),
+            status => bail!("WebDAV server answered {}", status),
+        }
+    }
+
+    /// The remote file, None when it doesn't exist yet
+    pub fn download(&self) -> Result<Option<RemoteFile>> {
+        let response = self.http.send(self.request(Method::GET, &self.file_url))?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let version = RemoteVersion::of(response.headers());
+                let content = response
+                    .text()
+                    .context("Failed to read the remote accounts")?;
+                Ok(Some(RemoteFile { content, version }))
+            }
+            status => bail!("WebDAV server answered {}", status),
+        }
+    }
+
+    // Write the file only if it is still `expected`, or still absent when it
+    // didn't `exist`. Missing folders are created on the first upload.
+    fn upload(
+        &self,
+        content: &str,
+        expected: Option<&RemoteVersion>,
+        exists: bool,
+    ) -> Result<Upload> {
+        let put = || {
+            let request = self
+                .request(Method::PUT, &self.file_url)
+                .header("Content-Type", "text/csv; charset=utf-8")
+                .body(content.to_string());
+            let request = match expected {
+                Some(RemoteVersion::ETag(etag)) => request.header(IF_MATCH, etag),
+                Some(RemoteVersion::Modified(date)) => request.header(IF_UNMODIFIED_SINCE, date),
+                // A server that reports no version can't guard against overwrites
+                None if exists => request,
+                None => request.header(IF_NONE_MATCH, "*"),
+            };
+            self.http.send(request)
+        };
+
+        let mut response = put()?;
+        if response.status() == StatusCode::CONFLICT {
+            self.create_parents()?;
+            response = put()?;
+        }
+        match response.status() {
+            StatusCode::PRECONDITION_FAILED => Ok(Upload::Changed),
+            status if status.is_success() => {
+                // Not every server reports the new version in the PUT response
+                match RemoteVersion::of(response.headers()) {
+                    Some(version) => Ok(Upload::Stored(Some(version))),
+                    None => Ok(Upload::Stored(self.version()?)),
+                }
+            }
+            status => bail!("WebDAV server refused the upload: {}", status),
+        }
+    }
+
+    // Only folders below the server URL are created, never the URL itself
+    fn create_parents(&self) -> Result<()> {
+        let mkcol = Method::from_bytes(b"MKCOL")?;
+        let folders: Vec<&str> = self.path.split('/').collect();
+        for depth in 1..folders.len() {
+            let folder = self
+                .base_url
+                .join(&format!("{}/", folders[..depth].join("/")))?;
+            let status = self
+                .http
+                .send(self.request(mkcol.clone(), &folder))?
+                .status();
+            // Folders that already exist answer 405
+            if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+                bail!(
+                    "WebDAV server refused to create {}: {}",
+                    folder.path(),
+                    status
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+// The server URL as a folder, and `path` relative to it even with a leading slash
+fn split_url(base: &str, path: &str) -> Result<(Url, String)> {
+    let mut base = Url::parse(base.trim()).context("Invalid WebDAV URL")?;
+    if !matches!(base.scheme(), "http" | "https") {
+        bail!("WebDAV URL must be an http or https address");
+    }
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    let path = path.trim().trim_start_matches('/');
+    if path.is_empty() || path.ends_with('/') {
+        bail!("WebDAV path must name a file");
+    }
+    Ok((base, path.to_string()))
+}
+
+/// One email edited differently on both sides since the last sync
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub email: String,
+    pub local: Account,
+    pub remote: Account,
+}
+
+/// Which version of a conflicting row to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncSide {
+    Local,
+    Remote,
+}
+
+/// Outcome of merging the local and remote accounts
+#[derive(Debug)]
+pub struct Merge {
+    /// What the local CSV should hold; conflicting rows keep the local version
+    pub local: Vec<Account>,
+    /// What gets uploaded; conflicting rows keep the remote version
+    pub remote: Vec<Account>,
+    /// The agreed rows, base of the next sync. Conflicts keep their old base.
+    pub base: Vec<Account>,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// Merge both sides row by row, keyed by email. Without a base (the first
+/// sync) every row of either side is kept.
+pub fn merge(local: &[Account], remote: &[Account], base: Option<&[Account]>) -> Merge {
+    let by_email = |accounts: &[Account]| -> HashMap<String, Account> {
+        accounts
+            .iter()
+            .rev()
+            .map(|account| (account.email.clone(), account.clone()))
+            .collect()
+    };
+    let remote_rows = by_email(remote);
+    let base_rows = base.map(by_email);
+
+    // Local order first, then rows only the remote has
+    let mut emails = Vec::new();
+    let mut seen = HashSet::new();
+    for account in local.iter().chain(remote) {
+        if seen.insert(account.email.as_str()) {
+            emails.push(account.email.clone());
+        }
+    }
+    let local_rows = by_email(local);
+    let mut next_index = local.iter().map(|a| a.index).max().unwrap_or(0);
+
+    let mut merged = Merge {
+        local: Vec::new(),
+        remote: Vec::new(),
+        base: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    for email in emails {
+        let l = local_rows.get(&email);
+        let r = remote_rows.get(&email);
+        let b = base_rows.as_ref().and_then(|rows| rows.get(&email));
+
+        match merge_row(l, r, b) {
+            Ok(Some(mut account)) => {
+                account.index = match l {
+                    Some(l) => l.index,
+                    None => {
+                        next_index += 1;
+                        next_index
+                    }
+                };
+                merged.local.push(account.clone());
+                merged.remote.push(account.clone());
+                merged.base.push(account);
+            }
+            Ok(None) => {}
+            Err(BothEdited) => {
+                let (Some(local), Some(remote)) = (l.cloned(), r.cloned()) else {
+                    continue;
+                };
+                merged.local.push(local.clone());
+                merged.remote.push(remote.clone());
+                merged.base.extend(b.cloned());
+                merged.conflicts.push(SyncConflict {
+                    email,
+                    local,
+                    remote,
+                });
+            }
+        }
+    }
+    merged
+}
+
+/// Both sides edited a row differently; each keeps its own version
+struct BothEdited;
+
+// The merged row, None when it is dropped from both sides
+fn merge_row(
+    l: Option<&Account>,
+    r: Option<&Account>,
+    b: Option<&Account>,
+) -> Result<Option<Account>, BothEdited> {
+    match (l, r) {
+        (None, None) => Ok(None),
+        // Added on one side, or deleted on one side and edited on the other
+        (Some(only), None) | (None, Some(only)) if b.is_none() => Ok(Some(only.clone())),
+        (Some(only), None) | (None, Some(only)) => {
+            let deleted = b.is_some_and(|b| same_row(only, b));
+            Ok((!deleted).then(|| only.clone()))
+        }
+        (Some(l), Some(r)) if same_row(l, r) => Ok(Some(l.clone())),
+        (Some(l), Some(r)) => {
+            let (local_edited, remote_edited) = match b {
+                Some(b) => (!same_edits(l, b), !same_edits(r, b)),
+                // Nothing to tell who changed what
+                None => (true, true),
+            };
+            if local_edited && remote_edited && !same_edits(l, r) {
+                return Err(BothEdited);
+            }
+            let edits = if remote_edited { r } else { l };
+            Ok(Some(with_edits(newer(l, r), edits)))
+        }
+    }
+}
+
+/// Equal apart from the position in the file
+fn same_row(a: &Account, b: &Account) -> bool {
+    Account {
+        index: b.index,
+        ..a.clone()
+    } == *b
+}
+
+/// Equal in what the user sets: credentials, headers, label, privacy mode,
+/// pool, tags, the favorite star, whether it is archived, the password and
+/// the nickname
+fn same_edits(a: &Account, b: &Account) -> bool {
+    with_edits(b, a) == *b
+}
+
+/// `stats` with the user-set fields of `edits`
+fn with_edits(stats: &Account, edits: &Account) -> Account {
+    Account {
+        access_token: edits.access_token.clone(),
+        refresh_token: edits.refresh_token.clone(),
+        cookie: edits.cookie.clone(),
+        label_emoji: edits.label_emoji.clone(),
+        label_color: edits.label_color.clone(),
+        privacy_mode: edits.privacy_mode,
+        extra_headers: edits.extra_headers.clone(),
+        pool: edits.pool.clone(),
+        tags: edits.tags.clone(),
+        favorite: edits.favorite,
+        archived: edits.archived,
+        password: edits.password.clone(),
+        nickname: edits.nickname.clone(),
+        ..stats.clone()
+    }
+}
+
+// Record times are `record_time::FORMAT`, so they sort as text; ties go to local
+fn newer<'a>(local: &'a Account, remote: &'a Account) -> &'a Account {
+    if remote.record_time > local.record_time {
+        remote
+    } else {
+        local
+    }
+}
+
+/// What the last sync left behind, next to the account CSV
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncState {
+    /// Remote version the base was read from or written as
+    pub version: Option<RemoteVersion>,
+    pub last_synced: Option<String>,
+    /// Rows waiting for `resolve_sync_conflict`
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// The sync state file and the base copy of the accounts
+pub struct SyncStore {
+    state_path: PathBuf,
+    base_path: PathBuf,
+}
+
+impl SyncStore {
+    pub fn new(csv_path: &Path) -> Self {
+        Self {
+            state_path: csv_path.with_file_name(SYNC_STATE_FILE),
+            base_path: csv_path.with_file_name(SYNC_BASE_FILE),
+        }
+    }
+
+    pub fn load_state(&self) -> Result<SyncState> {
+        if !self.state_path.exists() {
+            return Ok(SyncState::default());
+        }
+        let content = fs::read_to_string(&self.state_path).context("Failed to read sync state")?;
+        serde_json::from_str(&content).context("Failed to parse sync state")
+    }
+
+    pub fn save_state(&self, state: &SyncState) -> Result<()> {
+        let content = serde_json::to_string_pretty(state)?;
+        fs::write(&self.state_path, content).context("Failed to write sync state")
+    }
+
+    /// None before the first sync
+    pub fn load_base(&self) -> Result<Option<Vec<Account>>> {
+        if !self.base_path.exists() {
+            return Ok(None);
+        }
+        CsvManager::new(self.base_path.clone())
+            .read_accounts()
+            .map(Some)
+    }
+
+    pub fn save_base(&self, accounts: &[Account]) -> Result<()> {
+        CsvManager::new(self.base_path.clone()).write_accounts(accounts)
+    }
+}
+
+/// Returned by `sync_now`; conflicts have their tokens masked
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncReport {
+    /// Local rows added, changed or removed by the remote copy
+    pub pulled: usize,
+    /// Remote rows added, changed or removed by this sync
+    pub pushed: usize,
+    pub conflicts: Vec<SyncConflict>,
+    pub synced_at: String,
+}
+
+/// Download, merge and upload. The local accounts are only rewritten once
+/// the upload went through, so a failed sync leaves both sides as they were.
+pub fn sync(client: &WebDavClient, accounts: &dyn AccountStore, now: &str) -> Result<SyncReport> {
+    let store = SyncStore::new(accounts.path());
+
+    for _ in 0..MAX_ATTEMPTS {
+        let state = store.load_state()?;
+        let mut base = store.load_base()?;
+
+        // An unchanged remote file is the base; no need to download it
+        let unchanged =
+            base.is_some() && state.version.is_some() && client.version()? == state.version;
+        let (remote, version, remote_exists) = if unchanged {
+            (
+                base.clone().unwrap_or_default(),
+                state.version.clone(),
+                true,
+            )
+        } else {
+            match client.download()? {
+                Some(file) => (
+                    csv_manager::accounts_from_csv(&file.content)?,
+                    file.version,
+                    true,
+                ),
+                // A deleted remote file is uploaded again rather than emptying this side
+                None => {
+                    base = None;
+                    (Vec::new(), None, false)
+                }
+            }
+        };
+
+        let local = accounts.read_accounts()?;
+        let merged = merge(&local, &remote, base.as_deref());
+        let pulled = changed_rows(&local, &merged.local);
+        let pushed = changed_rows(&remote, &merged.remote);
+
+        let version = if pushed > 0 || !remote_exists {
+            let content = csv_manager::accounts_to_csv(&merged.remote)?;
+            match client.upload(&content, version.as_ref(), remote_exists)? {
+                Upload::Stored(version) => version,
+                Upload::Changed => {
+                    tracing::info!("Remote accounts changed during sync, merging again");
+                    continue;
+                }
+            }
+        } else {
+            version
+        };
+
+        if merged.local != local {
+            accounts.write_accounts(&merged.local)?;
+        }
+        store.save_base(&merged.base)?;
+        store.save_state(&SyncState {
+            version,
+            last_synced: Some(now.to_string()),
+            conflicts: merged.conflicts.clone(),
+        })?;
+
+        return Ok(SyncReport {
+            pulled,
+            pushed,
+            conflicts: merged.conflicts,
+            synced_at: now.to_string(),
+        });
+    }
+    bail!("Remote accounts kept changing during sync, try again later")
+}
+
+// Emails whose row was added, removed or changed
+fn changed_rows(before: &[Account], after: &[Account]) -> usize {
+    let before: HashMap<&str, &Account> = before.iter().map(|a| (a.email.as_str(), a)).collect();
+    let after: HashMap<&str, &Account> = after.iter().map(|a| (a.email.as_str(), a)).collect();
+    let emails: HashSet<&str> = before.keys().chain(after.keys()).copied().collect();
+    emails
+        .into_iter()
+        .filter(|email| match (before.get(email), after.get(email)) {
+            (Some(a), Some(b)) => !same_row(a, b),
+            _ => true,
+        })
+        .count()
+}
+
+/// Settle a conflict by keeping one version. The kept row is uploaded by the
+/// next sync. Returns false when there is no conflict for `email`.
+pub fn resolve_conflict(accounts: &dyn AccountStore, email: &str, keep: SyncSide) -> Result<bool> {
+    let store = SyncStore::new(accounts.path());
+    let mut state = store.load_state()?;
+    let Some(position) = state.conflicts.iter().position(|c| c.email == email) else {
+        return Ok(false);
+    };
+    let conflict = state.conflicts.remove(position);
+
+    if keep == SyncSide::Remote {
+        let remote = Account {
+            index: conflict.local.index,
+            ..conflict.remote.clone()
+        };
+        if !accounts.update_account(email, remote.clone())? {
+            accounts.add_account(remote)?;
+        }
+    }
+
+    // With the remote version as the base, the next sync sees only the local
+    // side as edited and takes it, whichever one that is now
+    let mut base = store.load_base()?.unwrap_or_default();
+    base.retain(|account| account.email != email);
+    base.push(conflict.remote);
+    store.save_base(&base)?;
+    store.save_state(&state)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MembershipStatus;
+
+    fn account(email: &str, token: &str, used: f64, record_time: &str) -> Account {
+        Account {
+            index: 1,
+            email: email.to_string(),
+            access_token: token.into(),
+            refresh_token: token.into(),
+            status: MembershipStatus::Pro,
+            record_time: record_time.to_string(),
+            usage_used: Some(used),
+            ..Default::default()
+        }
+    }
+
+    fn tokens(accounts: &[Account]) -> Vec<(&str, &str)> {
+        accounts
+            .iter()
+            .map(|a| (a.email.as_str(), a.access_token.expose()))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_url() {
+        let url = |base, path| {
+            let (base, path) = split_url(base, path).unwrap();
+            base.join(&path).unwrap().to_string()
+        };
+        assert_eq!(
+            url("https://dav.example.com/files/me", "/sync/accounts.csv"),
+            "https://dav.example.com/files/me/sync/accounts.csv"
+        );
+        assert_eq!(
+            url("https://dav.example.com/files/me/", "accounts.csv"),
+            "https://dav.example.com/files/me/accounts.csv"
+        );
+        assert!(split_url("https://dav.example.com", "folder/").is_err());
+        assert!(split_url("file:///tmp", "accounts.csv").is_err());
+    }
+
+    #[test]
+    fn test_one_sided_changes_are_taken() {
+        let a = account("a@x.com", "a1", 10.0, "2024-03-01 10:00:00");
+        let b = account("b@x.com", "b1", 10.0, "2024-03-01 10:00:00");
+        let c = account("c@x.com", "c1", 10.0, "2024-03-01 10:00:00");
+        let base = vec![a.clone(), b.clone(), c.clone()];
+
+        let local = vec![
+            account("a@x.com", "a2", 10.0, "2024-03-02 10:00:00"),
+            b.clone(),
+            c.clone(),
+        ];
+        let remote = vec![
+            a.clone(),
+            account("b@x.com", "b2", 10.0, "2024-03-02 10:00:00"),
+            account("d@x.com", "d1", 0.0, "2024-03-02 10:00:00"),
+        ];
+        let merged = merge(&local, &remote, Some(&base));
+
+        // c was deleted remotely and untouched here, so it goes
+        let expected = vec![("a@x.com", "a2"), ("b@x.com", "b2"), ("d@x.com", "d1")];
+        assert_eq!(tokens(&merged.local), expected);
+        assert_eq!(tokens(&merged.remote), expected);
+        assert_eq!(tokens(&merged.base), expected);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.local[2].index, 2);
+    }
+
+    #[test]
+    fn test_stats_follow_the_newer_record_time() {
+        let base = vec![account("a@x.com", "a1", 10.0, "2024-03-01 10:00:00")];
+        // Refreshed on both sides, and the label set remotely
+        let local = vec![account("a@x.com", "a1", 40.0, "2024-03-03 10:00:00")];
+        let mut labelled = account("a@x.com", "a1", 20.0, "2024-03-02 10:00:00");
+        labelled.label_emoji = Some("🐱".to_string());
+
+        let merged = merge(&local, &[labelled], Some(&base));
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.local[0].usage_used, Some(40.0));
+        assert_eq!(merged.local[0].label_emoji.as_deref(), Some("🐱"));
+        assert_eq!(merged.remote, merged.local);
+    }
+
+    #[test]
+    fn test_edits_on_both_sides_conflict() {
+        let base = vec![account("a@x.com", "a1", 10.0, "2024-03-01 10:00:00")];
+        let local = vec![account("a@x.com", "local", 10.0, "2024-03-02 10:00:00")];
+        let remote = vec![account("a@x.com", "remote", 10.0, "2024-03-03 10:00:00")];
+
+        let merged = merge(&local, &remote, Some(&base));
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(tokens(&merged.local), vec![("a@x.com", "local")]);
+        assert_eq!(tokens(&merged.remote), vec![("a@x.com", "remote")]);
+        assert_eq!(tokens(&merged.base), vec![("a@x.com", "a1")]);
+
+        // Without a base differing tokens can't be told apart from edits either
+        let first = merge(&local, &remote, None);
+        assert_eq!(first.conflicts.len(), 1);
+        assert!(first.base.is_empty());
+    }
+
+    #[test]
+    fn test_edit_beats_delete() {
+        let base = vec![account("a@x.com", "a1", 10.0, "2024-03-01 10:00:00")];
+        let remote = vec![account("a@x.com", "a2", 10.0, "2024-03-02 10:00:00")];
+
+        let merged = merge(&[], &remote, Some(&base));
+        assert_eq!(tokens(&merged.local), vec![("a@x.com", "a2")]);
+    }
+
+    #[test]
+    fn test_resolve_conflict() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("accounts.csv");
+        let csv = CsvManager::new(csv_path.clone());
+        let base = vec![account("a@x.com", "a1", 10.0, "2024-03-01 10:00:00")];
+        let local = vec![account("a@x.com", "local", 10.0, "2024-03-02 10:00:00")];
+        let remote = vec![account("a@x.com", "remote", 10.0, "2024-03-03 10:00:00")];
+        csv.write_accounts(&local).unwrap();
+
+        let store = SyncStore::new(&csv_path);
+        let merged = merge(&local, &remote, Some(&base));
+        store.save_base(&merged.base).unwrap();
+        store
+            .save_state(&SyncState {
+                conflicts: merged.conflicts,
+                ..SyncState::default()
+            })
+            .unwrap();
+
+        assert!(!resolve_conflict(&csv, "b@x.com", SyncSide::Remote).unwrap());
+        assert!(resolve_conflict(&csv, "a@x.com", SyncSide::Local).unwrap());
+        assert!(store.load_state().unwrap().conflicts.is_empty());
+
+        // The next merge keeps the local row and uploads it
+        let base = store.load_base().unwrap().unwrap();
+        let merged = merge(&csv.read_accounts().unwrap(), &remote, Some(&base));
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(tokens(&merged.remote), vec![("a@x.com", "local")]);
+    }
+}
@@ -0,0 +1,410 @@
+// Accounts as an Excel workbook for teammates who'd rather not read CSV.
+// The few parts of the format needed are written by hand: a zip archive of
+// SpreadsheetML documents, with strings stored inline in their cells.
+
+use crate::types::Account;
+use anyhow::{Context, Result};
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+const ACCOUNT_HEADERS: [&str; 7] = [
+    "Email",
+    "Status",
+    "Days Remaining",
+    "Usage Used",
+    "Usage Remaining",
+    "Usage Total",
+    "Last Recorded",
+];
+const TOKEN_HEADERS: [&str; 4] = ["Email", "Access Token", "Refresh Token", "Cookie"];
+const PASSWORD_HEADERS: [&str; 2] = ["Email", "Password"];
+
+// Indices into `cellXfs` of STYLES
+const STYLE_HEADER: u8 = 1;
+const STYLE_DAYS: u8 = 2;
+const STYLE_USD: u8 = 3;
+
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+<numFmts count="2"><numFmt numFmtId="164" formatCode="0.0"/><numFmt numFmtId="165" formatCode="&quot;$&quot;#,##0.00"/></numFmts>
+<fonts count="2"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font></fonts>
+<fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills>
+<borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders>
+<cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs>
+<cellXfs count="4"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/><xf numFmtId="0" fontId="1" fillId="0" borderId="0" xfId="0" applyFont="1"/><xf numFmtId="164" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/><xf numFmtId="165" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/></cellXfs>
+</styleSheet>"#;
+
+enum Cell<'a> {
+    Text(&'a str),
+    Number(Option<f64>, u8),
+}
+
+/// Write `accounts` to an .xlsx workbook at `path`, creating its folder.
+/// `include_tokens` adds a sheet with the raw tokens, `include_passwords` one
+/// with the stored passwords. Returns the absolute path written.
+pub fn write_workbook(
+    path: &Path,
+    accounts: &[Account],
+    include_tokens: bool,
+    include_passwords: bool,
+) -> Result<PathBuf> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, workbook(accounts, include_tokens, include_passwords)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    fs::canonicalize(path).with_context(|| format!("Failed to resolve {}", path.display()))
+}
+
+/// The bytes of the .xlsx file
+pub fn workbook(
+    accounts: &[Account],
+    include_tokens: bool,
+    include_passwords: bool,
+) -> Result<Vec<u8>> {
+    let account_rows = accounts.iter().map(|a| {
+        vec![
+            Cell::Text(&a.email),
+            Cell::Text(a.status.as_str()),
+            Cell::Number(a.days_remaining, STYLE_DAYS),
+            Cell::Number(a.usage_used, STYLE_USD),
+            Cell::Number(a.usage_remaining, STYLE_USD),
+            Cell::Number(a.usage_total, STYLE_USD),
+            Cell::Text(&a.record_time),
+        ]
+    });
+    let mut sheets = vec![("Accounts", sheet(&ACCOUNT_HEADERS, account_rows))];
+    if include_tokens {
+        let token_rows = accounts.iter().map(|a| {
+            vec![
+                Cell::Text(&a.email),
+                Cell::Text(a.access_token.expose()),
+                Cell::Text(a.refresh_token.expose()),
+                Cell::Text(a.cookie.expose()),
+            ]
+        });
+        sheets.push(("Tokens", sheet(&TOKEN_HEADERS, token_rows)));
+    }
+    if include_passwords {
+        // Only the accounts that have one
+        let password_rows = accounts.iter().filter_map(|a| {
+            let password = a.password.as_ref()?;
+            Some(vec![Cell::Text(&a.email), Cell::Text(password.expose())])
+        });
+        sheets.push(("Passwords", sheet(&PASSWORD_HEADERS, password_rows)));
+    }
+
+    let mut content_types = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>"#,
+    );
+    let mut workbook = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>"#,
+    );
+    let mut relationships = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    );
+    for (number, (name, _)) in (1..).zip(&sheets) {
+        let _ = write!(
+            content_types,
+            r#"<Override PartName="/xl/worksheets/sheet{number}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#
+        );
+        let _ = write!(
+            workbook,
+            r#"<sheet name="{name}" sheetId="{number}" r:id="rId{number}"/>"#
+        );
+        let _ = write!(
+            relationships,
+            r#"<Relationship Id="rId{number}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{number}.xml"/>"#
+        );
+    }
+    content_types.push_str("</Types>");
+    workbook.push_str("</sheets></workbook>");
+    let _ = write!(
+        relationships,
+        r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#,
+        sheets.len() + 1
+    );
+
+    let mut archive = ZipWriter::default();
+    archive.add("[Content_Types].xml", &content_types)?;
+    archive.add(
+        "_rels/.rels",
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#,
+    )?;
+    archive.add("xl/workbook.xml", &workbook)?;
+    archive.add("xl/_rels/workbook.xml.rels", &relationships)?;
+    archive.add("xl/styles.xml", STYLES)?;
+    for (number, (_, sheet)) in (1..).zip(&sheets) {
+        archive.add(&format!("xl/worksheets/sheet{number}.xml"), sheet)?;
+    }
+    Ok(archive.finish())
+}
+
+fn sheet<'a>(headers: &[&'a str], rows: impl Iterator<Item = Vec<Cell<'a>>>) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#,
+    );
+    let header = headers.iter().map(|h| Cell::Text(h)).collect();
+    for (number, cells) in (1..).zip(std::iter::once(header).chain(rows)) {
+        let _ = write!(xml, r#"<row r="{number}">"#);
+        for (column, cell) in cells.into_iter().enumerate() {
+            let reference = format!("{}{}", column_name(column), number);
+            match cell {
+                // The header is the only text with a style
+                Cell::Text(text) if number == 1 => {
+                    let _ = write!(
+                        xml,
+                        r#"<c r="{reference}" s="{STYLE_HEADER}" t="inlineStr"><is><t>{}</t></is></c>"#,
+                        escape(text)
+                    );
+                }
+                Cell::Text(text) => {
+                    let _ = write!(
+                        xml,
+                        r#"<c r="{reference}" t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#,
+                        escape(text)
+                    );
+                }
+                Cell::Number(Some(value), style) if value.is_finite() => {
+                    let _ = write!(xml, r#"<c r="{reference}" s="{style}"><v>{value}</v></c>"#);
+                }
+                // Unknown values stay blank instead of reading as 0
+                Cell::Number(..) => {}
+            }
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData></worksheet>");
+    xml
+}
+
+/// "A" for the first column; there are never more than 26
+fn column_name(column: usize) -> char {
+    (b'A' + column as u8) as char
+}
+
+fn escape(text: &str) -> String {
+    // Control characters other than tab and newlines aren't allowed in XML
+    text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .fold(String::with_capacity(text.len()), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                _ => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+/// Just enough of the zip format for a workbook: deflated entries, no
+/// timestamps, no zip64
+#[derive(Default)]
+struct ZipWriter {
+    bytes: Vec<u8>,
+    central_directory: Vec<u8>,
+    entries: u16,
+}
+
+// 1980-01-01, the earliest date zip can store
+const DOS_DATE: u16 = 0x21;
+
+impl ZipWriter {
+    fn add(&mut self, name: &str, content: &str) -> Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content.as_bytes())?;
+        let compressed = encoder.finish()?;
+        let mut crc = Crc::new();
+        crc.update(content.as_bytes());
+
+        let offset = self.bytes.len() as u32;
+        // Fields shared by the local header and the central directory entry
+        let mut fields = Vec::new();
+        fields.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        fields.extend_from_slice(&0u16.to_le_bytes()); // flags
+        fields.extend_from_slice(&8u16.to_le_bytes()); // deflate
+        fields.extend_from_slice(&0u16.to_le_bytes()); // time
+        fields.extend_from_slice(&DOS_DATE.to_le_bytes());
+        fields.extend_from_slice(&crc.sum().to_le_bytes());
+        fields.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+
+        self.bytes.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        self.bytes.extend_from_slice(&fields);
+        self.bytes.extend_from_slice(name.as_bytes());
+        self.bytes.extend_from_slice(&compressed);
+
+        self.central_directory
+            .extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.central_directory
+            .extend_from_slice(&20u16.to_le_bytes()); // made by
+        self.central_directory.extend_from_slice(&fields);
+        self.central_directory.extend_from_slice(&[0; 6]); // comment length, disk, internal attributes
+        self.central_directory
+            .extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        self.central_directory
+            .extend_from_slice(&offset.to_le_bytes());
+        self.central_directory.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let directory_offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(&self.central_directory);
+        self.bytes.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        self.bytes.extend_from_slice(&[0; 4]); // disk numbers
+        self.bytes.extend_from_slice(&self.entries.to_le_bytes());
+        self.bytes.extend_from_slice(&self.entries.to_le_bytes());
+        self.bytes
+            .extend_from_slice(&(self.central_directory.len() as u32).to_le_bytes());
+        self.bytes
+            .extend_from_slice(&directory_offset.to_le_bytes());
+        self.bytes.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MembershipStatus;
+    use flate2::read::DeflateDecoder;
+    use std::io::Read;
+
+    fn account(email: &str, usage_used: Option<f64>) -> Account {
+        Account {
+            email: email.to_string(),
+            access_token: format!("access_{}", email).into(),
+            refresh_token: "refresh".into(),
+            days_remaining: Some(12.5),
+            status: MembershipStatus::Pro,
+            record_time: "2024-05-01 12:00:00".to_string(),
+            usage_used,
+            usage_total: Some(20.0),
+            ..Default::default()
+        }
+    }
+
+    fn u16_at(bytes: &[u8], at: usize) -> usize {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    /// Every entry of the archive by name, inflated, checked against its CRC
+    fn entries(bytes: &[u8]) -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        let mut at = 0;
+        while u32_at(bytes, at) == 0x0403_4b50 {
+            let compressed_size = u32_at(bytes, at + 18);
+            let name_length = u16_at(bytes, at + 26);
+            let data_start = at + 30 + name_length + u16_at(bytes, at + 28);
+            let name = String::from_utf8(bytes[at + 30..at + 30 + name_length].to_vec()).unwrap();
+
+            let mut content = String::new();
+            DeflateDecoder::new(&bytes[data_start..data_start + compressed_size])
+                .read_to_string(&mut content)
+                .unwrap();
+            let mut crc = Crc::new();
+            crc.update(content.as_bytes());
+            assert_eq!(crc.sum() as usize, u32_at(bytes, at + 14), "{}", name);
+            assert_eq!(content.len(), u32_at(bytes, at + 22));
+
+            entries.push((name, content));
+            at = data_start + compressed_size;
+        }
+        // The end of central directory record counts the same entries
+        let end = bytes.len() - 22;
+        assert_eq!(u32_at(bytes, end), 0x0605_4b50);
+        assert_eq!(u16_at(bytes, end + 10), entries.len());
+        assert_eq!(u32_at(bytes, end + 16), at);
+        entries
+    }
+
+    #[test]
+    fn test_workbook_structure_and_cells() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("exports/nested/accounts.xlsx");
+        let accounts = vec![account("a@x.com", Some(3.25)), account("b<&>@x.com", None)];
+
+        let written = write_workbook(&path, &accounts, false, false).unwrap();
+        assert!(written.is_absolute());
+        assert_eq!(written, fs::canonicalize(&path).unwrap());
+        let bytes = fs::read(&written).unwrap();
+        assert!(bytes.starts_with(b"PK\x03\x04"));
+        assert!(bytes.len() > 1_000);
+
+        let entries = entries(&bytes);
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "[Content_Types].xml",
+                "_rels/.rels",
+                "xl/workbook.xml",
+                "xl/_rels/workbook.xml.rels",
+                "xl/styles.xml",
+                "xl/worksheets/sheet1.xml",
+            ]
+        );
+        let sheet = &entries[5].1;
+        assert!(sheet.contains(r#"<c r="A1" s="1" t="inlineStr"><is><t>Email</t></is></c>"#));
+        assert!(sheet.contains(r#"<c r="C2" s="2"><v>12.5</v></c>"#));
+        assert!(sheet.contains(r#"<c r="D2" s="3"><v>3.25</v></c>"#));
+        assert!(sheet.contains("b&lt;&amp;&gt;@x.com"));
+        // No usage known, so no cell instead of a misleading 0
+        assert!(!sheet.contains(r#"r="D3""#));
+        assert!(sheet.contains(r#"<c r="F3" s="3"><v>20</v></c>"#));
+        assert!(!sheet.contains("access_"));
+    }
+
+    #[test]
+    fn test_tokens_sheet_is_optional() {
+        let accounts = vec![account("a@x.com", Some(1.0))];
+        let entries = entries(&workbook(&accounts, true, false).unwrap());
+
+        let workbook_xml = &entries[2].1;
+        assert!(workbook_xml.contains(r#"<sheet name="Tokens" sheetId="2" r:id="rId2"/>"#));
+        assert!(entries[3].1.contains("Target=\"worksheets/sheet2.xml\""));
+        assert!(entries[3].1.contains(r#"Id="rId3""#));
+        let (name, tokens) = entries.last().unwrap();
+        assert_eq!(name, "xl/worksheets/sheet2.xml");
+        assert!(tokens.contains(">access_a@x.com<"));
+        assert!(tokens.contains(">refresh<"));
+    }
+
+    #[test]
+    fn test_passwords_sheet_is_optional() {
+        let mut accounts = vec![account("a@x.com", None), account("b@x.com", None)];
+        accounts[1].password = Some("secret_password".into());
+        let without = entries(&workbook(&accounts, true, false).unwrap());
+        assert!(without
+            .iter()
+            .all(|(_, xml)| !xml.contains("secret_password")));
+
+        let entries = entries(&workbook(&accounts, false, true).unwrap());
+        assert!(entries[2]
+            .1
+            .contains(r#"<sheet name="Passwords" sheetId="2""#));
+        let (_, passwords) = entries.last().unwrap();
+        assert!(passwords.contains(">b@x.com<") && passwords.contains(">secret_password<"));
+        assert!(!passwords.contains(">a@x.com<"));
+    }
+}
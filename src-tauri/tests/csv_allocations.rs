@@ -0,0 +1,86 @@
+// Looking up one account by email must not allocate per stored row, so it
+// stays cheap for CSVs with tens of thousands of accounts
+
+use cursor_switcher::csv_manager::CsvManager;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::Write;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Counts the allocations of this thread only, so the test harness doesn't interfere
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+fn write_csv(rows: usize) -> (tempfile::TempDir, CsvManager) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("accounts.csv");
+    let manager = CsvManager::new(path.clone());
+    manager.ensure_csv_exists().unwrap();
+
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    for i in 1..=rows {
+        writeln!(
+            file,
+            "{i},user{i}@example.com,eyJhbGciOiJIUzI1NiJ9.access{i},eyJhbGciOiJIUzI1NiJ9.refresh{i},,7,pro,2024-01-01 00:00:00,imported,1.5,18.5,20,7.5,,,,,,,,,,,,"
+        )
+        .unwrap();
+    }
+    (temp_dir, manager)
+}
+
+#[test]
+fn test_find_by_email_allocations_do_not_grow_with_rows() {
+    let (_small_dir, small) = write_csv(1_000);
+    let (_large_dir, large) = write_csv(20_000);
+
+    let small_allocations = allocations_during(|| {
+        let account = small.find_account_by_email("user1000@example.com").unwrap();
+        assert!(account.is_some());
+    });
+    let large_allocations = allocations_during(|| {
+        let account = large
+            .find_account_by_email("user20000@example.com")
+            .unwrap();
+        assert!(account.is_some());
+    });
+
+    // 20x the rows may grow the reused buffers a little, but nothing per row
+    assert!(
+        large_allocations < small_allocations + 20,
+        "{} allocations for 1k rows, {} for 20k rows",
+        small_allocations,
+        large_allocations
+    );
+    assert!(large_allocations < 1_000);
+
+    // A full read allocates for every row, which is what the lookup avoids
+    let full_read = allocations_during(|| {
+        large.read_accounts().unwrap();
+    });
+    assert!(full_read > 20_000);
+}
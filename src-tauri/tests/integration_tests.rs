@@ -1,53 +1,505 @@
 // Integration tests for cursor-switcher
-// These tests verify that different modules work together correctly
+// These run the account flows of the library end to end against a temporary
+// Cursor data directory, with Cursor's API and process replaced by fakes
 
+use anyhow::{bail, Result};
+use cursor_switcher::api_client::CursorApi;
+use cursor_switcher::change_set::{ChangeSetStore, CHANGE_SETS_FILE};
+use cursor_switcher::csv_manager::CsvManager;
+use cursor_switcher::database::Database;
+use cursor_switcher::intent_journal::IntentJournal;
+use cursor_switcher::operations::{self, SwitchOptions, SwitchStage, SyncOutcome};
+use cursor_switcher::path_detector::PathDetector;
+use cursor_switcher::process_utils::CursorProcess;
+use cursor_switcher::state_backups;
+use cursor_switcher::token_auth::TokenCheck;
+use cursor_switcher::types::{Account, AccountInfo, AccountSource, MembershipStatus, UsageInfo};
+use reqwest::header::HeaderMap;
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use tempfile::TempDir;
 
-// Helper function to create a temporary directory
-fn setup_test_env() -> TempDir {
-    tempfile::tempdir().unwrap()
+/// Knows the tokens in `valid_tokens`; everything else is rejected like an expired token
+struct FakeApi {
+    valid_tokens: Vec<&'static str>,
+}
+
+impl CursorApi for FakeApi {
+    fn get_account_info(
+        &self,
+        email: &str,
+        access_token: &str,
+        _extra: &HeaderMap,
+    ) -> Result<AccountInfo> {
+        if !self.valid_tokens.contains(&access_token) {
+            bail!("HTTP 401");
+        }
+        Ok(AccountInfo {
+            email: email.to_string(),
+            membership_type: MembershipStatus::Pro,
+            days_remaining: 12.0,
+            is_student: false,
+            privacy_mode: None,
+        })
+    }
+
+    fn get_usage_info(&self, access_token: &str, _extra: &HeaderMap) -> Result<UsageInfo> {
+        if !self.valid_tokens.contains(&access_token) {
+            bail!("HTTP 401");
+        }
+        Ok(UsageInfo {
+            total_quota: 500.0,
+            used: 125.0,
+            remaining: 375.0,
+            usage_percentage: 25.0,
+        })
+    }
+
+    fn get_email(&self, access_token: &str) -> Result<String> {
+        bail!("no email for {}", access_token)
+    }
+
+    fn check_access_token(&self, access_token: &str) -> TokenCheck {
+        if self.valid_tokens.contains(&access_token) {
+            TokenCheck::Valid
+        } else {
+            TokenCheck::Rejected { status: 401 }
+        }
+    }
+}
+
+/// Counts what would have been done to the real Cursor process
+#[derive(Default)]
+struct FakeCursor {
+    /// Cleared by a kill that goes through, set again by a restart
+    running: AtomicBool,
+    fail_kill: bool,
+    fail_restart: bool,
+    kills: AtomicUsize,
+    restarts: AtomicUsize,
+}
+
+impl CursorProcess for FakeCursor {
+    fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    fn kill(&self) -> Result<()> {
+        if self.fail_kill {
+            bail!("Access is denied");
+        }
+        self.kills.fetch_add(1, Ordering::SeqCst);
+        self.running.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn restart(&self) -> Result<()> {
+        if self.fail_restart {
+            bail!("Cursor executable not found");
+        }
+        self.restarts.fetch_add(1, Ordering::SeqCst);
+        self.running.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+// A temporary Cursor data directory with an empty state database, and an empty CSV next to it
+fn setup_test_env() -> (TempDir, PathBuf, CsvManager) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let base_path = temp_dir.path().join("Cursor");
+    let db_path = PathDetector::get_db_path(&base_path);
+    std::fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ItemTable (key TEXT PRIMARY KEY, value TEXT)",
+        [],
+    )
+    .unwrap();
+
+    let store = CsvManager::new(temp_dir.path().join("accounts.csv"));
+    store.ensure_csv_exists().unwrap();
+    (temp_dir, base_path, store)
+}
+
+fn account(email: &str, access_token: &str) -> Account {
+    Account {
+        email: email.to_string(),
+        access_token: access_token.into(),
+        refresh_token: format!("{}_refresh", access_token).into(),
+        ..Default::default()
+    }
 }
 
 #[test]
 fn test_full_account_workflow() {
-    // This test verifies the complete workflow:
-    // 1. Create CSV manager
-    // 2. Add accounts
-    // 3. Read accounts
-    // 4. Update accounts
-    // 5. Delete accounts
+    let (_temp_dir, _base_path, store) = setup_test_env();
+    assert!(operations::list_accounts(&store).unwrap().is_empty());
 
-    // This is a placeholder that demonstrates integration testing
-    // In a real integration test, you would:
-    // - Set up a test environment with all necessary components
-    // - Execute a workflow that spans multiple modules
-    // - Verify the results
+    let csv_manager = &store;
+    csv_manager
+        .batch_add_accounts(vec![
+            account("a@x.com", "token_a"),
+            account("b@x.com", "token_b"),
+        ])
+        .unwrap();
 
-    let temp_dir = setup_test_env();
-    assert!(temp_dir.path().exists());
+    let mut updated = account("b@x.com", "token_b2");
+    updated.label_emoji = Some("🚀".to_string());
+    assert!(csv_manager.update_account("b@x.com", updated).unwrap());
+    assert!(csv_manager.delete_account("a@x.com").unwrap());
+
+    let accounts = operations::list_accounts(&store).unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].email, "b@x.com");
+    assert_eq!(accounts[0].access_token.expose(), "token_b2");
+    assert_eq!(accounts[0].label_emoji.as_deref(), Some("🚀"));
 }
 
 #[test]
-fn test_database_csv_integration() {
-    // This test would verify that the database and CSV manager
-    // can work together to manage account switching
+fn test_sync_then_switch_between_accounts() {
+    let (_temp_dir, base_path, store) = setup_test_env();
+    let db = Database::new(PathDetector::get_db_path(&base_path));
+    db.update_auth("a@x.com", "token_a", Some("token_a_refresh"))
+        .unwrap();
+    let api = FakeApi {
+        valid_tokens: vec!["token_a", "token_b"],
+    };
+    let cursor = FakeCursor::default();
+    let journal = IntentJournal::new(store.file_path().with_file_name("pending_operation.json"));
+
+    // The logged-in account is picked up with its API data
+    let outcome = operations::sync_current_account(&base_path, &store, true, &api).unwrap();
+    match outcome {
+        SyncOutcome::Added {
+            account,
+            info_fetched,
+        } => {
+            assert!(info_fetched);
+            assert_eq!(account.status, MembershipStatus::Pro);
+            assert_eq!(account.usage_remaining, Some(375.0));
+            assert_eq!(account.source, AccountSource::WebLogin);
+        }
+        other => panic!("unexpected sync outcome: {:?}", other),
+    }
 
-    let temp_dir = setup_test_env();
-    let test_path = temp_dir.path().to_path_buf();
+    store.add_account(account("b@x.com", "token_b")).unwrap();
+    let options = SwitchOptions {
+        reset_machine: false,
+        restart: true,
+        validate_token: true,
+        preserve_settings: false,
+        state_db_backup_retention: 2,
+    };
+    let switched = operations::switch_to_stored_account(
+        &base_path, &store, "b@x.com", &options, &journal, &api, &cursor,
+    )
+    .unwrap();
+    assert_eq!(switched.email, "b@x.com");
+    assert_eq!(
+        db.get_auth_info().unwrap(),
+        ("b@x.com".to_string(), "token_b".to_string())
+    );
+    assert_eq!(
+        db.get_refresh_token().unwrap().as_deref(),
+        Some("token_b_refresh")
+    );
+    assert_eq!(cursor.kills.load(Ordering::SeqCst), 1);
+    assert_eq!(cursor.restarts.load(Ordering::SeqCst), 1);
+    assert_eq!(journal.pending().unwrap(), None);
+    // The database as it was before the switch wrote to it
+    let backups = state_backups::list(&base_path).unwrap();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(backups[0].files[0], "state.vscdb");
+    let change_set = ChangeSetStore::new(store.file_path().with_file_name(CHANGE_SETS_FILE))
+        .latest()
+        .unwrap()
+        .unwrap();
+    assert_eq!(change_set.email.as_deref(), Some("b@x.com"));
+    let changed: Vec<&str> = change_set
+        .changes
+        .iter()
+        .filter(|change| change.is_change())
+        .map(|change| change.key.as_str())
+        .collect();
+    assert_eq!(
+        changed,
+        vec![
+            "cursorAuth/cachedEmail",
+            "cursorAuth/accessToken",
+            "cursorAuth/refreshToken"
+        ]
+    );
 
-    // Verify the test environment is set up correctly
-    assert!(test_path.exists());
+    // And back again, now that the first account is stored
+    operations::switch_to_stored_account(
+        &base_path, &store, "a@x.com", &options, &journal, &api, &cursor,
+    )
+    .unwrap();
+    assert_eq!(db.get_auth_info().unwrap().0, "a@x.com");
+    assert_eq!(cursor.kills.load(Ordering::SeqCst), 2);
 }
 
 #[test]
-fn test_machine_id_persistence() {
-    // This test would verify that machine IDs are generated
-    // and can be persisted correctly
+fn test_switch_to_rejected_token_leaves_cursor_alone() {
+    let (_temp_dir, base_path, store) = setup_test_env();
+    let db = Database::new(PathDetector::get_db_path(&base_path));
+    db.update_auth("a@x.com", "token_a", None).unwrap();
+    store
+        .add_account(account("expired@x.com", "token_expired"))
+        .unwrap();
+    let api = FakeApi {
+        valid_tokens: vec!["token_a"],
+    };
+    let cursor = FakeCursor::default();
+    let journal = IntentJournal::new(store.file_path().with_file_name("pending_operation.json"));
 
-    let temp_dir = setup_test_env();
-    assert!(temp_dir.path().exists());
+    let options = SwitchOptions {
+        validate_token: true,
+        ..SwitchOptions::default()
+    };
+    let result = operations::switch_to_stored_account(
+        &base_path,
+        &store,
+        "expired@x.com",
+        &options,
+        &journal,
+        &api,
+        &cursor,
+    );
+    assert!(result.is_err());
+    assert_eq!(db.get_auth_info().unwrap().0, "a@x.com");
+    assert_eq!(cursor.kills.load(Ordering::SeqCst), 0);
 }
 
-// Note: Full integration tests that involve Tauri commands
-// should be run in a separate test suite with a running Tauri app
-// These tests demonstrate the structure for integration testing
+#[test]
+fn test_refresh_accounts_from_api() {
+    let (_temp_dir, _base_path, store) = setup_test_env();
+    store
+        .batch_add_accounts(vec![
+            account("good@x.com", "token_good"),
+            account("dead@x.com", "token_dead"),
+        ])
+        .unwrap();
+    let api = FakeApi {
+        valid_tokens: vec!["token_good"],
+    };
+
+    let accounts = operations::batch_update_all_accounts(&store, &api).unwrap();
+    assert_eq!(accounts[0].status, MembershipStatus::Pro);
+    assert_eq!(accounts[0].days_remaining, Some(12.0));
+    assert_eq!(accounts[0].usage_percentage, Some(25.0));
+    assert_eq!(accounts[1].status, MembershipStatus::Error);
+
+    // Results are written back, and one account can be refreshed with another token
+    let stored = operations::list_accounts(&store).unwrap();
+    assert_eq!(stored[0].usage_total, Some(500.0));
+    let account =
+        operations::update_account_info(&store, "dead@x.com", Some("token_good"), &api).unwrap();
+    assert_eq!(account.status, MembershipStatus::Pro);
+    assert!(operations::update_account_info(&store, "nobody@x.com", None, &api).is_err());
+}
+
+// Make Cursor's database refuse writes of `value` under `key`
+fn reject_write(db_path: &Path, key: &str, value: &str) {
+    Connection::open(db_path)
+        .unwrap()
+        .execute_batch(&format!(
+            "CREATE TRIGGER reject_{n} BEFORE INSERT ON ItemTable \
+             WHEN NEW.key = '{key}' AND NEW.value = '{value}' \
+             BEGIN SELECT RAISE(ABORT, 'disk I/O error'); END;",
+            n = key.replace(['/', '.'], "_"),
+        ))
+        .unwrap();
+}
+
+// Logged in as a@x.com, with b@x.com stored to switch to
+fn setup_switch(target: Account) -> (TempDir, PathBuf, CsvManager, Database, IntentJournal) {
+    let (temp_dir, base_path, store) = setup_test_env();
+    let db = Database::new(PathDetector::get_db_path(&base_path));
+    db.update_auth("a@x.com", "token_a", Some("token_a_refresh"))
+        .unwrap();
+    store.add_account(target).unwrap();
+    let journal = IntentJournal::new(store.file_path().with_file_name("pending_operation.json"));
+    (temp_dir, base_path, store, db, journal)
+}
+
+const NO_CHECK: SwitchOptions = SwitchOptions {
+    reset_machine: false,
+    restart: false,
+    validate_token: false,
+    preserve_settings: false,
+    state_db_backup_retention: 0,
+};
+
+#[test]
+fn test_failed_switch_stages_are_rolled_back() {
+    let api = FakeApi {
+        valid_tokens: vec!["token_a", "token_b"],
+    };
+    let still_on_a = |db: &Database| {
+        assert_eq!(
+            db.get_auth_info().unwrap(),
+            ("a@x.com".to_string(), "token_a".to_string())
+        );
+        assert_eq!(
+            db.get_refresh_token().unwrap().as_deref(),
+            Some("token_a_refresh")
+        );
+    };
+
+    // Cursor won't close: nothing was changed, so nothing is undone
+    let (_temp_dir, base_path, store, db, journal) = setup_switch(account("b@x.com", "token_b"));
+    let cursor = FakeCursor {
+        running: AtomicBool::new(true),
+        fail_kill: true,
+        ..FakeCursor::default()
+    };
+    let failure = operations::switch_to_stored_account(
+        &base_path, &store, "b@x.com", &NO_CHECK, &journal, &api, &cursor,
+    )
+    .unwrap_err();
+    assert_eq!(failure.stage, SwitchStage::CloseCursor);
+    assert!(failure.rolled_back.is_empty());
+    assert_eq!(cursor.restarts.load(Ordering::SeqCst), 0);
+    still_on_a(&db);
+
+    // The token fails, so the email written with it isn't kept; the old login is put back with a running Cursor
+    let (_temp_dir, base_path, store, db, journal) = setup_switch(account("b@x.com", "token_b"));
+    reject_write(
+        &PathDetector::get_db_path(&base_path),
+        "cursorAuth/accessToken",
+        "token_b",
+    );
+    let cursor = FakeCursor {
+        running: AtomicBool::new(true),
+        ..FakeCursor::default()
+    };
+    let failure = operations::switch_to_stored_account(
+        &base_path, &store, "b@x.com", &NO_CHECK, &journal, &api, &cursor,
+    )
+    .unwrap_err();
+    assert_eq!(failure.stage, SwitchStage::WriteCredentials);
+    assert_eq!(
+        failure.rolled_back,
+        vec![SwitchStage::WriteCredentials, SwitchStage::CloseCursor]
+    );
+    assert!(failure.is_consistent());
+    assert_eq!(cursor.restarts.load(Ordering::SeqCst), 1);
+    assert_eq!(journal.pending().unwrap(), None);
+    still_on_a(&db);
+
+    // A storage.json the reset can't read; a missing one stops the switch before it starts
+    let (_temp_dir, base_path, store, db, journal) = setup_switch(account("b@x.com", "token_b"));
+    std::fs::write(PathDetector::get_storage_path(&base_path), "not json").unwrap();
+    let options = SwitchOptions {
+        reset_machine: true,
+        ..NO_CHECK
+    };
+    let cursor = FakeCursor::default();
+    let failure = operations::switch_to_stored_account(
+        &base_path, &store, "b@x.com", &options, &journal, &api, &cursor,
+    )
+    .unwrap_err();
+    assert_eq!(failure.stage, SwitchStage::ResetMachineId);
+    assert_eq!(
+        failure.rolled_back,
+        vec![SwitchStage::ResetMachineId, SwitchStage::WriteCredentials]
+    );
+    assert_eq!(
+        std::fs::read_to_string(PathDetector::get_storage_path(&base_path)).unwrap(),
+        "not json"
+    );
+    // Cursor wasn't running, so it isn't started either
+    assert_eq!(cursor.restarts.load(Ordering::SeqCst), 0);
+    still_on_a(&db);
+
+    // The privacy mode fails after the machine ID was reset, which is undone too
+    let (_temp_dir, base_path, store, db, journal) = setup_switch(Account {
+        privacy_mode: Some(true),
+        ..account("b@x.com", "token_b")
+    });
+    let storage_path = PathDetector::get_storage_path(&base_path);
+    let storage = r#"{"telemetry.machineId": "old-machine-id"}"#;
+    std::fs::write(&storage_path, storage).unwrap();
+    reject_write(
+        &PathDetector::get_db_path(&base_path),
+        "cursorai/donotchange/privacyMode",
+        "true",
+    );
+    let failure = operations::switch_to_stored_account(
+        &base_path,
+        &store,
+        "b@x.com",
+        &options,
+        &journal,
+        &api,
+        &FakeCursor::default(),
+    )
+    .unwrap_err();
+    assert_eq!(failure.stage, SwitchStage::ApplyPrivacyMode);
+    assert_eq!(std::fs::read_to_string(&storage_path).unwrap(), storage);
+    assert_eq!(db.get_privacy_mode().unwrap(), None);
+    still_on_a(&db);
+}
+
+#[test]
+fn test_failed_restart_keeps_the_switch() {
+    let (_temp_dir, base_path, store, db, journal) = setup_switch(account("b@x.com", "token_b"));
+    let api = FakeApi {
+        valid_tokens: vec!["token_b"],
+    };
+    let cursor = FakeCursor {
+        fail_restart: true,
+        ..FakeCursor::default()
+    };
+    let options = SwitchOptions {
+        restart: true,
+        ..NO_CHECK
+    };
+
+    let failure = operations::switch_to_stored_account(
+        &base_path, &store, "b@x.com", &options, &journal, &api, &cursor,
+    )
+    .unwrap_err();
+    assert_eq!(failure.stage, SwitchStage::RestartCursor);
+    assert!(failure.rolled_back.is_empty());
+    assert!(failure
+        .to_string()
+        .starts_with("Switched, but restarting Cursor failed"));
+    assert_eq!(db.get_auth_info().unwrap().0, "b@x.com");
+    assert_eq!(journal.pending().unwrap(), None);
+}
+
+#[test]
+fn test_failed_rollback_leaves_the_switch_to_resume() {
+    let (_temp_dir, base_path, store, db, journal) = setup_switch(Account {
+        privacy_mode: Some(true),
+        ..account("b@x.com", "token_b")
+    });
+    let db_path = PathDetector::get_db_path(&base_path);
+    // The login is written, then the privacy mode fails
+    reject_write(&db_path, "cursorai/donotchange/privacyMode", "true");
+    // Putting the old email back fails as well
+    reject_write(&db_path, "cursorAuth/cachedEmail", "a@x.com");
+    let api = FakeApi {
+        valid_tokens: vec!["token_b"],
+    };
+    let cursor = FakeCursor {
+        running: AtomicBool::new(true),
+        ..FakeCursor::default()
+    };
+
+    let failure = operations::switch_to_stored_account(
+        &base_path, &store, "b@x.com", &NO_CHECK, &journal, &api, &cursor,
+    )
+    .unwrap_err();
+    assert_eq!(failure.stage, SwitchStage::ApplyPrivacyMode);
+    assert!(!failure.is_consistent());
+    assert!(failure.next_step.contains("resume or discard"));
+    // Cursor stays closed on the half-written login, and the switch can be resumed
+    assert_eq!(cursor.restarts.load(Ordering::SeqCst), 0);
+    assert_eq!(db.get_auth_info().unwrap().0, "b@x.com");
+    assert!(journal.pending().unwrap().is_some());
+}
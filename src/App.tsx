@@ -7,7 +7,8 @@ import SettingsPage from './pages/SettingsPage';
 import LogPage from './pages/LogPage';
 import DetailedUsagePage from './pages/DetailedUsagePage';
 import { APP_VERSION } from './version';
-import { AccountInfo, UsageInfo, Account } from './types';
+import { AccountInfo, UsageInfo, Account, JournalEntry, MaybeStale } from './types';
+import { errorMessage } from './errors';
 
 type TabType = 'home' | 'accounts' | 'detailed-usage' | 'logs' | 'settings';
 
@@ -58,10 +59,12 @@ function App() {
     setError('');
 
     try {
+      // The backend prefetches at startup; only skip its cache once data has been shown
+      const refresh = forceRefresh && lastFetchTime.current > 0;
       // Fetch both API calls in parallel for better performance
       const [info, usage] = await Promise.all([
-        invoke<AccountInfo>('get_current_account_info'),
-        invoke<UsageInfo>('get_usage_info'),
+        invoke<MaybeStale<AccountInfo>>('get_current_account_info', { refresh }),
+        invoke<MaybeStale<UsageInfo>>('get_usage_info', { refresh }),
       ]);
 
       setAccountInfo(info);
@@ -77,7 +80,7 @@ function App() {
         console.warn('Failed to sync current account:', syncErr);
       }
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
       // On error, clear the last fetch time so next attempt won't be blocked by cache
       lastFetchTime.current = 0;
     } finally {
@@ -85,6 +88,27 @@ function App() {
     }
   }, []); // Remove accountInfo from dependencies to prevent recreation
 
+  // Offer to finish a switch or reset that a crash cut short
+  const checkInterruptedOperation = async () => {
+    try {
+      const entry = await invoke<JournalEntry | null>('get_interrupted_operation');
+      if (!entry) return;
+
+      const what =
+        entry.operation === 'switch'
+          ? `switching to ${entry.email}`
+          : 'resetting the machine ID';
+      const resume = confirm(
+        `The app closed while ${what} (started ${new Date(entry.started_at).toLocaleString()}).\n\n` +
+          'OK runs it again, Cancel leaves Cursor as it is.'
+      );
+      await invoke('resume_or_discard_interrupted', { action: resume ? 'resume' : 'discard' });
+      if (resume) loadAccountInfo(true);
+    } catch (err) {
+      alert(`Failed to handle the interrupted operation: ${errorMessage(err)}`);
+    }
+  };
+
   const accountsDataRef = useRef({ length: 0, lastRefreshTime: null as Date | null });
 
   // Update ref when data changes
@@ -174,6 +198,7 @@ function App() {
       detectPath();
       // Load account info on startup
       loadAccountInfo(true);
+      checkInterruptedOperation();
     }
   }, [loadAccountInfo]); // Include loadAccountInfo dependency
 
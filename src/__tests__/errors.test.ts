@@ -0,0 +1,17 @@
+import { describe, it, expect } from 'vitest';
+import { errorMessage, isAppError } from '../errors';
+
+describe('errorMessage', () => {
+  it('should use the message of a command error', () => {
+    const err = { code: 'busy', message: 'Busy: refresh in progress' };
+
+    expect(isAppError(err)).toBe(true);
+    expect(errorMessage(err)).toBe('Busy: refresh in progress');
+  });
+
+  it('should keep plain strings and JS errors readable', () => {
+    expect(errorMessage('Cursor path not set')).toBe('Cursor path not set');
+    expect(errorMessage(new Error('Reset failed'))).toBe('Reset failed');
+    expect(isAppError('Cursor path not set')).toBe(false);
+  });
+});
@@ -0,0 +1,21 @@
+import type { AppError } from './types';
+
+export function isAppError(err: unknown): err is AppError {
+  return (
+    typeof err === 'object' &&
+    err !== null &&
+    typeof (err as AppError).code === 'string' &&
+    typeof (err as AppError).message === 'string'
+  );
+}
+
+// Commands reject with an AppError; anything else (plain strings, JS errors) is shown as-is
+export function errorMessage(err: unknown): string {
+  if (isAppError(err)) {
+    return err.message;
+  }
+  if (err instanceof Error) {
+    return err.message;
+  }
+  return String(err);
+}
@@ -1,10 +1,18 @@
 import React from 'react';
 import ReactDOM from 'react-dom/client';
 import App from './App';
+import AccountDetailsWindow from './pages/AccountDetailsWindow';
 import './styles.css';
 
+// Secondary windows load the same bundle and pick their view from the query string
+const params = new URLSearchParams(window.location.search);
+
 ReactDOM.createRoot(document.getElementById('root') as HTMLElement).render(
   <React.StrictMode>
-    <App />
+    {params.get('view') === 'account-details' ? (
+      <AccountDetailsWindow initialEmail={params.get('email')} />
+    ) : (
+      <App />
+    )}
   </React.StrictMode>,
 );
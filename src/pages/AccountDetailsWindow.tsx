@@ -0,0 +1,123 @@
+import { useState, useEffect, useCallback } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
+import { AccountOverview } from '../types';
+import { RefreshCw, User, Calendar, TrendingUp } from 'lucide-react';
+import { errorMessage } from '../errors';
+
+interface AccountDetailsWindowProps {
+  initialEmail: string | null;
+}
+
+function AccountDetailsWindow({ initialEmail }: AccountDetailsWindowProps) {
+  const [email, setEmail] = useState<string | null>(initialEmail);
+  const [overview, setOverview] = useState<AccountOverview | null>(null);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState('');
+
+  const loadOverview = useCallback(async () => {
+    setLoading(true);
+    setError('');
+    try {
+      setOverview(await invoke<AccountOverview>('get_account_overview', { email }));
+    } catch (err) {
+      setError(errorMessage(err));
+    } finally {
+      setLoading(false);
+    }
+  }, [email]);
+
+  useEffect(() => {
+    loadOverview();
+  }, [loadOverview]);
+
+  // The tray reuses this window for other accounts instead of opening a new one
+  useEffect(() => {
+    const unlisten = listen<string | null>('account-details-target', (event) => {
+      setEmail(event.payload);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const usage = overview?.usage;
+  const cycle = overview?.billing_cycle;
+
+  return (
+    <div className="p-4 text-sm">
+      <div className="mb-4 flex items-center justify-between">
+        <h2 className="text-lg font-bold text-gray-800">Account Details</h2>
+        <button
+          onClick={loadOverview}
+          disabled={loading}
+          className="p-2 text-gray-600 hover:text-blue-600 disabled:opacity-50"
+          title="Refresh"
+        >
+          <RefreshCw size={16} className={loading ? 'animate-spin' : ''} />
+        </button>
+      </div>
+
+      {error && (
+        <div className="mb-4 p-3 bg-red-50 border border-red-200 rounded-lg text-red-700">
+          {error}
+        </div>
+      )}
+
+      {overview && (
+        <div className="space-y-3">
+          <div className="flex items-center gap-3">
+            <User className="text-blue-600" size={18} />
+            <div>
+              <p className="text-gray-500">Email</p>
+              <p className="font-medium text-gray-800">
+                {overview.account.email}
+                {overview.is_current && <span className="ml-2 text-green-600">(current)</span>}
+              </p>
+            </div>
+          </div>
+
+          <div className="flex items-center gap-3">
+            <TrendingUp className="text-green-600" size={18} />
+            <div>
+              <p className="text-gray-500">Usage{overview.usage_cached && ' (cached)'}</p>
+              <p className="font-medium text-gray-800">
+                {usage
+                  ? `${usage.used} / ${usage.total_quota} (${usage.usage_percentage.toFixed(0)}%)`
+                  : '—'}
+              </p>
+            </div>
+          </div>
+
+          <div className="flex items-center gap-3">
+            <Calendar className="text-purple-600" size={18} />
+            <div>
+              <p className="text-gray-500">Days Remaining</p>
+              <p className="font-medium text-gray-800">{overview.account.days_remaining_display}</p>
+            </div>
+          </div>
+
+          {cycle && (
+            <div className="flex items-center gap-3">
+              <Calendar className="text-orange-600" size={18} />
+              <div>
+                <p className="text-gray-500">Billing Cycle</p>
+                <p className="font-medium text-gray-800">
+                  {cycle.start_date ?? '—'} – {cycle.end_date ?? '—'}
+                </p>
+              </div>
+            </div>
+          )}
+
+          {overview.errors.map((message) => (
+            <p key={message} className="text-xs text-gray-400">
+              {message}
+            </p>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+}
+
+export default AccountDetailsWindow;
@@ -1,7 +1,7 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
 import { ask } from '@tauri-apps/api/dialog';
-import { Account, AccountInfo, TokenInfo } from '../types';
+import { Account, AccountInfo, SwitchPlan, TextImport, TokenInfo } from '../types';
 import {
   RefreshCw,
   Plus,
@@ -13,6 +13,42 @@ import {
   Clock,
   Key,
 } from 'lucide-react';
+import { errorMessage } from '../errors';
+
+// Spell out what a switch will do so the confirmation is more than "are you sure"
+const describeSwitchPlan = (plan: SwitchPlan): string => {
+  const lines = [
+    `${plan.current_email ?? 'No account'} → ${plan.target_email}`,
+    plan.cursor_running ? 'Cursor is running and will be closed.' : 'Cursor is not running.',
+    plan.reset_machine ? 'Machine IDs will be reset.' : 'Machine IDs will not be reset.',
+    plan.restart ? 'Cursor will be restarted.' : 'Cursor will not be restarted.',
+    plan.validate_token
+      ? 'The token will be checked with Cursor first.'
+      : 'The token will not be checked first.',
+    `Writes ${plan.database_path}`,
+  ];
+  if (plan.storage_path) {
+    lines.push(`Writes ${plan.storage_path}`);
+  }
+
+  for (const problem of plan.problems) {
+    switch (problem.kind) {
+      case 'account_not_found':
+        lines.push('⚠ The account is not in the account list.');
+        break;
+      case 'missing_token':
+        lines.push('⚠ The account has no access token.');
+        break;
+      case 'inactive':
+        lines.push(`⚠ The account looks inactive (${problem.status}).`);
+        break;
+      case 'already_active':
+        lines.push('⚠ This account is already logged in.');
+        break;
+    }
+  }
+  return lines.join('\n');
+};
 
 interface AccountPageProps {
   accountInfo: AccountInfo | null;
@@ -82,11 +118,12 @@ function AccountPage({
     }
 
     try {
+      // Archives the account; its tokens stay until it is deleted for good
       await invoke('delete_account', { email });
-      alert(`Account ${email} deleted successfully!`);
+      alert(`Account ${email} archived. It can be restored from the archived accounts.`);
       onRefresh();
     } catch (err) {
-      alert('Failed to delete account: ' + err);
+      alert('Failed to delete account: ' + errorMessage(err));
     }
   };
 
@@ -97,9 +134,25 @@ function AccountPage({
       return;
     }
 
-    const confirmed = await ask(`Switch to account ${account.email}?`, {
+    let plan: SwitchPlan;
+    try {
+      plan = await invoke<SwitchPlan>('preview_switch', {
+        email: account.email,
+        resetMachine: true,
+      });
+    } catch (err) {
+      alert('Failed to prepare switch: ' + errorMessage(err));
+      return;
+    }
+
+    if (!plan.can_switch) {
+      alert(`Cannot switch to ${account.email}:\n\n${describeSwitchPlan(plan)}`);
+      return;
+    }
+
+    const confirmed = await ask(describeSwitchPlan(plan), {
       title: 'Confirm Switch',
-      type: 'info',
+      type: plan.problems.length > 0 ? 'warning' : 'info',
     });
 
     if (!confirmed) {
@@ -109,8 +162,6 @@ function AccountPage({
     try {
       await invoke('switch_account', {
         email: account.email,
-        accessToken: account.access_token,
-        refreshToken: account.refresh_token,
         resetMachine: true,
       });
       alert('Account switched successfully! Cursor has been closed. Please restart it.');
@@ -121,7 +172,7 @@ function AccountPage({
         onRefresh(); // Refresh accounts list
       }, 1000); // Small delay to ensure DB is updated
     } catch (err) {
-      alert('Failed to switch account: ' + err);
+      alert('Failed to switch account: ' + errorMessage(err));
     }
   };
 
@@ -135,23 +186,26 @@ function AccountPage({
       }
     } catch (err) {
       if (!silent) {
-        alert('Failed to update accounts: ' + err);
+        alert('Failed to update accounts: ' + errorMessage(err));
       }
     }
   };
 
   const handleImport = async () => {
     try {
-      const parsed = await invoke<Account[]>('import_accounts', { text: importText });
-      await invoke('batch_add_accounts', { accounts: parsed });
+      const parsed = await invoke<TextImport>('import_accounts', { text: importText });
+      await invoke('batch_add_accounts', { accounts: parsed.accounts });
       setShowImport(false);
       setImportText('');
-      alert(`Successfully imported ${parsed.length} account(s)!`);
+      const skipped = parsed.errors.map((e) => `Line ${e.line_number}: ${e.error}`);
+      alert(
+        [`Successfully imported ${parsed.accounts.length} account(s)!`, ...skipped].join('\n'),
+      );
 
       // Refresh the list to show new accounts without fetching API data
       onRefresh();
     } catch (err) {
-      alert('Failed to import accounts: ' + err);
+      alert('Failed to import accounts: ' + errorMessage(err));
     }
   };
 
@@ -187,7 +241,7 @@ function AccountPage({
 
       alert(`Account ${account.email} imported successfully!`);
     } catch (err) {
-      alert(`Token import failed: ${err}`);
+      alert(`Token import failed: ${errorMessage(err)}`);
     } finally {
       setTokenLoading(false);
       setTokenProgress('');
@@ -411,6 +465,14 @@ function AccountPage({
                   </td>
                   <td className="px-6 py-4 whitespace-nowrap text-sm font-medium text-gray-900">
                     <div className="flex items-center gap-2">
+                      {(account.label_emoji || account.label_color) && (
+                        <span
+                          className="inline-flex items-center justify-center min-w-6 h-6 px-1 rounded-full text-sm border border-gray-200"
+                          style={{ backgroundColor: account.label_color ?? undefined }}
+                        >
+                          {account.label_emoji}
+                        </span>
+                      )}
                       <span>{account.email}</span>
                       {accountInfo && accountInfo.email === account.email && (
                         <span className="inline-flex items-center gap-1 px-2 py-0.5 rounded-full text-xs font-medium text-green-700 bg-green-100 border border-green-200">
@@ -433,10 +495,10 @@ function AccountPage({
                     </span>
                   </td>
                   <td className="px-6 py-4 whitespace-nowrap text-sm text-gray-500">
-                    {account.days_remaining === 'N/A' || account.days_remaining === '-1.0' ? (
+                    {account.days_remaining === null ? (
                       <span className="text-gray-400 italic">—</span>
                     ) : (
-                      account.days_remaining
+                      account.days_remaining_display
                     )}
                   </td>
                   <td className="px-6 py-4 whitespace-nowrap text-sm text-gray-500">
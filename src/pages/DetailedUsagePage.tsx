@@ -19,6 +19,7 @@ import {
   Activity,
   ChevronRight,
 } from 'lucide-react';
+import { errorMessage } from '../errors';
 
 interface DetailedUsagePageProps {
   onRefresh?: () => void;
@@ -52,7 +53,7 @@ function DetailedUsagePage({ onRefresh }: DetailedUsagePageProps) {
       setUserInfo(infoData);
       setInvoices(invoicesData);
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
     } finally {
       setLoading(false);
     }
@@ -3,6 +3,7 @@ import { invoke } from '@tauri-apps/api/tauri';
 import { ask } from '@tauri-apps/api/dialog';
 import { AccountInfo, UsageInfo } from '../types';
 import { RefreshCw, User, Calendar, TrendingUp, DollarSign, Clock } from 'lucide-react';
+import { errorMessage } from '../errors';
 
 interface HomePageProps {
   accountInfo: AccountInfo | null;
@@ -68,7 +69,7 @@ function HomePage({
       await invoke('reset_machine_id');
       alert('Machine ID reset successfully! Please restart Cursor to apply changes.');
     } catch (err) {
-      alert('Failed to reset machine ID: ' + err);
+      alert('Failed to reset machine ID: ' + errorMessage(err));
     }
   };
 
@@ -2,6 +2,7 @@ import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
 import { ask } from '@tauri-apps/api/dialog';
 import { FileText, Trash2, Download, RefreshCw } from 'lucide-react';
+import { errorMessage } from '../errors';
 
 interface LogEntry {
   timestamp: string;
@@ -21,7 +22,7 @@ function LogPage() {
       setLogs(logData.reverse()); // Show newest first
     } catch (err) {
       console.error('Failed to load logs:', err);
-      alert('Failed to load logs: ' + err);
+      alert('Failed to load logs: ' + errorMessage(err));
     } finally {
       setLoading(false);
     }
@@ -56,7 +57,7 @@ function LogPage() {
       setLogs([]);
       alert('Logs cleared successfully!');
     } catch (err) {
-      alert('Failed to clear logs: ' + err);
+      alert('Failed to clear logs: ' + errorMessage(err));
     }
   };
 
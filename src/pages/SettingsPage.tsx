@@ -3,6 +3,7 @@ import { invoke } from '@tauri-apps/api/tauri';
 import { ask } from '@tauri-apps/api/dialog';
 import { Folder, RotateCcw, Power, PlayCircle, Info } from 'lucide-react';
 import { APP_VERSION } from '../version';
+import { errorMessage } from '../errors';
 
 function SettingsPage() {
   const [cursorPath, setCursorPath] = useState('');
@@ -50,7 +51,7 @@ function SettingsPage() {
       await invoke('reset_machine_id');
       alert('Machine ID reset successfully! Please restart Cursor to apply changes.');
     } catch (err) {
-      alert('Failed to reset machine ID: ' + err);
+      alert('Failed to reset machine ID: ' + errorMessage(err));
     } finally {
       setLoading(false);
     }
@@ -70,7 +71,7 @@ function SettingsPage() {
       await invoke('kill_cursor_process');
       alert('Cursor process has been terminated.');
     } catch (err) {
-      alert('Failed to kill Cursor process: ' + err);
+      alert('Failed to kill Cursor process: ' + errorMessage(err));
     }
   };
 
@@ -88,7 +89,7 @@ function SettingsPage() {
       await invoke('restart_cursor_process', { cursorAppPath: null });
       alert('Cursor is starting...');
     } catch (err) {
-      alert('Failed to restart Cursor: ' + err);
+      alert('Failed to restart Cursor: ' + errorMessage(err));
     }
   };
 
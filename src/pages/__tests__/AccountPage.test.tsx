@@ -133,14 +133,29 @@ describe('AccountPage Component', () => {
 
   it('should handle account switching', async () => {
     const user = userEvent.setup();
-    const { confirm } = await import('@tauri-apps/api/dialog');
+    const { ask } = await import('@tauri-apps/api/dialog');
     const mockAccount = createMockAccount({
       email: 'switch@example.com',
       access_token: 'access_123',
       refresh_token: 'refresh_123',
     });
 
-    global.mockInvoke.mockResolvedValue(undefined); // switch account
+    global.mockInvoke.mockImplementation(async (command: string) =>
+      command === 'preview_switch'
+        ? {
+            current_email: 'current@example.com',
+            target_email: 'switch@example.com',
+            cursor_running: true,
+            reset_machine: true,
+            restart: false,
+            validate_token: true,
+            database_path: '/cursor/state.vscdb',
+            storage_path: '/cursor/storage.json',
+            problems: [],
+            can_switch: true,
+          }
+        : undefined,
+    );
 
     const onRefreshHome = vi.fn();
     const onRefresh = vi.fn();
@@ -164,13 +179,16 @@ describe('AccountPage Component', () => {
     if (switchButton) {
       await user.click(switchButton);
 
-      expect(confirm).toHaveBeenCalled();
+      await waitFor(() => {
+        expect(ask).toHaveBeenCalledWith(
+          expect.stringContaining('current@example.com → switch@example.com'),
+          expect.any(Object),
+        );
+      });
 
       await waitFor(() => {
         expect(global.mockInvoke).toHaveBeenCalledWith('switch_account', {
           email: 'switch@example.com',
-          accessToken: 'access_123',
-          refreshToken: 'refresh_123',
           resetMachine: true,
         });
       });
@@ -196,7 +214,8 @@ describe('AccountPage Component', () => {
     const updatedAccounts = mockAccounts.map((acc) => ({
       ...acc,
       status: 'premium',
-      days_remaining: '45.0',
+      days_remaining: 45,
+      days_remaining_display: '45',
     }));
 
     const onAccountsUpdate = vi.fn();
@@ -244,7 +263,7 @@ describe('AccountPage Component', () => {
     const parsedAccounts = [createMockAccount({ email: 'test@example.com' })];
 
     global.mockInvoke
-      .mockResolvedValueOnce(parsedAccounts) // import_accounts
+      .mockResolvedValueOnce({ accounts: parsedAccounts, errors: [] }) // import_accounts
       .mockResolvedValueOnce(undefined); // batch_add_accounts
 
     renderAccountPage();
@@ -14,7 +14,8 @@ export function createMockAccount(overrides = {}) {
     access_token: 'test_access_token',
     refresh_token: 'test_refresh_token',
     cookie: 'test_cookie',
-    days_remaining: '30.0',
+    days_remaining: 30,
+    days_remaining_display: '30',
     status: 'premium',
     record_time: '2024-01-01 12:00:00',
     ...overrides,
@@ -50,6 +51,9 @@ export function createMockMachineIds(overrides = {}) {
     mac_machine_id: 'test-mac-machine-id',
     dev_device_id: 'test-dev-device-id',
     sqm_id: '{TEST-SQM-ID}',
+    generated_at: '2024-01-01T00:00:00Z',
+    applied_to: [],
+    source: 'generated',
     ...overrides,
   };
 }
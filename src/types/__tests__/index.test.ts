@@ -10,7 +10,8 @@ describe('Type Definitions', () => {
         access_token: 'token',
         refresh_token: 'refresh',
         cookie: 'cookie',
-        days_remaining: '30',
+        days_remaining: 30,
+        days_remaining_display: '30',
         status: 'premium',
         record_time: '2024-01-01',
       };
@@ -80,6 +81,9 @@ describe('Type Definitions', () => {
         mac_machine_id: 'mac-id',
         dev_device_id: 'dev-id',
         sqm_id: '{SQM-ID}',
+        generated_at: null,
+        applied_to: [],
+        source: 'generated',
       };
 
       expect(machineIds).toHaveProperty('machine_id');
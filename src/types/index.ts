@@ -4,14 +4,106 @@ export interface Account {
   access_token: string;
   refresh_token: string;
   cookie: string;
-  days_remaining: string;
-  status: string;
+  /** Null for paid plans without a trial and unknown values */
+  days_remaining: number | null;
+  /** Preformatted days_remaining ("12.5" or "N/A"), kept while the UI moves to the number */
+  days_remaining_display: string;
+  status: MembershipStatus;
   record_time: string;
-  source: string; // "imported" or "web_login"
+  /** When the account was last switched to or synced; empty if never */
+  last_used: string;
+  source: AccountSource;
   usage_used?: number;
   usage_remaining?: number;
   usage_total?: number;
   usage_percentage?: number;
+  /** Set with set_account_label */
+  label_emoji?: string;
+  /** Hex color such as "#ff8800" */
+  label_color?: string;
+  /** Privacy mode applied when switching to this account; unset leaves Cursor's value */
+  privacy_mode?: boolean;
+  /** Sent with every Cursor API request for this account; values arrive masked */
+  extra_headers?: Record<string, string>;
+  /** Set with set_account_pool; unset is the "default" pool */
+  pool?: string;
+  /** Why the account needs attention beyond its status, e.g. 'revert_on_launch' */
+  status_reason?: string;
+  /** Set with set_account_tags; trimmed and without repeats */
+  tags?: string[];
+  /** Flipped with toggle_favorite; favorites come first in the tray */
+  favorite?: boolean;
+  /** Set by archive_account; only listed, counted and reported with include_archived */
+  archived?: boolean;
+  /** Only sent to add_account/update_account; read it with get_account_password */
+  password?: string;
+  /** Set with set_account_nickname; shown as "nickname (email)" in the tray */
+  nickname?: string;
+  /** Set by get_all_accounts: last refreshed `stale_after_hours` ago or earlier, or never */
+  stale?: boolean;
+  /** Set by get_all_accounts; null when the record time can't be read */
+  data_age_hours?: number | null;
+}
+
+/** Returned by get_fleet_stats; money is in US dollars */
+export interface FleetStats {
+  accounts: number;
+  by_status: Record<string, number>;
+  used_usd: number;
+  remaining_usd: number;
+  without_usage: number;
+}
+
+export type ReportFormat = 'markdown' | 'html';
+
+export type ReportColumn =
+  | 'email'
+  | 'status'
+  | 'days_remaining'
+  | 'usage_used'
+  | 'usage_remaining'
+  | 'usage_percentage'
+  | 'last_refresh'
+  | 'pool';
+
+/** `sort` of get_all_accounts; stored order when omitted */
+export type AccountSort = 'favorites';
+
+/** Passed to move_account */
+export type MoveDirection = 'up' | 'down';
+
+/** Returned by list_pools */
+export interface PoolSummary {
+  name: string;
+  accounts: number;
+}
+
+/** Known values; anything else the API reports is passed through unchanged */
+export type MembershipStatus =
+  | 'free'
+  | 'free_trial'
+  | 'pro'
+  | 'premium'
+  | 'ultra'
+  | 'expired'
+  | 'banned'
+  | 'error'
+  | 'unknown'
+  | (string & {});
+
+export type AccountSource =
+  | 'imported'
+  | 'web_login'
+  | 'token_import'
+  | 'cookie_import'
+  | (string & {});
+
+/** Returned by get_account_secrets; get_all_accounts only carries masked tokens */
+export interface AccountSecrets {
+  email: string;
+  access_token: string;
+  refresh_token: string;
+  cookie: string;
 }
 
 export interface AccountInfo {
@@ -19,6 +111,23 @@ export interface AccountInfo {
   membership_type: string;
   days_remaining: number;
   is_student: boolean;
+  /** Cursor's privacy mode, null when never set */
+  privacy_mode?: boolean | null;
+}
+
+/** One step of run_self_test */
+export interface CheckResult {
+  check: string;
+  passed: boolean;
+  duration_ms: number;
+  hint: string | null;
+  detail: string | null;
+}
+
+/** Returned by set_privacy_mode */
+export interface PrivacyModeChange {
+  enabled: boolean;
+  restart_required: boolean;
 }
 
 export interface UsageInfo {
@@ -28,11 +137,45 @@ export interface UsageInfo {
   usage_percentage: number;
 }
 
+/** Returned by reset_machine_id with the files the new IDs were written to */
 export interface MachineIds {
   machine_id: string;
   mac_machine_id: string;
   dev_device_id: string;
   sqm_id: string;
+  generated_at: string | null;
+  applied_to: string[];
+  source: 'generated' | 'restored' | 'user_provided';
+  /** What was put back from the settings snapshot taken before the reset */
+  preserved_settings: SettingsRestore | null;
+  /** Keys the reset wrote; left out when there are none */
+  changes?: KeyChange[];
+}
+
+/** Returned by get_machine_ids; a key storage.json doesn't have is null */
+export interface CurrentMachineIds {
+  machine_id: string | null;
+  mac_machine_id: string | null;
+  dev_device_id: string | null;
+  sqm_id: string | null;
+  /** MachineGuid from the registry; always null outside Windows */
+  registry_guid: string | null;
+}
+
+/** Returned by restore_cursor_settings_snapshot and sent as `cursor-settings-restored` */
+export interface SettingsRestore {
+  snapshot_id: string;
+  /** File names and state keys written back */
+  restored: string[];
+  /** Wiped state keys left alone because Cursor was running */
+  skipped: string[];
+}
+
+/** Returned by list_cursor_settings_snapshots, newest first */
+export interface SnapshotSummary {
+  id: string;
+  taken_at: string;
+  items: string[];
 }
 
 export interface TokenInfo {
@@ -86,3 +229,621 @@ export interface InvoicesResponse {
   invoices: Invoice[];
   total?: number;
 }
+
+export type Locale = 'en' | 'zh-CN';
+
+export interface AppSettings {
+  start_minimized: boolean;
+  launch_at_login: boolean;
+  http_api_enabled: boolean;
+  http_api_port: number;
+  http_api_token: string | null;
+  clipboard_watcher_enabled: boolean;
+  auto_update_check: boolean;
+  confirm_tray_switch: boolean;
+  /** Used by tray switches and by the options switch_account callers leave out */
+  default_reset_machine: boolean;
+  default_auto_restart: boolean;
+  default_validate_before_switch: boolean;
+  token_health_check: boolean;
+  /** Run the self-test on first launch and emit `self-test-results` */
+  startup_self_test: boolean;
+  notify_tray_success: boolean;
+  auto_export_enabled: boolean;
+  auto_export_dir: string | null;
+  auto_export_format: 'csv' | 'json';
+  auto_export_retention: number;
+  auto_export_include_passwords: boolean;
+  /** Always null in responses; the hash never leaves the backend */
+  app_lock_hash: string | null;
+  app_lock_minutes: number;
+  /** Colored dots in the tray account list; text tags when false */
+  tray_status_emoji: boolean;
+  tray_usage_warning: number;
+  tray_usage_critical: number;
+  locale: Locale;
+  onboarding_steps: OnboardingStep[];
+  /** Proxy for all requests to Cursor and GitHub; rejected on save when invalid */
+  http_proxy: string | null;
+  api_policy: ApiPolicy;
+  allow_custom_cursor_executable: boolean;
+  /** Always null in responses; the private keys never leave the backend */
+  manifest_signing_key: string | null;
+  manifest_encryption_key: string | null;
+  webdav: WebDavSettings;
+  /** Tray refresh clicks within this many seconds of the last one are skipped; up to 3600 */
+  tray_refresh_cooldown_secs: number;
+  /** Only accounts with this tag are listed in the tray; null lists all */
+  tray_tag_filter: string | null;
+  /** Never exported or imported with the other settings */
+  switch_hooks: SwitchHooks;
+  /** What a switch to an account used here less than `cooldown_hours` ago does; 1 to 168 hours */
+  cooldown_mode: CooldownMode;
+  cooldown_hours: number;
+  /** Snapshot Cursor's settings before machine ID resets and restore what Cursor wipes */
+  preserve_cursor_settings: boolean;
+  /** Seconds to check that Cursor kept the account after a restarting switch; 0 is off, up to 3600 */
+  switch_verify_timeout_secs: number;
+  /** Refuse every command that isn't a read with `read_only`; changing it needs the app unlocked */
+  read_only_mode: boolean;
+  /** Whether refreshes still run in read-only mode */
+  read_only_allow_refresh: boolean;
+  /** Hours after a refresh until an account is stale; 1 to 720 */
+  stale_after_hours: number;
+  /** 'sqlite' only once migrate_store_to_sqlite created the database */
+  account_store: StoreBackend;
+  /** Backups of the account CSV kept in backups/accounts; 0 takes none */
+  account_backup_retention: number;
+  /** Backups of Cursor's state.vscdb a switch takes in backups/state; 0 takes none */
+  state_db_backup_retention: number;
+  /** Rows kept in switch_history.csv; 1 to 100000 */
+  switch_history_max_rows: number;
+  /** Folder the accounts are kept in, null for the app data dir; set with set_data_storage_path */
+  data_storage_dir: string | null;
+}
+
+/** What set_data_storage_path does with accounts already in the new folder */
+export type ExistingStore = 'overwrite' | 'merge';
+
+export type CooldownMode = 'off' | 'warn' | 'enforce';
+
+export type StoreBackend = 'csv' | 'sqlite';
+
+export interface SwitchHooks {
+  pre: SwitchHook | null;
+  /** Runs in the background after a successful switch */
+  post: SwitchHook | null;
+}
+
+export interface SwitchHook {
+  /** Absolute path; run directly, not through a shell */
+  path: string;
+  /** `{email}`, `{status}` and `{reset_machine}` are filled in */
+  args: string[];
+  /** 1 to 600 */
+  timeout_secs: number;
+  /** Pre-switch only: a failing hook cancels the switch */
+  blocking: boolean;
+}
+
+/** The password is stored separately with set_webdav_password */
+export interface WebDavSettings {
+  enabled: boolean;
+  url: string;
+  path: string;
+  username: string;
+  /** 0 syncs only on request, otherwise 5-1440 */
+  interval_minutes: number;
+}
+
+/** Bounds are checked on save: timeout 1-300 s, retries up to 10, backoff up to
+ * 60000 ms, concurrency 1-16, spacing up to 10000 ms */
+export interface ApiPolicy {
+  timeout_secs: number;
+  max_retries: number;
+  backoff_base_ms: number;
+  max_concurrency: number;
+  min_request_spacing_ms: number;
+}
+
+export interface CredentialConflict {
+  kind: 'shared_token' | 'shared_user_id' | 'identity_mismatch';
+  /** User ID from the token, or the masked token when it isn't a JWT */
+  subject: string;
+  emails: string[];
+}
+
+export type OnboardingStep = 'cursor_path' | 'import_accounts' | 'finished';
+
+export interface OnboardingStatus {
+  cursor_path: string | null;
+  cursor_path_valid: boolean;
+  account_count: number;
+  cursor_running: boolean;
+  current_login: string | null;
+  completed_steps: OnboardingStep[];
+  setup_required: boolean;
+  show_wizard: boolean;
+}
+
+/** Long-running operation that blocks other switches, refreshes, syncs and resets */
+export type RunningOperation =
+  | 'switching'
+  | 'refreshing'
+  | 'resetting'
+  | 'syncing'
+  | 'reauthenticating'
+  | 'importing'
+  | 'cloud_sync';
+
+export interface AppLockStatus {
+  enabled: boolean;
+  locked: boolean;
+  unlocked_for_secs: number | null;
+}
+
+export interface DetectedToken {
+  token_type: string;
+  user_id: string;
+}
+
+export interface AutostartStatus {
+  enabled: boolean;
+  changed: boolean;
+  location: string;
+}
+
+export interface UpdateInfo {
+  current: string;
+  latest: string;
+  update_available: boolean;
+  release_notes: string;
+  download_url: string;
+  release_url: string;
+}
+
+export type SyncOutcome =
+  | { result: 'added'; account: Account; info_fetched: boolean }
+  | { result: 'updated'; account: Account; info_fetched: boolean }
+  | { result: 'nothing_logged_in' };
+
+export interface ScoreBreakdown {
+  remaining_quota: number | null;
+  quota_points: number;
+  days_remaining: number | null;
+  days_points: number;
+  stale: boolean;
+  staleness_factor: number;
+  total: number;
+}
+
+export interface RankedAccount {
+  email: string;
+  score: ScoreBreakdown;
+}
+
+/** Returned by compare_accounts; money in USD, percentages 0-100 */
+export interface AccountComparison {
+  email: string;
+  /** False when the email isn't stored; the other fields are then empty */
+  found: boolean;
+  membership: string | null;
+  days_remaining: number | null;
+  used_usd: number | null;
+  remaining_usd: number | null;
+  limit_usd: number | null;
+  used_percent: number | null;
+  data_age_secs: number | null;
+  refreshed: boolean;
+  refresh_error: string | null;
+}
+
+/** Returned by start_import_job and list_import_jobs; also the `import-job-progress` payload */
+export interface ImportJobSummary {
+  id: string;
+  created_at: string;
+  updated_at: string;
+  total: number;
+  imported: number;
+  failed: number;
+  pending: number;
+}
+
+/** Leftover entry of a switch or reset that never finished */
+export type JournalEntry = { started_at: string } & (
+  | { operation: 'switch'; email: string; options: SwitchOptions }
+  | { operation: 'reset_machine_id' }
+);
+
+/** Returned by list_switch_reverts and sent with the `switch-reverted` event */
+export interface SwitchRevert {
+  target_email: string;
+  /** Logged in once Cursor settled; unset when nobody was */
+  found_email?: string | null;
+  switched_at: string;
+  detected_at: string;
+}
+
+/** Result of `recommend_account` */
+export interface Recommendation {
+  email: string;
+  score: ScoreBreakdown;
+  live: boolean;
+  alternatives: RankedAccount[];
+  /** Every candidate is in its cooldown, so the best of them was picked anyway */
+  all_cooling_down: boolean;
+}
+
+/** Payload of the `tray-operation-failed` event */
+export interface TrayOperationFailure {
+  operation: 'switch' | 'sync' | 'refresh' | 'reauth';
+  email: string | null;
+  code: AppErrorCode;
+  reason: string;
+  error: string;
+  /** Set when a switch failed after Cursor was changed */
+  switch_failure?: SwitchFailure;
+}
+
+export type SwitchStage =
+  | 'prepare'
+  | 'close_cursor'
+  | 'write_credentials'
+  | 'reset_machine_id'
+  | 'apply_privacy_mode'
+  | 'restart_cursor';
+
+/** Where a switch stopped and what was undone */
+export interface SwitchFailure {
+  stage: SwitchStage;
+  error: string;
+  /** Most recent first */
+  rolled_back: SwitchStage[];
+  /** Cursor may be left half switched when not empty */
+  rollback_errors: string[];
+  next_step: string;
+}
+
+/** Returned by get_account_overview for the account detail window */
+export interface AccountOverview {
+  account: Account;
+  is_current: boolean;
+  usage: UsageInfo | null;
+  usage_cached: boolean;
+  billing_cycle: BillingCycle | null;
+  errors: string[];
+}
+
+export interface ExportReport {
+  path: string;
+  accounts: number;
+  pruned: number;
+}
+
+/** Safe to paste into bug reports: contains no tokens or settings values */
+export interface AppInfo {
+  version: string;
+  git_commit: string;
+  build_timestamp: string | null;
+  os: string;
+  arch: string;
+  app_data_dir: string | null;
+  csv_path: string | null;
+  log_dir: string | null;
+  settings_path: string | null;
+  cursor_path: string | null;
+  cursor_version: string | null;
+  is_offline: boolean;
+}
+
+/** Payload of check_connectivity and the `connectivity-changed` event */
+export interface ConnectivityStatus {
+  offline: boolean;
+}
+
+/** get_current_account_info and get_usage_info; `stale` when served offline */
+export type MaybeStale<T> = T & {
+  stale: boolean;
+  cache_age_secs: number | null;
+};
+
+export type SwitchProblem =
+  | { kind: 'account_not_found' }
+  | { kind: 'missing_token' }
+  | { kind: 'inactive'; status: string }
+  | { kind: 'already_active' }
+  | { kind: 'reset_check_failed'; check: ResetCheckKind; detail: string };
+
+export type ResetCheckKind = 'disk_space' | 'writable' | 'latest_backup';
+
+/** Returned by check_reset_machine_id and in SwitchPlan; a reset aborts on any failed check */
+export interface ResetCheck {
+  kind: ResetCheckKind;
+  path: string;
+  passed: boolean;
+  detail: string;
+}
+
+/** Options a switch was carried out with */
+export interface SwitchOptions {
+  reset_machine: boolean;
+  restart: boolean;
+  validate_token: boolean;
+  preserve_settings: boolean;
+  /** Backups of state.vscdb kept; 0 switches without taking one */
+  state_db_backup_retention: number;
+}
+
+/** Returned by switch_account; warns e.g. when the token check was skipped offline */
+export interface SwitchReport extends SwitchOptions {
+  warnings: string[];
+  change_set: ChangeSet | null;
+}
+
+/** A key written to one of Cursor's files; values are SHA-256 hex, never the value itself */
+export interface KeyChange {
+  file: string;
+  key: string;
+  /** null when the key wasn't set before */
+  old_hash: string | null;
+  new_hash: string | null;
+}
+
+/** Returned by get_last_change_set and with switch and reset results */
+export interface ChangeSet {
+  operation: 'switch' | 'reset_machine_id';
+  email: string | null;
+  at: string;
+  changes: KeyChange[];
+}
+
+/** Returned by preview_switch; describes a switch without performing it */
+export interface SwitchPlan {
+  current_email: string | null;
+  target_email: string;
+  cursor_running: boolean;
+  reset_machine: boolean;
+  restart: boolean;
+  validate_token: boolean;
+  preserve_settings: boolean;
+  /** A copy of the database is taken before writing it unless this is 0 */
+  state_db_backup_retention: number;
+  database_path: string;
+  storage_path: string | null;
+  /** Empty unless reset_machine is set */
+  reset_checks: ResetCheck[];
+  problems: SwitchProblem[];
+  can_switch: boolean;
+}
+
+export interface SettingsImportReport {
+  settings: AppSettings;
+  /** Keys this version doesn't know; they were skipped */
+  unknown_keys: string[];
+  /** Secrets found in the file, which are never imported */
+  ignored_keys: string[];
+  backup_path: string | null;
+}
+
+/** Returned by generate_manifest_keys; share these with teammates */
+export interface ManifestPublicKeys {
+  verify_key: string;
+  encryption_key: string;
+}
+
+/** Returned by import_pool_manifest */
+export interface PoolImportReport {
+  /** Verify key the manifest was signed with */
+  signer: string;
+  exporter: string;
+  created_at: string;
+  added: string[];
+  /** Already stored, so left as they are */
+  skipped: string[];
+}
+
+/** An entry of the pasted JSON that was skipped; position counts from 0 */
+export interface JsonImportError {
+  position: number;
+  reason: string;
+}
+
+/** Returned by batch_add_accounts */
+export interface MergeReport {
+  added: string[];
+  /** Already stored, so only their tokens, cookie and record time changed */
+  merged: string[];
+}
+
+/** Returned by import_accounts_json */
+export interface JsonImport {
+  accounts: Account[];
+  errors: JsonImportError[];
+}
+
+/** A pasted line that was skipped; line_number counts from 1 */
+export interface ImportLineError {
+  line_number: number;
+  raw_line: string;
+  error: string;
+}
+
+/** Returned by import_accounts */
+export interface TextImport {
+  accounts: Account[];
+  errors: ImportLineError[];
+}
+
+export type ClipboardFormat = 'lines' | 'json' | 'jwt' | 'session_cookie';
+
+/** Returned by import_from_clipboard; tokens are masked */
+export interface ClipboardImport {
+  format: ClipboardFormat;
+  accounts: Account[];
+  /** Lines or JSON entries that couldn't be read */
+  skipped: number;
+  /** Only set when the accounts were stored, i.e. not for a preview */
+  stored?: MergeReport;
+}
+
+export interface AccountCodeImport {
+  email: string;
+  /** False when the account was already stored */
+  added: boolean;
+}
+
+/** A row edited differently here and on the WebDAV server; tokens are masked */
+export interface SyncConflict {
+  email: string;
+  local: Account;
+  remote: Account;
+}
+
+/** Argument of resolve_sync_conflict */
+export type SyncSide = 'local' | 'remote';
+
+export type RefreshTrigger = 'manual' | 'tray' | 'scheduled' | 'api';
+
+/** `interrupted`: the app closed or crashed before the run finished */
+export type RefreshStatus = 'running' | 'completed' | 'failed' | 'interrupted';
+
+/** Returned by get_last_refresh_info */
+export interface RefreshInfo {
+  trigger: RefreshTrigger;
+  status: RefreshStatus;
+  started_at: string;
+  finished_at: string | null;
+  success_count: number;
+  error_count: number;
+  failed_emails: string[];
+  /** Why a failed run stopped */
+  error: string | null;
+}
+
+/** Returned by sync_now */
+export interface SyncReport {
+  pulled: number;
+  pushed: number;
+  conflicts: SyncConflict[];
+  synced_at: string;
+}
+
+export type AppErrorCode =
+  | 'cursor_path_not_set'
+  | 'not_logged_in'
+  | 'locked'
+  | 'store_locked'
+  | 'busy'
+  | 'read_only'
+  | 'invalid_input'
+  | 'database_locked'
+  | 'account_not_found'
+  | 'unauthorized'
+  | 'network_error'
+  | 'permission_denied'
+  | 'cooling_down'
+  | 'internal';
+
+/** What a failed command rejects with */
+export interface AppError {
+  code: AppErrorCode;
+  message: string;
+  /** Full cause chain, when it says more than the message */
+  detail?: string;
+  /** Parameter that failed validation, for `invalid_input` */
+  field?: string;
+  /** Seconds until the account may be switched to, for `cooling_down` */
+  remaining_secs?: number;
+}
+
+/** Payload of the `startup-data-ready` event; a field is null when its fetch failed */
+export interface StartupData {
+  account_info: AccountInfo | null;
+  usage_info: UsageInfo | null;
+}
+
+export type TokenHealth =
+  | { state: 'healthy' }
+  | { state: 'expiring'; expires_at: string }
+  | { state: 'expired' };
+
+/** Returned by get_token_health and sent with `token-health-changed` */
+export interface TokenHealthReport {
+  email: string;
+  health: TokenHealth;
+  checked_at: string;
+}
+
+/** Whether Cursor's main.js still has the machine ID patch */
+export type PatchStatus = 'patched' | 'unpatched' | 'unrecognized' | 'missing' | 'not_applicable';
+
+/** Payload of the cursor-updated event */
+export interface CursorUpdate {
+  previous: string;
+  version: string;
+  patch_status: PatchStatus;
+}
+
+/** Returned by get_cursor_update_history, oldest first */
+export interface VersionObservation {
+  version: string;
+  first_seen: string;
+}
+
+export type InsufficientReason = 'too_few_snapshots' | 'no_recent_usage';
+
+/** Returned by forecast_account and, soonest first, by forecast_all */
+export type AccountForecast = { email: string } & (
+  | {
+      state: 'projected';
+      exhausts_at: string;
+      /** Confidence band; latest is null when usage may as well be flat */
+      earliest: string;
+      latest: string | null;
+      dollars_per_day: number;
+      remaining: number;
+      snapshots: number;
+    }
+  | { state: 'insufficient_data'; reason: InsufficientReason }
+);
+
+/** A copy of the account CSV, taken before it was rewritten */
+/** A row of the account CSV that reads skip, from get_store_diagnostics */
+export interface CorruptRow {
+  /** Counting from 1, the header included */
+  line: number;
+  reason: string;
+}
+
+export interface BackupInfo {
+  path: string;
+  timestamp: string;
+  rows: number;
+}
+
+/** A copy of state.vscdb taken before a switch, as returned by list_state_db_backups */
+export interface StateBackupInfo {
+  /** The folder to pass to restore_state_db_backup */
+  path: string;
+  timestamp: string;
+  /** state.vscdb, plus its -wal and -shm files when there were any */
+  files: string[];
+}
+
+export type SwitchSource = 'window' | 'tray' | 'api' | 'deep_link' | 'cli';
+
+/** A row of switch_history.csv, as returned by get_switch_history */
+export interface SwitchLogEntry {
+  /** Local time, 'YYYY-MM-DD HH:MM:SS' */
+  timestamp: string;
+  email: string;
+  reset_machine: boolean;
+  source: SwitchSource;
+}
+
+/** Returned by query_accounts: one page of the matching accounts */
+export interface AccountPage {
+  /** Matching accounts on every page */
+  total: number;
+  items: Account[];
+}